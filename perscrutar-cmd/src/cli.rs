@@ -0,0 +1,316 @@
+/*!
+
+Command line definition and argument parsing.
+
+All subcommands are described declaratively by `CommandSpec`s. The table is
+turned into a clap `Command`, which parses the arguments and renders `--help`,
+and which clap_complete turns into the shell completion scripts, so the three
+cannot drift apart.
+
+*/
+
+use std::collections::HashMap;
+use std::fmt;
+use clap::builder::PossibleValuesParser;
+use clap::error::ErrorKind;
+use clap::{Arg, ArgAction, ArgMatches, Command, ValueHint};
+
+#[derive(Debug, Clone)]
+pub struct ArgSpec {
+    pub long: &'static str,
+    pub short: Option<char>,
+    /** Name of the value the option takes; `None` for flags. */
+    pub value: Option<&'static str>,
+    pub help: &'static str,
+    /** Accepted values, if restricted. */
+    pub choices: &'static [&'static str],
+}
+
+impl ArgSpec {
+    pub const fn flag(long: &'static str, help: &'static str) -> ArgSpec {
+        ArgSpec { long, short: None, value: None, help, choices: &[] }
+    }
+
+    pub const fn option(long: &'static str, value: &'static str, help: &'static str) -> ArgSpec {
+        ArgSpec { long, short: None, value: Some(value), help, choices: &[] }
+    }
+
+    pub const fn short(mut self, short: char) -> ArgSpec {
+        self.short = Some(short);
+        self
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PositionalSpec {
+    pub name: &'static str,
+    pub help: &'static str,
+    pub required: bool,
+    /** Accepts any number of values; only valid for the last positional. */
+    pub multiple: bool,
+    pub choices: &'static [&'static str],
+}
+
+impl PositionalSpec {
+    pub const fn required(name: &'static str, help: &'static str) -> PositionalSpec {
+        PositionalSpec { name, help, required: true, multiple: false, choices: &[] }
+    }
+
+    pub const fn optional(name: &'static str, help: &'static str) -> PositionalSpec {
+        PositionalSpec { name, help, required: false, multiple: false, choices: &[] }
+    }
+
+    pub const fn multiple(mut self) -> PositionalSpec {
+        self.multiple = true;
+        self
+    }
+
+    pub const fn choices(mut self, choices: &'static [&'static str]) -> PositionalSpec {
+        self.choices = choices;
+        self
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CommandSpec {
+    pub name: &'static str,
+    pub about: &'static str,
+    pub args: Vec<ArgSpec>,
+    pub positionals: Vec<PositionalSpec>,
+}
+
+/**
+Options accepted by every subcommand.
+*/
+pub fn global_args() -> Vec<ArgSpec> {
+    vec![
+        ArgSpec::flag("json", "Print machine-readable JSON instead of text"),
+        ArgSpec::option("output", "FILE", "Write the result to FILE instead of standard output").short('o'),
+    ]
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CliError {
+    pub message: String,
+    pub code: i32,
+}
+
+impl CliError {
+    /** An error in the command line itself; exits with status 2. */
+    pub fn usage(message: &str) -> CliError {
+        CliError { message: String::from(message), code: 2 }
+    }
+
+    /** A failure while running a command; exits with status 1. */
+    pub fn failure(message: &str) -> CliError {
+        CliError { message: String::from(message), code: 1 }
+    }
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Matches {
+    pub command: String,
+    options: HashMap<String, Vec<String>>,
+    positionals: Vec<String>,
+}
+
+impl Matches {
+    pub fn flag(&self, long: &str) -> bool {
+        self.options.contains_key(long)
+    }
+
+    /**
+    Last value given for an option.
+    */
+    pub fn value(&self, long: &str) -> Option<&str> {
+        self.options.get(long).and_then(|v| v.last()).map(|s| s.as_str())
+    }
+
+    /**
+    Every value given for an option, in order.
+    */
+    pub fn values(&self, long: &str) -> Vec<&str> {
+        self.options.get(long).map(|v| v.iter().map(|s| s.as_str()).collect()).unwrap_or_default()
+    }
+
+    pub fn positional(&self, index: usize) -> Option<&str> {
+        self.positionals.get(index).map(|s| s.as_str())
+    }
+
+    pub fn positionals(&self) -> &[String] {
+        &self.positionals
+    }
+
+    pub fn json(&self) -> bool {
+        self.flag("json")
+    }
+}
+
+impl From<clap::Error> for CliError {
+    /**
+    Requests for help or the version come back as errors with status 0 whose
+    message is the text to print, as is the usage when no command is given.
+    Other errors keep the first line of clap's.
+    */
+    fn from(e: clap::Error) -> CliError {
+        let text = e.to_string();
+        match e.kind() {
+            ErrorKind::DisplayHelp | ErrorKind::DisplayVersion | ErrorKind::DisplayHelpOnMissingArgumentOrSubcommand =>
+                CliError { message: text, code: 0 },
+            _ => {
+                let line = text.lines().next().unwrap_or_default();
+                CliError { message: String::from(line.strip_prefix("error: ").unwrap_or(line)), code: e.exit_code() }
+            }
+        }
+    }
+}
+
+fn option(a: &ArgSpec) -> Arg {
+    let mut arg = Arg::new(a.long).long(a.long).help(a.help);
+    if let Some(c) = a.short {
+        arg = arg.short(c);
+    }
+    match a.value {
+        None => arg.action(ArgAction::SetTrue),
+        // values may start with `-`, as in `--interval -1`
+        Some(v) => {
+            arg = arg.value_name(v).action(ArgAction::Append).allow_hyphen_values(true);
+            if a.choices.is_empty() {
+                arg.value_hint(if v == "FILE" { ValueHint::FilePath } else { ValueHint::Other })
+            } else {
+                arg.value_parser(PossibleValuesParser::new(a.choices))
+            }
+        }
+    }
+}
+
+fn positional(p: &PositionalSpec) -> Arg {
+    let mut arg = Arg::new(p.name).value_name(p.name).help(p.help).required(p.required);
+    if p.multiple {
+        arg = arg.action(ArgAction::Append).num_args(if p.required { 1.. } else { 0.. });
+    }
+    if p.choices.is_empty() {
+        arg.value_hint(ValueHint::AnyPath)
+    } else {
+        arg.value_parser(PossibleValuesParser::new(p.choices))
+    }
+}
+
+/**
+The clap definition of `program` with `commands` as its subcommands.
+*/
+pub fn command(program: &'static str, commands: &[CommandSpec]) -> Command {
+    // after the options of the subcommand in its help
+    let globals: Vec<Arg> = global_args().iter().map(|a| option(a).global(true).display_order(100)).collect();
+    Command::new(program)
+        .version(env!("CARGO_PKG_VERSION"))
+        .subcommand_required(true)
+        .arg_required_else_help(true)
+        .args_override_self(true)
+        .args(globals)
+        .subcommands(commands.iter().map(|c| {
+            Command::new(c.name)
+                .about(c.about)
+                .args(c.args.iter().map(option))
+                .args(c.positionals.iter().map(positional))
+        }))
+}
+
+fn matches(spec: &CommandSpec, sub: &ArgMatches) -> Matches {
+    let mut m = Matches { command: String::from(spec.name), ..Matches::default() };
+    for a in spec.args.iter().chain(global_args().iter()) {
+        if a.value.is_none() {
+            if sub.get_flag(a.long) {
+                m.options.insert(String::from(a.long), vec![String::new()]);
+            }
+        } else if let Some(values) = sub.get_many::<String>(a.long) {
+            m.options.insert(String::from(a.long), values.cloned().collect());
+        }
+    }
+    for p in spec.positionals.iter() {
+        if let Some(values) = sub.get_many::<String>(p.name) {
+            m.positionals.extend(values.cloned());
+        }
+    }
+    m
+}
+
+/**
+Parse `args` (without the program name). The first argument selects the
+subcommand; a lone `-` is a positional value (standard input/output).
+*/
+pub fn parse(commands: &[CommandSpec], args: &[String]) -> Result<Matches, CliError> {
+    let program = crate::commands::PROGRAM;
+    let all = command(program, commands)
+        .try_get_matches_from(std::iter::once(program).chain(args.iter().map(|s| s.as_str())))?;
+    let (name, sub) = all.subcommand().ok_or_else(|| CliError::usage("no command given"))?;
+    let spec = commands.iter().find(|c| c.name == name).expect("clap only accepts known commands");
+    Ok(matches(spec, sub))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn spec() -> Vec<CommandSpec> {
+        vec![CommandSpec {
+            name: "convert",
+            about: "Convert",
+            args: vec![
+                ArgSpec { choices: &["bibtex", "json"], ..ArgSpec::option("to", "FORMAT", "Target") },
+                ArgSpec::flag("check", "Check"),
+            ],
+            positionals: vec![PositionalSpec::required("input", "Input").multiple()],
+        }]
+    }
+
+    fn args(a: &[&str]) -> Vec<String> {
+        a.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse() {
+        let m = parse(&spec(), &args(&["convert", "a.bib", "--to=json", "-o", "out.json", "--json", "-", "--", "--b"])).unwrap();
+        assert_eq!(m.command, "convert");
+        assert_eq!(m.value("to"), Some("json"));
+        assert_eq!(m.value("output"), Some("out.json"));
+        assert!(m.json());
+        assert!(!m.flag("check"));
+        assert_eq!(m.positionals(), &["a.bib", "-", "--b"]);
+        assert_eq!(parse(&spec(), &args(&["convert", "-oout", "x"])).unwrap().value("output"), Some("out"));
+        assert_eq!(parse(&spec(), &args(&["convert", "-oa", "-o", "b", "x"])).unwrap().values("output"), vec!["a", "b"]);
+        assert!(m.values("check").is_empty());
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(parse(&spec(), &args(&["frobnicate"])).unwrap_err().code, 2);
+        assert!(parse(&spec(), &args(&["convert"])).is_err());
+        assert!(parse(&spec(), &args(&["convert", "a", "--to", "xml"])).is_err());
+        assert!(parse(&spec(), &args(&["convert", "a", "--output"])).is_err());
+        assert!(parse(&spec(), &args(&["convert", "a", "--check=yes"])).is_err());
+        assert_eq!(parse(&spec(), &args(&["convert", "a", "-é"])).unwrap_err().code, 2);
+        assert_eq!(parse(&spec(), &args(&["convert", "a", "-oé.json"])).unwrap().value("output"), Some("é.json"));
+        assert!(parse(&spec(), &args(&[])).unwrap_err().message.contains("Commands:"));
+        assert_eq!(parse(&spec(), &args(&["convert", "a", "--to", "xml"])).unwrap_err().message,
+            "invalid value 'xml' for '--to <FORMAT>'");
+    }
+
+    #[test]
+    fn test_help() {
+        let e = parse(&spec(), &args(&["convert", "--help"])).unwrap_err();
+        assert_eq!(e.code, 0);
+        assert!(e.message.contains("Usage: perscrutar convert [OPTIONS] <input>..."), "{}", e.message);
+        assert!(e.message.contains("-o, --output <FILE>"));
+        assert!(e.message.contains("    --json"));
+        assert!(e.message.contains("[possible values: bibtex, json]"));
+        assert_eq!(parse(&spec(), &args(&["--version"])).unwrap_err().code, 0);
+    }
+}
@@ -0,0 +1,74 @@
+use perscrutarlib::audit::Event;
+use perscrutarlib::bibtex::bibliography::Bibliography;
+use perscrutarlib::bibtex::data::Entry;
+use perscrutarlib::bibtex::keys::unique_key;
+use perscrutarlib::bibtex::writer::{write_entries, WriteOptions};
+use perscrutarlib::import::{convert, ImportError};
+use perscrutarlib::json::JsonValue;
+use crate::cli::{CliError, Matches};
+use crate::commands::{Outcome, PROGRAM};
+use crate::io;
+
+#[cfg(feature = "net")]
+fn import(text: &str) -> Result<Vec<Entry>, ImportError> {
+    perscrutarlib::import::import(&perscrutarlib::net::CurlClient::default(), text)
+}
+
+#[cfg(not(feature = "net"))]
+fn import(text: &str) -> Result<Vec<Entry>, ImportError> {
+    convert(text)
+}
+
+/**
+Add the entries for the given text, or the clipboard, to a bibliography:
+a DOI, an arXiv identifier, an ISBN, a BibTeX, RIS or CSL-JSON snippet, or a
+reference to look up. Works already in the bibliography (by DOI) are
+skipped and new keys made unique.
+*/
+pub fn run(m: &Matches) -> Result<Outcome, CliError> {
+    let text = match m.positionals() {
+        [] => io::read_clipboard()?,
+        [path] if path == io::STDIO => io::read_input(io::STDIO)?,
+        words => words.join(" "),
+    };
+    let mut entries = match m.flag("offline") {
+        true => convert(&text),
+        false => import(&text),
+    }.map_err(|e| CliError::failure(&e.to_string()))?;
+
+    let config = io::load_config()?;
+    let target = match m.value("bibliography") {
+        Some(path) => path,
+        None => config.library().map_err(|e| CliError::failure(&e.to_string()))?
+            .ok_or_else(|| CliError::usage("no bibliography given and no library configured"))?,
+    };
+    let existing = match std::path::Path::new(target).exists() {
+        true => io::read_input(target)?,
+        false => String::new(),
+    };
+    let bibliography = match existing.trim() {
+        "" => Bibliography::new(),
+        _ => Bibliography::from_entries(io::load_entries(target)?),
+    };
+
+    let mut added: Vec<Entry> = Vec::new();
+    for mut entry in entries.drain(..) {
+        if let Some(old) = entry.get("doi").and_then(|d| bibliography.get_by_doi(d)) {
+            eprintln!("{}: {} is already in {} as `{}`", PROGRAM, entry.get("doi").unwrap_or_default(), target, old.key());
+            continue;
+        }
+        let key = unique_key(entry.key(), |k| bibliography.get(k).is_some() || added.iter().any(|e| e.key() == k));
+        entry.set_key(&key);
+        added.push(entry);
+    }
+    let document = write_entries(&added, &WriteOptions::default());
+    if !added.is_empty() && !m.flag("dry-run") {
+        let separator = if existing.trim().is_empty() { "" } else { "\n\n" };
+        io::write_output(target, &format!("{}{}{}", existing.trim_end(), separator, document))?;
+        let keys: Vec<&str> = added.iter().map(|e| e.key()).collect();
+        io::log_changes(target, vec![Event::now("add", &keys)]);
+    }
+    let json = JsonValue::Array(added.iter().map(|e| JsonValue::str(e.key())).collect());
+    let code = if added.is_empty() { 1 } else { 0 };
+    Ok(Outcome { code, ..Outcome::new(document, json) })
+}
@@ -0,0 +1,42 @@
+use perscrutarlib::bibtex::data::Entry;
+use perscrutarlib::compare::{compare, render, CompareOptions};
+use perscrutarlib::json::JsonValue;
+use crate::cli::{CliError, Matches};
+use crate::commands::Outcome;
+use crate::io;
+
+fn find<'a>(entries: &'a [Entry], key: &str) -> Result<&'a Entry, CliError> {
+    entries.iter().find(|e| e.key() == key)
+        .or_else(|| entries.iter().find(|e| e.is_known_as(key)))
+        .ok_or_else(|| CliError::failure(&format!("no entry with key `{}`", key)))
+}
+
+fn opt_str(v: &Option<String>) -> JsonValue {
+    v.as_deref().map(JsonValue::str).unwrap_or(JsonValue::Null)
+}
+
+pub fn run(m: &Matches) -> Result<Outcome, CliError> {
+    let input = m.positional(0).unwrap_or(io::STDIO);
+    let entries = io::load_entries(input)?;
+    let left = find(&entries, m.positional(1).unwrap_or_default())?;
+    let right = find(&entries, m.positional(2).unwrap_or_default())?;
+
+    let to_terminal = m.value("output").unwrap_or(io::STDIO) == io::STDIO && io::stdout_is_terminal();
+    let options = CompareOptions {
+        color: to_terminal,
+        only_differences: m.flag("differences"),
+        ..CompareOptions::default()
+    };
+    let rows = compare(left, right).into_iter().map(|r| JsonValue::object(vec![
+        ("field", JsonValue::str(&r.name)),
+        ("left", opt_str(&r.left)),
+        ("right", opt_str(&r.right)),
+        ("diff", JsonValue::str(&r.diff.marker().to_string())),
+    ])).collect();
+    let json = JsonValue::object(vec![
+        ("left", JsonValue::str(left.key())),
+        ("right", JsonValue::str(right.key())),
+        ("fields", JsonValue::Array(rows)),
+    ]);
+    Ok(Outcome::new(render(left, right, &options), json))
+}
@@ -0,0 +1,116 @@
+use perscrutarlib::bibtex::writer::{write_bibliography, write_strings, WriteOptions};
+use perscrutarlib::config::Config;
+use perscrutarlib::csl::to_csl_json_with;
+use perscrutarlib::formats::Format;
+use perscrutarlib::json::JsonValue;
+use perscrutarlib::ris::to_ris_with;
+use crate::cli::{CliError, Matches};
+use crate::commands::Outcome;
+use crate::io;
+
+/** The formats `convert` can write. */
+pub const OUTPUT_FORMATS: [Format; 3] = [Format::BibTeX, Format::CslJson, Format::Ris];
+
+fn format(name: &str) -> Result<Format, CliError> {
+    Format::from_name(name).ok_or_else(|| CliError::usage(&format!("unknown format `{}`", name)))
+}
+
+/**
+Convert a bibliography between formats. The input format is detected from
+the extension or the content unless `--from` gives it; the output format
+is `--to`, or else the one the `--output` extension implies. Private
+fields are left out unless `--include-private` is given. BibTeX written
+from BibTeX keeps the `@string` definitions and `@preamble`s.
+*/
+pub fn run(m: &Matches) -> Result<Outcome, CliError> {
+    convert(m, &io::load_config()?)
+}
+
+/** `run` with the project configuration `config`. */
+fn convert(m: &Matches, config: &Config) -> Result<Outcome, CliError> {
+    let policy = io::field_policy(m, config)?;
+    let input = m.positional(0).unwrap_or(io::STDIO);
+    let output = m.value("output").unwrap_or(io::STDIO);
+    let from = m.value("from").map(format).transpose()?;
+    let to = match m.value("to") {
+        Some(name) => format(name)?,
+        None => Format::from_path(output).ok_or_else(|| CliError::usage("give the output format with --to"))?,
+    };
+    if !OUTPUT_FORMATS.contains(&to) {
+        return Err(CliError::usage(&format!("cannot write {}", to)));
+    }
+    let (bibliography, macros) = io::load_bibliography_as(input, from)?;
+    let entries = bibliography.entries();
+    let document = match to {
+        Format::CslJson => format!("{}\n", to_csl_json_with(entries, &policy).to_pretty_string()),
+        Format::Ris => to_ris_with(entries, &policy),
+        _ => {
+            let options = WriteOptions { policy, ..WriteOptions::default() };
+            format!("{}{}", write_strings(&macros, &options), write_bibliography(&bibliography, &options))
+        }
+    };
+    let json = JsonValue::object(vec![
+        ("input", JsonValue::str(input)),
+        ("from", JsonValue::str(from.or_else(|| Format::from_path(input)).map_or("detected", |f| f.name()))),
+        ("to", JsonValue::str(to.name())),
+        ("entries", JsonValue::Num(entries.len() as f64)),
+    ]);
+    Ok(Outcome::new(document, json))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::cli::parse;
+    use crate::commands::commands;
+
+    #[test]
+    fn test_private_fields() {
+        let path = std::env::temp_dir().join(format!("perscrutar-convert-{}.bib", std::process::id()));
+        std::fs::write(&path, "@article{a, title = {T}, annote = {mine}, x-rating = {5}, note = {kept}}\n").unwrap();
+        let config = Config::parse("[fields]\nprivate = [\"annote\", \"x-*\"]\n").unwrap();
+        let run = |extra: &[&str]| {
+            let mut args = vec!["convert", path.to_str().unwrap()];
+            args.extend_from_slice(extra);
+            let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+            convert(&parse(&commands(), &args).unwrap(), &config).unwrap().text
+        };
+        for to in ["bibtex", "csl-json", "ris"] {
+            let text = run(&["--to", to]);
+            assert!(!text.contains("mine") && !text.contains('5'), "{}: {}", to, text);
+            assert!(text.contains("kept"), "{}: {}", to, text);
+        }
+        let all = run(&["--to", "bibtex", "--include-private"]);
+        assert!(all.contains("annote = {mine}") && all.contains("x-rating = {5}"));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_strings_and_preambles() {
+        let path = std::env::temp_dir().join(format!("perscrutar-convert-strings-{}.bib", std::process::id()));
+        std::fs::write(&path, "@preamble{\"\\newcommand{\\noopsort}[1]{}\"}\n@string{acm = {ACM Press}}\n\
+            @book{k, title = {T}, publisher = acm, month = jan}\n").unwrap();
+        let run = |to: &str| {
+            let args: Vec<String> = ["convert", path.to_str().unwrap(), "--to", to].iter().map(|a| a.to_string()).collect();
+            convert(&parse(&commands(), &args).unwrap(), &Config::default()).unwrap().text
+        };
+        assert_eq!(run("bibtex"), "@string{acm = {ACM Press}}\n\n@preamble{{\\newcommand{\\noopsort}[1]{}}}\n\n\
+            @book{k,\n  title = {T},\n  publisher = {ACM Press},\n  month = jan\n}\n");
+        assert!(!run("ris").contains("noopsort"));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_non_ascii_names() {
+        let path = std::env::temp_dir().join(format!("perscrutar-convert-names-{}.bib", std::process::id()));
+        std::fs::write(&path, "@book{b, title = {T}, author = {Jürgen Müller and 村上 春樹}, publisher = {P}, year = 2001}\n").unwrap();
+        let run = |to: &str| {
+            let args: Vec<String> = ["convert", path.to_str().unwrap(), "--to", to].iter().map(|a| a.to_string()).collect();
+            convert(&parse(&commands(), &args).unwrap(), &Config::default()).unwrap().text
+        };
+        assert!(run("ris").contains("AU  - Müller, Jürgen"));
+        assert!(run("csl-json").contains("\"family\": \"春樹\""));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,197 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use perscrutarlib::audit::{Event, RENAME};
+use perscrutarlib::bibtex::data::Entry;
+use perscrutarlib::bibtex::parser::{parse_with, parse_with_spans, Macros, ParseOptions};
+use perscrutarlib::bibtex::types::TypeRegistry;
+use perscrutarlib::config::Config;
+use perscrutarlib::json::JsonValue;
+use perscrutarlib::lint::{check_entry, Severity};
+use crate::cli::{CliError, Matches};
+use crate::commands::{Outcome, PROGRAM};
+use crate::io;
+
+/** `$VISUAL`, else `$EDITOR`, else `vi`. */
+fn editor() -> String {
+    ["VISUAL", "EDITOR"].into_iter()
+        .filter_map(|v| std::env::var(v).ok())
+        .find(|e| !e.trim().is_empty())
+        .unwrap_or_else(|| String::from("vi"))
+}
+
+/** Run the editor on `path`, through the shell so `EDITOR` may have arguments. */
+fn run_editor(editor: &str, path: &Path) -> Result<(), CliError> {
+    let status = Command::new("sh").arg("-c").arg(format!("{} \"$1\"", editor)).arg("sh").arg(path)
+        .status()
+        .map_err(|e| CliError::failure(&format!("cannot run {}: {}", editor, e)))?;
+    if !status.success() {
+        return Err(CliError::failure(&format!("{} exited with {}", editor, status)));
+    }
+    Ok(())
+}
+
+/**
+A new file in the temporary directory holding `contents`, readable only
+by its owner on Unix. The name is never that of an existing file, so a
+link planted there cannot redirect the write.
+*/
+fn temp_file(contents: &str) -> Result<PathBuf, CliError> {
+    use std::io::Write;
+
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    loop {
+        let nanos = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|d| d.subsec_nanos()).unwrap_or(0);
+        let name = format!("{}-{}-{}-{:08x}.bib", PROGRAM, std::process::id(), COUNTER.fetch_add(1, Ordering::Relaxed), nanos);
+        let path = std::env::temp_dir().join(name);
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        match options.open(&path) {
+            Ok(mut file) => return file.write_all(contents.as_bytes()).map(|_| path.clone())
+                .map_err(|e| CliError::failure(&format!("cannot write {}: {}", path.display(), e))),
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(CliError::failure(&format!("cannot write {}: {}", path.display(), e))),
+        }
+    }
+}
+
+/**
+The entry in the edited text, or why it cannot be saved: the text must
+hold exactly one entry, whose key no other entry has, and which lint finds
+no errors in. Warnings are printed but do not stop the save. A `pinned`
+entry must keep the key and type of the `original`.
+*/
+fn validate(text: &str, macros: &Macros, others: &[&Entry], original: &Entry, pinned: bool) -> Result<Entry, String> {
+    let mut entries = parse_with(text, &mut macros.clone(), ParseOptions::standard()).map_err(|e| e.to_string())?;
+    if entries.len() != 1 {
+        return Err(format!("expected one entry, found {}", entries.len()));
+    }
+    let entry = entries.remove(0);
+    if others.iter().any(|e| e.key() == entry.key()) {
+        return Err(format!("another entry already has the key `{}`", entry.key()));
+    }
+    if pinned && (entry.key() != original.key() || entry.entry_type() != original.entry_type()) {
+        return Err(format!("`{}` is pinned, so its key and type cannot change", original.key()));
+    }
+    let diagnostics = check_entry(&entry, &TypeRegistry::default());
+    for diagnostic in diagnostics.iter().filter(|d| d.severity != Severity::Error) {
+        eprintln!("{}: {}", PROGRAM, diagnostic);
+    }
+    match diagnostics.iter().find(|d| d.severity == Severity::Error) {
+        Some(error) => Err(error.to_string()),
+        None => Ok(entry),
+    }
+}
+
+/**
+Open the entry with the given key in the user's editor and write it back
+into its file in place. Text that does not parse or fails validation can
+be edited again; without a terminal to ask on, the command fails and
+leaves the file alone.
+*/
+pub fn run(m: &Matches) -> Result<Outcome, CliError> {
+    let config = io::load_config()?;
+    let input = match m.value("bibliography") {
+        Some(path) => path,
+        None => config.library().map_err(|e| CliError::failure(&e.to_string()))?
+            .ok_or_else(|| CliError::usage("no bibliography given and no library configured"))?,
+    };
+    if input == io::STDIO {
+        return Err(CliError::usage("edit needs a bibliography file"));
+    }
+    edit(input, m.positional(0).unwrap_or_default(), &editor(), &config)
+}
+
+/**
+Edit the entry `key` of the file `input` with `editor`. The editor is
+given the entry as it is written in the file, and the text saved from it
+replaces the entry as it is, so macros, delimiters and layout stay as the
+user left them.
+*/
+fn edit(input: &str, key: &str, editor: &str, config: &Config) -> Result<Outcome, CliError> {
+    let text = io::read_input(input)?;
+    let mut macros = Macros::new();
+    let (entries, _) = parse_with_spans(&text, &mut macros, ParseOptions::standard())
+        .map_err(|e| CliError::failure(&format!("{}: {}", input, e)))?;
+    let index = entries.iter().position(|(e, _)| e.key() == key)
+        .or_else(|| entries.iter().position(|(e, _)| e.is_known_as(key)))
+        .ok_or_else(|| CliError::failure(&format!("no entry with key `{}` in {}", key, input)))?;
+    let (original, span) = &entries[index];
+    let others: Vec<&Entry> = entries.iter().enumerate().filter(|(i, _)| *i != index).map(|(_, (e, _))| e).collect();
+
+    let pinned = config.pinned_keys().map_err(|e| CliError::failure(&e.to_string()))?.iter().any(|k| k == original.key());
+
+    let path = temp_file(&format!("{}\n", &text[span.start..span.end]))?;
+    let (edited, source) = loop {
+        run_editor(editor, &path)?;
+        let source = std::fs::read_to_string(&path)
+            .map_err(|e| CliError::failure(&format!("cannot read {}: {}", path.display(), e)))?;
+        match validate(&source, &macros, &others, original, pinned) {
+            Ok(entry) => break (entry, source),
+            Err(message) => {
+                eprintln!("{}: {}", PROGRAM, message);
+                if !io::confirm("Edit again?") {
+                    return Err(CliError::failure(&format!("not saved; the edited entry is in {}", path.display())));
+                }
+            }
+        }
+    };
+    let _ = std::fs::remove_file(&path);
+
+    let changed = !edited.is_identical(original);
+    if changed {
+        io::write_output(input, &format!("{}{}{}", &text[..span.start], source.trim(), &text[span.end..]))?;
+        let mut events = Vec::new();
+        if edited.key() != original.key() {
+            events.push(Event::now(RENAME, &[original.key(), edited.key()]));
+        }
+        events.push(Event::now("edit", &[edited.key()]));
+        io::log_changes(input, events);
+    }
+    let summary = if changed { format!("{}: saved to {}\n", edited.key(), input) } else { format!("{}: unchanged\n", edited.key()) };
+    let json = JsonValue::object(vec![
+        ("key", JsonValue::str(edited.key())),
+        ("changed", JsonValue::Boolean(changed)),
+    ]);
+    Ok(Outcome::new(summary, json))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_temp_file() {
+        let a = temp_file("a").unwrap();
+        let b = temp_file("b").unwrap();
+        assert_ne!(a, b);
+        assert_eq!(std::fs::read_to_string(&b).unwrap(), "b");
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(std::fs::metadata(&a).unwrap().permissions().mode() & 0o777, 0o600);
+        }
+        std::fs::remove_file(a).unwrap();
+        std::fs::remove_file(b).unwrap();
+    }
+
+    #[test]
+    fn test_edit_keeps_source() {
+        let path = std::env::temp_dir().join(format!("perscrutar-edit-{}.bib", std::process::id()));
+        let text = "% header\n@string{jacm = {Journal of the ACM}}\n\n@article{Knuth84,\n  author = {Donald E. Knuth},\n  journal = jacm,\n  title = \"Literate Programming\",\n  year = 1984,\n  month = jan\n}\n\n@misc{a/b, title = {Other}}\n";
+        std::fs::write(&path, text).unwrap();
+        let input = path.to_str().unwrap();
+        let outcome = edit(input, "Knuth84", "sed -i s/1984/1985/", &Config::default()).unwrap();
+        assert!(outcome.text.contains("saved"));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), text.replace("1984", "1985"));
+        let outcome = edit(input, "a/b", "true", &Config::default()).unwrap();
+        assert!(outcome.text.contains("unchanged"));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,71 @@
+use perscrutarlib::bibtex::writer::{write_bibliography, write_strings, WriteOptions};
+use perscrutarlib::json::JsonValue;
+use perscrutarlib::latex::scan::scan;
+use perscrutarlib::search::SearchIndex;
+use crate::cli::{CliError, Matches};
+use crate::commands::{Outcome, PROGRAM};
+use crate::io;
+
+/**
+The keys in a key list: one or more per line, separated by commas or
+spaces, with `%` or `#` starting a comment.
+*/
+fn read_keys(path: &str) -> Result<Vec<String>, CliError> {
+    let text = io::read_input(path)?;
+    Ok(text.lines()
+        .map(|line| line.split(['%', '#']).next().unwrap_or_default())
+        .flat_map(|line| line.split(|c: char| c == ',' || c.is_whitespace()))
+        .filter(|k| !k.is_empty())
+        .map(String::from)
+        .collect())
+}
+
+/**
+Print a bibliography with only the entries named in `--keys`, matching
+`--query` or cited by the `--tex` document, and the entries they depend
+on, after the `@string` definitions and `@preamble`s of a BibTeX input.
+Keys that are not found are reported on standard error. Private fields
+are left out unless `--include-private` is given.
+*/
+pub fn run(m: &Matches) -> Result<Outcome, CliError> {
+    if m.value("keys").is_none() && m.value("query").is_none() && m.value("tex").is_none() {
+        return Err(CliError::usage("give the entries to extract with --keys, --query or --tex"));
+    }
+    let config = io::load_config()?;
+    let input = match m.positional(0) {
+        Some(path) => path,
+        None => config.library().map_err(|e| CliError::failure(&e.to_string()))?.unwrap_or(io::STDIO),
+    };
+    let (bibliography, macros) = io::load_bibliography_as(input, None)?;
+    let mut keys = match m.value("keys") {
+        Some(path) => read_keys(path)?,
+        None => Vec::new(),
+    };
+    if let Some(query) = m.value("query") {
+        let index = SearchIndex::build(bibliography.entries());
+        keys.extend(index.search(query).into_iter().map(String::from));
+    }
+    if let Some(tex) = m.value("tex") {
+        let cited = scan(tex).map_err(|e| CliError::failure(&e.to_string()))?;
+        for name in cited.unresolved.iter() {
+            eprintln!("{}: no file for `{}` included in {}", PROGRAM, name, tex);
+        }
+        match cited.all {
+            true => keys.extend(bibliography.entries().iter().map(|e| String::from(e.key()))),
+            false => keys.extend(cited.keys().into_iter().map(String::from)),
+        }
+    }
+
+    let extract = bibliography.extract_for(&keys);
+    for key in extract.missing.iter() {
+        eprintln!("{}: entry `{}` not found in {}", PROGRAM, key, io::display_name(input));
+    }
+    let entries = extract.bibliography.entries();
+    let options = WriteOptions { policy: io::field_policy(m, &config)?, ..WriteOptions::default() };
+    let document = format!("{}{}", write_strings(&macros, &options), write_bibliography(&extract.bibliography, &options));
+    let json = JsonValue::object(vec![
+        ("keys", JsonValue::Array(entries.iter().map(|e| JsonValue::str(e.key())).collect())),
+        ("missing", JsonValue::Array(extract.missing.iter().map(|k| JsonValue::str(k)).collect())),
+    ]);
+    Ok(Outcome::new(document, json))
+}
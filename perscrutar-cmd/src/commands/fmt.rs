@@ -0,0 +1,72 @@
+use perscrutarlib::bibtex::format::{format, FormatOptions};
+use perscrutarlib::config::{Config, ConfigError, CONFIG_FILE};
+use perscrutarlib::json::JsonValue;
+use crate::cli::{CliError, Matches};
+use crate::commands::Outcome;
+use crate::io;
+
+fn config_error(e: ConfigError) -> CliError {
+    CliError::failure(&format!("{}: {}", CONFIG_FILE, e))
+}
+
+/**
+The `[format]` options of the configuration, with `--sort` and
+`--preserve-order` taking precedence over its `sort`.
+*/
+fn options(m: &Matches, config: &Config) -> Result<FormatOptions, CliError> {
+    let mut options = config.format_options().map_err(config_error)?;
+    if m.flag("sort") && m.flag("preserve-order") {
+        return Err(CliError::usage("--sort and --preserve-order cannot be used together"));
+    }
+    if m.flag("sort") {
+        options.sort = true;
+    }
+    if m.flag("preserve-order") {
+        options.sort = false;
+    }
+    Ok(options)
+}
+
+/**
+Rewrite bibliographies in canonical form, in place; standard input is
+written to standard output. With `--check` nothing is written, and the
+command fails if any input is not formatted.
+*/
+pub fn run(m: &Matches) -> Result<Outcome, CliError> {
+    let config = io::load_config()?;
+    let options = options(m, &config)?;
+    let check = m.flag("check");
+    let inputs: Vec<&str> = if m.positionals().is_empty() {
+        vec![config.library().map_err(config_error)?.unwrap_or(io::STDIO)]
+    } else {
+        m.positionals().iter().map(|s| s.as_str()).collect()
+    };
+
+    let mut text = String::new();
+    let mut changed = Vec::new();
+    for input in inputs.iter() {
+        let content = io::read_input(input)?;
+        let formatted = format(&content, &options)
+            .map_err(|e| CliError::failure(&format!("{}: {}", io::display_name(input), e)))?;
+        if *input == io::STDIO && !check {
+            text.push_str(&formatted);
+        } else if formatted != content {
+            if check {
+                text.push_str(&format!("{}: not formatted\n", io::display_name(input)));
+            } else {
+                io::write_output(input, &formatted)?;
+                text.push_str(&format!("{}: formatted\n", input));
+            }
+        }
+        if formatted != content {
+            changed.push(JsonValue::str(io::display_name(input)));
+        }
+    }
+    let code = if check && !changed.is_empty() { 1 } else { 0 };
+    let json = JsonValue::object(vec![
+        ("checked", JsonValue::Num(inputs.len() as f64)),
+        (if check { "unformatted" } else { "formatted" }, JsonValue::Array(changed)),
+        ("passed", JsonValue::Boolean(code == 0)),
+    ]);
+    Ok(Outcome { code, ..Outcome::new(text, json) })
+}
@@ -0,0 +1,105 @@
+use std::path::{Path, PathBuf};
+use perscrutarlib::config::{starter, CONFIG_FILE};
+use perscrutarlib::json::JsonValue;
+use crate::cli::{CliError, Matches};
+use crate::commands::{Outcome, PROGRAM};
+
+pub const LIBRARY_FILE: &str = "master.bib";
+
+const MONTHS: [(&str, &str); 12] = [
+    ("jan", "January"), ("feb", "February"), ("mar", "March"), ("apr", "April"),
+    ("may", "May"), ("jun", "June"), ("jul", "July"), ("aug", "August"),
+    ("sep", "September"), ("oct", "October"), ("nov", "November"), ("dec", "December"),
+];
+
+const JOURNALS: [(&str, &str); 6] = [
+    ("cacm", "Communications of the ACM"),
+    ("jacm", "Journal of the ACM"),
+    ("tocs", "ACM Transactions on Computer Systems"),
+    ("toplas", "ACM Transactions on Programming Languages and Systems"),
+    ("tse", "IEEE Transactions on Software Engineering"),
+    ("sicomp", "SIAM Journal on Computing"),
+];
+
+fn library_template() -> String {
+    let mut out = String::new();
+    for (name, value) in MONTHS.iter().chain(JOURNALS.iter()) {
+        out.push_str(&format!("@string{{{} = \"{}\"}}\n", name, value));
+    }
+    out
+}
+
+fn hook_script() -> String {
+    format!("#!/bin/sh\n# installed by `{0} init`\nset -e\n{0} fmt --check\n{0} lint\n", PROGRAM)
+}
+
+fn write(path: &Path, content: &str) -> Result<(), CliError> {
+    std::fs::write(path, content)
+        .map_err(|e| CliError::failure(&format!("cannot write {}: {}", path.display(), e)))
+}
+
+#[cfg(unix)]
+fn make_executable(path: &Path) -> Result<(), CliError> {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o755))
+        .map_err(|e| CliError::failure(&format!("cannot make {} executable: {}", path.display(), e)))
+}
+
+#[cfg(not(unix))]
+fn make_executable(_path: &Path) -> Result<(), CliError> {
+    Ok(())
+}
+
+pub fn run(m: &Matches) -> Result<Outcome, CliError> {
+    let dir = PathBuf::from(m.positional(0).unwrap_or("."));
+    std::fs::create_dir_all(&dir)
+        .map_err(|e| CliError::failure(&format!("cannot create {}: {}", dir.display(), e)))?;
+
+    let mut files: Vec<(PathBuf, String)> = vec![
+        (dir.join(LIBRARY_FILE), library_template()),
+        (dir.join(CONFIG_FILE), starter(LIBRARY_FILE)),
+    ];
+    if m.flag("hook") {
+        let hooks = dir.join(".git").join("hooks");
+        if !hooks.is_dir() {
+            return Err(CliError::failure(&format!("{} is not a git repository, cannot install a hook", dir.display())));
+        }
+        files.push((hooks.join("pre-commit"), hook_script()));
+    }
+    // check everything first so that a refusal leaves nothing half-written
+    if !m.flag("force") {
+        if let Some((path, _)) = files.iter().find(|(p, _)| p.exists()) {
+            return Err(CliError::failure(&format!("{} already exists, use --force to overwrite it", path.display())));
+        }
+    }
+    for (path, content) in files.iter() {
+        write(path, content)?;
+    }
+    if m.flag("hook") {
+        make_executable(&files[2].0)?;
+    }
+
+    let names: Vec<String> = files.iter().map(|(p, _)| p.display().to_string()).collect();
+    let text = names.iter().map(|n| format!("created {}\n", n)).collect();
+    let json = JsonValue::object(vec![
+        ("created", JsonValue::Array(names.iter().map(|n| JsonValue::str(n)).collect())),
+    ]);
+    Ok(Outcome::new(text, json))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use perscrutarlib::bibtex::format::{is_formatted, FormatOptions};
+    use perscrutarlib::bibtex::parser::parse_entries;
+
+    #[test]
+    fn test_templates() {
+        assert!(parse_entries(&library_template()).unwrap().is_empty());
+        assert!(library_template().contains("@string{tocs = \"ACM Transactions on Computer Systems\"}"));
+        // the pre-commit hook checks the library with `fmt --check`
+        assert!(is_formatted(&library_template(), &FormatOptions::default()).unwrap());
+        assert!(hook_script().starts_with("#!/bin/sh\n"));
+    }
+}
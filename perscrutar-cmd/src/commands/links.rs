@@ -0,0 +1,72 @@
+use crate::cli::{CliError, Matches};
+use crate::commands::Outcome;
+
+#[cfg(feature = "net")]
+pub fn run(m: &Matches) -> Result<Outcome, CliError> {
+    use std::time::Duration;
+    use perscrutarlib::json::JsonValue;
+    use perscrutarlib::links::{LinkCheckOptions, LinkChecker, LinkStatus};
+    use perscrutarlib::net::CurlClient;
+    use crate::io;
+
+    let config = io::load_config()?;
+    let input = match m.positional(0) {
+        Some(path) => path,
+        None => config.library().map_err(|e| CliError::failure(&e.to_string()))?.unwrap_or(io::STDIO),
+    };
+    let delay = match m.value("delay") {
+        Some(s) => s.parse::<f64>().ok().filter(|d| d.is_finite() && *d >= 0.0)
+            .ok_or_else(|| CliError::usage(&format!("invalid value `{}` for --delay, expected seconds", s)))?,
+        None => 1.0,
+    };
+    let options = LinkCheckOptions {
+        delay: Duration::from_secs_f64(delay),
+        suggest: !m.flag("no-archive"),
+        ..LinkCheckOptions::default()
+    };
+    let entries = io::load_entries(input)?;
+    let client = CurlClient::default();
+    let checks = LinkChecker::new(&client, options).check(&entries);
+
+    let mut text = String::new();
+    let mut list = Vec::new();
+    let mut dead = 0;
+    for c in checks.iter() {
+        let (state, detail) = match &c.status {
+            LinkStatus::Ok => ("ok", String::new()),
+            LinkStatus::Redirected { to, permanent: true } => ("moved", format!(" -> {}", to)),
+            LinkStatus::Redirected { to, permanent: false } => ("redirected", format!(" -> {}", to)),
+            LinkStatus::Dead { message, .. } => {
+                dead += 1;
+                ("dead", format!(" ({})", message))
+            }
+            LinkStatus::Unknown(message) => ("unknown", format!(" ({})", message)),
+        };
+        if c.status != LinkStatus::Ok || m.flag("all") {
+            text.push_str(&format!("{}: {} {}{}\n", c.key, state, c.url, detail));
+            if let Some(s) = &c.suggestion {
+                text.push_str(&format!("  suggested: {}\n", s));
+            }
+        }
+        let mut members = vec![
+            ("key", JsonValue::str(&c.key)),
+            ("url", JsonValue::str(&c.url)),
+            ("status", JsonValue::str(state)),
+        ];
+        match &c.status {
+            LinkStatus::Redirected { to, .. } => members.push(("location", JsonValue::str(to))),
+            LinkStatus::Dead { status: Some(code), .. } => members.push(("http_status", JsonValue::Num(*code as f64))),
+            _ => {}
+        }
+        members.push(("suggestion", c.suggestion.as_deref().map(JsonValue::str).unwrap_or(JsonValue::Null)));
+        list.push(JsonValue::object(members));
+    }
+    text.push_str(&format!("{} links checked, {} dead\n", checks.len(), dead));
+    let code = if dead > 0 { 1 } else { 0 };
+    Ok(Outcome { code, ..Outcome::new(text, JsonValue::Array(list)) })
+}
+
+#[cfg(not(feature = "net"))]
+pub fn run(_m: &Matches) -> Result<Outcome, CliError> {
+    Err(CliError::usage("links needs a build with the `net` feature"))
+}
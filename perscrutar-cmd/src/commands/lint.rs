@@ -0,0 +1,189 @@
+use std::path::Path;
+use perscrutarlib::bibtex::data::Entry;
+use perscrutarlib::bibtex::parser::{parse_with_spans, Macros, ParseOptions};
+use perscrutarlib::bibtex::types::TypeRegistry;
+use perscrutarlib::config::{Config, ConfigError, CONFIG_FILE};
+use perscrutarlib::formats::Format;
+use perscrutarlib::json::JsonValue;
+use perscrutarlib::lint::{check, check_datamodel, check_located, counts, Diagnostic, ExitPolicy, Severity};
+use perscrutarlib::spell::{self, Dictionary};
+use crate::cli::{CliError, Matches};
+use crate::commands::Outcome;
+use crate::io;
+
+fn config_error(e: ConfigError) -> CliError {
+    CliError::failure(&format!("{}: {}", CONFIG_FILE, e))
+}
+
+fn policy(m: &Matches, config: &Config) -> Result<ExitPolicy, CliError> {
+    let mut policy = config.lint_policy().map_err(config_error)?;
+    if let Some(deny) = m.value("deny") {
+        policy.deny = Severity::parse(deny)
+            .ok_or_else(|| CliError::usage(&format!("invalid value `{}` for --deny, expected errors, warnings or info", deny)))?;
+    }
+    if let Some(max) = m.value("max-warnings") {
+        policy.max_warnings = Some(max.parse()
+            .map_err(|_| CliError::usage(&format!("invalid value `{}` for --max-warnings, expected a number", max)))?);
+    }
+    Ok(policy)
+}
+
+/**
+The spelling dictionary from `--dictionary`/`--words` or the `[spell]`
+section of the configuration; `None` if spell checking is not enabled.
+*/
+fn dictionary(m: &Matches, config: &Config) -> Result<Option<Dictionary>, CliError> {
+    let dic = match m.value("dictionary") {
+        Some(path) => Some(path),
+        None => config.get_str("spell", "dictionary").map_err(config_error)?,
+    };
+    let words = match m.value("words") {
+        Some(path) => Some(path),
+        None => config.get_str("spell", "words").map_err(config_error)?,
+    };
+    if dic.is_none() && words.is_none() {
+        return Ok(None);
+    }
+    let mut dict = match dic {
+        Some(path) => {
+            let aff_path = Path::new(path).with_extension("aff");
+            let aff = std::fs::read_to_string(&aff_path).ok();
+            Dictionary::from_hunspell(&io::read_input(path)?, aff.as_deref())
+                .map_err(|e| CliError::failure(&format!("{}: {}", aff_path.display(), e)))?
+        }
+        None => Dictionary::new(),
+    };
+    if let Some(path) = words {
+        dict.add_word_list(&io::read_input(path)?);
+    }
+    Ok(Some(dict))
+}
+
+/**
+Load the `--plugin`s once and return what checks entries with them.
+*/
+#[cfg(feature = "plugin")]
+fn plugins(m: &Matches) -> Result<impl Fn(&[Entry]) -> Result<Vec<Diagnostic>, CliError>, CliError> {
+    use perscrutarlib::plugin::Plugin;
+
+    let plugins = m.values("plugin").into_iter()
+        .map(|path| Plugin::load(path).map_err(|e| CliError::failure(&e.to_string())))
+        .collect::<Result<Vec<Plugin>, CliError>>()?;
+    Ok(move |entries: &[Entry]| {
+        let mut diagnostics = Vec::new();
+        for plugin in plugins.iter() {
+            diagnostics.extend(plugin.check(entries)
+                .map_err(|e| CliError::failure(&format!("{}: {}", plugin.name(), e)))?);
+        }
+        Ok(diagnostics)
+    })
+}
+
+#[cfg(not(feature = "plugin"))]
+fn plugins(m: &Matches) -> Result<impl Fn(&[Entry]) -> Result<Vec<Diagnostic>, CliError>, CliError> {
+    if !m.values("plugin").is_empty() {
+        return Err(CliError::usage("--plugin needs a build with the `plugin` feature"));
+    }
+    Ok(|_: &[Entry]| Ok(vec![]))
+}
+
+/** The entries of an input, the line of each, and their diagnostics. */
+struct Checked {
+    entries: Vec<Entry>,
+    /** Empty unless the input is a BibTeX file. */
+    lines: Vec<usize>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+/**
+Load and check `input`. BibTeX files are parsed with spans, so that
+diagnostics point at the line of their entry.
+*/
+fn load_and_check(input: &str, registry: &TypeRegistry) -> Result<Checked, CliError> {
+    if input.starts_with("https://") || input.starts_with("http://") {
+        let entries = io::load_entries(input)?;
+        let diagnostics = check(&entries, registry);
+        return Ok(Checked { entries, lines: vec![], diagnostics });
+    }
+    let content = io::read_input(input)?;
+    if Format::resolve(Some(input), &content) != Some(Format::BibTeX) {
+        let entries = io::parse_as(input, &content, None)?;
+        let diagnostics = check(&entries, registry);
+        return Ok(Checked { entries, lines: vec![], diagnostics });
+    }
+    let (located, _) = parse_with_spans(&content, &mut Macros::new(), ParseOptions::standard())
+        .map_err(|e| CliError::failure(&format!("{}: {}", io::display_name(input), e)))?;
+    let diagnostics = check_located(&located, registry);
+    let lines = located.iter().map(|(_, span)| span.line).collect();
+    Ok(Checked { entries: located.into_iter().map(|(entry, _)| entry).collect(), lines, diagnostics })
+}
+
+/**
+Check bibliographies. With `--datamodel` (or `datamodel = true` in the
+`[lint]` section) entry types and their fields are checked against
+biblatex's data model, as biber does, instead of the BibTeX types.
+*/
+pub fn run(m: &Matches) -> Result<Outcome, CliError> {
+    let config = io::load_config()?;
+    let policy = policy(m, &config)?;
+    let datamodel = m.flag("datamodel") || config.get_bool("lint", "datamodel").map_err(config_error)?.unwrap_or(false);
+    let registry = TypeRegistry::default();
+    let dict = dictionary(m, &config)?;
+    let plugin_check = plugins(m)?;
+    let inputs: Vec<&str> = if m.positionals().is_empty() {
+        vec![config.library().map_err(config_error)?.unwrap_or(io::STDIO)]
+    } else {
+        m.positionals().iter().map(|s| s.as_str()).collect()
+    };
+
+    let mut text = String::new();
+    let mut list = Vec::new();
+    let mut all = Vec::new();
+    for input in inputs {
+        let Checked { entries, lines, diagnostics: mut diags } = load_and_check(input, &registry)?;
+        if datamodel {
+            diags.retain(|d| !matches!(d.rule, "missing-field" | "unknown-type"));
+            diags.extend(check_datamodel(&entries, &lines));
+        }
+        let mut extra = plugin_check(&entries)?;
+        if let Some(dict) = &dict {
+            extra.extend(spell::check(&entries, dict));
+        }
+        diags.extend(extra.into_iter().map(|d| Diagnostic {
+            line: entries.iter().position(|e| e.key() == d.key).and_then(|i| lines.get(i).copied()),
+            ..d
+        }));
+        for d in diags.iter() {
+            match d.line {
+                Some(line) => text.push_str(&format!("{}:{}: {}\n", io::display_name(input), line, d)),
+                None => text.push_str(&format!("{}: {}\n", io::display_name(input), d)),
+            }
+            list.push(JsonValue::object(vec![
+                ("file", JsonValue::str(io::display_name(input))),
+                ("line", d.line.map_or(JsonValue::Null, |line| JsonValue::Num(line as f64))),
+                ("key", JsonValue::str(&d.key)),
+                ("rule", JsonValue::str(d.rule)),
+                ("severity", JsonValue::str(d.severity.name())),
+                ("message", JsonValue::str(&d.message)),
+            ]));
+        }
+        all.extend(diags);
+    }
+
+    let c = counts(&all);
+    let code = policy.exit_code(&all);
+    text.push_str(&format!("{} errors, {} warnings, {} notes{}\n",
+        c[Severity::Error as usize], c[Severity::Warning as usize], c[Severity::Info as usize],
+        if code == 0 { "" } else { " (failed)" }));
+    let json = JsonValue::object(vec![
+        ("diagnostics", JsonValue::Array(list)),
+        ("errors", JsonValue::Num(c[Severity::Error as usize] as f64)),
+        ("warnings", JsonValue::Num(c[Severity::Warning as usize] as f64)),
+        ("info", JsonValue::Num(c[Severity::Info as usize] as f64)),
+        ("passed", JsonValue::Boolean(code == 0)),
+    ]);
+    if m.value("format") == Some("json") {
+        text = format!("{}\n", json.to_pretty_string());
+    }
+    Ok(Outcome { code, ..Outcome::new(text, json) })
+}
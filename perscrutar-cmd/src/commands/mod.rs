@@ -0,0 +1,282 @@
+/*!
+
+Subcommand table and dispatch.
+
+Every subcommand returns an `Outcome` carrying both its human-readable text
+and a JSON value; `main` prints whichever the user asked for with `--json`.
+
+*/
+
+pub mod add;
+pub mod compare;
+pub mod convert;
+pub mod edit;
+pub mod extract;
+pub mod fmt;
+pub mod init;
+pub mod links;
+pub mod lint;
+pub mod pandoc;
+pub mod publist;
+pub mod search;
+pub mod stats;
+pub mod styles;
+pub mod sync;
+pub mod transform;
+pub mod types;
+pub mod usage;
+pub mod watch;
+
+use perscrutarlib::json::JsonValue;
+use crate::cli::{ArgSpec, CliError, CommandSpec, Matches, PositionalSpec};
+use crate::completions;
+
+pub const PROGRAM: &str = "perscrutar";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Outcome {
+    pub text: String,
+    pub json: JsonValue,
+    /** Process exit status. */
+    pub code: i32,
+}
+
+impl Outcome {
+    pub fn new(text: String, json: JsonValue) -> Outcome {
+        Outcome { text, json, code: 0 }
+    }
+}
+
+pub fn commands() -> Vec<CommandSpec> {
+    vec![
+        CommandSpec {
+            name: "types",
+            about: "List the known entry types and their required fields",
+            args: vec![],
+            positionals: vec![PositionalSpec::optional("type", "Only show these types").multiple()],
+        },
+        CommandSpec {
+            name: "add",
+            about: "Add a paper from a DOI, arXiv id, ISBN, BibTeX, RIS or CSL-JSON snippet or reference, by default the clipboard's",
+            args: vec![
+                ArgSpec::option("bibliography", "FILE", "Bibliography to add to (default: the configured library)").short('b'),
+                ArgSpec::flag("offline", "Only convert snippets, without looking anything up"),
+                ArgSpec::flag("dry-run", "Print the new entries without adding them").short('n'),
+            ],
+            positionals: vec![PositionalSpec::optional("text", "What to add, `-` for standard input (default: the clipboard)").multiple()],
+        },
+        CommandSpec {
+            name: "compare",
+            about: "Show two entries side by side, marking differing fields",
+            args: vec![ArgSpec::flag("differences", "Only show fields that differ").short('d')],
+            positionals: vec![
+                PositionalSpec::required("input", "Bibliography to read, `-` for standard input"),
+                PositionalSpec::required("left", "Citation key of the first entry"),
+                PositionalSpec::required("right", "Citation key of the second entry"),
+            ],
+        },
+        CommandSpec {
+            name: "convert",
+            about: "Convert a bibliography between BibTeX, CSL-JSON and RIS",
+            args: vec![
+                ArgSpec { choices: &["bibtex", "csl-json", "ris", "cff", "codemeta"], ..ArgSpec::option("from", "FORMAT", "Input format (default: from the extension or the content)").short('f') },
+                ArgSpec { choices: &["bibtex", "csl-json", "ris"], ..ArgSpec::option("to", "FORMAT", "Output format (default: from the --output extension)").short('t') },
+                ArgSpec::flag("include-private", "Keep the fields the configuration marks as private"),
+            ],
+            positionals: vec![PositionalSpec::optional("input", "Bibliography to read, `-` for standard input")],
+        },
+        CommandSpec {
+            name: "edit",
+            about: "Edit an entry in $EDITOR and write it back into its file if it is valid",
+            args: vec![ArgSpec::option("bibliography", "FILE", "Bibliography holding the entry (default: the configured library)").short('b')],
+            positionals: vec![PositionalSpec::required("key", "Citation key of the entry")],
+        },
+        CommandSpec {
+            name: "extract",
+            about: "Write the entries with the listed keys, matching a query or cited in a LaTeX document, with those they depend on",
+            args: vec![
+                ArgSpec::option("keys", "FILE", "Citation keys to extract, one or more per line, `-` for standard input").short('k'),
+                ArgSpec::option("query", "TEXT", "Also extract the entries a search for TEXT finds").short('q'),
+                ArgSpec::option("tex", "FILE", "Also extract the entries a LaTeX document and the files it includes cite"),
+                ArgSpec::flag("include-private", "Keep the fields the configuration marks as private"),
+            ],
+            positionals: vec![PositionalSpec::optional("input", "Bibliography to read, `-` for standard input (default: the configured library)")],
+        },
+        CommandSpec {
+            name: "fmt",
+            about: "Rewrite bibliographies in canonical form, or check that they are",
+            args: vec![
+                ArgSpec::flag("check", "Change nothing; fail if an input is not formatted"),
+                ArgSpec::flag("sort", "Order entries by citation key"),
+                ArgSpec::flag("preserve-order", "Keep the entries in file order"),
+            ],
+            positionals: vec![PositionalSpec::optional("input", "Bibliographies to format, `-` for standard input (default: the configured library)").multiple()],
+        },
+        CommandSpec {
+            name: "init",
+            about: "Create a starter bibliography and configuration",
+            args: vec![
+                ArgSpec::flag("hook", "Also install a git pre-commit hook running lint"),
+                ArgSpec::flag("force", "Overwrite existing files"),
+            ],
+            positionals: vec![PositionalSpec::optional("dir", "Project directory (default: current directory)")],
+        },
+        CommandSpec {
+            name: "links",
+            about: "Check that url fields still work, reporting dead and redirected links (`net` feature)",
+            args: vec![
+                ArgSpec::option("delay", "SECONDS", "Time between two requests to the same host (default: 1)"),
+                ArgSpec::flag("no-archive", "Do not look up archived copies of dead links"),
+                ArgSpec::flag("all", "Also list the links that work"),
+            ],
+            positionals: vec![PositionalSpec::optional("input", "Bibliography to check, `-` for standard input (default: the configured library)")],
+        },
+        CommandSpec {
+            name: "lint",
+            about: "Check bibliographies for missing, empty and duplicate data",
+            args: vec![
+                ArgSpec::option("deny", "SEVERITY", "Fail on diagnostics of this severity or worse (errors, warnings, info)"),
+                ArgSpec::option("max-warnings", "N", "Fail when there are more than N warnings"),
+                ArgSpec::option("dictionary", "FILE", "Spell-check prose fields against a Hunspell .dic (and its .aff)"),
+                ArgSpec::option("words", "FILE", "Personal word list accepted by the spell check"),
+                ArgSpec::option("plugin", "FILE", "Also check with the lint rules of a WebAssembly plugin; repeatable (`plugin` feature)"),
+                ArgSpec::flag("datamodel", "Check types and fields against biblatex's data model, as biber does"),
+                ArgSpec { choices: &["text", "json"], ..ArgSpec::option("format", "FORMAT", "Output format; `json` is the same as --json") },
+            ],
+            positionals: vec![PositionalSpec::optional("input", "Bibliographies to check, `-` for standard input (default: the configured library)").multiple()],
+        },
+        CommandSpec {
+            name: "pandoc",
+            about: "Act as a pandoc JSON filter, filling in citations and adding the references",
+            args: vec![
+                ArgSpec::option("bibliography", "FILE", "Bibliography to cite from (default: the document's `bibliography`, then the configured library)").short('b'),
+                ArgSpec { choices: &["author-year", "numeric"], ..ArgSpec::option("style", "STYLE", "Citation style (default: author-year)") },
+                ArgSpec::option("template", "FILE", "Reference template with {field} placeholders and [optional] segments"),
+                ArgSpec::option("title", "TEXT", "Heading for the references (default: the document's `reference-section-title`)"),
+            ],
+            positionals: vec![PositionalSpec::optional("format", "Output format, as passed by pandoc; ignored")],
+        },
+        CommandSpec {
+            name: "publist",
+            about: "Render a publication list grouped by year as HTML or Markdown",
+            args: vec![
+                ArgSpec::option("author", "NAME", "Only include works by this author"),
+                ArgSpec::option("orcid", "ID", "Only include works with this ORCID iD"),
+                ArgSpec { choices: &["html", "markdown"], ..ArgSpec::option("format", "FORMAT", "Output format (default: markdown)") },
+                ArgSpec::option("template", "FILE", "Item template with {field} placeholders and [optional] segments"),
+                ArgSpec::option("page-template", "FILE", "Handlebars template for the whole page (`templates` feature)"),
+                ArgSpec::option("title", "TEXT", "Heading for the list"),
+                ArgSpec::flag("include-private", "Keep the fields the configuration marks as private"),
+            ],
+            positionals: vec![PositionalSpec::optional("input", "Bibliography to read, `-` for standard input")],
+        },
+        CommandSpec {
+            name: "search",
+            about: "Find entries by words of their authors, titles, venue, year or keywords",
+            args: vec![ArgSpec::option("bibliography", "FILE", "Bibliography to search (default: the configured library)").short('b')],
+            positionals: vec![PositionalSpec::required("query", "Words the entries must contain, the last one as a prefix").multiple()],
+        },
+        CommandSpec {
+            name: "stats",
+            about: "Count entries by type, DOIs, URLs, completeness and validation issues, optionally keeping a history",
+            args: vec![
+                ArgSpec::option("history", "FILE", "Append the counts to FILE, one JSON object per line, and print them all"),
+                ArgSpec { choices: &["csv", "json"], ..ArgSpec::option("format", "FORMAT", "Print the counts as CSV or JSON instead of a summary") },
+            ],
+            positionals: vec![PositionalSpec::optional("input", "Bibliography to read, `-` for standard input (default: the configured library)")],
+        },
+        CommandSpec {
+            name: "styles",
+            about: "Download CSL styles by name into the style cache, or list the cached ones",
+            args: vec![ArgSpec::flag("update", "Download the styles again even if they are cached")],
+            positionals: vec![PositionalSpec::optional("name", "Style names such as `apa` (default: list the cached styles)").multiple()],
+        },
+        CommandSpec {
+            name: "sync",
+            about: "Commit local .bib changes per entry and pull from (or push to) the git remote (`sync` feature)",
+            args: vec![
+                ArgSpec::option("remote", "URL", "Clone from URL first if the directory is not a git working copy"),
+                ArgSpec::flag("push", "Push the new commits"),
+                ArgSpec::flag("format", "Format changed files as `fmt` does before committing them"),
+            ],
+            positionals: vec![PositionalSpec::optional("dir", "Working copy (default: current directory)")],
+        },
+        CommandSpec {
+            name: "transform",
+            about: "Rewrite a bibliography with named transforms, or list them",
+            args: vec![
+                ArgSpec::option("apply", "NAMES", "Comma-separated transforms to run in order").short('t'),
+                ArgSpec::option("script", "FILE", "Also run a Rhai field-manipulation script on every entry (`script` feature)"),
+                ArgSpec::option("plugin", "FILE", "Also run the transform of a WebAssembly plugin on every entry; repeatable (`plugin` feature)"),
+                ArgSpec::option("set", "FIELD=TEMPLATE", "Set a field from a template such as `Imported from {source}`; repeatable"),
+                ArgSpec::option("var", "NAME=VALUE", "Variable for --set templates, used before fields; repeatable"),
+                ArgSpec { choices: &["skip", "empty", "fail"], ..ArgSpec::option("missing", "POLICY", "When a template's field is missing: skip the field (default), fill in nothing, or fail the entry") },
+                ArgSpec::flag("keep-existing", "Leave fields that already have a value alone with --set"),
+                ArgSpec::flag("list", "List the available transforms"),
+                ArgSpec::flag("in-place", "Overwrite the input file and print a summary instead").short('i'),
+            ],
+            positionals: vec![PositionalSpec::optional("input", "Bibliography to read, `-` for standard input")],
+        },
+        CommandSpec {
+            name: "usage",
+            about: "Count citations of each entry across LaTeX and Markdown documents",
+            args: vec![
+                ArgSpec::option("library", "FILE", "Bibliography to check for uncited entries (default: the configured library)").short('l'),
+                ArgSpec::flag("orphans", "Only list library entries that are never cited"),
+            ],
+            positionals: vec![PositionalSpec::required("document", "Documents to scan, `-` for standard input").multiple()],
+        },
+        CommandSpec {
+            name: "watch",
+            about: "Watch a bibliography and emit an event for every added, changed or removed entry",
+            args: vec![
+                ArgSpec::option("webhook", "URL", "POST events as JSON to URL instead of printing them"),
+                ArgSpec::option("interval", "SECONDS", "How often to check the file (default: 2)"),
+            ],
+            positionals: vec![PositionalSpec::required("input", "Bibliography to watch")],
+        },
+        CommandSpec {
+            name: "completions",
+            about: "Print the completion script for a shell",
+            args: vec![],
+            positionals: vec![PositionalSpec::required("shell", "Target shell").choices(completions::SHELLS)],
+        },
+    ]
+}
+
+fn run_completions(m: &Matches) -> Result<Outcome, CliError> {
+    let shell = m.positional(0).unwrap_or_default();
+    let script = completions::generate(shell, PROGRAM, &commands())
+        .ok_or_else(|| CliError::usage(&format!("unsupported shell `{}`", shell)))?;
+    let json = JsonValue::object(vec![
+        ("shell", JsonValue::str(shell)),
+        ("script", JsonValue::str(&script)),
+    ]);
+    Ok(Outcome::new(script, json))
+}
+
+pub fn run(m: &Matches) -> Result<Outcome, CliError> {
+    match m.command.as_str() {
+        "types" => types::run(m),
+        "add" => add::run(m),
+        "compare" => compare::run(m),
+        "convert" => convert::run(m),
+        "edit" => edit::run(m),
+        "extract" => extract::run(m),
+        "fmt" => fmt::run(m),
+        "init" => init::run(m),
+        "links" => links::run(m),
+        "lint" => lint::run(m),
+        "pandoc" => pandoc::run(m),
+        "publist" => publist::run(m),
+        "search" => search::run(m),
+        "stats" => stats::run(m),
+        "styles" => styles::run(m),
+        "sync" => sync::run(m),
+        "transform" => transform::run(m),
+        "usage" => usage::run(m),
+        "watch" => watch::run(m),
+        "completions" => run_completions(m),
+        other => Err(CliError::usage(&format!("unknown command `{}`", other))),
+    }
+}
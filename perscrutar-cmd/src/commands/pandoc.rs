@@ -0,0 +1,39 @@
+use perscrutarlib::json::parse;
+use perscrutarlib::pandoc::{filter, meta_string, CiteStyle, FilterOptions};
+use perscrutarlib::publist::Template;
+use crate::cli::{CliError, Matches};
+use crate::commands::{Outcome, PROGRAM};
+use crate::io;
+
+/**
+Runs as a pandoc JSON filter: the document comes on standard input and goes
+back on standard output. The output format pandoc passes as the first
+argument is accepted and ignored.
+*/
+pub fn run(m: &Matches) -> Result<Outcome, CliError> {
+    let input = io::read_input(io::STDIO)?;
+    let mut doc = parse(&input).map_err(|e| CliError::failure(&format!("<stdin>: {}", e)))?;
+
+    let config = io::load_config()?;
+    let library = match m.value("bibliography") {
+        Some(path) => path.to_string(),
+        None => meta_string(&doc, "bibliography")
+            .or(config.library().map_err(|e| CliError::failure(&e.to_string()))?.map(String::from))
+            .ok_or_else(|| CliError::usage("no bibliography: pass --bibliography or set it in the document metadata"))?,
+    };
+    let entries = io::load_entries(&library)?;
+
+    let mut options = FilterOptions {
+        style: CiteStyle::from_name(m.value("style").unwrap_or("author-year")).expect("choices are checked by the parser"),
+        title: m.value("title").map(String::from),
+        ..FilterOptions::default()
+    };
+    if let Some(path) = m.value("template") {
+        options.template = Template::new(io::read_input(path)?.trim_end_matches('\n'));
+    }
+    let report = filter(&mut doc, &entries, &options).map_err(|e| CliError::failure(&format!("<stdin>: {}", e)))?;
+    for key in report.missing.iter() {
+        eprintln!("{}: citation `{}` not found in {}", PROGRAM, key, library);
+    }
+    Ok(Outcome::new(format!("{}\n", doc), doc))
+}
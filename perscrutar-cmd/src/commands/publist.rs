@@ -0,0 +1,49 @@
+use perscrutarlib::bibtex::data::Entry;
+use perscrutarlib::json::JsonValue;
+use perscrutarlib::publist::{render, AuthorFilter, PubFormat, PublistOptions, Template};
+use crate::cli::{CliError, Matches};
+use crate::commands::Outcome;
+use crate::io;
+
+/**
+Render a publication list. Templates cannot show private fields unless
+`--include-private` is given.
+*/
+pub fn run(m: &Matches) -> Result<Outcome, CliError> {
+    let config = io::load_config()?;
+    let entries = io::load_entries(m.positional(0).unwrap_or(io::STDIO))?;
+    let format = PubFormat::from_name(m.value("format").unwrap_or("markdown")).expect("choices are checked by the parser");
+    let template = match m.value("template") {
+        Some(path) => Some(Template::new(io::read_input(path)?.trim_end_matches('\n'))),
+        None => None,
+    };
+    let options = PublistOptions {
+        format,
+        filter: AuthorFilter {
+            name: m.value("author").map(String::from),
+            orcid: m.value("orcid").map(String::from),
+        },
+        template,
+        title: m.value("title").map(String::from),
+        policy: io::field_policy(m, &config)?,
+    };
+    let text = match m.value("page-template") {
+        Some(path) => render_page(path, &entries, &options)?,
+        None => render(&entries, &options),
+    };
+    let json = JsonValue::object(vec![("document", JsonValue::str(&text))]);
+    Ok(Outcome::new(text, json))
+}
+
+#[cfg(feature = "templates")]
+fn render_page(path: &str, entries: &[Entry], options: &PublistOptions) -> Result<String, CliError> {
+    use perscrutarlib::publist::PageTemplate;
+
+    let failure = |e: perscrutarlib::publist::TemplateError| CliError::failure(&format!("{}: {}", io::display_name(path), e));
+    PageTemplate::new(&io::read_input(path)?).map_err(failure)?.render(entries, options).map_err(failure)
+}
+
+#[cfg(not(feature = "templates"))]
+fn render_page(_path: &str, _entries: &[Entry], _options: &PublistOptions) -> Result<String, CliError> {
+    Err(CliError::usage("--page-template needs a build with the `templates` feature"))
+}
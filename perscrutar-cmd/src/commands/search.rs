@@ -0,0 +1,35 @@
+use perscrutarlib::json::JsonValue;
+use perscrutarlib::search::SearchIndex;
+use crate::cli::{CliError, Matches};
+use crate::commands::Outcome;
+use crate::io;
+
+/**
+Print the key and title of the entries matching the query words; exits
+with 1 if there are none, like grep.
+*/
+pub fn run(m: &Matches) -> Result<Outcome, CliError> {
+    let config = io::load_config()?;
+    let input = match m.value("bibliography") {
+        Some(path) => path,
+        None => config.library().map_err(|e| CliError::failure(&e.to_string()))?.unwrap_or(io::STDIO),
+    };
+    let query = m.positionals().join(" ");
+    let entries = io::load_entries(input)?;
+    let index = SearchIndex::build(&entries);
+
+    let mut text = String::new();
+    let mut list = Vec::new();
+    for key in index.search(&query) {
+        for entry in entries.iter().filter(|e| e.key() == key) {
+            let title = entry.get("title").unwrap_or_default();
+            text.push_str(&format!("{}: {}\n", key, title));
+            list.push(JsonValue::object(vec![
+                ("key", JsonValue::str(key)),
+                ("title", JsonValue::str(title)),
+            ]));
+        }
+    }
+    let code = if list.is_empty() { 1 } else { 0 };
+    Ok(Outcome { code, ..Outcome::new(text, JsonValue::Array(list)) })
+}
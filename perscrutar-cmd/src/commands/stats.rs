@@ -0,0 +1,64 @@
+use std::io::Write;
+use std::time::SystemTime;
+use perscrutarlib::analytics::{parse_history, to_csv, to_json, Statistics};
+use perscrutarlib::bibtex::bibliography::Bibliography;
+use perscrutarlib::validate::RuleSet;
+use crate::cli::{CliError, Matches};
+use crate::commands::Outcome;
+use crate::io;
+
+/** The measurements in `path`, none if it does not exist yet. */
+fn read_history(path: &str) -> Result<Vec<Statistics>, CliError> {
+    match std::fs::read_to_string(path) {
+        Ok(text) => parse_history(&text).map_err(|e| CliError::failure(&format!("{}: {}", path, e))),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(CliError::failure(&format!("cannot read {}: {}", path, e))),
+    }
+}
+
+fn append_history(path: &str, stats: &Statistics) -> Result<(), CliError> {
+    std::fs::OpenOptions::new().create(true).append(true).open(path)
+        .and_then(|mut file| file.write_all(stats.to_line().as_bytes()))
+        .map_err(|e| CliError::failure(&format!("cannot write {}: {}", path, e)))
+}
+
+fn summary(stats: &Statistics, previous: Option<&Statistics>) -> String {
+    let types: Vec<String> = stats.types.iter().map(|(name, n)| format!("{} {}", n, name)).collect();
+    let mut text = format!("{} entries: {}\n", stats.entries, types.join(", "));
+    if let Some(previous) = previous {
+        text.push_str(&format!("{:+} since {}\n", stats.entries as i64 - previous.entries as i64, previous.time));
+    }
+    text.push_str(&format!("{} with a DOI, {} with a URL\n", stats.with_doi, stats.with_url));
+    text.push_str(&format!("completeness {:.2}, {} validation issues\n", stats.completeness, stats.issues));
+    text
+}
+
+/**
+Measure a bibliography. With `--history` the measurement is appended to
+the history file and the whole series is printed; `--format` prints it as
+CSV or JSON instead of a summary of the latest measurement.
+*/
+pub fn run(m: &Matches) -> Result<Outcome, CliError> {
+    let config = io::load_config()?;
+    let input = match m.positional(0) {
+        Some(path) => path,
+        None => config.library().map_err(|e| CliError::failure(&e.to_string()))?.unwrap_or(io::STDIO),
+    };
+    let bibliography = Bibliography::from_entries(io::load_entries(input)?);
+    let current = Statistics::compute(&bibliography, &RuleSet::bibtex(), SystemTime::now());
+    let mut series = match m.value("history") {
+        Some(path) => {
+            let series = read_history(path)?;
+            append_history(path, &current)?;
+            series
+        }
+        None => Vec::new(),
+    };
+    series.push(current);
+    let text = match m.value("format") {
+        Some("csv") => to_csv(&series),
+        Some("json") => format!("{}\n", to_json(&series).to_pretty_string()),
+        _ => summary(&series[series.len() - 1], series.len().checked_sub(2).map(|i| &series[i])),
+    };
+    Ok(Outcome::new(text, to_json(&series)))
+}
@@ -0,0 +1,53 @@
+use perscrutarlib::json::JsonValue;
+use perscrutarlib::styles::{style_title, StyleError, StyleStore};
+use crate::cli::{CliError, Matches};
+use crate::commands::Outcome;
+
+fn failure(e: StyleError) -> CliError {
+    CliError::failure(&e.to_string())
+}
+
+#[cfg(feature = "net")]
+fn fetch(store: &StyleStore, name: &str, update: bool) -> Result<String, CliError> {
+    let client = perscrutarlib::net::CurlClient::default();
+    match update {
+        true => store.fetch(&client, name),
+        false => store.get(&client, name),
+    }.map_err(failure)
+}
+
+#[cfg(not(feature = "net"))]
+fn fetch(store: &StyleStore, name: &str, _update: bool) -> Result<String, CliError> {
+    match store.load(name) {
+        Err(StyleError::NotFound(_)) => Err(CliError::usage("downloading styles needs a build with the `net` feature")),
+        other => other.map_err(failure),
+    }
+}
+
+pub fn run(m: &Matches) -> Result<Outcome, CliError> {
+    let dir = StyleStore::default_dir()
+        .ok_or_else(|| CliError::failure("cannot find a cache directory; set XDG_CACHE_HOME or HOME"))?;
+    let store = StyleStore::new(dir);
+    let names: Vec<String> = match m.positionals().is_empty() {
+        true => store.installed(),
+        false => m.positionals().iter().map(|n| n.to_string()).collect(),
+    };
+
+    let mut text = String::new();
+    let mut list = Vec::new();
+    for name in names.iter() {
+        let xml = match m.positionals().is_empty() {
+            true => store.load(name).map_err(failure)?,
+            false => fetch(&store, name, m.flag("update"))?,
+        };
+        let title = style_title(&xml).unwrap_or("");
+        let path = store.path(name).map_err(failure)?;
+        text.push_str(&format!("{:<30} {}\n", name, title));
+        list.push(JsonValue::object(vec![
+            ("name", JsonValue::str(name)),
+            ("title", JsonValue::str(title)),
+            ("path", JsonValue::str(&path.to_string_lossy())),
+        ]));
+    }
+    Ok(Outcome::new(text, JsonValue::Array(list)))
+}
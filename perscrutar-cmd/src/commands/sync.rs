@@ -0,0 +1,53 @@
+use crate::cli::{CliError, Matches};
+use crate::commands::Outcome;
+
+#[cfg(feature = "sync")]
+pub fn run(m: &Matches) -> Result<Outcome, CliError> {
+    use std::path::Path;
+    use perscrutarlib::bibtex::format::format;
+    use perscrutarlib::json::JsonValue;
+    use perscrutarlib::sync::{sync, Formatter, Git, SyncOptions};
+    use crate::io;
+
+    let dir = Path::new(m.positional(0).unwrap_or("."));
+    let failed = |e: perscrutarlib::sync::SyncError| CliError::failure(&e.to_string());
+    let git = match m.value("remote") {
+        Some(remote) if !dir.join(".git").exists() => Git::clone_from(remote, dir).map_err(failed)?,
+        _ => Git::open(dir),
+    };
+    let formatter = if m.flag("format") {
+        let options = io::load_config_in(dir)?.format_options().map_err(|e| CliError::failure(&e.to_string()))?;
+        Some(move |text: &str| format(text, &options).map_err(|e| e.to_string()))
+    } else {
+        None
+    };
+    let options = SyncOptions { formatter: formatter.as_ref().map(|f| f as &Formatter), push: m.flag("push") };
+    let report = sync(&git, &options).map_err(failed)?;
+
+    let mut text = String::new();
+    for (file, changes) in report.commits.iter() {
+        text.push_str(&format!("{}:\n", file));
+        for c in changes {
+            text.push_str(&format!("  {}\n", c));
+        }
+    }
+    if report.commits.is_empty() {
+        text.push_str("no local changes\n");
+    }
+    if report.pushed {
+        text.push_str("pushed\n");
+    }
+    let json = JsonValue::object(vec![
+        ("commits", JsonValue::Array(report.commits.iter().map(|(file, changes)| JsonValue::object(vec![
+            ("file", JsonValue::str(file)),
+            ("changes", JsonValue::Array(changes.iter().map(|c| JsonValue::str(&c.to_string())).collect())),
+        ])).collect())),
+        ("pushed", JsonValue::Boolean(report.pushed)),
+    ]);
+    Ok(Outcome::new(text, json))
+}
+
+#[cfg(not(feature = "sync"))]
+pub fn run(_m: &Matches) -> Result<Outcome, CliError> {
+    Err(CliError::usage("sync needs a build with the `sync` feature"))
+}
@@ -0,0 +1,230 @@
+use perscrutarlib::batch::{BatchEdit, MissingField, ValueTemplate};
+use perscrutarlib::bibtex::bibliography::Bibliography;
+use perscrutarlib::bibtex::data::Entry;
+use perscrutarlib::bibtex::error::Span;
+use perscrutarlib::bibtex::parser::{parse_document, Macros, ParseOptions};
+use perscrutarlib::bibtex::format::update_entry;
+use perscrutarlib::bibtex::writer::{write_entries, WriteOptions};
+use perscrutarlib::config::Config;
+use perscrutarlib::formats::Format;
+use perscrutarlib::json::JsonValue;
+use perscrutarlib::transform::{minimize_transform, month_transform, Transform, TransformRegistry};
+use crate::cli::{CliError, Matches};
+use crate::commands::Outcome;
+use crate::io;
+
+fn registry(config: &Config) -> Result<TransformRegistry, CliError> {
+    let mut registry = TransformRegistry::default();
+    registry.register(month_transform(config.month_languages().map_err(|e| CliError::failure(&e.to_string()))?));
+    for profile in config.minimize_profiles().map_err(|e| CliError::failure(&e.to_string()))? {
+        registry.register(minimize_transform(profile));
+    }
+    #[cfg(feature = "net")]
+    registry.register(Box::new(perscrutarlib::archive::ArchiveTransform::new(Box::new(perscrutarlib::net::CurlClient::default()))));
+    Ok(registry)
+}
+
+fn list(registry: &TransformRegistry) -> Outcome {
+    let width = registry.iter().map(|t| t.name().len()).max().unwrap_or(0);
+    let text = registry.iter().map(|t| format!("{:width$}  {}\n", t.name(), t.description(), width = width)).collect();
+    let json = JsonValue::Array(registry.iter().map(|t| JsonValue::object(vec![
+        ("name", JsonValue::str(t.name())),
+        ("description", JsonValue::str(t.description())),
+    ])).collect());
+    Outcome::new(text, json)
+}
+
+#[cfg(feature = "script")]
+fn script(path: &str) -> Result<Box<dyn Transform>, CliError> {
+    use perscrutarlib::script::{Script, ScriptTransform};
+
+    let script = Script::parse(&io::read_input(path)?)
+        .map_err(|e| CliError::failure(&format!("{}: {}", io::display_name(path), e)))?;
+    let name = std::path::Path::new(path).file_stem().and_then(|s| s.to_str()).unwrap_or("script");
+    Ok(Box::new(ScriptTransform { name: String::from(name), script }))
+}
+
+#[cfg(not(feature = "script"))]
+fn script(_path: &str) -> Result<Box<dyn Transform>, CliError> {
+    Err(CliError::usage("--script needs a build with the `script` feature"))
+}
+
+#[cfg(feature = "plugin")]
+fn plugin(path: &str) -> Result<Box<dyn Transform>, CliError> {
+    use perscrutarlib::plugin::{Plugin, PluginTransform};
+
+    let plugin = Plugin::load(path).map_err(|e| CliError::failure(&e.to_string()))?;
+    if !plugin.has_transform() {
+        return Err(CliError::usage(&format!("{} has no `transform`", path)));
+    }
+    Ok(Box::new(PluginTransform { plugin }))
+}
+
+#[cfg(not(feature = "plugin"))]
+fn plugin(_path: &str) -> Result<Box<dyn Transform>, CliError> {
+    Err(CliError::usage("--plugin needs a build with the `plugin` feature"))
+}
+
+/** `arg`, the value of `--option`, split at its `=`. */
+fn split<'a>(option: &str, arg: &'a str) -> Result<(&'a str, &'a str), CliError> {
+    arg.split_once('=').map(|(name, value)| (name.trim(), value))
+        .filter(|(name, _)| !name.is_empty())
+        .ok_or_else(|| CliError::usage(&format!("invalid value `{}` for --{}, expected NAME=VALUE", arg, option)))
+}
+
+/** The `--set` assignments as a batch edit, if there are any. */
+fn batch_edit(m: &Matches) -> Result<Option<BatchEdit>, CliError> {
+    if m.values("set").is_empty() {
+        return Ok(None);
+    }
+    let mut edit = BatchEdit::new();
+    for arg in m.values("set") {
+        let (field, template) = split("set", arg)?;
+        let template = ValueTemplate::parse(template)
+            .map_err(|e| CliError::usage(&format!("invalid template for `{}`: {}", field, e)))?;
+        edit = edit.set(field, template);
+    }
+    for arg in m.values("var") {
+        let (name, value) = split("var", arg)?;
+        edit = edit.variable(name, value);
+    }
+    edit.missing = m.value("missing").and_then(MissingField::parse).unwrap_or_default();
+    edit.keep_existing = m.flag("keep-existing");
+    Ok(Some(edit))
+}
+
+/** The text of a BibTeX input and the span of each of its entries. */
+type Source = (String, Vec<Span>);
+
+/**
+The entries of `input` with, for a BibTeX file, its text and preambles and
+the span of each entry, so that what the transforms leave alone can be
+written back as it was.
+*/
+fn load(input: &str) -> Result<(Bibliography, Option<Source>), CliError> {
+    if input.starts_with("http://") || input.starts_with("https://") {
+        return Ok((Bibliography::from_entries(io::load_entries(input)?), None));
+    }
+    let text = io::read_input(input)?;
+    if Format::resolve(Some(input), &text) != Some(Format::BibTeX) {
+        return Ok((Bibliography::from_entries(io::parse_as(input, &text, None)?), None));
+    }
+    let document = parse_document(&text, &mut Macros::new(), ParseOptions::standard())
+        .map_err(|e| CliError::failure(&format!("{}: {}", io::display_name(input), e)))?;
+    let (entries, spans): (Vec<Entry>, Vec<Span>) = document.entries.into_iter().unzip();
+    let mut bibliography = Bibliography::from_entries(entries);
+    for preamble in document.preambles.iter() {
+        bibliography.add_preamble(preamble);
+    }
+    Ok((bibliography, Some((text, spans))))
+}
+
+/**
+`text` with each entry at `spans` that differs from its counterpart in
+`entries` written anew, keeping the fields the transforms left alone as
+they were written, and everything else kept.
+*/
+fn splice(text: &str, spans: &[Span], originals: &[Entry], entries: &[Entry]) -> Result<String, CliError> {
+    let mut out = String::from(text);
+    for ((span, original), entry) in spans.iter().zip(originals).zip(entries).rev() {
+        if !entry.is_identical(original) {
+            let written = update_entry(&text[span.start..span.end], original, entry, &WriteOptions::default())
+                .map_err(|e| CliError::failure(&e.to_string()))?;
+            out.replace_range(span.start..span.end, &written);
+        }
+    }
+    Ok(out)
+}
+
+pub fn run(m: &Matches) -> Result<Outcome, CliError> {
+    transform(m, &io::load_config()?)
+}
+
+fn transform(m: &Matches, config: &Config) -> Result<Outcome, CliError> {
+    let registry = registry(config)?;
+    if m.flag("list") || ["apply", "plugin", "script", "set"].iter().all(|o| m.value(o).is_none()) {
+        return Ok(list(&registry));
+    }
+    let names: Vec<&str> = m.value("apply").unwrap_or_default().split(',').map(str::trim).filter(|n| !n.is_empty()).collect();
+    let mut transforms = names.iter().map(|n| registry.get(n)
+        .ok_or_else(|| CliError::usage(&format!("unknown transform `{}` (see `transform --list`)", n))))
+        .collect::<Result<Vec<&dyn Transform>, CliError>>()?;
+    let plugins = m.values("plugin").into_iter().map(plugin).collect::<Result<Vec<_>, CliError>>()?;
+    transforms.extend(plugins.iter().map(|p| p.as_ref()));
+    let user = m.value("script").map(script).transpose()?;
+    transforms.extend(user.as_deref());
+    let edit = batch_edit(m)?;
+    if let Some(edit) = edit.as_ref() {
+        transforms.push(edit);
+    }
+
+    let input = m.positional(0).unwrap_or(io::STDIO);
+    let in_place = m.flag("in-place");
+    if in_place && input == io::STDIO {
+        return Err(CliError::usage("--in-place needs an input file"));
+    }
+    if in_place && (input.starts_with("http://") || input.starts_with("https://")) {
+        return Err(CliError::usage("--in-place cannot write back to a URL"));
+    }
+    let (mut bibliography, source) = load(input)?;
+    let originals = bibliography.entries().to_vec();
+    for key in config.pinned_keys().map_err(|e| CliError::failure(&e.to_string()))? {
+        bibliography.pin(key);
+    }
+    let mut summary = String::new();
+    let mut reports = Vec::new();
+    for t in transforms {
+        let mut report = t.apply(&mut bibliography);
+        report.messages.extend(bibliography.take_refused());
+        summary.push_str(&format!("{}: {} entries changed\n", t.name(), report.changed.len()));
+        for message in report.messages.iter() {
+            summary.push_str(&format!("{}: {}\n", t.name(), message));
+        }
+        reports.push(JsonValue::object(vec![("transform", JsonValue::str(t.name())), ("report", report.to_json())]));
+    }
+
+    let document = match source {
+        Some((text, spans)) => {
+            if bibliography.len() != spans.len() {
+                return Err(CliError::failure("the transforms added or removed entries"));
+            }
+            splice(&text, &spans, &originals, bibliography.entries())?
+        }
+        None => write_entries(bibliography.entries(), &WriteOptions::default()),
+    };
+    let json = JsonValue::object(vec![("reports", JsonValue::Array(reports))]);
+    if in_place {
+        io::write_output(input, &document)?;
+        return Ok(Outcome::new(summary, json));
+    }
+    Ok(Outcome::new(document, json))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::cli::parse;
+    use crate::commands::commands;
+
+    #[test]
+    fn test_in_place() {
+        let path = std::env::temp_dir().join(format!("perscrutar-transform-{}.bib", std::process::id()));
+        let text = "% Group library\n@string{jacm = {Journal of the ACM}}\n@preamble{\"\\newcommand{\\noop}[1]{}\"}\n@comment{kept as is}\n\n@article{knuth84,\n  journal = jacm # { Letters},\n  publisher = jacm,\n  month = {May},\n  title = {Literate Programming}\n}\n\n@misc{other,   title = \"Untouched\", journal = jacm}\n";
+        std::fs::write(&path, text).unwrap();
+        let args: Vec<String> = ["transform", "-t", "normalize-month", "-i", path.to_str().unwrap()].iter().map(|a| a.to_string()).collect();
+        let outcome = transform(&parse(&commands(), &args).unwrap(), &Config::default()).unwrap();
+        assert!(outcome.text.contains("normalize-month: 1 entries changed"));
+        let written = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let (before, after) = text.split_once("@article").unwrap();
+        assert!(written.starts_with(before), "{}", written);
+        assert!(written.ends_with(&after[after.find("@misc").unwrap()..]), "{}", written);
+        assert!(written.contains("month = {5}"), "{}", written);
+        assert!(written.contains("journal = jacm # { Letters}"), "{}", written);
+        assert!(written.contains("publisher = jacm,"), "{}", written);
+
+        let args: Vec<String> = ["transform", "-t", "normalize-month", "-i", "https://example.org/refs.bib"].iter().map(|a| a.to_string()).collect();
+        assert!(transform(&parse(&commands(), &args).unwrap(), &Config::default()).is_err());
+    }
+}
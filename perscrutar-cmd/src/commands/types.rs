@@ -0,0 +1,28 @@
+use perscrutarlib::bibtex::types::TypeRegistry;
+use perscrutarlib::json::JsonValue;
+use crate::cli::{CliError, Matches};
+use crate::commands::Outcome;
+
+pub fn run(m: &Matches) -> Result<Outcome, CliError> {
+    let registry = TypeRegistry::default();
+    let names: Vec<String> = if m.positionals().is_empty() {
+        registry.names().into_iter().map(String::from).collect()
+    } else {
+        m.positionals().iter().map(|n| registry.resolve(n).name().to_string()).collect()
+    };
+
+    let mut text = String::new();
+    let mut list = Vec::new();
+    for name in names.iter() {
+        let schema = registry.schema(&registry.resolve(name))
+            .ok_or_else(|| CliError::failure(&format!("unknown entry type `{}`", name)))?;
+        let required: Vec<String> = schema.required().iter().map(|r| r.fields().join("|")).collect();
+        text.push_str(&format!("@{:<16} {}\n", name, required.join(", ")));
+        list.push(JsonValue::object(vec![
+            ("type", JsonValue::str(name)),
+            ("required", JsonValue::Array(required.iter().map(|r| JsonValue::str(r)).collect())),
+            ("optional", JsonValue::Array(schema.optional_fields().iter().map(|f| JsonValue::str(f)).collect())),
+        ]));
+    }
+    Ok(Outcome::new(text, JsonValue::Array(list)))
+}
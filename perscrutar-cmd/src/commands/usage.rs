@@ -0,0 +1,58 @@
+use perscrutarlib::citations::{orphans, scan, usage};
+use perscrutarlib::json::JsonValue;
+use crate::cli::{CliError, Matches};
+use crate::commands::Outcome;
+use crate::io;
+
+pub fn run(m: &Matches) -> Result<Outcome, CliError> {
+    let mut citations = Vec::new();
+    for doc in m.positionals() {
+        citations.extend(scan(io::display_name(doc), &io::read_input(doc)?));
+    }
+    let usage = usage(&citations);
+
+    let config = io::load_config()?;
+    let library = match m.value("library") {
+        Some(path) => Some(path),
+        None => config.library().map_err(|e| CliError::failure(&e.to_string()))?,
+    };
+    let entries = match library {
+        Some(path) => Some(io::load_entries(path)?),
+        None => None,
+    };
+    let orphans = entries.as_deref().map(|e| orphans(e, &usage)).unwrap_or_default();
+    let missing: Vec<&str> = match entries.as_deref() {
+        Some(e) => usage.iter().map(|u| u.key.as_str()).filter(|k| !e.iter().any(|x| x.is_known_as(k))).collect(),
+        None => vec![],
+    };
+
+    let mut text = String::new();
+    if !m.flag("orphans") {
+        for u in usage.iter() {
+            text.push_str(&format!("{:>5}  {:<30} {}:{}:{}\n", u.count, u.key, u.first.file, u.first.line, u.first.column));
+        }
+        for key in missing.iter() {
+            text.push_str(&format!("missing from the library: {}\n", key));
+        }
+    }
+    for key in orphans.iter() {
+        text.push_str(&format!("not cited: {}\n", key));
+    }
+
+    let strs = |v: &[&str]| JsonValue::Array(v.iter().map(|k| JsonValue::str(k)).collect());
+    let json = JsonValue::object(vec![
+        ("usage", JsonValue::Array(usage.iter().map(|u| JsonValue::object(vec![
+            ("key", JsonValue::str(&u.key)),
+            ("count", JsonValue::Num(u.count as f64)),
+            ("first", JsonValue::object(vec![
+                ("file", JsonValue::str(&u.first.file)),
+                ("line", JsonValue::Num(u.first.line as f64)),
+                ("column", JsonValue::Num(u.first.column as f64)),
+            ])),
+            ("documents", JsonValue::Array(u.documents.iter().map(|d| JsonValue::str(d)).collect())),
+        ])).collect())),
+        ("missing", strs(&missing)),
+        ("orphans", strs(&orphans)),
+    ]);
+    Ok(Outcome::new(text, json))
+}
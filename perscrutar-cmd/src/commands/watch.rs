@@ -0,0 +1,75 @@
+use std::time::{Duration, SystemTime};
+use perscrutarlib::bibtex::data::Entry;
+use perscrutarlib::events::{diff, EventSink, JsonLines};
+use crate::cli::{CliError, Matches};
+use crate::commands::Outcome;
+use crate::io;
+
+fn modified(path: &str) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+#[cfg(feature = "net")]
+fn webhook_sink(url: &str) -> Result<Box<dyn EventSink>, CliError> {
+    use perscrutarlib::events::Webhook;
+    use perscrutarlib::net::CurlClient;
+    Ok(Box::new(Webhook { client: CurlClient::default(), url: String::from(url) }))
+}
+
+#[cfg(not(feature = "net"))]
+fn webhook_sink(_url: &str) -> Result<Box<dyn EventSink>, CliError> {
+    Err(CliError::usage("--webhook needs a build with the `net` feature"))
+}
+
+/**
+Poll `input` and report every change to its entries as events, until
+interrupted.
+*/
+pub fn run(m: &Matches) -> Result<Outcome, CliError> {
+    let input = m.positional(0).unwrap_or_default();
+    if input == io::STDIO {
+        return Err(CliError::usage("cannot watch standard input"));
+    }
+    let interval = match m.value("interval") {
+        Some(s) => s.parse::<f64>().ok().filter(|s| *s > 0.0).and_then(|s| Duration::try_from_secs_f64(s).ok())
+            .ok_or_else(|| CliError::usage(&format!("invalid value `{}` for --interval, expected seconds", s)))?,
+        None => Duration::from_secs(2),
+    };
+    let mut sink: Box<dyn EventSink> = match m.value("webhook") {
+        Some(url) => webhook_sink(url)?,
+        None => Box::new(JsonLines(std::io::stdout())),
+    };
+
+    let mut entries: Vec<Entry> = io::load_entries(input)?;
+    let mut stamp = modified(input);
+    loop {
+        std::thread::sleep(interval);
+        let now = modified(input);
+        if now == stamp {
+            continue;
+        }
+        stamp = now;
+        // a half-written file fails to parse; the next save will be picked up
+        let Ok(current) = io::load_entries(input) else { continue };
+        if let Err(e) = sink.send(&diff(&entries, &current)) {
+            eprintln!("{}: {}", crate::commands::PROGRAM, e);
+        }
+        entries = current;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::cli::parse;
+    use crate::commands::commands;
+
+    #[test]
+    fn test_interval() {
+        for interval in ["inf", "1e300", "NaN", "0", "-1"] {
+            let args: Vec<String> = ["watch", "lib.bib", "--interval", interval].iter().map(|a| a.to_string()).collect();
+            assert_eq!(run(&parse(&commands(), &args).unwrap()).err().map(|e| e.code), Some(2), "{}", interval);
+        }
+    }
+}
@@ -0,0 +1,60 @@
+/*!
+
+Shell completion scripts generated from the command table by clap_complete.
+
+*/
+
+use clap_complete::Shell;
+use crate::cli::{command, CommandSpec};
+
+pub const SHELLS: &[&str] = &["bash", "zsh", "fish"];
+
+pub fn generate(shell: &str, program: &'static str, commands: &[CommandSpec]) -> Option<String> {
+    let shell = match shell {
+        "bash" => Shell::Bash,
+        "zsh" => Shell::Zsh,
+        "fish" => Shell::Fish,
+        _ => return None,
+    };
+    let mut out = Vec::new();
+    clap_complete::generate(shell, &mut command(program, commands), program, &mut out);
+    String::from_utf8(out).ok()
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::cli::PositionalSpec;
+
+    fn spec() -> Vec<CommandSpec> {
+        vec![CommandSpec {
+            name: "completions",
+            about: "Print a shell's completion script",
+            args: vec![],
+            positionals: vec![PositionalSpec::required("shell", "Shell").choices(SHELLS)],
+        }]
+    }
+
+    #[test]
+    fn test_scripts_mention_everything() {
+        for shell in SHELLS {
+            let s = generate(shell, "perscrutar", &spec()).unwrap();
+            assert!(s.contains("completions"), "{}", shell);
+            assert!(s.contains("output"), "{}", shell);
+            assert!(s.contains("json"), "{}", shell);
+        }
+        // clap_complete offers the choices of positionals to bash and zsh only
+        for shell in ["bash", "zsh"] {
+            assert!(generate(shell, "perscrutar", &spec()).unwrap().contains("bash zsh fish"), "{}", shell);
+        }
+        assert!(generate("tcsh", "perscrutar", &spec()).is_none());
+    }
+
+    #[test]
+    fn test_quoting() {
+        let fish = generate("fish", "perscrutar", &spec()).unwrap();
+        assert!(fish.contains("Print a shell\\'s completion script"), "{}", fish);
+        assert!(fish.contains("-s o -l output"), "{}", fish);
+    }
+}
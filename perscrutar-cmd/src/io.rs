@@ -0,0 +1,232 @@
+/*!
+
+Input and output plumbing shared by the subcommands.
+
+A path of `-` means standard input or standard output everywhere, so every
+subcommand can sit in a pipeline. Anything interactive (prompts, colour) is
+only used when the relevant stream is a terminal. Reading standard input
+from a terminal, as commands do when given no file and no library is
+configured, says so on standard error first rather than waiting silently.
+
+*/
+
+use std::io::{IsTerminal, Read, Write};
+use std::path::Path;
+use perscrutarlib::audit::Event;
+use perscrutarlib::bibtex::bibliography::Bibliography;
+use perscrutarlib::bibtex::data::Entry;
+use perscrutarlib::bibtex::parser::{parse_document, parse_with, Macros, ParseOptions};
+use perscrutarlib::bibtex::policy::FieldPolicy;
+use perscrutarlib::config::{Config, CONFIG_FILE};
+use perscrutarlib::csl::from_csl_json;
+use perscrutarlib::formats::Format;
+use perscrutarlib::metadata::MetadataStore;
+use perscrutarlib::ris::from_ris;
+use perscrutarlib::software::{from_cff, from_codemeta};
+use crate::cli::{CliError, Matches};
+use crate::commands::PROGRAM;
+
+pub const STDIO: &str = "-";
+
+pub fn read_input(path: &str) -> Result<String, CliError> {
+    if path == STDIO {
+        if std::io::stdin().is_terminal() {
+            eprintln!("{}: reading standard input, end it with Ctrl-D (or pass a file, or set `[library] path` in {})", PROGRAM, CONFIG_FILE);
+        }
+        let mut s = String::new();
+        std::io::stdin().read_to_string(&mut s)
+            .map_err(|e| CliError::failure(&format!("cannot read standard input: {}", e)))?;
+        Ok(s)
+    } else {
+        std::fs::read_to_string(path)
+            .map_err(|e| CliError::failure(&format!("cannot read {}: {}", path, e)))
+    }
+}
+
+pub fn write_output(path: &str, text: &str) -> Result<(), CliError> {
+    if path == STDIO {
+        let mut out = std::io::stdout().lock();
+        out.write_all(text.as_bytes()).and_then(|_| out.flush())
+            .map_err(|e| CliError::failure(&format!("cannot write standard output: {}", e)))
+    } else {
+        std::fs::write(path, text)
+            .map_err(|e| CliError::failure(&format!("cannot write {}: {}", path, e)))
+    }
+}
+
+/**
+Human-friendly name for error messages.
+*/
+pub fn display_name(path: &str) -> &str {
+    if path == STDIO { "<stdin>" } else { path }
+}
+
+pub fn stdout_is_terminal() -> bool {
+    std::io::stdout().is_terminal()
+}
+
+/** Programs that print the clipboard, tried in order. */
+const CLIPBOARD: [&[&str]; 4] = [
+    &["wl-paste", "--no-newline"],
+    &["xclip", "-selection", "clipboard", "-out"],
+    &["xsel", "--clipboard", "--output"],
+    &["pbpaste"],
+];
+
+/** The text on the clipboard, from the first clipboard tool that works. */
+pub fn read_clipboard() -> Result<String, CliError> {
+    for command in CLIPBOARD {
+        let output = std::process::Command::new(command[0]).args(&command[1..])
+            .stdin(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .output();
+        match output {
+            Ok(output) if output.status.success() => return Ok(String::from_utf8_lossy(&output.stdout).into_owned()),
+            _ => {}
+        }
+    }
+    Err(CliError::failure("cannot read the clipboard (tried wl-paste, xclip, xsel and pbpaste)"))
+}
+
+/**
+Ask a yes/no question on standard error, defaulting to yes. Unless
+someone is at a terminal, with standard output going to it and standard
+input coming from it, or if the input ends, the answer is no.
+*/
+pub fn confirm(question: &str) -> bool {
+    if !stdout_is_terminal() || !std::io::stdin().is_terminal() {
+        return false;
+    }
+    eprint!("{} [Y/n] ", question);
+    let mut answer = String::new();
+    if !matches!(std::io::stdin().read_line(&mut answer), Ok(n) if n > 0) {
+        return false;
+    }
+    let answer = answer.trim().to_lowercase();
+    answer.is_empty() || answer.starts_with('y')
+}
+
+/**
+Append `events` to the change log in the sidecar of `bibliography`. The
+change itself is already made, so a sidecar that cannot be updated is
+only warned about.
+*/
+pub fn log_changes(bibliography: &str, events: Vec<Event>) {
+    let path = MetadataStore::sidecar_path(bibliography);
+    let logged = MetadataStore::load(&path).and_then(|mut store| {
+        events.into_iter().for_each(|e| store.log(e));
+        store.save(&path)
+    });
+    if let Err(e) = logged {
+        eprintln!("{}: {}: change not logged: {}", PROGRAM, path.display(), e);
+    }
+}
+
+/**
+Read and parse the entries of an input, detecting its format from the
+extension or, for standard input, from the content. With the `net`
+feature, `http(s)://` URLs are fetched through the HTTP cache.
+*/
+pub fn load_entries(path: &str) -> Result<Vec<Entry>, CliError> {
+    load_entries_as(path, None)
+}
+
+/** `load_entries`, reading the input as `format` if one is given. */
+pub fn load_entries_as(path: &str, format: Option<Format>) -> Result<Vec<Entry>, CliError> {
+    load_bibliography_as(path, format).map(|(bibliography, _)| bibliography.into_entries())
+}
+
+/**
+`load_entries_as`, keeping what a BibTeX output of the entries needs: for
+BibTeX input, the `@preamble`s on the bibliography and the `@string`
+definitions in the macros; other formats have neither.
+*/
+pub fn load_bibliography_as(path: &str, format: Option<Format>) -> Result<(Bibliography, Macros), CliError> {
+    #[cfg(feature = "net")]
+    if path.starts_with("https://") || path.starts_with("http://") {
+        use perscrutarlib::net::{CurlClient, HttpCache};
+        let cache = HttpCache::default_dir().map(HttpCache::new);
+        return Bibliography::load_url(&CurlClient::default(), path, cache.as_ref())
+            .map(|bibliography| (bibliography, Macros::new()))
+            .map_err(|e| CliError::failure(&format!("{}: {}", path, e)));
+    }
+    let content = read_input(path)?;
+    if format.or_else(|| Format::resolve(Some(path), &content)) != Some(Format::BibTeX) {
+        return parse_as(path, &content, format).map(|entries| (Bibliography::from_entries(entries), Macros::new()));
+    }
+    let mut macros = Macros::new();
+    let document = parse_document(&content, &mut macros, ParseOptions::standard())
+        .map_err(|e| CliError::failure(&format!("{}: {}", display_name(path), e)))?;
+    let mut bibliography = Bibliography::from_entries(document.entries.into_iter().map(|(entry, _)| entry).collect());
+    for preamble in document.preambles.iter() {
+        bibliography.add_preamble(preamble);
+    }
+    Ok((bibliography, macros))
+}
+
+/**
+Parse `content`, read from `path`, as `format` or else as the format
+detected from the extension or the content.
+*/
+pub fn parse_as(path: &str, content: &str, format: Option<Format>) -> Result<Vec<Entry>, CliError> {
+    match format.or_else(|| Format::resolve(Some(path), content)) {
+        Some(Format::BibTeX) => parse_with(content, &mut Macros::new(), ParseOptions::standard())
+            .map_err(|e| CliError::failure(&format!("{}: {}", display_name(path), e))),
+        Some(Format::CslJson) => from_csl_json(content)
+            .map_err(|e| CliError::failure(&format!("{}: {}", display_name(path), e))),
+        Some(Format::Ris) => from_ris(content)
+            .map_err(|e| CliError::failure(&format!("{}: {}", display_name(path), e))),
+        Some(Format::Cff) => from_cff(content).map(|e| vec![e])
+            .map_err(|e| CliError::failure(&format!("{}: {}", display_name(path), e))),
+        Some(Format::CodeMeta) => from_codemeta(content).map(|e| vec![e])
+            .map_err(|e| CliError::failure(&format!("{}: {}", display_name(path), e))),
+        None => Err(CliError::failure(&format!("{}: cannot determine the input format", display_name(path)))),
+    }
+}
+
+/**
+The project configuration in the current directory, or the defaults if
+there is none.
+*/
+pub fn load_config() -> Result<Config, CliError> {
+    load_config_in(Path::new("."))
+}
+
+/** `load_config` for the project in `dir`. */
+pub fn load_config_in(dir: &Path) -> Result<Config, CliError> {
+    let path = dir.join(CONFIG_FILE);
+    let name = if dir == Path::new(".") { String::from(CONFIG_FILE) } else { path.display().to_string() };
+    match std::fs::read_to_string(&path) {
+        Ok(s) => Config::parse(&s).map_err(|e| CliError::failure(&format!("{}: {}", name, e))),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Config::default()),
+        Err(e) => Err(CliError::failure(&format!("cannot read {}: {}", name, e))),
+    }
+}
+
+/**
+The fields an export leaves out: those `[fields] private` names, or none
+with `--include-private`.
+*/
+pub fn field_policy(m: &Matches, config: &Config) -> Result<FieldPolicy, CliError> {
+    if m.flag("include-private") {
+        return Ok(FieldPolicy::new());
+    }
+    config.field_policy().map_err(|e| CliError::failure(&format!("{}: {}", CONFIG_FILE, e)))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_parse_as() {
+        let content = "@article{oneil, author = {O'Neil, Pat}, title = {Is it (really) fast? Yes!}}\n\
+            % a comment between entries\n@book{sharp, title = \"Programming C#\"}\n";
+        let entries = parse_as("refs.bib", content, None).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].get("author"), Some("O'Neil, Pat"));
+        assert_eq!(entries[0].get("title"), Some("Is it (really) fast? Yes!"));
+        assert_eq!(entries[1].get("title"), Some("Programming C#"));
+    }
+}
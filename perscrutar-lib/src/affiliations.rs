@@ -0,0 +1,224 @@
+/*!
+
+Author affiliations and the per-institution report built from them.
+
+Affiliations do not belong in the .bib file itself, so they are kept in an
+`AffiliationStore` keyed by citation key, which can be saved next to the
+bibliography as JSON. They are usually filled in by enrichment, e.g. from
+the `affiliation` arrays of Crossref authors.
+
+*/
+
+use std::collections::BTreeMap;
+use crate::json::{self, JsonError, JsonValue};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Affiliation {
+    pub institution: String,
+    pub country: Option<String>,
+}
+
+const COUNTRIES: [(&str, &str); 48] = [
+    ("usa", "United States"), ("us", "United States"), ("united states", "United States"),
+    ("united states of america", "United States"), ("uk", "United Kingdom"),
+    ("united kingdom", "United Kingdom"), ("england", "United Kingdom"), ("scotland", "United Kingdom"),
+    ("germany", "Germany"), ("deutschland", "Germany"), ("france", "France"), ("switzerland", "Switzerland"),
+    ("schweiz", "Switzerland"), ("suisse", "Switzerland"), ("austria", "Austria"), ("italy", "Italy"),
+    ("spain", "Spain"), ("portugal", "Portugal"), ("netherlands", "Netherlands"),
+    ("the netherlands", "Netherlands"), ("belgium", "Belgium"), ("denmark", "Denmark"),
+    ("sweden", "Sweden"), ("norway", "Norway"), ("finland", "Finland"), ("poland", "Poland"),
+    ("czech republic", "Czech Republic"), ("ireland", "Ireland"), ("greece", "Greece"),
+    ("israel", "Israel"), ("canada", "Canada"), ("mexico", "Mexico"), ("brazil", "Brazil"),
+    ("argentina", "Argentina"), ("chile", "Chile"), ("china", "China"), ("p.r. china", "China"),
+    ("japan", "Japan"), ("korea", "South Korea"), ("south korea", "South Korea"), ("india", "India"),
+    ("singapore", "Singapore"), ("australia", "Australia"), ("new zealand", "New Zealand"),
+    ("south africa", "South Africa"), ("russia", "Russia"), ("turkey", "Turkey"), ("taiwan", "Taiwan"),
+];
+
+/**
+Canonical country name for `name`, if it is one of the known spellings.
+*/
+pub fn canonical_country(name: &str) -> Option<&'static str> {
+    let lower = name.trim().trim_end_matches('.').to_lowercase();
+    COUNTRIES.iter().find(|(k, _)| *k == lower).map(|(_, v)| *v)
+}
+
+impl Affiliation {
+    /**
+    Split a free-text affiliation such as `"Dept. of Mathematics, Harvard
+    University, Cambridge, MA, USA"`. The country is recognised from the last
+    component; the institution is the component naming a university,
+    institute, laboratory or company, or the first component otherwise.
+    */
+    pub fn parse(text: &str) -> Affiliation {
+        let parts: Vec<&str> = text.split(',').map(|p| p.trim()).filter(|p| !p.is_empty()).collect();
+        let country = parts.last().and_then(|p| canonical_country(p)).map(String::from);
+        let markers = ["universit", "institut", "college", "school", "laborator", "lab", "inc", "ltd", "gmbh", "corporation", "centre", "center", "eth", "epfl", "cnrs", "inria"];
+        let institution = parts.iter()
+            .find(|p| {
+                let lower = p.to_lowercase();
+                lower.split(|c: char| !c.is_alphanumeric())
+                    .any(|w| markers.iter().any(|m| w.starts_with(m)))
+            })
+            .or(parts.first())
+            .map(|p| p.to_string())
+            .unwrap_or_default();
+        Affiliation { institution, country }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuthorAffiliation {
+    pub author: String,
+    pub affiliations: Vec<Affiliation>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AffiliationStore {
+    by_key: BTreeMap<String, Vec<AuthorAffiliation>>,
+}
+
+impl AffiliationStore {
+    pub fn new() -> AffiliationStore {
+        AffiliationStore::default()
+    }
+
+    pub fn set(&mut self, key: &str, authors: Vec<AuthorAffiliation>) {
+        self.by_key.insert(String::from(key), authors);
+    }
+
+    pub fn get(&self, key: &str) -> Option<&[AuthorAffiliation]> {
+        self.by_key.get(key).map(|v| v.as_slice())
+    }
+
+    pub fn remove(&mut self, key: &str) -> Option<Vec<AuthorAffiliation>> {
+        self.by_key.remove(key)
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.by_key.keys().map(|k| k.as_str())
+    }
+
+    pub fn to_json(&self) -> JsonValue {
+        JsonValue::Object(self.by_key.iter().map(|(key, authors)| {
+            let list = authors.iter().map(|a| JsonValue::object(vec![
+                ("author", JsonValue::str(&a.author)),
+                ("affiliations", JsonValue::Array(a.affiliations.iter().map(|f| JsonValue::object(vec![
+                    ("institution", JsonValue::str(&f.institution)),
+                    ("country", f.country.as_deref().map(JsonValue::str).unwrap_or(JsonValue::Null)),
+                ])).collect())),
+            ])).collect();
+            (key.clone(), JsonValue::Array(list))
+        }).collect())
+    }
+
+    pub fn from_json(text: &str) -> Result<AffiliationStore, JsonError> {
+        let mut store = AffiliationStore::new();
+        if let JsonValue::Object(members) = json::parse(text)? {
+            for (key, authors) in members {
+                let authors = authors.as_array().unwrap_or(&[]).iter().map(|a| AuthorAffiliation {
+                    author: a.get("author").and_then(|v| v.as_str()).unwrap_or("").to_string(),
+                    affiliations: a.get("affiliations").and_then(|v| v.as_array()).unwrap_or(&[])
+                        .iter()
+                        .map(|f| Affiliation {
+                            institution: f.get("institution").and_then(|v| v.as_str()).unwrap_or("").to_string(),
+                            country: f.get("country").and_then(|v| v.as_str()).map(String::from),
+                        })
+                        .collect(),
+                }).collect();
+                store.set(&key, authors);
+            }
+        }
+        Ok(store)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InstitutionReport {
+    /** Institution and the keys of the publications affiliated with it. */
+    pub institutions: Vec<(String, Vec<String>)>,
+    /** Country and the keys of the publications affiliated with it. */
+    pub countries: Vec<(String, Vec<String>)>,
+}
+
+fn push_unique(map: &mut BTreeMap<String, Vec<String>>, group: &str, key: &str) {
+    let keys = map.entry(String::from(group)).or_default();
+    if !keys.iter().any(|k| k == key) {
+        keys.push(String::from(key));
+    }
+}
+
+fn by_count(map: BTreeMap<String, Vec<String>>) -> Vec<(String, Vec<String>)> {
+    let mut list: Vec<(String, Vec<String>)> = map.into_iter().collect();
+    list.sort_by(|a, b| b.1.len().cmp(&a.1.len()).then_with(|| a.0.cmp(&b.0)));
+    list
+}
+
+/**
+Group publications by institution and by country, largest groups first.
+A publication counts once per group however many of its authors share it.
+*/
+pub fn institution_report(store: &AffiliationStore) -> InstitutionReport {
+    let mut institutions = BTreeMap::new();
+    let mut countries = BTreeMap::new();
+    for (key, authors) in store.by_key.iter() {
+        for f in authors.iter().flat_map(|a| a.affiliations.iter()) {
+            if !f.institution.is_empty() {
+                push_unique(&mut institutions, &f.institution, key);
+            }
+            if let Some(country) = &f.country {
+                push_unique(&mut countries, country, key);
+            }
+        }
+    }
+    InstitutionReport {
+        institutions: by_count(institutions),
+        countries: by_count(countries),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn author(name: &str, affiliations: &[&str]) -> AuthorAffiliation {
+        AuthorAffiliation {
+            author: String::from(name),
+            affiliations: affiliations.iter().map(|a| Affiliation::parse(a)).collect(),
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        let a = Affiliation::parse("Dept. of Mathematics, Harvard University, Cambridge, MA, USA");
+        assert_eq!(a.institution, "Harvard University");
+        assert_eq!(a.country.as_deref(), Some("United States"));
+
+        let b = Affiliation::parse("ETH Zürich, Switzerland");
+        assert_eq!(b.institution, "ETH Zürich");
+        assert_eq!(b.country.as_deref(), Some("Switzerland"));
+
+        let c = Affiliation::parse("Acme");
+        assert_eq!(c.institution, "Acme");
+        assert_eq!(c.country, None);
+    }
+
+    #[test]
+    fn test_report_and_json() {
+        let mut store = AffiliationStore::new();
+        store.set("a", vec![
+            author("Cox", &["Amherst College, Amherst, MA, USA"]),
+            author("Doe", &["Amherst College, USA", "ETH Zürich, Switzerland"]),
+        ]);
+        store.set("b", vec![author("Roe", &["ETH Zürich, Switzerland"])]);
+
+        let report = institution_report(&store);
+        assert_eq!(report.institutions[0], (String::from("ETH Zürich"), vec![String::from("a"), String::from("b")]));
+        assert_eq!(report.institutions[1].1, vec![String::from("a")]);
+        assert_eq!(report.countries.len(), 2);
+
+        let text = store.to_json().to_string();
+        assert_eq!(AffiliationStore::from_json(&text).unwrap(), store);
+    }
+}
@@ -0,0 +1,187 @@
+/*!
+
+Library statistics, recorded each time a library is loaded.
+
+`Statistics::compute` measures a bibliography: how many entries of each
+type it has, how many have a DOI or URL, their mean completeness (see
+`bibtex::completeness`) and how many `ValidationIssue`s the `RuleSet`
+finds. Appending each measurement to a history file, one JSON object per
+line, gives a time series of the library's growth and quality that
+`to_csv` and `to_json` export for dashboards:
+
+```text
+{"time": "2026-10-16T09:12:44Z", "entries": 412, "with_doi": 380, ...}
+{"time": "2026-10-17T08:03:10Z", "entries": 415, "with_doi": 384, ...}
+```
+
+In the CSV each entry type is a `type:NAME` column, with 0 where a
+measurement has no entry of that type.
+
+*/
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::SystemTime;
+use crate::audit::timestamp;
+use crate::bibtex::bibliography::Bibliography;
+use crate::json::{self, JsonValue};
+use crate::validate::RuleSet;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AnalyticsError(pub String);
+
+impl fmt::Display for AnalyticsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for AnalyticsError {}
+
+/** The columns of `to_csv` before the types. */
+pub const CSV_COLUMNS: [&str; 6] = ["time", "entries", "with_doi", "with_url", "completeness", "issues"];
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Statistics {
+    /** When the library was measured, as `audit::timestamp` writes it. */
+    pub time: String,
+    pub entries: usize,
+    /** How many entries there are of each type, by type name. */
+    pub types: BTreeMap<String, usize>,
+    pub with_doi: usize,
+    pub with_url: usize,
+    /** Mean completeness score, 0 for an empty library. */
+    pub completeness: f64,
+    /** Validation issues of all entries. */
+    pub issues: usize,
+}
+
+impl Statistics {
+    pub fn compute(bib: &Bibliography, rules: &RuleSet, time: SystemTime) -> Statistics {
+        let entries = bib.entries();
+        let has = |field: &str| entries.iter().filter(|e| e.get(field).map(|v| !v.trim().is_empty()).unwrap_or(false)).count();
+        let mut types = BTreeMap::new();
+        for entry in entries {
+            *types.entry(String::from(entry.entry_type().name())).or_insert(0) += 1;
+        }
+        let completeness = match entries.is_empty() {
+            true => 0.0,
+            false => entries.iter().map(|e| e.completeness_score()).sum::<f64>() / entries.len() as f64,
+        };
+        Statistics {
+            time: timestamp(time),
+            entries: entries.len(),
+            types,
+            with_doi: has("doi"),
+            with_url: has("url"),
+            completeness,
+            issues: rules.validate_bibliography(bib).len(),
+        }
+    }
+
+    pub fn to_json(&self) -> JsonValue {
+        let count = |n: usize| JsonValue::Num(n as f64);
+        JsonValue::object(vec![
+            ("time", JsonValue::str(&self.time)),
+            ("entries", count(self.entries)),
+            ("with_doi", count(self.with_doi)),
+            ("with_url", count(self.with_url)),
+            ("completeness", JsonValue::Num(self.completeness)),
+            ("issues", count(self.issues)),
+            ("types", JsonValue::object(self.types.iter().map(|(name, n)| (name.as_str(), count(*n))).collect())),
+        ])
+    }
+
+    pub fn from_json(json: &JsonValue) -> Result<Statistics, AnalyticsError> {
+        let number = |name: &str| json.get(name).and_then(JsonValue::as_f64)
+            .ok_or_else(|| AnalyticsError(format!("missing number `{}`", name)));
+        let mut types = BTreeMap::new();
+        if let Some(JsonValue::Object(members)) = json.get("types") {
+            for (name, n) in members {
+                types.insert(name.clone(), n.as_f64().unwrap_or(0.0) as usize);
+            }
+        }
+        Ok(Statistics {
+            time: String::from(json.get("time").and_then(JsonValue::as_str).unwrap_or_default()),
+            entries: number("entries")? as usize,
+            types,
+            with_doi: number("with_doi")? as usize,
+            with_url: number("with_url")? as usize,
+            completeness: number("completeness")?,
+            issues: number("issues")? as usize,
+        })
+    }
+
+    /** A line of a history file. */
+    pub fn to_line(&self) -> String {
+        format!("{}\n", self.to_json())
+    }
+}
+
+/** The measurements in a history file, oldest first. Blank lines are skipped. */
+pub fn parse_history(text: &str) -> Result<Vec<Statistics>, AnalyticsError> {
+    text.lines().enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            let value = json::parse(line).map_err(|e| AnalyticsError(format!("line {}: {}", i + 1, e)))?;
+            Statistics::from_json(&value).map_err(|e| AnalyticsError(format!("line {}: {}", i + 1, e)))
+        })
+        .collect()
+}
+
+pub fn to_json(series: &[Statistics]) -> JsonValue {
+    JsonValue::Array(series.iter().map(Statistics::to_json).collect())
+}
+
+/** `series` as CSV, one row per measurement, with a `type:NAME` column for every type in any of them. */
+pub fn to_csv(series: &[Statistics]) -> String {
+    let mut types: Vec<&str> = series.iter().flat_map(|s| s.types.keys().map(|t| t.as_str())).collect();
+    types.sort_unstable();
+    types.dedup();
+    let mut header: Vec<String> = CSV_COLUMNS.iter().map(|c| String::from(*c)).collect();
+    header.extend(types.iter().map(|t| format!("type:{}", t)));
+    let mut out = header.join(",");
+    out.push('\n');
+    for s in series {
+        let mut row = vec![
+            s.time.clone(), s.entries.to_string(), s.with_doi.to_string(), s.with_url.to_string(),
+            format!("{:.3}", s.completeness), s.issues.to_string(),
+        ];
+        row.extend(types.iter().map(|t| s.types.get(*t).copied().unwrap_or(0).to_string()));
+        out.push_str(&row.join(","));
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use std::time::{Duration, UNIX_EPOCH};
+    use crate::bibtex::parser::parse_bibliography;
+
+    #[test]
+    fn test_statistics() {
+        let bib = parse_bibliography("@article{a, author = {A}, title = {T}, journal = {J}, year = 2001, doi = {10.1/x}}\n\
+            @misc{b, url = {https://example.org}}\n@misc{c, title = {C}, note = {}}").unwrap();
+        let first = Statistics::compute(&bib, &RuleSet::bibtex(), UNIX_EPOCH);
+        assert_eq!((first.entries, first.with_doi, first.with_url, first.issues), (3, 1, 1, 1));
+        assert_eq!(first.types.get("misc"), Some(&2));
+        assert!(first.completeness > 0.0 && first.completeness < 1.0);
+        assert_eq!(Statistics::compute(&Bibliography::new(), &RuleSet::bibtex(), UNIX_EPOCH).completeness, 0.0);
+
+        let mut second = Statistics::compute(&bib, &RuleSet::bibtex(), UNIX_EPOCH + Duration::from_secs(86400));
+        second.types.insert(String::from("book"), 1);
+        let history = parse_history(&format!("{}\n{}", first.to_line(), second.to_line())).unwrap();
+        assert_eq!(history, vec![first.clone(), second]);
+        assert_eq!(to_json(&history).as_array().map(|a| a.len()), Some(2));
+
+        let csv = to_csv(&history);
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], "time,entries,with_doi,with_url,completeness,issues,type:article,type:book,type:misc");
+        assert_eq!(lines[1], format!("1970-01-01T00:00:00Z,3,1,1,{:.3},1,1,0,2", first.completeness));
+        assert!(lines[2].starts_with("1970-01-02T00:00:00Z,") && lines[2].ends_with(",1,1,2"));
+        assert!(parse_history("{\"time\": \"x\"}").is_err());
+    }
+}
@@ -0,0 +1,199 @@
+/*!
+
+Snapshots of web pages in the Internet Archive's Wayback Machine.
+
+Links in published bibliographies rot. `archive` asks the Wayback Machine to
+take a snapshot of a URL and returns where it is kept; `ArchiveTransform`
+(named `archive-urls`) does this for the `url` field of every entry and
+records the snapshot in `archiveurl`, with its date in `urldate`:
+
+```text
+url        = {https://example.org/report.pdf},
+archiveurl = {https://web.archive.org/web/20240105120000/https://example.org/report.pdf},
+urldate    = {2024-01-05},
+```
+
+`closest` only looks up an existing snapshot, without taking a new one.
+Reading the Wayback Machine's answers is always available; talking to it
+needs the `net` feature.
+
+*/
+
+use crate::json::{parse, JsonValue};
+#[cfg(feature = "net")]
+use crate::bibtex::bibliography::Bibliography;
+#[cfg(feature = "net")]
+use crate::net::{encode_component, expect_success, HttpClient, NetError};
+#[cfg(feature = "net")]
+use crate::transform::{Report, Transform};
+
+const WAYBACK: &str = "https://web.archive.org";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    pub url: String,
+    /** `YYYYMMDDhhmmss`, as in Wayback Machine URLs. */
+    pub timestamp: String,
+}
+
+impl Snapshot {
+    /**
+    Snapshot from a Wayback Machine URL or path such as
+    `/web/20240105120000/https://example.org/`.
+    */
+    pub fn from_url(url: &str) -> Option<Snapshot> {
+        let path = url.strip_prefix(WAYBACK).or_else(|| url.strip_prefix("http://web.archive.org")).unwrap_or(url);
+        let rest = path.strip_prefix("/web/")?;
+        let (timestamp, original) = rest.split_once('/')?;
+        let timestamp = timestamp.trim_end_matches(|c: char| c.is_ascii_alphabetic() || c == '_');
+        if timestamp.len() < 8 || !timestamp.chars().all(|c| c.is_ascii_digit()) || original.is_empty() {
+            return None;
+        }
+        Some(Snapshot { url: format!("{}{}", WAYBACK, path), timestamp: String::from(timestamp) })
+    }
+
+    /** Date of the snapshot as `YYYY-MM-DD`. */
+    pub fn date(&self) -> String {
+        format!("{}-{}-{}", &self.timestamp[..4], &self.timestamp[4..6], &self.timestamp[6..8])
+    }
+}
+
+/**
+The snapshot in an answer of the availability API
+(`https://archive.org/wayback/available?url=...`), if there is one.
+*/
+pub fn parse_availability(body: &str) -> Option<Snapshot> {
+    let json = parse(body).ok()?;
+    let closest = json.path(&["archived_snapshots", "closest"])?;
+    if closest.get("available").and_then(JsonValue::as_bool) != Some(true) {
+        return None;
+    }
+    let url = closest.get("url").and_then(JsonValue::as_str)?.replacen("http://", "https://", 1);
+    Snapshot::from_url(&url)
+}
+
+/**
+The most recent snapshot of `url`, if the Wayback Machine has one.
+*/
+#[cfg(feature = "net")]
+pub fn closest(client: &dyn HttpClient, url: &str) -> Result<Option<Snapshot>, NetError> {
+    let api = format!("https://archive.org/wayback/available?url={}", encode_component(url));
+    let response = expect_success(&api, client.get(&api, &[])?)?;
+    Ok(parse_availability(&response.body))
+}
+
+/**
+Have the Wayback Machine take a snapshot of `url` now and return it.
+*/
+#[cfg(feature = "net")]
+pub fn archive(client: &dyn HttpClient, url: &str) -> Result<Snapshot, NetError> {
+    let save = format!("{}/save/{}", WAYBACK, url);
+    let response = expect_success(&save, client.get(&save, &[])?)?;
+    // the answer names the new snapshot; if it does not, ask for the latest one
+    if let Some(snapshot) = response.header("content-location").and_then(Snapshot::from_url) {
+        return Ok(snapshot);
+    }
+    closest(client, url)?.ok_or_else(|| NetError::Transport(format!("no snapshot of {} after saving it", url)))
+}
+
+/**
+Archives the `url` of every entry that has none archived yet (or, with
+`refresh`, every entry with a `url`).
+*/
+#[cfg(feature = "net")]
+pub struct ArchiveTransform {
+    client: Box<dyn HttpClient>,
+    pub refresh: bool,
+}
+
+#[cfg(feature = "net")]
+impl ArchiveTransform {
+    pub fn new(client: Box<dyn HttpClient>) -> ArchiveTransform {
+        ArchiveTransform { client, refresh: false }
+    }
+}
+
+#[cfg(feature = "net")]
+impl Transform for ArchiveTransform {
+    fn name(&self) -> &str {
+        "archive-urls"
+    }
+
+    fn description(&self) -> &str {
+        "Save `url` fields in the Wayback Machine and record the snapshot in archiveurl and urldate"
+    }
+
+    fn apply(&self, bibliography: &mut Bibliography) -> Report {
+        let mut messages = Vec::new();
+        let changed = bibliography.visit_mut(|e| {
+            let Some(url) = e.get("url").map(|u| u.trim().trim_matches(|c| c == '{' || c == '}').to_string()) else { return };
+            if !(url.starts_with("http://") || url.starts_with("https://")) || (e.has("archiveurl") && !self.refresh) {
+                return;
+            }
+            match archive(self.client.as_ref(), &url) {
+                Ok(snapshot) => {
+                    e.set("archiveurl", &snapshot.url);
+                    e.set("urldate", &snapshot.date());
+                }
+                Err(err) => messages.push(format!("{}: cannot archive {}: {}", e.key(), url, err)),
+            }
+        });
+        Report { changed, messages }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_parse_availability() {
+        let body = r#"{"url": "example.com", "archived_snapshots": {"closest": {"status": "200", "available": true,
+            "url": "http://web.archive.org/web/20130919044612/http://example.com/", "timestamp": "20130919044612"}}}"#;
+        let snapshot = parse_availability(body).unwrap();
+        assert_eq!(snapshot.url, "https://web.archive.org/web/20130919044612/http://example.com/");
+        assert_eq!(snapshot.date(), "2013-09-19");
+        assert_eq!(parse_availability(r#"{"url": "x", "archived_snapshots": {}}"#), None);
+        assert_eq!(Snapshot::from_url("/web/20240105120000id_/https://e.org/").unwrap().timestamp, "20240105120000");
+        assert_eq!(Snapshot::from_url("https://example.org/web/x"), None);
+    }
+
+    #[cfg(feature = "net")]
+    #[test]
+    fn test_archive_transform() {
+        use crate::bibtex::data::{BibType, Entry};
+        use crate::net::Response;
+
+        struct Wayback;
+
+        impl HttpClient for Wayback {
+            fn get(&self, url: &str, _headers: &[(&str, &str)]) -> Result<Response, NetError> {
+                match url {
+                    "https://web.archive.org/save/https://example.org/a" => Ok(Response {
+                        status: 200,
+                        headers: vec![(String::from("Content-Location"), String::from("/web/20240105120000/https://example.org/a"))],
+                        body: String::new(),
+                    }),
+                    _ => Ok(Response { status: 429, headers: vec![], body: String::new() }),
+                }
+            }
+        }
+
+        let mut a = Entry::new(BibType::Misc, "a");
+        a.set("url", "https://example.org/a");
+        let mut b = Entry::new(BibType::Misc, "b");
+        b.set("url", "https://example.org/b");
+        let mut done = Entry::new(BibType::Misc, "done");
+        done.set("url", "https://example.org/c");
+        done.set("archiveurl", "https://web.archive.org/web/2020/https://example.org/c");
+        let mut bib = Bibliography::from_entries(vec![a, b, done]);
+        let report = ArchiveTransform::new(Box::new(Wayback)).apply(&mut bib);
+        assert_eq!(report.changed, vec!["a"]);
+        assert_eq!(report.messages.len(), 1);
+        assert!(report.messages[0].starts_with("b: cannot archive https://example.org/b"));
+        let a = bib.get("a").unwrap();
+        assert_eq!(a.get("archiveurl"), Some("https://web.archive.org/web/20240105120000/https://example.org/a"));
+        assert_eq!(a.get("urldate"), Some("2024-01-05"));
+    }
+}
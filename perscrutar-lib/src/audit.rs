@@ -0,0 +1,156 @@
+/*!
+
+A record of who changed a bibliography, and how.
+
+In a library shared by a group it helps to know who added an entry, or
+when it was last edited. Each change is an `Event`: when and by whom, the
+operation, and the keys of the entries it touched. Events go in the
+`MetadataStore` sidecar, under `log`, in the order they happened:
+
+```json
+"log": [
+  {"time": "2026-10-16T09:12:44Z", "user": "ana", "operation": "add", "keys": ["knuth84"]},
+  {"time": "2026-10-16T09:30:02Z", "user": "ben", "operation": "rename", "keys": ["knuth84", "knuth1984"]}
+]
+```
+
+The log is only ever appended to. `history` gives the events of one entry,
+following it back through `rename` events (old key first, then new) to
+the events recorded under its earlier keys.
+
+*/
+
+use std::time::{SystemTime, UNIX_EPOCH};
+use crate::json::JsonValue;
+use crate::metadata::{MetadataError, MetadataStore};
+
+pub const RENAME: &str = "rename";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Event {
+    /** UTC, as `YYYY-MM-DDThh:mm:ssZ`. */
+    pub time: String,
+    pub user: String,
+    /** What was done, such as `add`, `edit`, `remove` or `rename`. */
+    pub operation: String,
+    pub keys: Vec<String>,
+}
+
+impl Event {
+    /** An event by the current user, now. */
+    pub fn now<S: AsRef<str>>(operation: &str, keys: &[S]) -> Event {
+        Event {
+            time: timestamp(SystemTime::now()),
+            user: user(),
+            operation: String::from(operation),
+            keys: keys.iter().map(|k| String::from(k.as_ref())).collect(),
+        }
+    }
+
+    pub fn to_json(&self) -> JsonValue {
+        JsonValue::object(vec![
+            ("time", JsonValue::str(&self.time)),
+            ("user", JsonValue::str(&self.user)),
+            ("operation", JsonValue::str(&self.operation)),
+            ("keys", JsonValue::Array(self.keys.iter().map(|k| JsonValue::str(k)).collect())),
+        ])
+    }
+
+    pub fn from_json(json: &JsonValue) -> Result<Event, MetadataError> {
+        let text = |name: &str| json.get(name).and_then(JsonValue::as_str).map(String::from)
+            .ok_or_else(|| MetadataError::Invalid(format!("log event without `{}`", name)));
+        let keys = json.get("keys").and_then(JsonValue::as_array)
+            .ok_or_else(|| MetadataError::Invalid(String::from("log event without `keys`")))?;
+        Ok(Event {
+            time: text("time")?,
+            user: text("user")?,
+            operation: text("operation")?,
+            keys: keys.iter().filter_map(JsonValue::as_str).map(String::from).collect(),
+        })
+    }
+}
+
+/** The login name from `$USER` (or `$USERNAME` on Windows), or `unknown`. */
+pub fn user() -> String {
+    ["USER", "USERNAME"].into_iter()
+        .find_map(|name| std::env::var(name).ok().filter(|u| !u.trim().is_empty()))
+        .unwrap_or_else(|| String::from("unknown"))
+}
+
+/** `time` in UTC as `YYYY-MM-DDThh:mm:ssZ`. */
+pub fn timestamp(time: SystemTime) -> String {
+    let seconds = time.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    let (days, rest) = ((seconds / 86400) as i64, seconds % 86400);
+    // days to a civil date, after Howard Hinnant's algorithm
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z", year, month, day, rest / 3600, rest % 3600 / 60, rest % 60)
+}
+
+impl MetadataStore {
+    /**
+    The events that touched the entry now keyed `key`, oldest first,
+    including those from before it was renamed.
+    */
+    pub fn history(&self, key: &str) -> Vec<&Event> {
+        let mut keys = vec![String::from(key)];
+        let mut found: Vec<&Event> = Vec::new();
+        for event in self.events().iter().rev() {
+            if !event.keys.iter().any(|k| keys.contains(k)) {
+                continue;
+            }
+            if let [old, new] = event.keys.as_slice() {
+                if event.operation == RENAME && keys.contains(new) {
+                    keys.push(old.clone());
+                }
+            }
+            found.push(event);
+        }
+        found.reverse();
+        found
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_timestamp() {
+        assert_eq!(timestamp(UNIX_EPOCH), "1970-01-01T00:00:00Z");
+        assert_eq!(timestamp(UNIX_EPOCH + Duration::from_secs(951_827_696)), "2000-02-29T12:34:56Z");
+        assert_eq!(timestamp(UNIX_EPOCH + Duration::from_secs(1_791_969_164)), "2026-10-14T09:12:44Z");
+    }
+
+    #[test]
+    fn test_history() {
+        let event = |user: &str, operation: &str, keys: &[&str]| Event {
+            time: String::from("2026-10-16T09:12:44Z"),
+            user: String::from(user),
+            operation: String::from(operation),
+            keys: keys.iter().map(|k| String::from(*k)).collect(),
+        };
+        let mut store = MetadataStore::new();
+        store.log(event("ana", "add", &["knuth84", "texbook"]));
+        store.log(event("ben", "edit", &["texbook"]));
+        store.log(event("ben", RENAME, &["knuth84", "knuth1984"]));
+        store.log(event("ana", "edit", &["knuth1984"]));
+
+        let store = MetadataStore::parse(&store.to_json().to_string()).unwrap();
+        assert_eq!(store.events().len(), 4);
+        let operations = |key: &str| store.history(key).into_iter().map(|e| e.operation.as_str()).collect::<Vec<_>>();
+        assert_eq!(operations("knuth1984"), vec!["add", RENAME, "edit"]);
+        assert_eq!(operations("texbook"), vec!["add", "edit"]);
+        assert_eq!(operations("knuth84"), vec!["add", RENAME]);
+        assert_eq!(Event::now("add", &["a"]).keys, vec!["a"]);
+    }
+}
@@ -0,0 +1,278 @@
+/*!
+
+Batch edits setting fields from templates over other fields.
+
+A `ValueTemplate` is text with `{name}` placeholders, filled in per entry:
+
+```text
+note = Imported from {source} on {date}
+url  = https://example.org/papers/{@key}.pdf
+note = {publisher|Self-published}, {year}
+```
+
+A placeholder names a variable given to the `BatchEdit`, or else a field
+of the entry; `{@key}` and `{@type}` are the citation key and entry type.
+Variables come first, so `{date}` can be the date of an import even for
+entries with a `date` field. After a `|`, a placeholder gives the text to
+use when there is no such field. `{{` and `}}` are literal braces.
+
+A field that is missing or empty and has no fallback is handled as the
+edit's `MissingField` policy says: by default the assignment is skipped
+for that entry, other assignments still being made. Assignments are made
+in order, so a template can use a field set by an earlier one.
+`BatchEdit` is a `Transform` (`batch-edit`) as well.
+
+*/
+
+use std::fmt;
+use crate::bibtex::bibliography::Bibliography;
+use crate::bibtex::data::Entry;
+use crate::transform::{Report, Transform};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TemplateError(pub String);
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for TemplateError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Text(String),
+    Field { name: String, fallback: Option<String> },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValueTemplate {
+    parts: Vec<Part>,
+}
+
+impl ValueTemplate {
+    pub fn parse(template: &str) -> Result<ValueTemplate, TemplateError> {
+        let mut parts = Vec::new();
+        let mut text = String::new();
+        let mut chars = template.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.next_if_eq(&'{').is_some() => text.push('{'),
+                '}' if chars.next_if_eq(&'}').is_some() => text.push('}'),
+                '}' => return Err(TemplateError(String::from("unmatched `}`; write `}}` for a literal brace"))),
+                '{' => {
+                    let mut inner = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some('{') | None => return Err(TemplateError(format!("unclosed placeholder `{{{}`", inner))),
+                            Some(c) => inner.push(c),
+                        }
+                    }
+                    let (name, fallback) = match inner.split_once('|') {
+                        Some((name, fallback)) => (name.trim(), Some(String::from(fallback))),
+                        None => (inner.trim(), None),
+                    };
+                    if name.is_empty() || name.contains(char::is_whitespace) {
+                        return Err(TemplateError(format!("`{{{}}}` does not name a field", inner)));
+                    }
+                    if !text.is_empty() {
+                        parts.push(Part::Text(std::mem::take(&mut text)));
+                    }
+                    parts.push(Part::Field { name: name.to_lowercase(), fallback });
+                }
+                c => text.push(c),
+            }
+        }
+        if !text.is_empty() {
+            parts.push(Part::Text(text));
+        }
+        Ok(ValueTemplate { parts })
+    }
+
+    /** The fields and variables the template refers to, in order. */
+    pub fn names(&self) -> Vec<&str> {
+        self.parts.iter().filter_map(|p| match p {
+            Part::Field { name, .. } => Some(name.as_str()),
+            Part::Text(_) => None,
+        }).collect()
+    }
+
+    /**
+    The template filled in for `entry`, or the name of the first field
+    that is missing and has no fallback. With `empty_if_missing`, missing
+    fields are filled in as nothing instead.
+    */
+    pub fn render(&self, entry: &Entry, variables: &[(String, String)], empty_if_missing: bool) -> Result<String, String> {
+        let mut out = String::new();
+        for part in self.parts.iter() {
+            match part {
+                Part::Text(text) => out.push_str(text),
+                Part::Field { name, fallback } => {
+                    let value = variables.iter().find(|(v, _)| v == name).map(|(_, value)| value.as_str())
+                        .or_else(|| match name.as_str() {
+                            "@key" => Some(entry.key()),
+                            "@type" => Some(entry.entry_type().name()),
+                            _ => entry.get(name).filter(|v| !v.trim().is_empty()),
+                        });
+                    match (value, fallback) {
+                        (Some(value), _) => out.push_str(value),
+                        (None, Some(fallback)) => out.push_str(fallback),
+                        (None, None) if empty_if_missing => {}
+                        (None, None) => return Err(name.clone()),
+                    }
+                }
+            }
+        }
+        Ok(out)
+    }
+}
+
+/** What to do when a template refers to a field an entry does not have. */
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MissingField {
+    /** Leave that field of that entry alone. */
+    #[default]
+    Skip,
+    /** Fill in nothing for the placeholder. */
+    Empty,
+    /** Leave the whole entry alone and report it. */
+    Fail,
+}
+
+impl MissingField {
+    pub fn parse(name: &str) -> Option<MissingField> {
+        match name {
+            "skip" => Some(MissingField::Skip),
+            "empty" => Some(MissingField::Empty),
+            "fail" | "error" => Some(MissingField::Fail),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BatchEdit {
+    /** Fields to set and their templates, in order. */
+    pub assignments: Vec<(String, ValueTemplate)>,
+    /** Variables the templates can refer to, such as the date of an import. */
+    pub variables: Vec<(String, String)>,
+    pub missing: MissingField,
+    /** Leave fields the entry already has a value for alone. */
+    pub keep_existing: bool,
+}
+
+impl BatchEdit {
+    pub fn new() -> BatchEdit {
+        BatchEdit::default()
+    }
+
+    pub fn set(mut self, field: &str, template: ValueTemplate) -> BatchEdit {
+        self.assignments.push((field.to_lowercase(), template));
+        self
+    }
+
+    pub fn variable(mut self, name: &str, value: &str) -> BatchEdit {
+        self.variables.push((name.to_lowercase(), String::from(value)));
+        self
+    }
+
+    /**
+    Make the assignments on `entry`. Returns whether it changed, or with
+    `MissingField::Fail` the first missing field, leaving `entry` as it was.
+    */
+    pub fn edit(&self, entry: &mut Entry) -> Result<bool, String> {
+        let mut edited = entry.clone();
+        for (field, template) in self.assignments.iter() {
+            if self.keep_existing && edited.get(field).map(|v| !v.trim().is_empty()).unwrap_or(false) {
+                continue;
+            }
+            match template.render(&edited, &self.variables, self.missing == MissingField::Empty) {
+                Ok(value) => { edited.set(field, &value); }
+                Err(_) if self.missing == MissingField::Skip => {}
+                Err(missing) => return Err(missing),
+            }
+        }
+        let changed = !edited.is_identical(entry);
+        *entry = edited;
+        Ok(changed)
+    }
+}
+
+impl Transform for BatchEdit {
+    fn name(&self) -> &str {
+        "batch-edit"
+    }
+
+    fn description(&self) -> &str {
+        "Set fields from templates over other fields"
+    }
+
+    fn apply(&self, bibliography: &mut Bibliography) -> Report {
+        let mut messages = Vec::new();
+        let changed = bibliography.visit_mut(|e| {
+            if let Err(missing) = self.edit(e) {
+                messages.push(format!("{}: no `{}` field, so not edited", e.key(), missing));
+            }
+        });
+        Report { changed, messages }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::bibtex::data::BibType;
+
+    #[test]
+    fn test_template() {
+        let t = ValueTemplate::parse("Imported from {source} on {date}").unwrap();
+        assert_eq!(t.names(), vec!["source", "date"]);
+        let mut e = Entry::new(BibType::Article, "knuth84");
+        e.set("source", "DBLP");
+        e.set("date", "1984-05");
+        let today = [(String::from("date"), String::from("2026-10-16"))];
+        assert_eq!(t.render(&e, &today, false), Ok(String::from("Imported from DBLP on 2026-10-16")));
+        assert_eq!(t.render(&e, &[], false), Ok(String::from("Imported from DBLP on 1984-05")));
+
+        let t = ValueTemplate::parse("{{{@key}}}: {publisher|self-published}{series}").unwrap();
+        assert_eq!(t.render(&e, &[], true), Ok(String::from("{knuth84}: self-published")));
+        assert_eq!(t.render(&e, &[], false), Err(String::from("series")));
+        assert!(ValueTemplate::parse("{unclosed").is_err());
+        assert!(ValueTemplate::parse("a } b").is_err());
+        assert!(ValueTemplate::parse("{}").is_err());
+    }
+
+    #[test]
+    fn test_batch_edit() {
+        let mut a = Entry::new(BibType::Article, "a");
+        a.set("source", "DBLP");
+        a.set("note", "keep me");
+        let b = Entry::new(BibType::Book, "b");
+        let edit = BatchEdit::new()
+            .set("note", ValueTemplate::parse("Imported from {source} on {today}").unwrap())
+            .set("howpublished", ValueTemplate::parse("@{@type}").unwrap())
+            .variable("today", "2026-10-16");
+
+        let mut bib = Bibliography::from_entries(vec![a.clone(), b.clone()]);
+        let report = edit.apply(&mut bib);
+        assert_eq!(report.changed, vec!["a", "b"]);
+        assert_eq!(bib.get("a").unwrap().get("note"), Some("Imported from DBLP on 2026-10-16"));
+        assert_eq!(bib.get("b").unwrap().get("note"), None);
+        assert_eq!(bib.get("b").unwrap().get("howpublished"), Some("@book"));
+
+        let keep = BatchEdit { keep_existing: true, ..edit.clone() };
+        let mut e = a.clone();
+        assert_eq!(keep.edit(&mut e), Ok(true));
+        assert_eq!(e.get("note"), Some("keep me"));
+
+        let strict = BatchEdit { missing: MissingField::Fail, ..edit };
+        let mut bib = Bibliography::from_entries(vec![a, b]);
+        let report = strict.apply(&mut bib);
+        assert_eq!(report.changed, vec!["a"]);
+        assert_eq!(report.messages, vec!["b: no `source` field, so not edited"]);
+        assert!(bib.get("b").unwrap().is_empty());
+    }
+}
@@ -0,0 +1,598 @@
+/*!
+
+A whole bibliography: the entries of one .bib file, in file order, and
+its `@preamble`s and, if the parser kept them, `@comment`s.
+
+Lookups by citation key and DOI go through indexes. To keep them correct,
+entries can only be changed through `Bibliography::visit_mut`, which
+updates the indexes afterwards and records every change in a journal of
+`LibraryEvent`s (see `events`), read with `journal` or `take_journal`.
+
+With the `net` feature a bibliography can also be fetched from a web server
+with `Bibliography::load_url`, optionally through an `HttpCache` so that
+unchanged files are not downloaded again.
+
+`Bibliography::load_dir` gathers the .bib files scattered over a directory
+tree into one bibliography, reporting the files it could not read and the
+keys defined more than once instead of stopping at the first problem.
+
+Keys cited by published documents must not change. Entries can be
+`pin`ned for this: `visit_mut` undoes changes to the key or type of a
+pinned entry and `retain` keeps it, so no transform can rename, retype or
+delete it. What was refused is kept for `take_refused`.
+
+`Bibliography::extract_for` copies out the entries for a list of keys,
+say those a paper cites, together with the entries they depend on through
+`crossref`, `xref`, `xdata` and `related`, so the result stands on its own.
+What the entries inherit through these references is worked out in
+`crossrefs`.
+
+*/
+
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use crate::bibtex::data::Entry;
+use crate::events::{diff, LibraryEvent};
+use crate::bibtex::error::ParseError;
+use crate::bibtex::parser::parse_bibliography;
+use crate::bibtex::policy::FieldPattern;
+#[cfg(feature = "net")]
+use crate::net::{expect_success, HttpCache, HttpClient, NetError};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Bibliography {
+    entries: Vec<Entry>,
+    /** Index of the first entry with each key. */
+    keys: HashMap<String, usize>,
+    /** Index of the first entry with each lower-cased DOI. */
+    dois: HashMap<String, usize>,
+    journal: Vec<LibraryEvent>,
+    pinned: Vec<String>,
+    /** Changes to pinned entries that were undone, as messages. */
+    refused: Vec<String>,
+    preambles: Vec<String>,
+    comments: Vec<String>,
+}
+
+fn doi_of(entry: &Entry) -> Option<String> {
+    entry.get("doi").map(|d| d.trim().to_lowercase()).filter(|d| !d.is_empty())
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LoadError {
+    Io(String),
+    #[cfg(feature = "net")]
+    Net(NetError),
+    Parse(ParseError),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::Io(msg) => f.write_str(msg),
+            #[cfg(feature = "net")]
+            LoadError::Net(e) => write!(f, "{}", e),
+            LoadError::Parse(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+#[cfg(feature = "net")]
+impl From<NetError> for LoadError {
+    fn from(e: NetError) -> Self {
+        LoadError::Net(e)
+    }
+}
+
+impl From<ParseError> for LoadError {
+    fn from(e: ParseError) -> Self {
+        LoadError::Parse(e)
+    }
+}
+
+/**
+Outcome of `Bibliography::load_dir`.
+*/
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DirLoad {
+    /** The entries and preambles of all files read, file by file. */
+    pub bibliography: Bibliography,
+    /** The files read, in order. */
+    pub files: Vec<PathBuf>,
+    /** The files that could not be read or parsed, with why. */
+    pub errors: Vec<(PathBuf, LoadError)>,
+    /** Keys used by several entries, with the file of each. */
+    pub duplicates: Vec<(String, Vec<PathBuf>)>,
+}
+
+/**
+The files in `dir` whose names match `pattern`, and with `recursive` those
+in its subdirectories, sorted by path. Hidden directories such as `.git`
+are not entered; subdirectories that cannot be read go to `errors`.
+*/
+fn find_files(dir: &Path, recursive: bool, pattern: &FieldPattern, files: &mut Vec<PathBuf>, errors: &mut Vec<(PathBuf, LoadError)>)
+    -> Result<(), LoadError> {
+    let read = std::fs::read_dir(dir).map_err(|e| LoadError::Io(format!("cannot read {}: {}", dir.display(), e)))?;
+    let mut paths: Vec<PathBuf> = read.flatten().map(|e| e.path()).collect();
+    paths.sort();
+    for path in paths {
+        let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+        if path.is_dir() {
+            if recursive && !name.starts_with('.') {
+                if let Err(e) = find_files(&path, recursive, pattern, files, errors) {
+                    errors.push((path, e));
+                }
+            }
+        } else if pattern.matches(name) {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/** Fields holding the keys of other entries, separated by commas. */
+pub const KEY_REFERENCES: [&str; 4] = ["crossref", "xref", "xdata", "related"];
+
+/** What `Bibliography::extract_for` copied. */
+#[derive(Debug, Clone, Default)]
+pub struct Extract {
+    /** The entries, in the order of the original, and its preambles. */
+    pub bibliography: Bibliography,
+    /** Keys asked for or referenced that no entry has, in the order met. */
+    pub missing: Vec<String>,
+}
+
+/**
+Outcome of `Bibliography::rename_field`.
+*/
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FieldRename {
+    /** Keys of the entries that changed. */
+    pub renamed: Vec<String>,
+    /** Keys of the entries left alone because they have a different `to`. */
+    pub conflicts: Vec<String>,
+}
+
+impl Bibliography {
+    pub fn new() -> Bibliography {
+        Bibliography::default()
+    }
+
+    pub fn from_entries(entries: Vec<Entry>) -> Bibliography {
+        let mut bib = Bibliography { entries, ..Bibliography::default() };
+        bib.reindex();
+        bib
+    }
+
+    fn index(&mut self, i: usize) {
+        let entry = &self.entries[i];
+        self.keys.entry(String::from(entry.key())).or_insert(i);
+        if let Some(doi) = doi_of(entry) {
+            self.dois.entry(doi).or_insert(i);
+        }
+    }
+
+    fn reindex(&mut self) {
+        self.keys.clear();
+        self.dois.clear();
+        for i in 0..self.entries.len() {
+            self.index(i);
+        }
+    }
+
+    /**
+    Parse a whole .bib file, ignoring text between entries (see
+    `parser::parse_bibliography`).
+    */
+    pub fn parse(input: &str) -> Result<Bibliography, ParseError> {
+        parse_bibliography(input)
+    }
+
+    /**
+    Fetch and parse a .bib file from `url`. With a cache, the request is
+    made conditional on the cached copy's `ETag`/`Last-Modified`.
+    */
+    #[cfg(feature = "net")]
+    pub fn load_url(client: &dyn HttpClient, url: &str, cache: Option<&HttpCache>) -> Result<Bibliography, LoadError> {
+        let body = match cache {
+            Some(cache) => cache.get(client, url)?,
+            None => expect_success(url, client.get(url, &[])?)?.body,
+        };
+        Ok(Bibliography::parse(&body)?)
+    }
+
+    /** The contents of the `@preamble`s, in file order. */
+    pub fn preambles(&self) -> &[String] {
+        &self.preambles
+    }
+
+    pub fn add_preamble(&mut self, preamble: &str) {
+        self.preambles.push(String::from(preamble));
+    }
+
+    /** The contents of the `@comment`s, in file order. */
+    pub fn comments(&self) -> &[String] {
+        &self.comments
+    }
+
+    pub fn add_comment(&mut self, comment: &str) {
+        self.comments.push(String::from(comment));
+    }
+
+    /**
+    Read every .bib file in `dir`, and with `recursive` in its
+    subdirectories too (see `load_dir_matching`).
+    */
+    pub fn load_dir<P: AsRef<Path>>(dir: P, recursive: bool) -> Result<DirLoad, LoadError> {
+        Bibliography::load_dir_matching(dir, recursive, "*.bib")
+    }
+
+    /**
+    Read the files in `dir` (and with `recursive` below it) whose names
+    match `pattern`, where `*` matches any run of characters, ignoring
+    case. Files are read in path order; one that cannot be read or parsed
+    is reported in `DirLoad::errors` and skipped. Fails only if `dir`
+    itself cannot be read.
+    */
+    pub fn load_dir_matching<P: AsRef<Path>>(dir: P, recursive: bool, pattern: &str) -> Result<DirLoad, LoadError> {
+        let mut load = DirLoad::default();
+        let mut found = Vec::new();
+        find_files(dir.as_ref(), recursive, &FieldPattern::new(pattern), &mut found, &mut load.errors)?;
+        let mut entries = Vec::new();
+        let mut origins: Vec<(String, PathBuf)> = Vec::new();
+        for path in found {
+            let parsed = std::fs::read_to_string(&path)
+                .map_err(|e| LoadError::Io(format!("cannot read {}: {}", path.display(), e)))
+                .and_then(|s| Ok(Bibliography::parse(&s)?));
+            match parsed {
+                Ok(bib) => {
+                    load.bibliography.preambles.extend(bib.preambles);
+                    origins.extend(bib.entries.iter().map(|e| (String::from(e.key()), path.clone())));
+                    entries.extend(bib.entries);
+                    load.files.push(path);
+                }
+                Err(e) => load.errors.push((path, e)),
+            }
+        }
+        for (key, _) in &origins {
+            if load.duplicates.iter().any(|(k, _)| k == key) {
+                continue;
+            }
+            let files: Vec<PathBuf> = origins.iter().filter(|(k, _)| k == key).map(|(_, p)| p.clone()).collect();
+            if files.len() > 1 {
+                load.duplicates.push((key.clone(), files));
+            }
+        }
+        load.bibliography.entries = entries;
+        load.bibliography.reindex();
+        Ok(load)
+    }
+
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    pub fn into_entries(self) -> Vec<Entry> {
+        self.entries
+    }
+
+    /**
+    The first entry with citation key `key`, or else the first one listing
+    `key` among its `ids` aliases.
+    */
+    pub fn get(&self, key: &str) -> Option<&Entry> {
+        self.position(key).map(|i| &self.entries[i])
+    }
+
+    fn position(&self, key: &str) -> Option<usize> {
+        match self.keys.get(key) {
+            Some(i) => Some(*i),
+            None => self.entries.iter().position(|e| e.ids().contains(&key)),
+        }
+    }
+
+    /**
+    A new bibliography with the entries for `keys` (citation keys or `ids`
+    aliases) and, recursively, those they reference in `KEY_REFERENCES`
+    fields. Each entry is copied once, in the order of this bibliography;
+    the preambles are copied too, since the entries may use their macros.
+    */
+    pub fn extract_for<S: AsRef<str>>(&self, keys: &[S]) -> Extract {
+        let mut extract = Extract::default();
+        let mut selected = vec![false; self.entries.len()];
+        let mut pending: Vec<String> = keys.iter().rev().map(|k| String::from(k.as_ref().trim())).collect();
+        while let Some(key) = pending.pop() {
+            if key.is_empty() {
+                continue;
+            }
+            let Some(i) = self.position(&key) else {
+                if !extract.missing.contains(&key) {
+                    extract.missing.push(key);
+                }
+                continue;
+            };
+            if selected[i] {
+                continue;
+            }
+            selected[i] = true;
+            for field in KEY_REFERENCES {
+                if let Some(value) = self.entries[i].get(field) {
+                    pending.extend(value.split(',').rev().map(|k| String::from(k.trim())));
+                }
+            }
+        }
+        let entries = self.entries.iter().zip(selected).filter(|(_, s)| *s).map(|(e, _)| e.clone()).collect();
+        extract.bibliography = Bibliography::from_entries(entries);
+        extract.bibliography.preambles = self.preambles.clone();
+        extract
+    }
+
+    /**
+    The first entry with `doi`, compared case-insensitively.
+    */
+    pub fn get_by_doi(&self, doi: &str) -> Option<&Entry> {
+        self.dois.get(&doi.trim().to_lowercase()).map(|i| &self.entries[*i])
+    }
+
+    pub fn push(&mut self, entry: Entry) {
+        self.journal.extend(diff(&[], std::slice::from_ref(&entry)));
+        self.entries.push(entry);
+        self.index(self.entries.len() - 1);
+    }
+
+    /**
+    Call `f` on every entry in order, then bring the indexes up to date and
+    journal what changed. Returns the keys of the changed entries.
+    */
+    pub fn visit_mut<F: FnMut(&mut Entry)>(&mut self, mut f: F) -> Vec<String> {
+        let mut changed = Vec::new();
+        let mut reindex = false;
+        for entry in self.entries.iter_mut() {
+            let before = entry.clone();
+            f(entry);
+            if self.pinned.iter().any(|k| k == before.key()) {
+                if entry.key() != before.key() {
+                    self.refused.push(format!("{}: pinned, so not renamed to `{}`", before.key(), entry.key()));
+                    entry.set_key(before.key());
+                }
+                if entry.entry_type() != before.entry_type() {
+                    self.refused.push(format!("{}: pinned, so not changed to @{}", before.key(), entry.entry_type().name()));
+                    entry.set_entry_type(before.entry_type().clone());
+                }
+            }
+            if entry.is_identical(&before) {
+                continue;
+            }
+            reindex |= entry.key() != before.key() || doi_of(entry) != doi_of(&before);
+            // a renamed entry is journaled as removed and added
+            self.journal.extend(diff(std::slice::from_ref(&before), std::slice::from_ref(entry)));
+            changed.push(String::from(entry.key()));
+        }
+        if reindex {
+            self.reindex();
+        }
+        changed
+    }
+
+    /**
+    Rename field `from` to `to` in every entry, passing its value through
+    `map` (`String::from` keeps it as it is); the changes are journaled like
+    those of `visit_mut`. The field keeps its place. An entry that already
+    has `to` only loses `from` if the mapped value is the same; otherwise it
+    is left alone and listed in `conflicts`.
+    */
+    pub fn rename_field<F: FnMut(&str) -> String>(&mut self, from: &str, to: &str, mut map: F) -> FieldRename {
+        let mut conflicts = Vec::new();
+        let renamed = self.visit_mut(|e| {
+            let Some(value) = e.get(from).map(&mut map) else { return };
+            if from.eq_ignore_ascii_case(to) {
+                e.set(from, &value);
+            } else if e.rename(from, to) {
+                e.set(to, &value);
+            } else if e.get(to) == Some(value.as_str()) {
+                e.remove(from);
+            } else {
+                conflicts.push(String::from(e.key()));
+            }
+        });
+        FieldRename { renamed, conflicts }
+    }
+
+    /**
+    Remove the entries for which `keep` is false, journaling their removal.
+    Pinned entries are kept.
+    */
+    pub fn retain<F: FnMut(&Entry) -> bool>(&mut self, mut keep: F) {
+        let (pinned, refused) = (&self.pinned, &mut self.refused);
+        let (kept, removed): (Vec<Entry>, Vec<Entry>) = std::mem::take(&mut self.entries).into_iter().partition(|e| {
+            let keep = keep(e);
+            if !keep && pinned.iter().any(|k| k == e.key()) {
+                refused.push(format!("{}: pinned, so not removed", e.key()));
+                return true;
+            }
+            keep
+        });
+        self.journal.extend(diff(&removed, &[]));
+        self.entries = kept;
+        self.reindex();
+    }
+
+    /**
+    Changes made since the bibliography was created or the journal taken.
+    */
+    pub fn journal(&self) -> &[LibraryEvent] {
+        &self.journal
+    }
+
+    pub fn take_journal(&mut self) -> Vec<LibraryEvent> {
+        std::mem::take(&mut self.journal)
+    }
+
+    /**
+    Protect the entry with citation key `key` from being renamed, given
+    another type or removed.
+    */
+    pub fn pin(&mut self, key: &str) {
+        if !self.is_pinned(key) {
+            self.pinned.push(String::from(key));
+        }
+    }
+
+    pub fn is_pinned(&self, key: &str) -> bool {
+        self.pinned.iter().any(|k| k == key)
+    }
+
+    /**
+    What `visit_mut` and `retain` refused to do to pinned entries since the
+    last call, one message per change.
+    */
+    pub fn take_refused(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.refused)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::bibtex::data::BibType;
+    use crate::events::EventKind;
+
+    #[test]
+    fn test_parse() {
+        let bib = Bibliography::parse("@misc{a,\n  title = {A}\n}\n@misc{b,\n  title = {B}\n}").unwrap();
+        assert_eq!(bib.len(), 2);
+        assert_eq!(bib.get("b").and_then(|e| e.get("title")), Some("B"));
+        assert!(bib.get("c").is_none());
+        let bib = Bibliography::parse("@misc{new,\n  ids = {old, older}\n}\n@misc{old,\n  title = {Real}\n}").unwrap();
+        assert_eq!(bib.get("older").map(|e| e.key()), Some("new"));
+        assert_eq!(bib.get("old").map(|e| e.key()), Some("old"));
+    }
+
+    #[test]
+    fn test_visit_mut() {
+        let mut bib = Bibliography::parse("@misc{a,\n  doi = {10.1/X}\n}\n@misc{b,\n  title = {B}\n}").unwrap();
+        assert_eq!(bib.get_by_doi("10.1/x").map(|e| e.key()), Some("a"));
+        let changed = bib.visit_mut(|e| if e.key() == "a" {
+            e.set_key("c");
+            e.set("doi", "10.2/y");
+        });
+        assert_eq!(changed, vec!["c"]);
+        assert!(bib.get("a").is_none());
+        assert_eq!(bib.get("c").and_then(|e| e.get("doi")), Some("10.2/y"));
+        assert_eq!(bib.get_by_doi("10.2/Y").map(|e| e.key()), Some("c"));
+        assert!(bib.get_by_doi("10.1/x").is_none());
+        let kinds: Vec<(EventKind, &str)> = bib.journal().iter().map(|e| (e.kind, e.key.as_str())).collect();
+        assert_eq!(kinds, vec![(EventKind::Added, "c"), (EventKind::Removed, "a")]);
+
+        bib.take_journal();
+        bib.retain(|e| e.key() != "b");
+        assert_eq!(bib.journal()[0].kind, EventKind::Removed);
+        assert!(bib.get("b").is_none());
+        bib.push(Entry::new(BibType::Misc, "b"));
+        assert_eq!(bib.get("b").map(|e| e.key()), Some("b"));
+    }
+
+    #[test]
+    fn test_pin() {
+        let mut bib = Bibliography::parse("@misc{a,\n  title = {A}\n}\n@misc{b,\n  title = {B}\n}").unwrap();
+        bib.pin("a");
+        let changed = bib.visit_mut(|e| {
+            e.set_key(&e.key().to_uppercase());
+            e.set_entry_type(BibType::Article);
+            e.set("note", "seen");
+        });
+        assert_eq!(changed, vec!["a", "B"]);
+        assert_eq!(bib.get("a").map(|e| (e.entry_type(), e.get("note"))), Some((&BibType::Misc, Some("seen"))));
+        bib.retain(|_| false);
+        let keys: Vec<&str> = bib.entries().iter().map(|e| e.key()).collect();
+        assert_eq!(keys, vec!["a"]);
+        assert_eq!(bib.take_refused(), vec!["a: pinned, so not renamed to `A`", "a: pinned, so not changed to @article", "a: pinned, so not removed"]);
+        assert!(bib.take_refused().is_empty());
+    }
+
+    #[test]
+    fn test_rename_field() {
+        let mut bib = Bibliography::parse("@misc{a,\n  adsurl = {http://x.org},\n  year = {2001}\n}\n\
+            @misc{b,\n  url = {https://x.org},\n  adsurl = {http://x.org}\n}\n\
+            @misc{c,\n  url = {https://y.org},\n  adsurl = {http://z.org}\n}\n@misc{d,\n  year = {2002}\n}").unwrap();
+        let rename = bib.rename_field("adsurl", "url", |v| v.replacen("http://", "https://", 1));
+        assert_eq!(rename.renamed, vec!["a", "b"]);
+        assert_eq!(rename.conflicts, vec!["c"]);
+        assert_eq!(bib.get("a").unwrap().field_names(), vec!["url", "year"]);
+        assert_eq!(bib.get("a").and_then(|e| e.get("url")), Some("https://x.org"));
+        assert_eq!(bib.get("b").unwrap().field_names(), vec!["url"]);
+        assert!(bib.get("c").unwrap().has("adsurl"));
+        assert_eq!(bib.journal().len(), 2);
+        assert_eq!(bib.rename_field("year", "year", |v| format!("{{{}}}", v)).renamed, vec!["a", "d"]);
+    }
+
+    #[test]
+    fn test_extract_for() {
+        let bib = Bibliography::parse("@string{acm = {ACM}}\n@preamble{\"\\newcommand{\\noopsort}[1]{}\"}\n\
+            @proceedings{popl84,\n  publisher = acm\n}\n@misc{other,\n  title = {O}\n}\n\
+            @inproceedings{paper,\n  crossref = {popl84},\n  related = {errata, gone}\n}\n\
+            @misc{errata,\n  ids = {fix},\n  xref = {paper}\n}").unwrap();
+        let extract = bib.extract_for(&["fix", "paper", "nope"]);
+        let keys: Vec<&str> = extract.bibliography.entries().iter().map(|e| e.key()).collect();
+        assert_eq!(keys, vec!["popl84", "paper", "errata"]);
+        assert_eq!(extract.missing, vec!["gone", "nope"]);
+        assert_eq!(extract.bibliography.preambles(), bib.preambles());
+        assert!(bib.extract_for::<&str>(&[]).bibliography.is_empty());
+    }
+
+    #[test]
+    fn test_load_dir() {
+        let dir = std::env::temp_dir().join(format!("perscrutar-load-dir-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        for sub in ["papers/2013", ".git"] {
+            std::fs::create_dir_all(dir.join(sub)).unwrap();
+        }
+        std::fs::write(dir.join("main.bib"), "@misc{a,\n  title = {A}\n}\n").unwrap();
+        std::fs::write(dir.join("notes.txt"), "@misc{n,\n  title = {N}\n}\n").unwrap();
+        std::fs::write(dir.join("papers/2013/cox.BIB"), "@book{cox,\n  title = {Primes}\n}\n@misc{a,\n  title = {Again}\n}\n").unwrap();
+        std::fs::write(dir.join("papers/broken.bib"), "@misc{").unwrap();
+        std::fs::write(dir.join(".git/stray.bib"), "@misc{g,\n  title = {G}\n}\n").unwrap();
+
+        let flat = Bibliography::load_dir(&dir, false).unwrap();
+        assert_eq!(flat.files, vec![dir.join("main.bib")]);
+        let load = Bibliography::load_dir(&dir, true).unwrap();
+        let keys: Vec<&str> = load.bibliography.entries().iter().map(|e| e.key()).collect();
+        assert_eq!(keys, vec!["a", "cox", "a"]);
+        assert_eq!(load.bibliography.get("a").and_then(|e| e.get("title")), Some("A"));
+        assert_eq!(load.errors.iter().map(|(p, _)| p.clone()).collect::<Vec<_>>(), vec![dir.join("papers/broken.bib")]);
+        assert_eq!(load.duplicates, vec![(String::from("a"), vec![dir.join("main.bib"), dir.join("papers/2013/cox.BIB")])]);
+        assert_eq!(Bibliography::load_dir_matching(&dir, false, "*.txt").unwrap().bibliography.len(), 1);
+        assert!(matches!(Bibliography::load_dir(dir.join("missing"), true), Err(LoadError::Io(_))));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(feature = "net")]
+    #[test]
+    fn test_load_url() {
+        use crate::net::Response;
+
+        struct Fixed(u16, &'static str);
+        impl HttpClient for Fixed {
+            fn get(&self, _url: &str, _headers: &[(&str, &str)]) -> Result<Response, NetError> {
+                Ok(Response { status: self.0, headers: vec![], body: String::from(self.1) })
+            }
+        }
+
+        let bib = Bibliography::load_url(&Fixed(200, "@misc{a,\n  title = {A}\n}"), "https://x/lib.bib", None).unwrap();
+        assert_eq!(bib.len(), 1);
+        assert!(matches!(Bibliography::load_url(&Fixed(404, ""), "https://x/lib.bib", None), Err(LoadError::Net(_))));
+        assert!(matches!(Bibliography::load_url(&Fixed(200, "@misc{"), "https://x/lib.bib", None), Err(LoadError::Parse(_))));
+    }
+}
@@ -0,0 +1,185 @@
+/*!
+
+Completeness scoring for entries.
+
+Every entry type has a set of weighted fields; the score is the weight of the
+fields present divided by the total weight, so `1.0` means nothing useful is
+missing. Fields listed together (`author|editor`) count once if any of them
+is present. Empty values count as missing.
+
+*/
+
+use std::collections::HashMap;
+use crate::bibtex::data::{BibType, Entry};
+
+#[derive(Debug, Clone)]
+pub struct CompletenessWeights {
+    common: Vec<(String, f64)>,
+    per_type: HashMap<String, Vec<(String, f64)>>,
+}
+
+fn weights(list: &[(&str, f64)]) -> Vec<(String, f64)> {
+    list.iter().map(|(f, w)| (f.to_string(), *w)).collect()
+}
+
+impl CompletenessWeights {
+    /**
+    Weights without any fields; use `common` and `for_type` to fill them in.
+    */
+    pub fn empty() -> CompletenessWeights {
+        CompletenessWeights { common: Vec::new(), per_type: HashMap::new() }
+    }
+
+    /**
+    Set the weight of a field that matters for every entry type.
+    */
+    pub fn common(mut self, field: &str, weight: f64) -> CompletenessWeights {
+        set_weight(&mut self.common, field, weight);
+        self
+    }
+
+    /**
+    Set the weight of a field for one entry type only.
+    */
+    pub fn for_type(mut self, itemtype: &BibType, field: &str, weight: f64) -> CompletenessWeights {
+        let list = self.per_type.entry(itemtype.name().to_string()).or_default();
+        set_weight(list, field, weight);
+        self
+    }
+
+    fn fields_for(&self, itemtype: &BibType) -> Vec<(&str, f64)> {
+        let mut fields: Vec<(&str, f64)> = self.common.iter().map(|(f, w)| (f.as_str(), *w)).collect();
+        if let Some(specific) = self.per_type.get(itemtype.name()) {
+            for (f, w) in specific.iter() {
+                match fields.iter_mut().find(|(n, _)| n == f) {
+                    Some(existing) => existing.1 = *w,
+                    None => fields.push((f.as_str(), *w)),
+                }
+            }
+        }
+        fields
+    }
+
+    pub fn score(&self, entry: &Entry) -> f64 {
+        let fields = self.fields_for(entry.entry_type());
+        let total: f64 = fields.iter().map(|(_, w)| w).sum();
+        if total <= 0.0 {
+            return 1.0;
+        }
+        let present: f64 = fields.iter()
+            .filter(|(f, _)| is_present(entry, f))
+            .map(|(_, w)| w)
+            .sum();
+        present / total
+    }
+
+    /**
+    The weighted fields missing from `entry`, most important first.
+    */
+    pub fn missing<'a>(&'a self, entry: &Entry) -> Vec<&'a str> {
+        let mut missing: Vec<(&str, f64)> = self.fields_for(entry.entry_type())
+            .into_iter()
+            .filter(|(f, _)| !is_present(entry, f))
+            .collect();
+        missing.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+        missing.into_iter().map(|(f, _)| f).collect()
+    }
+}
+
+fn set_weight(list: &mut Vec<(String, f64)>, field: &str, weight: f64) {
+    let field = field.to_lowercase();
+    match list.iter_mut().find(|(f, _)| *f == field) {
+        Some(existing) => existing.1 = weight,
+        None => list.push((field, weight)),
+    }
+}
+
+fn is_present(entry: &Entry, alternatives: &str) -> bool {
+    alternatives.split('|')
+        .any(|f| entry.get(f).map(|v| !v.trim().is_empty()).unwrap_or(false))
+}
+
+impl Default for CompletenessWeights {
+    fn default() -> Self {
+        let mut w = CompletenessWeights::empty();
+        w.common = weights(&[
+            ("author|editor", 3.0), ("title", 3.0), ("year", 3.0),
+            ("doi", 2.0), ("url", 0.5), ("abstract", 1.0), ("keywords", 0.5),
+        ]);
+        let specific: [(BibType, &[(&str, f64)]); 8] = [
+            (BibType::Article, &[("journal", 3.0), ("volume", 1.0), ("number", 0.5), ("pages", 1.5)]),
+            (BibType::Book, &[("publisher", 2.0), ("isbn", 1.5), ("address", 0.5), ("edition", 0.5)]),
+            (BibType::InCollection, &[("booktitle", 3.0), ("editor", 1.0), ("publisher", 1.5), ("pages", 1.0)]),
+            (BibType::InProceedings, &[("booktitle", 3.0), ("publisher", 1.0), ("pages", 1.5)]),
+            (BibType::Report, &[("institution", 2.0), ("number", 1.0)]),
+            (BibType::Thesis, &[("institution|school", 2.0), ("type", 0.5)]),
+            (BibType::PhdThesis, &[("school|institution", 2.0)]),
+            (BibType::MastersThesis, &[("school|institution", 2.0)]),
+        ];
+        for (itemtype, list) in specific.iter() {
+            w.per_type.insert(itemtype.name().to_string(), weights(list));
+        }
+        w.per_type.insert(BibType::Misc.name().to_string(),
+            weights(&[("howpublished", 1.0), ("url", 1.5), ("doi", 1.0)]));
+        w
+    }
+}
+
+impl Entry {
+    /**
+    Completeness of this entry between `0.0` and `1.0` using the default
+    weights. Sorting by this score surfaces the entries most in need of
+    attention.
+    */
+    pub fn completeness_score(&self) -> f64 {
+        CompletenessWeights::default().score(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_score_article() {
+        let mut e = Entry::new(BibType::Article, "a");
+        assert_eq!(e.completeness_score(), 0.0);
+
+        e.set("author", "Cox, David A.");
+        e.set("title", "Primes");
+        e.set("year", "2013");
+        let partial = e.completeness_score();
+        assert!(partial > 0.0 && partial < 1.0);
+
+        e.set("doi", "10.1002/9781118400722");
+        assert!(e.completeness_score() > partial);
+
+        let weights = CompletenessWeights::default();
+        let missing = weights.missing(&e);
+        assert_eq!(missing[0], "journal");
+        assert!(missing.contains(&"pages"));
+    }
+
+    #[test]
+    fn test_alternatives_and_empty() {
+        let w = CompletenessWeights::empty()
+            .common("author|editor", 1.0)
+            .common("title", 1.0);
+        let mut e = Entry::new(BibType::Book, "b");
+        e.set("editor", "Knuth, Donald E.");
+        e.set("title", "  ");
+        assert_eq!(w.score(&e), 0.5);
+    }
+
+    #[test]
+    fn test_type_override() {
+        let w = CompletenessWeights::empty()
+            .common("doi", 1.0)
+            .for_type(&BibType::Misc, "doi", 0.0)
+            .for_type(&BibType::Misc, "url", 1.0);
+        let mut e = Entry::new(BibType::Misc, "m");
+        e.set("url", "https://example.org");
+        assert_eq!(w.score(&e), 1.0);
+    }
+}
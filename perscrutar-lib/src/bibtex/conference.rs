@@ -0,0 +1,284 @@
+/*!
+
+Structured conference names.
+
+Proceedings titles are written in many ways for the same event:
+
+```text
+Proceedings of the 30th Annual ACM Symposium on Theory of Computing (STOC '98)
+Proc. Thirtieth Annual ACM Symposium on Theory of Computing, 1998
+STOC 1998: 30th Annual ACM Symposium on Theory of Computing
+```
+
+`Conference::parse` recovers the venue name, the ordinal, the year and the
+acronym from such a `booktitle`; `check_entry` reports a year in the
+booktitle that contradicts the `year` field, and `normalize_booktitle`
+rewrites the booktitle from a template, by default `CANONICAL`.
+
+Templates use `{name}`, `{ordinal}`, `{year}` and `{acronym}`; text in
+square brackets is dropped unless all its placeholders have a value.
+
+*/
+
+use crate::bibtex::data::Entry;
+use crate::lint::{Diagnostic, Severity};
+
+pub const CANONICAL: &str = "Proceedings of the [{ordinal} ]{name}[ ({acronym}[ {year}])]";
+
+const PREFIXES: [&str; 6] = ["proceedings of the ", "proceedings of ", "proceedings ", "proc. of the ", "proc. of ", "proc. "];
+
+const ORDINAL_WORDS: [&str; 20] = [
+    "first", "second", "third", "fourth", "fifth", "sixth", "seventh", "eighth", "ninth", "tenth",
+    "eleventh", "twelfth", "thirteenth", "fourteenth", "fifteenth", "sixteenth", "seventeenth",
+    "eighteenth", "nineteenth", "twentieth",
+];
+
+const TENS_WORDS: [(&str, u32); 8] = [
+    ("twenty", 20), ("thirty", 30), ("forty", 40), ("fifty", 50),
+    ("sixty", 60), ("seventy", 70), ("eighty", 80), ("ninety", 90),
+];
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Conference {
+    pub name: String,
+    pub ordinal: Option<u32>,
+    pub year: Option<u32>,
+    pub acronym: Option<String>,
+}
+
+/** `30th`, `Thirtieth`, `Twenty-First`. */
+fn parse_ordinal(word: &str) -> Option<u32> {
+    let lower = word.to_lowercase();
+    let digits = lower.trim_end_matches(|c: char| c.is_alphabetic());
+    if !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit()) {
+        let suffix = &lower[digits.len()..];
+        return if ["st", "nd", "rd", "th"].contains(&suffix) { digits.parse().ok() } else { None };
+    }
+    if let Some(i) = ORDINAL_WORDS.iter().position(|w| *w == lower) {
+        return Some(i as u32 + 1);
+    }
+    if let Some(tens) = TENS_WORDS.iter().find(|(w, _)| lower == format!("{}ieth", w.trim_end_matches('y'))) {
+        return Some(tens.1);
+    }
+    let (tens, unit) = lower.split_once('-')?;
+    let tens = TENS_WORDS.iter().find(|(w, _)| *w == tens)?.1;
+    let unit = ORDINAL_WORDS.iter().position(|w| *w == unit).filter(|u| *u < 9)? as u32 + 1;
+    Some(tens + unit)
+}
+
+pub fn ordinal(n: u32) -> String {
+    let suffix = match (n % 10, n % 100) {
+        (_, 11..=13) => "th",
+        (1, _) => "st",
+        (2, _) => "nd",
+        (3, _) => "rd",
+        _ => "th",
+    };
+    format!("{}{}", n, suffix)
+}
+
+/** `1998`, or `'98` read as 1998 (before 50) or 2000s. */
+fn parse_year(word: &str) -> Option<u32> {
+    if let Some(short) = word.strip_prefix('\'').or_else(|| word.strip_prefix('’')) {
+        let yy: u32 = short.parse().ok().filter(|_| short.len() == 2)?;
+        return Some(if yy >= 50 { 1900 + yy } else { 2000 + yy });
+    }
+    let year: u32 = word.parse().ok().filter(|_| word.len() == 4)?;
+    if (1900..2100).contains(&year) { Some(year) } else { None }
+}
+
+fn is_acronym(word: &str) -> bool {
+    word.chars().filter(|c| c.is_uppercase()).count() >= 2
+        && word.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '+')
+}
+
+impl Conference {
+    pub fn parse(booktitle: &str) -> Conference {
+        let mut conf = Conference::default();
+        let text: String = booktitle.chars().filter(|c| *c != '{' && *c != '}').collect();
+        let mut text = text.split_whitespace().collect::<Vec<&str>>().join(" ");
+        // `(STOC '98)` or a leading `STOC 1998:`
+        if let Some(open) = text.rfind('(') {
+            if let Some(close) = text[open..].find(')').map(|c| open + c) {
+                let inner: Vec<String> = text[open + 1..close].split_whitespace().map(String::from).collect();
+                if inner.iter().all(|w| is_acronym(w) || parse_year(w).is_some()) {
+                    for w in inner {
+                        match parse_year(&w) {
+                            Some(y) => conf.year = Some(y),
+                            None => conf.acronym = Some(w),
+                        }
+                    }
+                    text.replace_range(open..=close, "");
+                }
+            }
+        }
+        if let Some((head, rest)) = text.split_once(": ") {
+            let words: Vec<&str> = head.split_whitespace().collect();
+            if words.len() <= 2 && words.first().map(|w| is_acronym(w)).unwrap_or(false)
+                && words.iter().skip(1).all(|w| parse_year(w).is_some()) {
+                conf.acronym = Some(String::from(words[0]));
+                conf.year = conf.year.or_else(|| words.get(1).and_then(|w| parse_year(w)));
+                text = String::from(rest);
+            }
+        }
+        let lower = text.to_lowercase();
+        if let Some(p) = PREFIXES.iter().find(|p| lower.starts_with(*p)) {
+            text = String::from(&text[p.len()..]);
+        }
+        let mut words = Vec::new();
+        for word in text.split_whitespace() {
+            let bare = word.trim_end_matches([',', '.', ';']);
+            if conf.ordinal.is_none() && words.is_empty() {
+                if let Some(n) = parse_ordinal(bare) {
+                    conf.ordinal = Some(n);
+                    continue;
+                }
+            }
+            if let Some(y) = parse_year(bare) {
+                conf.year = conf.year.or(Some(y));
+                continue;
+            }
+            words.push(word);
+        }
+        let name = words.join(" ");
+        conf.name = String::from(name.trim_matches([',', ' ', ':', '-']).trim_start_matches("the "));
+        conf
+    }
+
+    fn value(&self, placeholder: &str) -> Option<String> {
+        match placeholder {
+            "name" => Some(self.name.clone()).filter(|n| !n.is_empty()),
+            "ordinal" => self.ordinal.map(ordinal),
+            "year" => self.year.map(|y| y.to_string()),
+            "acronym" => self.acronym.clone(),
+            _ => None,
+        }
+    }
+
+    fn fill(&self, segment: &str, optional: bool) -> Option<String> {
+        let mut out = String::new();
+        let mut rest = segment;
+        while let Some(open) = rest.find('{') {
+            out.push_str(&rest[..open]);
+            let Some(close) = rest[open..].find('}').map(|c| open + c) else { break };
+            match self.value(&rest[open + 1..close]) {
+                Some(v) => out.push_str(&v),
+                None if optional => return None,
+                None => {}
+            }
+            rest = &rest[close + 1..];
+        }
+        out.push_str(rest);
+        Some(out)
+    }
+
+    fn render_segment(&self, template: &str, optional: bool) -> Option<String> {
+        let mut out = String::new();
+        let mut rest = template;
+        while let Some(open) = rest.find('[') {
+            let mut depth = 0;
+            let close = rest[open..].char_indices().find_map(|(i, c)| {
+                match c {
+                    '[' => depth += 1,
+                    ']' => {
+                        depth -= 1;
+                        if depth == 0 {
+                            return Some(open + i);
+                        }
+                    }
+                    _ => {}
+                }
+                None
+            });
+            let Some(close) = close else { break };
+            out.push_str(&self.fill(&rest[..open], optional)?);
+            if let Some(s) = self.render_segment(&rest[open + 1..close], true) {
+                out.push_str(&s);
+            }
+            rest = &rest[close + 1..];
+        }
+        out.push_str(&self.fill(rest, optional)?);
+        Some(out)
+    }
+
+    /**
+    Render a booktitle from `template`; see the module documentation.
+    */
+    pub fn render(&self, template: &str) -> String {
+        self.render_segment(template, false).unwrap_or_default()
+    }
+}
+
+/**
+Report a year in the booktitle of a conference paper that differs from the
+entry's `year`.
+*/
+pub fn check_entry(entry: &Entry) -> Vec<Diagnostic> {
+    let (Some(booktitle), Some(year)) = (entry.get("booktitle"), entry.get("year")) else { return vec![] };
+    let Ok(year) = year.trim().parse::<u32>() else { return vec![] };
+    match Conference::parse(booktitle).year {
+        Some(y) if y != year => vec![Diagnostic::new(entry.key(), "booktitle-year", Severity::Warning,
+            &format!("booktitle says {} but year is {}", y, year))],
+        _ => vec![],
+    }
+}
+
+/**
+Rewrite the booktitle from `template`, filling in the year from the `year`
+field if the booktitle has none. Returns whether the entry changed.
+*/
+pub fn normalize_booktitle(entry: &mut Entry, template: &str) -> bool {
+    let Some(booktitle) = entry.get("booktitle") else { return false };
+    let mut conf = Conference::parse(booktitle);
+    if conf.name.is_empty() {
+        return false;
+    }
+    if conf.year.is_none() {
+        conf.year = entry.get("year").and_then(|y| y.trim().parse().ok());
+    }
+    let rendered = conf.render(template);
+    if rendered == booktitle {
+        return false;
+    }
+    entry.set("booktitle", &rendered);
+    true
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::bibtex::data::BibType;
+
+    const STOC: &str = "ACM Symposium on Theory of Computing";
+
+    #[test]
+    fn test_parse() {
+        for title in [
+            "Proceedings of the 30th Annual ACM Symposium on Theory of Computing (STOC '98)",
+            "Proc. Thirtieth Annual {ACM} Symposium on Theory of Computing, 1998",
+            "STOC 1998: 30th Annual ACM Symposium on Theory of Computing",
+        ] {
+            let c = Conference::parse(title);
+            assert_eq!(c.name, format!("Annual {}", STOC), "{}", title);
+            assert_eq!((c.ordinal, c.year), (Some(30), Some(1998)), "{}", title);
+        }
+        assert_eq!(parse_ordinal("Twenty-First"), Some(21));
+        assert_eq!(ordinal(112), "112th");
+        assert_eq!(Conference::parse("Workshop on Foo").ordinal, None);
+    }
+
+    #[test]
+    fn test_normalize() {
+        let mut e = Entry::new(BibType::InProceedings, "p");
+        e.set("booktitle", "Proc. 30th Annual ACM Symposium on Theory of Computing (STOC)");
+        e.set("year", "1997");
+        assert!(check_entry(&e).is_empty());
+        assert!(normalize_booktitle(&mut e, CANONICAL));
+        assert_eq!(e.get("booktitle"), Some("Proceedings of the 30th Annual ACM Symposium on Theory of Computing (STOC 1997)"));
+        assert!(!normalize_booktitle(&mut e, CANONICAL));
+        e.set("year", "1998");
+        assert_eq!(check_entry(&e)[0].rule, "booktitle-year");
+        assert_eq!(Conference::parse("Workshop").render("{name}[, {year}]"), "Workshop");
+    }
+}
@@ -0,0 +1,247 @@
+/*!
+
+Fields entries inherit from the entries they refer to.
+
+A paper in conference proceedings can leave the proceedings' editors,
+publisher and year to a `@proceedings` entry named in its `crossref`
+field. How the fields pass down depends on the `Inheritance`:
+
+- `BibTeX` copies every field the child does not have, under its own
+  name, as BibTeX does;
+- `Biblatex` follows biber's defaults: the parent's `title` becomes the
+  child's `booktitle` (or `maintitle`, `journaltitle`, depending on the
+  two types, see `RULES`), `shorttitle` and the like are not inherited,
+  and the fields of every `@xdata` entry named in `xdata` are copied too,
+  before those of the `crossref`.
+
+Neither inherits through `xref`, which only says that the child is part of
+the parent, nor the fields in `NEVER`. Chains of references are followed,
+and a cycle ends the chain. A child's own fields always win.
+
+`Bibliography::inherited` looks up one field as the child sees it,
+without copying anything; `Bibliography::resolved` gives a copy of an
+entry with everything it inherits, and `Bibliography::resolve_crossrefs`
+does this to every entry, for tools that do not resolve references
+themselves. The reference fields are kept.
+
+*/
+
+use crate::bibtex::bibliography::Bibliography;
+use crate::bibtex::data::Entry;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Inheritance {
+    /** `crossref` only, every field under its own name. */
+    #[default]
+    BibTeX,
+    /** `crossref` with biber's default `RULES`, and `xdata`. */
+    Biblatex,
+}
+
+/** Fields that are never inherited, biber's defaults. */
+pub const NEVER: [&str; 17] = [
+    "crossref", "entryset", "entrysubtype", "execute", "ids", "label", "options", "presort", "related",
+    "relatedoptions", "relatedstring", "relatedtype", "shorthand", "shorthandintro", "sortkey", "xdata",
+    "xref",
+];
+
+/** A parent field and the child fields it becomes; none means it is not inherited. */
+type Mapping = (&'static str, &'static [&'static str]);
+
+const MAIN_TITLES: [Mapping; 7] = [
+    ("title", &["maintitle"]), ("subtitle", &["mainsubtitle"]), ("titleaddon", &["maintitleaddon"]),
+    ("shorttitle", &[]), ("sorttitle", &[]), ("indextitle", &[]), ("indexsorttitle", &[]),
+];
+
+const BOOK_TITLES: [Mapping; 7] = [
+    ("title", &["booktitle"]), ("subtitle", &["booksubtitle"]), ("titleaddon", &["booktitleaddon"]),
+    ("shorttitle", &[]), ("sorttitle", &[]), ("indextitle", &[]), ("indexsorttitle", &[]),
+];
+
+const JOURNAL_TITLES: [Mapping; 7] = [
+    ("title", &["journaltitle"]), ("subtitle", &["journalsubtitle"]), ("titleaddon", &["journaltitleaddon"]),
+    ("shorttitle", &[]), ("sorttitle", &[]), ("indextitle", &[]), ("indexsorttitle", &[]),
+];
+
+/**
+biber's default inheritance for `Inheritance::Biblatex`: parent types,
+child types, and how fields map between them. Fields no rule for the two
+types mentions are inherited under their own name.
+*/
+pub const RULES: [(&[&str], &[&str], &[Mapping]); 8] = [
+    (&["mvbook", "book"], &["inbook", "bookinbook", "suppbook"], &[("author", &["author", "bookauthor"])]),
+    (&["mvbook"], &["book", "inbook", "bookinbook", "suppbook"], &MAIN_TITLES),
+    (&["mvcollection", "mvreference"], &["collection", "reference", "incollection", "inreference", "suppcollection"],
+        &MAIN_TITLES),
+    (&["mvproceedings"], &["proceedings", "inproceedings"], &MAIN_TITLES),
+    (&["book"], &["inbook", "bookinbook", "suppbook"], &BOOK_TITLES),
+    (&["collection", "reference"], &["incollection", "inreference", "suppcollection"], &BOOK_TITLES),
+    (&["proceedings"], &["inproceedings"], &BOOK_TITLES),
+    (&["periodical"], &["article", "suppperiodical"], &JOURNAL_TITLES),
+];
+
+/** The mappings of the rules for a `parent` and `child` of these types. */
+fn mappings(parent: &Entry, child: &Entry, inheritance: Inheritance) -> Vec<Mapping> {
+    if inheritance == Inheritance::BibTeX {
+        return Vec::new();
+    }
+    let (parent, child) = (parent.entry_type().name(), child.entry_type().name());
+    RULES.iter()
+        .filter(|(parents, children, _)| parents.contains(&parent) && children.contains(&child))
+        .flat_map(|(_, _, mappings)| mappings.iter().copied())
+        .collect()
+}
+
+/** The child fields the `parent` field `field` becomes. */
+fn targets<'a>(mappings: &[Mapping], field: &'a str) -> Vec<&'a str> {
+    let mapped: Vec<Mapping> = mappings.iter().copied().filter(|(source, _)| *source == field).collect();
+    match mapped.is_empty() {
+        true => vec![field],
+        false => mapped.iter().flat_map(|(_, targets)| targets.iter().copied()).collect(),
+    }
+}
+
+/** The parent fields that become the child field `field`, in the order tried. */
+fn sources<'a>(mappings: &[Mapping], field: &'a str) -> Vec<&'a str> {
+    let mut out: Vec<&str> = mappings.iter().filter(|(_, targets)| targets.contains(&field)).map(|(source, _)| *source).collect();
+    if !mappings.iter().any(|(source, _)| *source == field) {
+        out.push(field);
+    }
+    out
+}
+
+impl Bibliography {
+    /** The entries named in `entry`'s reference field `field`. */
+    fn referenced(&self, entry: &Entry, field: &str) -> Vec<&Entry> {
+        entry.get(field).into_iter()
+            .flat_map(|keys| keys.split(','))
+            .filter_map(|key| self.get(key.trim()))
+            .collect()
+    }
+
+    /**
+    The value of `field` for `entry`: its own, or else what it inherits.
+    Nothing is copied.
+    */
+    pub fn inherited<'a>(&'a self, entry: &'a Entry, field: &str, inheritance: Inheritance) -> Option<&'a str> {
+        self.lookup(entry, &field.to_lowercase(), inheritance, &mut Vec::new())
+    }
+
+    fn lookup<'a>(&'a self, entry: &'a Entry, field: &str, inheritance: Inheritance, path: &mut Vec<&'a str>)
+        -> Option<&'a str> {
+        if let Some(value) = entry.get(field) {
+            return Some(value);
+        }
+        if NEVER.contains(&field) || path.contains(&entry.key()) {
+            return None;
+        }
+        path.push(entry.key());
+        let mut found = None;
+        if inheritance == Inheritance::Biblatex {
+            found = self.referenced(entry, "xdata").into_iter().find_map(|data| self.lookup(data, field, inheritance, path));
+        }
+        if found.is_none() {
+            if let Some(parent) = self.referenced(entry, "crossref").into_iter().next() {
+                found = sources(&mappings(parent, entry, inheritance), field).into_iter()
+                    .find_map(|source| self.lookup(parent, source, inheritance, path));
+            }
+        }
+        path.pop();
+        found
+    }
+
+    /** A copy of `entry` with every field it inherits added after its own. */
+    pub fn resolved(&self, entry: &Entry, inheritance: Inheritance) -> Entry {
+        self.resolve(entry, inheritance, &mut Vec::new())
+    }
+
+    fn resolve<'a>(&'a self, entry: &'a Entry, inheritance: Inheritance, path: &mut Vec<&'a str>) -> Entry {
+        let mut out = entry.clone();
+        if path.contains(&entry.key()) {
+            return out;
+        }
+        path.push(entry.key());
+        let mut parents: Vec<(&Entry, Vec<Mapping>)> = Vec::new();
+        if inheritance == Inheritance::Biblatex {
+            parents.extend(self.referenced(entry, "xdata").into_iter().map(|data| (data, Vec::new())));
+        }
+        if let Some(parent) = self.referenced(entry, "crossref").into_iter().next() {
+            parents.push((parent, mappings(parent, entry, inheritance)));
+        }
+        for (parent, mappings) in parents {
+            let parent = self.resolve(parent, inheritance, path);
+            for (name, value) in parent.fields() {
+                for target in targets(&mappings, name) {
+                    if !NEVER.contains(&name) && !NEVER.contains(&target) && !out.has(target) {
+                        out.set(target, value);
+                    }
+                }
+            }
+        }
+        path.pop();
+        out
+    }
+
+    /**
+    Add to every entry the fields it inherits, as `resolved`, journaling the
+    changes. Returns the keys of the entries that changed.
+    */
+    pub fn resolve_crossrefs(&mut self, inheritance: Inheritance) -> Vec<String> {
+        let mut resolved = self.entries().iter().map(|e| self.resolved(e, inheritance)).collect::<Vec<Entry>>().into_iter();
+        self.visit_mut(|e| {
+            if let Some(r) = resolved.next() {
+                *e = r;
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::bibtex::parser::parse_bibliography;
+
+    #[test]
+    fn test_inherited() {
+        let bib = parse_bibliography("@inproceedings{paper, title = {Paper}, crossref = {popl84}, xdata = {acm}}\n\
+            @proceedings{popl84, title = {POPL 1984}, shorttitle = {POPL}, year = 1984, crossref = {popl}, ids = {p84}}\n\
+            @mvproceedings{popl, title = {Principles of Programming Languages}}\n\
+            @xdata{acm, publisher = {ACM}, year = 1983}\n\
+            @misc{loop, crossref = {loop2}}\n@misc{loop2, crossref = {loop}}").unwrap();
+        let paper = bib.get("paper").unwrap();
+        let field = |name: &str, inheritance: Inheritance| bib.inherited(paper, name, inheritance);
+
+        assert_eq!(field("title", Inheritance::BibTeX), Some("Paper"));
+        assert_eq!(field("Year", Inheritance::BibTeX), Some("1984"));
+        assert_eq!(field("booktitle", Inheritance::BibTeX), None);
+        assert_eq!(field("shorttitle", Inheritance::BibTeX), Some("POPL"));
+        assert_eq!(field("publisher", Inheritance::BibTeX), None);
+        assert_eq!(field("ids", Inheritance::BibTeX), None);
+
+        assert_eq!(field("booktitle", Inheritance::Biblatex), Some("POPL 1984"));
+        assert_eq!(field("maintitle", Inheritance::Biblatex), Some("Principles of Programming Languages"));
+        assert_eq!(field("shorttitle", Inheritance::Biblatex), None);
+        assert_eq!(field("publisher", Inheritance::Biblatex), Some("ACM"));
+        assert_eq!(field("year", Inheritance::Biblatex), Some("1983"));
+        assert_eq!(bib.inherited(bib.get("loop").unwrap(), "title", Inheritance::BibTeX), None);
+
+        let resolved = bib.resolved(paper, Inheritance::Biblatex);
+        let fields: Vec<&str> = resolved.field_names();
+        assert_eq!(fields, vec!["title", "crossref", "xdata", "publisher", "year", "booktitle", "maintitle"]);
+        for name in fields {
+            assert_eq!(resolved.get(name), field(name, Inheritance::Biblatex));
+        }
+    }
+
+    #[test]
+    fn test_resolve_crossrefs() {
+        let mut bib = parse_bibliography("@inproceedings{paper, crossref = {procs}, year = 2001}\n\
+            @proceedings{procs, title = {P}, year = 2000}\n@misc{loop, crossref = {loop}}").unwrap();
+        assert_eq!(bib.resolve_crossrefs(Inheritance::BibTeX), vec!["paper"]);
+        let paper = bib.get("paper").unwrap();
+        assert_eq!((paper.get("title"), paper.get("year"), paper.get("crossref")), (Some("P"), Some("2001"), Some("procs")));
+        assert_eq!(bib.journal().len(), 1);
+        assert!(bib.resolve_crossrefs(Inheritance::BibTeX).is_empty());
+    }
+}
@@ -1,6 +1,11 @@
 
-use std::collections::HashMap;
+use core::cmp::Ordering;
+use core::fmt;
+use core::hash::{Hash, Hasher};
+use alloc::string::String;
+use alloc::vec::Vec;
 
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum BibType {
     Article,
     Book,
@@ -11,9 +16,360 @@ pub enum BibType {
     Thesis,
     PhdThesis,
     MastersThesis,
+    Other(String),
 }
 
-pub struct Entry<'a> {
+impl BibType {
+    /**
+    Resolve an entry type name as written after the `@`. Matching is
+    case-insensitive and accepts the usual aliases (`techreport`,
+    `conference`); anything else is kept as `Other` in lowercase.
+    */
+    pub fn parse(name: &str) -> BibType {
+        let lower = name.trim().to_lowercase();
+        match lower.as_str() {
+            "article" => BibType::Article,
+            "book" => BibType::Book,
+            "incollection" => BibType::InCollection,
+            "inproceedings" | "conference" => BibType::InProceedings,
+            "misc" => BibType::Misc,
+            "report" | "techreport" => BibType::Report,
+            "thesis" => BibType::Thesis,
+            "phdthesis" => BibType::PhdThesis,
+            "mastersthesis" => BibType::MastersThesis,
+            _ => BibType::Other(lower),
+        }
+    }
+
+    /**
+    Canonical lowercase name, suitable for writing back after the `@`.
+    */
+    pub fn name(&self) -> &str {
+        match self {
+            BibType::Article => "article",
+            BibType::Book => "book",
+            BibType::InCollection => "incollection",
+            BibType::InProceedings => "inproceedings",
+            BibType::Misc => "misc",
+            BibType::Report => "techreport",
+            BibType::Thesis => "thesis",
+            BibType::PhdThesis => "phdthesis",
+            BibType::MastersThesis => "mastersthesis",
+            BibType::Other(name) => name,
+        }
+    }
+}
+
+impl From<&str> for BibType {
+    fn from(name: &str) -> Self {
+        BibType::parse(name)
+    }
+}
+
+impl fmt::Display for BibType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/**
+Fields whose values are taken character for character, as biblatex's
+`verbatim` and `uri` fields are: URLs, DOIs, file paths and the like,
+where `#`, `%`, `\\` and `~` are text and spaces cannot be moved. The
+parser reads them as standard BibTeX whatever the `Comments`, the writer
+never wraps them, and nothing that rewrites values should touch them.
+*/
+pub const VERBATIM_FIELDS: [&str; 9] = ["doi", "eprint", "file", "pdf", "url", "urlraw", "verba", "verbb", "verbc"];
+
+/** Whether `field` is one of the `VERBATIM_FIELDS`, in any case. */
+pub fn is_verbatim(field: &str) -> bool {
+    VERBATIM_FIELDS.iter().any(|f| f.eq_ignore_ascii_case(field))
+}
+
+/**
+A single bibliography entry. Field names are case-insensitive in BibTeX,
+so they are stored lowercased; values are kept as parsed. Fields keep the
+order in which they were added, which for parsed entries is the order of
+the file; `reorder` and `sort_fields` change it explicitly.
+
+An entry read as one of the aliases `BibType::parse` accepts, such as
+`@conference` or `@report`, keeps that spelling in `type_name`, so that it
+is written back as it was; `set_entry_type` converts it to the canonical
+name.
+
+A field whose value is a single macro name with no `@string` definition,
+such as `month = jan`, which BibTeX styles define themselves, holds that
+name and `is_macro` says so, so that it is written back bare; setting the
+field makes it an ordinary value.
+
+Equality is semantic: two entries are equal if they have the same type,
+key and fields, in any order, with values that differ at most in
+whitespace (outside the `VERBATIM_FIELDS`), so an entry equals itself
+reformatted. `is_identical` compares exactly. `Hash` and `Ord` agree with
+equality; entries order by key, then type, then fields.
+*/
+#[derive(Debug, Clone)]
+pub struct Entry {
     itemtype : BibType,
-    entries : HashMap<&'a str, &'a str>,
+    /** The type as written, when that is an alias of `itemtype`. */
+    alias : Option<String>,
+    key : String,
+    entries : Vec<(String, String)>,
+    /** Fields whose value is the name of an undefined macro. */
+    macros : Vec<String>,
+}
+
+impl Entry {
+    pub fn new(itemtype: BibType, key: &str) -> Entry {
+        Entry {
+            itemtype,
+            alias: None,
+            key: String::from(key),
+            entries: Vec::new(),
+            macros: Vec::new(),
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub fn set_key(&mut self, key: &str) {
+        self.key = String::from(key);
+    }
+
+    pub fn entry_type(&self) -> &BibType {
+        &self.itemtype
+    }
+
+    pub fn set_entry_type(&mut self, itemtype: BibType) {
+        self.itemtype = itemtype;
+        self.alias = None;
+    }
+
+    /** The type name to write after the `@`: as it was written, or the canonical one. */
+    pub fn type_name(&self) -> &str {
+        self.alias.as_deref().unwrap_or(self.itemtype.name())
+    }
+
+    /** Set the type from `name` as written, keeping the spelling of an alias. */
+    pub fn set_type_name(&mut self, name: &str) {
+        self.itemtype = BibType::parse(name);
+        let name = name.trim().to_lowercase();
+        self.alias = (name != self.itemtype.name()).then_some(name);
+    }
+
+    fn position(&self, field: &str) -> Option<usize> {
+        let field = field.to_lowercase();
+        self.entries.iter().position(|(k, _)| *k == field)
+    }
+
+    pub fn get(&self, field: &str) -> Option<&str> {
+        self.position(field).map(|i| self.entries[i].1.as_str())
+    }
+
+    pub fn has(&self, field: &str) -> bool {
+        self.position(field).is_some()
+    }
+
+    /**
+    Set a field, returning the previous value if there was one. A new field
+    is added after the existing ones; an existing one keeps its place.
+    */
+    pub fn set(&mut self, field: &str, value: &str) -> Option<String> {
+        self.macros.retain(|k| !k.eq_ignore_ascii_case(field));
+        match self.position(field) {
+            Some(i) => Some(core::mem::replace(&mut self.entries[i].1, String::from(value))),
+            None => {
+                self.entries.push((field.to_lowercase(), String::from(value)));
+                None
+            }
+        }
+    }
+
+    /**
+    Set a field to a reference to the macro `name`, which has no `@string`
+    definition, as in `month = jan`.
+    */
+    pub fn set_macro(&mut self, field: &str, name: &str) -> Option<String> {
+        let previous = self.set(field, name);
+        self.macros.push(field.to_lowercase());
+        previous
+    }
+
+    /**
+    Whether the value of `field` is the name of an undefined macro, to be
+    written without delimiters.
+    */
+    pub fn is_macro(&self, field: &str) -> bool {
+        self.macros.iter().any(|k| k.eq_ignore_ascii_case(field))
+    }
+
+    pub fn remove(&mut self, field: &str) -> Option<String> {
+        self.macros.retain(|k| !k.eq_ignore_ascii_case(field));
+        self.position(field).map(|i| self.entries.remove(i).1)
+    }
+
+    /**
+    Rename field `from` to `to` in place, keeping its value. Returns false,
+    changing nothing, if there is no `from` or there already is a `to`.
+    */
+    pub fn rename(&mut self, from: &str, to: &str) -> bool {
+        match (self.position(from), self.position(to)) {
+            (Some(i), None) => {
+                self.entries[i].0 = to.to_lowercase();
+                for k in self.macros.iter_mut().filter(|k| k.eq_ignore_ascii_case(from)) {
+                    *k = to.to_lowercase();
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /**
+    Move the fields named in `order` to the front, in that order; the other
+    fields follow in their current order. Unknown names are ignored.
+    */
+    pub fn reorder(&mut self, order: &[&str]) {
+        let rank = |k: &str| order.iter().position(|o| o.eq_ignore_ascii_case(k)).unwrap_or(order.len());
+        // stable, so unlisted fields keep their relative order
+        self.entries.sort_by_key(|(k, _)| rank(k));
+    }
+
+    /**
+    Put the fields in alphabetical order.
+    */
+    pub fn sort_fields(&mut self) {
+        self.entries.sort_by(|a, b| a.0.cmp(&b.0));
+    }
+
+    /**
+    Alternative citation keys from biblatex's `ids` field, e.g.
+    `ids = {cox2013, cox-primes}`.
+    */
+    pub fn ids(&self) -> Vec<&str> {
+        self.get("ids").map(|ids| ids.split(',').map(str::trim).filter(|k| !k.is_empty()).collect())
+            .unwrap_or_default()
+    }
+
+    /**
+    Whether `key` is the citation key of this entry or one of its `ids`.
+    */
+    pub fn is_known_as(&self, key: &str) -> bool {
+        self.key == key || self.ids().contains(&key)
+    }
+
+    /**
+    Fields with their values, in order.
+    */
+    pub fn fields(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.entries.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+
+    /**
+    Field names in order.
+    */
+    pub fn field_names(&self) -> Vec<&str> {
+        self.entries.iter().map(|(k, _)| k.as_str()).collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /**
+    Whether `other` has the same type, key and fields, in the same order
+    and with the same values, character for character, and the same macro
+    references.
+    */
+    pub fn is_identical(&self, other: &Entry) -> bool {
+        self.type_name() == other.type_name() && self.key == other.key && self.entries == other.entries
+            && self.fields().all(|(k, _)| self.is_macro(k) == other.is_macro(k))
+    }
+
+    /** The fields sorted by name, with whitespace collapsed outside verbatim fields. */
+    fn canonical_fields(&self) -> Vec<(&str, String)> {
+        let mut fields: Vec<(&str, String)> = self.fields().map(|(name, value)| {
+            let value = if is_verbatim(name) {
+                String::from(value)
+            } else {
+                value.split_whitespace().collect::<Vec<&str>>().join(" ")
+            };
+            (name, value)
+        }).collect();
+        fields.sort();
+        fields
+    }
+}
+
+impl PartialEq for Entry {
+    fn eq(&self, other: &Entry) -> bool {
+        self.itemtype == other.itemtype && self.key == other.key && self.canonical_fields() == other.canonical_fields()
+    }
+}
+
+impl Eq for Entry {}
+
+impl Hash for Entry {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.itemtype.hash(state);
+        self.key.hash(state);
+        self.canonical_fields().hash(state);
+    }
+}
+
+impl PartialOrd for Entry {
+    fn partial_cmp(&self, other: &Entry) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Entry {
+    fn cmp(&self, other: &Entry) -> Ordering {
+        self.key.cmp(&other.key)
+            .then_with(|| self.itemtype.cmp(&other.itemtype))
+            .then_with(|| self.canonical_fields().cmp(&other.canonical_fields()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use std::collections::{BTreeSet, HashSet};
+
+    #[test]
+    fn test_equality() {
+        let mut a = Entry::new(BibType::Article, "knuth84");
+        a.set("title", "Literate\n  Programming");
+        a.set("url", "https://example.org/a  b");
+        let mut b = Entry::new(BibType::Article, "knuth84");
+        b.set("url", "https://example.org/a  b");
+        b.set("title", " Literate Programming");
+        assert_eq!(a, b);
+        assert!(!a.is_identical(&b));
+        assert!(a.is_identical(&a.clone()));
+        assert_eq!(HashSet::from([a.clone(), b.clone()]).len(), 1);
+
+        b.set("url", "https://example.org/a b");
+        assert_ne!(a, b);
+        let earlier = Entry::new(BibType::Book, "cox2013");
+        let set = BTreeSet::from([a.clone(), b.clone(), earlier.clone()]);
+        assert_eq!(set.len(), 3);
+        assert_eq!(set.first(), Some(&earlier));
+
+        let mut report = Entry::new(BibType::Report, "r");
+        report.set_type_name("Report");
+        assert_eq!((report.entry_type(), report.type_name()), (&BibType::Report, "report"));
+        let techreport = Entry::new(BibType::Report, "r");
+        assert_eq!(report, techreport);
+        assert!(!report.is_identical(&techreport));
+        report.set_entry_type(BibType::Report);
+        assert_eq!(report.type_name(), "techreport");
+    }
 }
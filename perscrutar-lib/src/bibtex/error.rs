@@ -0,0 +1,137 @@
+use core::fmt;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/**
+Error produced when a .bib input cannot be parsed. Positions are given
+both as a byte offset and as 1-based line and column; `entry` is the key of
+the entry the error is in, if it got as far as a key.
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub offset: usize,
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+    pub entry: Option<String>,
+}
+
+impl ParseError {
+    pub fn at(input: &str, offset: usize, message: &str) -> ParseError {
+        let (line, column) = line_column(input, offset);
+        ParseError { offset, line, column, message: String::from(message), entry: None }
+    }
+
+    pub fn in_entry(mut self, key: &str) -> ParseError {
+        self.entry = Some(String::from(key));
+        self
+    }
+}
+
+/**
+Where a parsed entry is in the input: its bytes `start..end`, from the `@`
+to the closing delimiter, and the line and column of its start.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Span {
+    pub fn new(input: &str, start: usize, end: usize) -> Span {
+        let (line, column) = line_column(input, start);
+        Span { start, end, line, column }
+    }
+}
+
+/**
+1-based line and column (in characters) of byte `offset` in `input`.
+*/
+pub fn line_column(input: &str, offset: usize) -> (usize, usize) {
+    let offset = offset.min(input.len());
+    let before = &input[..offset];
+    let line = before.matches('\n').count() + 1;
+    let line_start = before.rfind('\n').map(|p| p + 1).unwrap_or(0);
+    (line, before[line_start..].chars().count() + 1)
+}
+
+/**
+Where the lines of an input start, to locate many offsets in it, such as
+the spans of all its entries, without counting lines from the start for
+each. Lines end at `\n`, so a `\r` before it is the last character of its
+line, as with `line_column`.
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineIndex<'a> {
+    input: &'a str,
+    starts: Vec<usize>,
+}
+
+impl<'a> LineIndex<'a> {
+    pub fn new(input: &'a str) -> LineIndex<'a> {
+        let starts = core::iter::once(0).chain(input.match_indices('\n').map(|(i, _)| i + 1)).collect();
+        LineIndex { input, starts }
+    }
+
+    /** `line_column(input, offset)`, found by binary search. */
+    pub fn line_column(&self, offset: usize) -> (usize, usize) {
+        let offset = offset.min(self.input.len());
+        let line = self.starts.partition_point(|&start| start <= offset);
+        (line, self.input[self.starts[line - 1]..offset].chars().count() + 1)
+    }
+
+    pub fn span(&self, start: usize, end: usize) -> Span {
+        let (line, column) = self.line_column(start);
+        Span { start, end, line, column }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(key) = &self.entry {
+            write!(f, "entry `{}`, ", key)?;
+        }
+        write!(f, "line {}, column {}: {}", self.line, self.column, self.message)
+    }
+}
+
+impl core::error::Error for ParseError {}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_line_index() {
+        for input in ["", "a", "\n\n", "ab\ncd\r\n\r\nü€x\n", "@misc{k,\r\n  title = {T}\r\n}\r\n"] {
+            let lines = LineIndex::new(input);
+            for offset in (0..=input.len() + 1).filter(|&o| o > input.len() || input.is_char_boundary(o)) {
+                assert_eq!(lines.line_column(offset), line_column(input, offset), "{:?} at {}", input, offset);
+            }
+        }
+        let lines = LineIndex::new("ab\r\nü€x");
+        assert_eq!(lines.line_column(2), (1, 3));
+        assert_eq!(lines.line_column(9), (2, 3));
+        assert_eq!(lines.span(4, 7), Span::new("ab\r\nü€x", 4, 7));
+    }
+}
+
+/**
+An input the lenient parser skipped (see `parser::ParseOptions::lenient`):
+why it failed and the first line of what was skipped.
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseDiagnostic {
+    pub error: ParseError,
+    pub snippet: String,
+}
+
+impl fmt::Display for ParseDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (skipped `{}`)", self.error, self.snippet)
+    }
+}
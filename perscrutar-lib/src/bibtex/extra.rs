@@ -0,0 +1,262 @@
+/*!
+
+Zotero-style structured data stored in free-text fields.
+
+Zotero (and Better BibTeX) put data they have no field for into `extra`,
+`note` or `annote` as one `Key: Value` pair per line:
+
+```text
+Citation Key: Cox-CFT
+tex.ids: cox2013, cox-primes
+original-date: 1989
+Some remark that is not a key/value pair.
+```
+
+`Extra` splits such a value into its sub-fields while keeping every other
+line, so that writing it back reproduces the original text. A line is
+written as it was read, spacing and all, until `set` changes its value.
+
+*/
+
+use std::fmt;
+use crate::bibtex::data::Entry;
+
+/**
+Fields that may carry Zotero extra data, in the order they are consulted.
+*/
+pub const EXTRA_FIELDS: [&str; 3] = ["extra", "note", "annote"];
+
+/**
+A line of an extra field. `raw` is the line as it was read, or `None`
+once the value has been set.
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExtraLine {
+    Field { key: String, value: String, raw: Option<String> },
+    Text(String),
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Extra {
+    lines: Vec<ExtraLine>,
+}
+
+/**
+Keys are short labels such as `Citation Key`, `tex.ids` or `PMID`; a colon
+in ordinary prose should not turn a sentence into a field.
+*/
+fn split_key(line: &str) -> Option<(&str, &str)> {
+    let pos = line.find(':')?;
+    let key = &line[..pos];
+    if key.is_empty()
+        || key.len() > 40
+        || key.split(' ').count() > 3
+        || !key.chars().next().map(|c| c.is_alphabetic()).unwrap_or(false)
+        || !key.chars().all(|c| c.is_alphanumeric() || " .-_".contains(c))
+        || key.ends_with(' ')
+    {
+        return None;
+    }
+    Some((key, line[pos + 1..].trim()))
+}
+
+impl Extra {
+    /**
+    Split `text` into lines; a trailing newline is kept as a last, empty
+    line.
+    */
+    pub fn parse(text: &str) -> Extra {
+        if text.is_empty() {
+            return Extra::default();
+        }
+        let lines = text.split('\n').map(|line| {
+            match split_key(line) {
+                Some((key, value)) => ExtraLine::Field {
+                    key: String::from(key),
+                    value: String::from(value),
+                    raw: Some(String::from(line)),
+                },
+                None => ExtraLine::Text(String::from(line)),
+            }
+        }).collect();
+        Extra { lines }
+    }
+
+    pub fn lines(&self) -> &[ExtraLine] {
+        &self.lines
+    }
+
+    /**
+    All key/value pairs in order of appearance.
+    */
+    pub fn fields(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.lines.iter().filter_map(|l| match l {
+            ExtraLine::Field { key, value, .. } => Some((key.as_str(), value.as_str())),
+            ExtraLine::Text(_) => None,
+        })
+    }
+
+    /**
+    First value for `key`, compared case-insensitively.
+    */
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.get_all(key).into_iter().next()
+    }
+
+    pub fn get_all(&self, key: &str) -> Vec<&str> {
+        self.fields()
+            .filter(|(k, _)| k.eq_ignore_ascii_case(key))
+            .map(|(_, v)| v)
+            .collect()
+    }
+
+    /**
+    Replace the first value for `key`, or append a new line if there is none
+    (before the trailing newline, if the text had one).
+    */
+    pub fn set(&mut self, key: &str, value: &str) {
+        for l in self.lines.iter_mut() {
+            if let ExtraLine::Field { key: k, value: v, raw } = l {
+                if k.eq_ignore_ascii_case(key) {
+                    if v != value {
+                        *v = String::from(value);
+                        *raw = None;
+                    }
+                    return;
+                }
+            }
+        }
+        let at = match self.lines.last() {
+            Some(ExtraLine::Text(last)) if last.is_empty() && self.lines.len() > 1 => self.lines.len() - 1,
+            _ => self.lines.len(),
+        };
+        self.lines.insert(at, ExtraLine::Field {
+            key: String::from(key),
+            value: String::from(value),
+            raw: None,
+        });
+    }
+
+    /**
+    Remove every line for `key`, returning whether any was present.
+    */
+    pub fn remove(&mut self, key: &str) -> bool {
+        let before = self.lines.len();
+        self.lines.retain(|l| match l {
+            ExtraLine::Field { key: k, .. } => !k.eq_ignore_ascii_case(key),
+            ExtraLine::Text(_) => true,
+        });
+        before != self.lines.len()
+    }
+
+    /**
+    Whether there is nothing left but blank lines.
+    */
+    pub fn is_empty(&self) -> bool {
+        self.lines.iter().all(|l| matches!(l, ExtraLine::Text(text) if text.trim().is_empty()))
+    }
+}
+
+impl fmt::Display for Extra {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, l) in self.lines.iter().enumerate() {
+            if i > 0 {
+                f.write_str("\n")?;
+            }
+            match l {
+                ExtraLine::Field { raw: Some(raw), .. } => f.write_str(raw)?,
+                ExtraLine::Field { key, value, raw: None } => write!(f, "{}: {}", key, value)?,
+                ExtraLine::Text(text) => f.write_str(text)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Entry {
+    /**
+    Structured view of the first Zotero extra field present on the entry,
+    together with the name of that field.
+    */
+    pub fn extra(&self) -> Option<(&'static str, Extra)> {
+        EXTRA_FIELDS.iter()
+            .find_map(|f| self.get(f).map(|v| (*f, Extra::parse(v))))
+    }
+
+    /**
+    Write `extra` back into `field`, removing the field if nothing is left.
+    */
+    pub fn set_extra(&mut self, field: &str, extra: &Extra) {
+        if extra.is_empty() {
+            self.remove(field);
+        } else {
+            self.set(field, &extra.to_string());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::bibtex::data::BibType;
+
+    const NOTE: &str = "Citation Key: Cox-CFT\ntex.ids: cox2013, cox-primes\nSee the errata, p. 12: the bound is corrected.\noriginal-date: 1989";
+
+    #[test]
+    fn test_parse() {
+        let extra = Extra::parse(NOTE);
+        assert_eq!(extra.get("citation key"), Some("Cox-CFT"));
+        assert_eq!(extra.get("tex.ids"), Some("cox2013, cox-primes"));
+        assert_eq!(extra.get("original-date"), Some("1989"));
+        assert_eq!(extra.fields().count(), 3);
+
+        let prose = Extra::parse("As noted by the author in the thesis: the bound is tight");
+        assert_eq!(prose.fields().count(), 0);
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let mut extra = Extra::parse(NOTE);
+        assert_eq!(extra.to_string(), NOTE);
+
+        extra.set("PMID", "12345");
+        extra.set("Original-Date", "1990");
+        assert!(extra.remove("tex.ids"));
+        assert_eq!(extra.to_string(),
+            "Citation Key: Cox-CFT\nSee the errata, p. 12: the bound is corrected.\noriginal-date: 1990\nPMID: 12345");
+
+        let text = "PMID:123\ntex.ids:  cox2013 \n";
+        let mut extra = Extra::parse(text);
+        assert_eq!(extra.get("pmid"), Some("123"));
+        assert_eq!(extra.to_string(), text);
+        extra.set("tex.ids", "cox2013");
+        assert_eq!(extra.to_string(), text);
+        extra.set("PMID", "456");
+        extra.set("Original-Date", "1989");
+        assert_eq!(extra.to_string(), "PMID: 456\ntex.ids:  cox2013 \nOriginal-Date: 1989\n");
+        assert!(!extra.is_empty());
+        extra.remove("pmid");
+        extra.remove("tex.ids");
+        extra.remove("original-date");
+        assert!(extra.is_empty());
+    }
+
+    #[test]
+    fn test_entry_extra() {
+        let mut e = Entry::new(BibType::Book, "Cox-CFT");
+        assert!(e.extra().is_none());
+        e.set("note", "Citation Key: Cox-CFT");
+
+        let (field, mut extra) = e.extra().unwrap();
+        assert_eq!(field, "note");
+        extra.set("tex.ids", "cox2013");
+        e.set_extra(field, &extra);
+        assert_eq!(e.get("note"), Some("Citation Key: Cox-CFT\ntex.ids: cox2013"));
+
+        extra.remove("citation key");
+        extra.remove("tex.ids");
+        e.set_extra(field, &extra);
+        assert!(!e.has("note"));
+    }
+}
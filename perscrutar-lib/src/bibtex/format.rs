@@ -0,0 +1,290 @@
+/*!
+
+Canonical formatting of whole .bib files, as `perscrutar fmt` does it.
+
+Entries are written anew, so that the same entries format to the same
+bytes however they were laid out:
+
+- entry types and field names are lowercase, and every field is on its
+  own line with the indentation, alignment and delimiters of
+  `FormatOptions::write`;
+- runs of whitespace in single-line values become single spaces, except
+  in the `data::VERBATIM_FIELDS`; values written over several lines, such
+  as the `Key: Value` lines of a Zotero `extra` field, are left as they are;
+- `@string` abbreviations stay as they were used, and `#` concatenations
+  are kept, as `piece # piece`.
+
+Everything else in the file stays where it is: `@string` definitions,
+`@comment`s and `@preamble`s are kept as written, and so are `%` comments
+and other text between entries, apart from trailing whitespace. A text on
+the same line after an entry stays on that line. Items are separated by a
+blank line where they were and by a line break where they were not, so
+groups such as a block of `@string`s keep their shape.
+
+With `FormatOptions::sort`, entries are ordered by citation key within
+each run of entries that nothing but whitespace separates, so that no
+entry moves above an `@string` it uses or away from a comment about it.
+
+`update_entry` writes one entry that has been changed in the same way,
+keeping the values of its unchanged fields as they were written.
+
+`is_formatted` tells whether formatting would change a file, for checks
+such as pre-commit hooks.
+
+*/
+
+use crate::bibtex::data::{is_verbatim, Entry};
+use crate::bibtex::error::ParseError;
+use crate::bibtex::parser::{parse_items, Comments, Fields, Piece, RawItem};
+use crate::bibtex::writer::{has_bare_quote, protect_capitals, wrap, Delimiter, WriteOptions, TITLE_FIELDS};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FormatOptions {
+    /** Order entries by citation key, ignoring case; otherwise keep the file order. */
+    pub sort: bool,
+    pub write: WriteOptions,
+}
+
+/** `value` with whitespace runs collapsed to single spaces and trimmed. */
+pub fn normalize_whitespace(value: &str) -> String {
+    value.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+/** `value` with whitespace runs collapsed to single spaces, but not trimmed. */
+fn collapse_whitespace(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c.is_whitespace() {
+            true if out.ends_with(' ') => {}
+            true => out.push(' '),
+            false => out.push(c),
+        }
+    }
+    out
+}
+
+/** A field's value written out, with `column` the column it starts at. */
+fn format_value(name: &str, pieces: &[Piece], column: usize, options: &WriteOptions) -> String {
+    let single = pieces.len() == 1;
+    pieces.iter().map(|piece| match piece {
+        Piece::Macro(name) => name.clone(),
+        Piece::Text(text) => {
+            let mut value = match text.contains('\n') || is_verbatim(name) {
+                true => text.clone(),
+                false if single => normalize_whitespace(text),
+                // a space at either end of a `#` piece is part of the value
+                false => collapse_whitespace(text),
+            };
+            if options.protect_capitals && TITLE_FIELDS.contains(&name) {
+                value = protect_capitals(&value);
+            }
+            let (open, close) = match options.delimiter {
+                Delimiter::Quotes if !has_bare_quote(&value) => ('"', '"'),
+                _ => ('{', '}'),
+            };
+            match options.width {
+                // the closing `},` counts towards the limit
+                Some(width) if single && !value.contains('\n') && !is_verbatim(name) =>
+                    format!("{}{}{}", open, wrap(&value, column + 1, column + 1, width.saturating_sub(2)), close),
+                _ => format!("{}{}{}", open, value, close),
+            }
+        }
+    }).collect::<Vec<String>>().join(" # ")
+}
+
+fn format_entry(itemtype: &str, key: &str, fields: &Fields, options: &WriteOptions) -> String {
+    let mut fields: Vec<(String, &[Piece])> = fields.iter().map(|(name, pieces)| (name.to_lowercase(), pieces.as_slice())).collect();
+    let rank = |k: &str| options.field_order.iter().position(|o| o.eq_ignore_ascii_case(k)).unwrap_or(options.field_order.len());
+    fields.sort_by_key(|(name, _)| rank(name));
+    let name_width = if options.align { fields.iter().map(|(n, _)| n.chars().count()).max().unwrap_or(0) } else { 0 };
+    let mut out = format!("@{}{{{}", itemtype.to_lowercase(), key);
+    for (name, pieces) in fields {
+        let prefix = format!("{}{:width$} = ", " ".repeat(options.indent), name, width = name_width);
+        out.push_str(",\n");
+        out.push_str(&prefix);
+        out.push_str(&format_value(&name, pieces, prefix.chars().count(), options));
+    }
+    out.push_str("\n}");
+    out
+}
+
+/** A part of a file, with entries written in canonical form. */
+enum Block<'a> {
+    Text(&'a str),
+    Item(&'a str),
+    /** The citation key and the entry. */
+    Entry(&'a str, String),
+}
+
+/** Order the entries of each run of entries that only whitespace separates by key. */
+fn sort_runs(blocks: &mut [Block]) {
+    let in_run = |b: &Block| match b {
+        Block::Entry(..) => true,
+        Block::Text(text) => text.trim().is_empty(),
+        Block::Item(_) => false,
+    };
+    let mut start = 0;
+    while start < blocks.len() {
+        let end = start + blocks[start..].iter().position(|b| !in_run(b)).unwrap_or(blocks.len() - start);
+        let slots: Vec<usize> = (start..end).filter(|i| matches!(blocks[*i], Block::Entry(..))).collect();
+        let mut entries: Vec<(&str, String)> = slots.iter().map(|i| match &blocks[*i] {
+            Block::Entry(key, text) => (*key, text.clone()),
+            _ => unreachable!("slots are entries"),
+        }).collect();
+        // stable, so entries with the same key keep their order
+        entries.sort_by_cached_key(|(key, _)| key.to_lowercase());
+        for (slot, (key, text)) in slots.into_iter().zip(entries) {
+            blocks[slot] = Block::Entry(key, text);
+        }
+        start = end + 1;
+    }
+}
+
+/**
+Text between items, after `out`: its first line stays on the line the
+item before ends on, and blank lines before and after the rest are kept
+as one.
+*/
+fn push_text(out: &mut String, text: &str) {
+    let lines: Vec<&str> = text.split('\n').map(str::trim_end).collect();
+    let rest = match out.is_empty() {
+        true => &lines[..],
+        false => {
+            if !lines[0].trim().is_empty() {
+                out.push(' ');
+                out.push_str(lines[0].trim());
+            }
+            if lines.len() == 1 {
+                return;
+            }
+            out.push('\n');
+            &lines[1..]
+        }
+    };
+    let Some(first) = rest.iter().position(|l| !l.is_empty()) else {
+        if rest.len() > 1 && !out.is_empty() {
+            out.push('\n');
+        }
+        return;
+    };
+    let last = rest.iter().rposition(|l| !l.is_empty()).unwrap_or(first);
+    if first > 0 && !out.is_empty() {
+        out.push('\n');
+    }
+    out.push_str(&rest[first..=last].join("\n"));
+    out.push('\n');
+    // the last line is where the next item starts
+    if rest.len() - last > 2 {
+        out.push('\n');
+    }
+}
+
+/** An item after `out`, on a line of its own. */
+fn push_item(out: &mut String, text: &str) {
+    if !out.is_empty() && !out.ends_with('\n') {
+        out.push('\n');
+    }
+    out.push_str(text);
+}
+
+/** The text of a .bib file in canonical form. */
+pub fn format(input: &str, options: &FormatOptions) -> Result<String, ParseError> {
+    let mut blocks: Vec<Block> = parse_items(input, Comments::Standard)?.into_iter().map(|item| match item {
+        RawItem::Text(text) => Block::Text(text),
+        RawItem::Special(text) => Block::Item(text.trim()),
+        RawItem::Entry { itemtype, key, fields } => Block::Entry(key, format_entry(itemtype, key, &fields, &options.write)),
+    }).collect();
+    if options.sort {
+        sort_runs(&mut blocks);
+    }
+    let mut out = String::new();
+    for block in blocks {
+        match block {
+            Block::Text(text) => push_text(&mut out, text),
+            Block::Item(text) => push_item(&mut out, text),
+            Block::Entry(_, text) => push_item(&mut out, &text),
+        }
+    }
+    let mut out = String::from(out.trim_end());
+    if !out.is_empty() {
+        out.push('\n');
+    }
+    Ok(out)
+}
+
+/**
+The entry `source`, as a .bib file has it, in canonical form with the
+type, key and fields of `entry`, a changed copy of `original`. Fields
+with the same value in both are written as in `source`, with their
+`@string` abbreviations and `#` concatenations; the others are written
+from `entry`. Private fields of `options.policy` are left out.
+*/
+pub fn update_entry(source: &str, original: &Entry, entry: &Entry, options: &WriteOptions) -> Result<String, ParseError> {
+    let raw = parse_items(source, Comments::Standard)?.into_iter().find_map(|item| match item {
+        RawItem::Entry { itemtype, fields, .. } => Some((itemtype, fields)),
+        _ => None,
+    });
+    let (itemtype, raw_fields) = raw.unwrap_or_default();
+    let itemtype = match entry.type_name() == original.type_name() && !itemtype.is_empty() {
+        true => itemtype,
+        false => entry.type_name(),
+    };
+    let fields: Fields = entry.fields().filter(|(name, _)| !options.policy.is_private(name)).map(|(name, value)| {
+        let written = raw_fields.iter().find(|(n, _)| n.eq_ignore_ascii_case(name)).map(|(_, pieces)| pieces);
+        let pieces = match written {
+            Some(pieces) if original.get(name) == Some(value) => pieces.clone(),
+            _ => vec![Piece::Text(String::from(value))],
+        };
+        (String::from(name), pieces)
+    }).collect();
+    Ok(format_entry(itemtype, entry.key(), &fields, options))
+}
+
+/** Whether `format` would leave `input` as it is. */
+pub fn is_formatted(input: &str, options: &FormatOptions) -> Result<bool, ParseError> {
+    format(input, options).map(|formatted| formatted == input)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_format() {
+        let messy = "% Library\n\n@String{ACM = \"ACM\"}\n@string{IEEE = \"IEEE\"}\n\n@Article{Zed,\n  TITLE=\"A   long title\", publisher = ACM # { Press},\n\
+            url = {https://example.org/a  b}}   % see also alpha\n@comment{ kept }\n\n@misc{alpha, note = {  padded  },\n\
+            extra = {Citation Key: alpha\nOriginal Date: 1999}}\n";
+        let options = FormatOptions::default();
+        let formatted = format(messy, &options).unwrap();
+        assert_eq!(formatted, "% Library\n\n@String{ACM = \"ACM\"}\n@string{IEEE = \"IEEE\"}\n\n\
+            @article{Zed,\n  title = {A long title},\n  publisher = ACM # { Press},\n  url = {https://example.org/a  b}\n} % see also alpha\n\
+            @comment{ kept }\n\n@misc{alpha,\n  note = {padded},\n  extra = {Citation Key: alpha\nOriginal Date: 1999}\n}\n");
+        assert_eq!(format(&formatted, &options).unwrap(), formatted);
+        assert!(is_formatted(&formatted, &options).unwrap());
+        assert!(!is_formatted(messy, &options).unwrap());
+        assert!(format("@article{a, title = {unclosed}", &options).is_err());
+    }
+
+    #[test]
+    fn test_update_entry() {
+        use crate::bibtex::parser::parse_entries;
+
+        let source = "@Article{k, journal = jacm # { Letters}, month = {May}, note = {old}}";
+        let original = parse_entries(&format!("@string{{jacm = {{JACM}}}}\n{}", source)).unwrap().remove(0);
+        let mut entry = original.clone();
+        entry.set("month", "5");
+        entry.remove("note");
+        entry.set("year", "1984");
+        let written = update_entry(source, &original, &entry, &WriteOptions::default()).unwrap();
+        assert_eq!(written, "@article{k,\n  journal = jacm # { Letters},\n  month = {5},\n  year = {1984}\n}");
+    }
+
+    #[test]
+    fn test_sort_runs() {
+        let input = "@string{j = \"J\"}\n\n@misc{c, year = 1}\n\n@misc{B, year = 2}\n% about a\n@misc{a, journal = j}\n\n@misc{Z, year = 3}\n@misc{y, year = 4}\n";
+        let sorted = format(input, &FormatOptions { sort: true, ..FormatOptions::default() }).unwrap();
+        assert_eq!(sorted, "@string{j = \"J\"}\n\n@misc{B,\n  year = {2}\n}\n\n@misc{c,\n  year = {1}\n}\n% about a\n\
+            @misc{a,\n  journal = j\n}\n\n@misc{y,\n  year = {4}\n}\n@misc{Z,\n  year = {3}\n}\n");
+    }
+}
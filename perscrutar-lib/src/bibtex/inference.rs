@@ -0,0 +1,153 @@
+/*!
+
+Better types for `@misc` entries.
+
+Importers that do not know what they are given write `@misc`, even for
+an article or a conference paper. The fields usually tell:
+
+- `journal` (or `journaltitle`) means `@article`;
+- `booktitle` means `@inproceedings`, or `@incollection` if it does not
+  name a conference and the entry has a `publisher`;
+- `school` means a thesis, `@mastersthesis` if its `type` says so and
+  `@phdthesis` otherwise;
+- `institution` with a `number` means `@techreport`;
+- `isbn` and `publisher` mean `@book`;
+- `eprint` and none of these means `@preprint`.
+
+`proposals` gives every type the fields point to, with a confidence from
+0 to 1, best first; `propose` the best for a `@misc` entry. The
+`retype-misc` transform retypes where the best proposal is at least
+`AUTOMATIC` confident, which leaves `@preprint` and other guesses for a
+person to confirm.
+
+*/
+
+use crate::bibtex::data::{BibType, Entry};
+use crate::bibtex::theses::type_key;
+
+/** The confidence at and above which `retype` changes the type. */
+pub const AUTOMATIC: f64 = 0.8;
+
+/** Words that make a book title the proceedings of a meeting. */
+const MEETINGS: [&str; 6] = ["proceedings", "conference", "workshop", "symposium", "congress", "meeting"];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Proposal {
+    pub entry_type: BibType,
+    /** From 0 to 1. */
+    pub confidence: f64,
+    /** The fields that suggest the type, e.g. `journal, volume`. */
+    pub reason: String,
+}
+
+fn has(entry: &Entry, field: &str) -> bool {
+    entry.get(field).map(|v| !v.trim().is_empty()).unwrap_or(false)
+}
+
+/** The types `entry`'s fields point to, most likely first. */
+pub fn proposals(entry: &Entry) -> Vec<Proposal> {
+    let mut out = Vec::new();
+    let proposal = |name: &str, confidence: f64, reason: &str| Proposal {
+        entry_type: BibType::parse(name),
+        confidence,
+        reason: String::from(reason),
+    };
+    let journal = ["journal", "journaltitle"].into_iter().find(|f| has(entry, f));
+    if let Some(journal) = journal {
+        match ["volume", "number", "pages"].into_iter().find(|f| has(entry, f)) {
+            Some(locator) => out.push(proposal("article", 0.95, &format!("{}, {}", journal, locator))),
+            None => out.push(proposal("article", 0.85, journal)),
+        }
+    }
+    if let Some(booktitle) = entry.get("booktitle").filter(|b| !b.trim().is_empty()) {
+        let lower = booktitle.to_lowercase();
+        match (MEETINGS.iter().any(|w| lower.contains(w)), has(entry, "publisher")) {
+            (true, _) => out.push(proposal("inproceedings", 0.9, "booktitle naming a meeting")),
+            (false, true) => {
+                out.push(proposal("incollection", 0.6, "booktitle, publisher"));
+                out.push(proposal("inproceedings", 0.55, "booktitle"));
+            }
+            (false, false) => out.push(proposal("inproceedings", 0.8, "booktitle")),
+        }
+    }
+    if has(entry, "school") {
+        match entry.get("type").and_then(type_key) {
+            Some("mathesis") => out.push(proposal("mastersthesis", 0.9, "school, type")),
+            Some("phdthesis") => out.push(proposal("phdthesis", 0.9, "school, type")),
+            _ => out.push(proposal("phdthesis", 0.7, "school")),
+        }
+    }
+    if has(entry, "institution") && has(entry, "number") && journal.is_none() {
+        out.push(proposal("techreport", 0.8, "institution, number"));
+    }
+    if has(entry, "isbn") && has(entry, "publisher") && !has(entry, "booktitle") {
+        out.push(proposal("book", 0.85, "isbn, publisher"));
+    }
+    if has(entry, "eprint") && out.is_empty() {
+        out.push(proposal("preprint", 0.5, "eprint"));
+    }
+    out.sort_by(|a, b| b.confidence.total_cmp(&a.confidence));
+    out
+}
+
+/** The best proposal for a `@misc` entry; other entries keep their type. */
+pub fn propose(entry: &Entry) -> Option<Proposal> {
+    match entry.entry_type() {
+        BibType::Misc => proposals(entry).into_iter().next(),
+        _ => None,
+    }
+}
+
+/**
+Give a `@misc` entry the type proposed for it, if the proposal is at
+least `AUTOMATIC` confident. Returns whether the type changed.
+*/
+pub fn retype(entry: &mut Entry) -> bool {
+    match propose(entry).filter(|p| p.confidence >= AUTOMATIC) {
+        Some(proposal) => {
+            entry.set_entry_type(proposal.entry_type);
+            true
+        }
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn misc(fields: &[(&str, &str)]) -> Entry {
+        let mut e = Entry::new(BibType::Misc, "x");
+        for (name, value) in fields {
+            e.set(name, value);
+        }
+        e
+    }
+
+    #[test]
+    fn test_proposals() {
+        let best = |fields: &[(&str, &str)]| propose(&misc(fields)).map(|p| (String::from(p.entry_type.name()), p.confidence));
+        let of = |name: &str, confidence: f64| Some((String::from(name), confidence));
+        assert_eq!(best(&[("journal", "CACM"), ("volume", "27")]), of("article", 0.95));
+        assert_eq!(best(&[("booktitle", "Proceedings of POPL")]), of("inproceedings", 0.9));
+        assert_eq!(best(&[("booktitle", "Essays"), ("publisher", "Springer")]), of("incollection", 0.6));
+        assert_eq!(best(&[("school", "MIT"), ("type", "Master's thesis")]), of("mastersthesis", 0.9));
+        assert_eq!(best(&[("institution", "CMU"), ("number", "CMU-CS-84-1")]), of("techreport", 0.8));
+        assert_eq!(best(&[("isbn", "9780201896831"), ("publisher", "Addison-Wesley")]), of("book", 0.85));
+        assert_eq!(best(&[("eprint", "2101.00001")]), of("preprint", 0.5));
+        assert_eq!(best(&[("howpublished", "Blog post")]), None);
+        assert_eq!(proposals(&misc(&[("booktitle", "Essays"), ("publisher", "Springer")])).len(), 2);
+
+        let mut article = Entry::new(BibType::Article, "a");
+        article.set("booktitle", "Proceedings of POPL");
+        assert_eq!(propose(&article), None);
+        assert_eq!(proposals(&article)[0].reason, "booktitle naming a meeting");
+
+        let mut e = misc(&[("journal", "CACM")]);
+        assert!(retype(&mut e));
+        assert_eq!(e.entry_type(), &BibType::Article);
+        let mut e = misc(&[("eprint", "2101.00001")]);
+        assert!(!retype(&mut e));
+    }
+}
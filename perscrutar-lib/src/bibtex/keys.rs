@@ -0,0 +1,448 @@
+/*!
+
+Citation keys that are easy to mix up.
+
+biber compares keys case-sensitively but many documents do not, so
+`Cox2013` and `cox2013` in the same bibliography are almost always one work
+entered twice, or a citation that silently resolves to the wrong entry.
+`near_duplicate` finds such pairs: keys differing only in case, or (for keys
+long enough for it to matter) by a single edit such as a trailing letter.
+Keys that differ only in their disambiguation letter (`knuth1984a`,
+`knuth1984b`) are deliberate and not reported.
+
+`KeyCase` is the convention the `key-case-*` transforms rewrite keys to,
+and `unique_key` adds the disambiguation letter to a new key that is
+already taken. `citation_key` is the key the readers of other formats
+give entries that come without one, such as `virtanen2020`.
+
+New keys can be made from a `KeyPattern`, written the way JabRef writes
+them: text in square brackets is a marker for part of the entry, anything
+else is copied as it is. `[auth][year][shorttitle]` gives
+`Knuth1984LiterateProgramming`. The markers are
+
+- `[auth]`, the first author's last name (the first editor's if there
+  are no authors), and `[authN]`, its first N letters;
+- `[authors]`, all authors' last names, and `[authorsN]`, the first N
+  followed by `EtAl` if there are more;
+- `[year]` (from `year`, or else `date`) and `[shortyear]`, its last two
+  digits;
+- `[title]`, the words of the title that are not `matching::STOPWORDS`,
+  capitalized, and `[shorttitle]` and `[veryshorttitle]`, the first
+  three and the first of them;
+- `[entrytype]`, `[firstpage]` and `[lastpage]`;
+- any other name, the field of that name.
+
+A marker may end in `:lower` or `:upper`, as in `[auth:lower]`. Markers
+keep only ASCII letters and digits, with TeX accents dropped.
+`Bibliography::regenerate_keys` gives every entry the key its pattern
+makes, adding disambiguation letters to keys made twice, and returns the
+old and new keys for updating documents that cite them.
+
+*/
+
+use std::cmp::min;
+use std::collections::BTreeMap;
+use std::fmt;
+use crate::bibtex::bibliography::{Bibliography, KEY_REFERENCES};
+use crate::bibtex::data::Entry;
+use crate::bibtex::names::{purify, Name};
+use crate::matching::STOPWORDS;
+
+/** Keys shorter than this are only compared for case. */
+const MIN_EDIT_LEN: usize = 5;
+
+/**
+Number of single-character insertions, deletions and substitutions that turn
+`a` into `b`.
+*/
+pub fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(ca != *cb);
+            diagonal = row[j + 1];
+            row[j + 1] = min(substitution, min(row[j], row[j + 1]) + 1);
+        }
+    }
+    row[b.len()]
+}
+
+/**
+`key` without its disambiguation letter, if it ends in a year followed by
+one, as in `knuth1984a`.
+*/
+fn stem(key: &str) -> Option<&str> {
+    let mut chars = key.chars().rev();
+    match (chars.next(), chars.next()) {
+        (Some(l), Some(d)) if l.is_ascii_lowercase() && d.is_ascii_digit() => Some(&key[..key.len() - 1]),
+        _ => None,
+    }
+}
+
+/**
+Whether `a` and `b` end in different disambiguation letters after the same
+year, as in `knuth1984a` and `knuth1984b`.
+*/
+fn disambiguated(a: &str, b: &str) -> bool {
+    matches!((stem(a), stem(b)), (Some(x), Some(y)) if x == y)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Similarity {
+    /** Equal ignoring case. */
+    Case,
+    /** One edit apart ignoring case. */
+    Edit,
+}
+
+/**
+How `a` and `b` are confusable, if they are; identical keys are not near
+duplicates but plain duplicates.
+*/
+pub fn near_duplicate(a: &str, b: &str) -> Option<Similarity> {
+    if a == b {
+        return None;
+    }
+    let (la, lb) = (a.to_lowercase(), b.to_lowercase());
+    if la == lb {
+        return Some(Similarity::Case);
+    }
+    let (na, nb) = (la.chars().count(), lb.chars().count());
+    if min(na, nb) < MIN_EDIT_LEN || na.abs_diff(nb) > 1 || disambiguated(&la, &lb) {
+        return None;
+    }
+    (levenshtein(&la, &lb) == 1).then_some(Similarity::Edit)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyCase {
+    /** Every key in lower case. */
+    Lower,
+    /** Every key in upper case. */
+    Upper,
+    /** Keys differing only in case take the spelling of the first one. */
+    First,
+}
+
+impl KeyCase {
+    pub const ALL: [KeyCase; 3] = [KeyCase::Lower, KeyCase::Upper, KeyCase::First];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            KeyCase::Lower => "lower",
+            KeyCase::Upper => "upper",
+            KeyCase::First => "first",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<KeyCase> {
+        KeyCase::ALL.into_iter().find(|c| c.name() == name)
+    }
+
+    /**
+    The canonical spelling of every key in `keys`, in order.
+    */
+    pub fn canonical(&self, keys: &[&str]) -> Vec<String> {
+        keys.iter().map(|k| match self {
+            KeyCase::Lower => k.to_lowercase(),
+            KeyCase::Upper => k.to_uppercase(),
+            KeyCase::First => keys.iter().find(|o| o.to_lowercase() == k.to_lowercase()).unwrap_or(k).to_string(),
+        }).collect()
+    }
+}
+
+/**
+Whether a record id from another format can be used as a citation key as
+it is: reference managers' numeric ids and URLs cannot, and neither can
+anything BibTeX would read as more than a key.
+*/
+pub fn is_key(id: &str) -> bool {
+    !id.is_empty() && !id.chars().all(|c| c.is_ascii_digit()) && id.chars().all(key_char)
+}
+
+fn key_char(c: char) -> bool {
+    !c.is_whitespace() && !matches!(c, ',' | '{' | '}' | '(' | ')' | '=' | '"' | '#' | '%' | '/')
+}
+
+/**
+`base`, or if `taken` says it is in use, `base` with the first free letter
+appended (`knuth1984a`, `knuth1984b`, ...). If all letters are taken too,
+`base` is returned as it is.
+*/
+pub fn unique_key<F: Fn(&str) -> bool>(base: &str, taken: F) -> String {
+    if !taken(base) {
+        return String::from(base);
+    }
+    ('a'..='z').map(|c| format!("{}{}", base, c))
+        .find(|k| !taken(k))
+        .unwrap_or_else(|| String::from(base))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PatternError(pub String);
+
+impl fmt::Display for PatternError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "bad key pattern: {}", self.0)
+    }
+}
+
+impl std::error::Error for PatternError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Text(String),
+    Marker { name: String, case: Option<KeyCase> },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyPattern {
+    parts: Vec<Part>,
+}
+
+impl KeyPattern {
+    /** JabRef's default, `Knuth1984`. */
+    pub const DEFAULT: &'static str = "[auth][year]";
+
+    pub fn parse(pattern: &str) -> Result<KeyPattern, PatternError> {
+        let mut parts = Vec::new();
+        let mut rest = pattern;
+        while !rest.is_empty() {
+            let Some(body) = rest.strip_prefix('[') else {
+                let end = rest.find('[').unwrap_or(rest.len());
+                let text = &rest[..end];
+                if let Some(c) = text.chars().find(|c| !key_char(*c) || matches!(c, ']')) {
+                    return Err(PatternError(format!("`{}` cannot be in a key", c)));
+                }
+                parts.push(Part::Text(String::from(text)));
+                rest = &rest[end..];
+                continue;
+            };
+            let end = body.find(']').ok_or_else(|| PatternError(format!("`[{}` is not closed", body)))?;
+            let (name, case) = match body[..end].split_once(':') {
+                None => (&body[..end], None),
+                Some((name, "lower")) => (name, Some(KeyCase::Lower)),
+                Some((name, "upper")) => (name, Some(KeyCase::Upper)),
+                Some((_, modifier)) => return Err(PatternError(format!("unknown modifier `{}`", modifier))),
+            };
+            if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric()) {
+                return Err(PatternError(format!("`[{}]` is not a marker", &body[..end])));
+            }
+            parts.push(Part::Marker { name: name.to_lowercase(), case });
+            rest = &body[end + 1..];
+        }
+        Ok(KeyPattern { parts })
+    }
+
+    /** The key for `entry`; empty if every marker is. */
+    pub fn key(&self, entry: &Entry) -> String {
+        let mut key = String::new();
+        let mut empty = true;
+        for part in &self.parts {
+            match part {
+                Part::Text(text) => key.push_str(text),
+                Part::Marker { name, case } => {
+                    let value = marker(entry, name);
+                    empty &= value.is_empty();
+                    match case {
+                        Some(case) => key.push_str(&case.canonical(&[value.as_str()]).remove(0)),
+                        None => key.push_str(&value),
+                    }
+                }
+            }
+        }
+        if empty { String::new() } else { key }
+    }
+}
+
+/**
+A citation key from the first author's last name and the year, such as
+`virtanen2020`, falling back to the title.
+*/
+pub fn citation_key(entry: &Entry) -> String {
+    let ascii = |s: &str| purify(s).to_lowercase().chars().filter(|c| c.is_ascii_alphanumeric()).collect::<String>();
+    let author = entry.authors().into_iter().next()
+        .map(|n| ascii(n.last.split_whitespace().next().unwrap_or("")))
+        .filter(|a| !a.is_empty());
+    let base = author
+        .or_else(|| entry.get("title").map(ascii).filter(|t| !t.is_empty()))
+        .unwrap_or_else(|| String::from(entry.entry_type().name()));
+    format!("{}{}", base, entry.get("year").unwrap_or(""))
+}
+
+/** ASCII letters and digits of `text`, without TeX markup. */
+fn ascii(text: &str) -> String {
+    purify(text).chars().filter(|c| c.is_ascii_alphanumeric()).collect()
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    chars.next().map(|c| c.to_uppercase().chain(chars).collect()).unwrap_or_default()
+}
+
+/** The value of the marker `name` (in lower case) for `entry`. */
+fn marker(entry: &Entry, name: &str) -> String {
+    let mut names = entry.authors();
+    if names.is_empty() {
+        names = entry.editors();
+    }
+    let last = |n: &Name| ascii(&n.last);
+    let count = |prefix: &str| name.strip_prefix(prefix).and_then(|n| n.parse::<usize>().ok());
+    let year = entry.get("year").or_else(|| entry.get("date").and_then(|d| d.get(..4)))
+        .map(ascii)
+        .unwrap_or_default();
+    let words = || {
+        let title = purify(entry.get("title").unwrap_or(""));
+        title.split(|c: char| !c.is_alphanumeric())
+            .filter(|w| !w.is_empty() && !STOPWORDS.contains(&w.to_lowercase().as_str()))
+            .map(|w| capitalize(&ascii(w)))
+            .collect::<Vec<String>>()
+    };
+    let pages = entry.get("pages").unwrap_or("");
+    match name {
+        "auth" => names.first().map(last).unwrap_or_default(),
+        "authors" => names.iter().map(last).collect(),
+        "year" => year,
+        "shortyear" => String::from(&year[year.len().saturating_sub(2)..]),
+        "title" => words().concat(),
+        "shorttitle" => words().into_iter().take(3).collect(),
+        "veryshorttitle" => words().into_iter().take(1).collect(),
+        "entrytype" => String::from(entry.entry_type().name()),
+        "firstpage" => ascii(pages.split('-').next().unwrap_or("")),
+        "lastpage" => ascii(pages.rsplit('-').next().unwrap_or("")),
+        _ => match (count("authors"), count("auth")) {
+            (Some(n), _) => {
+                let mut out: String = names.iter().take(n).map(last).collect();
+                if names.len() > n {
+                    out.push_str("EtAl");
+                }
+                out
+            }
+            (None, Some(n)) => names.first().map(|a| last(a).chars().take(n).collect()).unwrap_or_default(),
+            (None, None) => entry.get(name).map(ascii).unwrap_or_default(),
+        },
+    }
+}
+
+impl Bibliography {
+    /**
+    Give every entry the key `pattern` makes for it, followed by a
+    disambiguation letter if an earlier entry has it, and point the
+    `KEY_REFERENCES` at the new keys. Pinned entries, and entries for which
+    the pattern makes an empty key, keep theirs. Returns the keys that
+    changed, old to new.
+    */
+    pub fn regenerate_keys(&mut self, pattern: &KeyPattern) -> BTreeMap<String, String> {
+        let generated: Vec<Option<String>> = self.entries().iter()
+            .map(|e| Some(pattern.key(e)).filter(|k| !k.is_empty() && !self.is_pinned(e.key())))
+            .collect();
+        // keys that stay are taken before any are made
+        let mut taken: Vec<String> = self.entries().iter().zip(&generated)
+            .filter(|(_, g)| g.is_none())
+            .map(|(e, _)| String::from(e.key()))
+            .collect();
+        let mut renamed = BTreeMap::new();
+        for (entry, generated) in self.entries().iter().zip(&generated) {
+            let Some(base) = generated else { continue };
+            let key = unique_key(base, |k| taken.iter().any(|t| t == k));
+            if key != entry.key() {
+                renamed.insert(String::from(entry.key()), key.clone());
+            }
+            taken.push(key);
+        }
+        if renamed.is_empty() {
+            return renamed;
+        }
+        let mut i = 0;
+        self.visit_mut(|e| {
+            if generated[i].is_some() {
+                if let Some(key) = renamed.get(e.key()) {
+                    e.set_key(key);
+                }
+            }
+            i += 1;
+            for field in KEY_REFERENCES {
+                let Some(value) = e.get(field) else { continue };
+                let targets: Vec<&str> = value.split(',').map(str::trim).collect();
+                let followed: Vec<&str> = targets.iter().map(|t| renamed.get(*t).map(String::as_str).unwrap_or(t)).collect();
+                if followed != targets {
+                    e.set(field, &followed.join(", "));
+                }
+            }
+        });
+        renamed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_near_duplicate() {
+        assert_eq!(levenshtein("kitten", "sitting"), 3);
+        assert_eq!(levenshtein("", "abc"), 3);
+        assert_eq!(near_duplicate("Cox2013", "cox2013"), Some(Similarity::Case));
+        assert_eq!(near_duplicate("cox2013", "cox2013a"), Some(Similarity::Edit));
+        assert_eq!(near_duplicate("cox2013", "cox2031"), None);
+        assert_eq!(near_duplicate("knuth1984a", "knuth1984b"), None);
+        assert_eq!(near_duplicate("ab", "AB"), Some(Similarity::Case));
+        assert_eq!(near_duplicate("abc", "abd"), None);
+        assert_eq!(near_duplicate("cox2013", "cox2013"), None);
+        assert_eq!(unique_key("knuth1984", |k| ["knuth1984", "knuth1984a"].contains(&k)), "knuth1984b");
+    }
+
+    #[test]
+    fn test_key_case() {
+        let keys = ["Cox2013", "knuth84", "cox2013"];
+        assert_eq!(KeyCase::Lower.canonical(&keys), vec!["cox2013", "knuth84", "cox2013"]);
+        assert_eq!(KeyCase::Upper.canonical(&keys)[1], "KNUTH84");
+        assert_eq!(KeyCase::First.canonical(&keys), vec!["Cox2013", "knuth84", "Cox2013"]);
+        assert_eq!(KeyCase::parse("first"), Some(KeyCase::First));
+    }
+
+    #[test]
+    fn test_regenerate_keys() {
+        use crate::bibtex::data::BibType;
+
+        let mut knuth = Entry::new(BibType::Article, "k");
+        knuth.set("author", "Knuth, Donald E. and Lamport, Leslie and Doe, Jane");
+        knuth.set("title", "The {TeX}book: a Guide to {\\'E}l{\\'e}gance");
+        knuth.set("year", "1984");
+        knuth.set("pages", "97--111");
+        let key = |pattern: &str| KeyPattern::parse(pattern).unwrap().key(&knuth);
+        assert_eq!(key(KeyPattern::DEFAULT), "Knuth1984");
+        assert_eq!(key("[auth:lower][shortyear]-[veryshorttitle]"), "knuth84-TeXbook");
+        assert_eq!(key("[authors2][auth3][shorttitle]"), "KnuthLamportEtAlKnuTeXbookGuideElegance");
+        assert_eq!(key("[entrytype][firstpage][lastpage][journal]"), "article97111");
+        assert_eq!(key("[nothing]"), "");
+        assert!(KeyPattern::parse("[auth").is_err());
+        assert!(KeyPattern::parse("[auth:title]").is_err());
+        assert!(KeyPattern::parse("[auth] [year]").is_err());
+        let mut undated = knuth.clone();
+        undated.remove("year");
+        undated.set("date", "1éé");
+        assert_eq!(KeyPattern::parse(KeyPattern::DEFAULT).unwrap().key(&undated), "Knuth");
+
+        let mut other = knuth.clone();
+        other.set_key("other");
+        let mut paper = Entry::new(BibType::InProceedings, "paper");
+        paper.set("crossref", "other");
+        let mut pinned = knuth.clone();
+        pinned.set_key("Knuth1984");
+        let mut bib = Bibliography::from_entries(vec![knuth, other, paper, pinned]);
+        bib.pin("Knuth1984");
+        let renamed = bib.regenerate_keys(&KeyPattern::parse(KeyPattern::DEFAULT).unwrap());
+        assert_eq!(renamed.into_iter().collect::<Vec<_>>(), vec![
+            (String::from("k"), String::from("Knuth1984a")),
+            (String::from("other"), String::from("Knuth1984b")),
+        ]);
+        let keys: Vec<&str> = bib.entries().iter().map(|e| e.key()).collect();
+        assert_eq!(keys, vec!["Knuth1984a", "Knuth1984b", "paper", "Knuth1984"]);
+        assert_eq!(bib.get("paper").and_then(|e| e.get("crossref")), Some("Knuth1984b"));
+    }
+}
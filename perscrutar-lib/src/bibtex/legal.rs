@@ -0,0 +1,100 @@
+/*!
+
+Court decisions and legislation.
+
+biblatex reserves three entry types for legal material, which the
+biblatex-juradiss and biblatex-jura2 styles and their relatives print:
+
+- `@jurisdiction` for court decisions, with the case name as `title`, the
+  `court`, the `docket` number and, for reported cases, the `journaltitle`
+  (the reporter), `volume` and `pages`;
+- `@legislation` for statutes and regulations, with the `number` and the
+  `location` (the jurisdiction);
+- `@legal` for treaties and other legal documents.
+
+Cases and statutes are cited by their title, so they need no author.
+Without these types, cases end up as `@misc` entries with the court and
+docket in `note` or `howpublished`; `check_entry` suggests the proper type
+for a `@misc` entry with a `court` or `docket`, and `venue` gives the part
+of a case citation after the title, which `publist` uses.
+
+*/
+
+use crate::bibtex::data::Entry;
+use crate::lint::{Diagnostic, Severity};
+
+/** The legal entry types. */
+pub const TYPES: [&str; 3] = ["jurisdiction", "legislation", "legal"];
+
+pub fn is_legal(entry: &Entry) -> bool {
+    TYPES.contains(&entry.entry_type().name())
+}
+
+fn value<'a>(entry: &'a Entry, field: &str) -> Option<&'a str> {
+    entry.get(field).map(str::trim).filter(|v| !v.is_empty())
+}
+
+/**
+Where a case was decided or reported: the reporter citation if there is
+one (`347 U.S. 483`), otherwise the court and docket number
+(`Supreme Court of the United States, No. 1`).
+*/
+pub fn venue(entry: &Entry) -> Option<String> {
+    if entry.entry_type().name() != "jurisdiction" {
+        return None;
+    }
+    let reporter = value(entry, "journaltitle").or_else(|| value(entry, "journal"));
+    if let (Some(volume), Some(reporter)) = (value(entry, "volume"), reporter) {
+        return Some(match value(entry, "pages") {
+            Some(pages) => format!("{} {} {}", volume, reporter, pages),
+            None => format!("{} {}", volume, reporter),
+        });
+    }
+    match (value(entry, "court"), value(entry, "docket")) {
+        (Some(court), Some(docket)) => Some(format!("{}, No. {}", court, docket)),
+        (Some(court), None) => Some(String::from(court)),
+        (None, Some(docket)) => Some(format!("No. {}", docket)),
+        (None, None) => None,
+    }
+}
+
+/**
+Legal checks: a `@misc` entry with a `court` or `docket` is a court
+decision and better written as `@jurisdiction`.
+*/
+pub fn check_entry(entry: &Entry) -> Vec<Diagnostic> {
+    let mut out = Vec::new();
+    if entry.entry_type().name() == "misc" && (value(entry, "court").is_some() || value(entry, "docket").is_some()) {
+        out.push(Diagnostic::new(entry.key(), "legal-type", Severity::Info,
+            "an entry with a court or docket is a court decision, `@jurisdiction` in biblatex"));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::bibtex::data::BibType;
+
+    #[test]
+    fn test_venue() {
+        let mut case = Entry::new(BibType::parse("jurisdiction"), "brown");
+        case.set("title", "Brown v. Board of Education");
+        case.set("court", "Supreme Court of the United States");
+        case.set("docket", "1");
+        case.set("year", "1954");
+        assert!(is_legal(&case));
+        assert_eq!(venue(&case).as_deref(), Some("Supreme Court of the United States, No. 1"));
+        case.set("journaltitle", "U.S.");
+        case.set("volume", "347");
+        case.set("pages", "483");
+        assert_eq!(venue(&case).as_deref(), Some("347 U.S. 483"));
+        assert!(check_entry(&case).is_empty());
+
+        let mut misc = Entry::new(BibType::Misc, "brown");
+        misc.set("docket", "1");
+        assert_eq!(check_entry(&misc).into_iter().map(|d| d.rule).collect::<Vec<_>>(), vec!["legal-type"]);
+        assert_eq!(venue(&misc), None);
+    }
+}
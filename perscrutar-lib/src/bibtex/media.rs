@@ -0,0 +1,104 @@
+/*!
+
+Recordings, films and performances.
+
+biblatex reserves `@audio`, `@video`, `@movie` and `@performance` for
+audiovisual material. Who made a film or a recording is rarely its
+`author`: biblatex gives such contributors as an `editor` whose role is in
+`editortype` (and likewise `editora` with `editoratype`, `editorb` with
+`editorbtype`), while other styles use a `director` or `composer` field:
+
+```text
+@movie{vertigo, title = {Vertigo}, editor = {Hitchcock, Alfred}, editortype = {director}, ...}
+@audio{kind-of-blue, title = {Kind of Blue}, composer = {Davis, Miles}, ...}
+```
+
+`Entry::directors` and `Entry::composers` read either form. The length of a
+recording or film goes in `runtime` and the carrier (`DVD`, `LP`,
+`streaming`) in `medium`.
+
+RIS has types for some of these; `ris_type` and `from_ris_type` map
+between the two.
+
+*/
+
+use crate::bibtex::data::{BibType, Entry};
+use crate::bibtex::names::{parse_names, Name};
+
+/** The audiovisual entry types. */
+pub const TYPES: [&str; 4] = ["audio", "video", "movie", "performance"];
+
+/** Editor fields and the fields giving their roles. */
+const EDITORS: [(&str, &str); 3] = [("editor", "editortype"), ("editora", "editoratype"), ("editorb", "editorbtype")];
+
+impl Entry {
+    /**
+    The people in the role `role`: the names in the field of that name, or
+    else those of the first editor field whose type is `role`.
+    */
+    fn role(&self, role: &str) -> Vec<Name> {
+        if let Some(names) = self.get(role).filter(|v| !v.trim().is_empty()) {
+            return parse_names(names);
+        }
+        EDITORS.iter()
+            .find(|(_, kind)| self.get(kind).map(|k| k.trim().eq_ignore_ascii_case(role)) == Some(true))
+            .map(|(field, _)| parse_names(self.get(field).unwrap_or("")))
+            .unwrap_or_default()
+    }
+
+    /** The directors, from `director` or an editor of type `director`. */
+    pub fn directors(&self) -> Vec<Name> {
+        self.role("director")
+    }
+
+    /** The composers, from `composer` or an editor of type `composer`. */
+    pub fn composers(&self) -> Vec<Name> {
+        self.role("composer")
+    }
+}
+
+/**
+The RIS type for an audiovisual entry type. RIS has none for performances,
+which become generic references.
+*/
+pub fn ris_type(name: &str) -> Option<&'static str> {
+    match name.to_lowercase().as_str() {
+        "audio" => Some("SOUND"),
+        "video" => Some("VIDEO"),
+        "movie" => Some("MPCT"),
+        "performance" => Some("GEN"),
+        _ => None,
+    }
+}
+
+/** The audiovisual entry type for a RIS type. */
+pub fn from_ris_type(ty: &str) -> Option<BibType> {
+    match ty.trim() {
+        "SOUND" => Some(BibType::parse("audio")),
+        "VIDEO" => Some(BibType::parse("video")),
+        "MPCT" => Some(BibType::parse("movie")),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_roles() {
+        let mut movie = Entry::new(BibType::parse("movie"), "vertigo");
+        movie.set("editor", "Coleman, Herbert");
+        movie.set("editora", "Hitchcock, Alfred");
+        movie.set("editoratype", "Director");
+        assert_eq!(movie.directors().iter().map(|n| n.last.as_str()).collect::<Vec<_>>(), vec!["Hitchcock"]);
+        assert!(movie.composers().is_empty());
+        movie.set("composer", "Herrmann, Bernard");
+        assert_eq!(movie.composers()[0].first, "Bernard");
+
+        assert_eq!(ris_type("Movie"), Some("MPCT"));
+        assert_eq!(from_ris_type("SOUND").map(|t| t.name().to_string()).as_deref(), Some("audio"));
+        assert_eq!(from_ris_type("JOUR"), None);
+    }
+}
@@ -0,0 +1,129 @@
+/*!
+
+Stripping entries down to what a publisher wants to see.
+
+A `Profile` says which fields to keep for a submission: the fields the
+entry type requires (see `bibtex::types`), optionally the ones its schema
+recommends, and anything matching its `keep` patterns; fields matching
+`drop` go even if recommended. Required fields are never dropped, nor are
+`crossref` and `key`, which other fields depend on. Entries of unknown type
+only lose the `drop` fields.
+
+The built-in presets:
+
+- `minimal`: required fields only;
+- `standard`: required and recommended fields;
+- `ieee`: recommended fields without DOIs, URLs and identifiers, which some
+  IEEE templates reject;
+- `acm`: recommended fields, keeping DOIs and URLs as ACM asks.
+
+Projects can define their own profiles in the configuration (see
+`config::Config::minimize_profiles`).
+
+*/
+
+use crate::bibtex::data::Entry;
+use crate::bibtex::policy::FieldPattern;
+use crate::bibtex::types::TypeRegistry;
+
+/** Fields kept whatever the profile says. */
+const ALWAYS: [&str; 2] = ["crossref", "key"];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Profile {
+    pub name: String,
+    /** Keep the optional fields of the type's schema. */
+    pub recommended: bool,
+    pub keep: Vec<FieldPattern>,
+    pub drop: Vec<FieldPattern>,
+}
+
+impl Profile {
+    pub const PRESETS: [&'static str; 4] = ["minimal", "standard", "ieee", "acm"];
+
+    pub fn new(name: &str, recommended: bool) -> Profile {
+        Profile { name: String::from(name), recommended, keep: vec![], drop: vec![] }
+    }
+
+    pub fn keep(mut self, pattern: &str) -> Profile {
+        self.keep.push(FieldPattern::new(pattern));
+        self
+    }
+
+    pub fn drop(mut self, pattern: &str) -> Profile {
+        self.drop.push(FieldPattern::new(pattern));
+        self
+    }
+
+    pub fn preset(name: &str) -> Option<Profile> {
+        match name {
+            "minimal" => Some(Profile::new("minimal", false)),
+            "standard" => Some(Profile::new("standard", true)),
+            "ieee" => Some(Profile::new("ieee", true)
+                .drop("doi").drop("url").drop("urldate").drop("eprint*").drop("isbn").drop("issn")),
+            "acm" => Some(Profile::new("acm", true).keep("doi").keep("url")),
+            _ => None,
+        }
+    }
+
+    /**
+    Whether `entry` keeps `field`.
+    */
+    fn keeps(&self, entry: &Entry, field: &str, registry: &TypeRegistry) -> bool {
+        let Some(schema) = registry.schema(entry.entry_type()) else {
+            return !self.drop.iter().any(|p| p.matches(field));
+        };
+        if ALWAYS.contains(&field) || schema.required().iter().any(|r| r.fields().iter().any(|f| f == field)) {
+            return true;
+        }
+        if self.drop.iter().any(|p| p.matches(field)) {
+            return false;
+        }
+        (self.recommended && schema.mentions(field)) || self.keep.iter().any(|p| p.matches(field))
+    }
+
+    /**
+    Remove the fields the profile does not keep; returns whether any were.
+    */
+    pub fn minimize(&self, entry: &mut Entry, registry: &TypeRegistry) -> bool {
+        let removed: Vec<String> = entry.field_names().into_iter()
+            .filter(|f| !self.keeps(entry, f, registry))
+            .map(String::from)
+            .collect();
+        for field in removed.iter() {
+            entry.remove(field);
+        }
+        !removed.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::bibtex::data::BibType;
+
+    #[test]
+    fn test_minimize() {
+        let registry = TypeRegistry::default();
+        let mut e = Entry::new(BibType::Article, "a");
+        for (k, v) in [("author", "A"), ("title", "T"), ("journal", "J"), ("year", "2020"), ("volume", "3"),
+                       ("doi", "10.1/x"), ("url", "https://x"), ("abstract", "..."), ("x-read", "yes")] {
+            e.set(k, v);
+        }
+        let mut ieee = e.clone();
+        assert!(Profile::preset("ieee").unwrap().minimize(&mut ieee, &registry));
+        assert_eq!(ieee.field_names(), vec!["author", "title", "journal", "year", "volume"]);
+        let mut acm = e.clone();
+        Profile::preset("acm").unwrap().minimize(&mut acm, &registry);
+        assert_eq!(acm.field_names(), vec!["author", "title", "journal", "year", "volume", "doi", "url"]);
+        let mut minimal = e.clone();
+        Profile::preset("minimal").unwrap().keep("x-*").minimize(&mut minimal, &registry);
+        assert_eq!(minimal.field_names(), vec!["author", "title", "journal", "year", "x-read"]);
+        assert!(!Profile::preset("minimal").unwrap().minimize(&mut minimal.clone(), &TypeRegistry::empty()));
+        // required fields stay even if a profile drops them
+        let mut strict = e.clone();
+        Profile::new("strict", false).drop("title").minimize(&mut strict, &registry);
+        assert!(strict.has("title"));
+    }
+}
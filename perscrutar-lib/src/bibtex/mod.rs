@@ -1,3 +1,54 @@
 
+#[cfg(feature = "std")]
+pub mod bibliography;
+#[cfg(feature = "std")]
+pub mod completeness;
+#[cfg(feature = "std")]
+pub mod conference;
+#[cfg(feature = "std")]
+pub mod crossrefs;
 pub mod data;
+pub mod error;
+#[cfg(feature = "std")]
+pub mod extra;
+#[cfg(feature = "writer")]
+pub mod format;
+#[cfg(feature = "std")]
+pub mod inference;
+#[cfg(feature = "std")]
+pub mod keys;
+#[cfg(feature = "std")]
+pub mod legal;
+#[cfg(feature = "std")]
+pub mod media;
+#[cfg(feature = "std")]
+pub mod minimize;
+#[cfg(feature = "std")]
+pub mod months;
+#[cfg(feature = "std")]
+pub mod names;
 pub mod parser;
+#[cfg(feature = "std")]
+pub mod patents;
+#[cfg(feature = "std")]
+pub mod policy;
+#[cfg(feature = "std")]
+pub mod reader;
+#[cfg(feature = "std")]
+pub mod serialize;
+#[cfg(feature = "std")]
+pub mod sorting;
+#[cfg(feature = "std")]
+pub mod standards;
+#[cfg(feature = "std")]
+pub mod theses;
+#[cfg(feature = "std")]
+pub mod titles;
+#[cfg(feature = "std")]
+pub mod types;
+#[cfg(feature = "std")]
+pub mod values;
+#[cfg(feature = "std")]
+pub mod volumes;
+#[cfg(feature = "writer")]
+pub mod writer;
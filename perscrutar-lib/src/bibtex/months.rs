@@ -0,0 +1,178 @@
+/*!
+
+Month names in several languages.
+
+`month` values arrive in many shapes: `jan`, `January`, `Jan.`, `3`, and
+from non-English sources `März`, `janvier` or `sept.`. `parse_month`
+recognises English names and abbreviations always, and those of the
+`Language`s given, ignoring case, a trailing period, TeX braces and accents
+(`Marz`, `fevrier` and the German `Maerz` are understood as well).
+Abbreviations must have at least three letters and identify a single month.
+
+`normalize_month` rewrites a recognised month to its number, the form
+biblatex expects. Which languages to accept comes from the `[months]
+languages` configuration setting.
+
+*/
+
+use crate::bibtex::data::Entry;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Language {
+    English,
+    German,
+    French,
+    Spanish,
+    Italian,
+    Portuguese,
+    Dutch,
+}
+
+impl Language {
+    pub const ALL: [Language; 7] = [
+        Language::English, Language::German, Language::French, Language::Spanish,
+        Language::Italian, Language::Portuguese, Language::Dutch,
+    ];
+
+    /** ISO 639-1 code. */
+    pub fn code(&self) -> &'static str {
+        match self {
+            Language::English => "en",
+            Language::German => "de",
+            Language::French => "fr",
+            Language::Spanish => "es",
+            Language::Italian => "it",
+            Language::Portuguese => "pt",
+            Language::Dutch => "nl",
+        }
+    }
+
+    /** Accepts the ISO code or the English name of the language. */
+    pub fn from_code(code: &str) -> Option<Language> {
+        let code = code.trim().to_lowercase();
+        Language::ALL.into_iter().find(|l| l.code() == code || format!("{:?}", l).to_lowercase() == code)
+    }
+
+    /** Month names without accents, January first. */
+    fn months(&self) -> [&'static str; 12] {
+        match self {
+            Language::English => ["january", "february", "march", "april", "may", "june", "july",
+                "august", "september", "october", "november", "december"],
+            Language::German => ["januar", "februar", "marz", "april", "mai", "juni", "juli",
+                "august", "september", "oktober", "november", "dezember"],
+            Language::French => ["janvier", "fevrier", "mars", "avril", "mai", "juin", "juillet",
+                "aout", "septembre", "octobre", "novembre", "decembre"],
+            Language::Spanish => ["enero", "febrero", "marzo", "abril", "mayo", "junio", "julio",
+                "agosto", "septiembre", "octubre", "noviembre", "diciembre"],
+            Language::Italian => ["gennaio", "febbraio", "marzo", "aprile", "maggio", "giugno", "luglio",
+                "agosto", "settembre", "ottobre", "novembre", "dicembre"],
+            Language::Portuguese => ["janeiro", "fevereiro", "marco", "abril", "maio", "junho", "julho",
+                "agosto", "setembro", "outubro", "novembro", "dezembro"],
+            Language::Dutch => ["januari", "februari", "maart", "april", "mei", "juni", "juli",
+                "augustus", "september", "oktober", "november", "december"],
+        }
+    }
+}
+
+/**
+Lower-case `s` and strip accents, braces and TeX accent commands, so that
+`M{\"a}rz`, `März` and `Marz` compare equal. `ae`/`oe`/`ue` are German
+spellings of umlauts and are folded too.
+*/
+fn fold(s: &str) -> String {
+    let mut out = String::new();
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' | '}' => {}
+            '\\' => {
+                // `\"a`, `\'e`, `\^u`: drop the accent command
+                chars.next_if(|c| !c.is_alphabetic());
+            }
+            _ => out.extend(c.to_lowercase().map(|c| match c {
+                'á' | 'à' | 'â' | 'ä' | 'ã' => 'a',
+                'é' | 'è' | 'ê' | 'ë' => 'e',
+                'í' | 'ì' | 'î' | 'ï' => 'i',
+                'ó' | 'ò' | 'ô' | 'ö' | 'õ' => 'o',
+                'ú' | 'ù' | 'û' | 'ü' => 'u',
+                'ç' => 'c',
+                c => c,
+            })),
+        }
+    }
+    out
+}
+
+/**
+The month number (1–12) of `value`, or `None` if it is not a single month
+in English or one of `languages`.
+*/
+pub fn parse_month(value: &str, languages: &[Language]) -> Option<u8> {
+    let word = fold(value.trim()).trim_end_matches('.').trim().to_string();
+    if let Ok(n) = word.parse::<u8>() {
+        return Some(n).filter(|n| (1..=12).contains(n));
+    }
+    if word.chars().count() < 3 || !word.chars().all(|c| c.is_alphabetic()) {
+        return None;
+    }
+    let german = word.replace("ae", "a");
+    let mut found = None;
+    for lang in std::iter::once(&Language::English).chain(languages) {
+        for (i, name) in lang.months().iter().enumerate() {
+            let matches = name.starts_with(&word) || (*lang == Language::German && name.starts_with(&german));
+            match found {
+                _ if !matches => {}
+                None => found = Some(i as u8 + 1),
+                Some(m) if m != i as u8 + 1 => return None,
+                Some(_) => {}
+            }
+        }
+    }
+    found
+}
+
+/**
+Replace a recognised `month` with its number. Returns whether the entry
+changed.
+*/
+pub fn normalize_month(entry: &mut Entry, languages: &[Language]) -> bool {
+    let Some(value) = entry.get("month") else { return false };
+    match parse_month(value, languages) {
+        Some(m) if value != m.to_string() => {
+            entry.set("month", &m.to_string());
+            true
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::bibtex::data::BibType;
+
+    #[test]
+    fn test_parse_month() {
+        let all = &Language::ALL;
+        for (value, month) in [("jan", 1), ("Sept.", 9), ("{März}", 3), ("Maerz", 3), ("M{\\\"a}rz", 3),
+                               ("janvier", 1), ("août", 8), ("Juil.", 7), ("dic", 12), ("05", 5), ("mei", 5)] {
+            assert_eq!(parse_month(value, all), Some(month), "{}", value);
+        }
+        assert_eq!(parse_month("März", &[]), None);
+        assert_eq!(parse_month("jui", all), None);
+        assert_eq!(parse_month("13", all), None);
+        assert_eq!(parse_month("Jan.--Feb.", all), None);
+        assert_eq!(Language::from_code("German"), Some(Language::German));
+    }
+
+    #[test]
+    fn test_normalize_month() {
+        let mut e = Entry::new(BibType::Article, "a");
+        e.set("month", "février");
+        assert!(!normalize_month(&mut e, &[Language::German]));
+        assert!(normalize_month(&mut e, &[Language::French]));
+        assert_eq!(e.get("month"), Some("2"));
+        assert!(!normalize_month(&mut e, &[Language::French]));
+    }
+}
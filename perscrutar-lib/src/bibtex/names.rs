@@ -0,0 +1,335 @@
+/*!
+
+BibTeX personal names.
+
+A name list separates names with `and`; each name is written in one of
+BibTeX's three forms and split into its four parts:
+
+```text
+Donald E. Knuth             First von Last
+van Leunen, Mary-Claire     von Last, First
+Ford, Jr., Henry            von Last, Jr, First
+```
+
+The von part is the run of lower-case words before the last name, as in
+`Ludwig van Beethoven`. Text in braces is a single word and never split,
+so `{Barnes and Noble}` is one corporate name. `others` stands for
+further, unnamed authors (`et al.`).
+
+`Entry::authors`, `Entry::editors` and `Entry::holders` parse an entry's
+name lists.
+
+Names are equal if their parts are, ignoring runs of whitespace, and order
+by last name, then first name, von part and Jr part, as an index of
+authors would.
+
+*/
+
+use std::cmp::Ordering;
+use std::hash::{Hash, Hasher};
+use crate::bibtex::data::Entry;
+
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(default))]
+pub struct Name {
+    pub first: String,
+    pub von: String,
+    pub last: String,
+    pub jr: String,
+}
+
+/**
+Split `s` at `separator` (a word or a single character) where it is not
+inside braces.
+*/
+fn split_top_level<'a>(s: &'a str, separator: &str) -> Vec<&'a str> {
+    let mut out = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    let word = separator.len() > 1;
+    let bytes = s.as_bytes();
+    let mut i = 0;
+    while i < s.len() {
+        match bytes[i] {
+            b'{' => depth += 1,
+            b'}' if depth > 0 => depth -= 1,
+            _ if depth == 0 && bytes[i..].get(..separator.len()).is_some_and(|b| b.eq_ignore_ascii_case(separator.as_bytes())) => {
+                let before = i == 0 || bytes[i - 1].is_ascii_whitespace();
+                let after = bytes.get(i + separator.len()).map(|b| b.is_ascii_whitespace()).unwrap_or(true);
+                if !word || (before && after) {
+                    out.push(&s[start..i]);
+                    i += separator.len();
+                    start = i;
+                    continue;
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    out.push(&s[start..]);
+    out
+}
+
+/** Words of a name part; a braced group is one word. */
+fn words(s: &str) -> Vec<&str> {
+    let mut out = Vec::new();
+    let mut depth = 0;
+    let mut start = None;
+    for (i, c) in s.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            _ if c.is_whitespace() && depth == 0 => {
+                if let Some(s0) = start.take() {
+                    out.push(&s[s0..i]);
+                }
+                continue;
+            }
+            _ => {}
+        }
+        start.get_or_insert(i);
+    }
+    if let Some(s0) = start {
+        out.push(&s[s0..]);
+    }
+    out
+}
+
+/**
+Whether a word starts in lower case, which makes it part of the von part.
+Braced words count as upper case unless they begin with a TeX command.
+*/
+fn is_von(word: &str) -> bool {
+    let letters = word.trim_start_matches('{');
+    if word.starts_with('{') && !letters.starts_with('\\') {
+        return false;
+    }
+    let letters = letters.trim_start_matches('\\').trim_start_matches(|c: char| c.is_ascii_punctuation());
+    letters.chars().find(|c| c.is_alphabetic()).map(|c| c.is_lowercase()).unwrap_or(false)
+}
+
+impl Name {
+    pub fn parse(name: &str) -> Name {
+        let parts: Vec<&str> = split_top_level(name.trim(), ",").into_iter().map(str::trim).collect();
+        match parts.as_slice() {
+            [whole] => {
+                let w = words(whole);
+                if w.len() <= 1 {
+                    return Name { last: w.join(" "), ..Name::default() };
+                }
+                // von starts at the first lower-case word and ends before the
+                // last lower-case word that is not the final word
+                let von_start = w[..w.len() - 1].iter().position(|x| is_von(x));
+                match von_start {
+                    Some(s) => {
+                        let von_end = (s..w.len() - 1).rev().find(|i| is_von(w[*i])).unwrap_or(s) + 1;
+                        Name {
+                            first: w[..s].join(" "),
+                            von: w[s..von_end].join(" "),
+                            last: w[von_end..].join(" "),
+                            jr: String::new(),
+                        }
+                    }
+                    None => Name {
+                        first: w[..w.len() - 1].join(" "),
+                        last: String::from(w[w.len() - 1]),
+                        ..Name::default()
+                    },
+                }
+            }
+            [von_last, rest @ ..] => {
+                let w = words(von_last);
+                let von_end = (0..w.len().saturating_sub(1)).rev().find(|i| is_von(w[*i])).map(|i| i + 1).unwrap_or(0);
+                let (jr, first) = match rest {
+                    [first] => ("", *first),
+                    [jr, first, ..] => (*jr, *first),
+                    [] => ("", ""),
+                };
+                Name {
+                    first: String::from(first),
+                    von: w[..von_end].join(" "),
+                    last: w[von_end..].join(" "),
+                    jr: String::from(jr),
+                }
+            }
+            [] => Name::default(),
+        }
+    }
+
+    /** `others`, standing for "et al.". */
+    pub fn is_others(&self) -> bool {
+        self.first.is_empty() && self.von.is_empty() && self.jr.is_empty() && self.last == "others"
+    }
+
+    /** von and last part, as printed and sorted by most styles. */
+    pub fn von_last(&self) -> String {
+        if self.von.is_empty() { self.last.clone() } else { format!("{} {}", self.von, self.last) }
+    }
+
+    /** The parts in comparison order, with whitespace collapsed. */
+    fn canonical(&self) -> [String; 4] {
+        [&self.last, &self.first, &self.von, &self.jr].map(|part| part.split_whitespace().collect::<Vec<&str>>().join(" "))
+    }
+}
+
+impl PartialEq for Name {
+    fn eq(&self, other: &Name) -> bool {
+        self.canonical() == other.canonical()
+    }
+}
+
+impl Eq for Name {}
+
+impl Hash for Name {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.canonical().hash(state);
+    }
+}
+
+impl PartialOrd for Name {
+    fn partial_cmp(&self, other: &Name) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Name {
+    fn cmp(&self, other: &Name) -> Ordering {
+        self.canonical().cmp(&other.canonical())
+    }
+}
+
+/**
+The names of a name list such as the `author` field.
+*/
+pub fn parse_names(list: &str) -> Vec<Name> {
+    split_names(list).into_iter().map(Name::parse).collect()
+}
+
+/** The names of a name list as written, split at `and` outside braces. */
+pub fn split_names(list: &str) -> Vec<&str> {
+    if list.trim().is_empty() {
+        return vec![];
+    }
+    split_top_level(list, "and")
+}
+
+impl Entry {
+    /**
+    The names in `author`, with a final `others` for "et al."; empty if
+    the entry has no authors.
+    */
+    pub fn authors(&self) -> Vec<Name> {
+        parse_names(self.get("author").unwrap_or(""))
+    }
+
+    /** The names in `editor`, like `authors`. */
+    pub fn editors(&self) -> Vec<Name> {
+        parse_names(self.get("editor").unwrap_or(""))
+    }
+
+    /** The names in `holder`, the owners of a patent, like `authors`. */
+    pub fn holders(&self) -> Vec<Name> {
+        parse_names(self.get("holder").unwrap_or(""))
+    }
+}
+
+/** A name in BibTeX's `von Last, Jr, First` form. */
+pub fn bibtex_name(first: &str, von: &str, last: &str, jr: &str) -> String {
+    let von_last = if von.is_empty() { String::from(last) } else { format!("{} {}", von, last) };
+    match (jr.is_empty(), first.is_empty()) {
+        (true, true) => von_last,
+        (true, false) => format!("{}, {}", von_last, first),
+        (false, _) => format!("{}, {}, {}", von_last, jr, first),
+    }
+}
+
+/**
+Strip TeX markup for sorting and labels: braces and accent commands go,
+letters and digits are kept, everything else becomes a space.
+*/
+pub fn purify(s: &str) -> String {
+    let mut out = String::new();
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                // `\"o` keeps the `o`, `\ss` and other commands keep their name
+                chars.next_if(|c| !c.is_alphanumeric());
+            }
+            '{' | '}' => {}
+            '-' | '~' => out.push(' '),
+            c if c.is_alphanumeric() || c.is_whitespace() => out.push(c),
+            _ => {}
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let n = Name::parse("Donald E. Knuth");
+        assert_eq!((n.first.as_str(), n.last.as_str()), ("Donald E.", "Knuth"));
+        let n = Name::parse("Ludwig van Beethoven");
+        assert_eq!((n.first.as_str(), n.von.as_str(), n.last.as_str()), ("Ludwig", "van", "Beethoven"));
+        let n = Name::parse("van Leunen, Mary-Claire");
+        assert_eq!((n.first.as_str(), n.von.as_str(), n.last.as_str()), ("Mary-Claire", "van", "Leunen"));
+        let n = Name::parse("Ford, Jr., Henry");
+        assert_eq!((n.first.as_str(), n.last.as_str(), n.jr.as_str()), ("Henry", "Ford", "Jr."));
+        assert_eq!(Name::parse("{Barnes and Noble, Inc.}").last, "{Barnes and Noble, Inc.}");
+        assert_eq!(purify("G{\\\"o}del--Escher"), "Godel  Escher");
+    }
+
+    #[test]
+    fn test_parse_names() {
+        let names = parse_names("Knuth, Donald and {Barnes and Noble} AND Anderson, Andy and others");
+        let last: Vec<&str> = names.iter().map(|n| n.last.as_str()).collect();
+        assert_eq!(last, vec!["Knuth", "{Barnes and Noble}", "Anderson", "others"]);
+        assert!(names[3].is_others());
+        assert!(parse_names(" ").is_empty());
+        assert_eq!(parse_names("Alexander Random")[0].last, "Random");
+    }
+
+    #[test]
+    fn test_non_ascii() {
+        let names = parse_names("Jürgen Müller and Åsa Ångström and 村上 春樹 and Ünal, Öykü");
+        let last: Vec<&str> = names.iter().map(|n| n.last.as_str()).collect();
+        assert_eq!(last, vec!["Müller", "Ångström", "春樹", "Ünal"]);
+        assert_eq!(names[3].first, "Öykü");
+        assert_eq!(parse_names("毛泽东 and 周恩来").len(), 2);
+        assert_eq!(parse_names("Éand Ànd").len(), 1);
+    }
+
+    #[test]
+    fn test_authors() {
+        use crate::bibtex::data::BibType;
+
+        let mut e = Entry::new(BibType::Book, "b");
+        e.set("author", "Cox, David A. and Knuth, Donald E. and others");
+        let authors = e.authors();
+        assert_eq!(authors.len(), 3);
+        assert_eq!((authors[1].first.as_str(), authors[1].last.as_str()), ("Donald E.", "Knuth"));
+        assert!(authors[2].is_others());
+        assert!(e.editors().is_empty());
+    }
+
+    #[test]
+    fn test_ordering() {
+        use std::collections::HashSet;
+
+        let knuth = Name::parse("Knuth, Donald E.");
+        let spaced = Name { first: String::from("Donald  E."), last: String::from(" Knuth"), ..Name::default() };
+        assert_eq!(knuth, spaced);
+        assert_eq!(HashSet::from([knuth.clone(), spaced]).len(), 1);
+
+        let mut names = parse_names("Ludwig van Beethoven and Knuth, Donald and Knuth, Alan and Aho, A.");
+        names.sort();
+        let last: Vec<String> = names.iter().map(|n| format!("{} {}", n.first, n.last)).collect();
+        assert_eq!(last, vec!["A. Aho", "Ludwig Beethoven", "Alan Knuth", "Donald Knuth"]);
+    }
+}
@@ -1,4 +1,4 @@
-/**
+/*!
 
 The goal of this parser is to read in something like this:
 
@@ -12,27 +12,76 @@ The goal of this parser is to read in something like this:
     doi = {10.1002/9781118400722}
 }
 
+Besides quoted and braced strings, a value may be a bare number or the name
+of an `@string` macro, and pieces may be joined with `#`:
+
+@string{jacm = {Journal of the ACM}}
+@article{Knuth-LP,
+    journal = jacm # { Letters},
+    year = 1984,
+    month = jan
+}
+
+Macros are expanded while parsing, from the definitions seen so far (see
+`Macros`); a name without a definition, such as the month abbreviation
+above, stands for itself. A value that is only such a name is kept as a
+macro reference (`Entry::is_macro`), so that the writer puts it back bare.
+
+By default a `#` that does not join two pieces starts a comment running to
+the end of the line, as elsewhere, even inside a value. Standard BibTeX has
+no such comments: there `#` is always concatenation, and inside strings it
+is text like any other character but braces. `ParseOptions` chooses between
+the two (`Comments`), so files written for BibTeX, with `#` in titles, can be
+read as they are meant. The values of `data::VERBATIM_FIELDS`, such as a
+URL with a `#fragment`, are always read the standard way.
+
+One malformed entry normally fails the whole parse. With
+`ParseOptions::lenient`, the parser instead skips to the next `@` that
+starts a line and carries on; `parse_with_diagnostics` returns what was
+skipped along with the entries it could read.
+
+Every entry and error is located in the input. Errors carry the line,
+column and, once the parser has read it, the key of the entry they are in:
+
+entry `Cox-CFT`, line 42, column 5: expected `}`
+
+and `parse_with_spans` gives the `Span` of each entry, for editors to jump
+to.
+
+`@preamble`s are read like field values in standard BibTeX, whatever
+`Comments` says, and their content is kept on the
+`Bibliography` (`parse_bibliography`) to be written out again. `@comment`s
+are discarded unless `ParseOptions::keep_comments` says otherwise;
+`parse_document` returns both along with the entries.
+
+`parse_items` reads a file without losing anything: entries with their
+values unexpanded, `@string`s, `@comment`s and `@preamble`s as written, and
+the text between them, for tools such as the formatter that write a file
+back.
 
 */
 
-use std::str;
-use std::collections::HashMap;
-use std::ops::Not;
+use core::str;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
+use alloc::{format, vec};
 use nom::{
     branch::alt,
-    bytes::complete::{escaped, tag, take_while, take_while1, take_until, is_not},
-    character::complete::{alphanumeric1 as alphanumeric, char, one_of},
-    character::{is_alphabetic, is_alphanumeric},
-    combinator::{cut, map, opt, value},
-    error::{context, convert_error, ContextError, ErrorKind, ParseError, VerboseError},
-    multi::{fold_many0, separated_list0},
-    number::complete::double,
-    sequence::{delimited, pair, preceded, separated_pair, terminated, tuple},
+    bytes::complete::{escaped, tag, tag_no_case, take_while, take_while1, take_until},
+    character::complete::{char, one_of},
+    character::is_alphabetic,
+    combinator::{cut, map, opt, peek, value, verify},
+    error::{context, ContextError, ErrorKind, ParseError, VerboseError, VerboseErrorKind},
+    multi::separated_list0,
+    sequence::{delimited, preceded, terminated, tuple},
     Err, IResult,
 };
 
-use nom_unicode::is_alphanumeric as is_alphanumeric_unicode;
-use crate::bibtex::data::*;
+#[cfg(feature = "std")]
+use crate::bibtex::bibliography::Bibliography;
+use crate::bibtex::data::{is_verbatim, BibType, Entry};
+use crate::bibtex::error::{LineIndex, ParseDiagnostic, Span};
 
 /**
 Space Parser
@@ -53,61 +102,141 @@ fn alphabeticlabel<'a, E: ParseError<&'a str>>(i: &'a str) -> IResult<&'a str, &
   })(i)
 }
 
+/**
+Citation keys may also contain digits and some punctuation, as in
+`knuth:1984.lp`.
+*/
+fn keylabel<'a, E: ParseError<&'a str>>(i: &'a str) -> IResult<&'a str, &'a str, E> {
+  let chars = "-_:./+'";
+
+  take_while(move |c: char| {
+    c.is_alphanumeric() || chars.contains(c)
+  })(i)
+}
+
+fn keylabel_comment<'a, E: ParseError<&'a str>>(comments: Comments) -> impl FnMut(&'a str) -> IResult<&'a str, &'a str, E> {
+    move |i| alt((terminated(keylabel, eolcomment(comments)),
+         keylabel))(i)
+}
+
 fn alphanumericplus<'a, E: ParseError<&'a str>>(i: &'a str) -> IResult<&'a str, &'a str, E> {
-  let chars = "-_.,;:/ ^$+*\\\n";
+  // whitespace includes the `\r` of CRLF line endings in multi-line values
+  let chars = "-_.,;:/ ^$+*~\\\t\r\n";
 
   take_while(move |c: char| {
-    is_alphanumeric_unicode(c as char) || chars.contains(c)
+    c.is_alphanumeric() || chars.contains(c)
   })(i)
 }
 
+/**
+How a `#` that does not join two pieces of a value is read.
+*/
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Comments {
+    /**
+    It starts a comment running to the end of the line, inside values too;
+    strings hold letters, digits, spaces and common punctuation.
+    */
+    #[default]
+    Hash,
+    /**
+    Standard BibTeX: there are no such comments, and strings hold any text
+    with balanced braces.
+    */
+    Standard,
+}
+
 /**
 Parse alphanumeric strings, allowing escapes and other properties 
-that can be inside a label.
+that can be inside a label. In standard BibTeX, a string is anything up to
+a brace, or up to the closing `"` for a `quoted` one.
 */
-fn parse_str<'a, E: ParseError<&'a str>>(i: &'a str) -> IResult<&'a str, &'a str, E> {
-  escaped(alphanumericplus, '\\', one_of("\"n\\"))(i)
+fn parse_str<'a, E: ParseError<&'a str>>(comments: Comments, quoted: bool) -> impl FnMut(&'a str) -> IResult<&'a str, &'a str, E> {
+  move |i| match comments {
+    Comments::Hash => escaped(alphanumericplus, '\\', one_of("\"n\\"))(i),
+    Comments::Standard => take_while(|c: char| c != '{' && c != '}' && !(quoted && c == '"'))(i),
+  }
 }
 
 /**
 Utility function, remove comments entirely
 */
-fn eolcomment<'a, E: ParseError<&'a str>>(i: &'a str) -> IResult<&'a str, (), E> {
-  value(
-    (), // Output is thrown away.
-    tuple((
-        tag("#"),
-        take_until("\n"),
-        tag("\n")
-    ))
-  )(i)
+fn eolcomment<'a, E: ParseError<&'a str>>(comments: Comments) -> impl FnMut(&'a str) -> IResult<&'a str, (), E> {
+  move |i| match comments {
+    Comments::Hash => value(
+      (), // Output is thrown away.
+      tuple((
+          tag("#"),
+          take_until("\n"),
+          tag("\n")
+      ))
+    )(i),
+    Comments::Standard => Err(Err::Error(E::from_error_kind(i, ErrorKind::Tag))),
+  }
 }
 
-fn parse_str_with_comments<'a, E: ParseError<&'a str>>(i: &'a str) 
--> IResult<&'a str, String, E> {
-  map(separated_list0(eolcomment, parse_str), |result: Vec<&str>| {
+fn parse_str_with_comments<'a, E: ParseError<&'a str>>(comments: Comments, quoted: bool)
+-> impl FnMut(&'a str) -> IResult<&'a str, String, E> {
+  move |i| map(separated_list0(eolcomment(comments), parse_str(comments, quoted)), |result: Vec<&str>| {
     let mut s = String::new();
     for r in result.iter() {
         s.push_str(r)
     }
-    s.clone()
+    s
   })(i)
 }
 
-fn alphabeticlabel_comment<'a, E: ParseError<&'a str>>(i: &'a str) -> IResult<&'a str, &'a str, E> {
-    alt((terminated(alphabeticlabel, eolcomment),
+fn alphabeticlabel_comment<'a, E: ParseError<&'a str>>(comments: Comments) -> impl FnMut(&'a str) -> IResult<&'a str, &'a str, E> {
+    move |i| alt((terminated(alphabeticlabel, eolcomment(comments)),
          alphabeticlabel))(i)
 }
 
+/**
+Text that may contain balanced groups of braces, nested to any depth. The
+braces are kept, so case protection such as `{LaTeX}` survives being read
+and written again; `names::purify` removes them for display. Groups are
+counted rather than recursed into, so deep nesting cannot exhaust the stack.
+*/
+fn balanced<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
+  comments: Comments,
+  quoted: bool,
+) -> impl FnMut(&'a str) -> IResult<&'a str, String, E> {
+  move |mut i| {
+  let mut out = String::new();
+  let mut depth = 0usize;
+  loop {
+    // a `"` inside braces does not end a quoted string
+    let (rest, text) = match depth {
+      0 => parse_str_with_comments(comments, quoted)(i)?,
+      _ => cut(parse_str_with_comments(comments, false))(i)?,
+    };
+    out.push_str(&text);
+    if let Ok((rest, _)) = char::<&str, E>('{')(rest) {
+      out.push('{');
+      depth += 1;
+      i = rest;
+      continue;
+    }
+    if depth == 0 {
+      return Ok((rest, out));
+    }
+    let (rest, _) = cut(char('}'))(rest)?;
+    out.push('}');
+    depth -= 1;
+    i = rest;
+  }
+  }
+}
+
 /** String_spm finds entries surrounded by 
   "" possibly split over multiple lines
 */
 fn string_spm<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
-  i: &'a str,
-) -> IResult<&'a str, String, E> {
-  context(
+  comments: Comments,
+) -> impl FnMut(&'a str) -> IResult<&'a str, String, E> {
+  move |i| context(
     "string",
-    preceded(char('\"'), cut(terminated(parse_str_with_comments, char('\"')))),
+    preceded(char('\"'), cut(terminated(balanced(comments, true), char('\"')))),
   )(i)
 }
 
@@ -115,81 +244,586 @@ fn string_spm<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
   {} possibly split over multiple lines
 */
 fn string_brc<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
-  i: &'a str,
-) -> IResult<&'a str, String, E> {
-  context(
+  comments: Comments,
+) -> impl FnMut(&'a str) -> IResult<&'a str, String, E> {
+  move |i| context(
     "string",
-    preceded(char('{'), cut(terminated(parse_str_with_comments, char('}')))),
+    preceded(char('{'), cut(terminated(balanced(comments, false), char('}')))),
   )(i)
 }
 
+/**
+One piece of a field value: a quoted or braced string, a bare number or a
+macro name.
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Piece {
+    Text(String),
+    Macro(String),
+}
+
+/**
+A bare number or macro name.
+*/
+fn bare<'a, E: ParseError<&'a str>>(i: &'a str) -> IResult<&'a str, Piece, E> {
+  map(take_while1(|c: char| c.is_alphanumeric() || "-_.:+/".contains(c)), |name: &str| {
+    if name.chars().all(|c| c.is_ascii_digit()) {
+        Piece::Text(String::from(name))
+    } else {
+        Piece::Macro(String::from(name))
+    }
+  })(i)
+}
+
+fn piece<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
+  comments: Comments,
+) -> impl FnMut(&'a str) -> IResult<&'a str, Piece, E> {
+  move |i| alt((map(string_spm(comments), Piece::Text), map(string_brc(comments), Piece::Text), bare))(i)
+}
+
+/**
+Pieces joined by `#`. With `Comments::Hash`, a `#` followed by something
+that does not end where a value can end is left alone, as the start of a
+comment; in standard BibTeX a `#` must be followed by another piece. The
+strings of a `verbatim` value are read as standard BibTeX either way.
+*/
+fn concatenation<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
+  comments: Comments,
+  verbatim: bool,
+) -> impl FnMut(&'a str) -> IResult<&'a str, Vec<Piece>, E> {
+  move |i| {
+  let strings = if verbatim { Comments::Standard } else { comments };
+  let (mut i, first) = piece(strings)(i)?;
+  let mut pieces = vec![first];
+  loop {
+    let next = match comments {
+        Comments::Hash => tuple((sp::<E>, char('#'), sp, piece(strings), peek(preceded(sp, one_of(",}#")))))(i)
+            .ok()
+            .map(|(rest, (_, _, _, p, _))| (rest, p)),
+        Comments::Standard => match preceded(tuple((sp::<E>, char('#'), sp)), cut(piece(strings)))(i) {
+            Ok(next) => Some(next),
+            Err(Err::Error(_)) => None,
+            Err(e) => return Err(e),
+        },
+    };
+    match next {
+        Some((rest, p)) => {
+            pieces.push(p);
+            i = rest;
+        }
+        None => return Ok((i, pieces)),
+    }
+  }
+  }
+}
+
 fn key_value<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
-  i: &'a str,
-) -> IResult<&'a str, (&'a str, String), E> {
-  separated_pair(
-    preceded(sp, alphabeticlabel_comment),
-    cut(preceded(sp, char('='))),
-    preceded(sp, alt((string_spm, string_brc)))
-  )(i)
+  comments: Comments,
+) -> impl FnMut(&'a str) -> IResult<&'a str, (&'a str, Vec<Piece>), E> {
+  move |i| {
+    let (rest, name) = preceded(sp, verify(alphabeticlabel_comment(comments), |n: &str| !n.is_empty()))(i)?;
+    let (rest, _) = cut(preceded(sp, char('=')))(rest)?;
+    let (rest, value) = preceded(sp, concatenation(comments, is_verbatim(name)))(rest)?;
+    Ok((rest, (name, value)))
+  }
 }
 
+/**
+Field names and unexpanded values of an entry, in file order, repeated
+fields included.
+*/
+pub type Fields = Vec<(String, Vec<Piece>)>;
+
 fn kvlist<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
-  i: &'a str,
-) -> IResult<&'a str, HashMap<String, String>, E> {
+  comments: Comments,
+) -> impl FnMut(&'a str) -> IResult<&'a str, Fields, E> {
+    move |i| {
     let sep = alt((
-            terminated(preceded(sp, tag(",")), preceded(sp, eolcomment)),
-            terminated(tag(","), preceded(sp, eolcomment)),
-            terminated(preceded(sp, tag(",")), eolcomment),
+            terminated(preceded(sp, tag(",")), preceded(sp, eolcomment(comments))),
+            terminated(tag(","), preceded(sp, eolcomment(comments))),
+            terminated(preceded(sp, tag(",")), eolcomment(comments)),
             preceded(sp, tag(",")),
             tag(","),
         ));
     context(
         "map",
+        // a comma after the last field is allowed
         cut(terminated(
             map(
-            separated_list0(sep, key_value),
+            terminated(separated_list0(sep, key_value(comments)), opt(preceded(sp, tag(",")))),
             |tuple_vec| {
                 tuple_vec
                 .into_iter()
-                .map(|(k, v)| (String::from(k), String::from(v)))
+                .map(|(k, v)| (String::from(k), v))
                 .collect()
             },
             ),
             sp,
         )),
     )(i)
+    }
+}
+
+/**
+Parse a single entry, returning its type, citation key and fields.
+*/
+pub fn bibentry<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
+  i: &'a str,
+) -> IResult<&'a str, (&'a str, &'a str, Fields), E> {
+    bibentry_with(Comments::Hash)(i)
 }
 
-#[derive(Debug)]
-struct BibItem<'a>(&'a str, &'a str, HashMap<String, String>);
+/**
+`bibentry` reading `#` as `comments` says. Entries are delimited by braces
+or, as BibTeX also allows, parentheses, and may have no fields at all, as
+in `@misc{key}`.
+*/
+pub fn bibentry_with<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
+  comments: Comments,
+) -> impl FnMut(&'a str) -> IResult<&'a str, (&'a str, &'a str, Fields), E> {
+    move |i| context("bibitem", |i| {
+        let (i, _) = preceded(sp, char('@'))(i)?;
+        let (i, itemtype) = cut(terminated(alphabeticlabel_comment(comments), sp))(i)?;
+        let (i, close) = match i.chars().next() {
+            Some('(') => (&i[1..], ')'),
+            _ => (cut(char('{'))(i)?.0, '}'),
+        };
+        let (i, key) = cut(preceded(sp, terminated(keylabel_comment(comments), sp)))(i)?;
+        let (i, _) = match peek(char::<&str, E>(close))(i) {
+            Ok(_) => (i, None),
+            Err(_) => cut(map(char(','), Some))(i)?,
+        };
+        let (i, fields) = kvlist(comments)(i)?;
+        let (i, _) = cut(char(close))(i)?;
+        Ok((i, (itemtype, key, fields)))
+    })(i)
+}
 
-fn bibentry<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
+/**
+Parse an `@string{name = value}` macro definition.
+*/
+pub fn string_definition<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
   i: &'a str,
-) -> IResult<&'a str, (&str, &str, HashMap<String, String>), E> {
-    context(
-        "bibitem",
+) -> IResult<&'a str, (&'a str, Vec<Piece>), E> {
+    string_definition_with(Comments::Hash)(i)
+}
+
+/**
+`string_definition` reading `#` as `comments` says.
+*/
+pub fn string_definition_with<'a, E: ParseError<&'a str> + ContextError<&'a str>>(
+  comments: Comments,
+) -> impl FnMut(&'a str) -> IResult<&'a str, (&'a str, Vec<Piece>), E> {
+    move |i| context(
+        "string definition",
         preceded(sp,
         preceded(
-            char('@'),
-            tuple((
-                cut(terminated(
-                    terminated(alphabeticlabel_comment, sp),
-                    char('{'),
-                )),
-                cut(terminated(
-                    preceded(sp, terminated(alphabeticlabel_comment, sp)),
-                    char(','),
-                )),
-                cut(terminated(
-                    kvlist,
-                    char('}'),
-                )),
-            )),
+            terminated(tag_no_case("@string"), sp),
+            cut(delimited(char('{'), key_value(comments), preceded(sp, char('}')))),
         ),
         ),
     )(i)
 }
 
+fn describe(kind: &VerboseErrorKind) -> String {
+    match kind {
+        VerboseErrorKind::Context(c) => format!("invalid {}", c),
+        VerboseErrorKind::Char(c) => format!("expected `{}`", c),
+        VerboseErrorKind::Nom(k) => format!("unexpected input ({:?})", k),
+    }
+}
+
+fn convert_error(input: &str, e: Err<VerboseError<&str>>) -> crate::bibtex::error::ParseError {
+    match e {
+        Err::Error(e) | Err::Failure(e) => {
+            let (at, kind) = &e.errors[0];
+            crate::bibtex::error::ParseError::at(input, input.len() - at.len(), &describe(kind))
+        }
+        Err::Incomplete(_) => crate::bibtex::error::ParseError::at(input, input.len(), "unexpected end of input"),
+    }
+}
+
+/**
+`@string` macros by name, compared case-insensitively as BibTeX does.
+*/
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Macros {
+    /** Values by lower-cased name. */
+    definitions: BTreeMap<String, String>,
+}
+
+impl Macros {
+    pub fn new() -> Macros {
+        Macros::default()
+    }
+
+    /**
+    Define `name`, replacing any earlier definition.
+    */
+    pub fn define(&mut self, name: &str, value: &str) {
+        self.definitions.insert(name.to_lowercase(), String::from(value));
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        match self.definitions.get(name) {
+            Some(v) => Some(v.as_str()),
+            None if name.chars().any(char::is_uppercase) => self.definitions.get(&name.to_lowercase()).map(String::as_str),
+            None => None,
+        }
+    }
+
+    /**
+    Definitions by lower-cased name, in alphabetical order.
+    */
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.definitions.iter().map(|(n, v)| (n.as_str(), v.as_str()))
+    }
+
+    /**
+    The value `pieces` stand for; an undefined macro stands for its name.
+    */
+    pub fn expand(&self, pieces: &[Piece]) -> String {
+        pieces.iter().map(|p| match p {
+            Piece::Text(t) => t.as_str(),
+            Piece::Macro(name) => self.get(name).unwrap_or(name),
+        }).collect()
+    }
+
+    /**
+    The name `pieces` consist of, if they are a single macro without a
+    definition, such as the months BibTeX styles define.
+    */
+    pub fn undefined<'p>(&self, pieces: &'p [Piece]) -> Option<&'p str> {
+        match pieces {
+            [Piece::Macro(name)] if self.get(name).is_none() => Some(name),
+            _ => None,
+        }
+    }
+}
+
+/**
+The `@comment` or `@preamble` `rest` starts with: whether it is a preamble,
+its content between the delimiters, and the input after it; `None` if
+`rest` does not start with one.
+*/
+fn special<'a>(input: &str, rest: &'a str) -> Result<Option<(bool, &'a str, &'a str)>, crate::bibtex::error::ParseError> {
+    // the keyword must end there, so that `@commentary` is an entry
+    let starts = |name: &str| rest.as_bytes().get(..name.len()).is_some_and(|b| b.eq_ignore_ascii_case(name.as_bytes()))
+        && rest[name.len()..].chars().next().is_none_or(|c| c.is_whitespace() || c == '{' || c == '(');
+    let (preamble, after) = match ["@comment", "@preamble"].into_iter().find(|n| starts(n)) {
+        Some(name) => (name == "@preamble", rest[name.len()..].trim_start()),
+        None => return Ok(None),
+    };
+    let close = match after.chars().next() {
+        Some('{') => '}',
+        Some('(') => ')',
+        // a bare `@comment` comments out the rest of its line
+        _ if !preamble => {
+            let end = rest.find('\n').unwrap_or(rest.len());
+            return Ok(Some((false, rest["@comment".len()..end].trim(), &rest[end..])));
+        }
+        _ => return Err(crate::bibtex::error::ParseError::at(input, input.len() - after.len(), "expected `{` or `(`")),
+    };
+    let mut depth = 0;
+    for (i, c) in after.char_indices().skip(1) {
+        match c {
+            '{' => depth += 1,
+            '}' if depth > 0 => depth -= 1,
+            c if c == close && depth == 0 => return Ok(Some((preamble, &after[1..i], &after[i + 1..]))),
+            _ => {}
+        }
+    }
+    Err(crate::bibtex::error::ParseError::at(input, input.len() - rest.len(), "unterminated @comment or @preamble"))
+}
+
+/**
+Parse every entry in `input`, in order. Entries may only be separated
+by whitespace, `@comment`s and `@preamble`s. `@string` macros are expanded
+as they are used.
+*/
+pub fn parse_entries(input: &str) -> Result<Vec<Entry>, crate::bibtex::error::ParseError> {
+    parse_entries_with(input, &mut Macros::new())
+}
+
+/**
+`parse_entries` starting from the definitions in `macros`, such as
+abbreviations shared between several files, and adding those in `input`.
+*/
+pub fn parse_entries_with(input: &str, macros: &mut Macros) -> Result<Vec<Entry>, crate::bibtex::error::ParseError> {
+    parse_with(input, macros, ParseOptions::default())
+}
+
+/**
+Parse a whole .bib file as BibTeX reads it: text outside entries, up to the
+next `@`, is ignored, so notes and commented-out text between entries do no
+harm. Entries keep their file order.
+*/
+#[cfg(feature = "std")]
+pub fn parse_bibliography(input: &str) -> Result<Bibliography, crate::bibtex::error::ParseError> {
+    parse_bibliography_with(input, ParseOptions::standard())
+}
+
+/**
+`parse_bibliography` as `options` say, keeping the preambles and, with
+`keep_comments`, the comments on the `Bibliography`.
+*/
+#[cfg(feature = "std")]
+pub fn parse_bibliography_with(input: &str, options: ParseOptions) -> Result<Bibliography, crate::bibtex::error::ParseError> {
+    let document = parse_document(input, &mut Macros::new(), options)?;
+    let mut bibliography = Bibliography::from_entries(document.entries.into_iter().map(|(entry, _)| entry).collect());
+    for preamble in document.preambles {
+        bibliography.add_preamble(&preamble);
+    }
+    for comment in document.comments {
+        bibliography.add_comment(&comment);
+    }
+    Ok(bibliography)
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ParseOptions {
+    pub comments: Comments,
+    /** Ignore text outside entries, as BibTeX does, instead of failing on it. */
+    pub skip_text: bool,
+    /** Skip malformed entries instead of failing on the first one. */
+    pub lenient: bool,
+    /** Keep the text of `@comment`s rather than discarding it. */
+    pub keep_comments: bool,
+}
+
+impl ParseOptions {
+    /** Reading files as BibTeX does, with `#` only as concatenation. */
+    pub fn standard() -> ParseOptions {
+        ParseOptions { comments: Comments::Standard, skip_text: true, ..ParseOptions::default() }
+    }
+}
+
+/**
+Parse every entry in `input`, in order, as `options` say, starting from
+the definitions in `macros` and adding those in `input`. Entries a lenient
+parse skips are not reported; `parse_with_diagnostics` reports them.
+*/
+pub fn parse_with(input: &str, macros: &mut Macros, options: ParseOptions) -> Result<Vec<Entry>, crate::bibtex::error::ParseError> {
+    parse_with_diagnostics(input, macros, options).map(|(entries, _)| entries)
+}
+
+/**
+The input after a malformed item at the start of `rest`: from the next `@`
+that is the first character on its line, or nothing.
+*/
+fn recover(rest: &str) -> &str {
+    let mut offset = rest.find('\n').unwrap_or(rest.len());
+    while offset < rest.len() {
+        let line = &rest[offset + 1..];
+        if line.trim_start_matches([' ', '\t']).starts_with('@') {
+            return line;
+        }
+        offset += 1 + line.find('\n').unwrap_or(line.len());
+    }
+    ""
+}
+
+/**
+`parse_with`, also returning the malformed entries a lenient parse skipped.
+Without `lenient` the first malformed entry is an error and no diagnostics
+are returned.
+*/
+pub fn parse_with_diagnostics(input: &str, macros: &mut Macros, options: ParseOptions)
+    -> Result<(Vec<Entry>, Vec<ParseDiagnostic>), crate::bibtex::error::ParseError> {
+    parse_with_spans(input, macros, options)
+        .map(|(entries, diagnostics)| (entries.into_iter().map(|(entry, _)| entry).collect(), diagnostics))
+}
+
+/** An entry and where it is in the input. */
+pub type Located = (Entry, Span);
+
+/**
+`parse_with_diagnostics`, with each entry paired with where it is in
+`input`.
+*/
+pub fn parse_with_spans(input: &str, macros: &mut Macros, options: ParseOptions)
+    -> Result<(Vec<Located>, Vec<ParseDiagnostic>), crate::bibtex::error::ParseError> {
+    parse_document(input, macros, options).map(|document| (document.entries, document.diagnostics))
+}
+
+/**
+Everything read from a .bib file: the entries with their spans, the
+contents of `@preamble`s (with macros expanded) and, if kept, `@comment`s,
+each in file order, and what a lenient parse skipped.
+*/
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Document {
+    pub entries: Vec<Located>,
+    pub preambles: Vec<String>,
+    pub comments: Vec<String>,
+    pub diagnostics: Vec<ParseDiagnostic>,
+}
+
+enum Item {
+    Entry(Entry),
+    Preamble(String),
+    Comment(String),
+    /** A macro definition or a discarded comment. */
+    Nothing,
+}
+
+/**
+Parse all of `input` as `options` say, starting from the definitions in
+`macros` and adding those in `input`.
+*/
+pub fn parse_document(input: &str, macros: &mut Macros, options: ParseOptions) -> Result<Document, crate::bibtex::error::ParseError> {
+    let mut document = Document::default();
+    let lines = LineIndex::new(input);
+    let mut rest = input;
+    loop {
+        rest = rest.trim_start();
+        if options.skip_text {
+            rest = rest.find('@').map(|i| &rest[i..]).unwrap_or("");
+        }
+        if rest.is_empty() {
+            return Ok(document);
+        }
+        let error = match parse_item(input, rest, macros, options) {
+            Ok((r, item)) => {
+                let start = input.len() - rest.len();
+                match item {
+                    Item::Entry(e) => document.entries.push((e, lines.span(start, input.len() - r.len()))),
+                    Item::Preamble(p) => document.preambles.push(p),
+                    Item::Comment(c) => document.comments.push(c),
+                    Item::Nothing => {}
+                }
+                rest = r;
+                continue;
+            }
+            Err(error) if options.lenient => error,
+            Err(error) => return Err(error),
+        };
+        let snippet: String = rest.lines().next().unwrap_or("").trim_end().chars().take(80).collect();
+        document.diagnostics.push(ParseDiagnostic { error, snippet });
+        rest = recover(rest);
+    }
+}
+
+/**
+Parse the item at the start of `rest`: an entry, an `@string`, a
+`@comment` or a `@preamble`.
+*/
+fn parse_item<'a>(input: &str, rest: &'a str, macros: &mut Macros, options: ParseOptions)
+    -> Result<(&'a str, Item), crate::bibtex::error::ParseError> {
+    match special(input, rest)? {
+        Some((false, comment, r)) if options.keep_comments => return Ok((r, Item::Comment(String::from(comment)))),
+        Some((false, _, r)) => return Ok((r, Item::Nothing)),
+        Some((true, content, r)) => {
+            let at = input.len() - r.len() - 1 - content.len();
+            // preambles hold TeX, which `#` comments would not let through
+            return match delimited(sp, concatenation::<VerboseError<&str>>(Comments::Standard, false), sp)(content) {
+                Ok(("", pieces)) => Ok((r, Item::Preamble(macros.expand(&pieces)))),
+                _ => Err(crate::bibtex::error::ParseError::at(input, at, "invalid @preamble")),
+            };
+        }
+        None => {}
+    }
+    match string_definition_with::<VerboseError<&str>>(options.comments)(rest) {
+        Ok((r, (name, pieces))) => {
+            let value = macros.expand(&pieces);
+            macros.define(name, &value);
+            return Ok((r, Item::Nothing));
+        }
+        Err(Err::Error(_)) => {}
+        Err(e) => return Err(convert_error(input, e)),
+    }
+    match bibentry_with::<VerboseError<&str>>(options.comments)(rest) {
+        Ok((r, (itemtype, key, fields))) => {
+            let mut entry = Entry::new(BibType::parse(itemtype), key);
+            entry.set_type_name(itemtype);
+            for (k, v) in fields.iter() {
+                // as in BibTeX and biber, the first of repeated fields counts
+                if entry.has(k) {
+                    continue;
+                }
+                match macros.undefined(v) {
+                    Some(name) => entry.set_macro(k, name),
+                    None => entry.set(k, &macros.expand(v)),
+                };
+            }
+            Ok((r, Item::Entry(entry)))
+        }
+        Err(e) => Err(entry_error(input, rest, e)),
+    }
+}
+
+/** `e`, from parsing the entry `rest` starts with, as a located error naming the entry. */
+fn entry_error(input: &str, rest: &str, e: Err<VerboseError<&str>>) -> crate::bibtex::error::ParseError {
+    let error = convert_error(input, e);
+    match entry_key(rest) {
+        Some(key) => error.in_entry(key),
+        None => error,
+    }
+}
+
+/** A part of a .bib file, as `parse_items` reads it. */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RawItem<'a> {
+    /** Text outside items, such as whitespace, `%` comments and notes. */
+    Text(&'a str),
+    /** An `@string`, `@comment` or `@preamble`, as written. */
+    Special(&'a str),
+    /** An entry's type and key as written, and its fields with their values unexpanded. */
+    Entry { itemtype: &'a str, key: &'a str, fields: Fields },
+}
+
+/**
+All of `input`, item by item, reading `#` as `comments` says; the items
+and texts together make up the whole input. Text outside items is skipped
+as BibTeX skips it, up to the next `@`.
+*/
+pub fn parse_items(input: &str, comments: Comments) -> Result<Vec<RawItem<'_>>, crate::bibtex::error::ParseError> {
+    let mut items = Vec::new();
+    let mut rest = input;
+    while !rest.is_empty() {
+        let at = rest.find('@').unwrap_or(rest.len());
+        if at > 0 {
+            items.push(RawItem::Text(&rest[..at]));
+            rest = &rest[at..];
+            continue;
+        }
+        let r = match special(input, rest)? {
+            Some((_, _, r)) => r,
+            None => match string_definition_with::<VerboseError<&str>>(comments)(rest) {
+                Ok((r, _)) => r,
+                Err(Err::Error(_)) => match bibentry_with::<VerboseError<&str>>(comments)(rest) {
+                    Ok((r, (itemtype, key, fields))) => {
+                        items.push(RawItem::Entry { itemtype, key, fields });
+                        rest = r;
+                        continue;
+                    }
+                    Err(e) => return Err(entry_error(input, rest, e)),
+                },
+                Err(e) => return Err(convert_error(input, e)),
+            },
+        };
+        items.push(RawItem::Special(&rest[..rest.len() - r.len()]));
+        rest = r;
+    }
+    Ok(items)
+}
+
+/**
+The key of the entry `rest` starts with, as far as it can be made out
+without parsing the entry: the text between the opening delimiter and the
+first comma.
+*/
+fn entry_key(rest: &str) -> Option<&str> {
+    let after = rest.strip_prefix('@')?;
+    let open = after.find(['{', '('])?;
+    if !after[..open].trim().chars().all(|c| c.is_ascii_alphabetic()) {
+        return None;
+    }
+    let body = &after[open + 1..];
+    let key = body[..body.find(',')?].trim();
+    (!key.is_empty() && !key.contains(char::is_whitespace) && !key.contains(['{', '}', '='])).then_some(key)
+}
+
 #[cfg(test)]
 mod tests {
   
@@ -204,7 +838,7 @@ Test more also this line # ends with a comment too
 #starts with a comment
 #gogogo
 Ok, no comment."#;
-        let r1 = parse_str_with_comments::<(&str, ErrorKind)>(r1t);
+        let r1 = parse_str_with_comments::<(&str, ErrorKind)>(Comments::Hash, false)(r1t);
         println!("{:?}", r1);
         assert_eq!(r1, Ok(("", String::from("This is validTest more also this line Ok, no comment."))));
         /*let r2 = comment_discarded::<(&str, ErrorKind)>("This is valid # This is a comment");
@@ -214,33 +848,34 @@ Ok, no comment."#;
     #[test]
     fn test_kv_one() {
         
-        let r1 = key_value::<(&str, ErrorKind)>(" Author = {Some Author}");
-        assert_eq!(r1, Ok(("", ("Author", String::from("Some Author")))));
+        let text = |s: &str| vec![Piece::Text(String::from(s))];
+        let r1 = key_value::<(&str, ErrorKind)>(Comments::Hash)(" Author = {Some Author}");
+        assert_eq!(r1, Ok(("", ("Author", text("Some Author")))));
         //println!("{:?}", r1);
 
-        let r2 = key_value::<(&str, ErrorKind)>("   Author = \"Sömé Àüthör\",");
-        assert_eq!(r2, Ok((",", ("Author", String::from("Sömé Àüthör")))));
+        let r2 = key_value::<(&str, ErrorKind)>(Comments::Hash)("   Author = \"Sömé Àüthör\",");
+        assert_eq!(r2, Ok((",", ("Author", text("Sömé Àüthör")))));
         //println!("{:?}", r2);
     
-        let r3 = key_value::<(&str, ErrorKind)>("   Author = {Sömé Àüthör\",");
+        let r3 = key_value::<(&str, ErrorKind)>(Comments::Hash)("   Author = {Sömé Àüthör\",");
         //println!("{:?}", r3);
         assert_eq!(r3, Err(Failure(("\",", ErrorKind::Char))));
 
-        let r4 = key_value::<(&str, ErrorKind)>("{Author Sömé Àüthör");
+        let r4 = key_value::<(&str, ErrorKind)>(Comments::Hash)("{Author Sömé Àüthör");
         assert!(r4.is_err());
 
-        let r5 = key_value::<(&str, ErrorKind)>("title = {Primes of the form $x^2 + ny^2$: Fermat, Class Field Theory, and Complex Multiplication},");
+        let r5 = key_value::<(&str, ErrorKind)>(Comments::Hash)("title = {Primes of the form $x^2 + ny^2$: Fermat, Class Field Theory, and Complex Multiplication},");
         //println!("{:?}", r5);
-        assert!(r5.is_err() == false);
+        assert!(r5.is_ok());
 
-        let r6 = key_value::<(&str, ErrorKind)>("title = {Primes of the form $x^2 + ny^2$: Fermat, Class Field Theory, and Complex Multiplication}, # some comment");
+        let r6 = key_value::<(&str, ErrorKind)>(Comments::Hash)("title = {Primes of the form $x^2 + ny^2$: Fermat, Class Field Theory, and Complex Multiplication}, # some comment");
         println!("{:?}", r6);
         
-        let r7 = key_value::<(&str, ErrorKind)>("title = {Primes of # some comment\nthe form $x^2 + ny^2$: Fermat, Class Field Theory, and Complex Multiplication}");
+        let r7 = key_value::<(&str, ErrorKind)>(Comments::Hash)("title = {Primes of # some comment\nthe form $x^2 + ny^2$: Fermat, Class Field Theory, and Complex Multiplication}");
         //println!("{:?}", r7);
-        assert!(r7.is_err() == false);
+        assert!(r7.is_ok());
 
-        let r8 = key_value::<(&str, ErrorKind)>("ti # tle = {Primes of # some comment");
+        let r8 = key_value::<(&str, ErrorKind)>(Comments::Hash)("ti # tle = {Primes of # some comment");
         println!("{:?}", r8);
 
         let r9t = r#"
@@ -249,9 +884,9 @@ Ok, no comment."#;
                 Sömé Àüthör};
         "#;
 
-        let r9 = key_value::<(&str, ErrorKind)>(r9t);
+        let r9 = key_value::<(&str, ErrorKind)>(Comments::Hash)(r9t);
         //println!("{:?}", r9);
-        assert!(r9.is_err() == false);
+        assert!(r9.is_ok());
     }
 
     #[test]
@@ -263,9 +898,9 @@ Ok, no comment."#;
            
         "#;
 
-        let r1 = kvlist::<(&str, ErrorKind)>(b1);
+        let r1 = kvlist::<(&str, ErrorKind)>(Comments::Hash)(b1);
         //println!("{:?}", r1);
-        assert!(r1.is_err() == false);
+        assert!(r1.is_ok());
     }
 
     #[test]
@@ -320,19 +955,258 @@ Ok, no comment."#;
 
         let r1 = bibentry::<(&str, ErrorKind)>(b1);
         println!("{:?}", r1);
-        assert!(r1.is_err() == false);
+        assert!(r1.is_ok());
 
         let r2 = bibentry::<(&str, ErrorKind)>(b2);
         println!("{:?}", r2);
-        assert!(r2.is_err() == false);
+        assert!(r2.is_ok());
 
         let r2a = bibentry::<(&str, ErrorKind)>(b2a);
         println!("{:?}", r2a);
-        assert!(r2a.is_err() == false);
+        assert!(r2a.is_ok());
 
         let r3 = bibentry::<(&str, ErrorKind)>(b3);
         println!("{:?}", r3);
-        assert!(r3.is_err() == false);
+        assert!(r3.is_ok());
+
+    }
+
+    #[test]
+    fn test_entry_forms() {
+        let parse = |text: &str| parse_with(text, &mut Macros::new(), ParseOptions::standard());
+        let entries = parse("@article(paren, title = {In (round) brackets}, year = 2001)\n\
+            @misc{trailing, title = {T},\n}\n@misc{empty}\n@misc( bare )\n@misc{twice, year = 1984, YEAR = 1985}").unwrap();
+        let keys: Vec<&str> = entries.iter().map(|e| e.key()).collect();
+        assert_eq!(keys, vec!["paren", "trailing", "empty", "bare", "twice"]);
+        assert_eq!(entries[0].get("title"), Some("In (round) brackets"));
+        assert_eq!(entries[1].get("title"), Some("T"));
+        assert!(entries[2].is_empty() && entries[3].is_empty());
+        assert_eq!(entries[4].get("year"), Some("1984"));
+        assert_eq!(entries[4].len(), 1);
+
+        assert!(parse("@misc(a, title = {T}}").is_err());
+        assert!(parse("@misc{a, title = {T})").is_err());
+        assert!(parse("@misc{a, , title = {T}}").is_err());
+    }
+
+    #[test]
+    fn test_parse_entries() {
+        let b1 = r#"
+@book{Cox-CFT,
+    author = {David A. Cox},
+    year = {2013}
+}
+
+@Article{Knuth-LP,
+    Author = {Donald E. Knuth},
+    title = {Literate Programming}
+}
+"#;
+        let mut entries = parse_entries(b1).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].field_names(), vec!["author", "year"]);
+        entries[1].set("year", "1984");
+        entries[1].reorder(&["year", "Title"]);
+        assert_eq!(entries[1].field_names(), vec!["year", "title", "author"]);
+        entries[1].sort_fields();
+        assert_eq!(entries[1].field_names(), vec!["author", "title", "year"]);
+        assert_eq!(entries[0].key(), "Cox-CFT");
+        assert_eq!(entries[1].entry_type(), &BibType::Article);
+        assert_eq!(entries[1].get("author"), Some("Donald E. Knuth"));
+
+        let b2 = "@book{a,\n  title = {A}\n}\n@book{b,\n  title = {B\n}";
+        let e = parse_entries(b2).unwrap_err();
+        assert_eq!((e.line, e.column), (6, 2));
+        assert!(parse_entries("  \n ").unwrap().is_empty());
+        assert_eq!(parse_entries("@STRING{jan = \"January\"}\n@misc{a,\n title = {x}}").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_owned_entries() {
+        let entries = {
+            let input = String::from("@ARTICLE{Knuth-LP, TITLE = {Literate Programming}}\n@TechReport{r, year = {1984}}\n@Dataset{d, doi = {10.5281/x}}");
+            parse_entries(&input).unwrap()
+        };
+        let types: Vec<&BibType> = entries.iter().map(|e| e.entry_type()).collect();
+        assert_eq!(types, vec![&BibType::Article, &BibType::Report, &BibType::Other(String::from("dataset"))]);
+        assert_eq!(entries[0].key(), "Knuth-LP");
+        assert_eq!(entries[0].get("title"), Some("Literate Programming"));
+        assert_eq!(entries[0].get("Title"), Some("Literate Programming"));
+        assert_eq!(entries[1].get("year"), Some("1984"));
+        assert_eq!(entries[2].get("title"), None);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_parse_bibliography() {
+        let b1 = r#"
+Exported from the group library; see README.
+
+@preamble{ "\newcommand{\noop}[1]{}" }
+@comment{old entry: @misc{x, title = {X}}}
+@book{cox:2013,
+    title = {Primes}
+}
+junk between entries
+@Comment this line is ignored too
+@misc(knuth84.lp, ...)
+"#;
+        assert!(parse_entries(b1).is_err());
+        let e = parse_bibliography(b1).unwrap_err();
+        assert_eq!(e.line, 11);
+        let bib = parse_bibliography(&b1.replace("@misc(knuth84.lp, ...)", "@misc{knuth84.lp,\n  year = {1984}\n}")).unwrap();
+        let keys: Vec<&str> = bib.entries().iter().map(|e| e.key()).collect();
+        assert_eq!(keys, vec!["cox:2013", "knuth84.lp"]);
+        assert_eq!(bib.preambles(), ["\\newcommand{\\noop}[1]{}"]);
+        assert_eq!(bib.get("knuth84.lp").and_then(|e| e.get("year")), Some("1984"));
+        assert!(parse_bibliography("@comment{open").is_err());
+        assert!(parse_bibliography("no entries at all").unwrap().is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "writer")]
+    fn test_nested_braces() {
+        use crate::bibtex::writer::{write_entries, WriteOptions};
+
+        let text = |s: &str| vec![Piece::Text(String::from(s))];
+        let r1 = key_value::<(&str, ErrorKind)>(Comments::Hash)("title = {The {LaTeX} Companion {with {deep} nesting}},");
+        assert_eq!(r1, Ok((",", ("title", text("The {LaTeX} Companion {with {deep} nesting}")))));
+        let r2 = key_value::<(&str, ErrorKind)>(Comments::Hash)("title = \"{B}ib{T}e{X}\"");
+        assert_eq!(r2, Ok(("", ("title", text("{B}ib{T}e{X}")))));
+        assert!(key_value::<(&str, ErrorKind)>(Comments::Hash)("title = {The {LaTeX Companion}").is_err());
+
+        let b1 = "@book{latex,\n  title = {The {LaTeX} Companion {with {deep} nesting}},\n  note = {{}}\n}\n";
+        let entries = parse_entries(b1).unwrap();
+        assert_eq!(entries[0].get("note"), Some("{}"));
+        let written = write_entries(&entries, &WriteOptions::default());
+        assert_eq!(written, b1);
+        assert_eq!(parse_entries(&written).unwrap(), entries);
+
+        let deep = format!("@misc{{deep, note = {{{}{}}}}}", "{".repeat(100_000), "}".repeat(100_000));
+        assert_eq!(parse_entries(&deep).unwrap()[0].get("note").map(str::len), Some(200_000));
+        let unclosed = format!("@misc{{deep, note = {{{}}}", "{".repeat(100_000));
+        assert!(parse_entries(&unclosed).is_err());
+    }
+
+    #[test]
+    fn test_macros() {
+        let b1 = r#"
+@string{jacm = {Journal of the ACM}}
+@STRING{jacmf = JACM # ", Full"}
+@article{Knuth-LP,
+    journal = jacmf # { Letters} # jacm, # not a macro
+    year = 1984,
+    month = jan
+}
+"#;
+        let mut macros = Macros::new();
+        let entries = parse_entries_with(b1, &mut macros).unwrap();
+        assert_eq!(entries[0].get("journal"), Some("Journal of the ACM, Full LettersJournal of the ACM"));
+        assert_eq!(entries[0].get("year"), Some("1984"));
+        assert_eq!(entries[0].get("month"), Some("jan"));
+        assert_eq!(macros.get("JACM"), Some("Journal of the ACM"));
+        macros.define("JACMF", "redefined");
+        assert_eq!(macros.iter().collect::<Vec<_>>(), vec![("jacm", "Journal of the ACM"), ("jacmf", "redefined")]);
+
+        macros.define("jan", "January");
+        let entries = parse_entries_with("@misc{a,\n  month = jan # { 1}\n}", &mut macros).unwrap();
+        assert_eq!(entries[0].get("month"), Some("January 1"));
+        let r = key_value::<(&str, ErrorKind)>(Comments::Hash)("title = {A} # see below\n}");
+        assert_eq!(r, Ok((" # see below\n}", ("title", vec![Piece::Text(String::from("A"))]))));
+    }
+
+    #[test]
+    fn test_bare_values() {
+        assert_eq!(bare::<(&str, ErrorKind)>("2013,"), Ok((",", Piece::Text(String::from("2013")))));
+        assert_eq!(bare::<(&str, ErrorKind)>("jan}"), Ok(("}", Piece::Macro(String::from("jan")))));
+        let b1 = "@string{jan = \"January\"}\n@book{cox, year = 2013, month = jan}\n@book{other, month = feb}";
+        let entries = parse_entries(b1).unwrap();
+        assert_eq!(entries[0].get("year"), Some("2013"));
+        assert_eq!(entries[0].get("month"), Some("January"));
+        assert_eq!(entries[1].get("month"), Some("feb"));
+    }
+
+    #[test]
+    fn test_standard_comments() {
+        let b1 = r#"
+@string{jan = "January"}
+@misc{rfc,
+    title = "Part A" # " and " # "Part B",
+    month = jan # "~15",
+    url = {https://example.org/a#section-2},
+    note = "{"}Quoted{"} {C#} & 100% (sic)" # { "braced" }
+}
+"#;
+        let entries = parse_with(b1, &mut Macros::new(), ParseOptions::standard()).unwrap();
+        assert_eq!(entries[0].get("title"), Some("Part A and Part B"));
+        assert_eq!(entries[0].get("month"), Some("January~15"));
+        assert_eq!(entries[0].get("url"), Some("https://example.org/a#section-2"));
+        assert_eq!(entries[0].get("note"), Some("{\"}Quoted{\"} {C#} & 100% (sic) \"braced\" "));
+
+        // with `#` comments, a `#` in an ordinary field comments out the closing braces
+        assert!(parse_entries("@misc{a,\n  note = {See C#}\n}").is_err());
+        // but not in a verbatim one
+        let url = "@misc{a,\n  URL = {https://example.org/a#section-2?q=100%}, # a comment\n  file = {My Papers/a~b.pdf}\n}";
+        let entries = parse_entries(url).unwrap();
+        assert_eq!(entries[0].get("url"), Some("https://example.org/a#section-2?q=100%"));
+        assert_eq!(entries[0].get("file"), Some("My Papers/a~b.pdf"));
+        assert_eq!(parse_with(url, &mut Macros::new(), ParseOptions::standard()).unwrap_err().line, 2);
+        let e = parse_with("@misc{a,\n  title = {A} # see below\n}", &mut Macros::new(), ParseOptions::standard()).unwrap_err();
+        assert_eq!(e.line, 2);
+    }
+
+    #[test]
+    fn test_lenient() {
+        let b1 = "@misc{a, title = {A}}\n@article{broken,\n  title = {B,\n  note = {x@y}\n@misc{c, title = {C}}\n  @book{d title = {D}}\n@misc{e, title = {E}}\n";
+        assert!(parse_entries(b1).is_err());
+        let options = ParseOptions { lenient: true, ..ParseOptions::default() };
+        let (entries, diagnostics) = parse_with_diagnostics(b1, &mut Macros::new(), options).unwrap();
+        assert_eq!(entries.iter().map(|e| e.key()).collect::<Vec<_>>(), vec!["a", "c", "e"]);
+        assert_eq!(diagnostics.iter().map(|d| (d.error.line, d.snippet.as_str())).collect::<Vec<_>>(),
+            vec![(4, "@article{broken,"), (6, "@book{d title = {D}}")]);
+        assert_eq!(parse_with(b1, &mut Macros::new(), options).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_spans() {
+        let b1 = "% refs\n@misc{a, title = {A}}\n\n  @book{Cox-CFT,\n  title = {B}\n}\n";
+        let options = ParseOptions { skip_text: true, ..ParseOptions::default() };
+        let (entries, _) = parse_with_spans(b1, &mut Macros::new(), options).unwrap();
+        let spans: Vec<(&str, usize, usize)> = entries.iter().map(|(e, s)| (e.key(), s.line, s.column)).collect();
+        assert_eq!(spans, vec![("a", 2, 1), ("Cox-CFT", 4, 3)]);
+        assert_eq!(&b1[entries[1].1.start..entries[1].1.end], "@book{Cox-CFT,\n  title = {B}\n}");
+
+        let e = parse_entries("@misc{a, title = {A}}\n@book{Cox-CFT,\n  title = {B\n}\n").unwrap_err();
+        assert_eq!(e.entry.as_deref(), Some("Cox-CFT"));
+        assert!(e.to_string().starts_with("entry `Cox-CFT`, line "));
+        assert_eq!(parse_entries("@misc{").unwrap_err().entry, None);
+
+        // CRLF line endings, multi-line values and non-ASCII text before an entry
+        let crlf = "% Müller\r\n@misc{a,\r\n  title = {Two\r\n    lines}\r\n}\r\n\r\n  \t@book{b, title = {Ü}}\r\n";
+        let (entries, _) = parse_with_spans(crlf, &mut Macros::new(), options).unwrap();
+        let spans: Vec<(&str, usize, usize)> = entries.iter().map(|(e, s)| (e.key(), s.line, s.column)).collect();
+        assert_eq!(spans, vec![("a", 2, 1), ("b", 7, 4)]);
+        assert_eq!(&crlf[entries[0].1.start..entries[0].1.end], "@misc{a,\r\n  title = {Two\r\n    lines}\r\n}");
+        assert_eq!(&crlf[entries[1].1.start..entries[1].1.end], "@book{b, title = {Ü}}");
+        let e = parse_entries("@misc{a,\r\n  title = {A},\r\n  year = {1}\r\n}\r\n@misc{b,\r\n  title = {Ü} x\r\n}\r\n").unwrap_err();
+        assert_eq!((e.entry.as_deref(), e.line, e.column), (Some("b"), 6, 15));
+    }
+
+    #[test]
+    #[cfg(feature = "writer")]
+    fn test_preamble() {
+        use crate::bibtex::writer::{write_bibliography, WriteOptions};
 
+        let b1 = "@string{pkg = {\\usepackage{url}}}\n@preamble{ \"\\newcommand{\\noopsort}[1]{}\" # \" \" # pkg }\n@comment{generated {by} hand}\n@comment ignore this\n@misc{a, title = {A}}\n";
+        let bib = parse_bibliography_with(b1, ParseOptions::standard()).unwrap();
+        assert_eq!(bib.preambles(), ["\\newcommand{\\noopsort}[1]{} \\usepackage{url}"]);
+        assert!(bib.comments().is_empty());
+        let options = ParseOptions { keep_comments: true, ..ParseOptions::standard() };
+        let bib = parse_bibliography_with(b1, options).unwrap();
+        assert_eq!(bib.comments(), ["generated {by} hand", "ignore this"]);
+        assert_eq!(bib.len(), 1);
+        let again = parse_bibliography_with(&write_bibliography(&bib, &WriteOptions::default()), options).unwrap();
+        assert_eq!(again, bib);
+        assert!(parse_entries("@preamble{x y}").is_err());
     }
 }
@@ -0,0 +1,268 @@
+/*!
+
+Patents.
+
+biblatex cites a patent as `@patent`: the inventors are its `author`, the
+owners (usually a company) its `holder`, and `number` names the document,
+with the issuing office in front:
+
+```text
+number = {US 7,654,321 B2}         granted US patent, kind code B2
+number = {US 2005/0123456 A1}      published US application
+number = {EP 1 234 567 A1}         European application
+number = {WO 2005/012345 A1}       international (PCT) application
+```
+
+`PatentNumber::parse` reads these in any of the usual spellings
+(`US7654321B2`, `U.S. Patent No. 7,654,321`, `WO2005012345A1`), and
+`PatentNumber::from_entry` also reads a bare number whose office is given
+by biblatex's `type` (`patentus`, `patreqeu`, ...) or `location`
+(`countryus`). Some exports put the holder in an `assignee` field, which
+biblatex does not know; `check_entry` reports it.
+
+*/
+
+use crate::bibtex::data::Entry;
+use crate::lint::{Diagnostic, Severity};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Office {
+    Us,
+    /** European Patent Office. */
+    Ep,
+    /** WIPO, for international applications under the PCT. */
+    Wo,
+}
+
+impl Office {
+    pub fn code(&self) -> &'static str {
+        match self {
+            Office::Us => "US",
+            Office::Ep => "EP",
+            Office::Wo => "WO",
+        }
+    }
+
+    fn parse(code: &str) -> Option<Office> {
+        match code.trim().to_lowercase().trim_start_matches("country").trim_start_matches("patreq").trim_start_matches("patent") {
+            "us" | "usa" | "united states" => Some(Office::Us),
+            "ep" | "eu" | "epo" => Some(Office::Ep),
+            "wo" | "pct" => Some(Office::Wo),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PatentNumber {
+    pub office: Office,
+    /**
+    The number without separators: `7654321`, `RE43000`, `20050123456` for
+    a US application, `2005012345` for a WO application.
+    */
+    pub number: String,
+    /** Kind code, such as `A1` or `B2`. */
+    pub kind: Option<String>,
+}
+
+/** Group the digits of `n` by threes from the right with `sep`. */
+fn group(n: &str, sep: char) -> String {
+    let mut out = String::new();
+    for (i, c) in n.chars().enumerate() {
+        if i > 0 && (n.len() - i).is_multiple_of(3) {
+            out.push(sep);
+        }
+        out.push(c);
+    }
+    out
+}
+
+impl PatentNumber {
+    /**
+    Parse a patent number written with its office: `US 7,654,321 B2`,
+    `US7654321`, `U.S. Patent No. 7,654,321`, `US 2005/0123456 A1`,
+    `EP 1 234 567 A1`, `WO 2005/012345 A1` or `WO2005012345A1`.
+    */
+    pub fn parse(number: &str) -> Option<PatentNumber> {
+        let compact: String = number.chars()
+            .filter(|c| !matches!(c, ' ' | ',' | '.' | '~' | '-' | '{' | '}'))
+            .collect::<String>()
+            .to_uppercase();
+        let office = Office::parse(compact.get(..2)?)?;
+        let rest = compact[2..].trim_start_matches("PATENT").trim_start_matches("NO");
+        // a kind code is a letter and at most one digit at the end
+        let digits = rest.trim_end_matches(|c: char| c.is_ascii_digit());
+        let (body, kind) = match digits.chars().last() {
+            Some(c) if c.is_ascii_alphabetic() && rest.len() - digits.len() <= 1 && digits.len() > 1 => {
+                (&rest[..digits.len() - 1], Some(String::from(&rest[digits.len() - 1..])))
+            }
+            _ => (rest, None),
+        };
+        let number = match (office, body.split_once('/')) {
+            // year and serial of a published application
+            (Office::Us, Some((year, serial))) if year.len() == 4 && serial.len() <= 7 =>
+                format!("{}{:0>7}", year, serial),
+            (Office::Wo, Some((year, serial))) if year.len() == 4 && serial.len() <= 6 =>
+                format!("{}{:0>6}", year, serial),
+            (_, Some(_)) => return None,
+            (Office::Us, None) => {
+                // reissues, designs, plants and statutory invention registrations
+                let prefix = ["RE", "PP", "D", "H"].into_iter().find(|p| body.starts_with(p)).unwrap_or("");
+                let n = &body[prefix.len()..];
+                let valid = !n.is_empty() && n.len() <= 11 && (prefix.is_empty() || n.len() <= 8);
+                (valid && n.chars().all(|c| c.is_ascii_digit())).then(|| String::from(body))?
+            }
+            (Office::Ep, None) if body.len() == 7 => String::from(body),
+            (Office::Wo, None) if body.len() == 10 => String::from(body),
+            _ => return None,
+        };
+        number.chars().skip_while(|c| c.is_ascii_alphabetic()).all(|c| c.is_ascii_digit())
+            .then_some(PatentNumber { office, number, kind })
+    }
+
+    /**
+    The patent `entry` describes, from its `number`, or from a bare number
+    whose office is given by `type` or `location`.
+    */
+    pub fn from_entry(entry: &Entry) -> Option<PatentNumber> {
+        let number = entry.get("number")?.trim();
+        if let Some(patent) = PatentNumber::parse(number) {
+            return Some(patent);
+        }
+        let office = ["type", "location"].into_iter()
+            .filter_map(|f| entry.get(f))
+            .find_map(|v| v.split(" and ").find_map(Office::parse))?;
+        PatentNumber::parse(&format!("{} {}", office.code(), number))
+    }
+
+    /**
+    Whether this is a published application rather than a granted patent.
+    International publications are always applications; a European number
+    without a kind code is taken to be a patent.
+    */
+    pub fn is_application(&self) -> bool {
+        match self.office {
+            Office::Us => self.number.len() == 11,
+            Office::Ep => self.kind.as_deref().map(|k| k.starts_with('A')).unwrap_or(false),
+            Office::Wo => true,
+        }
+    }
+
+    /**
+    The number as the office writes it: `US 7,654,321 B2`,
+    `US 2005/0123456 A1`, `EP 1 234 567 A1`, `WO 2005/012345 A1`.
+    */
+    pub fn canonical_number(&self) -> String {
+        let n = &self.number;
+        let number = match self.office {
+            Office::Us if self.is_application() => format!("{}/{}", &n[..4], &n[4..]),
+            Office::Us => {
+                let digits = n.trim_start_matches(|c: char| c.is_ascii_alphabetic());
+                format!("{}{}", &n[..n.len() - digits.len()], group(digits, ','))
+            }
+            Office::Ep => group(n, ' '),
+            Office::Wo => format!("{}/{}", &n[..4], &n[4..]),
+        };
+        match &self.kind {
+            Some(kind) => format!("{} {} {}", self.office.code(), number, kind),
+            None => format!("{} {}", self.office.code(), number),
+        }
+    }
+
+    /** The number without spaces, as patent databases use it: `US7654321B2`. */
+    pub fn compact(&self) -> String {
+        format!("{}{}{}", self.office.code(), self.number, self.kind.as_deref().unwrap_or(""))
+    }
+
+    /** The document on Google Patents. */
+    pub fn url(&self) -> String {
+        format!("https://patents.google.com/patent/{}", self.compact())
+    }
+
+    /** biblatex's `type` key: `patentus`, `patreqeu`, ... */
+    pub fn biblatex_type(&self) -> &'static str {
+        match (self.office, self.is_application()) {
+            (Office::Us, false) => "patentus",
+            (Office::Us, true) => "patrequs",
+            (Office::Ep, false) => "patenteu",
+            (Office::Ep, true) => "patreqeu",
+            // biblatex has no key for international applications
+            (Office::Wo, _) => "patreq",
+        }
+    }
+
+    /** `Patent US 7,654,321 B2` or `Patent application WO 2005/012345 A1`. */
+    pub fn describe(&self) -> String {
+        let kind = if self.is_application() { "Patent application" } else { "Patent" };
+        format!("{} {}", kind, self.canonical_number())
+    }
+}
+
+/**
+Patent checks: the holder belongs in `holder` rather than `assignee`, and
+a patent number is better written the way the office writes it.
+*/
+pub fn check_entry(entry: &Entry) -> Vec<Diagnostic> {
+    let mut out = Vec::new();
+    if entry.entry_type().name() != "patent" {
+        return out;
+    }
+    if entry.has("assignee") && !entry.has("holder") {
+        out.push(Diagnostic::new(entry.key(), "patent-holder", Severity::Warning,
+            "`assignee` is not a biblatex field; the owner of a patent goes in `holder`"));
+    }
+    let number = entry.get("number").map(str::trim).unwrap_or("");
+    if let Some(patent) = PatentNumber::parse(number) {
+        if number != patent.canonical_number() {
+            out.push(Diagnostic::new(entry.key(), "patent-number", Severity::Info,
+                &format!("number `{}` is usually written `{}`", number, patent.canonical_number())));
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::bibtex::data::BibType;
+
+    #[test]
+    fn test_parse() {
+        let canonical = |s: &str| PatentNumber::parse(s).map(|p| p.canonical_number());
+        assert_eq!(canonical("US7654321B2").as_deref(), Some("US 7,654,321 B2"));
+        assert_eq!(canonical("U.S. Patent No. 7,654,321").as_deref(), Some("US 7,654,321"));
+        assert_eq!(canonical("US RE43000 E").as_deref(), Some("US RE43,000 E"));
+        assert_eq!(canonical("US 2005/123456 A1").as_deref(), Some("US 2005/0123456 A1"));
+        assert_eq!(canonical("US20050123456A1").as_deref(), Some("US 2005/0123456 A1"));
+        assert_eq!(canonical("EP1234567A1").as_deref(), Some("EP 1 234 567 A1"));
+        assert_eq!(canonical("WO2005012345A1").as_deref(), Some("WO 2005/012345 A1"));
+        assert_eq!(canonical("WO 2005/12345").as_deref(), Some("WO 2005/012345"));
+        assert_eq!(canonical("EP 123"), None);
+        assert_eq!(canonical("DE 10 2004 001 234"), None);
+        assert_eq!(canonical("US patent pending"), None);
+
+        let us = PatentNumber::parse("US 7,654,321 B2").unwrap();
+        assert_eq!(us.url(), "https://patents.google.com/patent/US7654321B2");
+        assert_eq!((us.biblatex_type(), us.describe().as_str()), ("patentus", "Patent US 7,654,321 B2"));
+        let ep = PatentNumber::parse("EP 1 234 567 A1").unwrap();
+        assert_eq!((ep.biblatex_type(), ep.describe().as_str()), ("patreqeu", "Patent application EP 1 234 567 A1"));
+    }
+
+    #[test]
+    fn test_from_entry() {
+        let mut e = Entry::new(BibType::parse("patent"), "p");
+        e.set("author", "Doe, Jane and Roe, Richard");
+        e.set("assignee", "{Acme Corporation}");
+        e.set("number", "7654321");
+        e.set("location", "countryus");
+        assert_eq!(PatentNumber::from_entry(&e).map(|p| p.canonical_number()).as_deref(), Some("US 7,654,321"));
+        let rules = |e: &Entry| check_entry(e).into_iter().map(|d| d.rule).collect::<Vec<_>>();
+        assert_eq!(rules(&e), vec!["patent-holder"]);
+        e.rename("assignee", "holder");
+        e.set("number", "US7654321B2");
+        assert_eq!(rules(&e), vec!["patent-number"]);
+        assert_eq!(e.holders().len(), 1);
+        assert_eq!(e.authors().len(), 2);
+    }
+}
@@ -0,0 +1,168 @@
+/*!
+
+Field visibility policy shared by every writer and exporter.
+
+Some fields only make sense in a private library (`note`, `annote`, local
+`x-*` bookkeeping fields, file paths). Rather than each exporter growing its
+own exclusion option, a single `FieldPolicy` describes which fields are
+private; exporters consult it and drop those fields unless the caller
+explicitly exposes them again.
+
+*/
+
+use crate::bibtex::data::Entry;
+
+/**
+A field name pattern. `*` matches any run of characters, everything else
+matches literally. Matching is case-insensitive, as field names are in BibTeX.
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldPattern(String);
+
+impl FieldPattern {
+    pub fn new(pattern: &str) -> FieldPattern {
+        FieldPattern(pattern.trim().to_lowercase())
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    pub fn matches(&self, field: &str) -> bool {
+        glob_match(&self.0, &field.to_lowercase())
+    }
+}
+
+fn glob_match(pattern: &str, name: &str) -> bool {
+    let parts: Vec<&str> = pattern.split('*').collect();
+    if parts.len() == 1 {
+        return pattern == name;
+    }
+    let first = parts[0];
+    let last = parts[parts.len() - 1];
+    if !name.starts_with(first) || name.len() < first.len() + last.len() {
+        return false;
+    }
+    let mut rest = &name[first.len()..name.len() - last.len()];
+    if !name.ends_with(last) {
+        return false;
+    }
+    for middle in &parts[1..parts.len() - 1] {
+        match rest.find(middle) {
+            Some(pos) => rest = &rest[pos + middle.len()..],
+            None => return false,
+        }
+    }
+    true
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FieldPolicy {
+    private: Vec<FieldPattern>,
+    exposed: Vec<FieldPattern>,
+}
+
+impl FieldPolicy {
+    /**
+    A policy under which every field is public.
+    */
+    pub fn new() -> FieldPolicy {
+        FieldPolicy::default()
+    }
+
+    /**
+    Mark all fields matching `pattern` as private.
+    */
+    pub fn private(mut self, pattern: &str) -> FieldPolicy {
+        self.private.push(FieldPattern::new(pattern));
+        self
+    }
+
+    /**
+    Override the private patterns for a single export, exposing the
+    fields matching `pattern` again.
+    */
+    pub fn expose(mut self, pattern: &str) -> FieldPolicy {
+        self.exposed.push(FieldPattern::new(pattern));
+        self
+    }
+
+    pub fn private_patterns(&self) -> &[FieldPattern] {
+        &self.private
+    }
+
+    pub fn is_private(&self, field: &str) -> bool {
+        self.private.iter().any(|p| p.matches(field))
+            && !self.exposed.iter().any(|p| p.matches(field))
+    }
+
+    /**
+    Keep only the fields an exporter is allowed to write, preserving
+    their order.
+    */
+    pub fn filter<'a, V, I>(&self, fields: I) -> Vec<(&'a str, V)>
+    where
+        I: IntoIterator<Item = (&'a str, V)>,
+    {
+        fields.into_iter().filter(|(k, _)| !self.is_private(k)).collect()
+    }
+
+    /**
+    `entry` without its private fields.
+    */
+    pub fn apply(&self, entry: &Entry) -> Entry {
+        let mut public = entry.clone();
+        for name in entry.field_names().into_iter().filter(|n| self.is_private(n)) {
+            public.remove(name);
+        }
+        public
+    }
+
+    /**
+    `entries` without their private fields.
+    */
+    pub fn apply_all(&self, entries: &[Entry]) -> Vec<Entry> {
+        entries.iter().map(|e| self.apply(e)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::bibtex::data::BibType;
+
+    #[test]
+    fn test_patterns() {
+        assert!(FieldPattern::new("note").matches("NOTE"));
+        assert!(!FieldPattern::new("note").matches("notes"));
+        assert!(FieldPattern::new("x-*").matches("x-read-status"));
+        assert!(!FieldPattern::new("x-*").matches("xdata"));
+        assert!(FieldPattern::new("*url").matches("adsurl"));
+        assert!(FieldPattern::new("a*b*c").matches("axxbyyc"));
+        assert!(!FieldPattern::new("a*b*c").matches("axxc"));
+        assert!(!FieldPattern::new("ab*ba").matches("aba"));
+    }
+
+    #[test]
+    fn test_policy() {
+        let policy = FieldPolicy::new().private("note").private("x-*");
+        assert!(policy.is_private("Note"));
+        assert!(policy.is_private("x-rating"));
+        assert!(!policy.is_private("title"));
+
+        let fields = vec![("title", "T"), ("note", "secret"), ("x-rating", "5"), ("year", "2013")];
+        assert_eq!(policy.filter(fields.clone()), vec![("title", "T"), ("year", "2013")]);
+
+        let overridden = policy.clone().expose("note");
+        assert_eq!(overridden.filter(fields), vec![("title", "T"), ("note", "secret"), ("year", "2013")]);
+
+        let mut entry = Entry::new(BibType::InProceedings, "a");
+        entry.set_type_name("conference");
+        entry.set("title", "T");
+        entry.set("X-Rating", "5");
+        let public = policy.apply(&entry);
+        assert_eq!(public.field_names(), vec!["title"]);
+        assert_eq!((public.key(), public.type_name()), ("a", "conference"));
+    }
+}
@@ -0,0 +1,245 @@
+/*!
+
+Reading .bib files entry by entry, for files too large to hold in memory.
+
+`BibReader` pulls lines from any `BufRead` and yields the entries as they
+are completed, keeping only the item being read:
+
+```text
+let reader = BibReader::open("aggregated.bib")?;
+for entry in reader {
+    let entry = entry?;
+    ...
+}
+```
+
+The input is cut before each line starting with `@` outside items, the
+same places where a lenient parse resumes, and each piece is parsed as
+`parse_document` would parse it, with the `@string` definitions seen so
+far. The entries, errors and diagnostics are those of parsing the whole
+file at once, with lines and offsets counted from the start of the input.
+Braces are only counted inside an item, from its opening delimiter to the
+closing one, so a stray brace in a `%` comment or other text between
+entries does not hold the piece open. A line that starts with `@` inside
+an unbalanced `#` comment keeps the piece going; the entries still come
+out right, only later.
+
+Preambles and kept comments are collected on the reader as it goes. After
+an error, or at the end of the input, iteration stops.
+
+*/
+
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use crate::bibtex::bibliography::LoadError;
+use crate::bibtex::data::Entry;
+use crate::bibtex::error::{ParseDiagnostic, ParseError};
+use crate::bibtex::parser::{parse_document, Macros, ParseOptions};
+
+pub struct BibReader<R: BufRead> {
+    reader: R,
+    options: ParseOptions,
+    macros: Macros,
+    /** Lines read but not yet parsed. */
+    pending: String,
+    /** Where the end of `pending` is. */
+    scan: Scan,
+    /** 1-based line and byte offset of the start of `pending` in the input. */
+    line: usize,
+    offset: usize,
+    entries: VecDeque<Entry>,
+    preambles: Vec<String>,
+    comments: Vec<String>,
+    diagnostics: Vec<ParseDiagnostic>,
+    done: bool,
+}
+
+impl BibReader<BufReader<File>> {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<BibReader<BufReader<File>>, LoadError> {
+        let path = path.as_ref();
+        let file = File::open(path).map_err(|e| LoadError::Io(format!("cannot read {}: {}", path.display(), e)))?;
+        Ok(BibReader::from_reader(BufReader::new(file)))
+    }
+}
+
+impl<R: BufRead> BibReader<R> {
+    /** A reader parsing as `parse_bibliography` does. */
+    pub fn from_reader(reader: R) -> BibReader<R> {
+        BibReader::with_options(reader, ParseOptions::standard())
+    }
+
+    pub fn with_options(reader: R, options: ParseOptions) -> BibReader<R> {
+        BibReader {
+            reader,
+            options,
+            macros: Macros::new(),
+            pending: String::new(),
+            scan: Scan::Outside,
+            line: 1,
+            offset: 0,
+            entries: VecDeque::new(),
+            preambles: Vec::new(),
+            comments: Vec::new(),
+            diagnostics: Vec::new(),
+            done: false,
+        }
+    }
+
+    /** The `@preamble`s read so far. */
+    pub fn preambles(&self) -> &[String] {
+        &self.preambles
+    }
+
+    /** The `@comment`s read so far, if `ParseOptions::keep_comments`. */
+    pub fn comments(&self) -> &[String] {
+        &self.comments
+    }
+
+    /** What a lenient parse has skipped so far. */
+    pub fn diagnostics(&self) -> &[ParseDiagnostic] {
+        &self.diagnostics
+    }
+
+    /** The `@string` definitions read so far. */
+    pub fn macros(&self) -> &Macros {
+        &self.macros
+    }
+
+    /** `error`, found in `pending`, located in the whole input. */
+    fn locate(&self, mut error: ParseError) -> ParseError {
+        error.line += self.line - 1;
+        error.offset += self.offset;
+        error
+    }
+
+    /** Parse `pending` and start the next piece with `next`. */
+    fn parse_pending(&mut self, next: String) -> Result<(), LoadError> {
+        let piece = std::mem::replace(&mut self.pending, next);
+        let document = parse_document(&piece, &mut self.macros, self.options).map_err(|e| self.locate(e))?;
+        self.entries.extend(document.entries.into_iter().map(|(entry, _)| entry));
+        self.preambles.extend(document.preambles);
+        self.comments.extend(document.comments);
+        for mut diagnostic in document.diagnostics {
+            diagnostic.error = self.locate(diagnostic.error);
+            self.diagnostics.push(diagnostic);
+        }
+        self.line += piece.matches('\n').count();
+        self.offset += piece.len();
+        Ok(())
+    }
+
+    /** Read lines until a piece is complete, or the input ends, and parse it. */
+    fn fill(&mut self) -> Result<(), LoadError> {
+        loop {
+            let mut line = String::new();
+            let read = self.reader.read_line(&mut line).map_err(|e| LoadError::Io(format!("cannot read input: {}", e)))?;
+            if read == 0 {
+                self.done = true;
+                return self.parse_pending(String::new());
+            }
+            let starts_item = line.trim_start_matches([' ', '\t']).starts_with('@');
+            if starts_item && !matches!(self.scan, Scan::Item { .. }) && !self.pending.is_empty() {
+                self.scan = Scan::Outside.after(&line);
+                return self.parse_pending(line);
+            }
+            self.scan = self.scan.after(&line);
+            self.pending.push_str(&line);
+        }
+    }
+}
+
+/** Where a piece of input is, for cutting it into items. */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Scan {
+    /** Between items, where braces do not count. */
+    Outside,
+    /** After an `@`, in the item's type. */
+    Type,
+    /** In an item at this brace depth, closed by `)` if `paren`. */
+    Item { depth: usize, paren: bool },
+}
+
+impl Scan {
+    /** Where the input is after `text`. */
+    fn after(self, text: &str) -> Scan {
+        text.chars().fold(self, |scan, c| match (scan, c) {
+            (Scan::Outside, '@') => Scan::Type,
+            (Scan::Outside, _) => Scan::Outside,
+            (Scan::Type, '{') => Scan::Item { depth: 1, paren: false },
+            (Scan::Type, '(') => Scan::Item { depth: 0, paren: true },
+            (Scan::Type, c) if c.is_alphanumeric() || c.is_whitespace() || "@-_".contains(c) => Scan::Type,
+            // an `@` in text, such as an email address, starts no item
+            (Scan::Type, _) => Scan::Outside,
+            (Scan::Item { depth, paren }, '{') => Scan::Item { depth: depth + 1, paren },
+            (Scan::Item { depth: 1, paren: false }, '}') | (Scan::Item { depth: 0, paren: true }, ')') => Scan::Outside,
+            (Scan::Item { depth, paren }, '}') => Scan::Item { depth: depth.saturating_sub(1), paren },
+            (scan, _) => scan,
+        })
+    }
+}
+
+impl<R: BufRead> Iterator for BibReader<R> {
+    type Item = Result<Entry, LoadError>;
+
+    fn next(&mut self) -> Option<Result<Entry, LoadError>> {
+        loop {
+            if let Some(entry) = self.entries.pop_front() {
+                return Some(Ok(entry));
+            }
+            if self.done {
+                return None;
+            }
+            if let Err(e) = self.fill() {
+                self.done = true;
+                return Some(Err(e));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::bibtex::parser::{parse_bibliography, parse_bibliography_with, parse_with_diagnostics};
+
+    #[test]
+    fn test_reader() {
+        let text = "Notes before.\n@string{acm = {ACM}}\n@preamble{\"\\noop\"}\n@article{a,\n  title = {Two\n@lines},\n  publisher = acm}\n\
+            @misc{b, note = {x}} @misc{c, year = 2001}\n\n@book{d,\n  title = {D}\n}\n";
+        let whole = parse_bibliography_with(text, ParseOptions::standard()).unwrap();
+        let mut reader = BibReader::with_options(text.as_bytes(), ParseOptions::standard());
+        let entries: Vec<Entry> = reader.by_ref().collect::<Result<Vec<Entry>, LoadError>>().unwrap();
+        assert_eq!(entries, whole.entries());
+        assert!(entries.iter().zip(whole.entries()).all(|(a, b)| a.is_identical(b)));
+        assert_eq!(entries[0].get("publisher"), Some("ACM"));
+        assert_eq!(reader.preambles(), whole.preambles());
+
+        let broken = "@misc{a, title = {A}}\n\n@misc{b, title = {B}\n@misc{c, title = {C}}\n";
+        let results: Vec<Result<Entry, LoadError>> = BibReader::from_reader(broken.as_bytes()).collect();
+        assert_eq!(results.len(), 2);
+        let Err(LoadError::Parse(error)) = &results[1] else { panic!("expected a parse error") };
+        assert_eq!(error.line, parse_bibliography(broken).unwrap_err().line);
+
+        let options = ParseOptions { lenient: true, ..ParseOptions::default() };
+        let mut lenient = BibReader::with_options(broken.as_bytes(), options);
+        let keys: Vec<String> = lenient.by_ref().map(|e| String::from(e.unwrap().key())).collect();
+        assert_eq!(keys, vec!["a", "c"]);
+        assert_eq!(lenient.diagnostics(), parse_with_diagnostics(broken, &mut Macros::new(), options).unwrap().1);
+    }
+
+    #[test]
+    fn test_junk_braces() {
+        // the entries before the error come out, so the stray braces did not join the pieces
+        let junk = "% an unbalanced { brace\n@misc{a, title = {A}}\nsee {also\n@misc{b, title = {B}} } see {\n@misc{c, title = }\n";
+        let results: Vec<Result<Entry, LoadError>> = BibReader::from_reader(junk.as_bytes()).collect();
+        let keys: Vec<&str> = results.iter().filter_map(|r| r.as_ref().ok()).map(|e| e.key()).collect();
+        assert_eq!(keys, vec!["a", "b"]);
+        assert!(results[2].is_err());
+        assert_eq!(Scan::Outside.after("mail me@example.org {\n"), Scan::Outside);
+        assert_eq!(Scan::Outside.after("@article(a, title = {)})\n"), Scan::Outside);
+        assert_eq!(Scan::Outside.after("@misc{a, title = {A\n"), Scan::Item { depth: 2, paren: false });
+    }
+}
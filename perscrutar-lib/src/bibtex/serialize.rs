@@ -0,0 +1,310 @@
+/*!
+
+Bibliographies as JSON, for tools that would rather not parse BibTeX.
+
+Every type converts to a `JsonValue` with `to_json` and back with
+`from_json`, without losing anything the parser keeps:
+
+```json
+{
+  "preambles": ["\\newcommand{\\noop}[1]{}"],
+  "comments": [],
+  "entries": [
+    {"type": "article", "key": "knuth84",
+     "fields": {"author": "Knuth, Donald E.", "title": "Literate Programming"}}
+  ]
+}
+```
+
+Fields are kept in the entry's order and values as they are, TeX and all.
+A `BibType` is its name, and a `Name` an object of its four parts. The
+journal and pinned keys of a `Bibliography` belong to one session and are
+not written.
+
+With the `serde` feature the same types implement `Serialize` and
+`Deserialize`, in the same shape, for use with `serde_json` and the like.
+
+*/
+
+use std::fmt;
+use crate::bibtex::bibliography::Bibliography;
+use crate::bibtex::data::{BibType, Entry};
+use crate::bibtex::names::Name;
+use crate::json::JsonValue;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SerializeError(pub String);
+
+impl fmt::Display for SerializeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for SerializeError {}
+
+fn string<'a>(json: &'a JsonValue, member: &str) -> Result<&'a str, SerializeError> {
+    json.get(member).and_then(JsonValue::as_str).ok_or_else(|| SerializeError(format!("missing string `{}`", member)))
+}
+
+/** The strings of array `member`, none if it is missing. */
+fn strings(json: &JsonValue, member: &str) -> Result<Vec<String>, SerializeError> {
+    json.get(member).and_then(JsonValue::as_array).unwrap_or_default().iter()
+        .map(|s| s.as_str().map(String::from).ok_or_else(|| SerializeError(format!("`{}` must hold strings", member))))
+        .collect()
+}
+
+impl BibType {
+    pub fn to_json(&self) -> JsonValue {
+        JsonValue::str(self.name())
+    }
+
+    pub fn from_json(json: &JsonValue) -> Result<BibType, SerializeError> {
+        json.as_str().map(BibType::parse).ok_or_else(|| SerializeError(String::from("an entry type must be a string")))
+    }
+}
+
+impl Name {
+    pub fn to_json(&self) -> JsonValue {
+        JsonValue::object(vec![
+            ("first", JsonValue::str(&self.first)),
+            ("von", JsonValue::str(&self.von)),
+            ("last", JsonValue::str(&self.last)),
+            ("jr", JsonValue::str(&self.jr)),
+        ])
+    }
+
+    /** Missing parts are empty. */
+    pub fn from_json(json: &JsonValue) -> Result<Name, SerializeError> {
+        if !matches!(json, JsonValue::Object(_)) {
+            return Err(SerializeError(String::from("a name must be an object")));
+        }
+        let part = |name: &str| String::from(json.get(name).and_then(JsonValue::as_str).unwrap_or_default());
+        Ok(Name { first: part("first"), von: part("von"), last: part("last"), jr: part("jr") })
+    }
+}
+
+impl Entry {
+    pub fn to_json(&self) -> JsonValue {
+        JsonValue::object(vec![
+            ("type", self.entry_type().to_json()),
+            ("key", JsonValue::str(self.key())),
+            ("fields", JsonValue::object(self.fields().map(|(name, value)| (name, JsonValue::str(value))).collect())),
+        ])
+    }
+
+    pub fn from_json(json: &JsonValue) -> Result<Entry, SerializeError> {
+        let key = string(json, "key")?;
+        let itemtype = BibType::from_json(json.get("type").unwrap_or(&JsonValue::Null))
+            .map_err(|e| SerializeError(format!("{}: {}", key, e)))?;
+        let mut entry = Entry::new(itemtype, key);
+        match json.get("fields") {
+            None => {}
+            Some(JsonValue::Object(fields)) => for (name, value) in fields {
+                let value = value.as_str().ok_or_else(|| SerializeError(format!("{}: field {} must be a string", key, name)))?;
+                entry.set(name, value);
+            },
+            Some(_) => return Err(SerializeError(format!("{}: `fields` must be an object", key))),
+        }
+        Ok(entry)
+    }
+}
+
+impl Bibliography {
+    pub fn to_json(&self) -> JsonValue {
+        let strings = |list: &[String]| JsonValue::Array(list.iter().map(|s| JsonValue::str(s)).collect());
+        JsonValue::object(vec![
+            ("preambles", strings(self.preambles())),
+            ("comments", strings(self.comments())),
+            ("entries", JsonValue::Array(self.entries().iter().map(Entry::to_json).collect())),
+        ])
+    }
+
+    pub fn from_json(json: &JsonValue) -> Result<Bibliography, SerializeError> {
+        let entries = json.get("entries").and_then(JsonValue::as_array)
+            .ok_or_else(|| SerializeError(String::from("missing array `entries`")))?;
+        let mut bib = Bibliography::from_entries(entries.iter().map(Entry::from_json).collect::<Result<Vec<Entry>, SerializeError>>()?);
+        for preamble in strings(json, "preambles")? {
+            bib.add_preamble(&preamble);
+        }
+        for comment in strings(json, "comments")? {
+            bib.add_comment(&comment);
+        }
+        Ok(bib)
+    }
+}
+
+#[cfg(feature = "serde")]
+mod with_serde {
+
+    use std::fmt;
+    use serde::de::{Deserializer, MapAccess, Visitor};
+    use serde::ser::{SerializeMap, SerializeStruct, Serializer};
+    use serde::{Deserialize, Serialize};
+    use crate::bibtex::bibliography::Bibliography;
+    use crate::bibtex::data::{BibType, Entry};
+
+    impl Serialize for BibType {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.serialize_str(self.name())
+        }
+    }
+
+    impl<'de> Deserialize<'de> for BibType {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<BibType, D::Error> {
+            String::deserialize(deserializer).map(|name| BibType::parse(&name))
+        }
+    }
+
+    /** The fields of an entry as an object, in their order. */
+    struct Fields<'a>(&'a Entry);
+
+    impl Serialize for Fields<'_> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            let mut map = serializer.serialize_map(None)?;
+            for (name, value) in self.0.fields() {
+                map.serialize_entry(name, value)?;
+            }
+            map.end()
+        }
+    }
+
+    impl Serialize for Entry {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            let mut entry = serializer.serialize_struct("Entry", 3)?;
+            entry.serialize_field("type", self.entry_type())?;
+            entry.serialize_field("key", self.key())?;
+            entry.serialize_field("fields", &Fields(self))?;
+            entry.end()
+        }
+    }
+
+    /** Fields read back in their order, which a map type would lose. */
+    #[derive(Default)]
+    struct FieldList(Vec<(String, String)>);
+
+    impl<'de> Deserialize<'de> for FieldList {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<FieldList, D::Error> {
+            struct FieldVisitor;
+
+            impl<'de> Visitor<'de> for FieldVisitor {
+                type Value = FieldList;
+
+                fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                    f.write_str("an object of string fields")
+                }
+
+                fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<FieldList, A::Error> {
+                    let mut fields = Vec::new();
+                    while let Some(field) = map.next_entry::<String, String>()? {
+                        fields.push(field);
+                    }
+                    Ok(FieldList(fields))
+                }
+            }
+
+            deserializer.deserialize_map(FieldVisitor)
+        }
+    }
+
+    #[derive(Deserialize)]
+    struct EntryData {
+        #[serde(rename = "type")]
+        itemtype: BibType,
+        key: String,
+        #[serde(default)]
+        fields: FieldList,
+    }
+
+    impl<'de> Deserialize<'de> for Entry {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Entry, D::Error> {
+            let data = EntryData::deserialize(deserializer)?;
+            let mut entry = Entry::new(data.itemtype, &data.key);
+            for (name, value) in data.fields.0 {
+                entry.set(&name, &value);
+            }
+            Ok(entry)
+        }
+    }
+
+    impl Serialize for Bibliography {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            let mut bib = serializer.serialize_struct("Bibliography", 3)?;
+            bib.serialize_field("preambles", self.preambles())?;
+            bib.serialize_field("comments", self.comments())?;
+            bib.serialize_field("entries", self.entries())?;
+            bib.end()
+        }
+    }
+
+    #[derive(Deserialize)]
+    struct BibliographyData {
+        #[serde(default)]
+        preambles: Vec<String>,
+        #[serde(default)]
+        comments: Vec<String>,
+        entries: Vec<Entry>,
+    }
+
+    impl<'de> Deserialize<'de> for Bibliography {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Bibliography, D::Error> {
+            let data = BibliographyData::deserialize(deserializer)?;
+            let mut bib = Bibliography::from_entries(data.entries);
+            for preamble in data.preambles {
+                bib.add_preamble(&preamble);
+            }
+            for comment in data.comments {
+                bib.add_comment(&comment);
+            }
+            Ok(bib)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::bibtex::parser::{parse_bibliography_with, ParseOptions};
+    use crate::json;
+
+    #[test]
+    fn test_round_trip() {
+        let text = "@preamble{\"\\newcommand{\\noop}[1]{}\"}\n@comment{hand made}\n\
+            @article{knuth84, author = {Knuth, Donald E. and van Leunen, Mary-Claire}, title = {Literate {P}rogramming},\n\
+            year = 1984, url = {https://example.org/a%20b}}\n@talk{t, Title = {T}}\n";
+        let bib = parse_bibliography_with(text, ParseOptions { keep_comments: true, ..ParseOptions::standard() }).unwrap();
+        let json = json::parse(&bib.to_json().to_string()).unwrap();
+        let back = Bibliography::from_json(&json).unwrap();
+        assert_eq!(back.entries(), bib.entries());
+        assert_eq!((back.preambles(), back.comments()), (bib.preambles(), bib.comments()));
+        assert_eq!(json.path(&["entries"]).and_then(|e| e.as_array()).map(|e| e[1].get("type")), Some(Some(&JsonValue::str("talk"))));
+
+        let names = bib.get("knuth84").unwrap().authors();
+        let back: Vec<Name> = names.iter().map(|n| Name::from_json(&n.to_json()).unwrap()).collect();
+        assert_eq!(back, names);
+        assert_eq!(back[1].von, "van");
+
+        assert!(Bibliography::from_json(&json::parse("{\"entries\": [{\"type\": \"misc\"}]}").unwrap()).is_err());
+        let error = Entry::from_json(&json::parse("{\"type\": \"misc\", \"key\": \"a\", \"fields\": {\"year\": 2001}}").unwrap());
+        assert_eq!(error, Err(SerializeError(String::from("a: field year must be a string"))));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde() {
+        let text = "@preamble{\"\\noop\"}\n@article{knuth84, Title = {Literate {P}rogramming}, author = {Knuth, Donald E.}, year = 1984}\n@talk{t}\n";
+        let bib = parse_bibliography_with(text, ParseOptions { keep_comments: true, ..ParseOptions::standard() }).unwrap();
+        let text = serde_json::to_string(&bib).unwrap();
+        assert_eq!(json::parse(&text).unwrap(), bib.to_json());
+        assert!(text.contains(r#"{"type":"article","key":"knuth84","fields":{"title":"Literate {P}rogramming","author":"#), "{}", text);
+        let back: Bibliography = serde_json::from_str(&text).unwrap();
+        assert_eq!(back.entries(), bib.entries());
+        assert_eq!(back.preambles(), bib.preambles());
+
+        let name: Name = serde_json::from_str(r#"{"last": "Knuth", "first": "Donald E."}"#).unwrap();
+        assert_eq!(serde_json::to_string(&name).unwrap(), r#"{"first":"Donald E.","von":"","last":"Knuth","jr":""}"#);
+        assert!(serde_json::from_str::<Entry>(r#"{"type": "misc", "key": "a", "fields": {"year": 2001}}"#).is_err());
+        assert!(serde_json::from_str::<Entry>(r#"{"type": "misc"}"#).is_err());
+    }
+}
@@ -0,0 +1,244 @@
+/*!
+
+Sorting and alphabetic labels the way the classic BibTeX styles do it.
+
+`sort` orders entries like `plain.bst`: by names, then year, then title
+without a leading article. `plain_order` and `by_field` are comparators
+for `sort_by` and friends. `alpha_labels` computes `alpha.bst` labels such
+as `Knu84`, `KL86` or `LKM+90`, adding `a`, `b`, ... to labels shared by
+several entries.
+
+The names come from the author list, for books the editors if there is no
+author, for patents the holders if there are no inventors, for films and
+recordings the directors or composers (`bibtex::media`), and for
+proceedings the editors or the organization. An entry
+without any names is sorted and labelled by its `key` field, which exists
+for exactly this purpose:
+
+```text
+@misc{gnu-manual, key = {GNU}, title = {GNU Make}, year = {2020}}
+  => label GNU20, sorted under "gnu"
+```
+
+Without a `key` field either, such an entry sorts first and its label is
+taken from the citation key, and `lint` reports it.
+
+*/
+
+use std::cmp::Ordering;
+use crate::bibtex::data::Entry;
+use crate::bibtex::names::{parse_names, purify, Name};
+
+/** Lower-case and purify for comparison, collapsing whitespace. */
+fn sortify(s: &str) -> String {
+    purify(s).to_lowercase().split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+fn present<'a>(entry: &'a Entry, field: &str) -> Option<&'a str> {
+    entry.get(field).filter(|v| !v.trim().is_empty())
+}
+
+/**
+Where the names used for sorting and labels come from.
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NameSource<'a> {
+    Names(Vec<Name>),
+    Organization(&'a str),
+    /** The special `key` field. */
+    Key(&'a str),
+}
+
+pub fn name_source(entry: &Entry) -> Option<NameSource<'_>> {
+    let names = |f: &str| present(entry, f).map(|v| NameSource::Names(parse_names(v)));
+    let key = || present(entry, "key").map(NameSource::Key);
+    let people = |names: Vec<Name>| (!names.is_empty()).then_some(NameSource::Names(names));
+    match entry.entry_type().name() {
+        "book" | "inbook" => names("author").or_else(|| names("editor")).or_else(key),
+        "patent" => names("author").or_else(|| names("holder")).or_else(key),
+        "movie" | "video" => names("author").or_else(|| people(entry.directors())).or_else(key),
+        "audio" => names("author").or_else(|| people(entry.composers())).or_else(key),
+        "proceedings" => names("editor").or_else(key)
+            .or_else(|| present(entry, "organization").map(NameSource::Organization)),
+        _ => names("author").or_else(key),
+    }
+}
+
+fn sort_names(names: &[Name]) -> String {
+    names.iter().map(|n| {
+        if n.is_others() {
+            String::from("et al")
+        } else {
+            format!("{}  {}  {}", n.von_last(), n.first, n.jr)
+        }
+    }).collect::<Vec<String>>().join("   ")
+}
+
+/** Title for sorting, without a leading `A`, `An` or `The`. */
+fn sort_title(title: &str) -> String {
+    let t = sortify(title);
+    for article in ["a ", "an ", "the "] {
+        if let Some(rest) = t.strip_prefix(article) {
+            return String::from(rest);
+        }
+    }
+    t
+}
+
+/**
+The string `plain.bst` sorts `entry` by.
+*/
+pub fn sort_key(entry: &Entry) -> String {
+    let names = match name_source(entry) {
+        Some(NameSource::Names(names)) => sort_names(&names),
+        Some(NameSource::Organization(org)) => String::from(org.trim().strip_prefix("The ").unwrap_or(org.trim())),
+        Some(NameSource::Key(key)) => String::from(key),
+        None => String::new(),
+    };
+    format!("{}    {}    {}", sortify(&names), sortify(entry.get("year").unwrap_or_default()),
+            sort_title(entry.get("title").unwrap_or_default()))
+}
+
+/**
+Sort entries by `sort_key`; entries with equal keys keep their order.
+*/
+pub fn sort(entries: &mut [Entry]) {
+    entries.sort_by_cached_key(sort_key);
+}
+
+/** Compare entries as `sort` orders them, for `sort_by` and sorted collections. */
+pub fn plain_order(a: &Entry, b: &Entry) -> Ordering {
+    sort_key(a).cmp(&sort_key(b))
+}
+
+/**
+A comparator ordering entries by the purified, lower-cased value of
+`field`, entries without it last and ties by citation key:
+`entries.sort_by(by_field("year"))`.
+*/
+pub fn by_field(field: &str) -> impl Fn(&Entry, &Entry) -> Ordering + '_ {
+    move |a, b| {
+        let value = |e: &Entry| present(e, field).map(sortify);
+        match (value(a), value(b)) {
+            (Some(x), Some(y)) => x.cmp(&y),
+            (x, y) => x.is_none().cmp(&y.is_none()),
+        }.then_with(|| a.key().cmp(b.key()))
+    }
+}
+
+fn initials(s: &str) -> String {
+    s.split_whitespace().filter_map(|w| purify(w).chars().find(|c| c.is_alphanumeric())).collect()
+}
+
+fn prefix(s: &str, n: usize) -> String {
+    purify(s).chars().filter(|c| !c.is_whitespace()).take(n).collect()
+}
+
+fn name_label(names: &[Name]) -> String {
+    match names {
+        [] => String::new(),
+        [one] => {
+            let label = initials(&one.von) + initials(&one.last).as_str();
+            if label.chars().count() < 2 { prefix(&one.last, 3) } else { label }
+        }
+        _ => {
+            let shown = if names.len() > 4 { 3 } else { names.len() };
+            let mut label: String = names[..shown].iter()
+                .map(|n| if n.is_others() { String::from("+") } else { initials(&n.von) + initials(&n.last).as_str() })
+                .collect();
+            if names.len() > 4 {
+                label.push('+');
+            }
+            label
+        }
+    }
+}
+
+/**
+The `alpha.bst` label of `entry` without the suffix telling apart entries
+with the same label.
+*/
+pub fn alpha_label(entry: &Entry) -> String {
+    let base = match name_source(entry) {
+        Some(NameSource::Names(names)) => name_label(&names),
+        Some(NameSource::Organization(org)) => prefix(org.trim().strip_prefix("The ").unwrap_or(org.trim()), 3),
+        Some(NameSource::Key(key)) => prefix(key, 3),
+        None => prefix(entry.key(), 3),
+    };
+    let year = purify(entry.get("year").unwrap_or_default()).chars().filter(|c| c.is_ascii_digit()).collect::<String>();
+    let yy = &year[year.len().saturating_sub(2)..];
+    base + yy
+}
+
+/**
+Labels for `entries` in their current order, with `a`, `b`, ... appended
+to every label shared by more than one entry.
+*/
+pub fn alpha_labels(entries: &[Entry]) -> Vec<String> {
+    let labels: Vec<String> = entries.iter().map(alpha_label).collect();
+    let mut seen: Vec<(&str, usize)> = Vec::new();
+    labels.iter().map(|label| {
+        if labels.iter().filter(|l| *l == label).count() == 1 {
+            return label.clone();
+        }
+        let n = match seen.iter_mut().find(|(l, _)| l == label) {
+            Some((_, n)) => {
+                *n += 1;
+                *n
+            }
+            None => {
+                seen.push((label, 0));
+                0
+            }
+        };
+        format!("{}{}", label, (b'a' + (n % 26) as u8) as char)
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::bibtex::data::BibType;
+
+    fn entry(key: &str, fields: &[(&str, &str)]) -> Entry {
+        let mut e = Entry::new(BibType::Misc, key);
+        for (f, v) in fields {
+            e.set(f, v);
+        }
+        e
+    }
+
+    #[test]
+    fn test_alpha_labels() {
+        let entries = vec![
+            entry("a", &[("author", "Donald E. Knuth"), ("year", "1984")]),
+            entry("b", &[("author", "Knuth, Donald and Lamport, Leslie"), ("year", "1986")]),
+            entry("c", &[("author", "A. Aho and B. Kernighan and P. Weinberger and X. Yu and Z. Zed"), ("year", "1990")]),
+            entry("d", &[("key", "GNU"), ("title", "GNU Make"), ("year", "2020")]),
+            entry("e", &[("author", "Knuth, D."), ("year", "{1984}")]),
+            entry("gnu-manual", &[("year", "2021")]),
+            entry("f", &[("author", "Ludwig van Beethoven")]),
+        ];
+        assert_eq!(alpha_labels(&entries), vec!["Knu84a", "KL86", "AKW+90", "GNU20", "Knu84b", "gnu21", "vB"]);
+    }
+
+    #[test]
+    fn test_sort() {
+        let mut entries = vec![
+            entry("z", &[("author", "Zed, Z."), ("year", "1999")]),
+            entry("k", &[("key", "Knuth"), ("title", "The Art")]),
+            entry("k2", &[("author", "Knuth, Donald"), ("year", "1984"), ("title", "An Earlier Book")]),
+            entry("none", &[("title", "Anonymous")]),
+        ];
+        sort(&mut entries);
+        let keys: Vec<&str> = entries.iter().map(|e| e.key()).collect();
+        assert_eq!(keys, vec!["none", "k", "k2", "z"]);
+        assert_eq!(sort_key(&entries[2]), "knuth donald    1984    earlier book");
+        assert_eq!(plain_order(&entries[3], &entries[0]), Ordering::Greater);
+
+        entries.sort_by(by_field("year"));
+        let keys: Vec<&str> = entries.iter().map(|e| e.key()).collect();
+        assert_eq!(keys, vec!["k2", "z", "k", "none"]);
+    }
+}
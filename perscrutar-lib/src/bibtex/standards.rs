@@ -0,0 +1,221 @@
+/*!
+
+RFCs and standards.
+
+Standards are cited as `@techreport`, `@misc` or biblatex `@standard`
+entries whose `number` names the document, with or without the issuing
+body in `series`:
+
+```text
+number = {RFC 8446}
+series = {Request for Comments}, number = {8446}
+number = {ISO/IEC 27001:2013}
+number = {NIST SP 800-63B}
+number = {FIPS 197}
+```
+
+`Standard::from_entry` recognises these, `Standard::canonical_number`
+writes them one way, and `Standard::url` gives the publisher's page for the
+document where its address can be derived from the number: the RFC Editor's
+info page for RFCs and the NIST Computer Security Resource Center for NIST
+publications. ISO and IEC pages use internal identifiers, so no URL is made
+up for them. RFCs also have a DOI.
+
+The `normalize-standards` transform applies all this to a bibliography; metadata for
+an RFC can be fetched with `lookup::rfc`.
+
+*/
+
+use crate::bibtex::data::Entry;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Body {
+    Rfc,
+    Iso,
+    /** NIST Special Publications, `SP 800-63B`. */
+    NistSp,
+    /** Federal Information Processing Standards, `FIPS 197`. */
+    Fips,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Standard {
+    pub body: Body,
+    /** Number within the series: `8446`, `IEC 27001:2013`, `800-63B`. */
+    pub number: String,
+}
+
+/** `series` values meaning the RFC series. */
+const RFC_SERIES: [&str; 3] = ["rfc", "request for comments", "requests for comments"];
+
+fn strip_prefix_ci<'a>(s: &'a str, prefix: &str) -> Option<&'a str> {
+    (s.len() >= prefix.len() && s.as_bytes()[..prefix.len()].eq_ignore_ascii_case(prefix.as_bytes()))
+        .then(|| &s[prefix.len()..])
+}
+
+impl Standard {
+    /**
+    Parse a document number such as `RFC 8446`, `RFC8446`, `ISO 8601:2004`,
+    `ISO/IEC 27001:2013`, `NIST SP 800-63B`, `NIST.SP.800-38D` or
+    `FIPS PUB 197`.
+    */
+    pub fn parse(number: &str) -> Option<Standard> {
+        let s = number.trim().trim_matches(|c| c == '{' || c == '}');
+        let rest = |prefix: &str| strip_prefix_ci(s, prefix).map(|r| r.trim_start_matches([' ', '.', '~', '-']).trim());
+        let standard = |body, n: &str| (!n.is_empty()).then(|| Standard { body, number: String::from(n) });
+        if let Some(n) = rest("RFC") {
+            return n.chars().all(|c| c.is_ascii_digit()).then(|| n.trim_start_matches('0'))
+                .and_then(|n| standard(Body::Rfc, n));
+        }
+        if let Some(n) = rest("NIST SP").or_else(|| rest("NIST.SP")).or_else(|| rest("SP")) {
+            // 800 53 Rev. 5 -> 800-53r5, as NIST writes revisions
+            let mut out = String::new();
+            for part in n.split([' ', '.', '-']).filter(|p| !p.is_empty()) {
+                if part.eq_ignore_ascii_case("rev") || part.eq_ignore_ascii_case("r") {
+                    out.push('r');
+                } else {
+                    if !out.is_empty() && !out.ends_with('r') {
+                        out.push('-');
+                    }
+                    out.push_str(&part.to_uppercase());
+                }
+            }
+            return out.starts_with(|c: char| c.is_ascii_digit()).then_some(out)
+                .and_then(|n| standard(Body::NistSp, &n));
+        }
+        if let Some(n) = rest("FIPS PUB").or_else(|| rest("FIPS")) {
+            return n.starts_with(|c: char| c.is_ascii_digit()).then_some(n).and_then(|n| standard(Body::Fips, n));
+        }
+        if let Some(n) = rest("ISO") {
+            let n = n.trim_start_matches('/');
+            return n.contains(|c: char| c.is_ascii_digit()).then_some(n).and_then(|n| standard(Body::Iso, n));
+        }
+        None
+    }
+
+    /**
+    The standard `entry` describes, from its `number`, or from a bare number
+    in the RFC `series`.
+    */
+    pub fn from_entry(entry: &Entry) -> Option<Standard> {
+        let number = entry.get("number")?.trim();
+        if let Some(standard) = Standard::parse(number) {
+            return Some(standard);
+        }
+        let series = entry.get("series").map(|s| s.trim().to_lowercase()).unwrap_or_default();
+        (RFC_SERIES.contains(&series.as_str()) || entry.get("type").map(|t| t.trim().eq_ignore_ascii_case("rfc")) == Some(true))
+            .then(|| Standard::parse(&format!("RFC {}", number)))
+            .flatten()
+    }
+
+    /**
+    The number as it is usually written: `RFC 8446`, `ISO/IEC 27001:2013`,
+    `NIST SP 800-63B`, `FIPS 197`.
+    */
+    pub fn canonical_number(&self) -> String {
+        match self.body {
+            Body::Rfc => format!("RFC {}", self.number),
+            Body::Iso if self.number.starts_with(|c: char| c.is_ascii_digit()) => format!("ISO {}", self.number),
+            Body::Iso => format!("ISO/{}", self.number),
+            Body::NistSp => format!("NIST SP {}", self.number),
+            Body::Fips => format!("FIPS {}", self.number),
+        }
+    }
+
+    /**
+    The publisher's page for the document, where it can be derived from
+    the number.
+    */
+    pub fn url(&self) -> Option<String> {
+        match self.body {
+            Body::Rfc => Some(format!("https://www.rfc-editor.org/info/rfc{}", self.number)),
+            Body::Iso => None,
+            Body::NistSp => {
+                // 800-53r5 -> sp/800/53/r5, 800-63B -> sp/800/63/b
+                let n = self.number.to_lowercase();
+                let (series, rest) = n.split_once('-')?;
+                let digits = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+                let mut path = format!("{}/{}", series, &rest[..digits]);
+                let suffix = rest[digits..].trim_matches(|c: char| c == '-' || c == ' ' || c == '.');
+                if !suffix.is_empty() {
+                    path.push('/');
+                    path.push_str(suffix);
+                }
+                Some(format!("https://csrc.nist.gov/pubs/sp/{}/final", path))
+            }
+            Body::Fips => Some(format!("https://csrc.nist.gov/pubs/fips/{}/final", self.number.to_lowercase())),
+        }
+    }
+
+    pub fn doi(&self) -> Option<String> {
+        (self.body == Body::Rfc).then(|| format!("10.17487/RFC{:0>4}", self.number))
+    }
+}
+
+/**
+Write the number of a standard canonically and add its `url` and `doi`
+where missing. Returns whether the entry changed.
+*/
+pub fn normalize_standard(entry: &mut Entry) -> bool {
+    let Some(standard) = Standard::from_entry(entry) else { return false };
+    let before = entry.clone();
+    // a bare RFC number in the RFC series stays bare, as the series says it
+    if Standard::parse(entry.get("number").unwrap_or_default()).is_some() {
+        entry.set("number", &standard.canonical_number());
+    }
+    for (field, value) in [("url", standard.url()), ("doi", standard.doi())] {
+        if let (false, Some(value)) = (entry.has(field), value) {
+            entry.set(field, &value);
+        }
+    }
+    !entry.is_identical(&before)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::bibtex::data::BibType;
+
+    #[test]
+    fn test_parse() {
+        let canonical = |s: &str| Standard::parse(s).map(|s| s.canonical_number());
+        assert_eq!(canonical("RFC8446").as_deref(), Some("RFC 8446"));
+        assert_eq!(canonical("rfc 0791").as_deref(), Some("RFC 791"));
+        assert_eq!(canonical("ISO/IEC 27001:2013").as_deref(), Some("ISO/IEC 27001:2013"));
+        assert_eq!(canonical("ISO 8601:2004").as_deref(), Some("ISO 8601:2004"));
+        assert_eq!(canonical("NIST.SP.800-38D").as_deref(), Some("NIST SP 800-38D"));
+        assert_eq!(canonical("NIST SP 800-53 Rev. 5").as_deref(), Some("NIST SP 800-53r5"));
+        assert_eq!(canonical("FIPS PUB 197").as_deref(), Some("FIPS 197"));
+        assert_eq!(canonical("RFC draft"), None);
+        assert_eq!(canonical("42"), None);
+
+        let url = |s: &str| Standard::parse(s).and_then(|s| s.url());
+        assert_eq!(url("RFC 8446").as_deref(), Some("https://www.rfc-editor.org/info/rfc8446"));
+        assert_eq!(url("NIST SP 800-63B").as_deref(), Some("https://csrc.nist.gov/pubs/sp/800/63/b/final"));
+        assert_eq!(url("NIST SP 800-53 Rev. 5").as_deref(), Some("https://csrc.nist.gov/pubs/sp/800/53/r5/final"));
+        assert_eq!(url("SP 800-90A"), Some(String::from("https://csrc.nist.gov/pubs/sp/800/90/a/final")));
+        assert_eq!(url("FIPS 197").as_deref(), Some("https://csrc.nist.gov/pubs/fips/197/final"));
+        assert_eq!(url("ISO 8601:2004"), None);
+        assert_eq!(Standard::parse("RFC 791").unwrap().doi().as_deref(), Some("10.17487/RFC0791"));
+    }
+
+    #[test]
+    fn test_normalize_standard() {
+        let mut rfc = Entry::new(BibType::Misc, "rfc8446");
+        rfc.set("series", "Request for Comments");
+        rfc.set("number", "8446");
+        assert!(normalize_standard(&mut rfc));
+        assert_eq!(rfc.get("number"), Some("8446"));
+        assert_eq!(rfc.get("doi"), Some("10.17487/RFC8446"));
+        assert!(!normalize_standard(&mut rfc));
+
+        let mut sp = Entry::new(BibType::Report, "sp");
+        sp.set("number", "NIST.SP.800-63b");
+        sp.set("url", "https://doi.org/10.6028/NIST.SP.800-63b");
+        assert!(normalize_standard(&mut sp));
+        assert_eq!(sp.get("number"), Some("NIST SP 800-63B"));
+        assert_eq!(sp.get("url"), Some("https://doi.org/10.6028/NIST.SP.800-63b"));
+        assert!(!normalize_standard(&mut Entry::new(BibType::Report, "r")));
+    }
+}
@@ -0,0 +1,181 @@
+/*!
+
+Theses in BibTeX and biblatex.
+
+The two disagree on how to describe a thesis:
+
+- BibTeX has `@phdthesis` and `@mastersthesis`, with the university in
+  `school`; an optional `type` is printed instead of "PhD thesis", so it
+  holds text such as `Habilitation`;
+- biblatex has one `@thesis` whose required `type` says which kind it is,
+  preferably as a localisation key (`phdthesis`, `mathesis`, ...), with the
+  university in `institution`. It still reads the BibTeX types and `school`.
+
+`check_entry` reports the mix-ups this leads to, and `to_biblatex` and
+`to_bibtex` (the `thesis-biblatex` and `thesis-bibtex` transforms) convert
+an entry from one convention to the other.
+
+*/
+
+use crate::bibtex::data::{BibType, Entry};
+use crate::lint::{Diagnostic, Severity};
+
+/** biblatex's localisation keys for `type`. */
+const TYPE_KEYS: [&str; 8] = ["phdthesis", "mathesis", "candthesis", "techreport", "resreport", "software", "datacd", "audiocd"];
+
+/**
+The biblatex key a thesis `type` stands for, whether it is written as the
+key itself or spelled out (`PhD thesis`, `Master's thesis`, `MSc`).
+*/
+pub fn type_key(value: &str) -> Option<&'static str> {
+    let v: String = value.to_lowercase().chars().filter(|c| c.is_alphanumeric()).collect();
+    if let Some(key) = TYPE_KEYS.into_iter().find(|k| *k == v) {
+        return Some(key);
+    }
+    match v.as_str() {
+        "phd" | "phdthesis" | "phddissertation" | "doctoralthesis" | "doctoraldissertation" | "dissertation" => Some("phdthesis"),
+        "ma" | "msc" | "masters" | "master" | "mastersthesis" | "masterthesis" | "msthesis" | "mscthesis" => Some("mathesis"),
+        _ => None,
+    }
+}
+
+fn value<'a>(entry: &'a Entry, field: &str) -> Option<&'a str> {
+    entry.get(field).map(str::trim).filter(|v| !v.is_empty())
+}
+
+/**
+Thesis checks: the kind of a biblatex `@thesis` should be given as a key
+in `type`, a BibTeX thesis should not have one as its `type`, and `school`
+and `institution` should not name different places.
+*/
+pub fn check_entry(entry: &Entry) -> Vec<Diagnostic> {
+    let mut out = Vec::new();
+    let key = entry.key();
+    let kind = value(entry, "type");
+    match (entry.entry_type(), kind) {
+        (BibType::Thesis, Some(t)) => match type_key(t) {
+            Some(k) if k != t => out.push(Diagnostic::new(key, "thesis-type", Severity::Info,
+                &format!("type `{}` is better given as the key `{}`, which biblatex translates", t, k))),
+            _ => {}
+        },
+        (BibType::PhdThesis | BibType::MastersThesis, Some(t)) if TYPE_KEYS.contains(&t) => {
+            out.push(Diagnostic::new(key, "thesis-type", Severity::Warning,
+                &format!("type `{}` is a biblatex key, which BibTeX styles print as is", t)));
+        }
+        _ => {}
+    }
+    if matches!(entry.entry_type(), BibType::Thesis | BibType::PhdThesis | BibType::MastersThesis) {
+        if let (Some(school), Some(institution)) = (value(entry, "school"), value(entry, "institution")) {
+            if school != institution {
+                out.push(Diagnostic::new(key, "thesis-institution", Severity::Warning,
+                    "school and institution differ; only one of them is printed"));
+            }
+        }
+    }
+    out
+}
+
+/**
+Move `from` to `to` unless `to` is already set; a `from` equal to it goes.
+*/
+fn move_field(entry: &mut Entry, from: &str, to: &str) {
+    if !entry.rename(from, to) && entry.get(from) == entry.get(to) {
+        entry.remove(from);
+    }
+}
+
+/**
+Turn `@phdthesis` and `@mastersthesis` into a biblatex `@thesis` with the
+matching `type` key, and `school` into `institution`. A spelled-out kind in
+`type` becomes its key; other text is kept. Returns whether the entry
+changed.
+*/
+pub fn to_biblatex(entry: &mut Entry) -> bool {
+    let before = entry.clone();
+    let key = match entry.entry_type() {
+        BibType::PhdThesis => "phdthesis",
+        BibType::MastersThesis => "mathesis",
+        BibType::Thesis => "",
+        _ => return false,
+    };
+    if !key.is_empty() {
+        entry.set_entry_type(BibType::Thesis);
+        if value(entry, "type").is_none() {
+            entry.set("type", key);
+        }
+    }
+    if let Some(k) = value(entry, "type").and_then(type_key) {
+        entry.set("type", k);
+    }
+    move_field(entry, "school", "institution");
+    !entry.is_identical(&before)
+}
+
+/**
+Turn a biblatex `@thesis` into `@phdthesis` or `@mastersthesis` by its
+`type`, and `institution` into `school`. A `type` that is neither kind stays
+as the text BibTeX prints, on a `@phdthesis`. Returns whether the entry
+changed.
+*/
+pub fn to_bibtex(entry: &mut Entry) -> bool {
+    let before = entry.clone();
+    if *entry.entry_type() == BibType::Thesis {
+        let kind = value(entry, "type").and_then(type_key);
+        entry.set_entry_type(if kind == Some("mathesis") { BibType::MastersThesis } else { BibType::PhdThesis });
+        if matches!(kind, Some("phdthesis" | "mathesis")) {
+            entry.remove("type");
+        }
+    }
+    match entry.entry_type() {
+        BibType::PhdThesis | BibType::MastersThesis => move_field(entry, "institution", "school"),
+        _ => return false,
+    }
+    !entry.is_identical(&before)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn thesis(itemtype: BibType, fields: &[(&str, &str)]) -> Entry {
+        let mut e = Entry::new(itemtype, "t");
+        e.set("author", "Doe, Jane");
+        for (k, v) in fields {
+            e.set(k, v);
+        }
+        e
+    }
+
+    #[test]
+    fn test_check_entry() {
+        let rules = |e: &Entry| check_entry(e).into_iter().map(|d| (d.rule, d.severity)).collect::<Vec<_>>();
+        assert_eq!(type_key("Master's thesis"), Some("mathesis"));
+        assert_eq!(type_key("Habilitation"), None);
+        assert_eq!(rules(&thesis(BibType::Thesis, &[("type", "PhD thesis")])), vec![("thesis-type", Severity::Info)]);
+        assert!(rules(&thesis(BibType::Thesis, &[("type", "phdthesis"), ("institution", "MIT")])).is_empty());
+        assert_eq!(rules(&thesis(BibType::MastersThesis, &[("type", "mathesis"), ("school", "MIT"), ("institution", "ETH")])),
+            vec![("thesis-type", Severity::Warning), ("thesis-institution", Severity::Warning)]);
+    }
+
+    #[test]
+    fn test_convert() {
+        let mut e = thesis(BibType::MastersThesis, &[("school", "MIT"), ("year", "2001")]);
+        assert!(to_biblatex(&mut e));
+        assert_eq!(e.entry_type(), &BibType::Thesis);
+        assert_eq!(e.field_names(), vec!["author", "institution", "year", "type"]);
+        assert_eq!(e.get("type"), Some("mathesis"));
+        assert!(!to_biblatex(&mut e));
+        assert!(to_bibtex(&mut e));
+        assert_eq!(e, thesis(BibType::MastersThesis, &[("school", "MIT"), ("year", "2001")]));
+
+        let mut hab = thesis(BibType::Thesis, &[("type", "Habilitation"), ("institution", "TUM"), ("school", "TUM")]);
+        assert!(to_bibtex(&mut hab));
+        assert_eq!(hab.entry_type(), &BibType::PhdThesis);
+        assert_eq!(hab.field_names(), vec!["author", "type", "school"]);
+        let mut phd = thesis(BibType::PhdThesis, &[("type", "Doctoral dissertation")]);
+        to_biblatex(&mut phd);
+        assert_eq!(phd.get("type"), Some("phdthesis"));
+        assert!(!to_bibtex(&mut Entry::new(BibType::Article, "a")));
+    }
+}
@@ -0,0 +1,150 @@
+/*!
+
+Titles with subtitles and additions.
+
+biblatex keeps a title in up to four fields: `title`, `subtitle`,
+`titleaddon` (an annotation such as `Extended version`) and `shorttitle`
+for short citations. `Title::full` joins them the way the renderers print
+them:
+
+```text
+title = {Concrete Mathematics}, subtitle = {A Foundation for Computer Science}
+  => Concrete Mathematics: A Foundation for Computer Science
+```
+
+A title that already ends in `?`, `!` or `:` is followed by a space only.
+BibTeX data often has the subtitle in the title itself; `split_title`
+moves it into `subtitle`.
+
+*/
+
+use crate::bibtex::data::Entry;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Title {
+    pub title: String,
+    pub subtitle: Option<String>,
+    pub titleaddon: Option<String>,
+    pub shorttitle: Option<String>,
+}
+
+fn join(out: &mut String, separator: &str, part: &str) {
+    if out.ends_with(['?', '!', ':', '.']) {
+        out.push(' ');
+    } else if !out.is_empty() {
+        out.push_str(separator);
+    }
+    out.push_str(part);
+}
+
+impl Title {
+    /**
+    Title, subtitle and title addition joined for display.
+    */
+    pub fn full(&self) -> String {
+        let mut out = self.title.clone();
+        if let Some(sub) = &self.subtitle {
+            join(&mut out, ": ", sub);
+        }
+        if let Some(addon) = &self.titleaddon {
+            join(&mut out, ". ", addon);
+        }
+        out
+    }
+
+    /**
+    The short title, falling back to the title without subtitle.
+    */
+    pub fn short(&self) -> &str {
+        self.shorttitle.as_deref().unwrap_or(&self.title)
+    }
+}
+
+impl Entry {
+    /**
+    The title fields, or `None` if the entry has no title.
+    */
+    pub fn title_parts(&self) -> Option<Title> {
+        let get = |f: &str| self.get(f).map(str::trim).filter(|v| !v.is_empty()).map(String::from);
+        Some(Title {
+            title: get("title")?,
+            subtitle: get("subtitle"),
+            titleaddon: get("titleaddon"),
+            shorttitle: get("shorttitle"),
+        })
+    }
+}
+
+/**
+Byte offset of the first `": "` outside of TeX braces, so that protected
+text such as `{Tor: The Second-Generation Onion Router}` is left alone.
+*/
+fn separator(title: &str) -> Option<usize> {
+    let mut depth = 0;
+    let mut prev = '\0';
+    for (i, c) in title.char_indices() {
+        match c {
+            '{' if prev != '\\' => depth += 1,
+            '}' if prev != '\\' && depth > 0 => depth -= 1,
+            ':' if depth == 0 && title[i + 1..].starts_with(' ') => return Some(i),
+            _ => {}
+        }
+        prev = c;
+    }
+    None
+}
+
+/**
+Split a combined `Title: Subtitle` into `title` and `subtitle`. Entries
+that already have a subtitle are left alone. Returns whether the entry
+changed.
+*/
+pub fn split_title(entry: &mut Entry) -> bool {
+    if entry.get("subtitle").map(|s| !s.trim().is_empty()).unwrap_or(false) {
+        return false;
+    }
+    let Some(title) = entry.get("title").map(String::from) else { return false };
+    let Some(at) = separator(&title) else { return false };
+    let (main, sub) = (title[..at].trim(), title[at + 1..].trim());
+    if main.is_empty() || sub.is_empty() {
+        return false;
+    }
+    entry.set("subtitle", sub);
+    entry.set("title", main);
+    true
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::bibtex::data::BibType;
+
+    #[test]
+    fn test_full() {
+        let mut t = Title { title: String::from("Concrete Mathematics"), ..Title::default() };
+        t.subtitle = Some(String::from("A Foundation for Computer Science"));
+        t.titleaddon = Some(String::from("Second edition"));
+        assert_eq!(t.full(), "Concrete Mathematics: A Foundation for Computer Science. Second edition");
+        t.title = String::from("Why Functional Programming Matters?");
+        t.titleaddon = None;
+        assert_eq!(t.full(), "Why Functional Programming Matters? A Foundation for Computer Science");
+        assert_eq!(t.short(), "Why Functional Programming Matters?");
+    }
+
+    #[test]
+    fn test_split_title() {
+        let mut e = Entry::new(BibType::Book, "Knuth-CM");
+        e.set("title", "Concrete Mathematics: A Foundation for Computer Science");
+        assert!(split_title(&mut e));
+        assert_eq!(e.get("title"), Some("Concrete Mathematics"));
+        assert_eq!(e.get("subtitle"), Some("A Foundation for Computer Science"));
+        assert!(!split_title(&mut e));
+
+        let mut tor = Entry::new(BibType::InProceedings, "Tor");
+        tor.set("title", "{Tor: The Second-Generation Onion Router}");
+        assert!(!split_title(&mut tor));
+        tor.set("title", "Re:Think");
+        assert!(!split_title(&mut tor));
+    }
+}
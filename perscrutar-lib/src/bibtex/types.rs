@@ -0,0 +1,261 @@
+/*!
+
+Registry of entry types and the fields each of them requires.
+
+The built-in types follow the classic BibTeX definitions. Organisations with
+their own conventions (`@standard`, `@video`, `@talk`, ...) can register
+additional types with a schema instead of folding them into `@misc`:
+
+    use perscrutarlib::bibtex::types::{TypeRegistry, TypeSchema};
+
+    let mut registry = TypeRegistry::default();
+    registry.register("standard", TypeSchema::new()
+        .require("title")
+        .require("organization")
+        .optional("number"));
+    assert!(registry.is_known("Standard"));
+
+*/
+
+use std::collections::HashMap;
+use crate::bibtex::data::BibType;
+
+/**
+A single requirement: at least one of the listed fields must be present.
+Most requirements name one field; `author`/`editor` style alternatives
+name several.
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Requirement(Vec<String>);
+
+impl Requirement {
+    pub fn fields(&self) -> &[String] {
+        &self.0
+    }
+
+    pub fn is_satisfied_by<F: Fn(&str) -> bool>(&self, has_field: F) -> bool {
+        self.0.iter().any(|f| has_field(f))
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TypeSchema {
+    required: Vec<Requirement>,
+    optional: Vec<String>,
+}
+
+impl TypeSchema {
+    pub fn new() -> TypeSchema {
+        TypeSchema::default()
+    }
+
+    pub fn require(mut self, field: &str) -> TypeSchema {
+        self.required.push(Requirement(vec![field.to_lowercase()]));
+        self
+    }
+
+    /**
+    Require at least one of `fields`.
+    */
+    pub fn require_any(mut self, fields: &[&str]) -> TypeSchema {
+        self.required.push(Requirement(fields.iter().map(|f| f.to_lowercase()).collect()));
+        self
+    }
+
+    pub fn optional(mut self, field: &str) -> TypeSchema {
+        self.optional.push(field.to_lowercase());
+        self
+    }
+
+    pub fn required(&self) -> &[Requirement] {
+        &self.required
+    }
+
+    pub fn optional_fields(&self) -> &[String] {
+        &self.optional
+    }
+
+    /**
+    True if `field` is named anywhere in the schema.
+    */
+    pub fn mentions(&self, field: &str) -> bool {
+        let field = field.to_lowercase();
+        self.optional.contains(&field)
+            || self.required.iter().any(|r| r.0.contains(&field))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TypeRegistry {
+    schemas: HashMap<String, TypeSchema>,
+}
+
+impl TypeRegistry {
+    /**
+    A registry without any types, not even the built-in ones.
+    */
+    pub fn empty() -> TypeRegistry {
+        TypeRegistry { schemas: HashMap::new() }
+    }
+
+    /**
+    Register (or replace) the schema for `name` and return the type it
+    now resolves to.
+    */
+    pub fn register(&mut self, name: &str, schema: TypeSchema) -> BibType {
+        let itemtype = BibType::parse(name);
+        self.schemas.insert(itemtype.name().to_string(), schema);
+        itemtype
+    }
+
+    /**
+    Resolve a type name. Unknown names still resolve (to `BibType::Other`),
+    use `is_known` to distinguish registered types.
+    */
+    pub fn resolve(&self, name: &str) -> BibType {
+        BibType::parse(name)
+    }
+
+    pub fn is_known(&self, name: &str) -> bool {
+        self.schemas.contains_key(BibType::parse(name).name())
+    }
+
+    pub fn schema(&self, itemtype: &BibType) -> Option<&TypeSchema> {
+        self.schemas.get(itemtype.name())
+    }
+
+    /**
+    Names of all registered types, sorted.
+    */
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.schemas.keys().map(|k| k.as_str()).collect();
+        names.sort_unstable();
+        names
+    }
+}
+
+impl Default for TypeRegistry {
+    fn default() -> Self {
+        let mut r = TypeRegistry::empty();
+        r.register("article", TypeSchema::new()
+            .require("author").require("title").require("journal").require("year")
+            .optional("volume").optional("number").optional("pages").optional("month")
+            .optional("doi").optional("note"));
+        r.register("book", TypeSchema::new()
+            .require_any(&["author", "editor"]).require("title").require("publisher").require("year")
+            .optional("volume").optional("series").optional("number").optional("volumes")
+            .optional("maintitle").optional("mainsubtitle").optional("address").optional("edition")
+            .optional("isbn").optional("doi").optional("note"));
+        r.register("incollection", TypeSchema::new()
+            .require("author").require("title").require("booktitle").require("publisher").require("year")
+            .optional("editor").optional("pages").optional("address").optional("series").optional("volume")
+            .optional("number").optional("maintitle").optional("mainsubtitle").optional("doi").optional("note"));
+        r.register("inproceedings", TypeSchema::new()
+            .require("author").require("title").require("booktitle").require("year")
+            .optional("editor").optional("pages").optional("publisher").optional("address")
+            .optional("organization").optional("series").optional("volume").optional("number")
+            .optional("doi").optional("note"));
+        r.register("proceedings", TypeSchema::new()
+            .require("title").require("year")
+            .optional("editor").optional("booktitle").optional("publisher").optional("address")
+            .optional("organization").optional("series").optional("volume").optional("number")
+            .optional("volumes").optional("maintitle").optional("mainsubtitle").optional("doi").optional("note"));
+        r.register("misc", TypeSchema::new()
+            .optional("author").optional("title").optional("howpublished").optional("year")
+            .optional("url").optional("note"));
+        r.register("techreport", TypeSchema::new()
+            .require("author").require("title").require("institution").require("year")
+            .optional("type").optional("number").optional("address").optional("note"));
+        // biblatex reads `school` as `institution` and BibTeX styles ignore
+        // `institution` on theses, so either names the university
+        r.register("thesis", TypeSchema::new()
+            .require("author").require("title").require("type").require_any(&["institution", "school"]).require("year")
+            .optional("address").optional("doi").optional("url").optional("note"));
+        r.register("phdthesis", TypeSchema::new()
+            .require("author").require("title").require_any(&["school", "institution"]).require("year")
+            .optional("type").optional("address").optional("doi").optional("url").optional("note"));
+        r.register("mastersthesis", TypeSchema::new()
+            .require("author").require("title").require_any(&["school", "institution"]).require("year")
+            .optional("type").optional("address").optional("doi").optional("url").optional("note"));
+        // biblatex-software's types, as `software` writes them
+        r.register("software", TypeSchema::new()
+            .require_any(&["author", "editor"]).require("title").require_any(&["year", "date"])
+            .optional("version").optional("doi").optional("swhid").optional("repository").optional("url")
+            .optional("license").optional("publisher").optional("abstract").optional("keywords").optional("note"));
+        r.register("dataset", TypeSchema::new()
+            .require_any(&["author", "editor"]).require("title").require_any(&["year", "date"])
+            .optional("version").optional("doi").optional("swhid").optional("repository").optional("url")
+            .optional("license").optional("publisher").optional("abstract").optional("keywords").optional("note"));
+        // biblatex's: the inventors are the authors, the owners the holder
+        r.register("patent", TypeSchema::new()
+            .require("author").require("title").require("number").require_any(&["year", "date"])
+            .optional("holder").optional("type").optional("location").optional("month").optional("doi")
+            .optional("url").optional("note"));
+        // biblatex's legal types, see `legal`
+        r.register("jurisdiction", TypeSchema::new()
+            .require("title").require("court").require_any(&["year", "date"])
+            .optional("docket").optional("journaltitle").optional("journal").optional("volume").optional("pages")
+            .optional("location").optional("shorttitle").optional("url").optional("note"));
+        r.register("legislation", TypeSchema::new()
+            .require("title").require_any(&["year", "date"])
+            .optional("number").optional("location").optional("journaltitle").optional("volume").optional("pages")
+            .optional("shorttitle").optional("url").optional("note"));
+        r.register("legal", TypeSchema::new()
+            .require("title").require_any(&["year", "date"])
+            .optional("number").optional("location").optional("shorttitle").optional("url").optional("note"));
+        // audiovisual types, see `media`; contributors are often editors with a role
+        for name in ["audio", "video", "movie"] {
+            r.register(name, TypeSchema::new()
+                .require("title").require_any(&["year", "date"])
+                .optional("author").optional("director").optional("composer").optional("editor")
+                .optional("editortype").optional("editora").optional("editoratype").optional("editorb")
+                .optional("editorbtype").optional("publisher").optional("runtime").optional("medium")
+                .optional("doi").optional("url").optional("note"));
+        }
+        r.register("performance", TypeSchema::new()
+            .require("title").require_any(&["year", "date"])
+            .optional("author").optional("director").optional("composer").optional("editor")
+            .optional("editortype").optional("venue").optional("location").optional("runtime")
+            .optional("url").optional("note"));
+        r
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_parse_canonical() {
+        assert_eq!(BibType::parse("ARTICLE"), BibType::Article);
+        assert_eq!(BibType::parse("TechReport"), BibType::Report);
+        assert_eq!(BibType::parse("conference"), BibType::InProceedings);
+        assert_eq!(BibType::parse("Video"), BibType::Other(String::from("video")));
+        assert_eq!(BibType::Report.name(), "techreport");
+    }
+
+    #[test]
+    fn test_register_custom() {
+        let mut registry = TypeRegistry::default();
+        assert!(!registry.is_known("talk"));
+
+        let t = registry.register("Talk", TypeSchema::new()
+            .require("author").require("title").require("eventtitle"));
+        assert_eq!(t, BibType::Other(String::from("talk")));
+        assert!(registry.is_known("TALK"));
+
+        let schema = registry.schema(&registry.resolve("talk")).unwrap();
+        assert_eq!(schema.required().len(), 3);
+        assert!(schema.mentions("EventTitle"));
+    }
+
+    #[test]
+    fn test_builtin_alternatives() {
+        let registry = TypeRegistry::default();
+        let book = registry.schema(&BibType::Book).unwrap();
+        let names = book.required()[0].fields();
+        assert_eq!(names, &[String::from("author"), String::from("editor")]);
+        assert!(book.required()[0].is_satisfied_by(|f| f == "editor"));
+    }
+}
@@ -0,0 +1,203 @@
+/*!
+
+Typed field values.
+
+`Entry::get_parsed` reads a field as any type implementing `FieldValue`:
+
+```text
+let year: Option<Year> = entry.get_parsed("year")?;
+let pages: Option<Pages> = entry.get_parsed("pages")?;
+```
+
+A missing field is `Ok(None)`; a value that does not convert is a
+`FieldError` naming the entry, the field, the value and what is wrong with
+it. TeX grouping braces around the value are ignored.
+
+*/
+
+use std::fmt;
+use crate::bibtex::data::Entry;
+use crate::bibtex::months::parse_month;
+use crate::bibtex::names::{parse_names, Name};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldError {
+    pub key: String,
+    pub field: String,
+    pub value: String,
+    pub message: String,
+}
+
+impl fmt::Display for FieldError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {} `{}`: {}", self.key, self.field, self.value, self.message)
+    }
+}
+
+impl std::error::Error for FieldError {}
+
+pub trait FieldValue: Sized {
+    /** Convert a field value, explaining the problem if it does not fit. */
+    fn parse_field(value: &str) -> Result<Self, String>;
+}
+
+impl Entry {
+    pub fn get_parsed<T: FieldValue>(&self, field: &str) -> Result<Option<T>, FieldError> {
+        let Some(raw) = self.get(field) else { return Ok(None) };
+        let value = raw.trim().trim_start_matches('{').trim_end_matches('}').trim();
+        T::parse_field(value).map(Some).map_err(|message| FieldError {
+            key: String::from(self.key()),
+            field: field.to_lowercase(),
+            value: String::from(raw),
+            message,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Year(pub i32);
+
+impl FieldValue for Year {
+    fn parse_field(value: &str) -> Result<Year, String> {
+        if value.is_empty() || !value.chars().all(|c| c.is_ascii_digit()) {
+            return Err(String::from("expected a year such as 1984"));
+        }
+        value.parse().map(Year).map_err(|_| String::from("year out of range"))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pages {
+    pub first: String,
+    /** End of the range; `None` for a single page or article number. */
+    pub last: Option<String>,
+}
+
+impl FieldValue for Pages {
+    fn parse_field(value: &str) -> Result<Pages, String> {
+        let parts: Vec<&str> = value.split(['-', '–', '—']).map(str::trim).filter(|p| !p.is_empty()).collect();
+        let dashes = value.matches(['-', '–', '—']).count();
+        match parts.as_slice() {
+            [] => Err(String::from("no page number")),
+            [page] if dashes == 0 => Ok(Pages { first: String::from(*page), last: None }),
+            [first, last] => Ok(Pages { first: String::from(*first), last: Some(String::from(*last)) }),
+            [_] => Err(String::from("page range with only one end")),
+            _ => Err(String::from("more than one page range")),
+        }
+    }
+}
+
+impl fmt::Display for Pages {
+    /** In BibTeX form, with `--` between the ends of a range. */
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.last {
+            Some(last) => write!(f, "{}--{}", self.first, last),
+            None => f.write_str(&self.first),
+        }
+    }
+}
+
+/** A DOI without resolver prefix, lower-cased as DOIs are case-insensitive. */
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Doi(pub String);
+
+impl FieldValue for Doi {
+    fn parse_field(value: &str) -> Result<Doi, String> {
+        let lower = value.to_lowercase();
+        let bare = ["https://doi.org/", "http://doi.org/", "https://dx.doi.org/", "http://dx.doi.org/", "doi:"].iter()
+            .find_map(|p| lower.strip_prefix(p))
+            .unwrap_or(&lower)
+            .trim();
+        match bare.split_once('/') {
+            Some((prefix, suffix)) if prefix.starts_with("10.") && !suffix.is_empty() && !bare.contains(char::is_whitespace) =>
+                Ok(Doi(String::from(bare))),
+            _ => Err(String::from("expected a DOI such as 10.1000/xyz")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Month(pub u8);
+
+impl FieldValue for Month {
+    /** English names, abbreviations and numbers; see `months`. */
+    fn parse_field(value: &str) -> Result<Month, String> {
+        parse_month(value, &[]).map(Month).ok_or_else(|| String::from("expected a month name or number"))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NameList(pub Vec<Name>);
+
+impl FieldValue for NameList {
+    fn parse_field(value: &str) -> Result<NameList, String> {
+        let names = parse_names(value);
+        if names.is_empty() {
+            return Err(String::from("no names"));
+        }
+        if let Some(i) = names.iter().position(|n| n.last.is_empty()) {
+            return Err(format!("name {} is empty", i + 1));
+        }
+        Ok(NameList(names))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Url(pub String);
+
+impl FieldValue for Url {
+    fn parse_field(value: &str) -> Result<Url, String> {
+        let Some((scheme, rest)) = value.split_once("://") else {
+            return Err(String::from("expected an absolute URL such as https://example.org/"));
+        };
+        if !["http", "https", "ftp"].contains(&scheme.to_lowercase().as_str()) {
+            return Err(format!("unsupported URL scheme `{}`", scheme));
+        }
+        if rest.split('/').next().map(str::is_empty).unwrap_or(true) {
+            return Err(String::from("URL without host"));
+        }
+        if value.contains(char::is_whitespace) {
+            return Err(String::from("URL contains spaces"));
+        }
+        Ok(Url(String::from(value)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::bibtex::data::BibType;
+
+    #[test]
+    fn test_get_parsed() {
+        let mut e = Entry::new(BibType::Article, "Knuth-LP");
+        e.set("year", "{1984}");
+        e.set("pages", "97--111");
+        e.set("doi", "https://doi.org/10.1093/COMJNL/27.2.97");
+        e.set("month", "May");
+        e.set("author", "Knuth, Donald E.");
+        e.set("url", "https://example.org/lp");
+        assert_eq!(e.get_parsed::<Year>("year"), Ok(Some(Year(1984))));
+        assert_eq!(e.get_parsed::<Pages>("pages").unwrap().unwrap().to_string(), "97--111");
+        assert_eq!(e.get_parsed::<Doi>("doi").unwrap(), Some(Doi(String::from("10.1093/comjnl/27.2.97"))));
+        assert_eq!(e.get_parsed::<Month>("month"), Ok(Some(Month(5))));
+        assert_eq!(e.get_parsed::<NameList>("author").unwrap().unwrap().0[0].last, "Knuth");
+        assert!(e.get_parsed::<Url>("url").unwrap().is_some());
+        assert_eq!(e.get_parsed::<Year>("volume"), Ok(None));
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut e = Entry::new(BibType::Article, "a");
+        e.set("year", "forthcoming");
+        e.set("pages", "12-");
+        e.set("url", "www.example.org");
+        let err = e.get_parsed::<Year>("year").unwrap_err();
+        assert_eq!(err.to_string(), "a: year `forthcoming`: expected a year such as 1984");
+        assert_eq!(e.get_parsed::<Pages>("pages").unwrap_err().message, "page range with only one end");
+        assert!(e.get_parsed::<Url>("url").is_err());
+        assert!(Doi::parse_field("doi:11.1/x").is_err());
+        assert_eq!(Pages::parse_field("e1001"), Ok(Pages { first: String::from("e1001"), last: None }));
+    }
+}
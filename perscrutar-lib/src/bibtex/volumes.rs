@@ -0,0 +1,197 @@
+/*!
+
+Series and multi-volume works.
+
+Books and the contributions to them can be placed in a larger work in two
+ways, and the fields mean different things in each:
+
+- as part of a **series** (`series`, with the book's `number` in it, or
+  a `volume` of it);
+- as one volume of a **multi-volume work** in biblatex's sense: `maintitle`
+  and `mainsubtitle` name the whole work, `volume` the part at hand and
+  `volumes` the total number of volumes.
+
+`Volume` collects these fields, `check_entry` reports combinations styles
+cannot render sensibly, and `Volume::describe` gives the text the
+renderers print, e.g. `The Art of Computer Programming, vol. 1 of 4` or
+`LNCS 1234`. Journal articles use `volume` and `number` for the issue and
+are not covered.
+
+*/
+
+use crate::bibtex::data::Entry;
+use crate::lint::{Diagnostic, Severity};
+
+/** Entry types whose `volume` and `number` locate an issue, not a book. */
+const ISSUE_TYPES: [&str; 5] = ["article", "periodical", "report", "techreport", "patent"];
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Volume {
+    pub series: Option<String>,
+    pub volume: Option<String>,
+    pub number: Option<String>,
+    /** Total number of volumes of the multi-volume work. */
+    pub volumes: Option<String>,
+    pub maintitle: Option<String>,
+    pub mainsubtitle: Option<String>,
+}
+
+fn number(s: &Option<String>) -> Option<u32> {
+    s.as_deref().and_then(|v| v.trim().parse().ok())
+}
+
+impl Volume {
+    pub fn is_empty(&self) -> bool {
+        *self == Volume::default()
+    }
+
+    pub fn is_multivolume(&self) -> bool {
+        self.maintitle.is_some()
+    }
+
+    /**
+    Rule name, severity and message for every inconsistent combination.
+    */
+    pub fn problems(&self) -> Vec<(&'static str, Severity, String)> {
+        let mut out = Vec::new();
+        if self.mainsubtitle.is_some() && self.maintitle.is_none() {
+            out.push(("mainsubtitle-alone", Severity::Warning, String::from("mainsubtitle without maintitle")));
+        }
+        if self.is_multivolume() && self.volume.is_none() {
+            out.push(("volume-missing", Severity::Warning,
+                String::from("maintitle names a multi-volume work but volume does not say which one")));
+        }
+        if self.number.is_some() && self.series.is_none() {
+            out.push(("number-without-series", Severity::Warning, String::from("number is only meaningful within a series")));
+        }
+        if self.series.is_some() && self.volume.is_some() && self.number.is_some() && !self.is_multivolume() {
+            out.push(("volume-and-number", Severity::Warning,
+                String::from("a book in a series has a volume or a number, not both")));
+        }
+        if let (Some(volume), Some(volumes)) = (number(&self.volume), number(&self.volumes)) {
+            if volume == 0 || volume > volumes {
+                out.push(("volume-out-of-range", Severity::Error,
+                    format!("volume {} of a work in {} volumes", volume, volumes)));
+            }
+        }
+        out
+    }
+
+    /**
+    The placement in the larger work as renderers print it, or `None` if
+    there is nothing to say.
+    */
+    pub fn describe(&self) -> Option<String> {
+        let mut parts = Vec::new();
+        if let Some(main) = &self.maintitle {
+            let mut s = match &self.mainsubtitle {
+                Some(sub) => format!("{}: {}", main, sub),
+                None => main.clone(),
+            };
+            if let Some(v) = &self.volume {
+                s.push_str(&format!(", vol. {}", v));
+                if let Some(total) = &self.volumes {
+                    s.push_str(&format!(" of {}", total));
+                }
+            }
+            parts.push(s);
+        }
+        match (&self.series, &self.volume, &self.number) {
+            (Some(series), _, Some(n)) => parts.push(format!("{} {}", series, n)),
+            (Some(series), Some(v), None) if !self.is_multivolume() => parts.push(format!("vol. {} of {}", v, series)),
+            (Some(series), _, None) => parts.push(series.clone()),
+            (None, Some(v), _) if !self.is_multivolume() => parts.push(format!("vol. {}", v)),
+            _ => {}
+        }
+        if parts.is_empty() { None } else { Some(parts.join("; ")) }
+    }
+}
+
+impl Entry {
+    /**
+    The series and multi-volume fields, or `None` for journal articles and
+    other types where `volume` and `number` locate an issue.
+    */
+    pub fn volume_info(&self) -> Option<Volume> {
+        if ISSUE_TYPES.contains(&self.entry_type().name()) {
+            return None;
+        }
+        let get = |f: &str| self.get(f).map(str::trim).filter(|v| !v.is_empty()).map(String::from);
+        Some(Volume {
+            series: get("series"),
+            volume: get("volume"),
+            number: get("number"),
+            volumes: get("volumes"),
+            maintitle: get("maintitle"),
+            mainsubtitle: get("mainsubtitle"),
+        })
+    }
+
+    /**
+    Write `info` back, removing the fields it leaves unset.
+    */
+    pub fn set_volume_info(&mut self, info: &Volume) {
+        let fields = [
+            ("series", &info.series), ("volume", &info.volume), ("number", &info.number),
+            ("volumes", &info.volumes), ("maintitle", &info.maintitle), ("mainsubtitle", &info.mainsubtitle),
+        ];
+        for (name, value) in fields {
+            match value {
+                Some(v) => { self.set(name, v); }
+                None => { self.remove(name); }
+            }
+        }
+    }
+}
+
+pub fn check_entry(entry: &Entry) -> Vec<Diagnostic> {
+    entry.volume_info().map(|info| info.problems()).unwrap_or_default().into_iter()
+        .map(|(rule, severity, message)| Diagnostic::new(entry.key(), rule, severity, &message))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::bibtex::data::BibType;
+
+    #[test]
+    fn test_describe() {
+        let mut taocp = Entry::new(BibType::Book, "Knuth-TAOCP1");
+        taocp.set("maintitle", "The Art of Computer Programming");
+        taocp.set("volume", "1");
+        taocp.set("volumes", "4");
+        assert_eq!(taocp.volume_info().unwrap().describe().as_deref(), Some("The Art of Computer Programming, vol. 1 of 4"));
+        assert!(check_entry(&taocp).is_empty());
+
+        let mut lncs = Entry::new(BibType::InProceedings, "p");
+        lncs.set("series", "LNCS");
+        lncs.set("volume", "1234");
+        assert_eq!(lncs.volume_info().unwrap().describe().as_deref(), Some("vol. 1234 of LNCS"));
+
+        let mut article = Entry::new(BibType::Article, "a");
+        article.set("number", "2");
+        assert!(article.volume_info().is_none());
+        assert!(check_entry(&article).is_empty());
+    }
+
+    #[test]
+    fn test_check() {
+        let mut e = Entry::new(BibType::Book, "b");
+        e.set("mainsubtitle", "Fundamental Algorithms");
+        e.set("number", "3");
+        let rules: Vec<&str> = check_entry(&e).iter().map(|d| d.rule).collect();
+        assert_eq!(rules, vec!["mainsubtitle-alone", "number-without-series"]);
+
+        let mut info = e.volume_info().unwrap();
+        info.maintitle = Some(String::from("TAOCP"));
+        info.volume = Some(String::from("5"));
+        info.volumes = Some(String::from("4"));
+        info.number = None;
+        e.set_volume_info(&info);
+        assert!(!e.has("number"));
+        let rules: Vec<&str> = check_entry(&e).iter().map(|d| d.rule).collect();
+        assert_eq!(rules, vec!["volume-out-of-range"]);
+    }
+}
@@ -0,0 +1,528 @@
+/*!
+
+Writing entries back as BibTeX.
+
+Every field is written on its own line, by default with brace delimiters
+and in the entry's field order:
+
+```text
+@article{Cox-CFT,
+  author = {Cox, David},
+  title = {Galois theory}
+}
+```
+
+A value that refers to a macro with no `@string` definition, such as
+`month = jan`, is written bare, as it was read (`Entry::is_macro`).
+
+`WriteOptions` changes the indentation, delimits values with quotes
+instead, puts chosen fields first and lines up the `=` signs of an entry:
+
+```text
+@article{Cox-CFT,
+    title  = "Galois theory",
+    author = "Cox, David"
+}
+```
+
+With `WriteOptions::width` set, long values are wrapped onto continuation
+lines aligned with the start of the value. A line break is only put where
+TeX would see a space anyway and never inside a TeX command, a math
+segment (`$...$`, `\(...\)`, `\[...\]`), a URL or other word, or a braced
+group, so wrapping changes neither the parsed value beyond its whitespace
+nor the typeset result. A single word longer than the limit is kept whole,
+and `data::VERBATIM_FIELDS` are never wrapped.
+
+Styles that print titles in sentence case lower everything not in braces,
+so `GaN` comes out as `gan`. With `WriteOptions::protect_capitals`, words
+in the `TITLE_FIELDS` with a capital after their first letter, such as
+`GaN`, `PostScript` or `DNA`, are written braced (`{GaN}`); words already
+in braces, TeX commands and math are left as they are.
+
+`replace_entry` rewrites a single entry in place, leaving the rest of the
+file, comments and layout included, as it was.
+
+`write_bibliography` writes a bibliography's `@preamble`s and `@comment`s
+with its entries, and `write_strings` the `@string` definitions read with
+them, so that a .bib file read whole can be written whole.
+
+Output depends only on the entries and options: nothing is written in hash
+map order, so the same input always gives byte-identical files, as
+reproducible paper builds need.
+
+*/
+
+use crate::bibtex::bibliography::Bibliography;
+use crate::bibtex::data::{is_verbatim, Entry};
+use crate::bibtex::error::Span;
+use crate::bibtex::parser::Macros;
+use crate::bibtex::policy::FieldPolicy;
+
+/** The fields `protect_capitals` applies to. */
+pub const TITLE_FIELDS: [&str; 6] = ["title", "subtitle", "titleaddon", "booktitle", "maintitle", "shorttitle"];
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Delimiter {
+    #[default]
+    Braces,
+    /**
+    Double quotes. A value with a `"` outside braces is still braced, as
+    the quote would end it.
+    */
+    Quotes,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WriteOptions {
+    /** Spaces before each field. */
+    pub indent: usize,
+    /** Column limit for wrapping values; `None` never wraps. */
+    pub width: Option<usize>,
+    pub delimiter: Delimiter,
+    /**
+    Fields to write first, in this order; the others follow in the
+    entry's order.
+    */
+    pub field_order: Vec<String>,
+    /** Pad field names so that the `=` signs of each entry line up. */
+    pub align: bool,
+    /** Brace words with inner capitals in the `TITLE_FIELDS`; see `protect_capitals`. */
+    pub protect_capitals: bool,
+    /** Fields left out as private; by default every field is written. */
+    pub policy: FieldPolicy,
+}
+
+impl Default for WriteOptions {
+    fn default() -> Self {
+        WriteOptions { indent: 2, width: None, delimiter: Delimiter::Braces, field_order: vec![], align: false, protect_capitals: false, policy: FieldPolicy::new() }
+    }
+}
+
+/**
+Split `value` into the pieces between which a line break is allowed:
+whitespace outside braces, math and after anything but a backslash.
+*/
+pub fn break_points(value: &str) -> Vec<&str> {
+    let mut out = Vec::new();
+    let mut depth = 0usize;
+    let mut math: Option<&str> = None;
+    let mut start = None;
+    let mut chars = value.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        let breakable = c.is_whitespace() && depth == 0 && math.is_none();
+        if breakable {
+            if let Some(s) = start.take() {
+                out.push(&value[s..i]);
+            }
+            continue;
+        }
+        start.get_or_insert(i);
+        match c {
+            '\\' => {
+                let next = chars.peek().map(|(_, n)| *n);
+                match (next, math) {
+                    (Some('('), None) => math = Some("\\)"),
+                    (Some('['), None) => math = Some("\\]"),
+                    (Some(')'), Some("\\)")) | (Some(']'), Some("\\]")) => math = None,
+                    _ => {}
+                }
+                // the escaped character, including `\ ` and `\{`, is never a break
+                chars.next();
+            }
+            '$' if math.is_none() => {
+                if value[i + 1..].starts_with('$') {
+                    chars.next();
+                    math = Some("$$");
+                } else {
+                    math = Some("$");
+                }
+            }
+            '$' if math == Some("$$") && value[i + 1..].starts_with('$') => {
+                chars.next();
+                math = None;
+            }
+            '$' if math == Some("$") => math = None,
+            '{' => depth += 1,
+            '}' => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+    if let Some(s) = start {
+        out.push(&value[s..]);
+    }
+    out
+}
+
+/**
+Lay out `value` in lines of at most `width` columns, the first starting at
+column `first`, continuation lines at column `indent`.
+*/
+pub fn wrap(value: &str, first: usize, indent: usize, width: usize) -> String {
+    let mut out = String::new();
+    let mut column = first;
+    for (n, word) in break_points(value).into_iter().enumerate() {
+        let len = word.chars().count();
+        if n > 0 {
+            if column + 1 + len > width {
+                out.push('\n');
+                out.push_str(&" ".repeat(indent));
+                column = indent;
+            } else {
+                out.push(' ');
+                column += 1;
+            }
+        }
+        out.push_str(word);
+        column += len;
+    }
+    out
+}
+
+/**
+`value` with its words that have a capital after the first letter braced,
+unless they are in braces already, in math or part of a TeX command.
+*/
+pub fn protect_capitals(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut depth = 0usize;
+    let mut math = false;
+    let mut chars = value.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' => {
+                out.push(c);
+                // a command name, or the one character escaped
+                match chars.next_if(|(_, n)| n.is_ascii_alphabetic()) {
+                    Some((_, n)) => {
+                        out.push(n);
+                        while let Some((_, n)) = chars.next_if(|(_, n)| n.is_ascii_alphabetic()) {
+                            out.push(n);
+                        }
+                    }
+                    None => out.extend(chars.next().map(|(_, n)| n)),
+                }
+            }
+            '{' => {
+                depth += 1;
+                out.push(c);
+            }
+            '}' => {
+                depth = depth.saturating_sub(1);
+                out.push(c);
+            }
+            '$' => {
+                math = !math;
+                out.push(c);
+            }
+            c if c.is_alphanumeric() && depth == 0 && !math => {
+                let mut end = i + c.len_utf8();
+                while let Some((j, n)) = chars.next_if(|(_, n)| n.is_alphanumeric()) {
+                    end = j + n.len_utf8();
+                }
+                let word = &value[i..end];
+                if word.chars().skip(1).any(char::is_uppercase) {
+                    out.push('{');
+                    out.push_str(word);
+                    out.push('}');
+                } else {
+                    out.push_str(word);
+                }
+            }
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/** Whether `value` has a `"` outside braces, which quotes cannot delimit. */
+pub fn has_bare_quote(value: &str) -> bool {
+    let mut depth = 0usize;
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                chars.next();
+            }
+            '{' => depth += 1,
+            '}' => depth = depth.saturating_sub(1),
+            '"' if depth == 0 => return true,
+            _ => {}
+        }
+    }
+    false
+}
+
+pub fn write_entry(entry: &Entry, options: &WriteOptions) -> String {
+    let mut names = entry.field_names();
+    names.retain(|name| !options.policy.is_private(name));
+    let rank = |k: &str| options.field_order.iter().position(|o| o.eq_ignore_ascii_case(k)).unwrap_or(options.field_order.len());
+    names.sort_by_key(|k| rank(k));
+    let name_width = if options.align { names.iter().map(|n| n.chars().count()).max().unwrap_or(0) } else { 0 };
+    let mut out = format!("@{}{{{}", entry.type_name(), entry.key());
+    for name in names {
+        out.push_str(",\n");
+        if entry.is_macro(name) {
+            let value = entry.get(name).unwrap_or_default();
+            out.push_str(&format!("{}{:width$} = {}", " ".repeat(options.indent), name, value, width = name_width));
+            continue;
+        }
+        let mut value = String::from(entry.get(name).unwrap_or_default());
+        if options.protect_capitals && TITLE_FIELDS.iter().any(|t| t.eq_ignore_ascii_case(name)) {
+            value = protect_capitals(&value);
+        }
+        let value = value.as_str();
+        let (open, close) = match options.delimiter {
+            Delimiter::Quotes if !has_bare_quote(value) => ('"', '"'),
+            _ => ('{', '}'),
+        };
+        let prefix = format!("{}{:width$} = {}", " ".repeat(options.indent), name, open, width = name_width);
+        let column = prefix.chars().count();
+        out.push_str(&prefix);
+        match options.width {
+            // the closing `},` counts towards the limit
+            Some(width) if !is_verbatim(name) => out.push_str(&wrap(value, column, column, width.saturating_sub(2))),
+            _ => out.push_str(value),
+        }
+        out.push(close);
+    }
+    out.push_str("\n}\n");
+    out
+}
+
+/**
+All entries, separated by blank lines.
+*/
+pub fn write_entries(entries: &[Entry], options: &WriteOptions) -> String {
+    entries.iter().map(|e| write_entry(e, options)).collect::<Vec<String>>().join("\n")
+}
+
+/**
+`input` with the entry at `span` (as `parse_with_spans` gives it) written
+anew from `entry`.
+*/
+pub fn replace_entry(input: &str, span: &Span, entry: &Entry, options: &WriteOptions) -> String {
+    let text = write_entry(entry, options);
+    format!("{}{}{}", &input[..span.start], text.trim_end_matches('\n'), &input[span.end..])
+}
+
+/**
+An `@string` definition for each of `macros`, one per line, followed by a
+blank line if there are any. Entries are parsed with their macros
+expanded, so this is for keeping the definitions for other files and
+styles, not for the entries written with them.
+*/
+pub fn write_strings(macros: &Macros, options: &WriteOptions) -> String {
+    let mut out = String::new();
+    for (name, value) in macros.iter() {
+        match options.delimiter {
+            Delimiter::Quotes if !has_bare_quote(value) => out.push_str(&format!("@string{{{} = \"{}\"}}\n", name, value)),
+            _ => out.push_str(&format!("@string{{{} = {{{}}}}}\n", name, value)),
+        }
+    }
+    if !out.is_empty() {
+        out.push('\n');
+    }
+    out
+}
+
+/**
+The preambles, the comments and then the entries of a bibliography.
+Comments are not tied to entries, so they all come first.
+*/
+pub fn write_bibliography(bibliography: &Bibliography, options: &WriteOptions) -> String {
+    let mut out = String::new();
+    for preamble in bibliography.preambles() {
+        match options.delimiter {
+            Delimiter::Quotes if !has_bare_quote(preamble) => out.push_str(&format!("@preamble{{\"{}\"}}\n\n", preamble)),
+            _ => out.push_str(&format!("@preamble{{{{{}}}}}\n\n", preamble)),
+        }
+    }
+    for comment in bibliography.comments() {
+        out.push_str(&format!("@comment{{{}}}\n\n", comment));
+    }
+    out.push_str(&write_entries(bibliography.entries(), options));
+    out
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::bibtex::data::BibType;
+    use crate::bibtex::parser::{parse_entries, parse_with, ParseOptions};
+
+    const NASTY: [&str; 4] = [
+        "On the $O(n \\log n)$ bound for \\emph{very long} titles with {Protected Words That Go On and On}",
+        "See https://example.org/a/very/long/path/that/cannot/be/broken/anywhere/at/all for details",
+        "Display math $$\\sum_{i = 1}^{n} i = n(n + 1) / 2$$ and \\(a + b\\) and \\[x = y\\] inline",
+        "Escaped\\ space and \\{ literal braces \\} and {nested {groups of {words}} here}",
+    ];
+
+    fn unwrapped(s: &str) -> String {
+        s.split_whitespace().collect::<Vec<&str>>().join(" ")
+    }
+
+    #[test]
+    fn test_break_points() {
+        assert_eq!(break_points("a $x y$ {b c} \\emph{d e}\\ f"), vec!["a", "$x y$", "{b c}", "\\emph{d e}\\ f"]);
+        for value in NASTY {
+            let wrapped = wrap(value, 10, 10, 30);
+            assert_eq!(unwrapped(&wrapped), unwrapped(value));
+            assert_eq!(break_points(&wrapped), break_points(value), "{}", wrapped);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "render")]
+    fn test_deterministic() {
+        use crate::events::diff;
+        use crate::publist::{PubFormat, Template};
+
+        let fields = [("title", "T"), ("author", "A, B"), ("year", "2001"), ("doi", "10.1/x"), ("pages", "1--2"),
+                      ("journal", "J"), ("volume", "3"), ("note", "N"), ("url", "https://x.org")];
+        let build = || {
+            let mut e = Entry::new(BibType::Article, "k");
+            for (f, v) in fields {
+                e.set(f, v);
+            }
+            e
+        };
+        let export = |e: &Entry| (
+            write_entry(e, &WriteOptions { width: Some(30), ..WriteOptions::default() }),
+            diff(&[], std::slice::from_ref(e))[0].to_json().to_string(),
+            Template::new("{authors} {title} {venue} {series}").render(e, PubFormat::Html),
+            e.fields().map(|(f, _)| f).collect::<Vec<&str>>().join(","),
+        );
+        let first = export(&build());
+        assert!(first.0.starts_with("@article{k,\n  title = {T},\n  author = {A, B},"));
+        for _ in 0..20 {
+            assert_eq!(export(&build()), first);
+        }
+    }
+
+    #[test]
+    fn test_write_entry() {
+        let mut e = Entry::new(BibType::Article, "Cox-CFT");
+        e.set("title", "Galois theory and $x^2 + y^2$ with a title long enough to need wrapping somewhere");
+        e.set("url", "https://example.org/a/long/path");
+        e.set("file", "/home/me/My Papers/Galois theory and a long title too.pdf");
+        let options = WriteOptions { width: Some(40), ..WriteOptions::default() };
+        let text = write_entry(&e, &options);
+        assert!(text.starts_with("@article{Cox-CFT,\n  title = {Galois theory and\n           $x^2 + y^2$ with a title\n"), "{}", text);
+        // only a single unbreakable value, or a verbatim one, may overflow
+        assert!(text.lines().all(|l| {
+            let value = l.split_once(" = {").map(|(_, v)| v).unwrap_or(l);
+            l.chars().count() <= 40 || break_points(value).len() == 1 || l.starts_with("  file = ")
+        }), "{}", text);
+
+        let parsed = parse_entries(&text).unwrap();
+        assert_eq!(unwrapped(parsed[0].get("title").unwrap()), e.get("title").unwrap());
+        assert_eq!(parsed[0].get("url"), e.get("url"));
+        assert_eq!(parsed[0].get("file"), e.get("file"));
+        assert_eq!(write_entries(&[e.clone(), e], &WriteOptions::default()).matches("\n\n@article").count(), 1);
+
+        let mut aliases = parse_entries("@Report{r, title = {R}}\n@conference{c, title = {C}}\n").unwrap();
+        let written = write_entries(&aliases, &WriteOptions::default());
+        assert!(written.starts_with("@report{r,") && written.contains("@conference{c,"), "{}", written);
+        aliases[0].set_entry_type(BibType::Report);
+        assert!(write_entry(&aliases[0], &WriteOptions::default()).starts_with("@techreport{r,"));
+    }
+
+    #[test]
+    fn test_replace_entry() {
+        use crate::bibtex::parser::parse_with_spans;
+
+        let input = "% mine\n@misc{a, title = {A}}\n\n@book{b, title = {B}} % keep\n";
+        let (entries, _) = parse_with_spans(input, &mut Macros::new(), ParseOptions::standard()).unwrap();
+        let (mut entry, span) = entries[0].clone();
+        entry.set("year", "2001");
+        let output = replace_entry(input, &span, &entry, &WriteOptions::default());
+        assert_eq!(output, "% mine\n@misc{a,\n  title = {A},\n  year = {2001}\n}\n\n@book{b, title = {B}} % keep\n");
+    }
+
+    #[test]
+    fn test_options() {
+
+        let mut e = Entry::new(BibType::Article, "Cox-CFT");
+        e.set("author", "Cox, David");
+        e.set("title", "The \"{S}olvable\" {\"q\"} case");
+        e.set("year", "2013");
+        let options = WriteOptions {
+            indent: 4,
+            delimiter: Delimiter::Quotes,
+            field_order: vec![String::from("year"), String::from("Title")],
+            align: true,
+            ..WriteOptions::default()
+        };
+        let text = write_entry(&e, &options);
+        assert_eq!(text, "@article{Cox-CFT,\n    year   = \"2013\",\n    title  = {The \"{S}olvable\" {\"q\"} case},\n    author = \"Cox, David\"\n}\n");
+        let parsed = parse_with(&text, &mut Macros::new(), ParseOptions::standard()).unwrap();
+        assert_eq!(parsed[0].get("title"), e.get("title"));
+        assert!(!has_bare_quote("{\"q\"} and \\\"o"));
+        let bib = Bibliography::from_entries(vec![e.clone()]);
+        assert_eq!(write_bibliography(&bib, &WriteOptions::default()), write_entry(&e, &WriteOptions::default()));
+    }
+
+    #[test]
+    fn test_macros() {
+        let input = "@string{acm = {ACM Press}}\n@inproceedings{k,\n  publisher = acm,\n  month = jan,\n  note = {See } # Dec,\n  series = lncs\n}\n";
+        let mut entries = parse_entries(input).unwrap();
+        assert!(entries[0].is_macro("month") && entries[0].is_macro("series") && !entries[0].is_macro("publisher"));
+        let text = write_entry(&entries[0], &WriteOptions { align: true, ..WriteOptions::default() });
+        assert_eq!(text, "@inproceedings{k,\n  publisher = {ACM Press},\n  month     = jan,\n  note      = {See Dec},\n  series    = lncs\n}\n");
+        let parsed = parse_entries(&text).unwrap();
+        assert!(parsed[0].is_identical(&entries[0]), "{}", text);
+
+        entries[0].rename("series", "journal");
+        assert!(write_entry(&entries[0], &WriteOptions::default()).contains("  journal = lncs\n"));
+        entries[0].set("month", "1");
+        assert!(!entries[0].is_macro("month"));
+        assert!(write_entry(&entries[0], &WriteOptions::default()).contains("  month = {1},\n"));
+        assert!(!parse_entries("@misc{k, month = {jan}}").unwrap()[0].is_macro("month"));
+
+        let mut macros = Macros::new();
+        parse_with("@string{ACM = \"ACM Press\"}\n@string{q = {\"Q\"}}\n", &mut macros, ParseOptions::standard()).unwrap();
+        let strings = write_strings(&macros, &WriteOptions { delimiter: Delimiter::Quotes, ..WriteOptions::default() });
+        assert_eq!(strings, "@string{acm = \"ACM Press\"}\n@string{q = {\"Q\"}}\n\n");
+        let mut reread = Macros::new();
+        parse_with(&strings, &mut reread, ParseOptions::standard()).unwrap();
+        assert_eq!(reread, macros);
+        assert_eq!(write_strings(&Macros::new(), &WriteOptions::default()), "");
+    }
+
+    #[test]
+    fn test_protect_capitals() {
+        assert_eq!(protect_capitals("GaN-based LEDs in {PostScript} and PostScript"), "{GaN}-based {LEDs} in {PostScript} and {PostScript}");
+        assert_eq!(protect_capitals("The \\TeXbook, $O(nN)$ and \\emph{McCarthy} \\'{E}cole A"), "The \\TeXbook, $O(nN)$ and \\emph{McCarthy} \\'{E}cole A");
+        assert_eq!(protect_capitals("Über ÜBER"), "Über {ÜBER}");
+
+        let mut e = Entry::new(BibType::Article, "k");
+        e.set("title", "DNA sequencing");
+        e.set("journal", "IEEE Transactions");
+        let options = WriteOptions { protect_capitals: true, ..WriteOptions::default() };
+        assert_eq!(write_entry(&e, &options), "@article{k,\n  title = {{DNA} sequencing},\n  journal = {IEEE Transactions}\n}\n");
+        assert!(write_entry(&e, &WriteOptions::default()).contains("{DNA sequencing}"));
+    }
+
+    #[test]
+    fn test_snapshots() {
+        let entries = parse_entries(r#"
+@book{Cox-CFT,
+    author = {David A. Cox},
+    title = {Primes of the form $x^2 + ny^2$: Fermat, Class Field Theory, and Complex Multiplication},
+    edition = {2nd ed.},
+    publisher = {John Wiley and Sons Inc},
+    year = {2013},
+    doi = {10.1002/9781118400722}
+}
+@article{Knuth-LP,
+    author = {Donald E. Knuth},
+    title = {Literate Programming},
+    journal = {The Computer Journal},
+    year = {1984}
+}
+"#).unwrap();
+        insta::assert_snapshot!("default", write_entries(&entries, &WriteOptions::default()));
+        insta::assert_snapshot!("wrapped", write_entries(&entries, &WriteOptions { indent: 4, width: Some(50), ..WriteOptions::default() }));
+    }
+}
@@ -0,0 +1,298 @@
+/*!
+
+Citations in LaTeX and Markdown documents.
+
+`scan` finds the citation keys used by a document together with where each
+one appears. LaTeX documents are searched for `\cite`-like commands
+(`\cite`, `\citep`, `\parencite`, `\nocite`, ... with optional `*` and
+bracketed arguments); comments are skipped. Markdown documents use Pandoc
+syntax, `@key` or `@{key}`, outside code spans and fenced blocks.
+`latex::scan` does the same for a LaTeX document split over several files.
+
+`usage` aggregates the citations of several documents into per-entry counts,
+which `orphans` compares against a library to find entries nobody cites.
+
+*/
+
+use std::collections::{BTreeSet, HashMap};
+use std::path::Path;
+use crate::bibtex::data::Entry;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DocumentKind {
+    Latex,
+    Markdown,
+}
+
+impl DocumentKind {
+    /**
+    Kind of a document by extension; anything not Markdown is scanned as LaTeX.
+    */
+    pub fn from_path(path: &str) -> DocumentKind {
+        let ext = Path::new(path).extension().and_then(|e| e.to_str()).unwrap_or("").to_lowercase();
+        match ext.as_str() {
+            "md" | "markdown" | "rmd" | "qmd" => DocumentKind::Markdown,
+            _ => DocumentKind::Latex,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Location {
+    pub file: String,
+    /** 1-based line. */
+    pub line: usize,
+    /** 1-based column, in characters. */
+    pub column: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Citation {
+    pub key: String,
+    pub location: Location,
+}
+
+/**
+Tracks line and column while walking a document by character.
+*/
+struct Cursor {
+    line: usize,
+    column: usize,
+}
+
+impl Cursor {
+    fn advance(&mut self, c: char) {
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+    }
+}
+
+fn locate(file: &str, text: &str, offset: usize) -> Location {
+    let before = &text[..offset];
+    let line = before.matches('\n').count() + 1;
+    let column = before.rsplit('\n').next().unwrap_or("").chars().count() + 1;
+    Location { file: String::from(file), line, column }
+}
+
+fn is_cite_command(name: &str) -> bool {
+    name.to_lowercase().contains("cite")
+}
+
+/**
+Citations made by `\cite`-like commands in a LaTeX document.
+*/
+pub fn scan_latex(file: &str, text: &str) -> Vec<Citation> {
+    let mut out = Vec::new();
+    let bytes = text.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' => {
+                i = text[i..].find('\n').map(|n| i + n).unwrap_or(bytes.len());
+            }
+            b'\\' => {
+                let start = i;
+                i += 1;
+                let name_len = text[i..].find(|c: char| !c.is_ascii_alphabetic()).unwrap_or(bytes.len() - i);
+                if name_len == 0 {
+                    // an escaped character such as `\%`
+                    i += 1;
+                    continue;
+                }
+                let name = &text[i..i + name_len];
+                i += name_len;
+                if !is_cite_command(name) {
+                    continue;
+                }
+                let mut j = i;
+                if text[j..].starts_with('*') {
+                    j += 1;
+                }
+                loop {
+                    let rest = text[j..].trim_start();
+                    j = bytes.len() - rest.len();
+                    if !rest.starts_with('[') {
+                        break;
+                    }
+                    match rest.find(']') {
+                        Some(end) => j += end + 1,
+                        None => break,
+                    }
+                }
+                if !text[j..].starts_with('{') {
+                    continue;
+                }
+                let Some(end) = text[j..].find('}') else { continue };
+                let location = locate(file, text, start);
+                for key in text[j + 1..j + end].split(',').map(|k| k.trim()) {
+                    if !key.is_empty() && key != "*" {
+                        out.push(Citation { key: String::from(key), location: location.clone() });
+                    }
+                }
+                i = j + end + 1;
+            }
+            _ => i += 1,
+        }
+    }
+    out
+}
+
+fn is_key_char(c: char) -> bool {
+    c.is_alphanumeric() || "_:.#$%&-+?<>~/".contains(c)
+}
+
+/**
+Citations in Pandoc Markdown syntax.
+*/
+pub fn scan_markdown(file: &str, text: &str) -> Vec<Citation> {
+    let mut out = Vec::new();
+    let mut cursor = Cursor { line: 1, column: 1 };
+    let mut in_fence = false;
+    for line in text.split_inclusive('\n') {
+        let fence = line.trim_start().starts_with("```") || line.trim_start().starts_with("~~~");
+        if fence {
+            in_fence = !in_fence;
+        }
+        if fence || in_fence {
+            line.chars().for_each(|c| cursor.advance(c));
+            continue;
+        }
+        let chars: Vec<char> = line.chars().collect();
+        let mut in_code = false;
+        let mut k = 0;
+        while k < chars.len() {
+            let c = chars[k];
+            if c == '`' {
+                in_code = !in_code;
+            }
+            let after_word = k > 0 && (chars[k - 1].is_alphanumeric() || chars[k - 1] == '@');
+            if c == '@' && !in_code && !after_word {
+                let (key, used) = if chars.get(k + 1) == Some(&'{') {
+                    let end = chars[k + 2..].iter().position(|&c| c == '}');
+                    match end {
+                        Some(end) => (chars[k + 2..k + 2 + end].iter().collect::<String>(), end + 3),
+                        None => (String::new(), 1),
+                    }
+                } else {
+                    let len = chars[k + 1..].iter().take_while(|&&c| is_key_char(c)).count();
+                    let key: String = chars[k + 1..k + 1 + len].iter().collect();
+                    // internal punctuation is allowed, trailing punctuation is not
+                    let key = key.trim_end_matches(|c: char| !c.is_alphanumeric() && c != '_').to_string();
+                    let used = key.chars().count() + 1;
+                    (key, used)
+                };
+                if key.chars().next().map(|c| c.is_alphanumeric() || c == '_').unwrap_or(false) {
+                    out.push(Citation {
+                        key,
+                        location: Location { file: String::from(file), line: cursor.line, column: cursor.column },
+                    });
+                }
+                for &c in chars[k..k + used].iter() {
+                    cursor.advance(c);
+                }
+                k += used;
+                continue;
+            }
+            cursor.advance(c);
+            k += 1;
+        }
+    }
+    out
+}
+
+pub fn scan(file: &str, text: &str) -> Vec<Citation> {
+    match DocumentKind::from_path(file) {
+        DocumentKind::Latex => scan_latex(file, text),
+        DocumentKind::Markdown => scan_markdown(file, text),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Usage {
+    pub key: String,
+    /** Number of times the key is cited across all documents. */
+    pub count: usize,
+    /** First citation, in the order the documents were given. */
+    pub first: Location,
+    /** Documents citing the key, sorted. */
+    pub documents: Vec<String>,
+}
+
+/**
+Aggregate citations into per-key usage, most cited first.
+*/
+pub fn usage(citations: &[Citation]) -> Vec<Usage> {
+    let mut by_key: HashMap<&str, (usize, &Location, BTreeSet<&str>)> = HashMap::new();
+    for c in citations {
+        let e = by_key.entry(c.key.as_str()).or_insert((0, &c.location, BTreeSet::new()));
+        e.0 += 1;
+        e.2.insert(c.location.file.as_str());
+    }
+    let mut out: Vec<Usage> = by_key.into_iter().map(|(key, (count, first, docs))| Usage {
+        key: String::from(key),
+        count,
+        first: first.clone(),
+        documents: docs.into_iter().map(String::from).collect(),
+    }).collect();
+    out.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.key.cmp(&b.key)));
+    out
+}
+
+/**
+Keys of library entries that no document cites, in library order.
+*/
+pub fn orphans<'a>(entries: &'a [Entry], usage: &[Usage]) -> Vec<&'a str> {
+    let cited: BTreeSet<&str> = usage.iter().map(|u| u.key.as_str()).collect();
+    entries.iter()
+        .filter(|e| !cited.contains(e.key()) && !e.ids().iter().any(|id| cited.contains(id)))
+        .map(|e| e.key())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::bibtex::data::BibType;
+
+    #[test]
+    fn test_scan_latex() {
+        let tex = "See \\cite{a, b} and \\parencite*[p.~3][]{c}.\n% \\cite{commented}\n50\\% \\citet {a}\\nocite{*}\\section{x}";
+        let keys: Vec<(String, usize, usize)> = scan_latex("p.tex", tex).into_iter()
+            .map(|c| (c.key, c.location.line, c.location.column)).collect();
+        assert_eq!(keys, vec![
+            (String::from("a"), 1, 5), (String::from("b"), 1, 5),
+            (String::from("c"), 1, 21), (String::from("a"), 3, 6),
+        ]);
+    }
+
+    #[test]
+    fn test_scan_markdown() {
+        let md = "As @knuth84 shows [see @cox:cft, p. 3; @{odd key}].\nMail a@b.org.\n`@code` and\n```\n@fenced\n```\nLast @end.";
+        let found: Vec<(String, usize, usize)> = scan("notes.md", md).into_iter()
+            .map(|c| (c.key, c.location.line, c.location.column)).collect();
+        assert_eq!(found, vec![
+            (String::from("knuth84"), 1, 4), (String::from("cox:cft"), 1, 24),
+            (String::from("odd key"), 1, 40), (String::from("end"), 7, 6),
+        ]);
+    }
+
+    #[test]
+    fn test_usage_and_orphans() {
+        let mut cites = scan("a.tex", "\\cite{x}\n\\cite{y,x}");
+        cites.extend(scan("b.md", "@y and @x"));
+        let u = usage(&cites);
+        assert_eq!(u[0].key, "x");
+        assert_eq!(u[0].count, 3);
+        assert_eq!(u[0].documents, vec!["a.tex", "b.md"]);
+        assert_eq!((u[1].first.file.as_str(), u[1].first.line), ("a.tex", 2));
+        let mut w = Entry::new(BibType::Misc, "w");
+        w.set("ids", "y");
+        let lib = vec![Entry::new(BibType::Misc, "x"), Entry::new(BibType::Misc, "z"), w];
+        assert_eq!(orphans(&lib, &u), vec!["z"]);
+    }
+}
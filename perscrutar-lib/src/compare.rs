@@ -0,0 +1,237 @@
+/*!
+
+Side-by-side comparison of two entries, typically two candidate duplicates.
+
+Fields are aligned by name and every row carries a marker in the gutter:
+
+- `' '` same value on both sides (ignoring whitespace differences)
+- `'!'` both sides have the field but the values differ
+- `'<'` only the left entry has the field
+- `'>'` only the right entry has the field
+
+With `CompareOptions::color` set, differing values are additionally
+highlighted with ANSI colours for terminal use.
+
+*/
+
+use crate::bibtex::data::Entry;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldDiff {
+    Same,
+    Different,
+    LeftOnly,
+    RightOnly,
+}
+
+impl FieldDiff {
+    pub fn marker(&self) -> char {
+        match self {
+            FieldDiff::Same => ' ',
+            FieldDiff::Different => '!',
+            FieldDiff::LeftOnly => '<',
+            FieldDiff::RightOnly => '>',
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldRow {
+    pub name: String,
+    pub left: Option<String>,
+    pub right: Option<String>,
+    pub diff: FieldDiff,
+}
+
+#[derive(Debug, Clone)]
+pub struct CompareOptions {
+    /** Width of each value column, in characters. */
+    pub width: usize,
+    /** Highlight differing values with ANSI escapes. */
+    pub color: bool,
+    /** Omit rows whose values are the same on both sides. */
+    pub only_differences: bool,
+}
+
+impl Default for CompareOptions {
+    fn default() -> Self {
+        CompareOptions { width: 40, color: false, only_differences: false }
+    }
+}
+
+fn normalize(value: &str) -> String {
+    value.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+/**
+Align the fields of both entries. The entry type comes first as a
+pseudo-field `@type`, followed by the fields of `left` in their order and
+then those only `right` has, in its order.
+*/
+pub fn compare(left: &Entry, right: &Entry) -> Vec<FieldRow> {
+    let mut names: Vec<&str> = left.field_names();
+    for n in right.field_names() {
+        if !names.contains(&n) {
+            names.push(n);
+        }
+    }
+
+    let mut rows = vec![row(
+        "@type",
+        Some(left.entry_type().name()),
+        Some(right.entry_type().name()),
+    )];
+    for name in names {
+        rows.push(row(name, left.get(name), right.get(name)));
+    }
+    rows
+}
+
+fn row(name: &str, left: Option<&str>, right: Option<&str>) -> FieldRow {
+    let diff = match (left, right) {
+        (Some(l), Some(r)) if normalize(l) == normalize(r) => FieldDiff::Same,
+        (Some(_), Some(_)) => FieldDiff::Different,
+        (Some(_), None) => FieldDiff::LeftOnly,
+        _ => FieldDiff::RightOnly,
+    };
+    FieldRow {
+        name: String::from(name),
+        left: left.map(normalize),
+        right: right.map(normalize),
+        diff,
+    }
+}
+
+/**
+Break `value` into lines of at most `width` characters, preferring to
+break at spaces.
+*/
+fn wrap(value: &str, width: usize) -> Vec<String> {
+    let width = width.max(1);
+    let mut lines = Vec::new();
+    let mut current = String::new();
+    for word in value.split(' ') {
+        let mut word = word.to_string();
+        while word.chars().count() > width {
+            if !current.is_empty() {
+                lines.push(std::mem::take(&mut current));
+            }
+            let head: String = word.chars().take(width).collect();
+            word = word.chars().skip(width).collect();
+            lines.push(head);
+        }
+        let needed = if current.is_empty() { 0 } else { current.chars().count() + 1 };
+        if needed + word.chars().count() > width {
+            lines.push(std::mem::take(&mut current));
+        }
+        if !current.is_empty() {
+            current.push(' ');
+        }
+        current.push_str(&word);
+    }
+    if !current.is_empty() || lines.is_empty() {
+        lines.push(current);
+    }
+    lines
+}
+
+fn pad(s: &str, width: usize) -> String {
+    let len = s.chars().count();
+    format!("{}{}", s, " ".repeat(width.saturating_sub(len)))
+}
+
+/**
+Render the comparison as text, one or more lines per field.
+*/
+pub fn render(left: &Entry, right: &Entry, options: &CompareOptions) -> String {
+    let rows = compare(left, right);
+    let name_width = rows.iter().map(|r| r.name.len()).max().unwrap_or(0);
+    let width = options.width;
+    let (hl_on, hl_off) = if options.color { ("\x1b[1;33m", "\x1b[0m") } else { ("", "") };
+
+    let mut out = String::new();
+    out.push_str(&format!("  {} | {} | {}\n",
+        pad("", name_width), pad(left.key(), width), right.key()));
+    out.push_str(&format!("  {}-+-{}-+-{}\n",
+        "-".repeat(name_width), "-".repeat(width), "-".repeat(width)));
+
+    for r in rows.iter() {
+        if options.only_differences && r.diff == FieldDiff::Same {
+            continue;
+        }
+        let l = wrap(r.left.as_deref().unwrap_or(""), width);
+        let rr = wrap(r.right.as_deref().unwrap_or(""), width);
+        let highlight = r.diff != FieldDiff::Same;
+        for i in 0..l.len().max(rr.len()) {
+            let (marker, name) = if i == 0 {
+                (r.diff.marker(), r.name.as_str())
+            } else {
+                (' ', "")
+            };
+            let lcell = pad(l.get(i).map(|s| s.as_str()).unwrap_or(""), width);
+            let rcell = rr.get(i).map(|s| s.as_str()).unwrap_or("");
+            let line = if highlight {
+                format!("{} {} | {}{}{} | {}{}{}",
+                    marker, pad(name, name_width), hl_on, lcell, hl_off, hl_on, rcell, hl_off)
+            } else {
+                format!("{} {} | {} | {}", marker, pad(name, name_width), lcell, rcell)
+            };
+            out.push_str(line.trim_end());
+            out.push('\n');
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::bibtex::data::BibType;
+
+    fn pair() -> (Entry, Entry) {
+        let mut a = Entry::new(BibType::Book, "Cox-CFT");
+        a.set("title", "Primes of the form x^2 + ny^2");
+        a.set("year", "2013");
+        a.set("doi", "10.1002/9781118400722");
+        let mut b = Entry::new(BibType::Book, "cox2013");
+        b.set("title", "Primes of the  form x^2 + ny^2");
+        b.set("year", "2014");
+        b.set("isbn", "978-1-118-39018-4");
+        (a, b)
+    }
+
+    #[test]
+    fn test_compare_rows() {
+        let (a, b) = pair();
+        let rows = compare(&a, &b);
+        let diffs: Vec<(&str, FieldDiff)> = rows.iter().map(|r| (r.name.as_str(), r.diff)).collect();
+        assert_eq!(diffs, vec![
+            ("@type", FieldDiff::Same),
+            ("title", FieldDiff::Same),
+            ("year", FieldDiff::Different),
+            ("doi", FieldDiff::LeftOnly),
+            ("isbn", FieldDiff::RightOnly),
+        ]);
+    }
+
+    #[test]
+    fn test_render() {
+        let (a, b) = pair();
+        let text = render(&a, &b, &CompareOptions { width: 12, color: false, only_differences: true });
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines[0].trim_end(), "        | Cox-CFT      | cox2013");
+        assert!(lines.iter().any(|l| l.starts_with("! year  | 2013         | 2014")));
+        assert!(!lines.iter().any(|l| l.contains("title")));
+        // the DOI does not fit into 12 characters and continues on the next line
+        let doi = lines.iter().position(|l| l.starts_with("< doi")).unwrap();
+        assert!(lines[doi + 1].starts_with("        | "));
+    }
+
+    #[test]
+    fn test_wrap() {
+        assert_eq!(wrap("a bb ccc", 4), vec!["a bb", "ccc"]);
+        assert_eq!(wrap("abcdefgh", 3), vec!["abc", "def", "gh"]);
+        assert_eq!(wrap("", 3), vec![""]);
+    }
+}
@@ -0,0 +1,395 @@
+/*!
+
+Project configuration read from `.perscrutar.toml`.
+
+The file is TOML, read with the `toml` crate. Every setting is a string,
+an integer, a boolean or an array of strings, in a table named after what
+it configures; nested tables are named with dots, as `[minimize.journal]`
+is. Other values, such as floats, dates or arrays of tables, are errors
+that give the line of the setting.
+
+```toml
+[library]
+path = "master.bib"
+
+[lint]
+deny = "warnings"
+max-warnings = 10
+datamodel = true
+
+[fields]
+private = ["note", "x-*"]
+
+[months]
+languages = ["de", "fr"]
+
+[spell]
+dictionary = "/usr/share/hunspell/en_US.dic"
+words = "words.txt"
+
+[keys]
+pinned = ["knuth84", "cox2013"]
+
+[format]
+sort = true
+indent = 4
+delimiter = "quotes"
+align = true
+width = 80
+
+[minimize.journal]
+base = "ieee"
+keep = ["note"]
+drop = ["month"]
+```
+
+*/
+
+use std::fmt;
+use toml::de::{DeTable, DeValue};
+#[cfg(feature = "writer")]
+use crate::bibtex::format::FormatOptions;
+use crate::bibtex::months::Language;
+use crate::bibtex::minimize::Profile;
+use crate::bibtex::policy::FieldPolicy;
+#[cfg(feature = "writer")]
+use crate::bibtex::writer::Delimiter;
+use crate::lint::{ExitPolicy, Severity};
+
+pub const CONFIG_FILE: &str = ".perscrutar.toml";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigValue {
+    Str(String),
+    Int(i64),
+    Bool(bool),
+    List(Vec<String>),
+}
+
+impl ConfigValue {
+    fn kind(&self) -> &'static str {
+        match self {
+            ConfigValue::Str(_) => "a string",
+            ConfigValue::Int(_) => "an integer",
+            ConfigValue::Bool(_) => "a boolean",
+            ConfigValue::List(_) => "a list",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigError {
+    /** 1-based line of the offending setting; 0 if not tied to a line. */
+    pub line: usize,
+    pub message: String,
+}
+
+impl ConfigError {
+    fn new(line: usize, message: &str) -> ConfigError {
+        ConfigError { line, message: String::from(message) }
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.line == 0 {
+            write!(f, "{}", self.message)
+        } else {
+            write!(f, "line {}: {}", self.line, self.message)
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Setting {
+    section: String,
+    key: String,
+    value: ConfigValue,
+    line: usize,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Config {
+    settings: Vec<Setting>,
+}
+
+/** The 1-based line of byte `offset` of `input`. */
+fn line_of(input: &str, offset: usize) -> usize {
+    input.as_bytes()[..offset.min(input.len())].iter().filter(|&&b| b == b'\n').count() + 1
+}
+
+fn convert(value: &DeValue<'_>) -> Option<ConfigValue> {
+    match value {
+        DeValue::String(s) => Some(ConfigValue::Str(s.to_string())),
+        DeValue::Integer(i) => i64::from_str_radix(i.as_str(), i.radix()).ok().map(ConfigValue::Int),
+        DeValue::Boolean(b) => Some(ConfigValue::Bool(*b)),
+        DeValue::Array(items) => items.iter().map(|item| match item.get_ref() {
+            DeValue::String(s) => Some(s.to_string()),
+            _ => None,
+        }).collect::<Option<Vec<String>>>().map(ConfigValue::List),
+        _ => None,
+    }
+}
+
+impl Config {
+    pub fn parse(input: &str) -> Result<Config, ConfigError> {
+        let table = DeTable::parse(input).map_err(|e| {
+            ConfigError::new(e.span().map(|span| line_of(input, span.start)).unwrap_or(0), e.message())
+        })?;
+        let mut config = Config::default();
+        config.add_table(input, "", table.get_ref())?;
+        // tables are kept sorted by name; settings go back in the order of the file
+        config.settings.sort_by_key(|s| s.line);
+        Ok(config)
+    }
+
+    fn add_table(&mut self, input: &str, section: &str, table: &DeTable<'_>) -> Result<(), ConfigError> {
+        for (key, value) in table.iter() {
+            let line = line_of(input, key.span().start);
+            if let DeValue::Table(inner) = value.get_ref() {
+                let name = if section.is_empty() { key.get_ref().to_string() } else { format!("{}.{}", section, key.get_ref()) };
+                self.add_table(input, &name, inner)?;
+                continue;
+            }
+            let value = convert(value.get_ref()).ok_or_else(|| ConfigError::new(line,
+                &format!("`{}` must be a string, an integer, a boolean or an array of strings", key.get_ref())))?;
+            self.settings.push(Setting { section: String::from(section), key: key.get_ref().to_string(), value, line });
+        }
+        Ok(())
+    }
+
+    fn setting(&self, section: &str, key: &str) -> Option<&Setting> {
+        self.settings.iter().rev().find(|s| s.section == section && s.key == key)
+    }
+
+    pub fn get(&self, section: &str, key: &str) -> Option<&ConfigValue> {
+        self.setting(section, key).map(|s| &s.value)
+    }
+
+    fn typed<'a, T>(&'a self, section: &str, key: &str, want: &str,
+                    f: impl Fn(&'a ConfigValue) -> Option<T>) -> Result<Option<T>, ConfigError> {
+        match self.setting(section, key) {
+            None => Ok(None),
+            Some(s) => f(&s.value).map(Some).ok_or_else(|| ConfigError::new(s.line,
+                &format!("`{}.{}` must be {}, not {}", section, key, want, s.value.kind()))),
+        }
+    }
+
+    pub fn get_str(&self, section: &str, key: &str) -> Result<Option<&str>, ConfigError> {
+        self.typed(section, key, "a string", |v| match v { ConfigValue::Str(s) => Some(s.as_str()), _ => None })
+    }
+
+    pub fn get_int(&self, section: &str, key: &str) -> Result<Option<i64>, ConfigError> {
+        self.typed(section, key, "an integer", |v| match v { ConfigValue::Int(i) => Some(*i), _ => None })
+    }
+
+    pub fn get_bool(&self, section: &str, key: &str) -> Result<Option<bool>, ConfigError> {
+        self.typed(section, key, "a boolean", |v| match v { ConfigValue::Bool(b) => Some(*b), _ => None })
+    }
+
+    pub fn get_list(&self, section: &str, key: &str) -> Result<Option<&[String]>, ConfigError> {
+        self.typed(section, key, "a list", |v| match v { ConfigValue::List(l) => Some(l.as_slice()), _ => None })
+    }
+
+    /**
+    The project's main bibliography, `[library] path`.
+    */
+    pub fn library(&self) -> Result<Option<&str>, ConfigError> {
+        self.get_str("library", "path")
+    }
+
+    /**
+    Exit policy for `lint` from the `[lint]` section.
+    */
+    pub fn lint_policy(&self) -> Result<ExitPolicy, ConfigError> {
+        let mut policy = ExitPolicy::default();
+        if let Some(deny) = self.get_str("lint", "deny")? {
+            policy.deny = Severity::parse(deny).ok_or_else(|| ConfigError::new(
+                self.setting("lint", "deny").map(|s| s.line).unwrap_or(0),
+                &format!("unknown severity `{}`", deny)))?;
+        }
+        if let Some(max) = self.get_int("lint", "max-warnings")? {
+            policy.max_warnings = Some(max.max(0) as usize);
+        }
+        Ok(policy)
+    }
+
+    /**
+    Private fields from `[fields] private`.
+    */
+    pub fn field_policy(&self) -> Result<FieldPolicy, ConfigError> {
+        let patterns = self.get_list("fields", "private")?.unwrap_or_default();
+        Ok(patterns.iter().fold(FieldPolicy::new(), |p, pattern| p.private(pattern)))
+    }
+
+    /**
+    Submission profiles from `[minimize.NAME]` sections, each starting from
+    the preset named by `base` (default `standard`) and adding `keep` and
+    `drop` patterns.
+    */
+    pub fn minimize_profiles(&self) -> Result<Vec<Profile>, ConfigError> {
+        let mut names: Vec<&str> = Vec::new();
+        for s in self.settings.iter() {
+            if let Some(name) = s.section.strip_prefix("minimize.") {
+                if !names.contains(&name) {
+                    names.push(name);
+                }
+            }
+        }
+        names.iter().map(|name| {
+            let section = format!("minimize.{}", name);
+            let base = self.get_str(&section, "base")?.unwrap_or("standard");
+            let preset = Profile::preset(base).ok_or_else(|| ConfigError::new(
+                self.setting(&section, "base").map(|s| s.line).unwrap_or(0),
+                &format!("unknown profile `{}`, expected one of {}", base, Profile::PRESETS.join(", "))))?;
+            let mut profile = Profile { name: String::from(*name), ..preset };
+            for pattern in self.get_list(&section, "keep")?.unwrap_or_default() {
+                profile = profile.keep(pattern);
+            }
+            for pattern in self.get_list(&section, "drop")?.unwrap_or_default() {
+                profile = profile.drop(pattern);
+            }
+            Ok(profile)
+        }).collect()
+    }
+
+    /**
+    Keys that must not change, from `[keys] pinned`; see
+    `Bibliography::pin`.
+    */
+    pub fn pinned_keys(&self) -> Result<&[String], ConfigError> {
+        Ok(self.get_list("keys", "pinned")?.unwrap_or_default())
+    }
+
+    /**
+    Canonical formatting for `fmt` from the `[format]` section; settings
+    left out keep the `WriteOptions` defaults.
+    */
+    #[cfg(feature = "writer")]
+    pub fn format_options(&self) -> Result<FormatOptions, ConfigError> {
+        let mut options = FormatOptions::default();
+        let line = |key: &str| self.setting("format", key).map(|s| s.line).unwrap_or(0);
+        options.sort = self.get_bool("format", "sort")?.unwrap_or(false);
+        if let Some(indent) = self.get_int("format", "indent")? {
+            options.write.indent = usize::try_from(indent).map_err(|_| ConfigError::new(line("indent"), "indent must not be negative"))?;
+        }
+        if let Some(width) = self.get_int("format", "width")? {
+            options.write.width = Some(usize::try_from(width).map_err(|_| ConfigError::new(line("width"), "width must not be negative"))?);
+        }
+        options.write.delimiter = match self.get_str("format", "delimiter")? {
+            None | Some("braces") => Delimiter::Braces,
+            Some("quotes") => Delimiter::Quotes,
+            Some(other) => return Err(ConfigError::new(line("delimiter"),
+                &format!("unknown delimiter `{}`, expected \"braces\" or \"quotes\"", other))),
+        };
+        options.write.align = self.get_bool("format", "align")?.unwrap_or(false);
+        Ok(options)
+    }
+
+    /**
+    Languages whose month names are recognised besides English, from
+    `[months] languages`.
+    */
+    pub fn month_languages(&self) -> Result<Vec<Language>, ConfigError> {
+        let codes = self.get_list("months", "languages")?.unwrap_or_default();
+        codes.iter().map(|code| Language::from_code(code).ok_or_else(|| ConfigError::new(
+            self.setting("months", "languages").map(|s| s.line).unwrap_or(0),
+            &format!("unknown language `{}`", code)))).collect()
+    }
+}
+
+/**
+The configuration written by `perscrutar init`.
+*/
+pub fn starter(library: &str) -> String {
+    format!("\
+# perscrutar project configuration
+
+[library]
+path = \"{}\"
+
+[lint]
+# lowest severity that fails `perscrutar lint`: \"errors\", \"warnings\" or \"info\"
+deny = \"errors\"
+# max-warnings = 0
+# check types and fields against biblatex's data model, as biber does
+# datamodel = true
+
+[fields]
+# fields left out when exporting
+private = [\"annote\", \"file\", \"x-*\"]
+", library)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let c = Config::parse("top = 1\n[lint] # strictness\ndeny = \"warn#ings\"\nmax-warnings = 1_0\n\n[fields]\nprivate = [\"note\", \"x-*\",]\nlax = true\n").unwrap();
+        assert_eq!(c.get_int("", "top").unwrap(), Some(1));
+        assert_eq!(c.get_str("lint", "deny").unwrap(), Some("warn#ings"));
+        assert_eq!(c.get_bool("fields", "lax").unwrap(), Some(true));
+        assert_eq!(c.get_list("fields", "private").unwrap().unwrap(), &["note", "x-*"]);
+        assert!(c.field_policy().unwrap().is_private("x-added"));
+        assert!(c.month_languages().unwrap().is_empty());
+        assert_eq!(Config::parse("[keys]\npinned = [\"knuth84\"]\n").unwrap().pinned_keys().unwrap(), &["knuth84"]);
+        let m = Config::parse("[minimize.journal]\nbase = \"ieee\"\nkeep = [\"note\"]\n[minimize.draft]\n").unwrap();
+        let profiles = m.minimize_profiles().unwrap();
+        assert_eq!(profiles.iter().map(|p| p.name.as_str()).collect::<Vec<&str>>(), vec!["journal"]);
+        assert_eq!((profiles[0].drop.len(), profiles[0].keep.len()), (6, 1));
+        assert_eq!(Config::parse("[minimize.x]\nbase = \"nope\"\n").unwrap().minimize_profiles().unwrap_err().line, 2);
+        assert_eq!(Config::parse("[months]\nlanguages = [\"de\", \"xx\"]\n").unwrap().month_languages().unwrap_err().line, 2);
+        #[cfg(feature = "writer")]
+        {
+            let f = Config::parse("[format]\nsort = true\nindent = 4\ndelimiter = \"quotes\"\n").unwrap().format_options().unwrap();
+            assert_eq!((f.sort, f.write.indent, f.write.delimiter, f.write.width), (true, 4, Delimiter::Quotes, None));
+            assert_eq!(Config::parse("[format]\n\ndelimiter = \"<>\"\n").unwrap().format_options().unwrap_err().line, 3);
+        }
+        assert_eq!(c.get_int("lint", "max-warnings").unwrap(), Some(10));
+        assert_eq!(c.get_str("lint", "max-warnings").unwrap_err().line, 4);
+        assert_eq!(c.lint_policy().unwrap_err().line, 3);
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(Config::parse("[a]\njunk\n").unwrap_err().line, 2);
+        assert_eq!(Config::parse("a = \"open\n").unwrap_err().line, 1);
+        assert_eq!(Config::parse("a = [1, 2]\n").unwrap_err().line, 1);
+        assert_eq!(Config::parse("[lint]\ndeny = \"errors\"\n\nwidth = 1.5\n").unwrap_err().line, 4);
+        assert_eq!(Config::parse("[keys]\npinned = []\npinned = []\n").unwrap_err().line, 3);
+        assert_eq!(Config::parse("[[minimize]]\nbase = \"ieee\"\n").unwrap_err().line, 1);
+        assert_eq!(Config::parse("[fields]\nprivate = [\n  \"note\",\n  {a = 1},\n]\n").unwrap_err().line, 2);
+    }
+
+    #[test]
+    fn test_toml() {
+        let c = Config::parse("\
+[fields]
+private = [
+    \"note\",   # kept out of exports
+    'x-*',
+]
+
+[minimize]
+journal.base = \"ieee\"
+draft = {drop = [\"abstract\"]}
+").unwrap();
+        assert_eq!(c.get_list("fields", "private").unwrap().unwrap(), &["note", "x-*"]);
+        let profiles = c.minimize_profiles().unwrap();
+        assert_eq!(profiles.iter().map(|p| p.name.as_str()).collect::<Vec<&str>>(), vec!["journal", "draft"]);
+        assert_eq!(Config::parse("[lint]\ndeny = \"x\"\n").unwrap().lint_policy().unwrap_err().line, 2);
+    }
+
+    #[test]
+    fn test_starter() {
+        let c = Config::parse(&starter("refs.bib")).unwrap();
+        assert_eq!(c.library().unwrap(), Some("refs.bib"));
+        assert_eq!(c.lint_policy().unwrap(), ExitPolicy::default());
+        assert!(c.field_policy().unwrap().is_private("annote"));
+    }
+}
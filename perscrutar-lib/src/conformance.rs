@@ -0,0 +1,283 @@
+/*!
+
+A corpus of tricky .bib samples and what the parser should make of them
+(`test-utils` feature).
+
+Each sample is a .bib file in the corpus directory. `%` lines outside its
+entries, which the parser ignores like any other text between entries,
+say what the sample tests and what to expect:
+
+```text
+% feature: concatenation, macros
+% source: hand-written, string definitions and #
+% options: standard
+% entries: 2
+% type: knuth84 article
+% field: knuth84 journal = Journal of the ACM Letters
+% missing: knuth84 month
+% known-failure: macros defined after use are not expanded
+```
+
+`feature` names what the sample exercises, so results can be reported
+feature by feature; `source` says where the sample comes from, with the
+project and licence of any text taken from elsewhere; `options` are those of `ParseOptions` (`standard`,
+`lenient`, `keep-comments`); `entries`, `preambles`, `type`, `field` and
+`missing` are checked against the parsed bibliography, and `error`, with
+an optional line, expects the parse to fail instead. Directives cannot
+contain `@`, which would start an entry.
+
+Samples the parser does not handle yet are marked `known-failure`, with
+why. They are reported but do not fail the run, until they pass: then the
+run fails too, so that the mark is removed and the sample guards against
+regressions like the others.
+
+The corpus of this crate is in `conformance/` and is run by its tests; a
+`Report` prints as a table of features. Most samples are hand-written,
+each a few lines reproducing one construct found in real files; the
+others are extracts of real bibliographies from the test suites of
+Pygments and of the `biblatex` crate, which their `source` lines name
+along with the licence, whose text is in `conformance/licences/`.
+
+*/
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use crate::bibtex::bibliography::Bibliography;
+use crate::bibtex::parser::{parse_bibliography_with, Comments, ParseOptions};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expectation {
+    Entries(usize),
+    Preambles(usize),
+    Type { key: String, name: String },
+    Field { key: String, field: String, value: String },
+    Missing { key: String, field: String },
+    /** A parse error, on this line if given. */
+    Error(Option<usize>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Case {
+    pub name: String,
+    pub features: Vec<String>,
+    pub source: Option<String>,
+    pub options: ParseOptions,
+    pub expectations: Vec<Expectation>,
+    /** Why the parser is known to fail this case. */
+    pub known_failure: Option<String>,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    Pass,
+    Fail(String),
+    KnownFailure(String),
+    /** A `known-failure` case that passes. */
+    UnexpectedPass,
+}
+
+/** A key and the rest of `text`, split at the first space. */
+fn key_and(text: &str) -> Option<(String, &str)> {
+    let (key, rest) = text.trim().split_once(char::is_whitespace)?;
+    Some((String::from(key), rest.trim()))
+}
+
+impl Case {
+    /** The case in the sample `text`, called `name`. */
+    pub fn parse(name: &str, text: &str) -> Result<Case, String> {
+        let mut case = Case {
+            name: String::from(name),
+            features: Vec::new(),
+            source: None,
+            options: ParseOptions { skip_text: true, ..ParseOptions::default() },
+            expectations: Vec::new(),
+            known_failure: None,
+            text: String::from(text),
+        };
+        for (i, line) in text.lines().enumerate() {
+            let Some(directive) = line.trim_start().strip_prefix('%') else { continue };
+            let (word, value) = directive.split_once(':').unwrap_or((directive, ""));
+            let (word, value) = (word.trim(), value.trim());
+            let invalid = || format!("{}, line {}: invalid `{}` directive", name, i + 1, word);
+            let number = || value.parse::<usize>().map_err(|_| invalid());
+            match word {
+                "feature" => case.features.extend(value.split(',').map(|f| String::from(f.trim())).filter(|f| !f.is_empty())),
+                "source" => case.source = Some(String::from(value)),
+                "options" => for option in value.split(',').map(str::trim) {
+                    match option {
+                        "standard" => case.options.comments = Comments::Standard,
+                        "lenient" => case.options.lenient = true,
+                        "keep-comments" => case.options.keep_comments = true,
+                        _ => return Err(invalid()),
+                    }
+                },
+                "entries" => case.expectations.push(Expectation::Entries(number()?)),
+                "preambles" => case.expectations.push(Expectation::Preambles(number()?)),
+                "type" => {
+                    let (key, name) = key_and(value).ok_or_else(invalid)?;
+                    case.expectations.push(Expectation::Type { key, name: name.to_lowercase() });
+                }
+                "field" => {
+                    let (key, rest) = key_and(value).ok_or_else(invalid)?;
+                    let (field, value) = rest.split_once('=').ok_or_else(invalid)?;
+                    let (field, value) = (field.trim().to_lowercase(), String::from(value.trim()));
+                    case.expectations.push(Expectation::Field { key, field, value });
+                }
+                "missing" => {
+                    let (key, field) = key_and(value).ok_or_else(invalid)?;
+                    case.expectations.push(Expectation::Missing { key, field: field.to_lowercase() });
+                }
+                "error" => case.expectations.push(Expectation::Error(match value {
+                    "" => None,
+                    _ => Some(number()?),
+                })),
+                "known-failure" => case.known_failure = Some(String::from(value)),
+                // an ordinary comment
+                _ => continue,
+            }
+        }
+        match case.features.is_empty() {
+            true => Err(format!("{}: no `feature` directive", name)),
+            false => Ok(case),
+        }
+    }
+
+    /** Why the parse does not meet the expectations, if it does not. */
+    fn check(&self) -> Result<(), String> {
+        let bib = parse_bibliography_with(&self.text, self.options);
+        let expects_error = self.expectations.iter().find_map(|e| match e {
+            Expectation::Error(line) => Some(*line),
+            _ => None,
+        });
+        let bib: Bibliography = match (bib, expects_error) {
+            (Ok(_), Some(_)) => return Err(String::from("parsed, but should fail")),
+            (Err(e), Some(Some(line))) if e.line != line => return Err(format!("failed on line {}, not {}: {}", e.line, line, e)),
+            (Err(_), Some(_)) => return Ok(()),
+            (Err(e), None) => return Err(format!("parse failed: {}", e)),
+            (Ok(bib), None) => bib,
+        };
+        let entry = |key: &str| bib.get(key).ok_or_else(|| format!("no entry `{}`", key));
+        for expectation in &self.expectations {
+            match expectation {
+                Expectation::Entries(n) if bib.len() != *n => return Err(format!("{} entries, expected {}", bib.len(), n)),
+                Expectation::Preambles(n) if bib.preambles().len() != *n =>
+                    return Err(format!("{} preambles, expected {}", bib.preambles().len(), n)),
+                Expectation::Type { key, name } => {
+                    let actual = entry(key)?.entry_type().name();
+                    if actual != name {
+                        return Err(format!("{} is a @{}, expected a @{}", key, actual, name));
+                    }
+                }
+                Expectation::Field { key, field, value } => {
+                    let actual = entry(key)?.get(field);
+                    if actual != Some(value.as_str()) {
+                        return Err(format!("{} {} is {:?}, expected {:?}", key, field, actual, value));
+                    }
+                }
+                Expectation::Missing { key, field } => {
+                    if let Some(actual) = entry(key)?.get(field) {
+                        return Err(format!("{} {} is {:?}, expected no such field", key, field, actual));
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    pub fn run(&self) -> Outcome {
+        match (self.check(), &self.known_failure) {
+            (Ok(()), None) => Outcome::Pass,
+            (Ok(()), Some(_)) => Outcome::UnexpectedPass,
+            (Err(why), None) => Outcome::Fail(why),
+            (Err(_), Some(reason)) => Outcome::KnownFailure(reason.clone()),
+        }
+    }
+}
+
+/** The cases in the .bib files of `dir`, sorted by name. */
+pub fn load_corpus<P: AsRef<Path>>(dir: P) -> Result<Vec<Case>, String> {
+    let dir = dir.as_ref();
+    let read = std::fs::read_dir(dir).map_err(|e| format!("cannot read {}: {}", dir.display(), e))?;
+    let mut paths: Vec<PathBuf> = read.flatten().map(|e| e.path()).filter(|p| p.extension().is_some_and(|x| x == "bib")).collect();
+    paths.sort();
+    paths.iter().map(|path| {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("cannot read {}: {}", path.display(), e))?;
+        let name = path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+        Case::parse(&name, &text)
+    }).collect()
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Report {
+    /** Each case's name and features, and how it went, in corpus order. */
+    pub results: Vec<(String, Vec<String>, Outcome)>,
+}
+
+impl Report {
+    pub fn run(cases: &[Case]) -> Report {
+        Report { results: cases.iter().map(|c| (c.name.clone(), c.features.clone(), c.run())).collect() }
+    }
+
+    /** Whether every case passed or failed as known. */
+    pub fn is_ok(&self) -> bool {
+        self.results.iter().all(|(_, _, o)| matches!(o, Outcome::Pass | Outcome::KnownFailure(_)))
+    }
+
+    /** For each feature, the cases that pass and all its cases. */
+    pub fn by_feature(&self) -> BTreeMap<&str, (usize, usize)> {
+        let mut out = BTreeMap::new();
+        for (_, features, outcome) in &self.results {
+            for feature in features {
+                let counts: &mut (usize, usize) = out.entry(feature.as_str()).or_default();
+                counts.0 += usize::from(*outcome == Outcome::Pass);
+                counts.1 += 1;
+            }
+        }
+        out
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (feature, (passed, total)) in self.by_feature() {
+            writeln!(f, "{:<24} {:>3}/{:<3} {}", feature, passed, total, if passed == total { "ok" } else { "INCOMPLETE" })?;
+        }
+        for (name, _, outcome) in &self.results {
+            match outcome {
+                Outcome::Pass => {}
+                Outcome::Fail(why) => writeln!(f, "FAIL {}: {}", name, why)?,
+                Outcome::KnownFailure(why) => writeln!(f, "known failure {}: {}", name, why)?,
+                Outcome::UnexpectedPass => writeln!(f, "PASS {}: marked as a known failure, remove the mark", name)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_case() {
+        let case = Case::parse("sample", "% feature: quotes\n% a note\n% options: standard\n% field: a title = Say \"hi\"\n\
+            % known-failure: not yet\n@misc{a, title = {Say \"hi\"}}\n").unwrap();
+        assert_eq!(case.features, vec!["quotes"]);
+        assert_eq!(case.run(), Outcome::UnexpectedPass);
+        let failing = Case { known_failure: None, expectations: vec![Expectation::Entries(2)], ..case };
+        assert_eq!(failing.run(), Outcome::Fail(String::from("1 entries, expected 2")));
+        assert!(Case::parse("none", "@misc{a}").is_err());
+        assert!(Case::parse("bad", "% feature: x\n% entries: many").is_err());
+    }
+
+    #[test]
+    fn test_corpus() {
+        let cases = load_corpus(concat!(env!("CARGO_MANIFEST_DIR"), "/conformance")).unwrap();
+        let report = Report::run(&cases);
+        assert!(report.is_ok(), "conformance corpus:\n{}", report);
+    }
+}
@@ -0,0 +1,482 @@
+/*!
+
+Reading and writing CSL-JSON.
+
+CSL-JSON is the item format of the Citation Style Language, which Zotero,
+Mendeley and pandoc export: a list of objects such as
+
+```json
+[{"id": "knuth84", "type": "article-journal", "title": "Literate programming",
+  "author": [{"family": "Knuth", "given": "Donald E."}],
+  "container-title": "The Computer Journal", "volume": "27", "issue": "2",
+  "page": "97-111", "issued": {"date-parts": [[1984, 5]]},
+  "DOI": "10.1093/comjnl/27.2.97"}]
+```
+
+`from_csl_json` turns each item into an entry. The CSL `type` picks the
+entry type (see `entry_type`); names become BibTeX name lists, literal
+names kept whole in braces; `issued` becomes `date` and `year`, `accessed`
+`urldate`; page ranges get BibTeX's `--`. The publisher of a report is
+its `institution`, that of a thesis its `school`. The `id` is the citation
+key if it is one, as in Better BibTeX exports; Zotero's numeric or URL ids
+are replaced by a key from the first author and the year.
+
+Rich text markup CSL allows in titles (`<i>`, `<sup>`) is kept as it is.
+
+`to_csl_json` goes the other way, for pandoc and other CSL processors:
+`csl_type` picks the item type, names are split into their parts, `date`
+(or `year` and `month`) becomes `issued`, and values are decoded from
+LaTeX to Unicode (see `latex::decode`), since CSL-JSON is plain text.
+
+*/
+
+use std::fmt;
+use crate::bibtex::data::{BibType, Entry};
+use crate::bibtex::keys::{citation_key, is_key, unique_key};
+use crate::bibtex::months::parse_month;
+use crate::bibtex::names::{bibtex_name, parse_names, Name};
+use crate::bibtex::policy::FieldPolicy;
+use crate::bibtex::values::{FieldValue, Pages};
+use crate::json::{self, JsonError, JsonValue};
+use crate::latex::decode::decode;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CslError {
+    Json(JsonError),
+    /** The document is not a list of items, or item `index` is not an object. */
+    NotItem(usize),
+}
+
+impl fmt::Display for CslError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CslError::Json(e) => write!(f, "{}", e),
+            CslError::NotItem(index) => write!(f, "item {} is not a CSL-JSON object", index + 1),
+        }
+    }
+}
+
+impl std::error::Error for CslError {}
+
+impl From<JsonError> for CslError {
+    fn from(e: JsonError) -> Self {
+        CslError::Json(e)
+    }
+}
+
+/** CSL variables copied to a field of another name, or of the same. */
+const FIELDS: [(&str, &str); 22] = [
+    ("title", "title"),
+    ("title-short", "shorttitle"),
+    ("collection-title", "series"),
+    ("volume", "volume"),
+    ("issue", "number"),
+    ("number", "number"),
+    ("edition", "edition"),
+    ("publisher", "publisher"),
+    ("publisher-place", "location"),
+    ("event-title", "eventtitle"),
+    ("event", "eventtitle"),
+    ("event-place", "venue"),
+    ("genre", "type"),
+    ("number-of-pages", "pagetotal"),
+    ("DOI", "doi"),
+    ("URL", "url"),
+    ("ISBN", "isbn"),
+    ("ISSN", "issn"),
+    ("PMID", "pmid"),
+    ("abstract", "abstract"),
+    ("note", "note"),
+    ("language", "language"),
+];
+
+/** CSL name variables and the fields they go in. */
+const NAMES: [(&str, &str); 6] = [
+    ("author", "author"),
+    ("editor", "editor"),
+    ("translator", "translator"),
+    ("container-author", "bookauthor"),
+    ("director", "director"),
+    ("composer", "composer"),
+];
+
+/**
+The entry type for a CSL item type and, for theses, its `genre`. Types
+without a biblatex counterpart become `@misc`.
+*/
+pub fn entry_type(csl: &str, genre: Option<&str>) -> BibType {
+    let name = match csl {
+        "article-journal" | "article-magazine" | "article-newspaper" | "review" | "review-book" => "article",
+        "paper-conference" => "inproceedings",
+        "chapter" => "incollection",
+        "entry-encyclopedia" | "entry-dictionary" | "entry" => "inreference",
+        "book" | "classic" => "book",
+        "collection" => "collection",
+        "periodical" => "periodical",
+        "report" => "report",
+        "thesis" => {
+            let genre = genre.unwrap_or("").to_lowercase();
+            if genre.contains("phd") || genre.contains("ph.d") || genre.contains("doctor") {
+                return BibType::PhdThesis;
+            }
+            if genre.contains("master") {
+                return BibType::MastersThesis;
+            }
+            "thesis"
+        }
+        "manuscript" => "unpublished",
+        "webpage" | "post" | "post-weblog" => "online",
+        "software" => "software",
+        "dataset" => "dataset",
+        "standard" => "standard",
+        "patent" => "patent",
+        "legal_case" => "jurisdiction",
+        "legislation" | "bill" | "regulation" => "legislation",
+        "treaty" => "legal",
+        "motion_picture" => "movie",
+        "broadcast" => "video",
+        "song" => "audio",
+        "performance" => "performance",
+        _ => "misc",
+    };
+    BibType::parse(name)
+}
+
+/** A string or number member as text. */
+fn text(item: &JsonValue, name: &str) -> Option<String> {
+    let s = match item.get(name)? {
+        v @ JsonValue::Num(_) => v.to_string(),
+        v => String::from(v.as_str()?),
+    };
+    Some(String::from(s.trim())).filter(|s| !s.is_empty())
+}
+
+/** A CSL name object as a BibTeX name. */
+fn name(name: &JsonValue) -> Option<String> {
+    if let Some(literal) = text(name, "literal") {
+        return Some(format!("{{{}}}", literal));
+    }
+    let part = |p: &str| text(name, p).unwrap_or_default();
+    let von = [part("dropping-particle"), part("non-dropping-particle")].into_iter()
+        .filter(|p| !p.is_empty()).collect::<Vec<_>>().join(" ");
+    match text(name, "family") {
+        Some(family) => Some(bibtex_name(&part("given"), &von, &family, &part("suffix"))),
+        None => text(name, "given"),
+    }
+}
+
+/**
+A CSL date as `YYYY`, `YYYY-MM` or `YYYY-MM-DD`, from its first
+`date-parts`, or else from `raw` or `literal` if they start with a year.
+Ranges keep their start.
+*/
+fn date(date: &JsonValue) -> Option<String> {
+    if let Some(parts) = date.get("date-parts").and_then(|p| p.as_array()?.first()?.as_array()) {
+        let numbers: Vec<u32> = parts.iter()
+            .map_while(|p| p.as_f64().map(|n| n as u32).or_else(|| p.as_str()?.trim().parse().ok()))
+            .collect();
+        return match numbers.as_slice() {
+            [] => None,
+            [y] => Some(format!("{:04}", y)),
+            [y, m] => Some(format!("{:04}-{:02}", y, m)),
+            [y, m, d, ..] => Some(format!("{:04}-{:02}-{:02}", y, m, d)),
+        };
+    }
+    let raw = text(date, "raw").or_else(|| text(date, "literal"))?;
+    let iso: String = raw.chars().take(10).collect();
+    let valid = iso.len() >= 4 && iso.chars().enumerate().all(|(i, c)| c.is_ascii_digit() || (c == '-' && (i == 4 || i == 7)));
+    valid.then(|| String::from(iso.trim_end_matches('-')))
+}
+
+/** The entry for one CSL-JSON item. */
+pub fn from_csl_item(item: &JsonValue) -> Entry {
+    let csl_type = text(item, "type").unwrap_or_default();
+    let genre = text(item, "genre");
+    let ty = entry_type(&csl_type, genre.as_deref());
+    let mut entry = Entry::new(ty.clone(), "");
+    for (csl, field) in NAMES {
+        let names: Vec<String> = item.get(csl).and_then(JsonValue::as_array).unwrap_or(&[]).iter().filter_map(name).collect();
+        if !names.is_empty() {
+            entry.set(field, &names.join(" and "));
+        }
+    }
+    for (csl, field) in FIELDS {
+        // the thesis kind is in the type already
+        if entry.has(field) || (csl == "genre" && matches!(ty, BibType::PhdThesis | BibType::MastersThesis)) {
+            continue;
+        }
+        if let Some(value) = text(item, csl) {
+            entry.set(field, &value);
+        }
+    }
+    if let Some(container) = text(item, "container-title") {
+        let field = match ty.name() {
+            "article" => "journal",
+            "inproceedings" | "incollection" | "inreference" => "booktitle",
+            "online" => "organization",
+            _ => "howpublished",
+        };
+        entry.set(field, &container);
+    }
+    match csl_type.as_str() {
+        "article-magazine" => { entry.set("entrysubtype", "magazine"); }
+        "article-newspaper" => { entry.set("entrysubtype", "newspaper"); }
+        // the publisher of a report or thesis is the institution
+        "report" | "thesis" => {
+            let field = if csl_type == "report" { "institution" } else { "school" };
+            if let Some(publisher) = entry.remove("publisher") {
+                entry.set(field, &publisher);
+            }
+        }
+        _ => {}
+    }
+    if let Some(page) = text(item, "page") {
+        entry.set("pages", &Pages::parse_field(&page).map(|p| p.to_string()).unwrap_or(page));
+    }
+    if let Some(issued) = item.get("issued").and_then(date) {
+        entry.set("date", &issued);
+        entry.set("year", &issued[..4]);
+    }
+    if let Some(accessed) = item.get("accessed").and_then(date) {
+        entry.set("urldate", &accessed);
+    }
+    let keywords: Vec<String> = text(item, "keyword").unwrap_or_default()
+        .split([',', ';']).map(str::trim).filter(|k| !k.is_empty()).map(String::from).collect();
+    if !keywords.is_empty() {
+        entry.set("keywords", &keywords.join(", "));
+    }
+    let key = text(item, "id").filter(|id| is_key(id)).unwrap_or_else(|| citation_key(&entry));
+    entry.set_key(&key);
+    entry
+}
+
+/**
+The entries for a CSL-JSON document: a list of items, or a single item.
+Generated keys that come out the same for two items get a letter
+appended, `knuth1984a`.
+*/
+pub fn from_csl_json(input: &str) -> Result<Vec<Entry>, CslError> {
+    let document = json::parse(input)?;
+    let items = match &document {
+        JsonValue::Array(items) => items.as_slice(),
+        JsonValue::Object(_) => std::slice::from_ref(&document),
+        _ => return Err(CslError::NotItem(0)),
+    };
+    let mut entries: Vec<Entry> = Vec::new();
+    for (index, item) in items.iter().enumerate() {
+        if !matches!(item, JsonValue::Object(_)) {
+            return Err(CslError::NotItem(index));
+        }
+        let mut entry = from_csl_item(item);
+        let key = unique_key(entry.key(), |k| entries.iter().any(|e| e.key() == k));
+        entry.set_key(&key);
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+/** The CSL item type for an entry, the reverse of `entry_type`. */
+pub fn csl_type(entry: &Entry) -> &'static str {
+    match entry.entry_type().name() {
+        "article" => match entry.get("entrysubtype") {
+            Some("magazine") => "article-magazine",
+            Some("newspaper") => "article-newspaper",
+            _ => "article-journal",
+        },
+        "inproceedings" => "paper-conference",
+        "incollection" | "inbook" | "bookinbook" => "chapter",
+        "inreference" => "entry-encyclopedia",
+        "book" | "mvbook" => "book",
+        "collection" | "mvcollection" | "proceedings" | "mvproceedings" | "reference" => "collection",
+        "periodical" => "periodical",
+        "techreport" => "report",
+        "thesis" | "phdthesis" | "mastersthesis" => "thesis",
+        "unpublished" => "manuscript",
+        "online" => "webpage",
+        "software" => "software",
+        "dataset" => "dataset",
+        "standard" => "standard",
+        "patent" => "patent",
+        "jurisdiction" => "legal_case",
+        "legislation" => "legislation",
+        "legal" => "treaty",
+        "movie" => "motion_picture",
+        "video" => "broadcast",
+        "audio" => "song",
+        "performance" => "performance",
+        _ => "document",
+    }
+}
+
+/** A name as a CSL name object; a name in braces is a literal one. */
+fn csl_name(name: &Name) -> JsonValue {
+    let whole = name.last.starts_with('{') && name.last.ends_with('}');
+    if whole && name.first.is_empty() && name.von.is_empty() && name.jr.is_empty() {
+        return JsonValue::object(vec![("literal", JsonValue::str(&decode(&name.last)))]);
+    }
+    let parts = [("family", &name.last), ("given", &name.first), ("non-dropping-particle", &name.von), ("suffix", &name.jr)];
+    JsonValue::object(parts.into_iter()
+        .filter(|(_, part)| !part.is_empty())
+        .map(|(csl, part)| (csl, JsonValue::str(&decode(part))))
+        .collect())
+}
+
+/** A date such as `1984-05` or `1984-05-12/1984-05-14` as CSL `date-parts`, keeping a range's start. */
+fn csl_date(date: &str) -> Option<JsonValue> {
+    let parts: Vec<JsonValue> = date.split('/').next()?.split('-').take(3)
+        .map_while(|p| p.trim().parse::<u32>().ok())
+        .map(|n| JsonValue::Num(n as f64))
+        .collect();
+    (!parts.is_empty()).then(|| JsonValue::object(vec![("date-parts", JsonValue::Array(vec![JsonValue::Array(parts)]))]))
+}
+
+/** Add variable `csl` to `item`, unless it has it already. */
+fn add(item: &mut Vec<(String, JsonValue)>, csl: &str, value: JsonValue) {
+    if !item.iter().any(|(name, _)| name == csl) {
+        item.push((String::from(csl), value));
+    }
+}
+
+/** The CSL-JSON item for an entry. Fields CSL has no variable for are left out. */
+pub fn to_csl_item(entry: &Entry) -> JsonValue {
+    let ty = csl_type(entry);
+    let mut item: Vec<(String, JsonValue)> = vec![
+        (String::from("id"), JsonValue::str(entry.key())),
+        (String::from("type"), JsonValue::str(ty)),
+    ];
+    for (csl, field) in NAMES {
+        let names: Vec<JsonValue> = parse_names(entry.get(field).unwrap_or_default()).iter()
+            .filter(|n| !n.is_others())
+            .map(csl_name)
+            .collect();
+        if !names.is_empty() {
+            add(&mut item, csl, JsonValue::Array(names));
+        }
+    }
+    let genre = match entry.entry_type() {
+        BibType::PhdThesis => Some(String::from("PhD thesis")),
+        BibType::MastersThesis => Some(String::from("Master's thesis")),
+        _ => None,
+    };
+    if let Some(genre) = genre {
+        add(&mut item, "genre", JsonValue::str(&genre));
+    }
+    let container = ["journaltitle", "journal", "booktitle"].into_iter().find_map(|f| entry.field_unicode(f));
+    if let Some(container) = container {
+        add(&mut item, "container-title", JsonValue::str(&container));
+    }
+    for (csl, field) in FIELDS {
+        // `number` is the issue of an article, and the report or standard number otherwise
+        let wrong_number = (csl == "issue") != (ty.starts_with("article") || ty == "periodical");
+        if csl == "event" || (field == "number" && wrong_number) {
+            continue;
+        }
+        if let Some(value) = entry.field_unicode(field) {
+            add(&mut item, csl, JsonValue::str(&value));
+        }
+    }
+    for field in ["institution", "school"] {
+        if let Some(value) = entry.field_unicode(field) {
+            add(&mut item, "publisher", JsonValue::str(&value));
+        }
+    }
+    if let Some(location) = entry.field_unicode("address") {
+        add(&mut item, "publisher-place", JsonValue::str(&location));
+    }
+    if let Some(pages) = entry.get("pages") {
+        add(&mut item, "page", JsonValue::str(&pages.replace("--", "-")));
+    }
+    let month = entry.get("month").and_then(|m| parse_month(m, &[]));
+    let issued = entry.get("date").map(String::from)
+        .or_else(|| entry.get("year").map(|y| match month {
+            Some(m) => format!("{}-{:02}", y.trim(), m),
+            None => String::from(y.trim()),
+        }));
+    if let Some(issued) = issued.as_deref().and_then(csl_date) {
+        add(&mut item, "issued", issued);
+    }
+    if let Some(accessed) = entry.get("urldate").and_then(csl_date) {
+        add(&mut item, "accessed", accessed);
+    }
+    if let Some(keywords) = entry.field_unicode("keywords") {
+        add(&mut item, "keyword", JsonValue::str(&keywords));
+    }
+    JsonValue::Object(item)
+}
+
+/** A CSL-JSON document with an item for each entry, in order. */
+pub fn to_csl_json(entries: &[Entry]) -> JsonValue {
+    JsonValue::Array(entries.iter().map(to_csl_item).collect())
+}
+
+/** `to_csl_json` without the fields `policy` makes private. */
+pub fn to_csl_json_with(entries: &[Entry], policy: &FieldPolicy) -> JsonValue {
+    to_csl_json(&policy.apply_all(entries))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_from_csl_json() {
+        let entries = from_csl_json(r#"[
+            {"id": "knuth84", "type": "article-journal", "title": "Literate programming",
+             "author": [{"family": "Knuth", "given": "Donald E."}],
+             "container-title": "The Computer Journal", "volume": 27, "issue": "2",
+             "page": "97-111", "issued": {"date-parts": [[1984, 5]]}, "DOI": "10.1093/comjnl/27.2.97"},
+            {"id": "http://zotero.org/users/1/items/ABCD", "type": "thesis", "genre": "PhD thesis",
+             "title": "Pioneering", "publisher": "MIT",
+             "author": [{"family": "Beethoven", "given": "Ludwig", "non-dropping-particle": "van"}],
+             "issued": {"raw": "2001-09-11"}},
+            {"id": "1", "type": "report", "title": "A", "publisher": "NASA",
+             "author": [{"literal": "NASA"}], "issued": {"date-parts": [["2001"]]}},
+            {"id": "2", "type": "webpage", "title": "B", "author": [{"literal": "NASA"}],
+             "issued": {"date-parts": [[2001]]}, "accessed": {"date-parts": [[2026, 10, 16]]}}
+        ]"#).unwrap();
+        let keys: Vec<&str> = entries.iter().map(|e| e.key()).collect();
+        assert_eq!(keys, vec!["knuth84", "beethoven2001", "nasa2001", "nasa2001a"]);
+
+        let article = &entries[0];
+        assert_eq!(article.entry_type(), &BibType::Article);
+        assert_eq!(article.get("author"), Some("Knuth, Donald E."));
+        assert_eq!(article.get("journal"), Some("The Computer Journal"));
+        assert_eq!((article.get("volume"), article.get("number")), (Some("27"), Some("2")));
+        assert_eq!((article.get("pages"), article.get("date"), article.get("year")), (Some("97--111"), Some("1984-05"), Some("1984")));
+        assert_eq!(article.get("doi"), Some("10.1093/comjnl/27.2.97"));
+
+        assert_eq!(entries[1].entry_type(), &BibType::PhdThesis);
+        assert_eq!(entries[1].get("author"), Some("van Beethoven, Ludwig"));
+        assert!(!entries[1].has("type"));
+        assert_eq!(entries[1].get("school"), Some("MIT"));
+        assert_eq!(entries[2].get("institution"), Some("NASA"));
+        assert_eq!(entries[2].get("author"), Some("{NASA}"));
+        assert_eq!(entries[3].entry_type().name(), "online");
+        assert_eq!(entries[3].get("urldate"), Some("2026-10-16"));
+
+        assert_eq!(from_csl_json("[1]"), Err(CslError::NotItem(0)));
+        assert!(matches!(from_csl_json("{"), Err(CslError::Json(_))));
+    }
+
+    #[test]
+    fn test_to_csl_json() {
+        let entries = from_csl_json(r#"[{"id": "knuth84", "type": "article-journal", "title": "Literate programming",
+            "author": [{"family": "Knuth", "given": "Donald E."}], "container-title": "The Computer Journal",
+            "volume": "27", "issue": "2", "page": "97-111", "issued": {"date-parts": [[1984, 5]]},
+            "DOI": "10.1093/comjnl/27.2.97"}]"#).unwrap();
+        assert_eq!(from_csl_json(&to_csl_json(&entries).to_string()).unwrap(), entries);
+
+        let mut thesis = Entry::new(BibType::PhdThesis, "g");
+        thesis.set("author", "G{\\\"o}del, Kurt and {NASA} and van Leunen, Mary-Claire");
+        thesis.set("title", "{\\\"U}ber {F}ormal {U}nentscheidbare S{\\\"a}tze");
+        for (field, value) in [("school", "Wien"), ("year", "1930"), ("month", "jul"), ("number", "7"), ("url", "https://example.org/a~b")] {
+            thesis.set(field, value);
+        }
+        let item = to_csl_item(&thesis);
+        assert_eq!(item.to_string(), "{\"id\":\"g\",\"type\":\"thesis\",\"author\":[{\"family\":\"Gödel\",\"given\":\"Kurt\"},\
+            {\"literal\":\"NASA\"},{\"family\":\"Leunen\",\"given\":\"Mary-Claire\",\"non-dropping-particle\":\"van\"}],\
+            \"genre\":\"PhD thesis\",\"title\":\"Über Formal Unentscheidbare Sätze\",\"number\":\"7\",\
+            \"URL\":\"https://example.org/a~b\",\"publisher\":\"Wien\",\"issued\":{\"date-parts\":[[1930,7]]}}");
+    }
+}
@@ -0,0 +1,307 @@
+/*!
+
+biblatex's default data model, as biber checks entries against it.
+
+With `--validate-datamodel`, biber warns about every entry that does not
+fit the data model of `blx-dm.def`, and drops the values it cannot use
+before the document is typeset. `validate::RuleSet::biber` reports the
+same classes of problems, so they show up before a LaTeX run:
+
+- an entry type the data model does not declare;
+- a mandatory field that is missing, e.g. the `journaltitle` of an
+  `@article` or one of `date` and `year`;
+- both `date` and `year`, of which biber keeps only `date`;
+- a field the data model does not allow for the type, e.g. `publisher`
+  on an `@article`;
+- a value not of the field's datatype: a date that is not ISO 8601 (as
+  extended by EDTF), a `month` or `year` that is not an integer, an ISBN
+  or ISSN with a wrong check digit, a `gender` that is not one of the
+  data model's codes, a name with more than two commas.
+
+As biber does, entries are first mapped by the driver source map for
+BibTeX: `@phdthesis` becomes a `@thesis` with `type = phdthesis`, `journal`
+becomes `journaltitle`, `address` becomes `location`, and so on
+(`TYPE_ALIASES`, `FIELD_ALIASES`). A source field is left alone when its
+target is set as well, so it is then reported as not allowed.
+
+`@report` and `@techreport` are one `BibType` here, so `type` is not
+required of either; biber sets it for `@techreport` only.
+
+*/
+
+use crate::bibtex::data::{BibType, Entry};
+use crate::bibtex::names::split_names;
+use crate::bibtex::types::{TypeRegistry, TypeSchema};
+use crate::identifiers::normalize_isbn;
+
+/** Entry types the driver source map renames, with the `type` it sets. */
+pub const TYPE_ALIASES: [(&str, &str, Option<&str>); 4] = [
+    ("electronic", "online", None),
+    ("www", "online", None),
+    ("phdthesis", "thesis", Some("phdthesis")),
+    ("mastersthesis", "thesis", Some("mathesis")),
+];
+
+/** BibTeX fields the driver source map renames to their biblatex names. */
+pub const FIELD_ALIASES: [(&str, &str); 9] = [
+    ("address", "location"),
+    ("annote", "annotation"),
+    ("archiveprefix", "eprinttype"),
+    ("hyphenation", "langid"),
+    ("journal", "journaltitle"),
+    ("key", "sortkey"),
+    ("pdf", "file"),
+    ("primaryclass", "eprintclass"),
+    ("school", "institution"),
+];
+
+/** Fields the data model allows on every entry type. */
+pub const GLOBAL_FIELDS: [&str; 86] = [
+    "abstract", "addendum", "annotation", "authortype", "bookpagination", "crossref", "date", "doi",
+    "entryset", "entrysubtype", "eprint", "eprintclass", "eprinttype", "execute", "file", "gender", "ids",
+    "indexsorttitle", "indextitle", "isan", "ismn", "iswc", "keywords", "label", "langid", "langidopts",
+    "language", "library", "lista", "listb", "listc", "listd", "liste", "listf", "month", "namea",
+    "nameaddon", "nameatype", "nameb", "namebtype", "namec", "namectype", "note", "options", "origdate",
+    "origlanguage", "origlocation", "origpublisher", "origtitle", "pagination", "presort", "pubstate",
+    "related", "relatedoptions", "relatedstring", "relatedtype", "shortauthor", "shorteditor", "shorthand",
+    "shorthandintro", "shortjournal", "shortseries", "shorttitle", "sortkey", "sortname", "sortshorthand",
+    "sorttitle", "sortyear", "subtitle", "title", "titleaddon", "url", "urldate", "usera", "userb", "userc",
+    "userd", "usere", "userf", "verba", "verbb", "verbc", "xdata", "xref", "year", "yeardivision",
+];
+
+/** Fields holding name lists. */
+pub const NAME_FIELDS: [&str; 19] = [
+    "afterword", "annotator", "author", "bookauthor", "commentator", "editor", "editora", "editorb",
+    "editorc", "foreword", "holder", "introduction", "namea", "nameb", "namec", "shortauthor",
+    "shorteditor", "sortname", "translator",
+];
+
+/** Fields holding ISO 8601 dates or date ranges. */
+pub const DATE_FIELDS: [&str; 4] = ["date", "eventdate", "origdate", "urldate"];
+
+/** Entry types that must have exactly one of `date` and `year`. */
+pub const DATED_TYPES: [&str; 28] = [
+    "article", "book", "bookinbook", "booklet", "collection", "dataset", "inbook", "incollection",
+    "inproceedings", "inreference", "manual", "misc", "mvbook", "mvcollection", "mvproceedings",
+    "mvreference", "online", "patent", "periodical", "proceedings", "reference", "report", "software",
+    "suppbook", "suppcollection", "suppperiodical", "thesis", "unpublished",
+];
+
+/** Types the data model declares but the standard styles print as `@misc`. */
+const MISC_LIKE: [&str; 21] = [
+    "artwork", "audio", "bibnote", "commentary", "customa", "customb", "customc", "customd", "custome",
+    "customf", "image", "jurisdiction", "legal", "legislation", "letter", "movie", "music", "performance",
+    "review", "standard", "video",
+];
+
+const EDITORS: [&str; 8] = [
+    "editor", "editora", "editorb", "editorc", "editortype", "editoratype", "editorbtype", "editorctype",
+];
+
+const CONTRIBUTORS: [&str; 6] = ["afterword", "annotator", "commentator", "foreword", "introduction", "translator"];
+
+const BOOK: [&str; 16] = [
+    "chapter", "edition", "eid", "isbn", "location", "mainsubtitle", "maintitle", "maintitleaddon",
+    "number", "pages", "pagetotal", "part", "publisher", "series", "volume", "volumes",
+];
+
+const CONTAINER: [&str; 3] = ["booksubtitle", "booktitle", "booktitleaddon"];
+
+const PROCEEDINGS: [&str; 21] = [
+    "editor", "editortype", "chapter", "eid", "eventdate", "eventtitle", "eventtitleaddon", "isbn",
+    "location", "mainsubtitle", "maintitle", "maintitleaddon", "number", "organization", "pages",
+    "pagetotal", "part", "publisher", "series", "venue", "volume",
+];
+
+const MISC: [&str; 8] = ["author", "editor", "editortype", "howpublished", "location", "organization", "type", "version"];
+
+const GENDERS: [&str; 7] = ["sf", "sm", "sn", "pf", "pm", "pn", "pp"];
+
+const MONTHS: [&str; 12] = ["jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec"];
+
+fn schema(lists: &[&[&str]], mandatory: &[&[&str]], dated: bool) -> TypeSchema {
+    let mut schema = TypeSchema::new();
+    for fields in mandatory {
+        schema = schema.require_any(fields);
+    }
+    if dated {
+        schema = schema.require_any(&["date", "year"]);
+    }
+    lists.iter().flat_map(|fields| fields.iter()).fold(schema, |schema, field| schema.optional(field))
+}
+
+/** The entry types of the data model, with their mandatory and allowed fields. */
+pub fn registry() -> TypeRegistry {
+    let mut r = TypeRegistry::empty();
+    let mut add = |names: &[&str], lists: &[&[&str]], mandatory: &[&[&str]]| {
+        for name in names {
+            r.register(name, schema(lists, mandatory, DATED_TYPES.contains(name)));
+        }
+    };
+    add(&["article"], &[&["author", "eid", "issn", "issue", "issuesubtitle", "issuetitle", "issuetitleaddon",
+        "journalsubtitle", "journaltitle", "journaltitleaddon", "number", "pages", "series", "version", "volume",
+        "annotator", "commentator", "translator"], &EDITORS],
+        &[&["author"], &["title"], &["journaltitle"]]);
+    add(&["book", "mvbook"], &[&["author"], &BOOK, &EDITORS, &CONTRIBUTORS], &[&["author"], &["title"]]);
+    add(&["inbook", "bookinbook", "suppbook"], &[&["author"], &BOOK, &EDITORS, &CONTRIBUTORS, &["bookauthor"], &CONTAINER],
+        &[&["author"], &["title"], &["booktitle"]]);
+    add(&["booklet"], &[&["author", "editor", "editortype", "chapter", "eid", "howpublished", "location",
+        "pages", "pagetotal", "type"]], &[&["author", "editor"], &["title"]]);
+    add(&["collection", "mvcollection", "reference", "mvreference"], &[&BOOK, &EDITORS, &CONTRIBUTORS],
+        &[&["editor"], &["title"]]);
+    add(&["incollection", "suppcollection", "inreference"], &[&["author"], &BOOK, &EDITORS, &CONTRIBUTORS, &CONTAINER],
+        &[&["author"], &["editor"], &["title"], &["booktitle"]]);
+    add(&["dataset"], &[&MISC, &["edition", "number", "publisher", "series"]], &[&["author", "editor"], &["title"]]);
+    add(&["manual"], &[&MISC, &["chapter", "edition", "eid", "isbn", "number", "pages", "pagetotal", "publisher",
+        "series"]], &[&["author", "editor"], &["title"]]);
+    add(&["misc", "software"], &[&MISC], &[&["author", "editor"], &["title"]]);
+    add(&MISC_LIKE, &[&MISC], &[]);
+    add(&["online"], &[&["author", "editor", "editortype", "organization", "version"]],
+        &[&["author", "editor"], &["title"], &["doi", "eprint", "url"]]);
+    add(&["patent"], &[&["author", "holder", "location", "number", "type", "version"]],
+        &[&["author"], &["title"], &["number"]]);
+    add(&["periodical", "suppperiodical"], &[&EDITORS, &["issn", "issue", "issuesubtitle", "issuetitle",
+        "issuetitleaddon", "number", "series", "volume"]], &[&["editor"], &["title"]]);
+    add(&["proceedings", "mvproceedings"], &[&PROCEEDINGS, &["volumes"]], &[&["title"]]);
+    add(&["inproceedings"], &[&PROCEEDINGS, &["author"], &CONTAINER], &[&["author"], &["title"], &["booktitle"]]);
+    add(&["report"], &[&["author", "chapter", "eid", "institution", "isrn", "location", "number", "pages",
+        "pagetotal", "type", "version"]], &[&["author"], &["title"], &["institution"]]);
+    add(&["thesis"], &[&["author", "chapter", "eid", "institution", "location", "pages", "pagetotal", "type"]],
+        &[&["author"], &["title"], &["type"], &["institution"]]);
+    add(&["unpublished"], &[&["author", "eventdate", "eventtitle", "eventtitleaddon", "howpublished", "location",
+        "type", "venue"]], &[&["author"], &["title"]]);
+    add(&["set"], &[], &[&["entryset"]]);
+    // an @xdata entry only holds fields for others to use
+    let all: Vec<&str> = [&NAME_FIELDS[..], &BOOK, &EDITORS, &CONTAINER, &PROCEEDINGS, &MISC, &["eventdate",
+        "holder", "institution", "isrn", "issn", "issue", "issuesubtitle", "issuetitle", "issuetitleaddon",
+        "journalsubtitle", "journaltitle", "journaltitleaddon", "venue"]].concat();
+    add(&["xdata"], &[&all], &[]);
+    r
+}
+
+/** `entry` as biber's driver source map for BibTeX leaves it. */
+pub fn biber_entry(entry: &Entry) -> Entry {
+    let mut out = entry.clone();
+    if let Some((_, target, subtype)) = TYPE_ALIASES.iter().find(|(from, _, _)| *from == entry.entry_type().name()) {
+        out.set_entry_type(BibType::parse(target));
+        if let Some(subtype) = subtype.filter(|_| !out.has("type")) {
+            out.set("type", subtype);
+        }
+    }
+    for (from, to) in FIELD_ALIASES {
+        out.rename(from, to);
+    }
+    out
+}
+
+/** The BibTeX name the driver source map renames to `field`, if any. */
+pub fn bibtex_alias(field: &str) -> Option<&'static str> {
+    FIELD_ALIASES.iter().find(|(_, to)| *to == field).map(|(from, _)| *from)
+}
+
+/** Whether `value` is an ISO 8601 date, EDTF level 1, or a range of them. */
+pub fn is_date(value: &str) -> bool {
+    let open = |s: &str| s.is_empty() || s == "..";
+    match value.trim().split_once('/') {
+        Some((start, end)) => !(open(start) && open(end))
+            && (open(start) || is_single_date(start)) && (open(end) || is_single_date(end)),
+        None => is_single_date(value.trim()),
+    }
+}
+
+fn is_single_date(value: &str) -> bool {
+    let value = value.trim_end_matches(['?', '~', '%']);
+    let (date, time) = match value.split_once('T') {
+        Some((date, time)) => (date, Some(time)),
+        None => (value, None),
+    };
+    let parts: Vec<&str> = date.strip_prefix('-').unwrap_or(date).split('-').collect();
+    // `X` stands for unspecified digits, as in 199X or 2001-XX
+    let digits = |s: &str, len: usize, unspecified_from: usize| s.len() == len
+        && s.chars().enumerate().all(|(i, c)| c.is_ascii_digit() || (c == 'X' && i >= unspecified_from));
+    let number = |s: &str| s.parse::<u32>().ok();
+    let month = |s: &str| s == "XX" || (digits(s, 2, 2) && number(s).is_some_and(|m| (1..=12).contains(&m) || (21..=24).contains(&m)));
+    let day = |s: &str| s == "XX" || (digits(s, 2, 2) && number(s).is_some_and(|d| (1..=31).contains(&d)));
+    let valid_time = time.map(|t| !t.is_empty() && parts.len() == 3
+        && t.chars().all(|c| c.is_ascii_digit() || matches!(c, ':' | '.' | 'Z' | '+' | '-'))).unwrap_or(true);
+    valid_time && digits(parts[0], 4, 2) && match parts.len() {
+        1 => true,
+        2 => month(parts[1]),
+        3 => month(parts[1]) && number(parts[1]).is_none_or(|m| m <= 12) && day(parts[2]),
+        _ => false,
+    }
+}
+
+/** Whether `value` is an ISSN with the right check digit. */
+pub fn is_issn(value: &str) -> bool {
+    let chars: Vec<char> = value.chars().filter(|c| *c != '-' && !c.is_whitespace()).map(|c| c.to_ascii_uppercase()).collect();
+    if chars.len() != 8 || !chars[..7].iter().all(|c| c.is_ascii_digit()) {
+        return false;
+    }
+    let sum: u32 = chars[..7].iter().enumerate().map(|(i, c)| (8 - i as u32) * c.to_digit(10).unwrap_or(0)).sum();
+    match (11 - sum % 11) % 11 {
+        10 => chars[7] == 'X',
+        check => chars[7].to_digit(10) == Some(check),
+    }
+}
+
+/** Number of commas in `name` outside braces. */
+fn commas(name: &str) -> usize {
+    let mut depth = 0usize;
+    name.chars().filter(|c| {
+        match c {
+            '{' => depth += 1,
+            '}' => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+        *c == ',' && depth == 0
+    }).count()
+}
+
+/**
+What is wrong with `value` as the value of `field`, such as "is not a
+date", or `None` if biber would accept it.
+*/
+pub fn check_value(field: &str, value: &str) -> Option<String> {
+    let value = value.trim();
+    match field {
+        f if DATE_FIELDS.contains(&f) && !is_date(value) =>
+            Some(String::from("is not an ISO 8601 date such as 2001-05-17, 2001-05 or 2001/2003")),
+        "year" if value.strip_prefix('-').unwrap_or(value).parse::<u32>().is_err() =>
+            Some(String::from("is not an integer; use `date` or `pubstate` for anything else")),
+        "month" if !MONTHS.contains(&value.to_lowercase().as_str()) && !value.parse::<u32>().is_ok_and(|m| (1..=12).contains(&m)) =>
+            Some(String::from("is not a month number from 1 to 12")),
+        "isbn" if normalize_isbn(value).is_none() => Some(String::from("is not a valid ISBN")),
+        "issn" if !is_issn(value) => Some(String::from("is not a valid ISSN")),
+        "gender" if !GENDERS.contains(&value) => Some(format!("is not one of {}", GENDERS.join(", "))),
+        f if NAME_FIELDS.contains(&f) => split_names(value).into_iter().find(|name| commas(name) > 2)
+            .map(|name| format!("has a name with too many commas: `{}`", name.trim())),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_check_value() {
+        for date in ["2001", "2001-05", "2001-05-17", "2001-22", "199X", "2001-05-17T14:30:00Z", "-0044-03-15",
+            "2001?", "2001/", "2001/2003", "2001-05/..", "/2003", "2001-XX-XX"] {
+            assert!(is_date(date), "{}", date);
+        }
+        for date in ["May 2001", "01", "2001-13", "2001-22-01", "2001-02-32", "/", "20011", "2001-05T10:00"] {
+            assert!(!is_date(date), "{}", date);
+        }
+        assert!(is_issn("0010-4620") && is_issn("2049-3630") && !is_issn("0010-4621"));
+        assert_eq!(check_value("month", "may"), None);
+        assert_eq!(check_value("month", "5"), None);
+        assert!(check_value("month", "May 1984").is_some());
+        assert!(check_value("year", "1984a").is_some());
+        assert!(check_value("isbn", "978-0-201-89683-2").is_some());
+        assert_eq!(check_value("author", "Knuth, Donald E. and {Doe, Jr., III, IV}"), None);
+        assert_eq!(check_value("author", "Doe, John, Jr., III"),
+            Some(String::from("has a name with too many commas: `Doe, John, Jr., III`")));
+    }
+}
@@ -0,0 +1,337 @@
+/*!
+
+Finding and merging duplicate entries.
+
+The same work gets into a bibliography twice when it is added from two
+sources, or once as a preprint and once as published. `find_duplicates`
+looks for such pairs with a list of `Strategy`s, tried in order:
+
+- `Strategy::Doi`: the same DOI, however written;
+- `Strategy::TitleYear`: the same title, ignoring case, braces, accents and
+  punctuation, and the same year;
+- `Strategy::FuzzyTitle`: titles that are nearly the same, as with a typo,
+  an abbreviation or words in another order (`matching::token_sort_ratio`),
+  and the same year where both entries give one.
+
+`dedupe` merges each pair into one entry. Which entry stays is up to the
+`MergePolicy`: the earlier one, the one with more fields, or whatever a
+callback answers, which is how a front end asks the user. The entry that
+stays gains the fields only the other has, and the other's key among its
+`ids`, so documents citing either key still find it; `crossref` and the
+other `KEY_REFERENCES` to the removed key are pointed at the one kept.
+Values the two entries disagree on are kept as they are in the entry that
+stays and listed in the `DedupeReport`.
+
+A pinned entry (see `Bibliography::pin`) is always the one kept; two
+pinned entries are not merged.
+
+*/
+
+use crate::bibtex::bibliography::{Bibliography, KEY_REFERENCES};
+use crate::bibtex::data::Entry;
+use crate::bibtex::values::Doi;
+use crate::json::JsonValue;
+use crate::matching::{ratio, simplify, token_sort};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Strategy {
+    Doi,
+    TitleYear,
+    /** Titles at least this similar, from 0 to 1 (identical). */
+    FuzzyTitle(f64),
+}
+
+impl Strategy {
+    /** `Doi` and `TitleYear`, which rarely pair different works. */
+    pub const DEFAULT: [Strategy; 2] = [Strategy::Doi, Strategy::TitleYear];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Strategy::Doi => "doi",
+            Strategy::TitleYear => "title-year",
+            Strategy::FuzzyTitle(_) => "fuzzy-title",
+        }
+    }
+}
+
+/** An entry, `second`, found to duplicate an earlier one, `first` (indexes). */
+#[derive(Debug, Clone, PartialEq)]
+pub struct Duplicate {
+    pub first: usize,
+    pub second: usize,
+    pub strategy: Strategy,
+}
+
+/** What the strategies compare, worked out once per entry. */
+struct Traits {
+    doi: Option<String>,
+    title: String,
+    /** The title's `matching::token_sort`. */
+    tokens: String,
+    year: Option<String>,
+}
+
+impl Traits {
+    fn of(entry: &Entry) -> Traits {
+        let title = entry.get("title").unwrap_or("");
+        Traits {
+            doi: entry.get_parsed::<Doi>("doi").ok().flatten().map(|Doi(doi)| doi),
+            title: simplify(title),
+            tokens: token_sort(title),
+            year: entry.get("year").map(|y| String::from(y.trim())).filter(|y| !y.is_empty()),
+        }
+    }
+
+    fn matches(&self, other: &Traits, strategy: Strategy) -> bool {
+        match strategy {
+            Strategy::Doi => self.doi.is_some() && self.doi == other.doi,
+            Strategy::TitleYear => !self.title.is_empty() && self.title == other.title && self.year == other.year,
+            Strategy::FuzzyTitle(threshold) => {
+                let years = match (&self.year, &other.year) {
+                    (Some(a), Some(b)) => a == b,
+                    _ => true,
+                };
+                years && !self.tokens.is_empty() && !other.tokens.is_empty()
+                    && ratio(&self.tokens, &other.tokens) >= threshold
+            }
+        }
+    }
+}
+
+/**
+The duplicates in `entries`: each entry that any of `strategies` pairs
+with an earlier one, with the earliest such entry and the first strategy
+that pairs them.
+*/
+pub fn find_duplicates(entries: &[Entry], strategies: &[Strategy]) -> Vec<Duplicate> {
+    let traits: Vec<Traits> = entries.iter().map(Traits::of).collect();
+    let mut found = Vec::new();
+    for second in 1..entries.len() {
+        let pair = (0..second).find_map(|first| strategies.iter()
+            .find(|s| traits[first].matches(&traits[second], **s))
+            .map(|s| Duplicate { first, second, strategy: *s }));
+        found.extend(pair);
+    }
+    found
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Choice {
+    First,
+    Second,
+    /** Leave both entries as they are. */
+    Skip,
+}
+
+/** Which of two duplicates stays. */
+pub enum MergePolicy<'a> {
+    PreferFirst,
+    /** The entry with more fields, or the first if they have as many. */
+    PreferMostFields,
+    /** Whatever the callback answers for the first and second entry. */
+    Ask(&'a mut dyn FnMut(&Entry, &Entry) -> Choice),
+}
+
+impl MergePolicy<'_> {
+    fn choose(&mut self, first: &Entry, second: &Entry) -> Choice {
+        match self {
+            MergePolicy::PreferFirst => Choice::First,
+            MergePolicy::PreferMostFields if second.len() > first.len() => Choice::Second,
+            MergePolicy::PreferMostFields => Choice::First,
+            MergePolicy::Ask(ask) => ask(first, second),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Merge {
+    pub kept: String,
+    pub removed: String,
+    pub strategy: Strategy,
+    /** Fields both entries have with different values; the kept entry's stay. */
+    pub conflicts: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DedupeReport {
+    pub merged: Vec<Merge>,
+    /** Keys of duplicates left alone, by the policy or because both are pinned. */
+    pub skipped: Vec<(String, String)>,
+}
+
+impl DedupeReport {
+    pub fn to_json(&self) -> JsonValue {
+        JsonValue::object(vec![
+            ("merged", JsonValue::Array(self.merged.iter().map(|m| JsonValue::object(vec![
+                ("kept", JsonValue::str(&m.kept)),
+                ("removed", JsonValue::str(&m.removed)),
+                ("strategy", JsonValue::str(m.strategy.name())),
+                ("conflicts", JsonValue::Array(m.conflicts.iter().map(|c| JsonValue::str(c)).collect())),
+            ])).collect())),
+            ("skipped", JsonValue::Array(self.skipped.iter()
+                .map(|(a, b)| JsonValue::Array(vec![JsonValue::str(a), JsonValue::str(b)]))
+                .collect())),
+        ])
+    }
+}
+
+fn same(a: &str, b: &str) -> bool {
+    a.split_whitespace().eq(b.split_whitespace())
+}
+
+/**
+Give `kept` the fields only `other` has and `other`'s key and aliases as
+aliases. Returns the fields whose values differ.
+*/
+fn merge(kept: &mut Entry, other: &Entry) -> Vec<String> {
+    let mut conflicts = Vec::new();
+    for (name, value) in other.fields().filter(|(name, _)| !name.eq_ignore_ascii_case("ids")) {
+        match kept.get(name) {
+            None => { kept.set(name, value); }
+            Some(current) if !same(current, value) => conflicts.push(String::from(name)),
+            Some(_) => {}
+        }
+    }
+    let mut ids: Vec<String> = kept.ids().into_iter().map(String::from).collect();
+    for alias in std::iter::once(other.key()).chain(other.ids()) {
+        if alias != kept.key() && !ids.iter().any(|i| i == alias) {
+            ids.push(String::from(alias));
+        }
+    }
+    if !ids.is_empty() {
+        kept.set("ids", &ids.join(", "));
+    }
+    conflicts
+}
+
+/**
+Merge the duplicates `strategies` find in `bibliography`, as `policy`
+chooses. Changes go through `Bibliography::visit_mut` and `retain`, so
+they are journaled.
+*/
+pub fn dedupe(bibliography: &mut Bibliography, strategies: &[Strategy], policy: &mut MergePolicy) -> DedupeReport {
+    let mut entries: Vec<Entry> = bibliography.entries().to_vec();
+    let mut report = DedupeReport::default();
+    // the entry each one was merged into, itself if none
+    let mut into: Vec<usize> = (0..entries.len()).collect();
+    let root = |into: &[usize], mut i: usize| {
+        while into[i] != i {
+            i = into[i];
+        }
+        i
+    };
+    for duplicate in find_duplicates(&entries, strategies) {
+        let (a, b) = (root(&into, duplicate.first), root(&into, duplicate.second));
+        if a == b {
+            continue;
+        }
+        let (first, second) = (a.min(b), a.max(b));
+        let keys = (String::from(entries[first].key()), String::from(entries[second].key()));
+        let (kept, removed) = match policy.choose(&entries[first], &entries[second]) {
+            Choice::First => (first, second),
+            Choice::Second => (second, first),
+            Choice::Skip => {
+                report.skipped.push(keys);
+                continue;
+            }
+        };
+        let pinned = |i: usize| bibliography.is_pinned(entries[i].key());
+        let (kept, removed) = match (pinned(kept), pinned(removed)) {
+            (true, true) => {
+                report.skipped.push(keys);
+                continue;
+            }
+            (false, true) => (removed, kept),
+            _ => (kept, removed),
+        };
+        let other = entries[removed].clone();
+        let conflicts = merge(&mut entries[kept], &other);
+        into[removed] = kept;
+        report.merged.push(Merge {
+            kept: String::from(entries[kept].key()),
+            removed: String::from(other.key()),
+            strategy: duplicate.strategy,
+            conflicts,
+        });
+    }
+
+    let renamed: Vec<(String, String)> = (0..entries.len()).filter(|i| into[*i] != *i)
+        .map(|i| (String::from(entries[i].key()), String::from(entries[root(&into, i)].key())))
+        .collect();
+    for entry in entries.iter_mut() {
+        for field in KEY_REFERENCES {
+            let Some(value) = entry.get(field) else { continue };
+            let targets: Vec<&str> = value.split(',').map(str::trim).collect();
+            let followed: Vec<&str> = targets.iter()
+                .map(|t| renamed.iter().find(|(old, _)| old == t).map(|(_, new)| new.as_str()).unwrap_or(t))
+                .collect();
+            if followed != targets {
+                entry.set(field, &followed.join(", "));
+            }
+        }
+    }
+    let mut i = 0;
+    bibliography.visit_mut(|e| {
+        *e = entries[i].clone();
+        i += 1;
+    });
+    let mut i = 0;
+    bibliography.retain(|_| {
+        i += 1;
+        into[i - 1] == i - 1
+    });
+    report
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    const LIBRARY: &str = "@article{knuth84,\n  title = {Literate Programming},\n  year = {1984},\n  doi = {10.1093/comjnl/27.2.97}\n}\n\
+        @misc{lp,\n  title = {Literate programming},\n  year = {1984},\n  note = {Preprint}\n}\n\
+        @article{dup,\n  title = {Literate {P}rogramming.},\n  year = {1984},\n  doi = {https://doi.org/10.1093/COMJNL/27.2.97},\n  pages = {97--111}\n}\n\
+        @misc{typo,\n  title = {Literate Programing},\n  year = {1984}\n}\n\
+        @inproceedings{paper,\n  crossref = {dup},\n  title = {Other}\n}";
+
+    #[test]
+    fn test_find_duplicates() {
+        let bib = Bibliography::parse(LIBRARY).unwrap();
+        let pairs = |strategies: &[Strategy]| find_duplicates(bib.entries(), strategies).into_iter()
+            .map(|d| (d.first, d.second, d.strategy.name()))
+            .collect::<Vec<_>>();
+        assert_eq!(pairs(&[Strategy::Doi]), vec![(0, 2, "doi")]);
+        assert_eq!(pairs(&Strategy::DEFAULT), vec![(0, 1, "title-year"), (0, 2, "doi")]);
+        assert_eq!(pairs(&[Strategy::FuzzyTitle(0.9)]), vec![(0, 1, "fuzzy-title"), (0, 2, "fuzzy-title"), (0, 3, "fuzzy-title")]);
+        assert_eq!(pairs(&[Strategy::FuzzyTitle(0.99)]).len(), 2);
+    }
+
+    #[test]
+    fn test_dedupe() {
+        let mut bib = Bibliography::parse(LIBRARY).unwrap();
+        let report = dedupe(&mut bib, &Strategy::DEFAULT, &mut MergePolicy::PreferMostFields);
+        let merged: Vec<(&str, &str)> = report.merged.iter().map(|m| (m.kept.as_str(), m.removed.as_str())).collect();
+        assert_eq!(merged, vec![("knuth84", "lp"), ("knuth84", "dup")]);
+        assert_eq!(report.merged[1].conflicts, vec!["title", "doi"]);
+        let keys: Vec<&str> = bib.entries().iter().map(|e| e.key()).collect();
+        assert_eq!(keys, vec!["knuth84", "typo", "paper"]);
+        let kept = bib.get("knuth84").unwrap();
+        assert_eq!((kept.get("note"), kept.get("pages"), kept.get("ids")), (Some("Preprint"), Some("97--111"), Some("lp, dup")));
+        assert_eq!(bib.get("paper").and_then(|e| e.get("crossref")), Some("knuth84"));
+        assert_eq!(bib.get("dup").map(|e| e.key()), Some("knuth84"));
+
+        let mut bib = Bibliography::parse(LIBRARY).unwrap();
+        bib.pin("dup");
+        let mut asked = Vec::new();
+        let mut ask = |a: &Entry, b: &Entry| {
+            asked.push(format!("{}/{}", a.key(), b.key()));
+            if b.key() == "lp" { Choice::Skip } else { Choice::First }
+        };
+        let report = dedupe(&mut bib, &Strategy::DEFAULT, &mut MergePolicy::Ask(&mut ask));
+        assert_eq!(asked, vec!["knuth84/lp", "knuth84/dup"]);
+        assert_eq!(report.skipped, vec![(String::from("knuth84"), String::from("lp"))]);
+        assert_eq!((report.merged[0].kept.as_str(), report.merged[0].removed.as_str()), ("dup", "knuth84"));
+        assert!(bib.take_refused().is_empty());
+        assert_eq!(report.to_json().get("skipped").map(|s| s.to_string()).as_deref(), Some(r#"[["knuth84","lp"]]"#));
+    }
+}
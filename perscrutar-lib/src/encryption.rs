@@ -0,0 +1,231 @@
+/*!
+
+Encrypted metadata, for libraries whose notes should not be readable by
+whoever gets hold of the files.
+
+`MetadataStore::save_encrypted` writes the sidecar as an
+[age](https://age-encryption.org) file, which `load_encrypted` reads back
+with the same `Key`, and which the `age` command line tool can decrypt too.
+A key is either
+
+- a passphrase, stretched with scrypt, so that unlocking takes about a
+  second; or
+- a key file as written by `age-keygen` or `Key::to_keyfile`, holding an
+  `AGE-SECRET-KEY-1...` line.
+
+Only the sidecar is encrypted; the `.bib` file it belongs to is not. This
+module is only built with the `encrypt` feature; the encryption itself is
+done by the `age` crate.
+
+*/
+
+use std::fmt;
+use std::iter;
+use std::io::{Read, Write};
+use std::path::Path;
+use std::str::FromStr;
+use age::secrecy::{ExposeSecret, SecretString};
+use age::{scrypt, x25519, Decryptor, Encryptor};
+use crate::metadata::{MetadataError, MetadataStore};
+
+/** How every age file starts. */
+pub const MAGIC: &[u8] = b"age-encryption.org/v1\n";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EncryptionError {
+    Io(String),
+    /** A key file without a key, or a passphrase for a file encrypted to a key file. */
+    Key(String),
+    /** The wrong key, or a damaged file. */
+    Decrypt(String),
+    Metadata(MetadataError),
+}
+
+impl fmt::Display for EncryptionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EncryptionError::Io(msg) => f.write_str(msg),
+            EncryptionError::Key(msg) => write!(f, "invalid key: {}", msg),
+            EncryptionError::Decrypt(msg) => write!(f, "cannot decrypt: {}", msg),
+            EncryptionError::Metadata(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for EncryptionError {}
+
+impl From<MetadataError> for EncryptionError {
+    fn from(e: MetadataError) -> Self {
+        EncryptionError::Metadata(e)
+    }
+}
+
+pub enum Key {
+    Passphrase(SecretString),
+    Identity(x25519::Identity),
+}
+
+impl fmt::Debug for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Key::Passphrase(_) => f.write_str("Key::Passphrase(..)"),
+            Key::Identity(identity) => write!(f, "Key::Identity({})", identity.to_public()),
+        }
+    }
+}
+
+impl Key {
+    pub fn passphrase(passphrase: &str) -> Key {
+        Key::Passphrase(SecretString::from(String::from(passphrase)))
+    }
+
+    /**
+    A new random key, to be saved with `to_keyfile`.
+    */
+    pub fn generate() -> Key {
+        Key::Identity(x25519::Identity::generate())
+    }
+
+    /**
+    Read the first key of a key file; `#` starts a comment line.
+    */
+    pub fn parse_keyfile(text: &str) -> Result<Key, EncryptionError> {
+        let line = text.lines().map(str::trim).find(|l| !l.is_empty() && !l.starts_with('#'))
+            .ok_or_else(|| EncryptionError::Key(String::from("no key in the key file")))?;
+        x25519::Identity::from_str(line)
+            .map(Key::Identity)
+            .map_err(|_| EncryptionError::Key(String::from("expected an `AGE-SECRET-KEY-1` line")))
+    }
+
+    pub fn load_keyfile<P: AsRef<Path>>(path: P) -> Result<Key, EncryptionError> {
+        let text = std::fs::read_to_string(path.as_ref())
+            .map_err(|e| EncryptionError::Io(format!("cannot read {}: {}", path.as_ref().display(), e)))?;
+        Key::parse_keyfile(&text)
+    }
+
+    /**
+    The key file for this key, in the format of `age-keygen`, or `None` for
+    a passphrase.
+    */
+    pub fn to_keyfile(&self) -> Option<String> {
+        match self {
+            Key::Passphrase(_) => None,
+            Key::Identity(identity) => Some(format!("# public key: {}\n{}\n",
+                identity.to_public(), identity.to_string().expose_secret())),
+        }
+    }
+}
+
+pub fn is_encrypted(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+pub fn encrypt(plaintext: &[u8], key: &Key) -> Result<Vec<u8>, EncryptionError> {
+    let encryptor = match key {
+        Key::Passphrase(passphrase) => Encryptor::with_user_passphrase(passphrase.clone()),
+        Key::Identity(identity) => Encryptor::with_recipients(iter::once(&identity.to_public() as _))
+            .expect("there is a recipient"),
+    };
+    let mut out = Vec::with_capacity(plaintext.len() + 256);
+    let mut writer = encryptor.wrap_output(&mut out).map_err(|e| EncryptionError::Io(e.to_string()))?;
+    writer.write_all(plaintext).and_then(|_| writer.finish()).map_err(|e| EncryptionError::Io(e.to_string()))?;
+    Ok(out)
+}
+
+pub fn decrypt(ciphertext: &[u8], key: &Key) -> Result<Vec<u8>, EncryptionError> {
+    let decryptor = Decryptor::new_buffered(ciphertext).map_err(|e| EncryptionError::Decrypt(e.to_string()))?;
+    let mut reader = match key {
+        Key::Passphrase(_) if !decryptor.is_scrypt() =>
+            return Err(EncryptionError::Key(String::from("the file was encrypted with a key file, not a passphrase"))),
+        Key::Passphrase(passphrase) =>
+            decryptor.decrypt(iter::once(&scrypt::Identity::new(passphrase.clone()) as _)),
+        Key::Identity(identity) => decryptor.decrypt(iter::once(identity as _)),
+    }.map_err(|e| EncryptionError::Decrypt(e.to_string()))?;
+    let mut out = Vec::new();
+    reader.read_to_end(&mut out).map_err(|e| EncryptionError::Decrypt(e.to_string()))?;
+    Ok(out)
+}
+
+impl MetadataStore {
+    /**
+    Read a sidecar written by `save_encrypted`; a missing file is an empty
+    store, as with `load`.
+    */
+    pub fn load_encrypted<P: AsRef<Path>>(path: P, key: &Key) -> Result<MetadataStore, EncryptionError> {
+        let data = match std::fs::read(path.as_ref()) {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(MetadataStore::new()),
+            Err(e) => return Err(EncryptionError::Io(format!("cannot read {}: {}", path.as_ref().display(), e))),
+        };
+        if !is_encrypted(&data) {
+            return Err(EncryptionError::Decrypt(format!("{} is not encrypted", path.as_ref().display())));
+        }
+        let text = String::from_utf8(decrypt(&data, key)?)
+            .map_err(|_| EncryptionError::Decrypt(String::from("the contents are not UTF-8")))?;
+        Ok(MetadataStore::parse(&text)?)
+    }
+
+    pub fn save_encrypted<P: AsRef<Path>>(&self, path: P, key: &Key) -> Result<(), EncryptionError> {
+        let data = encrypt(format!("{}\n", self.to_json().to_pretty_string()).as_bytes(), key)?;
+        std::fs::write(path.as_ref(), data)
+            .map_err(|e| EncryptionError::Io(format!("cannot write {}: {}", path.as_ref().display(), e)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::bibtex::data::{BibType, Entry};
+    use crate::json::JsonValue;
+
+    fn store() -> MetadataStore {
+        let mut store = MetadataStore::new();
+        let mut e = Entry::new(BibType::Article, "knuth84");
+        e.set("doi", "10.1093/comjnl/27.2.97");
+        store.set(&e, "note", JsonValue::str("confidential"));
+        store
+    }
+
+    #[test]
+    fn test_keyfile() {
+        let dir = std::env::temp_dir().join(format!("perscrutar-encryption-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("library.meta.json.age");
+
+        let key = Key::generate();
+        let keyfile = key.to_keyfile().unwrap();
+        assert!(keyfile.starts_with("# public key: age1"));
+        store().save_encrypted(&path, &key).unwrap();
+        let data = std::fs::read(&path).unwrap();
+        assert!(is_encrypted(&data));
+        assert!(!data.windows(12).any(|w| w == b"confidential"));
+
+        let loaded = MetadataStore::load_encrypted(&path, &Key::parse_keyfile(&keyfile).unwrap()).unwrap();
+        assert_eq!(loaded.get("knuth84", "note"), Some(&JsonValue::str("confidential")));
+        assert!(matches!(MetadataStore::load_encrypted(&path, &Key::generate()), Err(EncryptionError::Decrypt(_))));
+        assert!(matches!(MetadataStore::load_encrypted(&path, &Key::passphrase("x")), Err(EncryptionError::Key(_))));
+        assert_eq!(MetadataStore::load(&path).unwrap_err().to_string(),
+            format!("invalid metadata: {} is encrypted", path.display()));
+        assert!(MetadataStore::load_encrypted(dir.join("missing"), &key).unwrap().records().is_empty());
+
+        store().save(&path).unwrap();
+        assert!(MetadataStore::load_encrypted(&path, &key).is_err());
+        assert!(Key::parse_keyfile("# nothing\n").is_err());
+        assert!(Key::parse_keyfile("AGE-SECRET-KEY-1XYZ").is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_passphrase() {
+        let key = Key::passphrase("correct horse battery staple");
+        assert!(key.to_keyfile().is_none());
+        let data = encrypt(b"secret notes", &key).unwrap();
+        assert_eq!(decrypt(&data, &key).unwrap(), b"secret notes");
+        assert!(decrypt(&data, &Key::passphrase("incorrect horse")).is_err());
+        let mut damaged = data.clone();
+        let last = damaged.len() - 1;
+        damaged[last] ^= 1;
+        assert!(decrypt(&damaged, &key).is_err());
+    }
+}
@@ -0,0 +1,228 @@
+/*!
+
+Structured events describing changes to a library.
+
+`diff` compares two versions of a bibliography and produces one
+`LibraryEvent` per added, changed or removed entry, including the field
+level differences. Events are delivered through an `EventSink`:
+
+- `JsonLines` writes one JSON object per line, for logs and pipes;
+- an `mpsc::Sender<LibraryEvent>` forwards them to another thread;
+- `Webhook` (`net` feature) POSTs them as JSON to a URL.
+
+A webhook request body looks like `{"events": [{"event": "changed",
+"key": "Cox-CFT", "fields": [{"field": "year", "old": "2012",
+"new": "2013"}]}]}`.
+
+*/
+
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+use std::sync::mpsc::Sender;
+use crate::bibtex::data::Entry;
+use crate::compare::{compare, FieldDiff, FieldRow};
+use crate::json::JsonValue;
+#[cfg(feature = "net")]
+use crate::net::{expect_success, HttpClient};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventKind {
+    Added,
+    Changed,
+    Removed,
+}
+
+impl EventKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            EventKind::Added => "added",
+            EventKind::Changed => "changed",
+            EventKind::Removed => "removed",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LibraryEvent {
+    pub kind: EventKind,
+    pub key: String,
+    /** Fields that differ; for added and removed entries, all of them. */
+    pub fields: Vec<FieldRow>,
+}
+
+fn rows(entry: &Entry, diff: FieldDiff) -> Vec<FieldRow> {
+    let mut values = vec![("@type", entry.entry_type().name())];
+    values.extend(entry.field_names().into_iter().map(|f| (f, entry.get(f).unwrap_or_default())));
+    values.into_iter().map(|(name, v)| {
+        let v = Some(String::from(v));
+        let (left, right) = if diff == FieldDiff::LeftOnly { (v, None) } else { (None, v) };
+        FieldRow { name: String::from(name), left, right, diff }
+    }).collect()
+}
+
+impl LibraryEvent {
+    pub fn to_json(&self) -> JsonValue {
+        let opt = |v: &Option<String>| v.as_deref().map(JsonValue::str).unwrap_or(JsonValue::Null);
+        JsonValue::object(vec![
+            ("event", JsonValue::str(self.kind.name())),
+            ("key", JsonValue::str(&self.key)),
+            ("fields", JsonValue::Array(self.fields.iter().map(|r| JsonValue::object(vec![
+                ("field", JsonValue::str(&r.name)),
+                ("old", opt(&r.left)),
+                ("new", opt(&r.right)),
+            ])).collect())),
+        ])
+    }
+}
+
+/**
+Events turning `old` into `new`, matching entries by citation key. Added
+and changed entries come in the order of `new`, removed ones after them in
+the order of `old`.
+*/
+pub fn diff(old: &[Entry], new: &[Entry]) -> Vec<LibraryEvent> {
+    let mut by_key: HashMap<&str, &Entry> = HashMap::new();
+    for entry in old {
+        // the first of several entries with a key, as a scan would find it
+        by_key.entry(entry.key()).or_insert(entry);
+    }
+    let mut out = Vec::new();
+    for entry in new {
+        let key = String::from(entry.key());
+        match by_key.get(entry.key()) {
+            None => out.push(LibraryEvent { kind: EventKind::Added, key, fields: rows(entry, FieldDiff::RightOnly) }),
+            Some(o) => {
+                let fields: Vec<FieldRow> = compare(o, entry).into_iter().filter(|r| r.diff != FieldDiff::Same).collect();
+                if !fields.is_empty() {
+                    out.push(LibraryEvent { kind: EventKind::Changed, key, fields });
+                }
+            }
+        }
+    }
+    let new_keys: HashSet<&str> = new.iter().map(Entry::key).collect();
+    for entry in old {
+        if !new_keys.contains(entry.key()) {
+            out.push(LibraryEvent {
+                kind: EventKind::Removed,
+                key: String::from(entry.key()),
+                fields: rows(entry, FieldDiff::LeftOnly),
+            });
+        }
+    }
+    out
+}
+
+pub trait EventSink {
+    /**
+    Deliver a batch of events, typically those of one change to the library.
+    */
+    fn send(&mut self, events: &[LibraryEvent]) -> Result<(), String>;
+}
+
+/**
+Writes every event as a single line of JSON.
+*/
+pub struct JsonLines<W: Write>(pub W);
+
+impl<W: Write> EventSink for JsonLines<W> {
+    fn send(&mut self, events: &[LibraryEvent]) -> Result<(), String> {
+        for e in events {
+            writeln!(self.0, "{}", e.to_json()).map_err(|e| e.to_string())?;
+        }
+        self.0.flush().map_err(|e| e.to_string())
+    }
+}
+
+impl EventSink for Sender<LibraryEvent> {
+    fn send(&mut self, events: &[LibraryEvent]) -> Result<(), String> {
+        for e in events {
+            Sender::send(self, e.clone()).map_err(|_| String::from("event receiver has gone away"))?;
+        }
+        Ok(())
+    }
+}
+
+/**
+POSTs each batch of events as one JSON document.
+*/
+#[cfg(feature = "net")]
+pub struct Webhook<C: HttpClient> {
+    pub client: C,
+    pub url: String,
+}
+
+#[cfg(feature = "net")]
+impl<C: HttpClient> EventSink for Webhook<C> {
+    fn send(&mut self, events: &[LibraryEvent]) -> Result<(), String> {
+        if events.is_empty() {
+            return Ok(());
+        }
+        let body = JsonValue::object(vec![
+            ("events", JsonValue::Array(events.iter().map(LibraryEvent::to_json).collect())),
+        ]);
+        let response = self.client.post(&self.url, &[("Content-Type", "application/json")], &body.to_string())
+            .map_err(|e| e.to_string())?;
+        expect_success(&self.url, response).map(|_| ()).map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::bibtex::data::BibType;
+
+    fn entry(key: &str, year: &str) -> Entry {
+        let mut e = Entry::new(BibType::Book, key);
+        e.set("year", year);
+        e
+    }
+
+    #[test]
+    fn test_diff() {
+        let events = diff(&[entry("a", "2012"), entry("b", "2000")], &[entry("a", "2013"), entry("c", "1999")]);
+        let kinds: Vec<(EventKind, &str)> = events.iter().map(|e| (e.kind, e.key.as_str())).collect();
+        assert_eq!(kinds, vec![(EventKind::Changed, "a"), (EventKind::Added, "c"), (EventKind::Removed, "b")]);
+        assert_eq!(events[0].to_json().to_string(),
+            r#"{"event":"changed","key":"a","fields":[{"field":"year","old":"2012","new":"2013"}]}"#);
+        assert_eq!(events[1].fields[0].right.as_deref(), Some("book"));
+        assert_eq!(events[2].fields[1].left.as_deref(), Some("2000"));
+    }
+
+    #[test]
+    fn test_sinks() {
+        let events = diff(&[], &[entry("a", "2012")]);
+        let mut out = JsonLines(Vec::new());
+        out.send(&events).unwrap();
+        assert_eq!(String::from_utf8(out.0).unwrap().lines().count(), 1);
+
+        let (mut tx, rx) = std::sync::mpsc::channel();
+        EventSink::send(&mut tx, &events).unwrap();
+        assert_eq!(rx.recv().unwrap().key, "a");
+    }
+
+    #[cfg(feature = "net")]
+    #[test]
+    fn test_webhook() {
+        use std::cell::RefCell;
+        use crate::net::{NetError, Response};
+
+        struct Recorder(RefCell<Vec<String>>);
+        impl HttpClient for Recorder {
+            fn get(&self, _: &str, _: &[(&str, &str)]) -> Result<Response, NetError> {
+                unreachable!()
+            }
+            fn post(&self, _: &str, _: &[(&str, &str)], body: &str) -> Result<Response, NetError> {
+                self.0.borrow_mut().push(String::from(body));
+                Ok(Response { status: 204, headers: vec![], body: String::new() })
+            }
+        }
+
+        let mut hook = Webhook { client: Recorder(RefCell::new(vec![])), url: String::from("https://hooks.example.org/bib") };
+        hook.send(&diff(&[entry("a", "1")], &[])).unwrap();
+        hook.send(&[]).unwrap();
+        let sent = hook.client.0.borrow();
+        assert_eq!(sent.len(), 1);
+        assert!(sent[0].starts_with(r#"{"events":[{"event":"removed","key":"a""#));
+    }
+}
@@ -0,0 +1,139 @@
+/*!
+
+Bibliography file formats and their detection.
+
+Tools reading from a pipe have no file name to go by, so `detect` sniffs
+the content; `from_path` uses the extension when there is one.
+
+*/
+
+use std::fmt;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Format {
+    BibTeX,
+    CslJson,
+    Ris,
+    /** `CITATION.cff` (`software`). */
+    Cff,
+    /** `codemeta.json` (`software`). */
+    CodeMeta,
+}
+
+impl Format {
+    pub const ALL: [Format; 5] = [Format::BibTeX, Format::CslJson, Format::Ris, Format::Cff, Format::CodeMeta];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Format::BibTeX => "bibtex",
+            Format::CslJson => "csl-json",
+            Format::Ris => "ris",
+            Format::Cff => "cff",
+            Format::CodeMeta => "codemeta",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Format> {
+        match name.to_lowercase().as_str() {
+            "bibtex" | "biblatex" | "bib" => Some(Format::BibTeX),
+            "csl-json" | "csljson" | "csl" | "json" => Some(Format::CslJson),
+            "ris" => Some(Format::Ris),
+            "cff" | "citation.cff" => Some(Format::Cff),
+            "codemeta" => Some(Format::CodeMeta),
+            _ => None,
+        }
+    }
+
+    /**
+    Format implied by a file extension, or by the name of a `codemeta.json`;
+    `None` for `-` and unknown extensions.
+    */
+    pub fn from_path(path: &str) -> Option<Format> {
+        let path = Path::new(path);
+        if path.file_name()?.to_str()?.eq_ignore_ascii_case("codemeta.json") {
+            return Some(Format::CodeMeta);
+        }
+        let ext = path.extension()?.to_str()?.to_lowercase();
+        match ext.as_str() {
+            "bib" | "bibtex" => Some(Format::BibTeX),
+            "json" | "csljson" => Some(Format::CslJson),
+            "ris" => Some(Format::Ris),
+            "cff" => Some(Format::Cff),
+            _ => None,
+        }
+    }
+
+    /**
+    Guess the format of `content`. BibTeX is recognised by an `@type{`
+    entry start, RIS by a `TY  - ` tag, CFF by its `cff-version` and
+    CSL-JSON by a leading `[` or `{`, unless a CodeMeta `@context` follows.
+    */
+    pub fn detect(content: &str) -> Option<Format> {
+        let trimmed = content.trim_start_matches('\u{feff}').trim_start();
+        if trimmed.starts_with('{') && trimmed.contains("\"@context\"") && trimmed.contains("codemeta") {
+            return Some(Format::CodeMeta);
+        }
+        if trimmed.starts_with('[') || trimmed.starts_with('{') {
+            return Some(Format::CslJson);
+        }
+        if trimmed.lines().take(20).any(|l| l.starts_with("TY  - ")) {
+            return Some(Format::Ris);
+        }
+        if trimmed.lines().take(20).any(|l| l.starts_with("cff-version:")) {
+            return Some(Format::Cff);
+        }
+        let mut rest = trimmed;
+        while let Some(pos) = rest.find('@') {
+            let after = &rest[pos + 1..];
+            let name_len = after.find(|c: char| !c.is_ascii_alphabetic()).unwrap_or(after.len());
+            if name_len > 0 && after[name_len..].trim_start().starts_with(['{', '(']) {
+                return Some(Format::BibTeX);
+            }
+            rest = after;
+        }
+        None
+    }
+
+    /**
+    Format of an input, preferring its extension and falling back to
+    sniffing the content.
+    */
+    pub fn resolve(path: Option<&str>, content: &str) -> Option<Format> {
+        path.and_then(Format::from_path).or_else(|| Format::detect(content))
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(Format::detect("% refs\n\n@Book { Cox-CFT,\n}"), Some(Format::BibTeX));
+        assert_eq!(Format::detect("mail me at a@b.org\n@article{x,}"), Some(Format::BibTeX));
+        assert_eq!(Format::detect("\u{feff}  [{\"id\": \"x\"}]"), Some(Format::CslJson));
+        assert_eq!(Format::detect("TY  - JOUR\nAU  - Cox, David\nER  - \n"), Some(Format::Ris));
+        assert_eq!(Format::detect("# citation\ncff-version: 1.2.0\ntitle: x\n"), Some(Format::Cff));
+        assert_eq!(Format::detect("{\"@context\": \"https://doi.org/10.5063/schema/codemeta-2.0\"}"), Some(Format::CodeMeta));
+        assert_eq!(Format::detect("nothing to see"), None);
+    }
+
+    #[test]
+    fn test_resolve() {
+        assert_eq!(Format::from_path("refs.BIB"), Some(Format::BibTeX));
+        assert_eq!(Format::from_path("-"), None);
+        assert_eq!(Format::resolve(Some("-"), "@misc{a,}"), Some(Format::BibTeX));
+        assert_eq!(Format::resolve(Some("x.ris"), "@misc{a,}"), Some(Format::Ris));
+        assert_eq!(Format::from_name("CSL-JSON"), Some(Format::CslJson));
+        assert_eq!(Format::from_path("sw/CITATION.cff"), Some(Format::Cff));
+        assert_eq!(Format::from_path("sw/codemeta.json"), Some(Format::CodeMeta));
+    }
+}
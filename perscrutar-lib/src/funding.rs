@@ -0,0 +1,272 @@
+/*!
+
+Funder and grant metadata.
+
+There is no standard BibTeX field for funding, so perscrutar uses a single
+`funding` field listing one funder per `;`-separated item, optionally with
+its Open Funder Registry DOI and the award numbers:
+
+```text
+funding = {National Science Foundation (10.13039/100000001): CCF-1234567, CNS-7654321;
+           European Research Council: 101001234}
+```
+
+The legacy field name `grants` is read as well. `report` groups a set of
+entries by funder and award, which is what grant reporting asks for.
+
+*/
+
+use std::collections::BTreeMap;
+use crate::bibtex::data::Entry;
+
+/**
+Fields read for funding information, in order.
+*/
+pub const FUNDING_FIELDS: [&str; 2] = ["funding", "grants"];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Funder {
+    pub name: String,
+    /** Open Funder Registry DOI, e.g. `10.13039/100000001`. */
+    pub doi: Option<String>,
+}
+
+impl Funder {
+    /**
+    Identity used to group works: the registry DOI when known, otherwise
+    the lowercased name.
+    */
+    pub fn id(&self) -> String {
+        match &self.doi {
+            Some(doi) => doi.to_lowercase(),
+            None => self.name.to_lowercase(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Grant {
+    pub funder: Funder,
+    pub awards: Vec<String>,
+}
+
+fn parse_item(item: &str) -> Option<Grant> {
+    let item = item.split_whitespace().collect::<Vec<&str>>().join(" ");
+    if item.is_empty() {
+        return None;
+    }
+    // the award list starts after the first colon that is not inside the DOI parenthesis
+    let paren_end = item.find(')').unwrap_or(0);
+    let (head, awards) = match item[paren_end..].find(':') {
+        Some(pos) => (&item[..paren_end + pos], &item[paren_end + pos + 1..]),
+        None => (item.as_str(), ""),
+    };
+    let (name, doi) = match (head.find('('), head.rfind(')')) {
+        (Some(open), Some(close)) if open < close => {
+            (head[..open].trim(), Some(head[open + 1..close].trim().to_string()))
+        }
+        _ => (head.trim(), None),
+    };
+    let awards = awards.split(',')
+        .map(|a| a.trim())
+        .filter(|a| !a.is_empty())
+        .map(String::from)
+        .collect();
+    Some(Grant {
+        funder: Funder { name: String::from(name), doi },
+        awards,
+    })
+}
+
+pub fn parse_funding(value: &str) -> Vec<Grant> {
+    value.split(';').filter_map(parse_item).collect()
+}
+
+pub fn format_funding(grants: &[Grant]) -> String {
+    grants.iter().map(|g| {
+        let mut s = g.funder.name.clone();
+        if let Some(doi) = &g.funder.doi {
+            s.push_str(&format!(" ({})", doi));
+        }
+        if !g.awards.is_empty() {
+            s.push_str(": ");
+            s.push_str(&g.awards.join(", "));
+        }
+        s
+    }).collect::<Vec<String>>().join("; ")
+}
+
+/**
+Add `grant` to `grants`, merging it into an existing grant of the same
+funder. Returns true if anything changed.
+*/
+pub fn merge_grant(grants: &mut Vec<Grant>, grant: Grant) -> bool {
+    match grants.iter_mut().find(|g| g.funder.id() == grant.funder.id()
+        || g.funder.name.eq_ignore_ascii_case(&grant.funder.name))
+    {
+        Some(existing) => {
+            let mut changed = false;
+            if existing.funder.doi.is_none() && grant.funder.doi.is_some() {
+                existing.funder.doi = grant.funder.doi;
+                changed = true;
+            }
+            for a in grant.awards {
+                if !existing.awards.contains(&a) {
+                    existing.awards.push(a);
+                    changed = true;
+                }
+            }
+            changed
+        }
+        None => {
+            grants.push(grant);
+            true
+        }
+    }
+}
+
+impl Entry {
+    pub fn funding(&self) -> Vec<Grant> {
+        let mut grants = Vec::new();
+        for f in FUNDING_FIELDS.iter() {
+            if let Some(v) = self.get(f) {
+                for g in parse_funding(v) {
+                    merge_grant(&mut grants, g);
+                }
+            }
+        }
+        grants
+    }
+
+    /**
+    Store `grants` in the canonical `funding` field, dropping `grants`.
+    */
+    pub fn set_funding(&mut self, grants: &[Grant]) {
+        self.remove("grants");
+        if grants.is_empty() {
+            self.remove("funding");
+        } else {
+            self.set("funding", &format_funding(grants));
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FunderWorks {
+    pub funder: Funder,
+    /** Award number and the keys of the works acknowledging it. */
+    pub awards: Vec<(String, Vec<String>)>,
+    /** Keys of all works acknowledging this funder, with or without award. */
+    pub keys: Vec<String>,
+}
+
+/**
+Group `entries` by funder, then by award. Funders are ordered by name,
+awards and keys alphabetically.
+*/
+pub fn report<'a, I: IntoIterator<Item = &'a Entry>>(entries: I) -> Vec<FunderWorks> {
+    type Awards = BTreeMap<String, Vec<String>>;
+    let mut by_funder: BTreeMap<String, (Funder, Awards, Vec<String>)> = BTreeMap::new();
+    for e in entries {
+        for g in e.funding() {
+            let slot = by_funder.entry(g.funder.id())
+                .or_insert_with(|| (g.funder.clone(), BTreeMap::new(), Vec::new()));
+            if slot.0.doi.is_none() {
+                slot.0.doi = g.funder.doi.clone();
+            }
+            if !slot.2.iter().any(|k| k == e.key()) {
+                slot.2.push(e.key().to_string());
+            }
+            for a in g.awards {
+                let keys = slot.1.entry(a).or_default();
+                if !keys.iter().any(|k| k == e.key()) {
+                    keys.push(e.key().to_string());
+                }
+            }
+        }
+    }
+    let mut works: Vec<FunderWorks> = by_funder.into_values().map(|(funder, awards, mut keys)| {
+        keys.sort();
+        FunderWorks {
+            funder,
+            awards: awards.into_iter().map(|(a, mut k)| {
+                k.sort();
+                (a, k)
+            }).collect(),
+            keys,
+        }
+    }).collect();
+    works.sort_by_key(|w| w.funder.name.to_lowercase());
+    works
+}
+
+pub fn render_report(works: &[FunderWorks]) -> String {
+    let mut out = String::new();
+    for w in works {
+        out.push_str(&w.funder.name);
+        if let Some(doi) = &w.funder.doi {
+            out.push_str(&format!(" ({})", doi));
+        }
+        out.push_str(&format!(": {} work(s)\n", w.keys.len()));
+        for (award, keys) in w.awards.iter() {
+            out.push_str(&format!("  {}: {}\n", award, keys.join(", ")));
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::bibtex::data::BibType;
+
+    #[test]
+    fn test_parse_format() {
+        let v = "National Science Foundation (10.13039/100000001): CCF-1234567, CNS-7654321;\n   European Research Council: 101001234; Internal";
+        let grants = parse_funding(v);
+        assert_eq!(grants.len(), 3);
+        assert_eq!(grants[0].funder.doi.as_deref(), Some("10.13039/100000001"));
+        assert_eq!(grants[0].awards, vec!["CCF-1234567", "CNS-7654321"]);
+        assert_eq!(grants[1].funder.name, "European Research Council");
+        assert!(grants[2].awards.is_empty());
+        assert_eq!(format_funding(&grants),
+            "National Science Foundation (10.13039/100000001): CCF-1234567, CNS-7654321; European Research Council: 101001234; Internal");
+    }
+
+    #[test]
+    fn test_report() {
+        let mut a = Entry::new(BibType::Article, "a");
+        a.set("funding", "NSF (10.13039/100000001): CCF-1");
+        let mut b = Entry::new(BibType::Article, "b");
+        b.set("grants", "National Science Foundation (10.13039/100000001): CCF-1, CCF-2");
+        let mut c = Entry::new(BibType::Article, "c");
+        c.set("funding", "ERC");
+
+        let works = report(vec![&a, &b, &c]);
+        assert_eq!(works.len(), 2);
+        assert_eq!(works[0].funder.name, "ERC");
+        assert_eq!(works[1].keys, vec!["a", "b"]);
+        assert_eq!(works[1].awards, vec![
+            (String::from("CCF-1"), vec![String::from("a"), String::from("b")]),
+            (String::from("CCF-2"), vec![String::from("b")]),
+        ]);
+        assert!(render_report(&works).contains("  CCF-1: a, b\n"));
+    }
+
+    #[test]
+    fn test_set_funding_merges() {
+        let mut e = Entry::new(BibType::Article, "a");
+        e.set("grants", "ERC: 1");
+        e.set("funding", "ERC: 2");
+        let mut grants = e.funding();
+        assert_eq!(grants.len(), 1);
+        assert!(merge_grant(&mut grants, Grant {
+            funder: Funder { name: String::from("ERC"), doi: Some(String::from("10.13039/501100000781")) },
+            awards: vec![String::from("1")],
+        }));
+        e.set_funding(&grants);
+        assert!(!e.has("grants"));
+        assert_eq!(e.get("funding"), Some("ERC (10.13039/501100000781): 2, 1"));
+    }
+}
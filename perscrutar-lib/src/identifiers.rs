@@ -0,0 +1,289 @@
+/*!
+
+Identifiers of works in free text.
+
+`detect` finds what identifies a work in text such as a pasted reference,
+an e-mail or a line of a document:
+
+- DOIs, bare or in `doi:` and `doi.org` form (`10.1093/comjnl/27.2.97`);
+- arXiv identifiers, after `arXiv:` or in an arXiv link, old style
+  (`hep-th/9901001`) or new (`2101.00001`), without their version;
+- ISBNs after `ISBN`, or bare ISBN-13s, whose check digit must be right;
+- PubMed ids after `PMID` or in a PubMed link;
+- other `http(s)` URLs;
+- citation keys, in `\cite`-like commands or as Pandoc's `@key` (see
+  `citations`).
+
+Text that is nothing but an identifier may also give it bare: a new-style
+arXiv id on its own is one, while in a sentence it could be any number.
+`Identifier::parse` reads such a single token.
+
+*/
+
+use crate::citations::{scan_latex, scan_markdown};
+use crate::formats::Format;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Identifier {
+    Doi(String),
+    /** Without its version. */
+    Arxiv(String),
+    /** Digits (and a final `X`) only. */
+    Isbn(String),
+    Pmid(String),
+    Url(String),
+    Key(String),
+}
+
+/** The DOIs in `text`: `10.`, a registrant code, `/` and a suffix. */
+pub fn find_dois(text: &str) -> Vec<&str> {
+    let mut out = Vec::new();
+    let mut from = 0;
+    while let Some(pos) = text[from..].find("10.") {
+        let start = from + pos;
+        let rest = &text[start + 3..];
+        let registrant = rest.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(rest.len());
+        from = start + 3;
+        if registrant < 4 || !rest[registrant..].starts_with('/') {
+            continue;
+        }
+        let suffix = &rest[registrant + 1..];
+        let len = suffix.find(|c: char| c.is_whitespace() || matches!(c, '"' | '<' | '}')).unwrap_or(suffix.len());
+        let doi = text[start..start + 3 + registrant + 1 + len].trim_end_matches(['.', ',', ';', ')', ']', '>', '\'']);
+        if doi.len() > 3 + registrant + 1 {
+            out.push(doi);
+            from = start + doi.len();
+        }
+    }
+    out
+}
+
+/** An arXiv identifier, old or new style, with any version removed. */
+fn arxiv_id(token: &str) -> Option<String> {
+    let id = token.trim_end_matches(".pdf");
+    let id = match id.rfind('v') {
+        Some(v) if v > 0 && !id[v + 1..].is_empty() && id[v + 1..].chars().all(|c| c.is_ascii_digit()) => &id[..v],
+        _ => id,
+    };
+    let new_style = id.split_once('.')
+        .map(|(ym, n)| ym.len() == 4 && (4..=5).contains(&n.len()) && ym.chars().chain(n.chars()).all(|c| c.is_ascii_digit()))
+        .unwrap_or(false);
+    let old_style = id.split_once('/')
+        .map(|(archive, n)| !archive.is_empty() && archive.chars().all(|c| c.is_ascii_lowercase() || c == '-' || c == '.')
+            && n.len() == 7 && n.chars().all(|c| c.is_ascii_digit()))
+        .unwrap_or(false);
+    (new_style || old_style).then(|| String::from(id))
+}
+
+/** The token starting at `text`, up to whitespace or closing punctuation. */
+fn token(text: &str) -> &str {
+    let end = text.find(|c: char| c.is_whitespace() || matches!(c, '"' | '<' | '>' | '?' | '#')).unwrap_or(text.len());
+    text[..end].trim_end_matches(['.', ',', ';', ')', ']', '\''])
+}
+
+/** Each `prefix` (compared case-insensitively) in `text`, with the token after it. */
+fn after<'a>(text: &'a str, prefixes: &[&str]) -> Vec<&'a str> {
+    let lower = text.to_ascii_lowercase();
+    let mut found: Vec<(usize, &str)> = Vec::new();
+    for prefix in prefixes {
+        found.extend(lower.match_indices(prefix).map(|(i, _)| (i, token(text[i + prefix.len()..].trim_start()))));
+    }
+    found.sort();
+    found.into_iter().map(|(_, t)| t).collect()
+}
+
+/** The arXiv identifiers given with `arXiv:` or in arXiv links. */
+pub fn find_arxiv(text: &str) -> Vec<String> {
+    let mut out: Vec<String> = Vec::new();
+    for id in after(text, &["arxiv.org/abs/", "arxiv.org/pdf/", "arxiv:"]).into_iter().filter_map(arxiv_id) {
+        if !out.contains(&id) {
+            out.push(id);
+        }
+    }
+    out
+}
+
+/** Whether `digits` is a valid ISBN-10 or ISBN-13. */
+pub fn is_isbn(digits: &str) -> bool {
+    let values: Vec<u32> = digits.chars().enumerate()
+        .map_while(|(i, c)| c.to_digit(10).or((c == 'X' && i == 9 && digits.len() == 10).then_some(10)))
+        .collect();
+    match values.len() {
+        _ if values.len() != digits.len() => false,
+        10 => values.iter().enumerate().map(|(i, v)| (10 - i as u32) * v).sum::<u32>().is_multiple_of(11),
+        13 => values.iter().enumerate().map(|(i, v)| if i % 2 == 0 { *v } else { 3 * v }).sum::<u32>().is_multiple_of(10),
+        _ => false,
+    }
+}
+
+/** `token` without hyphens and spaces, if that is an ISBN. */
+pub fn normalize_isbn(token: &str) -> Option<String> {
+    let digits: String = token.chars().filter(|c| !matches!(c, '-' | ' ')).map(|c| c.to_ascii_uppercase()).collect();
+    is_isbn(&digits).then_some(digits)
+}
+
+/**
+The ISBNs after `ISBN` (`ISBN 978-0-201-...`, `ISBN-13: ...`), and bare
+ISBN-13s starting with 978 or 979.
+*/
+pub fn find_isbns(text: &str) -> Vec<String> {
+    let mut out: Vec<String> = Vec::new();
+    let lower = text.to_ascii_lowercase();
+    let mut candidates: Vec<(usize, String)> = Vec::new();
+    for (i, _) in lower.match_indices("isbn") {
+        let rest = &text[i + 4..];
+        let rest = rest.strip_prefix("-13").or_else(|| rest.strip_prefix("-10")).unwrap_or(rest).trim_start_matches([':', ' ']);
+        let len = rest.find(|c: char| !(c.is_ascii_digit() || matches!(c, '-' | ' ' | 'X' | 'x'))).unwrap_or(rest.len());
+        candidates.push((i, String::from(rest[..len].trim_end_matches([' ', '-']))));
+    }
+    let starts = text.match_indices(|c: char| c.is_ascii_digit())
+        .map(|(i, _)| i)
+        .filter(|i| !text[..*i].ends_with(|c: char| c.is_ascii_digit() || c == '-'));
+    for i in starts {
+        let rest = &text[i..];
+        let len = rest.find(|c: char| !(c.is_ascii_digit() || c == '-')).unwrap_or(rest.len());
+        let candidate = &rest[..len];
+        if candidate.starts_with("978") || candidate.starts_with("979") {
+            candidates.push((i, String::from(candidate)));
+        }
+    }
+    candidates.sort();
+    for (_, candidate) in candidates {
+        if let Some(isbn) = normalize_isbn(&candidate).filter(|i| !out.contains(i)) {
+            out.push(isbn);
+        }
+    }
+    out
+}
+
+/** The PubMed ids after `PMID` or in PubMed links. */
+pub fn find_pmids(text: &str) -> Vec<String> {
+    let mut out: Vec<String> = Vec::new();
+    for token in after(text, &["pmid:", "pmid ", "pubmed.ncbi.nlm.nih.gov/", "ncbi.nlm.nih.gov/pubmed/"]) {
+        let id = token.trim_end_matches('/');
+        if (1..=9).contains(&id.len()) && id.chars().all(|c| c.is_ascii_digit()) && !out.iter().any(|o| o == id) {
+            out.push(String::from(id));
+        }
+    }
+    out
+}
+
+/** The `http` and `https` URLs. */
+pub fn find_urls(text: &str) -> Vec<&str> {
+    let mut out: Vec<&str> = Vec::new();
+    for (i, _) in text.match_indices("http") {
+        let rest = &text[i..];
+        if !(rest.starts_with("http://") || rest.starts_with("https://")) {
+            continue;
+        }
+        let len = rest.find(|c: char| c.is_whitespace() || matches!(c, '"' | '<' | '>' | '}')).unwrap_or(rest.len());
+        let url = rest[..len].trim_end_matches(['.', ',', ';', ')', ']', '\'']);
+        if url.len() > "https://".len() && !out.contains(&url) {
+            out.push(url);
+        }
+    }
+    out
+}
+
+impl Identifier {
+    /**
+    The identifier `token` is on its own, including bare arXiv ids, ISBNs
+    and DOIs, and `https://doi.org/...` or arXiv links.
+    */
+    pub fn parse(token: &str) -> Option<Identifier> {
+        let token = token.trim();
+        if token.contains(char::is_whitespace) && !token.starts_with("ISBN") {
+            return None;
+        }
+        if let Some(doi) = find_dois(token).first().filter(|d| token.ends_with(*d)) {
+            return Some(Identifier::Doi(String::from(*doi)));
+        }
+        if let Some(id) = find_arxiv(token).into_iter().next().or_else(|| arxiv_id(token)) {
+            return Some(Identifier::Arxiv(id));
+        }
+        if let Some(pmid) = find_pmids(token).into_iter().next() {
+            return Some(Identifier::Pmid(pmid));
+        }
+        if let Some(isbn) = normalize_isbn(token.trim_start_matches("ISBN").trim_start_matches(':').trim()) {
+            return Some(Identifier::Isbn(isbn));
+        }
+        find_urls(token).first().filter(|u| **u == token).map(|u| Identifier::Url(String::from(*u)))
+    }
+
+    pub fn value(&self) -> &str {
+        match self {
+            Identifier::Doi(v) | Identifier::Arxiv(v) | Identifier::Isbn(v)
+            | Identifier::Pmid(v) | Identifier::Url(v) | Identifier::Key(v) => v,
+        }
+    }
+}
+
+/**
+The identifiers in `text`, by kind (DOIs, arXiv ids, ISBNs, PubMed ids,
+URLs, citation keys) and within each kind in order of appearance, each
+once. Links to a DOI, an arXiv preprint or a PubMed record give that
+identifier rather than a URL. Pandoc keys are not looked for in BibTeX,
+whose `@type` would pass for one.
+*/
+pub fn detect(text: &str) -> Vec<Identifier> {
+    if let Some(identifier) = Identifier::parse(text) {
+        return vec![identifier];
+    }
+    let mut out: Vec<Identifier> = Vec::new();
+    out.extend(find_dois(text).into_iter().map(|d| Identifier::Doi(String::from(d))));
+    out.extend(find_arxiv(text).into_iter().filter(|a| !text.contains(&format!("arXiv.{}", a))).map(Identifier::Arxiv));
+    out.extend(find_isbns(text).into_iter().map(Identifier::Isbn));
+    out.extend(find_pmids(text).into_iter().map(Identifier::Pmid));
+    let known = |url: &str| url.contains("doi.org/10.") || url.contains("arxiv.org/") || url.contains("pubmed");
+    out.extend(find_urls(text).into_iter().filter(|u| !known(u)).map(|u| Identifier::Url(String::from(u))));
+    let mut citations = scan_latex("", text);
+    if Format::detect(text) != Some(Format::BibTeX) {
+        citations.extend(scan_markdown("", text));
+    }
+    citations.sort_by(|a, b| a.location.cmp(&b.location));
+    for citation in citations {
+        let key = Identifier::Key(citation.key);
+        if !out.contains(&key) {
+            out.push(key);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let parse = |s: &str| Identifier::parse(s);
+        assert_eq!(parse(" https://doi.org/10.1093/comjnl/27.2.97 "), Some(Identifier::Doi(String::from("10.1093/comjnl/27.2.97"))));
+        assert_eq!(parse("2101.00001v3"), Some(Identifier::Arxiv(String::from("2101.00001"))));
+        assert_eq!(parse("https://arxiv.org/pdf/hep-th/9901001v1.pdf"), Some(Identifier::Arxiv(String::from("hep-th/9901001"))));
+        assert_eq!(parse("978-0-201-53082-7"), Some(Identifier::Isbn(String::from("9780201530827"))));
+        assert_eq!(parse("ISBN 0-201-53082-1"), Some(Identifier::Isbn(String::from("0201530821"))));
+        assert_eq!(parse("978-0-201-53082-8"), None);
+        assert_eq!(parse("https://pubmed.ncbi.nlm.nih.gov/12345678/"), Some(Identifier::Pmid(String::from("12345678"))));
+        assert_eq!(parse("https://example.org/paper"), Some(Identifier::Url(String::from("https://example.org/paper"))));
+        assert_eq!(parse("Knuth 1984"), None);
+    }
+
+    #[test]
+    fn test_detect() {
+        let text = "As \\citet{knuth84} shows (doi:10.1093/comjnl/27.2.97; see also arXiv:2101.00001v2 and\n\
+            https://doi.org/10.48550/arXiv.2101.00001), ISBN-13: 978-0-201-53082-7, PMID: 12345678,\n\
+            https://example.org/lp. Mail me at a@b.org, or cite [@cox2013; @knuth84].";
+        assert_eq!(detect(text), vec![
+            Identifier::Doi(String::from("10.1093/comjnl/27.2.97")),
+            Identifier::Doi(String::from("10.48550/arXiv.2101.00001")),
+            Identifier::Isbn(String::from("9780201530827")),
+            Identifier::Pmid(String::from("12345678")),
+            Identifier::Url(String::from("https://example.org/lp")),
+            Identifier::Key(String::from("knuth84")),
+            Identifier::Key(String::from("cox2013")),
+        ]);
+        assert_eq!(detect("@article{knuth84,\n  doi = {10.1093/comjnl/27.2.97}\n}"), vec![Identifier::Doi(String::from("10.1093/comjnl/27.2.97"))]);
+        assert!(detect("Nothing to see, 1234.5678 times.").is_empty());
+    }
+}
@@ -0,0 +1,193 @@
+/*!
+
+Turning pasted text into entries.
+
+What gets copied when adding a paper varies: a DOI or a `doi.org` link, an
+arXiv identifier or abstract page, an ISBN, a BibTeX entry, a RIS or CSL-JSON
+export, or a reference copied from a reference list. `detect` tells these
+apart:
+
+```text
+10.1093/comjnl/27.2.97                          Kind::Doi
+https://arxiv.org/abs/2101.00001v2              Kind::Arxiv("2101.00001")
+ISBN 978-0-201-89683-1                          Kind::Isbn("9780201896831")
+@article{knuth84, ...}                          Kind::BibTeX
+TY  - JOUR ...                                  Kind::Ris
+[{"type": "article-journal", ...}]              Kind::CslJson
+D. E. Knuth. Literate programming. 1984.        Kind::Reference
+```
+
+The exports are converted without the network by `convert`. The others
+are looked up with `import` (`net` feature): a DOI through the DOI
+resolver, an arXiv identifier through the arXiv API (see `lookup::arxiv`),
+an ISBN through Open Library or Google Books (see `lookup::isbn`), and a
+reference through Crossref's search for the work it best matches,
+which may not be the one meant.
+
+*/
+
+use std::fmt;
+use crate::bibtex::data::Entry;
+use crate::bibtex::parser::{parse_with, Macros, ParseOptions};
+use crate::csl::from_csl_json;
+use crate::formats::Format;
+use crate::identifiers::{find_arxiv, find_dois, Identifier};
+use crate::json;
+use crate::lookup::LookupError;
+use crate::ris::from_ris;
+#[cfg(feature = "net")]
+use crate::{lookup::{arxiv, crossref, doi, isbn}, net::HttpClient};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Kind {
+    Doi(String),
+    /** An arXiv identifier without its version. */
+    Arxiv(String),
+    /** A valid ISBN, without hyphens. */
+    Isbn(String),
+    BibTeX,
+    Ris,
+    CslJson,
+    /** Free text, taken to be a formatted reference. */
+    Reference,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ImportError {
+    /** Nothing but whitespace was given. */
+    Empty,
+    /** An export did not parse. */
+    Invalid(String),
+    /** The kind needs a lookup, which `convert` does not do. */
+    NeedsLookup(Kind),
+    Lookup(LookupError),
+    /** The lookup found no work for the reference. */
+    NotFound,
+}
+
+impl fmt::Display for ImportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImportError::Empty => f.write_str("nothing to import"),
+            ImportError::Invalid(msg) => f.write_str(msg),
+            ImportError::NeedsLookup(_) => f.write_str("a DOI, arXiv identifier, ISBN or reference needs looking up online"),
+            ImportError::Lookup(e) => write!(f, "{}", e),
+            ImportError::NotFound => f.write_str("no matching work found"),
+        }
+    }
+}
+
+impl std::error::Error for ImportError {}
+
+impl From<LookupError> for ImportError {
+    fn from(e: LookupError) -> Self {
+        ImportError::Lookup(e)
+    }
+}
+
+/** What `text` is, or `None` if it is blank. */
+pub fn detect(text: &str) -> Option<Kind> {
+    let text = text.trim();
+    if text.is_empty() {
+        return None;
+    }
+    match Format::detect(text) {
+        Some(Format::BibTeX) => return Some(Kind::BibTeX),
+        Some(Format::Ris) => return Some(Kind::Ris),
+        // a reference list item may start with `[1]`
+        Some(Format::CslJson) if json::parse(text).is_ok() => return Some(Kind::CslJson),
+        _ => {}
+    }
+    // an arXiv DOI is looked up as a DOI
+    if let Some(doi) = find_dois(text).first() {
+        return Some(Kind::Doi(String::from(*doi)));
+    }
+    match Identifier::parse(text) {
+        Some(Identifier::Arxiv(id)) => Some(Kind::Arxiv(id)),
+        Some(Identifier::Isbn(isbn)) => Some(Kind::Isbn(isbn)),
+        _ => Some(find_arxiv(text).into_iter().next().map(Kind::Arxiv).unwrap_or(Kind::Reference)),
+    }
+}
+
+/** The entries in an export; other kinds of text need `import`. */
+pub fn convert(text: &str) -> Result<Vec<Entry>, ImportError> {
+    let kind = detect(text).ok_or(ImportError::Empty)?;
+    let invalid = |e: &dyn fmt::Display| ImportError::Invalid(e.to_string());
+    match kind {
+        Kind::BibTeX => parse_with(text, &mut Macros::new(), ParseOptions::standard()).map_err(|e| invalid(&e)),
+        Kind::Ris => from_ris(text).map_err(|e| invalid(&e)),
+        Kind::CslJson => from_csl_json(text).map_err(|e| invalid(&e)),
+        kind => Err(ImportError::NeedsLookup(kind)),
+    }
+}
+
+/** The entries for `text`, looked up with `client` if need be. */
+#[cfg(feature = "net")]
+pub fn import<C: HttpClient>(client: &C, text: &str) -> Result<Vec<Entry>, ImportError> {
+    match convert(text) {
+        Err(ImportError::NeedsLookup(Kind::Doi(id))) => Ok(vec![doi::fetch_entry(client, &id)?]),
+        Err(ImportError::NeedsLookup(Kind::Arxiv(id))) => Ok(vec![arxiv::fetch_entry(client, &id)?]),
+        Err(ImportError::NeedsLookup(Kind::Isbn(number))) => Ok(vec![isbn::fetch_entry(client, &number)?]),
+        Err(ImportError::NeedsLookup(_)) => {
+            let reference = text.split_whitespace().collect::<Vec<_>>().join(" ");
+            let found = crossref::search_reference(client, &reference)?.ok_or(ImportError::NotFound)?;
+            Ok(vec![doi::fetch_entry(client, &found)?])
+        }
+        other => other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_detect() {
+        let doi = |s: &str| Some(Kind::Doi(String::from(s)));
+        assert_eq!(detect(" 10.1093/comjnl/27.2.97\n"), doi("10.1093/comjnl/27.2.97"));
+        assert_eq!(detect("https://doi.org/10.1145/359576.359579."), doi("10.1145/359576.359579"));
+        assert_eq!(detect("Knuth, D. E. (1984). Literate programming. doi:10.1093/comjnl/27.2.97"), doi("10.1093/comjnl/27.2.97"));
+        assert_eq!(detect("https://arxiv.org/abs/2101.00001v2"), Some(Kind::Arxiv(String::from("2101.00001"))));
+        assert_eq!(detect("arXiv:hep-th/9901001"), Some(Kind::Arxiv(String::from("hep-th/9901001"))));
+        assert_eq!(detect("https://doi.org/10.48550/arXiv.2101.00001"), doi("10.48550/arXiv.2101.00001"));
+        assert_eq!(detect("ISBN 0-201-89683-4"), Some(Kind::Isbn(String::from("0201896834"))));
+        assert_eq!(detect("[1] D. E. Knuth, Literate programming, 1984."), Some(Kind::Reference));
+        assert_eq!(detect("10.12/x"), Some(Kind::Reference));
+        assert_eq!(detect("  \n"), None);
+    }
+
+    #[test]
+    fn test_convert() {
+        let keys = |text: &str| convert(text).unwrap().into_iter().map(|e| String::from(e.key())).collect::<Vec<_>>();
+        assert_eq!(keys("Copied from the site:\n@article{knuth84,\n  title = {Literate programming}\n}\n"), vec!["knuth84"]);
+        assert_eq!(keys("TY  - JOUR\nAU  - Knuth, Donald\nPY  - 1984\nER  - \n"), vec!["knuth1984"]);
+        assert_eq!(keys(r#"{"id": "lp", "type": "book", "title": "Literate Programming"}"#), vec!["lp"]);
+        assert_eq!(convert("arXiv:2101.00001"), Err(ImportError::NeedsLookup(Kind::Arxiv(String::from("2101.00001")))));
+        assert_eq!(convert(""), Err(ImportError::Empty));
+    }
+
+    #[cfg(feature = "net")]
+    #[test]
+    fn test_import() {
+        use crate::net::{NetError, Response};
+
+        struct Canned;
+        impl HttpClient for Canned {
+            fn get(&self, url: &str, _: &[(&str, &str)]) -> Result<Response, NetError> {
+                let body = match url {
+                    "https://export.arxiv.org/api/query?id_list=2101.00001&max_results=1" =>
+                        "<feed><entry><id>http://arxiv.org/abs/2101.00001v1</id><published>2021-01-01T18:00:00Z</published>\
+                            <title>A preprint</title><author><name>Jane Doe</name></author></entry></feed>",
+                    url if url.starts_with("https://api.crossref.org/works?") => r#"{"message": {"items": []}}"#,
+                    url => panic!("unexpected request for {}", url),
+                };
+                Ok(Response { status: 200, headers: vec![], body: String::from(body) })
+            }
+        }
+
+        let entries = import(&Canned, "arxiv.org/pdf/2101.00001v1.pdf").unwrap();
+        assert_eq!((entries[0].key(), entries[0].get("eprint")), ("doe2021", Some("2101.00001")));
+        assert_eq!(import(&Canned, "Nobody. Nothing. 1999."), Err(ImportError::NotFound));
+    }
+}
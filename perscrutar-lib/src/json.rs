@@ -0,0 +1,439 @@
+/*!
+
+Minimal JSON support, enough to read web API responses and to write
+machine-readable output without pulling in a serialization framework.
+
+Objects keep their members in document order, so serializing a value that
+was built in a fixed order always yields the same bytes.
+
+`parse` follows the JSON grammar strictly: numbers are written as JSON
+writes them, so `nan`, `inf` and `+1` are errors, and arrays and objects
+nest at most `MAX_DEPTH` deep, so that hostile input cannot exhaust the
+stack.
+
+*/
+
+use std::fmt;
+use nom::{
+    branch::alt,
+    bytes::complete::{tag, take_while, take_while_m_n},
+    character::complete::{char, digit0, digit1, none_of, one_of, satisfy},
+    combinator::{cut, map, map_opt, map_res, opt, recognize, value},
+    error::{context, ContextError, FromExternalError, ParseError, VerboseError},
+    multi::{fold_many0, separated_list0},
+    sequence::{delimited, pair, preceded, separated_pair, terminated, tuple},
+    Err, IResult,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum JsonValue {
+    Null,
+    Str(String),
+    Boolean(bool),
+    Num(f64),
+    Array(Vec<JsonValue>),
+    Object(Vec<(String, JsonValue)>),
+}
+
+impl JsonValue {
+    /**
+    Member `key` of an object, `None` for missing members and non-objects.
+    */
+    pub fn get(&self, key: &str) -> Option<&JsonValue> {
+        match self {
+            JsonValue::Object(members) => members.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    pub fn get_mut(&mut self, key: &str) -> Option<&mut JsonValue> {
+        match self {
+            JsonValue::Object(members) => members.iter_mut().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    /**
+    Follow a path of object members, e.g. `&["message", "title"]`.
+    */
+    pub fn path(&self, keys: &[&str]) -> Option<&JsonValue> {
+        keys.iter().try_fold(self, |v, k| v.get(k))
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            JsonValue::Str(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            JsonValue::Num(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            JsonValue::Boolean(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[JsonValue]> {
+        match self {
+            JsonValue::Array(items) => Some(items),
+            _ => None,
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        *self == JsonValue::Null
+    }
+
+    /**
+    Convenience constructor for objects from string keys.
+    */
+    pub fn object<K: Into<String>>(members: Vec<(K, JsonValue)>) -> JsonValue {
+        JsonValue::Object(members.into_iter().map(|(k, v)| (k.into(), v)).collect())
+    }
+
+    pub fn str(s: &str) -> JsonValue {
+        JsonValue::Str(String::from(s))
+    }
+
+    /**
+    Serialize with two-space indentation.
+    */
+    pub fn to_pretty_string(&self) -> String {
+        let mut out = String::new();
+        write_value(&mut out, self, Some(0));
+        out
+    }
+}
+
+impl fmt::Display for JsonValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut out = String::new();
+        write_value(&mut out, self, None);
+        f.write_str(&out)
+    }
+}
+
+fn write_string(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+fn write_number(out: &mut String, n: f64) {
+    if !n.is_finite() {
+        out.push_str("null");
+    } else if n.fract() == 0.0 && n.abs() < 1e15 {
+        out.push_str(&format!("{}", n as i64));
+    } else {
+        out.push_str(&format!("{}", n));
+    }
+}
+
+fn newline(out: &mut String, indent: Option<usize>) {
+    if let Some(level) = indent {
+        out.push('\n');
+        out.push_str(&"  ".repeat(level));
+    }
+}
+
+fn write_value(out: &mut String, v: &JsonValue, indent: Option<usize>) {
+    let inner = indent.map(|i| i + 1);
+    match v {
+        JsonValue::Null => out.push_str("null"),
+        JsonValue::Boolean(b) => out.push_str(if *b { "true" } else { "false" }),
+        JsonValue::Num(n) => write_number(out, *n),
+        JsonValue::Str(s) => write_string(out, s),
+        JsonValue::Array(items) => {
+            if items.is_empty() {
+                out.push_str("[]");
+                return;
+            }
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                newline(out, inner);
+                write_value(out, item, inner);
+            }
+            newline(out, indent);
+            out.push(']');
+        }
+        JsonValue::Object(members) => {
+            if members.is_empty() {
+                out.push_str("{}");
+                return;
+            }
+            out.push('{');
+            for (i, (k, item)) in members.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                newline(out, inner);
+                write_string(out, k);
+                out.push(':');
+                if indent.is_some() {
+                    out.push(' ');
+                }
+                write_value(out, item, inner);
+            }
+            newline(out, indent);
+            out.push('}');
+        }
+    }
+}
+
+fn sp<'a, E: ParseError<&'a str>>(i: &'a str) -> IResult<&'a str, &'a str, E> {
+    take_while(|c| " \t\r\n".contains(c))(i)
+}
+
+fn hex4<'a, E: ParseError<&'a str>>(i: &'a str) -> IResult<&'a str, u32, E> {
+    map_opt(
+        take_while_m_n(4, 4, |c: char| c.is_ascii_hexdigit()),
+        |h: &str| u32::from_str_radix(h, 16).ok(),
+    )(i)
+}
+
+/**
+A `\uXXXX` escape, combining UTF-16 surrogate pairs.
+*/
+fn unicode_escape<'a, E: ParseError<&'a str>>(i: &'a str) -> IResult<&'a str, char, E> {
+    let (rest, hi) = preceded(char('u'), hex4)(i)?;
+    if (0xD800..0xDC00).contains(&hi) {
+        let (rest, lo) = preceded(tag("\\u"), hex4)(rest)?;
+        let c = 0x10000 + ((hi - 0xD800) << 10) + (lo.wrapping_sub(0xDC00) & 0x3FF);
+        return Ok((rest, char::from_u32(c).unwrap_or('\u{FFFD}')));
+    }
+    Ok((rest, char::from_u32(hi).unwrap_or('\u{FFFD}')))
+}
+
+fn string_char<'a, E: ParseError<&'a str>>(i: &'a str) -> IResult<&'a str, char, E> {
+    alt((
+        preceded(char('\\'), alt((
+            value('"', char('"')),
+            value('\\', char('\\')),
+            value('/', char('/')),
+            value('\u{8}', char('b')),
+            value('\u{c}', char('f')),
+            value('\n', char('n')),
+            value('\r', char('r')),
+            value('\t', char('t')),
+            unicode_escape,
+        ))),
+        none_of("\"\\"),
+    ))(i)
+}
+
+fn string<'a, E: ParseError<&'a str> + ContextError<&'a str> + FromExternalError<&'a str, std::num::ParseFloatError>>(i: &'a str) -> IResult<&'a str, String, E> {
+    context(
+        "string",
+        preceded(
+            char('"'),
+            cut(terminated(
+                fold_many0(string_char, String::new, |mut s, c| {
+                    s.push(c);
+                    s
+                }),
+                char('"'),
+            )),
+        ),
+    )(i)
+}
+
+/** A number as the JSON grammar has it: no sign but `-`, no leading zeros, no `nan` or `inf`. */
+fn number<'a, E: ParseError<&'a str> + FromExternalError<&'a str, std::num::ParseFloatError>>(i: &'a str) -> IResult<&'a str, f64, E> {
+    map_res(
+        recognize(tuple((
+            opt(char('-')),
+            alt((tag("0"), recognize(pair(satisfy(|c| ('1'..='9').contains(&c)), digit0)))),
+            opt(pair(char('.'), digit1)),
+            opt(tuple((one_of("eE"), opt(one_of("+-")), digit1))),
+        ))),
+        str::parse::<f64>,
+    )(i)
+}
+
+fn array<'a, E: ParseError<&'a str> + ContextError<&'a str> + FromExternalError<&'a str, std::num::ParseFloatError>>(i: &'a str) -> IResult<&'a str, Vec<JsonValue>, E> {
+    context(
+        "array",
+        preceded(
+            char('['),
+            cut(terminated(
+                separated_list0(preceded(sp, char(',')), json_value),
+                preceded(sp, char(']')),
+            )),
+        ),
+    )(i)
+}
+
+fn hash<'a, E: ParseError<&'a str> + ContextError<&'a str> + FromExternalError<&'a str, std::num::ParseFloatError>>(i: &'a str) -> IResult<&'a str, Vec<(String, JsonValue)>, E> {
+    context(
+        "map",
+        preceded(
+            char('{'),
+            cut(terminated(
+                separated_list0(
+                    preceded(sp, char(',')),
+                    separated_pair(preceded(sp, string), cut(preceded(sp, char(':'))), cut(json_value)),
+                ),
+                preceded(sp, char('}')),
+            )),
+        ),
+    )(i)
+}
+
+fn json_value<'a, E: ParseError<&'a str> + ContextError<&'a str> + FromExternalError<&'a str, std::num::ParseFloatError>>(i: &'a str) -> IResult<&'a str, JsonValue, E> {
+    preceded(
+        sp,
+        alt((
+            map(hash, JsonValue::Object),
+            map(array, JsonValue::Array),
+            map(string, JsonValue::Str),
+            map(number, JsonValue::Num),
+            value(JsonValue::Boolean(true), tag("true")),
+            value(JsonValue::Boolean(false), tag("false")),
+            value(JsonValue::Null, tag("null")),
+        )),
+    )(i)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JsonError {
+    /** Byte offset into the input where parsing failed. */
+    pub offset: usize,
+    pub message: String,
+}
+
+impl fmt::Display for JsonError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid JSON at offset {}: {}", self.offset, self.message)
+    }
+}
+
+impl std::error::Error for JsonError {}
+
+/** How deep arrays and objects may nest in a document `parse` reads. */
+pub const MAX_DEPTH: usize = 128;
+
+/** The offset of the first bracket nested deeper than `MAX_DEPTH`, if any. */
+fn too_deep(input: &str) -> Option<usize> {
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+    for (offset, c) in input.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_string => escaped = true,
+            '"' => in_string = !in_string,
+            '[' | '{' if !in_string => {
+                depth += 1;
+                if depth > MAX_DEPTH {
+                    return Some(offset);
+                }
+            }
+            ']' | '}' if !in_string => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+    None
+}
+
+/**
+Parse a complete JSON document.
+*/
+pub fn parse(input: &str) -> Result<JsonValue, JsonError> {
+    if let Some(offset) = too_deep(input) {
+        return Err(JsonError { offset, message: format!("nested more than {} deep", MAX_DEPTH) });
+    }
+    match delimited(sp, json_value::<VerboseError<&str>>, sp)(input) {
+        Ok(("", v)) => Ok(v),
+        Ok((rest, _)) => Err(JsonError {
+            offset: input.len() - rest.len(),
+            message: String::from("trailing characters"),
+        }),
+        Err(Err::Error(e)) | Err(Err::Failure(e)) => {
+            let (rest, kind) = &e.errors[0];
+            Err(JsonError {
+                offset: input.len() - rest.len(),
+                message: format!("{:?}", kind),
+            })
+        }
+        Err(Err::Incomplete(_)) => Err(JsonError {
+            offset: input.len(),
+            message: String::from("unexpected end of input"),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let data = r#"  { "a"	: 42,
+  "b": [ "x", "y\né😀", 12.5 ] ,
+  "c": { "hello" : "world", "t": true, "n": null }
+  } "#;
+        let v = parse(data).unwrap();
+        assert_eq!(v.get("a"), Some(&JsonValue::Num(42.0)));
+        assert_eq!(v.get("b").unwrap().as_array().unwrap()[1].as_str(), Some("y\né😀"));
+        assert_eq!(v.path(&["c", "hello"]).and_then(|v| v.as_str()), Some("world"));
+        assert_eq!(v.path(&["c", "t"]).and_then(|v| v.as_bool()), Some(true));
+        assert!(v.path(&["c", "n"]).unwrap().is_null());
+        assert!(v.path(&["c", "missing"]).is_none());
+    }
+
+    #[test]
+    fn test_errors() {
+        let e = parse(r#"{ "a": 1, "b": }"#).unwrap_err();
+        assert_eq!(e.offset, 15);
+        assert!(parse("[1, 2] x").is_err());
+        assert!(parse("\"unterminated").is_err());
+        for number in ["nan", "inf", "-infinity", "+1", "01", "1.", ".5", "1e"] {
+            assert!(parse(number).is_err(), "{}", number);
+        }
+        assert_eq!(parse("[-0.5e+2, 0, 10E-1]").unwrap(), JsonValue::Array(vec![JsonValue::Num(-50.0), JsonValue::Num(0.0), JsonValue::Num(1.0)]));
+        let deep = format!("{}{}", "[".repeat(MAX_DEPTH), "]".repeat(MAX_DEPTH));
+        assert!(parse(&deep).is_ok());
+        let hostile = "[{\"a\": ".repeat(100_000);
+        // the bracket opening level MAX_DEPTH + 1
+        assert_eq!(parse(&hostile).unwrap_err().offset, "[{\"a\": ".len() * MAX_DEPTH / 2);
+        assert!(parse("[\"[[[[\"]").is_ok());
+    }
+
+    #[test]
+    fn test_serialize() {
+        let v = JsonValue::object(vec![
+            ("key", JsonValue::str("Cox-CFT")),
+            ("year", JsonValue::Num(2013.0)),
+            ("ratio", JsonValue::Num(0.5)),
+            ("tags", JsonValue::Array(vec![JsonValue::str("a\"b"), JsonValue::Null])),
+            ("empty", JsonValue::Object(vec![])),
+        ]);
+        let compact = v.to_string();
+        assert_eq!(compact, r#"{"key":"Cox-CFT","year":2013,"ratio":0.5,"tags":["a\"b",null],"empty":{}}"#);
+        assert_eq!(parse(&compact).unwrap(), v);
+        assert_eq!(parse(&v.to_pretty_string()).unwrap(), v);
+        assert!(v.to_pretty_string().contains("\n  \"year\": 2013,\n"));
+    }
+}
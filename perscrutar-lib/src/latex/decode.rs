@@ -0,0 +1,399 @@
+/*!
+
+LaTeX in field values, decoded to Unicode and encoded back.
+
+BibTeX predates Unicode, so many .bib files write `Gödel` as `G{\"o}del`
+and `Straße` as `Stra{\ss}e`. `decode` turns such markup into the
+characters it stands for, for display, searching and formats that take
+Unicode, such as CSL-JSON:
+
+- accents (`ACCENTS`), on a letter, a braced group or `\i`, as the
+  precomposed character where there is one (`ACCENTED`) and as the letter
+  and a combining accent otherwise;
+- commands for letters and symbols (`SYMBOLS`, and `MATH_SYMBOLS` such as
+  Greek letters);
+- escaped characters such as `\&` and `\%`, `~` as a no-break space, and
+  `--`, `---`, ```` `` ```` and `''` as dashes and quotation marks
+  (`LIGATURES`);
+- font commands such as `\emph` and `\textbf`, which are dropped with the
+  braces and `$` signs, keeping their text.
+
+Other commands are kept as they are. `Entry::field_unicode` decodes a
+field, leaving the `VERBATIM_FIELDS` alone.
+
+`encode` does the reverse for files that must be plain ASCII, writing
+each character as the command `decode` reads, braced so that BibTeX keeps
+it together: `G{\"o}del`, `Stra{\ss}e`, `{$\alpha$}`. Characters with no
+command are an `EncodeError`. `Entry::to_ascii` and the `encode-ascii`
+transform encode every field of an entry but the verbatim ones.
+
+*/
+
+use std::fmt;
+use crate::bibtex::bibliography::Bibliography;
+use crate::bibtex::data::{is_verbatim, Entry};
+use crate::transform::{Report, Transform};
+
+/** Accent commands and the combining characters they stand for. */
+pub const ACCENTS: [(char, char); 16] = [
+    ('`', '\u{300}'), ('\'', '\u{301}'), ('^', '\u{302}'), ('~', '\u{303}'), ('=', '\u{304}'), ('u', '\u{306}'),
+    ('.', '\u{307}'), ('"', '\u{308}'), ('r', '\u{30a}'), ('H', '\u{30b}'), ('v', '\u{30c}'), ('d', '\u{323}'),
+    ('c', '\u{327}'), ('k', '\u{328}'), ('b', '\u{331}'), ('t', '\u{361}'),
+];
+
+/** Letters with an accent of `ACCENTS`: the accent, the letter and the accented letter. */
+pub const ACCENTED: [(char, char, char); 306] = [
+    ('`', 'A', 'À'), ('\'', 'A', 'Á'), ('^', 'A', 'Â'), ('~', 'A', 'Ã'), ('"', 'A', 'Ä'), ('r', 'A', 'Å'),
+    ('c', 'C', 'Ç'), ('`', 'E', 'È'), ('\'', 'E', 'É'), ('^', 'E', 'Ê'), ('"', 'E', 'Ë'), ('`', 'I', 'Ì'),
+    ('\'', 'I', 'Í'), ('^', 'I', 'Î'), ('"', 'I', 'Ï'), ('~', 'N', 'Ñ'), ('`', 'O', 'Ò'), ('\'', 'O', 'Ó'),
+    ('^', 'O', 'Ô'), ('~', 'O', 'Õ'), ('"', 'O', 'Ö'), ('`', 'U', 'Ù'), ('\'', 'U', 'Ú'), ('^', 'U', 'Û'),
+    ('"', 'U', 'Ü'), ('\'', 'Y', 'Ý'), ('`', 'a', 'à'), ('\'', 'a', 'á'), ('^', 'a', 'â'), ('~', 'a', 'ã'),
+    ('"', 'a', 'ä'), ('r', 'a', 'å'), ('c', 'c', 'ç'), ('`', 'e', 'è'), ('\'', 'e', 'é'), ('^', 'e', 'ê'),
+    ('"', 'e', 'ë'), ('`', 'i', 'ì'), ('\'', 'i', 'í'), ('^', 'i', 'î'), ('"', 'i', 'ï'), ('~', 'n', 'ñ'),
+    ('`', 'o', 'ò'), ('\'', 'o', 'ó'), ('^', 'o', 'ô'), ('~', 'o', 'õ'), ('"', 'o', 'ö'), ('`', 'u', 'ù'),
+    ('\'', 'u', 'ú'), ('^', 'u', 'û'), ('"', 'u', 'ü'), ('\'', 'y', 'ý'), ('"', 'y', 'ÿ'), ('=', 'A', 'Ā'),
+    ('=', 'a', 'ā'), ('u', 'A', 'Ă'), ('u', 'a', 'ă'), ('k', 'A', 'Ą'), ('k', 'a', 'ą'), ('\'', 'C', 'Ć'),
+    ('\'', 'c', 'ć'), ('^', 'C', 'Ĉ'), ('^', 'c', 'ĉ'), ('.', 'C', 'Ċ'), ('.', 'c', 'ċ'), ('v', 'C', 'Č'),
+    ('v', 'c', 'č'), ('v', 'D', 'Ď'), ('v', 'd', 'ď'), ('=', 'E', 'Ē'), ('=', 'e', 'ē'), ('u', 'E', 'Ĕ'),
+    ('u', 'e', 'ĕ'), ('.', 'E', 'Ė'), ('.', 'e', 'ė'), ('k', 'E', 'Ę'), ('k', 'e', 'ę'), ('v', 'E', 'Ě'),
+    ('v', 'e', 'ě'), ('^', 'G', 'Ĝ'), ('^', 'g', 'ĝ'), ('u', 'G', 'Ğ'), ('u', 'g', 'ğ'), ('.', 'G', 'Ġ'),
+    ('.', 'g', 'ġ'), ('c', 'G', 'Ģ'), ('c', 'g', 'ģ'), ('^', 'H', 'Ĥ'), ('^', 'h', 'ĥ'), ('~', 'I', 'Ĩ'),
+    ('~', 'i', 'ĩ'), ('=', 'I', 'Ī'), ('=', 'i', 'ī'), ('u', 'I', 'Ĭ'), ('u', 'i', 'ĭ'), ('k', 'I', 'Į'),
+    ('k', 'i', 'į'), ('.', 'I', 'İ'), ('^', 'J', 'Ĵ'), ('^', 'j', 'ĵ'), ('c', 'K', 'Ķ'), ('c', 'k', 'ķ'),
+    ('\'', 'L', 'Ĺ'), ('\'', 'l', 'ĺ'), ('c', 'L', 'Ļ'), ('c', 'l', 'ļ'), ('v', 'L', 'Ľ'), ('v', 'l', 'ľ'),
+    ('\'', 'N', 'Ń'), ('\'', 'n', 'ń'), ('c', 'N', 'Ņ'), ('c', 'n', 'ņ'), ('v', 'N', 'Ň'), ('v', 'n', 'ň'),
+    ('=', 'O', 'Ō'), ('=', 'o', 'ō'), ('u', 'O', 'Ŏ'), ('u', 'o', 'ŏ'), ('H', 'O', 'Ő'), ('H', 'o', 'ő'),
+    ('\'', 'R', 'Ŕ'), ('\'', 'r', 'ŕ'), ('c', 'R', 'Ŗ'), ('c', 'r', 'ŗ'), ('v', 'R', 'Ř'), ('v', 'r', 'ř'),
+    ('\'', 'S', 'Ś'), ('\'', 's', 'ś'), ('^', 'S', 'Ŝ'), ('^', 's', 'ŝ'), ('c', 'S', 'Ş'), ('c', 's', 'ş'),
+    ('v', 'S', 'Š'), ('v', 's', 'š'), ('c', 'T', 'Ţ'), ('c', 't', 'ţ'), ('v', 'T', 'Ť'), ('v', 't', 'ť'),
+    ('~', 'U', 'Ũ'), ('~', 'u', 'ũ'), ('=', 'U', 'Ū'), ('=', 'u', 'ū'), ('u', 'U', 'Ŭ'), ('u', 'u', 'ŭ'),
+    ('r', 'U', 'Ů'), ('r', 'u', 'ů'), ('H', 'U', 'Ű'), ('H', 'u', 'ű'), ('k', 'U', 'Ų'), ('k', 'u', 'ų'),
+    ('^', 'W', 'Ŵ'), ('^', 'w', 'ŵ'), ('^', 'Y', 'Ŷ'), ('^', 'y', 'ŷ'), ('"', 'Y', 'Ÿ'), ('\'', 'Z', 'Ź'),
+    ('\'', 'z', 'ź'), ('.', 'Z', 'Ż'), ('.', 'z', 'ż'), ('v', 'Z', 'Ž'), ('v', 'z', 'ž'), ('v', 'A', 'Ǎ'),
+    ('v', 'a', 'ǎ'), ('v', 'I', 'Ǐ'), ('v', 'i', 'ǐ'), ('v', 'O', 'Ǒ'), ('v', 'o', 'ǒ'), ('v', 'U', 'Ǔ'),
+    ('v', 'u', 'ǔ'), ('v', 'G', 'Ǧ'), ('v', 'g', 'ǧ'), ('v', 'K', 'Ǩ'), ('v', 'k', 'ǩ'), ('k', 'O', 'Ǫ'),
+    ('k', 'o', 'ǫ'), ('v', 'j', 'ǰ'), ('\'', 'G', 'Ǵ'), ('\'', 'g', 'ǵ'), ('`', 'N', 'Ǹ'), ('`', 'n', 'ǹ'),
+    ('v', 'H', 'Ȟ'), ('v', 'h', 'ȟ'), ('.', 'A', 'Ȧ'), ('.', 'a', 'ȧ'), ('c', 'E', 'Ȩ'), ('c', 'e', 'ȩ'),
+    ('.', 'O', 'Ȯ'), ('.', 'o', 'ȯ'), ('=', 'Y', 'Ȳ'), ('=', 'y', 'ȳ'), ('.', 'B', 'Ḃ'), ('.', 'b', 'ḃ'),
+    ('d', 'B', 'Ḅ'), ('d', 'b', 'ḅ'), ('b', 'B', 'Ḇ'), ('b', 'b', 'ḇ'), ('.', 'D', 'Ḋ'), ('.', 'd', 'ḋ'),
+    ('d', 'D', 'Ḍ'), ('d', 'd', 'ḍ'), ('b', 'D', 'Ḏ'), ('b', 'd', 'ḏ'), ('c', 'D', 'Ḑ'), ('c', 'd', 'ḑ'),
+    ('.', 'F', 'Ḟ'), ('.', 'f', 'ḟ'), ('=', 'G', 'Ḡ'), ('=', 'g', 'ḡ'), ('.', 'H', 'Ḣ'), ('.', 'h', 'ḣ'),
+    ('d', 'H', 'Ḥ'), ('d', 'h', 'ḥ'), ('"', 'H', 'Ḧ'), ('"', 'h', 'ḧ'), ('c', 'H', 'Ḩ'), ('c', 'h', 'ḩ'),
+    ('\'', 'K', 'Ḱ'), ('\'', 'k', 'ḱ'), ('d', 'K', 'Ḳ'), ('d', 'k', 'ḳ'), ('b', 'K', 'Ḵ'), ('b', 'k', 'ḵ'),
+    ('d', 'L', 'Ḷ'), ('d', 'l', 'ḷ'), ('b', 'L', 'Ḻ'), ('b', 'l', 'ḻ'), ('\'', 'M', 'Ḿ'), ('\'', 'm', 'ḿ'),
+    ('.', 'M', 'Ṁ'), ('.', 'm', 'ṁ'), ('d', 'M', 'Ṃ'), ('d', 'm', 'ṃ'), ('.', 'N', 'Ṅ'), ('.', 'n', 'ṅ'),
+    ('d', 'N', 'Ṇ'), ('d', 'n', 'ṇ'), ('b', 'N', 'Ṉ'), ('b', 'n', 'ṉ'), ('\'', 'P', 'Ṕ'), ('\'', 'p', 'ṕ'),
+    ('.', 'P', 'Ṗ'), ('.', 'p', 'ṗ'), ('.', 'R', 'Ṙ'), ('.', 'r', 'ṙ'), ('d', 'R', 'Ṛ'), ('d', 'r', 'ṛ'),
+    ('b', 'R', 'Ṟ'), ('b', 'r', 'ṟ'), ('.', 'S', 'Ṡ'), ('.', 's', 'ṡ'), ('d', 'S', 'Ṣ'), ('d', 's', 'ṣ'),
+    ('.', 'T', 'Ṫ'), ('.', 't', 'ṫ'), ('d', 'T', 'Ṭ'), ('d', 't', 'ṭ'), ('b', 'T', 'Ṯ'), ('b', 't', 'ṯ'),
+    ('~', 'V', 'Ṽ'), ('~', 'v', 'ṽ'), ('d', 'V', 'Ṿ'), ('d', 'v', 'ṿ'), ('`', 'W', 'Ẁ'), ('`', 'w', 'ẁ'),
+    ('\'', 'W', 'Ẃ'), ('\'', 'w', 'ẃ'), ('"', 'W', 'Ẅ'), ('"', 'w', 'ẅ'), ('.', 'W', 'Ẇ'), ('.', 'w', 'ẇ'),
+    ('d', 'W', 'Ẉ'), ('d', 'w', 'ẉ'), ('.', 'X', 'Ẋ'), ('.', 'x', 'ẋ'), ('"', 'X', 'Ẍ'), ('"', 'x', 'ẍ'),
+    ('.', 'Y', 'Ẏ'), ('.', 'y', 'ẏ'), ('^', 'Z', 'Ẑ'), ('^', 'z', 'ẑ'), ('d', 'Z', 'Ẓ'), ('d', 'z', 'ẓ'),
+    ('b', 'Z', 'Ẕ'), ('b', 'z', 'ẕ'), ('b', 'h', 'ẖ'), ('"', 't', 'ẗ'), ('r', 'w', 'ẘ'), ('r', 'y', 'ẙ'),
+    ('d', 'A', 'Ạ'), ('d', 'a', 'ạ'), ('d', 'E', 'Ẹ'), ('d', 'e', 'ẹ'), ('~', 'E', 'Ẽ'), ('~', 'e', 'ẽ'),
+    ('d', 'I', 'Ị'), ('d', 'i', 'ị'), ('d', 'O', 'Ọ'), ('d', 'o', 'ọ'), ('d', 'U', 'Ụ'), ('d', 'u', 'ụ'),
+    ('`', 'Y', 'Ỳ'), ('`', 'y', 'ỳ'), ('d', 'Y', 'Ỵ'), ('d', 'y', 'ỵ'), ('~', 'Y', 'Ỹ'), ('~', 'y', 'ỹ'),
+];
+
+/** Commands for letters and symbols in text. */
+pub const SYMBOLS: [(&str, &str); 81] = [
+    ("ss", "ß"), ("ae", "æ"), ("AE", "Æ"), ("oe", "œ"), ("OE", "Œ"), ("aa", "å"), ("AA", "Å"), ("o", "ø"),
+    ("O", "Ø"), ("l", "ł"), ("L", "Ł"), ("i", "ı"), ("j", "ȷ"), ("dh", "ð"), ("DH", "Ð"), ("th", "þ"),
+    ("TH", "Þ"), ("ng", "ŋ"), ("NG", "Ŋ"), ("dj", "đ"), ("DJ", "Đ"), ("textendash", "–"), ("textemdash", "—"),
+    ("textquoteleft", "‘"), ("textquoteright", "’"), ("textquotedblleft", "“"), ("textquotedblright", "”"),
+    ("quotesinglbase", "‚"), ("quotedblbase", "„"), ("guillemotleft", "«"), ("guillemotright", "»"),
+    ("guilsinglleft", "‹"), ("guilsinglright", "›"), ("textellipsis", "…"), ("ldots", "…"), ("dots", "…"),
+    ("S", "§"), ("textsection", "§"), ("P", "¶"), ("textparagraph", "¶"), ("copyright", "©"),
+    ("textcopyright", "©"), ("textregistered", "®"), ("texttrademark", "™"), ("pounds", "£"),
+    ("textsterling", "£"), ("texteuro", "€"), ("euro", "€"), ("textyen", "¥"), ("textcent", "¢"),
+    ("textdegree", "°"), ("dag", "†"), ("textdagger", "†"), ("ddag", "‡"), ("textdaggerdbl", "‡"),
+    ("textbullet", "•"), ("textperiodcentered", "·"), ("textexclamdown", "¡"), ("textquestiondown", "¿"),
+    ("textordfeminine", "ª"), ("textordmasculine", "º"), ("textmu", "µ"), ("textonehalf", "½"),
+    ("textonequarter", "¼"), ("textthreequarters", "¾"), ("texttimes", "×"), ("textdiv", "÷"),
+    ("textbackslash", "\\"), ("textasciitilde", "~"), ("textasciicircum", "^"), ("textunderscore", "_"),
+    ("textbar", "|"), ("textless", "<"), ("textgreater", ">"), ("textbraceleft", "{"), ("textbraceright", "}"),
+    ("textdollar", "$"), ("nobreakspace", "\u{a0}"), ("LaTeX", "LaTeX"), ("TeX", "TeX"), ("BibTeX", "BibTeX"),
+];
+
+/** Commands for symbols in mathematics, which `encode` puts between `$` signs. */
+pub const MATH_SYMBOLS: [(&str, &str); 58] = [
+    ("alpha", "α"), ("beta", "β"), ("gamma", "γ"), ("delta", "δ"), ("epsilon", "ϵ"), ("varepsilon", "ε"),
+    ("zeta", "ζ"), ("eta", "η"), ("theta", "θ"), ("vartheta", "ϑ"), ("iota", "ι"), ("kappa", "κ"),
+    ("lambda", "λ"), ("mu", "μ"), ("nu", "ν"), ("xi", "ξ"), ("pi", "π"), ("rho", "ρ"), ("sigma", "σ"),
+    ("varsigma", "ς"), ("tau", "τ"), ("upsilon", "υ"), ("phi", "ϕ"), ("varphi", "φ"), ("chi", "χ"),
+    ("psi", "ψ"), ("omega", "ω"), ("Gamma", "Γ"), ("Delta", "Δ"), ("Theta", "Θ"), ("Lambda", "Λ"),
+    ("Xi", "Ξ"), ("Pi", "Π"), ("Sigma", "Σ"), ("Upsilon", "Υ"), ("Phi", "Φ"), ("Psi", "Ψ"), ("Omega", "Ω"),
+    ("times", "×"), ("pm", "±"), ("mp", "∓"), ("leq", "≤"), ("le", "≤"), ("geq", "≥"), ("ge", "≥"),
+    ("neq", "≠"), ("ne", "≠"), ("approx", "≈"), ("infty", "∞"), ("cdot", "⋅"), ("to", "→"),
+    ("rightarrow", "→"), ("leftarrow", "←"), ("in", "∈"), ("partial", "∂"), ("nabla", "∇"), ("ell", "ℓ"),
+    ("circ", "∘"),
+];
+
+/** Runs of ASCII characters that typeset as one other character. */
+pub const LIGATURES: [(&str, char); 7] = [
+    ("---", '—'), ("--", '–'), ("``", '“'), ("''", '”'), ("`", '‘'), ("~", '\u{a0}'), ("\\,", '\u{2009}'),
+];
+
+/** Commands that only change the font or box of their argument. */
+const FONT_COMMANDS: [&str; 21] = [
+    "emph", "mathbf", "mathit", "mathrm", "mathsf", "mathtt", "mbox", "hbox", "NoCaseChange", "text",
+    "textbf", "textit", "textmd", "textnormal", "textrm", "textsc", "textsf", "textsl", "texttt", "textup",
+    "ensuremath",
+];
+
+/** Characters that stand for themselves after a backslash. */
+const ESCAPED: [char; 8] = ['&', '%', '$', '#', '_', '{', '}', ' '];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EncodeError(pub String);
+
+impl fmt::Display for EncodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for EncodeError {}
+
+fn combining(accent: char) -> Option<char> {
+    ACCENTS.iter().find(|(a, _)| *a == accent).map(|(_, mark)| *mark)
+}
+
+/** `argument`, decoded, with `accent` on its first letter. */
+fn accented(accent: char, argument: &str) -> String {
+    let mut chars = argument.chars();
+    let Some(first) = chars.next() else { return String::new() };
+    let base = match first {
+        'ı' => 'i',
+        'ȷ' => 'j',
+        c => c,
+    };
+    let mut out = match ACCENTED.iter().find(|(a, b, _)| *a == accent && *b == base) {
+        Some((_, _, c)) => String::from(*c),
+        None => format!("{}{}", base, combining(accent).unwrap_or_default()),
+    };
+    out.extend(chars);
+    out
+}
+
+/** The name of the command starting at `chars[i]`, after the backslash, and where it ends. */
+fn command_name(chars: &[char], i: usize) -> (String, usize) {
+    match chars.get(i) {
+        Some(c) if c.is_ascii_alphabetic() => {
+            let end = (i..chars.len()).find(|j| !chars[*j].is_ascii_alphabetic()).unwrap_or(chars.len());
+            (chars[i..end].iter().collect(), end)
+        }
+        Some(c) => (String::from(*c), i + 1),
+        None => (String::new(), i),
+    }
+}
+
+/** The argument of an accent at `chars[i]`, decoded, and where it ends. */
+fn argument(chars: &[char], mut i: usize) -> (String, usize) {
+    while chars.get(i).is_some_and(|c| *c == ' ') {
+        i += 1;
+    }
+    match chars.get(i) {
+        Some('{') => {
+            let mut depth = 0;
+            let end = (i..chars.len()).find(|j| {
+                depth += match chars[*j] { '{' => 1, '}' => -1, _ => 0 };
+                depth == 0
+            }).unwrap_or(chars.len());
+            let inner: String = chars[i + 1..end.min(chars.len())].iter().collect();
+            (decode(&inner), end + 1)
+        }
+        Some('\\') => {
+            let (name, end) = command_name(chars, i + 1);
+            (decode(&format!("\\{}", name)), end)
+        }
+        Some(c) => (String::from(*c), i + 1),
+        None => (String::new(), i),
+    }
+}
+
+/** `text` with its LaTeX markup replaced by the Unicode characters it stands for. */
+pub fn decode(text: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut out = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let rest: String = chars[i..chars.len().min(i + 3)].iter().collect();
+        if let Some((ligature, c)) = LIGATURES.iter().find(|(l, _)| rest.starts_with(l)) {
+            out.push(*c);
+            i += ligature.chars().count();
+            continue;
+        }
+        match chars[i] {
+            '{' | '}' | '$' => i += 1,
+            '\\' => {
+                let (name, mut end) = command_name(&chars, i + 1);
+                let letters = name.starts_with(|c: char| c.is_ascii_alphabetic());
+                if letters {
+                    while chars.get(end).is_some_and(|c| *c == ' ') {
+                        end += 1;
+                    }
+                }
+                let accent = name.chars().next().filter(|_| name.chars().count() == 1).and_then(|c| combining(c).map(|_| c));
+                let symbol = SYMBOLS.iter().chain(MATH_SYMBOLS.iter()).find(|(command, _)| *command == name);
+                if let Some(accent) = accent {
+                    let (arg, after) = argument(&chars, end);
+                    out.push_str(&accented(accent, &arg));
+                    end = after;
+                } else if let Some((_, symbol)) = symbol {
+                    out.push_str(symbol);
+                    if chars.get(end) == Some(&'{') && chars.get(end + 1) == Some(&'}') {
+                        end += 2;
+                    }
+                } else if FONT_COMMANDS.contains(&name.as_str()) || name == "-" {
+                    // the argument's braces go like any others
+                } else if name == "\\" {
+                    out.push(' ');
+                } else if name.chars().count() == 1 && ESCAPED.contains(&name.chars().next().unwrap_or_default()) {
+                    out.push_str(&name);
+                } else {
+                    out.push('\\');
+                    out.push_str(&name);
+                    if letters && end > i + 1 + name.len() {
+                        out.push(' ');
+                    }
+                }
+                i = end;
+            }
+            c => {
+                out.push(c);
+                i += 1;
+            }
+        }
+    }
+    out
+}
+
+/** `text` in ASCII, with other characters written as the LaTeX commands for them. */
+pub fn encode(text: &str) -> Result<String, EncodeError> {
+    let mut out = String::new();
+    let mut unknown = Vec::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        let command = |accent: char, base: char| match accent.is_ascii_alphabetic() {
+            true => format!("{{\\{}{{{}}}}}", accent, base),
+            false => format!("{{\\{}{}}}", accent, base),
+        };
+        if let Some(accent) = chars.peek().and_then(|m| ACCENTS.iter().find(|(_, mark)| mark == m)).map(|(a, _)| *a) {
+            if c.is_ascii_alphabetic() {
+                chars.next();
+                out.push_str(&command(accent, c));
+                continue;
+            }
+        }
+        let s = c.to_string();
+        if c.is_ascii() {
+            out.push(c);
+        } else if let Some((accent, base, _)) = ACCENTED.iter().find(|(_, _, a)| *a == c) {
+            out.push_str(&command(*accent, *base));
+        } else if let Some((ligature, _)) = LIGATURES.iter().find(|(_, l)| *l == c) {
+            out.push_str(ligature);
+        } else if let Some((command, _)) = SYMBOLS.iter().find(|(_, symbol)| *symbol == s) {
+            out.push_str(&format!("{{\\{}}}", command));
+        } else if let Some((command, _)) = MATH_SYMBOLS.iter().find(|(_, symbol)| *symbol == s) {
+            out.push_str(&format!("{{$\\{}$}}", command));
+        } else if !unknown.contains(&c) {
+            unknown.push(c);
+        }
+    }
+    match unknown.is_empty() {
+        true => Ok(out),
+        false => Err(EncodeError(format!("no LaTeX command for {}",
+            unknown.iter().map(|c| format!("`{}` (U+{:04X})", c, *c as u32)).collect::<Vec<String>>().join(", ")))),
+    }
+}
+
+impl Entry {
+    /** Field `name` decoded to Unicode, or as it is if it is verbatim. */
+    pub fn field_unicode(&self, name: &str) -> Option<String> {
+        self.get(name).map(|value| match is_verbatim(name) {
+            true => String::from(value),
+            false => decode(value),
+        })
+    }
+
+    /**
+    `encode` every field but the verbatim ones. Nothing changes if a field
+    cannot be encoded. Returns whether a field changed.
+    */
+    pub fn to_ascii(&mut self) -> Result<bool, EncodeError> {
+        let mut encoded = Vec::new();
+        for (name, value) in self.fields() {
+            if is_verbatim(name) || value.is_ascii() {
+                continue;
+            }
+            let ascii = encode(value).map_err(|e| EncodeError(format!("{}: {}", name, e)))?;
+            encoded.push((String::from(name), ascii));
+        }
+        for (name, value) in encoded.iter() {
+            self.set(name, value);
+        }
+        Ok(!encoded.is_empty())
+    }
+}
+
+/** `encode-ascii`: `Entry::to_ascii` on every entry, reporting those it cannot encode. */
+pub struct AsciiTransform;
+
+impl Transform for AsciiTransform {
+    fn name(&self) -> &str {
+        "encode-ascii"
+    }
+
+    fn description(&self) -> &str {
+        "Write non-ASCII characters as LaTeX commands, for strict-ASCII .bib files"
+    }
+
+    fn apply(&self, bibliography: &mut Bibliography) -> Report {
+        let mut messages = Vec::new();
+        let changed = bibliography.visit_mut(|e| {
+            if let Err(error) = e.to_ascii() {
+                messages.push(format!("{}: {}", e.key(), error));
+            }
+        });
+        Report { changed, messages }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::bibtex::data::BibType;
+
+    #[test]
+    fn test_decode() {
+        assert_eq!(decode("G{\\\"o}del, \\'{e}t\\'e, Stra\\ss{}e, {\\ss}"), "Gödel, été, Straße, ß");
+        assert_eq!(decode("\\v{C}ech and \\c c, \\'{\\i}, \\H o, \\k{a}"), "Čech and ç, í, ő, ą");
+        assert_eq!(decode("\\t{oo} \\d{q}"), "o\u{361}o q\u{323}");
+        assert_eq!(decode("{\\AA}ngstr{\\\"o}m -- 1990---2000 ``quoted'' Smith~\\& Sons, 50\\%"),
+            "Ångström – 1990—2000 “quoted” Smith\u{a0}& Sons, 50%");
+        assert_eq!(decode("The $\\alpha$-helix in \\emph{vivo}, \\LaTeX\\ and \\unknown{x}"), "The α-helix in vivo, LaTeX and \\unknownx");
+    }
+
+    #[test]
+    fn test_encode() {
+        assert_eq!(encode("Gödel, Čech, Straße, Ångström").unwrap(), "G{\\\"o}del, {\\v{C}}ech, Stra{\\ss}e, {\\r{A}}ngstr{\\\"o}m");
+        assert_eq!(encode("The α-helix – e\u{301}").unwrap(), "The {$\\alpha$}-helix -- {\\'e}");
+        for text in ["Gödel, Čech, Straße", "Erdős and Łukasiewicz — “quoted” α"] {
+            assert_eq!(decode(&encode(text).unwrap()), text);
+        }
+        assert_eq!(encode("漢字 and 漢"), Err(EncodeError(String::from("no LaTeX command for `漢` (U+6F22), `字` (U+5B57)"))));
+
+        let mut e = Entry::new(BibType::Article, "a");
+        e.set("title", "Über {DNA}");
+        e.set("url", "https://example.org/über");
+        assert_eq!(e.field_unicode("title").as_deref(), Some("Über DNA"));
+        assert_eq!(e.to_ascii(), Ok(true));
+        assert_eq!((e.get("title"), e.get("url")), (Some("{\\\"U}ber {DNA}"), Some("https://example.org/über")));
+        e.set("note", "漢");
+        assert!(e.to_ascii().is_err());
+        assert_eq!(e.get("title"), Some("{\\\"U}ber {DNA}"));
+    }
+}
@@ -0,0 +1,12 @@
+/*!
+
+LaTeX sources, as opposed to the BibTeX files they cite.
+
+`scan` follows a document through the files it `\input`s and collects
+the keys it cites; `decode` turns the LaTeX in field values into Unicode
+and back.
+
+*/
+
+pub mod decode;
+pub mod scan;
@@ -0,0 +1,227 @@
+/*!
+
+The keys a LaTeX document cites, across all its files.
+
+A thesis or a long paper is split over several files, which the main one
+pulls in with `\input{...}`, `\include{...}` or `\subfile{...}`. `scan`
+reads the main file and, recursively, every file it includes, and finds
+the citations in each with `citations::scan_latex`: `\cite`, `\citep`,
+`\textcite`, `\autocite` and every other command whose name contains
+`cite`. Included files are looked for the way LaTeX does, relative to the
+main file's directory, with `.tex` added first; a file that is included
+twice is scanned once. Includes that name no file, such as those of
+generated files not built yet, are listed rather than failing the scan.
+
+`\nocite{*}`, which cites every entry, sets `CitedKeys::all`.
+
+To ship a document with a bibliography that is no bigger than it needs to
+be and has everything it cites, `Bibliography::prune` removes the entries
+the `CitedKeys` do not need, directly or through a `crossref` or other
+`KEY_REFERENCES` field, and `Bibliography::missing` lists the keys cited
+but not in the bibliography.
+
+*/
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+use crate::bibtex::bibliography::Bibliography;
+use crate::bibtex::data::Entry;
+use crate::citations::{scan_latex, Citation};
+
+/** Commands whose argument is a file to read in place. */
+const INCLUDES: [&str; 3] = ["input", "include", "subfile"];
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CitedKeys {
+    /** Every citation, file by file in the order LaTeX reads them. */
+    pub citations: Vec<Citation>,
+    /** Whether the document has `\nocite{*}`. */
+    pub all: bool,
+    /** The files scanned, the main one first. */
+    pub files: Vec<PathBuf>,
+    /** Included names for which there is no file. */
+    pub unresolved: Vec<String>,
+}
+
+impl CitedKeys {
+    /** The keys cited, each once, in order of first citation. */
+    pub fn keys(&self) -> Vec<&str> {
+        let mut keys: Vec<&str> = Vec::new();
+        for citation in &self.citations {
+            if !keys.contains(&citation.key.as_str()) {
+                keys.push(&citation.key);
+            }
+        }
+        keys
+    }
+
+    /** Whether `key` is cited, by name or by `\nocite{*}`. */
+    pub fn contains(&self, key: &str) -> bool {
+        self.all || self.citations.iter().any(|c| c.key == key)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScanError(pub String);
+
+impl fmt::Display for ScanError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for ScanError {}
+
+/** `text` without comments, keeping line breaks so positions stay on their line. */
+fn uncommented(text: &str) -> String {
+    text.split_inclusive('\n').map(|line| {
+        let mut escaped = false;
+        let end = line.char_indices().find(|(_, c)| {
+            let comment = *c == '%' && !escaped;
+            escaped = *c == '\\' && !escaped;
+            comment
+        });
+        match end {
+            Some((i, _)) if line.ends_with('\n') => format!("{}\n", &line[..i]),
+            Some((i, _)) => String::from(&line[..i]),
+            None => String::from(line),
+        }
+    }).collect()
+}
+
+/** The braced arguments of the commands `names` in `text`, with the line each is on. */
+fn arguments<'a>(text: &'a str, names: &[&str]) -> Vec<(usize, &'a str)> {
+    let mut out = Vec::new();
+    for (i, _) in text.match_indices('\\') {
+        let rest = &text[i + 1..];
+        let len = rest.find(|c: char| !c.is_ascii_alphabetic()).unwrap_or(rest.len());
+        if !names.contains(&&rest[..len]) {
+            continue;
+        }
+        let Some(argument) = rest[len..].trim_start().strip_prefix('{') else { continue };
+        if let Some(end) = argument.find('}') {
+            out.push((text[..i].matches('\n').count() + 1, argument[..end].trim()));
+        }
+    }
+    out
+}
+
+/** The file LaTeX reads for `\input{name}` in a document in `dir`. */
+fn resolve(dir: &Path, name: &str) -> Option<PathBuf> {
+    let path = dir.join(name);
+    let tex = path.with_file_name(format!("{}.tex", path.file_name()?.to_string_lossy()));
+    [tex, path].into_iter().find(|p| p.is_file())
+}
+
+fn scan_file(path: &Path, dir: &Path, found: &mut CitedKeys) -> Result<(), ScanError> {
+    let canonical = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    if found.files.iter().any(|f| f.canonicalize().unwrap_or_else(|_| f.clone()) == canonical) {
+        return Ok(());
+    }
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| ScanError(format!("cannot read {}: {}", path.display(), e)))?;
+    found.files.push(path.to_path_buf());
+    let mut citations = scan_latex(&path.to_string_lossy(), &text).into_iter().peekable();
+    let text = uncommented(&text);
+    found.all |= arguments(&text, &["nocite"]).iter().any(|(_, keys)| *keys == "*");
+    for (line, name) in arguments(&text, &INCLUDES) {
+        // citations on lines before an include come before those in the file it includes
+        while let Some(citation) = citations.next_if(|c| c.location.line <= line) {
+            found.citations.push(citation);
+        }
+        match resolve(dir, name) {
+            Some(included) => scan_file(&included, dir, found)?,
+            None => found.unresolved.push(String::from(name)),
+        }
+    }
+    found.citations.extend(citations);
+    Ok(())
+}
+
+/**
+The citations in the LaTeX document `main` and the files it includes.
+Only `main` itself must be readable.
+*/
+pub fn scan<P: AsRef<Path>>(main: P) -> Result<CitedKeys, ScanError> {
+    let main = main.as_ref();
+    let dir = main.parent().unwrap_or(Path::new("."));
+    let mut found = CitedKeys::default();
+    scan_file(main, dir, &mut found)?;
+    Ok(found)
+}
+
+impl Bibliography {
+    /**
+    Remove the entries that neither `cited` nor the entries it cites refer
+    to, by key or `ids` alias, and return them. Nothing is removed for
+    `\nocite{*}`, and pinned entries stay.
+    */
+    pub fn prune(&mut self, cited: &CitedKeys) -> Vec<Entry> {
+        if cited.all {
+            return Vec::new();
+        }
+        let needed = self.extract_for(&cited.keys()).bibliography;
+        let unused = |e: &Entry| needed.get(e.key()).is_none();
+        let removed = self.entries().iter()
+            .filter(|e| unused(e) && !self.is_pinned(e.key()))
+            .cloned()
+            .collect();
+        self.retain(|e| !unused(e));
+        removed
+    }
+
+    /**
+    The keys `cited` that no entry has, and those the cited entries refer
+    to that no entry has, in the order met.
+    */
+    pub fn missing(&self, cited: &CitedKeys) -> Vec<String> {
+        self.extract_for(&cited.keys()).missing
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_scan() {
+        let dir = std::env::temp_dir().join(format!("perscrutar-latex-scan-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("chapters")).unwrap();
+        std::fs::write(dir.join("main.tex"), "\\documentclass{book}\n\\begin{document}\nAs \\textcite{knuth84} says\n\
+            \\include{chapters/intro}\n% \\input{commented}\n\\input{figures/plot.tikz}\n\\autocite[p.~3]{lamport94}\n\
+            \\input{chapters/intro.tex}\n\\end{document}\n").unwrap();
+        std::fs::write(dir.join("chapters/intro.tex"), "\\citep{cox13, knuth84}\n\\nocite{*}\n").unwrap();
+
+        let cited = scan(dir.join("main.tex")).unwrap();
+        assert_eq!(cited.keys(), vec!["knuth84", "cox13", "lamport94"]);
+        assert_eq!(cited.citations[1].location.file, dir.join("chapters/intro.tex").to_string_lossy());
+        assert_eq!(cited.files.len(), 2);
+        assert_eq!(cited.unresolved, vec!["figures/plot.tikz"]);
+        assert!(cited.all && cited.contains("anything"));
+        assert!(scan(dir.join("missing.tex")).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_prune() {
+        use crate::bibtex::parser::parse_bibliography;
+
+        let cite = |keys: &[&str]| CitedKeys {
+            citations: scan_latex("main.tex", &format!("\\cite{{{}}}", keys.join(","))),
+            ..CitedKeys::default()
+        };
+        let mut bib = parse_bibliography("@inproceedings{paper, crossref = {procs}, ids = {old}}\n@proceedings{procs, title = {P}}\n\
+            @misc{unused, title = {U}}\n@misc{kept, title = {K}}\n@misc{other, title = {O}}").unwrap();
+        bib.pin("kept");
+        let cited = cite(&["old", "nowhere"]);
+        assert_eq!(bib.missing(&cited), vec!["nowhere"]);
+        let removed: Vec<String> = bib.prune(&cited).iter().map(|e| String::from(e.key())).collect();
+        assert_eq!(removed, vec!["unused", "other"]);
+        let keys: Vec<&str> = bib.entries().iter().map(|e| e.key()).collect();
+        assert_eq!(keys, vec!["paper", "procs", "kept"]);
+        assert_eq!(bib.take_refused(), vec!["kept: pinned, so not removed"]);
+        let all = CitedKeys { all: true, ..CitedKeys::default() };
+        assert!(bib.prune(&all).is_empty() && bib.missing(&all).is_empty());
+    }
+}
@@ -1,2 +1,124 @@
+/*!
 
+Reading, checking and transforming BibTeX bibliographies.
+
+The crate is split into cargo features so that programs needing less than
+all of it build less of it. `parser-core` is the part every build has:
+`bibtex::data`, `bibtex::error` and `bibtex::parser`, which need only
+`alloc`. Without `std` the crate is `no_std`, for WASM runtimes and
+embedded tools; every other feature needs `std`.
+
+`std` adds the rest of `bibtex` (without its writer), `json`, `events`,
+`lint`, `transform` and the other modules that work on parsed entries,
+and `parse_bibliography`, whose `Bibliography` reads directories and
+keeps a journal. The default features add the rest that needs no network:
+
+- `writer`: `bibtex::writer` and the canonical formatting of `bibtex::format`;
+- `formats-cff`, `formats-csl` and `formats-ris`: reading CITATION.cff and
+  codemeta.json (`software`), and reading and writing CSL-JSON (`csl`) and
+  RIS (`ris`);
+- `render`: `publist`, `pandoc` and `styles`;
+- `search`: `search` and `dedupe`;
+- `store`: the `metadata` sidecar and what is kept in it, `audit`,
+  `provenance` and `analytics`.
+
+`lookup` needs `formats-csl` and `store`, and `import` these and
+`formats-ris`.
+
+`net` (HTTP, lookups and link checking), `script`, `plugin` (WebAssembly
+lint rules and transforms, with `wasmtime`), `serde` (`Serialize` and
+`Deserialize` for the types of `bibtex::serialize`), `templates` (Handlebars
+page templates for `publist`), `sign` (Ed25519
+signatures, with `ed25519-dalek`), `encrypt` (encrypted metadata, with
+`age`), `sync` (git synchronisation, which runs the `git` executable) and
+`test-utils` are off by default. A program that only parses depends on the crate with
+`default-features = false, features = ["parser-core"]`, and calls
+`parse_document` or `parse_with`.
+
+*/
+
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+
+#[cfg(feature = "std")]
+pub mod affiliations;
+#[cfg(feature = "store")]
+pub mod analytics;
+#[cfg(feature = "net")]
+pub mod archive;
+#[cfg(feature = "store")]
+pub mod audit;
+#[cfg(feature = "std")]
+pub mod batch;
 pub mod bibtex;
+#[cfg(feature = "std")]
+pub mod citations;
+#[cfg(feature = "std")]
+pub mod compare;
+#[cfg(feature = "std")]
+pub mod config;
+#[cfg(any(all(test, feature = "std"), feature = "test-utils"))]
+pub mod conformance;
+#[cfg(feature = "formats-csl")]
+pub mod csl;
+#[cfg(feature = "std")]
+pub mod datamodel;
+#[cfg(feature = "search")]
+pub mod dedupe;
+#[cfg(feature = "encrypt")]
+pub mod encryption;
+#[cfg(feature = "std")]
+pub mod events;
+#[cfg(feature = "std")]
+pub mod formats;
+#[cfg(feature = "std")]
+pub mod funding;
+#[cfg(feature = "std")]
+pub mod identifiers;
+#[cfg(all(feature = "formats-csl", feature = "formats-ris", feature = "store"))]
+pub mod import;
+#[cfg(feature = "std")]
+pub mod json;
+#[cfg(feature = "std")]
+pub mod latex;
+#[cfg(feature = "net")]
+pub mod links;
+#[cfg(feature = "std")]
+pub mod lint;
+#[cfg(all(feature = "formats-csl", feature = "store"))]
+pub mod lookup;
+#[cfg(feature = "std")]
+pub mod matching;
+#[cfg(feature = "store")]
+pub mod metadata;
+#[cfg(feature = "net")]
+pub mod net;
+#[cfg(feature = "render")]
+pub mod pandoc;
+#[cfg(feature = "plugin")]
+pub mod plugin;
+#[cfg(feature = "store")]
+pub mod provenance;
+#[cfg(feature = "render")]
+pub mod publist;
+#[cfg(feature = "formats-ris")]
+pub mod ris;
+#[cfg(feature = "script")]
+pub mod script;
+#[cfg(feature = "search")]
+pub mod search;
+#[cfg(feature = "sign")]
+pub mod signing;
+#[cfg(feature = "formats-cff")]
+pub mod software;
+#[cfg(feature = "std")]
+pub mod spell;
+#[cfg(feature = "render")]
+pub mod styles;
+#[cfg(feature = "sync")]
+pub mod sync;
+#[cfg(feature = "std")]
+pub mod transform;
+#[cfg(feature = "std")]
+pub mod validate;
@@ -0,0 +1,291 @@
+/*!
+
+Checking that the `url` fields of a bibliography still work (`net` feature).
+
+`LinkChecker` requests every URL, following redirects itself so that it
+can report them, and classifies it as working, redirected or dead. It tries
+to be a polite client: it asks with `HEAD` and only falls back to `GET` for
+servers that do not support it, checks each distinct URL once, and waits
+`LinkCheckOptions::delay` between two requests to the same host.
+
+For a permanently redirected link the suggested replacement is its new
+location; for a dead one it is the entry's `archiveurl` or, failing that,
+the most recent Wayback Machine snapshot (see `archive`).
+
+Servers that refuse to answer robots (401, 403, 429) are reported as
+`Unknown` rather than dead.
+
+*/
+
+use std::collections::HashMap;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+use crate::archive::closest;
+use crate::bibtex::data::Entry;
+use crate::net::{HttpClient, Response};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LinkStatus {
+    Ok,
+    /** Works, but at another address. */
+    Redirected { to: String, permanent: bool },
+    Dead { status: Option<u16>, message: String },
+    /** The server would not say. */
+    Unknown(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LinkCheck {
+    pub key: String,
+    pub url: String,
+    pub status: LinkStatus,
+    pub suggestion: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct LinkCheckOptions {
+    /** Minimum time between two requests to the same host. */
+    pub delay: Duration,
+    pub max_redirects: usize,
+    /** Look up archived copies of dead links. */
+    pub suggest: bool,
+}
+
+impl Default for LinkCheckOptions {
+    fn default() -> Self {
+        LinkCheckOptions { delay: Duration::from_secs(1), max_redirects: 10, suggest: true }
+    }
+}
+
+fn host(url: &str) -> &str {
+    let rest = url.split_once("://").map(|(_, r)| r).unwrap_or(url);
+    rest.split(['/', '?', '#']).next().unwrap_or(rest)
+}
+
+/**
+Resolve the `Location` of a redirect against the URL that answered it.
+*/
+fn resolve(base: &str, location: &str) -> String {
+    if location.contains("://") {
+        return String::from(location);
+    }
+    let (scheme, rest) = base.split_once("://").unwrap_or(("https", base));
+    if let Some(rest) = location.strip_prefix("//") {
+        return format!("{}://{}", scheme, rest);
+    }
+    let origin = format!("{}://{}", scheme, host(base));
+    if location.starts_with('/') {
+        return format!("{}{}", origin, location);
+    }
+    let path = &rest[host(base).len()..];
+    let path = path.split(['?', '#']).next().unwrap_or("");
+    let dir = &path[..path.rfind('/').map(|i| i + 1).unwrap_or(0)];
+    let joined = format!("{}{}", if dir.is_empty() { "/" } else { dir }, location);
+    // drop `.` and `..` segments
+    let mut segments: Vec<&str> = Vec::new();
+    for segment in joined.split('/').skip(1) {
+        match segment {
+            "." => {}
+            ".." => { segments.pop(); }
+            _ => segments.push(segment),
+        }
+    }
+    format!("{}/{}", origin, segments.join("/"))
+}
+
+pub struct LinkChecker<'a> {
+    client: &'a dyn HttpClient,
+    options: LinkCheckOptions,
+    last_request: HashMap<String, Instant>,
+    checked: HashMap<String, LinkStatus>,
+}
+
+impl<'a> LinkChecker<'a> {
+    pub fn new(client: &'a dyn HttpClient, options: LinkCheckOptions) -> LinkChecker<'a> {
+        LinkChecker { client, options, last_request: HashMap::new(), checked: HashMap::new() }
+    }
+
+    fn wait_for(&mut self, url: &str) {
+        let host = host(url).to_lowercase();
+        if let Some(last) = self.last_request.get(&host) {
+            let elapsed = last.elapsed();
+            if elapsed < self.options.delay {
+                sleep(self.options.delay - elapsed);
+            }
+        }
+        self.last_request.insert(host, Instant::now());
+    }
+
+    fn request(&mut self, url: &str) -> Result<Response, String> {
+        self.wait_for(url);
+        match self.client.head(url, &[]) {
+            Ok(r) if r.status != 405 && r.status != 501 => Ok(r),
+            _ => {
+                self.wait_for(url);
+                self.client.get(url, &[]).map_err(|e| e.to_string())
+            }
+        }
+    }
+
+    /**
+    Check one URL, following up to `max_redirects` redirects.
+    */
+    pub fn check_url(&mut self, url: &str) -> LinkStatus {
+        if let Some(status) = self.checked.get(url) {
+            return status.clone();
+        }
+        let mut current = String::from(url);
+        let mut permanent = true;
+        let mut status = LinkStatus::Dead { status: None, message: String::from("too many redirects") };
+        for _ in 0..=self.options.max_redirects {
+            let response = match self.request(&current) {
+                Ok(r) => r,
+                Err(message) => {
+                    status = LinkStatus::Dead { status: None, message };
+                    break;
+                }
+            };
+            let location = response.header("location").map(|l| resolve(&current, l));
+            status = match (response.status, location) {
+                (200..=299, _) if current == url => LinkStatus::Ok,
+                (200..=299, _) => LinkStatus::Redirected { to: current.clone(), permanent },
+                (300..=399, Some(next)) => {
+                    permanent &= matches!(response.status, 301 | 308);
+                    current = next;
+                    continue;
+                }
+                (code @ (401 | 403 | 429), _) => LinkStatus::Unknown(format!("HTTP {}", code)),
+                (code, _) => LinkStatus::Dead { status: Some(code), message: format!("HTTP {}", code) },
+            };
+            break;
+        }
+        self.checked.insert(String::from(url), status.clone());
+        status
+    }
+
+    fn suggest(&mut self, entry: &Entry, url: &str, status: &LinkStatus) -> Option<String> {
+        match status {
+            LinkStatus::Redirected { to, permanent: true } => Some(to.clone()),
+            LinkStatus::Dead { .. } => match entry.get("archiveurl") {
+                Some(archived) => Some(String::from(archived)),
+                None if self.options.suggest => {
+                    self.wait_for("https://archive.org/");
+                    closest(self.client, url).ok().flatten().map(|s| s.url)
+                }
+                None => None,
+            },
+            _ => None,
+        }
+    }
+
+    /**
+    Check the `url` field of every entry that has one, in order.
+    */
+    pub fn check(&mut self, entries: &[Entry]) -> Vec<LinkCheck> {
+        let mut out = Vec::new();
+        for entry in entries {
+            let Some(url) = entry.get("url").map(|u| u.trim().trim_matches(|c| c == '{' || c == '}').to_string()) else { continue };
+            if url.is_empty() {
+                continue;
+            }
+            let status = self.check_url(&url);
+            let suggestion = self.suggest(entry, &url, &status);
+            out.push(LinkCheck { key: String::from(entry.key()), url, status, suggestion });
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use std::cell::RefCell;
+    use crate::bibtex::data::BibType;
+    use crate::net::NetError;
+
+    struct Web {
+        requests: RefCell<Vec<String>>,
+    }
+
+    fn respond(status: u16, location: Option<&str>) -> Result<Response, NetError> {
+        let headers = location.map(|l| vec![(String::from("Location"), String::from(l))]).unwrap_or_default();
+        Ok(Response { status, headers, body: String::new() })
+    }
+
+    impl HttpClient for Web {
+        fn get(&self, url: &str, _headers: &[(&str, &str)]) -> Result<Response, NetError> {
+            self.requests.borrow_mut().push(format!("GET {}", url));
+            match url {
+                "https://old.example/nohead" => respond(200, None),
+                u if u.starts_with("https://archive.org/wayback/available") => Ok(Response {
+                    status: 200,
+                    headers: vec![],
+                    body: String::from(r#"{"archived_snapshots": {"closest": {"available": true, "timestamp": "20200101000000",
+                        "url": "http://web.archive.org/web/20200101000000/https://gone.example/"}}}"#),
+                }),
+                _ => respond(404, None),
+            }
+        }
+
+        fn head(&self, url: &str, _headers: &[(&str, &str)]) -> Result<Response, NetError> {
+            self.requests.borrow_mut().push(format!("HEAD {}", url));
+            match url {
+                "https://old.example/a/paper" => respond(301, Some("../b/paper")),
+                "https://old.example/b/paper" => respond(302, Some("https://new.example/paper")),
+                "https://new.example/paper" => respond(200, None),
+                "https://old.example/moved" => respond(308, Some("/here")),
+                "https://old.example/here" => respond(200, None),
+                "https://old.example/nohead" => respond(405, None),
+                "https://shy.example/" => respond(403, None),
+                "https://loop.example/" => respond(302, Some("https://loop.example/")),
+                _ => respond(404, None),
+            }
+        }
+    }
+
+    #[test]
+    fn test_check() {
+        let web = Web { requests: RefCell::new(vec![]) };
+        let options = LinkCheckOptions { delay: Duration::ZERO, max_redirects: 3, suggest: true };
+        let mut checker = LinkChecker::new(&web, options);
+        assert_eq!(checker.check_url("https://old.example/a/paper"),
+            LinkStatus::Redirected { to: String::from("https://new.example/paper"), permanent: false });
+        assert_eq!(checker.check_url("https://old.example/nohead"), LinkStatus::Ok);
+        assert_eq!(checker.check_url("https://shy.example/"), LinkStatus::Unknown(String::from("HTTP 403")));
+        assert!(matches!(checker.check_url("https://loop.example/"), LinkStatus::Dead { status: None, .. }));
+
+        let entry = |key: &str, url: &str| {
+            let mut e = Entry::new(BibType::Misc, key);
+            e.set("url", url);
+            e
+        };
+        let mut archived = entry("archived", "https://gone.example/x");
+        archived.set("archiveurl", "https://web.archive.org/web/2019/https://gone.example/x");
+        let checks = checker.check(&[
+            entry("moved", "https://old.example/moved"),
+            entry("gone", "https://gone.example/"),
+            archived,
+            entry("again", "https://old.example/moved"),
+            Entry::new(BibType::Misc, "none"),
+        ]);
+        let found: Vec<(&str, Option<&str>)> = checks.iter().map(|c| (c.key.as_str(), c.suggestion.as_deref())).collect();
+        assert_eq!(found, vec![
+            ("moved", Some("https://old.example/here")),
+            ("gone", Some("https://web.archive.org/web/20200101000000/https://gone.example/")),
+            ("archived", Some("https://web.archive.org/web/2019/https://gone.example/x")),
+            ("again", Some("https://old.example/here")),
+        ]);
+        assert_eq!(checks[1].status, LinkStatus::Dead { status: Some(404), message: String::from("HTTP 404") });
+        let requests = web.requests.borrow();
+        assert_eq!(requests.iter().filter(|r| r.contains("old.example/moved")).count(), 1);
+    }
+
+    #[test]
+    fn test_resolve() {
+        assert_eq!(resolve("https://a.example/x/y?q=1", "z"), "https://a.example/x/z");
+        assert_eq!(resolve("https://a.example", "z"), "https://a.example/z");
+        assert_eq!(resolve("http://a.example/x/y", "//b.example/"), "http://b.example/");
+        assert_eq!(host("https://a.example:8080/x"), "a.example:8080");
+    }
+}
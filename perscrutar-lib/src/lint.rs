@@ -0,0 +1,555 @@
+/*!
+
+Bibliography quality checks.
+
+Besides per-entry checks against the `TypeRegistry`, conference papers and
+book chapters are checked for the usual mistakes: a `booktitle` that
+repeats the title, a `crossref` to a missing entry, and volume-level fields
+(`editor`, `publisher`, ...) placed on the paper instead of the volume,
+and a year in the booktitle that contradicts the `year` field. Theses are
+checked for the BibTeX and biblatex conventions being mixed up
+(`bibtex::theses`), patents for a holder in `assignee` and for numbers not
+written the way the office writes them (`bibtex::patents`), and `@misc`
+entries with a court or docket for being court decisions (`bibtex::legal`).
+Fields a `crossref`ed entry provides count as present. Aliases in `ids`
+must not collide with another entry's key or aliases, and keys should not
+be near duplicates of each other (`bibtex::keys`).
+
+Values are checked for the slips that survive a LaTeX run unnoticed: a
+`doi` that is not a DOI or is written as a link, a `year` that is not a
+plausible four-digit year, a title in all capitals (which no style can
+bring back to title or sentence case) and whitespace around a value.
+
+`check_datamodel` checks entries against biblatex's default data model
+instead of the `TypeRegistry`, as biber does (`validate::RuleSet::biber`),
+for documents built with biblatex. Values biber would drop are errors.
+
+`check_located` points each diagnostic at the line of its entry, for
+entries parsed with `parse_with_spans`.
+
+`check` runs every rule over a list of entries and returns the findings as
+`Diagnostic`s. Whether those findings should fail a build is a separate
+decision made by an `ExitPolicy`, so the same diagnostics can be reported
+leniently on a laptop and strictly in CI:
+
+- by default only errors fail;
+- `deny` lowers the threshold, e.g. to fail on warnings too;
+- `max_warnings` tolerates up to that many warnings before failing.
+
+*/
+
+use std::collections::HashMap;
+use std::fmt;
+use crate::bibtex::bibliography::Bibliography;
+use crate::bibtex::conference;
+use std::time::{SystemTime, UNIX_EPOCH};
+use crate::bibtex::data::{BibType, Entry};
+use crate::bibtex::keys::{near_duplicate, Similarity};
+use crate::bibtex::legal;
+use crate::bibtex::parser::Located;
+use crate::bibtex::patents;
+use crate::bibtex::sorting;
+use crate::bibtex::theses;
+use crate::bibtex::types::TypeRegistry;
+use crate::bibtex::volumes;
+use crate::identifiers::find_dois;
+use crate::validate::{IssueKind, RuleSet};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+impl Severity {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        }
+    }
+
+    /**
+    Parse a severity name; plurals are accepted so that `--deny warnings`
+    reads naturally.
+    */
+    pub fn parse(name: &str) -> Option<Severity> {
+        match name.to_lowercase().as_str() {
+            "info" | "infos" | "note" | "notes" => Some(Severity::Info),
+            "warning" | "warnings" | "warn" => Some(Severity::Warning),
+            "error" | "errors" => Some(Severity::Error),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /** Citation key of the offending entry. */
+    pub key: String,
+    /** Short identifier of the rule, e.g. `missing-field`. */
+    pub rule: &'static str,
+    pub severity: Severity,
+    pub message: String,
+    /** 1-based line of the entry in its file, if known. */
+    pub line: Option<usize>,
+}
+
+impl Diagnostic {
+    pub fn new(key: &str, rule: &'static str, severity: Severity, message: &str) -> Diagnostic {
+        Diagnostic { key: String::from(key), rule, severity, message: String::from(message), line: None }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {} [{}] {}", self.key, self.severity, self.rule, self.message)
+    }
+}
+
+/**
+Checks that only need the entry itself.
+*/
+pub fn check_entry(entry: &Entry, registry: &TypeRegistry) -> Vec<Diagnostic> {
+    check_fields(entry, None, registry)
+}
+
+fn has_value(entry: &Entry, field: &str) -> bool {
+    entry.get(field).map(|v| !v.trim().is_empty()).unwrap_or(false)
+}
+
+/**
+Whether `field` is set on `entry` or inherited from its crossref `parent`.
+A parent's `title` doubles as the child's `booktitle`, as in biblatex.
+*/
+fn has_inherited(entry: &Entry, parent: Option<&Entry>, field: &str) -> bool {
+    has_value(entry, field) || parent.map(|p| {
+        has_value(p, field) || (field == "booktitle" && has_value(p, "title"))
+    }).unwrap_or(false)
+}
+
+fn check_fields(entry: &Entry, parent: Option<&Entry>, registry: &TypeRegistry) -> Vec<Diagnostic> {
+    let mut out = Vec::new();
+    let key = entry.key();
+    match registry.schema(entry.entry_type()) {
+        None => out.push(Diagnostic::new(key, "unknown-type", Severity::Warning,
+            &format!("unknown entry type @{}", entry.entry_type()))),
+        Some(schema) => {
+            for req in schema.required() {
+                if !req.is_satisfied_by(|f| has_inherited(entry, parent, f)) {
+                    out.push(Diagnostic::new(key, "missing-field", Severity::Error,
+                        &format!("@{} requires {}", entry.entry_type(), req.fields().join(" or "))));
+                }
+            }
+        }
+    }
+    for name in entry.field_names() {
+        if entry.get(name).map(|v| v.trim().is_empty()).unwrap_or(false) {
+            out.push(Diagnostic::new(key, "empty-field", Severity::Warning,
+                &format!("field `{}` is empty", name)));
+        }
+    }
+    out.extend(check_values(entry));
+    out.extend(volumes::check_entry(entry));
+    out.extend(theses::check_entry(entry));
+    out.extend(patents::check_entry(entry));
+    out.extend(legal::check_entry(entry));
+    // cases and statutes are cited by their title
+    if sorting::name_source(entry).is_none() && !legal::is_legal(entry) {
+        out.push(Diagnostic::new(key, "no-sort-key", Severity::Info,
+            "no author, editor or `key` field to sort and label the entry by"));
+    }
+    out
+}
+
+/** The current year, by the system clock. */
+fn current_year() -> i64 {
+    let seconds = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    // close enough for telling a plausible year from a typo
+    1970 + (seconds / 31_556_952) as i64
+}
+
+/** Whether `title` has at least two words and no lower-case letter outside braces and commands. */
+fn is_all_caps(title: &str) -> bool {
+    let mut depth = 0usize;
+    let mut command = false;
+    let mut letters = 0;
+    for c in title.chars() {
+        if command && c.is_alphabetic() {
+            continue;
+        }
+        command = c == '\\';
+        match c {
+            '{' => depth += 1,
+            '}' => depth = depth.saturating_sub(1),
+            c if depth == 0 && c.is_lowercase() => return false,
+            c if depth == 0 && c.is_uppercase() => letters += 1,
+            _ => {}
+        }
+    }
+    letters >= 4 && title.split_whitespace().count() >= 2
+}
+
+/**
+Value heuristics: DOI format, implausible years, titles in all capitals
+and whitespace around values.
+*/
+fn check_values(entry: &Entry) -> Vec<Diagnostic> {
+    let mut out = Vec::new();
+    let key = entry.key();
+    if let Some(doi) = entry.get("doi").map(str::trim).filter(|d| !d.is_empty()) {
+        let bare = ["https://doi.org/", "http://doi.org/", "https://dx.doi.org/", "http://dx.doi.org/", "doi:"].iter()
+            .find_map(|prefix| doi.strip_prefix(prefix));
+        if find_dois(bare.unwrap_or(doi)) != [bare.unwrap_or(doi)] {
+            out.push(Diagnostic::new(key, "doi-format", Severity::Warning, &format!("`{}` is not a DOI", doi)));
+        } else if let Some(bare) = bare {
+            out.push(Diagnostic::new(key, "doi-format", Severity::Info,
+                &format!("doi is written as a link or with a prefix; give it bare, as `{}`", bare)));
+        }
+    }
+    if let Some(year) = entry.get("year").map(|y| y.trim().trim_matches(['{', '}'])).filter(|y| !y.is_empty()) {
+        let latest = current_year() + 1;
+        match year.parse::<i64>() {
+            Ok(y) if year.len() == 4 && (1400..=latest).contains(&y) => {}
+            Ok(y) if year.len() == 4 => out.push(Diagnostic::new(key, "suspicious-year", Severity::Warning,
+                &format!("year {} is {}", y, if y > latest { "in the future" } else { "implausibly early" }))),
+            _ => out.push(Diagnostic::new(key, "suspicious-year", Severity::Warning,
+                &format!("year `{}` is not a four-digit year", year))),
+        }
+    }
+    if entry.get("title").map(is_all_caps).unwrap_or(false) {
+        out.push(Diagnostic::new(key, "title-all-caps", Severity::Warning,
+            "title is in all capitals, which styles cannot change to title or sentence case"));
+    }
+    for (name, value) in entry.fields() {
+        if !value.trim().is_empty() && value.trim() != value {
+            out.push(Diagnostic::new(key, "trailing-whitespace", Severity::Info,
+                &format!("field `{}` starts or ends with whitespace", name)));
+        }
+    }
+    out
+}
+
+fn comparable(s: &str) -> String {
+    s.chars().filter(|c| *c != '{' && *c != '}').collect::<String>()
+        .split_whitespace().collect::<Vec<&str>>().join(" ").to_lowercase()
+}
+
+/** Fields describing the containing volume rather than the contribution. */
+const VOLUME_FIELDS: [&str; 4] = ["editor", "publisher", "address", "organization"];
+
+/**
+Conference paper and book chapter checks: `booktitle` repeating the `title`,
+a `crossref` to a missing or unsuitable entry, and volume-level fields kept
+on the contribution instead of the volume it cross-references.
+*/
+fn check_contribution(entry: &Entry, parent: Option<&Entry>, entries: &[Entry]) -> Vec<Diagnostic> {
+    let mut out = Vec::new();
+    let key = entry.key();
+    let container = match entry.entry_type() {
+        BibType::InProceedings => "proceedings",
+        BibType::InCollection => "book",
+        _ => return out,
+    };
+    out.extend(conference::check_entry(entry));
+    if let (Some(title), Some(booktitle)) = (entry.get("title"), entry.get("booktitle")) {
+        if !title.trim().is_empty() && comparable(title) == comparable(booktitle) {
+            out.push(Diagnostic::new(key, "booktitle-is-title", Severity::Warning,
+                "booktitle repeats the title; it should name the proceedings or book"));
+        }
+    }
+    let Some(target) = entry.get("crossref") else { return out };
+    let Some(parent) = parent else {
+        let message = if entries.iter().any(|e| e.key().eq_ignore_ascii_case(target)) {
+            format!("crossref `{}` differs in case from the entry's key", target)
+        } else {
+            format!("crossref `{}` does not exist", target)
+        };
+        out.push(Diagnostic::new(key, "crossref-missing", Severity::Error, &message));
+        return out;
+    };
+    if parent.entry_type().name() != container {
+        out.push(Diagnostic::new(key, "crossref-type", Severity::Warning,
+            &format!("crossref `{}` is a @{}, expected a @{}", target, parent.entry_type(), container)));
+    }
+    for field in VOLUME_FIELDS {
+        if has_value(entry, field) && !has_value(parent, field) {
+            out.push(Diagnostic::new(key, "misplaced-field", Severity::Warning,
+                &format!("`{}` belongs on the cross-referenced entry `{}`", field, target)));
+        }
+    }
+    out
+}
+
+/**
+`ids` aliases of `entry` that are also the key or an alias of another entry,
+so that citing them is ambiguous.
+*/
+fn check_ids(entry: &Entry, entries: &[Entry]) -> Vec<Diagnostic> {
+    let mut out = Vec::new();
+    for id in entry.ids() {
+        if id == entry.key() {
+            out.push(Diagnostic::new(entry.key(), "alias-collision", Severity::Warning,
+                "the entry lists its own key in ids"));
+        } else if let Some(other) = entries.iter().find(|e| e.key() == id) {
+            out.push(Diagnostic::new(entry.key(), "alias-collision", Severity::Error,
+                &format!("alias `{}` is the key of another @{} entry", id, other.entry_type())));
+        } else if entries.iter().any(|e| !std::ptr::eq(e, entry) && e.ids().contains(&id)) {
+            out.push(Diagnostic::new(entry.key(), "alias-collision", Severity::Error,
+                &format!("alias `{}` is also an alias of another entry", id)));
+        }
+    }
+    out
+}
+
+/**
+Keys of earlier entries that `entry`'s key is easily mistaken for.
+*/
+fn check_key(entry: &Entry, earlier: &[Entry]) -> Vec<Diagnostic> {
+    let mut out = Vec::new();
+    for other in earlier {
+        let (severity, how) = match near_duplicate(entry.key(), other.key()) {
+            Some(Similarity::Case) => (Severity::Warning, "differs only in case from"),
+            Some(Similarity::Edit) => (Severity::Info, "is one character away from"),
+            None => continue,
+        };
+        out.push(Diagnostic::new(entry.key(), "near-duplicate-key", severity,
+            &format!("key {} `{}`", how, other.key())));
+    }
+    out
+}
+
+/**
+Run all checks over `entries`, in entry order.
+*/
+pub fn check(entries: &[Entry], registry: &TypeRegistry) -> Vec<Diagnostic> {
+    check_at(entries, &[], registry)
+}
+
+/**
+`check` for entries as `parse_with_spans` gives them, with the line of
+each diagnostic's entry.
+*/
+pub fn check_located(located: &[Located], registry: &TypeRegistry) -> Vec<Diagnostic> {
+    let entries: Vec<Entry> = located.iter().map(|(entry, _)| entry.clone()).collect();
+    let lines: Vec<usize> = located.iter().map(|(_, span)| span.line).collect();
+    check_at(&entries, &lines, registry)
+}
+
+/** `check`, setting the line of the diagnostics of the `i`th entry to `lines[i]`. */
+fn check_at(entries: &[Entry], lines: &[usize], registry: &TypeRegistry) -> Vec<Diagnostic> {
+    let mut seen: HashMap<&str, usize> = HashMap::new();
+    let mut out = Vec::new();
+    for (i, entry) in entries.iter().enumerate() {
+        let start = out.len();
+        let count = seen.entry(entry.key()).or_insert(0);
+        *count += 1;
+        if *count == 2 {
+            out.push(Diagnostic::new(entry.key(), "duplicate-key", Severity::Error, "key is used by more than one entry"));
+        }
+        out.extend(check_key(entry, &entries[..i]));
+        out.extend(check_ids(entry, entries));
+        let parent = entry.get("crossref").and_then(|k| entries.iter().find(|e| e.key() == k));
+        out.extend(check_fields(entry, parent, registry));
+        out.extend(check_contribution(entry, parent, entries));
+        for d in out[start..].iter_mut() {
+            d.line = lines.get(i).copied();
+        }
+    }
+    out
+}
+
+/**
+The issues biber finds with `entries` against biblatex's default data
+model, as `datamodel` diagnostics, with the line of each entry as in
+`check_at`. Empty values are left to `check`.
+*/
+pub fn check_datamodel(entries: &[Entry], lines: &[usize]) -> Vec<Diagnostic> {
+    let bib = Bibliography::from_entries(entries.to_vec());
+    RuleSet::biber().validate_bibliography(&bib).into_iter()
+        .filter(|issue| issue.kind != IssueKind::EmptyValue)
+        .map(|issue| {
+            let severity = match issue.kind {
+                IssueKind::MissingField | IssueKind::InvalidValue => Severity::Error,
+                _ => Severity::Warning,
+            };
+            Diagnostic {
+                line: entries.iter().position(|e| e.key() == issue.key).and_then(|i| lines.get(i).copied()),
+                ..Diagnostic::new(&issue.key, "datamodel", severity, &issue.message())
+            }
+        })
+        .collect()
+}
+
+/**
+Number of diagnostics at each severity, indexed by `Severity as usize`.
+*/
+pub fn counts(diagnostics: &[Diagnostic]) -> [usize; 3] {
+    let mut c = [0; 3];
+    for d in diagnostics {
+        c[d.severity as usize] += 1;
+    }
+    c
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExitPolicy {
+    /** Lowest severity that fails the run. */
+    pub deny: Severity,
+    /** Fail when there are more warnings than this, even if warnings are not denied. */
+    pub max_warnings: Option<usize>,
+}
+
+impl Default for ExitPolicy {
+    fn default() -> Self {
+        ExitPolicy { deny: Severity::Error, max_warnings: None }
+    }
+}
+
+impl ExitPolicy {
+    pub fn passes(&self, diagnostics: &[Diagnostic]) -> bool {
+        let c = counts(diagnostics);
+        let denied = diagnostics.iter().any(|d| d.severity >= self.deny);
+        let too_many = self.max_warnings.map(|max| c[Severity::Warning as usize] > max).unwrap_or(false);
+        !denied && !too_many
+    }
+
+    /**
+    Process exit status for a run: 0 if it passes, 1 otherwise.
+    */
+    pub fn exit_code(&self, diagnostics: &[Diagnostic]) -> i32 {
+        if self.passes(diagnostics) { 0 } else { 1 }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::bibtex::parser::{parse_with_spans, Macros, ParseOptions};
+
+    fn entries() -> Vec<Entry> {
+        let mut a = Entry::new(BibType::Article, "Cox-CFT");
+        a.set("title", "Galois theory");
+        a.set("author", "Cox, David");
+        a.set("journal", "");
+        let mut b = Entry::new(BibType::parse("gadget"), "Cox-CFT");
+        b.set("title", "Widgets");
+        vec![a, b]
+    }
+
+    #[test]
+    fn test_check() {
+        let diags = check(&entries(), &TypeRegistry::default());
+        let rules: Vec<&str> = diags.iter().map(|d| d.rule).collect();
+        assert!(rules.contains(&"duplicate-key"));
+        assert!(rules.contains(&"unknown-type"));
+        assert!(rules.contains(&"empty-field"));
+        assert!(diags.iter().any(|d| d.rule == "missing-field" && d.message.contains("journal")));
+        assert_eq!(Severity::parse("Warnings"), Some(Severity::Warning));
+
+        let mut c = Entry::new(BibType::parse("misc"), "c");
+        c.set("ids", "Cox-CFT, cox2013");
+        let mut d = Entry::new(BibType::parse("misc"), "d");
+        d.set("ids", "cox2013");
+        let diags = check(&[entries()[0].clone(), c, d], &TypeRegistry::default());
+        let collisions: Vec<&str> = diags.iter().filter(|d| d.rule == "alias-collision").map(|d| d.key.as_str()).collect();
+        assert_eq!(collisions, vec!["c", "c", "d"]);
+
+        let keys = [Entry::new(BibType::Misc, "Cox2013"), Entry::new(BibType::Misc, "cox2013"),
+                    Entry::new(BibType::Misc, "cox2013a"), Entry::new(BibType::Misc, "cox2013b")];
+        let diags = check(&keys, &TypeRegistry::default());
+        let near: Vec<(&str, Severity)> = diags.iter().filter(|d| d.rule == "near-duplicate-key")
+            .map(|d| (d.key.as_str(), d.severity)).collect();
+        assert_eq!(near, vec![("cox2013", Severity::Warning), ("cox2013a", Severity::Info), ("cox2013a", Severity::Info),
+                              ("cox2013b", Severity::Info), ("cox2013b", Severity::Info)]);
+    }
+
+    #[test]
+    fn test_values() {
+        let mut e = Entry::new(BibType::Misc, "e");
+        e.set("doi", "https://doi.org/10.1093/comjnl/27.2.97");
+        e.set("year", "1894");
+        e.set("title", "THE ART OF {C}OMPUTER PROGRAMMING \\emph{VOL}");
+        e.set("note", "padded ");
+        let mut f = Entry::new(BibType::Misc, "f");
+        f.set("doi", "10.1093");
+        f.set("year", "84");
+        f.set("title", "The {ART} of Computer Programming");
+        let mut g = Entry::new(BibType::Misc, "g");
+        g.set("year", "3020");
+        let diags = check(&[e, f, g], &TypeRegistry::default());
+        let found: Vec<(&str, &str, Severity)> = diags.iter()
+            .filter(|d| matches!(d.rule, "doi-format" | "suspicious-year" | "title-all-caps" | "trailing-whitespace"))
+            .map(|d| (d.key.as_str(), d.rule, d.severity)).collect();
+        assert_eq!(found, vec![
+            ("e", "doi-format", Severity::Info), ("e", "title-all-caps", Severity::Warning),
+            ("e", "trailing-whitespace", Severity::Info),
+            ("f", "doi-format", Severity::Warning), ("f", "suspicious-year", Severity::Warning),
+            ("g", "suspicious-year", Severity::Warning),
+        ]);
+
+        let text = "@misc{a, title = {A}}\n\n@misc{b,\n  year = {84}}\n";
+        let (located, _) = parse_with_spans(text, &mut Macros::new(), ParseOptions::default()).unwrap();
+        let diags = check_located(&located, &TypeRegistry::default());
+        assert!(diags.iter().any(|d| d.key == "b" && d.rule == "suspicious-year" && d.line == Some(3)));
+        assert!(diags.iter().all(|d| d.line.is_some()));
+    }
+
+    #[test]
+    fn test_contributions() {
+        let mut proc = Entry::new(BibType::parse("proceedings"), "POPL84");
+        proc.set("title", "Principles of Programming Languages");
+        proc.set("year", "1984");
+        proc.set("publisher", "ACM");
+        proc.set("organization", "ACM");
+        let mut paper = Entry::new(BibType::InProceedings, "paper");
+        paper.set("author", "Knuth, Donald");
+        paper.set("title", "Literate programming");
+        paper.set("crossref", "POPL84");
+        paper.set("editor", "Someone");
+        let mut same = Entry::new(BibType::InCollection, "chapter");
+        same.set("title", "{Galois} Theory");
+        same.set("booktitle", "Galois  theory");
+        same.set("crossref", "popl84");
+
+        let diags = check(&[proc, paper, same], &TypeRegistry::default());
+        let of = |key: &str| diags.iter().filter(|d| d.key == key).map(|d| d.rule).collect::<Vec<&str>>();
+        assert!(of("POPL84").is_empty());
+        assert_eq!(of("paper"), vec!["misplaced-field"]);
+        assert!(of("chapter").contains(&"booktitle-is-title"));
+        assert!(diags.iter().any(|d| d.rule == "crossref-missing" && d.message.contains("differs in case")));
+    }
+
+    #[test]
+    fn test_non_ascii_names() {
+        let mut e = Entry::new(BibType::Article, "e");
+        e.set("author", "Jürgen Müller and Åsa Ångström and 村上 春樹");
+        e.set("editor", "Ünal, Öykü");
+        e.set("title", "Über Bäume");
+        e.set("journal", "Zeitschrift");
+        e.set("year", "2001");
+        let diags = check(&[e], &TypeRegistry::default());
+        assert!(diags.iter().all(|d| d.rule != "missing-field"), "{:?}", diags);
+    }
+
+    #[test]
+    fn test_exit_policy() {
+        let warn = vec![Diagnostic::new("a", "empty-field", Severity::Warning, ""); 3];
+        let err = vec![Diagnostic::new("a", "missing-field", Severity::Error, "")];
+        assert_eq!(ExitPolicy::default().exit_code(&warn), 0);
+        assert_eq!(ExitPolicy::default().exit_code(&err), 1);
+        assert_eq!(ExitPolicy { deny: Severity::Warning, ..ExitPolicy::default() }.exit_code(&warn), 1);
+        assert_eq!(ExitPolicy { max_warnings: Some(3), ..ExitPolicy::default() }.exit_code(&warn), 0);
+        assert_eq!(ExitPolicy { max_warnings: Some(2), ..ExitPolicy::default() }.exit_code(&warn), 1);
+        assert!(ExitPolicy { deny: Severity::Info, ..ExitPolicy::default() }.passes(&[]));
+    }
+}
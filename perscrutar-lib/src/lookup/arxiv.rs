@@ -0,0 +1,165 @@
+/*!
+
+Preprints from the arXiv API (<https://info.arxiv.org/help/api>).
+
+A query by identifier answers with an Atom feed holding one `entry`:
+
+```text
+<entry>
+  <id>http://arxiv.org/abs/2101.00001v2</id>
+  <published>2021-01-01T18:00:00Z</published>
+  <title>A preprint</title>
+  <summary>We show that ...</summary>
+  <author><name>Jane Doe</name></author>
+  <arxiv:doi>10.1000/xyz</arxiv:doi>
+  <arxiv:primary_category term="cs.CL" scheme="http://arxiv.org/schemas/atom"/>
+</entry>
+```
+
+`entry_from_atom` turns it into a `@misc` entry the way arXiv's own BibTeX
+export does: `eprint` is the identifier without its version, with
+`archivePrefix = {arXiv}` and the primary category in `primaryClass`.
+Identifiers may be new style (`2101.00001`) or old (`hep-th/9901001`); see
+`identifiers`.
+
+*/
+
+use crate::bibtex::data::{BibType, Entry};
+use crate::bibtex::keys::citation_key;
+use crate::bibtex::names::{bibtex_name, parse_names};
+use crate::identifiers::{find_arxiv, Identifier};
+use crate::lookup::LookupError;
+use crate::lookup::xml::{attribute, tags, text, texts};
+#[cfg(feature = "net")]
+use crate::net::{self, HttpClient};
+
+pub const API: &str = "https://export.arxiv.org/api/query";
+
+/** The API query for the preprint `id`. */
+pub fn query_url(id: &str) -> String {
+    format!("{}?id_list={}&max_results=1", API, id)
+}
+
+/** The identifier in `id`, an `arXiv:` reference or an arXiv link, without its version. */
+fn normalize(id: &str) -> Option<String> {
+    match Identifier::parse(id) {
+        Some(Identifier::Arxiv(id)) => Some(id),
+        _ => find_arxiv(id).into_iter().next(),
+    }
+}
+
+/**
+The entry for the first `entry` of an arXiv Atom feed. A feed without
+one, or with the error entry arXiv sends for unknown identifiers, is
+`Invalid`.
+*/
+pub fn entry_from_atom(feed: &str) -> Result<Entry, LookupError> {
+    let invalid = |what: &str| LookupError::Invalid(format!("no {} in arXiv feed", what));
+    let start = feed.find("<entry>").ok_or_else(|| invalid("entry"))?;
+    let atom = &feed[start..feed[start..].find("</entry>").map(|end| start + end).unwrap_or(feed.len())];
+    let id = text(atom, "id")
+        .and_then(|url| url.split_once("/abs/").and_then(|(_, id)| normalize(id)))
+        .ok_or_else(|| invalid("arXiv identifier"))?;
+
+    let mut entry = Entry::new(BibType::Misc, "");
+    let authors: Vec<String> = texts(atom, "name")
+        .filter_map(|name| parse_names(&name).into_iter().next())
+        .map(|n| bibtex_name(&n.first, &n.von, &n.last, &n.jr))
+        .collect();
+    if !authors.is_empty() {
+        entry.set("author", &authors.join(" and "));
+    }
+    entry.set("title", &text(atom, "title").ok_or_else(|| invalid("title"))?);
+    if let Some(published) = text(atom, "published").filter(|p| p.get(..10).is_some_and(|d| d.is_ascii())) {
+        entry.set("date", &published[..10]);
+        entry.set("year", &published[..4]);
+    }
+    entry.set("eprint", &id);
+    entry.set("archivePrefix", "arXiv");
+    if let Some(category) = tags(atom, "arxiv:primary_category").next().and_then(|t| attribute(t, "term")) {
+        entry.set("primaryClass", category);
+    }
+    if let Some(doi) = text(atom, "arxiv:doi") {
+        entry.set("doi", &doi);
+    }
+    entry.set("url", &format!("https://arxiv.org/abs/{}", id));
+    if let Some(summary) = text(atom, "summary") {
+        entry.set("abstract", &summary);
+    }
+    entry.set_key(&citation_key(&entry));
+    Ok(entry)
+}
+
+/**
+Fetch the preprint `id`, new or old style, with or without a version, as
+an entry.
+*/
+#[cfg(feature = "net")]
+pub fn fetch_entry<C: HttpClient>(client: &C, id: &str) -> Result<Entry, LookupError> {
+    let id = normalize(id).ok_or_else(|| LookupError::Invalid(format!("`{}` is not an arXiv identifier", id)))?;
+    // identifiers are letters, digits, `.`, `-` and `/`, which need no escaping
+    let url = query_url(&id);
+    let response = net::expect_success(&url, client.get(&url, &[("Accept", "application/atom+xml")])?)?;
+    entry_from_atom(&response.body)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    const FEED: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<feed xmlns="http://www.w3.org/2005/Atom">
+  <title type="html">ArXiv Query: id_list=hep-th/9901001</title>
+  <entry>
+    <id>http://arxiv.org/abs/hep-th/9901001v3</id>
+    <published>1999-01-04T12:00:00Z</published>
+    <title>Strings &amp; branes
+      in a box</title>
+    <summary>  We study
+      things.
+    </summary>
+    <author><name>Jane Q. Doe</name></author>
+    <author><name>Ludwig van Beethoven</name></author>
+    <arxiv:doi xmlns:arxiv="http://arxiv.org/schemas/atom">10.1000/xyz</arxiv:doi>
+    <arxiv:primary_category xmlns:arxiv="http://arxiv.org/schemas/atom" term="hep-th" scheme="http://arxiv.org/schemas/atom"/>
+    <category term="hep-th" scheme="http://arxiv.org/schemas/atom"/>
+  </entry>
+</feed>"#;
+
+    #[test]
+    fn test_entry_from_atom() {
+        let e = entry_from_atom(FEED).unwrap();
+        assert_eq!(e.key(), "doe1999");
+        assert_eq!(e.entry_type(), &BibType::Misc);
+        assert_eq!(e.get("author"), Some("Doe, Jane Q. and van Beethoven, Ludwig"));
+        assert_eq!(e.get("title"), Some("Strings & branes in a box"));
+        assert_eq!(e.get("abstract"), Some("We study things."));
+        assert_eq!((e.get("eprint"), e.get("archivePrefix"), e.get("primaryClass")), (Some("hep-th/9901001"), Some("arXiv"), Some("hep-th")));
+        assert_eq!((e.get("date"), e.get("year")), (Some("1999-01-04"), Some("1999")));
+        assert_eq!(e.get("doi"), Some("10.1000/xyz"));
+        assert_eq!(e.get("url"), Some("https://arxiv.org/abs/hep-th/9901001"));
+        assert_eq!(query_url("2101.00001"), "https://export.arxiv.org/api/query?id_list=2101.00001&max_results=1");
+
+        let error = r#"<feed><entry><id>http://arxiv.org/api/errors#incorrect_id_format_for_1</id><title>Error</title></entry></feed>"#;
+        assert!(matches!(entry_from_atom(error), Err(LookupError::Invalid(_))));
+        assert!(matches!(entry_from_atom("<feed></feed>"), Err(LookupError::Invalid(_))));
+    }
+
+    #[cfg(feature = "net")]
+    #[test]
+    fn test_fetch_entry() {
+        use crate::net::{NetError, Response};
+
+        struct Canned;
+        impl HttpClient for Canned {
+            fn get(&self, url: &str, _: &[(&str, &str)]) -> Result<Response, NetError> {
+                assert_eq!(url, "https://export.arxiv.org/api/query?id_list=hep-th/9901001&max_results=1");
+                Ok(Response { status: 200, headers: vec![], body: String::from(FEED) })
+            }
+        }
+
+        assert_eq!(fetch_entry(&Canned, "arXiv:hep-th/9901001v1").unwrap().get("eprint"), Some("hep-th/9901001"));
+        assert!(fetch_entry(&Canned, "not an id").is_err());
+    }
+}
@@ -0,0 +1,463 @@
+/*!
+
+Crossref metadata (<https://api.crossref.org>).
+
+`entry_from_work` builds a whole entry from a Crossref work: type, authors
+and editors, title and container, date, locator, publisher, identifiers,
+abstract and funding. With the `net` feature, `fetch_entry` looks a DOI
+up and returns that entry; `fetch_entry_async` does the same on a
+background thread, as a future to `.await`.
+
+*/
+
+use crate::affiliations::{Affiliation, AuthorAffiliation};
+use crate::bibtex::data::{BibType, Entry};
+use crate::bibtex::keys::citation_key;
+use crate::bibtex::names::bibtex_name;
+use crate::json::JsonValue;
+use crate::funding::{Funder, Grant};
+use crate::lookup::LookupError;
+#[cfg(feature = "net")]
+use crate::{affiliations::AffiliationStore, funding::merge_grant, json, net::{self, Background, HttpClient}};
+#[cfg(feature = "net")]
+use crate::{lookup::policy::{EnrichPolicy, Merged, Rule}, metadata::MetadataStore, provenance::Source};
+
+pub const API: &str = "https://api.crossref.org";
+
+/**
+Grants listed in the `funder` array of a Crossref work (the `message`
+object of a `/works/{doi}` response).
+*/
+pub fn grants_from_work(work: &JsonValue) -> Vec<Grant> {
+    let funders = match work.get("funder").and_then(|f| f.as_array()) {
+        Some(f) => f,
+        None => return Vec::new(),
+    };
+    funders.iter().filter_map(|f| {
+        let name = f.get("name")?.as_str()?.trim();
+        let doi = f.get("DOI").and_then(|d| d.as_str()).map(|d| d.to_lowercase());
+        let awards = f.get("award").and_then(|a| a.as_array()).unwrap_or(&[])
+            .iter()
+            .filter_map(|a| a.as_str())
+            .map(|a| a.trim().to_string())
+            .filter(|a| !a.is_empty())
+            .collect();
+        Some(Grant { funder: Funder { name: name.to_string(), doi }, awards })
+    }).collect()
+}
+
+/**
+Authors of a Crossref work with their affiliations, in author order.
+Authors without any affiliation are included with an empty list.
+*/
+pub fn affiliations_from_work(work: &JsonValue) -> Vec<AuthorAffiliation> {
+    work.get("author").and_then(|a| a.as_array()).unwrap_or(&[]).iter().map(|a| {
+        let given = a.get("given").and_then(|v| v.as_str()).unwrap_or("");
+        let family = a.get("family").and_then(|v| v.as_str())
+            .or_else(|| a.get("name").and_then(|v| v.as_str()))
+            .unwrap_or("");
+        let author = if given.is_empty() { family.to_string() } else { format!("{}, {}", family, given) };
+        let affiliations = a.get("affiliation").and_then(|v| v.as_array()).unwrap_or(&[])
+            .iter()
+            .filter_map(|f| f.get("name").and_then(|n| n.as_str()))
+            .map(Affiliation::parse)
+            .collect();
+        AuthorAffiliation { author, affiliations }
+    }).collect()
+}
+
+/**
+Where a Crossref work appears within its container: `pages`, `volume` and
+`number` (Crossref's `issue`), as BibTeX field values. Page ranges use the
+BibTeX `--` dash.
+*/
+pub fn locator_from_work(work: &JsonValue) -> Vec<(&'static str, String)> {
+    let text = |name: &str| work.get(name).and_then(|v| v.as_str()).map(str::trim).filter(|v| !v.is_empty());
+    let mut out = Vec::new();
+    if let Some(pages) = text("page") {
+        let pages = match pages.split_once('-') {
+            Some((first, last)) => format!("{}--{}", first.trim(), last.trim_start_matches('-').trim()),
+            None => String::from(pages),
+        };
+        out.push(("pages", pages));
+    }
+    if let Some(volume) = text("volume") {
+        out.push(("volume", String::from(volume)));
+    }
+    if let Some(issue) = text("issue") {
+        out.push(("number", String::from(issue)));
+    }
+    out
+}
+
+/**
+The entry type for a Crossref work `type`.
+*/
+pub fn entry_type(work_type: &str) -> BibType {
+    BibType::parse(match work_type {
+        "journal-article" => "article",
+        "proceedings-article" => "inproceedings",
+        "book-chapter" | "book-section" | "book-part" => "incollection",
+        "book" | "monograph" | "edited-book" | "reference-book" | "book-set" => "book",
+        "reference-entry" => "inreference",
+        "proceedings" => "proceedings",
+        "report" | "report-component" => "report",
+        "dissertation" => "thesis",
+        "dataset" => "dataset",
+        "standard" => "standard",
+        "posted-content" => "online",
+        _ => "misc",
+    })
+}
+
+fn first_text<'a>(work: &'a JsonValue, name: &str) -> Option<&'a str> {
+    let value = match work.get(name)? {
+        JsonValue::Array(items) => items.first()?,
+        value => value,
+    };
+    value.as_str().map(str::trim).filter(|s| !s.is_empty())
+}
+
+fn names(work: &JsonValue, role: &str) -> Option<String> {
+    let names: Vec<String> = work.get(role).and_then(|a| a.as_array()).unwrap_or(&[]).iter().filter_map(|a| {
+        let part = |p: &str| a.get(p).and_then(|v| v.as_str()).map(str::trim).unwrap_or("");
+        match (part("family"), part("name")) {
+            ("", "") => None,
+            ("", name) => Some(format!("{{{}}}", name)),
+            (family, _) => Some(bibtex_name(part("given"), "", family, part("suffix"))),
+        }
+    }).collect();
+    (!names.is_empty()).then(|| names.join(" and "))
+}
+
+/** Crossref abstracts are JATS XML; this keeps the text of its paragraphs. */
+fn strip_jats(abstract_: &str) -> String {
+    let mut out = String::new();
+    let mut rest = abstract_;
+    while let Some(open) = rest.find('<') {
+        out.push_str(&rest[..open]);
+        let close = rest[open..].find('>').map(|c| open + c + 1).unwrap_or(rest.len());
+        if rest[open..close].starts_with("</jats:p") {
+            out.push(' ');
+        }
+        rest = &rest[close..];
+    }
+    out.push_str(rest);
+    out.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/**
+An entry for a Crossref work (the `message` object of a `/works/{doi}`
+response), keyed like `software` keys entries.
+*/
+pub fn entry_from_work(work: &JsonValue) -> Result<Entry, LookupError> {
+    let ty = entry_type(first_text(work, "type").unwrap_or(""));
+    let mut entry = Entry::new(ty.clone(), "");
+    if let Some(authors) = names(work, "author") {
+        entry.set("author", &authors);
+    }
+    if let Some(editors) = names(work, "editor") {
+        entry.set("editor", &editors);
+    }
+    let title = first_text(work, "title").ok_or_else(|| LookupError::Invalid(String::from("no title in Crossref work")))?;
+    match first_text(work, "subtitle") {
+        Some(subtitle) => entry.set("title", &format!("{}: {}", title, subtitle)),
+        None => entry.set("title", title),
+    };
+    if let Some(container) = first_text(work, "container-title") {
+        let field = match ty.name() {
+            "article" => "journal",
+            "inproceedings" | "incollection" | "inreference" => "booktitle",
+            _ => "series",
+        };
+        entry.set(field, container);
+    }
+    if let Some(event) = work.get("event").and_then(|e| first_text(e, "name")) {
+        entry.set("eventtitle", event);
+    }
+    for (field, value) in locator_from_work(work) {
+        entry.set(field, &value);
+    }
+    if !entry.has("pages") {
+        if let Some(number) = first_text(work, "article-number") {
+            entry.set("eid", number);
+        }
+    }
+    let date: Vec<String> = work.path(&["issued", "date-parts"])
+        .and_then(|p| p.as_array()?.first()?.as_array())
+        .unwrap_or(&[])
+        .iter()
+        .map_while(|p| p.as_f64())
+        .map(|n| format!("{:02}", n as u32))
+        .collect();
+    if let Some(year) = date.first() {
+        entry.set("date", &date.join("-"));
+        entry.set("year", year);
+    }
+    let publisher = match ty.name() {
+        "report" => "institution",
+        "thesis" => "school",
+        _ => "publisher",
+    };
+    for (field, name) in [(publisher, "publisher"), ("location", "publisher-location"), ("isbn", "ISBN"), ("issn", "ISSN"), ("language", "language")] {
+        if let Some(value) = first_text(work, name) {
+            entry.set(field, value);
+        }
+    }
+    if let Some(doi) = first_text(work, "DOI") {
+        entry.set("doi", &doi.to_lowercase());
+    }
+    if let Some(abstract_) = first_text(work, "abstract") {
+        entry.set("abstract", &strip_jats(abstract_));
+    }
+    let keywords: Vec<&str> = work.get("subject").and_then(|s| s.as_array()).unwrap_or(&[]).iter().filter_map(|s| s.as_str()).collect();
+    if !keywords.is_empty() {
+        entry.set("keywords", &keywords.join(", "));
+    }
+    let grants = grants_from_work(work);
+    if !grants.is_empty() {
+        entry.set_funding(&grants);
+    }
+    let key = citation_key(&entry);
+    entry.set_key(&key);
+    Ok(entry)
+}
+
+/**
+Fetch the `message` object for the work with the given DOI.
+*/
+#[cfg(feature = "net")]
+pub fn fetch_work<C: HttpClient>(client: &C, doi: &str) -> Result<JsonValue, LookupError> {
+    let url = format!("{}/works/{}", API, net::encode_component(doi.trim()));
+    let response = net::expect_success(&url, client.get(&url, &[("Accept", "application/json")])?)?;
+    json::parse(&response.body)?
+        .get("message")
+        .cloned()
+        .ok_or_else(|| LookupError::Invalid(String::from("no `message` in Crossref response")))
+}
+
+/**
+The DOI of the work Crossref finds best matching a free-text reference,
+such as one copied from a reference list, if it finds any.
+*/
+#[cfg(feature = "net")]
+pub fn search_reference<C: HttpClient>(client: &C, reference: &str) -> Result<Option<String>, LookupError> {
+    let url = format!("{}/works?rows=1&select=DOI&query.bibliographic={}", API, net::encode_component(reference.trim()));
+    let response = net::expect_success(&url, client.get(&url, &[("Accept", "application/json")])?)?;
+    let found = json::parse(&response.body)?;
+    let items = found.path(&["message", "items"]).and_then(JsonValue::as_array)
+        .ok_or_else(|| LookupError::Invalid(String::from("no `message.items` in Crossref response")))?;
+    Ok(items.first().and_then(|w| w.get("DOI")).and_then(JsonValue::as_str).map(String::from))
+}
+
+/** The entry for the work with the given DOI. */
+#[cfg(feature = "net")]
+pub fn fetch_entry<C: HttpClient>(client: &C, doi: &str) -> Result<Entry, LookupError> {
+    entry_from_work(&fetch_work(client, doi)?)
+}
+
+/**
+`fetch_entry` on a background thread, for async code:
+`crossref::fetch_entry_async(CurlClient::default(), doi).await`.
+*/
+#[cfg(feature = "net")]
+pub fn fetch_entry_async<C: HttpClient + Send + 'static>(client: C, doi: &str) -> Background<Result<Entry, LookupError>> {
+    let doi = String::from(doi);
+    Background::spawn(move || fetch_entry(&client, &doi))
+}
+
+/**
+Merge the funders Crossref records for the entry's DOI with the grants in
+its `funding` field, as `policy` allows for `funding`. Entries without a
+DOI are left alone.
+*/
+#[cfg(feature = "net")]
+pub fn enrich_funding<C: HttpClient>(client: &C, entry: &mut Entry, policy: &EnrichPolicy, store: &mut MetadataStore, date: &str) -> Result<Merged, LookupError> {
+    let doi = match entry.get("doi") {
+        Some(doi) => doi.to_string(),
+        None => return Ok(Merged::default()),
+    };
+    let work = fetch_work(client, &doi)?;
+    let mut grants = entry.funding();
+    let changed = grants_from_work(&work).into_iter()
+        .filter(|g| merge_grant(&mut grants, g.clone()))
+        .count();
+    if changed == 0 {
+        return Ok(Merged::default());
+    }
+    let mut found = Entry::new(entry.entry_type().clone(), entry.key());
+    found.set_funding(&grants);
+    let merged = policy.apply(entry, &found, store, &Source::lookup("crossref", date));
+    if merged.changed() {
+        // the merged list includes what `grants` held
+        entry.remove("grants");
+    }
+    Ok(merged)
+}
+
+/**
+Fill `pages`, and with it `volume` and `number`, from Crossref for an
+entry that has a DOI, as `policy` says. With the default policy nothing
+is looked up for entries that have pages already.
+*/
+#[cfg(feature = "net")]
+pub fn enrich_pages<C: HttpClient>(client: &C, entry: &mut Entry, policy: &EnrichPolicy, store: &mut MetadataStore, date: &str) -> Result<Merged, LookupError> {
+    let missing = entry.get("pages").map(|v| v.trim().is_empty()).unwrap_or(true);
+    let doi = match entry.get("doi") {
+        Some(doi) if missing || policy.rule("pages") != Rule::Fill => doi.to_string(),
+        _ => return Ok(Merged::default()),
+    };
+    let mut found = Entry::new(entry.entry_type().clone(), entry.key());
+    for (field, value) in locator_from_work(&fetch_work(client, &doi)?) {
+        found.set(field, &value);
+    }
+    Ok(policy.apply(entry, &found, store, &Source::lookup("crossref", date)))
+}
+
+/**
+Record the affiliations Crossref lists for the entry's DOI in `store`.
+Returns false if the entry has no DOI or Crossref knows no affiliations.
+*/
+#[cfg(feature = "net")]
+pub fn enrich_affiliations<C: HttpClient>(client: &C, entry: &Entry, store: &mut AffiliationStore) -> Result<bool, LookupError> {
+    let doi = match entry.get("doi") {
+        Some(doi) => doi.to_string(),
+        None => return Ok(false),
+    };
+    let authors = affiliations_from_work(&fetch_work(client, &doi)?);
+    if authors.iter().all(|a| a.affiliations.is_empty()) {
+        return Ok(false);
+    }
+    store.set(entry.key(), authors);
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::json;
+
+    const WORK: &str = r#"{"status":"ok","message":{
+        "DOI":"10.1000/example","title":["Example"],
+        "funder":[
+            {"DOI":"10.13039/100000001","name":"National Science Foundation","award":["CCF-1234567"," "]},
+            {"name":"Some Foundation"},
+            {"DOI":"10.13039/x"}
+        ]}}"#;
+
+    #[test]
+    fn test_grants_from_work() {
+        let v = json::parse(WORK).unwrap();
+        let grants = grants_from_work(v.get("message").unwrap());
+        assert_eq!(grants.len(), 2);
+        assert_eq!(grants[0].funder.doi.as_deref(), Some("10.13039/100000001"));
+        assert_eq!(grants[0].awards, vec!["CCF-1234567"]);
+        assert!(grants[1].awards.is_empty());
+    }
+
+    #[test]
+    fn test_affiliations_from_work() {
+        let v = json::parse(r#"{"author":[
+            {"given":"David A.","family":"Cox","affiliation":[{"name":"Amherst College, Amherst, MA, USA"}]},
+            {"name":"The Consortium","affiliation":[]}
+        ]}"#).unwrap();
+        let authors = affiliations_from_work(&v);
+        assert_eq!(authors.len(), 2);
+        assert_eq!(authors[0].author, "Cox, David A.");
+        assert_eq!(authors[0].affiliations[0].institution, "Amherst College");
+        assert_eq!(authors[1].author, "The Consortium");
+        assert!(authors[1].affiliations.is_empty());
+    }
+
+    #[test]
+    fn test_locator_from_work() {
+        let v = json::parse(r#"{"page":"97-111","volume":"27","issue":" ","DOI":"10.1093/comjnl/27.2.97"}"#).unwrap();
+        assert_eq!(locator_from_work(&v), vec![("pages", String::from("97--111")), ("volume", String::from("27"))]);
+        let v = json::parse(r#"{"page":"e1001"}"#).unwrap();
+        assert_eq!(locator_from_work(&v), vec![("pages", String::from("e1001"))]);
+    }
+
+    #[test]
+    fn test_entry_from_work() {
+        let work = json::parse(r#"{"type": "journal-article", "DOI": "10.1093/COMJNL/27.2.97",
+            "title": ["Literate Programming"], "subtitle": [],
+            "author": [{"given": "D. E.", "family": "Knuth", "affiliation": []}, {"name": "The WEB Group"}],
+            "container-title": ["The Computer Journal"], "volume": "27", "issue": "2", "page": "97-111",
+            "issued": {"date-parts": [[1984, 2, 1]]}, "publisher": "Oxford University Press (OUP)",
+            "ISSN": ["0010-4620", "1460-2067"],
+            "abstract": "<jats:p>The author and his associates have been experimenting.</jats:p>\n<jats:p>Programs</jats:p>"}"#).unwrap();
+        let e = entry_from_work(&work).unwrap();
+        assert_eq!((e.key(), e.entry_type()), ("knuth1984", &BibType::Article));
+        assert_eq!(e.get("author"), Some("Knuth, D. E. and {The WEB Group}"));
+        assert_eq!(e.get("journal"), Some("The Computer Journal"));
+        assert_eq!((e.get("pages"), e.get("date")), (Some("97--111"), Some("1984-02-01")));
+        assert_eq!((e.get("issn"), e.get("doi")), (Some("0010-4620"), Some("10.1093/comjnl/27.2.97")));
+        assert_eq!(e.get("abstract"), Some("The author and his associates have been experimenting. Programs"));
+        assert!(entry_from_work(&json::parse(r#"{"type": "book"}"#).unwrap()).is_err());
+    }
+
+    #[cfg(feature = "net")]
+    #[test]
+    fn test_enrich_funding() {
+        use crate::bibtex::data::BibType;
+        use crate::net::{NetError, Response};
+
+        struct Canned;
+        impl HttpClient for Canned {
+            fn get(&self, url: &str, _: &[(&str, &str)]) -> Result<Response, NetError> {
+                assert_eq!(url, "https://api.crossref.org/works/10.1000%2Fexample");
+                Ok(Response { status: 200, headers: vec![], body: String::from(WORK) })
+            }
+        }
+
+        let mut e = Entry::new(BibType::Article, "a");
+        e.set("doi", "10.1000/example");
+        e.set("funding", "National Science Foundation: CCF-1234567");
+        let mut store = MetadataStore::new();
+        let merged = enrich_funding(&Canned, &mut e, &EnrichPolicy::default(), &mut store, "2026-10-16").unwrap();
+        assert_eq!(merged.updated, vec!["funding"]);
+        assert_eq!(e.get("funding"),
+            Some("National Science Foundation (10.13039/100000001): CCF-1234567; Some Foundation"));
+        assert_eq!(store.source(&e, "funding"), Source::lookup("crossref", "2026-10-16"));
+
+        // a bare DOI gives the whole entry, with or without blocking
+        let entry = fetch_entry(&Canned, "10.1000/example").unwrap();
+        assert_eq!((entry.key(), entry.funding().len()), ("example", 2));
+        let mut task = std::pin::pin!(fetch_entry_async(Canned, "10.1000/example"));
+        let mut cx = std::task::Context::from_waker(std::task::Waker::noop());
+        let entry = loop {
+            match std::future::Future::poll(task.as_mut(), &mut cx) {
+                std::task::Poll::Ready(entry) => break entry,
+                std::task::Poll::Pending => std::thread::yield_now(),
+            }
+        };
+        assert_eq!(entry.unwrap().get("doi"), Some("10.1000/example"));
+    }
+
+    #[cfg(feature = "net")]
+    #[test]
+    fn test_enrich_pages() {
+        use crate::bibtex::data::BibType;
+        use crate::net::{NetError, Response};
+
+        struct Canned;
+        impl HttpClient for Canned {
+            fn get(&self, _: &str, _: &[(&str, &str)]) -> Result<Response, NetError> {
+                let body = r#"{"status":"ok","message":{"page":"97-111","volume":"27","issue":"2"}}"#;
+                Ok(Response { status: 200, headers: vec![], body: String::from(body) })
+            }
+        }
+
+        let mut e = Entry::new(BibType::Article, "Knuth-LP");
+        e.set("doi", "10.1093/comjnl/27.2.97");
+        e.set("volume", "XXVII");
+        let (policy, mut store) = (EnrichPolicy::default(), MetadataStore::new());
+        let merged = enrich_pages(&Canned, &mut e, &policy, &mut store, "2026-10-16").unwrap();
+        assert_eq!(merged.added, vec!["pages", "number"]);
+        assert_eq!(merged.conflicts[0].field, "volume");
+        assert_eq!(e.get("pages"), Some("97--111"));
+        assert_eq!(e.get("volume"), Some("XXVII"));
+        assert!(!enrich_pages(&Canned, &mut e, &policy, &mut store, "2026-10-16").unwrap().changed());
+    }
+}
@@ -0,0 +1,237 @@
+/*!
+
+DataCite metadata (<https://api.datacite.org>).
+
+Datasets and software are mostly registered with DataCite rather than
+Crossref: Zenodo, figshare, Dryad and OSF DOIs are unknown to Crossref's
+API. DataCite describes what a DOI names by its `resourceTypeGeneral`, which
+decides the entry type:
+
+| `resourceTypeGeneral`               | entry type                |
+|-------------------------------------|---------------------------|
+| `Software`, `ComputationalNotebook` | `@software`               |
+| `Dataset`                           | `@dataset`                |
+| `JournalArticle`                    | `@article`                |
+| `ConferencePaper`                   | `@inproceedings`          |
+| `Book` / `BookChapter`              | `@book` / `@incollection` |
+| `Report`                            | `@techreport`             |
+| `Dissertation`                      | `@thesis`                 |
+| anything else                       | `@misc`                   |
+
+Software and datasets get the fields `software` uses: `version`,
+`publisher` (the repository, such as Zenodo), and a `swhid` or `repository`
+from the related identifiers.
+
+*/
+
+use crate::bibtex::data::{BibType, Entry};
+use crate::bibtex::keys::citation_key;
+use crate::json::JsonValue;
+use crate::lookup::LookupError;
+#[cfg(feature = "net")]
+use crate::{json, lookup::policy::{EnrichPolicy, Merged}, metadata::MetadataStore, net::{self, HttpClient}, provenance::Source};
+
+pub const API: &str = "https://api.datacite.org";
+
+/** DOI prefixes of the large DataCite repositories. */
+pub const PREFIXES: [&str; 6] = [
+    "10.5281",  // Zenodo
+    "10.6084",  // figshare
+    "10.5061",  // Dryad
+    "10.17605", // OSF
+    "10.48550", // arXiv
+    "10.7910",  // Harvard Dataverse
+];
+
+/**
+Whether `doi` belongs to a repository known to register with DataCite, so
+that it is looked up there instead of at Crossref.
+*/
+pub fn is_datacite(doi: &str) -> bool {
+    let prefix = doi.trim().split('/').next().unwrap_or("");
+    PREFIXES.contains(&prefix)
+}
+
+/**
+The entry type for a DataCite `resourceTypeGeneral`.
+*/
+pub fn entry_type(resource_type: &str) -> BibType {
+    BibType::parse(match resource_type {
+        "Software" | "ComputationalNotebook" => "software",
+        "Dataset" => "dataset",
+        "JournalArticle" => "article",
+        "ConferencePaper" => "inproceedings",
+        "Book" => "book",
+        "BookChapter" => "incollection",
+        "Report" => "techreport",
+        "Dissertation" => "thesis",
+        _ => "misc",
+    })
+}
+
+fn text<'a>(value: &'a JsonValue, name: &str) -> Option<&'a str> {
+    value.get(name)?.as_str().map(str::trim).filter(|s| !s.is_empty())
+}
+
+fn array<'a>(value: &'a JsonValue, name: &str) -> &'a [JsonValue] {
+    value.get(name).and_then(|v| v.as_array()).unwrap_or(&[])
+}
+
+/**
+An entry for a DataCite record (the `attributes` of a `/dois/{doi}`
+response), keyed like `software` keys entries.
+*/
+pub fn entry_from_record(record: &JsonValue) -> Result<Entry, LookupError> {
+    let resource_type = record.path(&["types", "resourceTypeGeneral"]).and_then(|t| t.as_str()).unwrap_or("");
+    let mut entry = Entry::new(entry_type(resource_type), "");
+    let authors: Vec<String> = array(record, "creators").iter().filter_map(|c| {
+        let organisation = text(c, "nameType") == Some("Organizational");
+        match (text(c, "familyName"), text(c, "givenName"), text(c, "name")) {
+            (Some(family), Some(given), _) if !organisation => Some(format!("{}, {}", family, given)),
+            (Some(family), None, _) if !organisation => Some(String::from(family)),
+            (_, _, Some(name)) if organisation => Some(format!("{{{}}}", name)),
+            (_, _, name) => name.map(String::from),
+        }
+    }).collect();
+    if !authors.is_empty() {
+        entry.set("author", &authors.join(" and "));
+    }
+    // the main title has no `titleType`; subtitles and translations do
+    let title = array(record, "titles").iter()
+        .find(|t| t.get("titleType").is_none())
+        .or_else(|| array(record, "titles").first())
+        .and_then(|t| text(t, "title"))
+        .ok_or_else(|| LookupError::Invalid(String::from("no title in DataCite record")))?;
+    entry.set("title", title);
+    let publisher = record.get("publisher").and_then(|p| p.as_str().map(str::trim).or_else(|| text(p, "name")));
+    if let Some(publisher) = publisher.filter(|p| !p.is_empty()) {
+        entry.set("publisher", publisher);
+    }
+    if let Some(version) = text(record, "version") {
+        entry.set("version", version);
+    }
+    let year = record.get("publicationYear").map(|y| y.as_str().map(String::from).unwrap_or_else(|| y.to_string()));
+    if let Some(year) = year.filter(|y| !y.trim().is_empty()) {
+        entry.set("year", year.trim());
+    }
+    if let Some(doi) = text(record, "doi") {
+        entry.set("doi", &doi.to_lowercase());
+    }
+    for related in array(record, "relatedIdentifiers") {
+        let (Some(kind), Some(id)) = (text(related, "relatedIdentifierType"), text(related, "relatedIdentifier")) else { continue };
+        let relation = text(related, "relationType").unwrap_or("");
+        if id.starts_with("swh:") && !entry.has("swhid") {
+            entry.set("swhid", id);
+        } else if kind == "URL" && relation == "IsSupplementTo" && !entry.has("repository") {
+            // Zenodo links a software release to the repository it archives
+            entry.set("repository", id.split("/tree/").next().unwrap_or(id));
+        }
+    }
+    if let Some(description) = array(record, "descriptions").iter()
+        .find(|d| text(d, "descriptionType") == Some("Abstract"))
+        .and_then(|d| text(d, "description")) {
+        entry.set("abstract", description);
+    }
+    let keywords: Vec<&str> = array(record, "subjects").iter().filter_map(|s| text(s, "subject")).collect();
+    if !keywords.is_empty() {
+        entry.set("keywords", &keywords.join(", "));
+    }
+    let key = citation_key(&entry);
+    entry.set_key(&key);
+    Ok(entry)
+}
+
+/**
+Fetch the DataCite record (`data.attributes`) of `doi`.
+*/
+#[cfg(feature = "net")]
+pub fn fetch_record<C: HttpClient>(client: &C, doi: &str) -> Result<JsonValue, LookupError> {
+    let url = format!("{}/dois/{}", API, net::encode_component(doi.trim()));
+    let response = net::expect_success(&url, client.get(&url, &[("Accept", "application/vnd.api+json")])?)?;
+    json::parse(&response.body)?
+        .path(&["data", "attributes"])
+        .cloned()
+        .ok_or_else(|| LookupError::Invalid(String::from("no `data.attributes` in DataCite response")))
+}
+
+/**
+Merge the DataCite record of an entry with a DOI into it, as `policy`
+says. A `@misc` entry for software or a dataset becomes `@software` or
+`@dataset`.
+*/
+#[cfg(feature = "net")]
+pub fn enrich<C: HttpClient>(client: &C, entry: &mut Entry, policy: &EnrichPolicy, store: &mut MetadataStore, date: &str) -> Result<Merged, LookupError> {
+    let Some(doi) = entry.get("doi").map(String::from) else { return Ok(Merged::default()) };
+    let found = entry_from_record(&fetch_record(client, &doi)?)?;
+    if *entry.entry_type() == BibType::Misc && matches!(found.entry_type().name(), "software" | "dataset") {
+        entry.set_entry_type(found.entry_type().clone());
+    }
+    Ok(policy.apply(entry, &found, store, &Source::lookup("datacite", date)))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::json;
+
+    const ZENODO: &str = r#"{"data":{"id":"10.5281/zenodo.4004276","type":"dois","attributes":{
+        "doi":"10.5281/ZENODO.4004276",
+        "creators":[
+            {"name":"Virtanen, Pauli","nameType":"Personal","givenName":"Pauli","familyName":"Virtanen"},
+            {"name":"SciPy Contributors","nameType":"Organizational"}
+        ],
+        "titles":[{"title":"scipy/scipy: SciPy 1.5.2"},{"title":"Release notes","titleType":"Other"}],
+        "publisher":"Zenodo",
+        "publicationYear":2020,
+        "types":{"resourceTypeGeneral":"Software","bibtex":"misc"},
+        "version":"v1.5.2",
+        "relatedIdentifiers":[
+            {"relationType":"IsSupplementTo","relatedIdentifier":"https://github.com/scipy/scipy/tree/v1.5.2","relatedIdentifierType":"URL"},
+            {"relationType":"IsVersionOf","relatedIdentifier":"10.5281/zenodo.595738","relatedIdentifierType":"DOI"}
+        ],
+        "subjects":[{"subject":"python"}]
+    }}}"#;
+
+    #[test]
+    fn test_entry_from_record() {
+        let v = json::parse(ZENODO).unwrap();
+        let e = entry_from_record(v.path(&["data", "attributes"]).unwrap()).unwrap();
+        assert_eq!(e.entry_type().name(), "software");
+        assert_eq!(e.key(), "virtanen2020");
+        assert_eq!(e.get("author"), Some("Virtanen, Pauli and {SciPy Contributors}"));
+        assert_eq!(e.get("title"), Some("scipy/scipy: SciPy 1.5.2"));
+        assert_eq!(e.get("year"), Some("2020"));
+        assert_eq!(e.get("doi"), Some("10.5281/zenodo.4004276"));
+        assert_eq!(e.get("repository"), Some("https://github.com/scipy/scipy"));
+        assert_eq!(e.get("keywords"), Some("python"));
+        assert!(is_datacite("10.5281/zenodo.4004276"));
+        assert!(!is_datacite("10.1093/comjnl/27.2.97"));
+        assert_eq!(entry_type("Dataset").name(), "dataset");
+        assert_eq!(entry_type("Image"), BibType::Misc);
+        assert!(matches!(entry_from_record(&json::parse("{}").unwrap()), Err(LookupError::Invalid(_))));
+    }
+
+    #[cfg(feature = "net")]
+    #[test]
+    fn test_enrich() {
+        use crate::net::{NetError, Response};
+
+        struct Canned;
+        impl HttpClient for Canned {
+            fn get(&self, url: &str, _: &[(&str, &str)]) -> Result<Response, NetError> {
+                assert_eq!(url, "https://api.datacite.org/dois/10.5281%2Fzenodo.4004276");
+                Ok(Response { status: 200, headers: vec![], body: String::from(ZENODO) })
+            }
+        }
+
+        let mut e = Entry::new(BibType::Misc, "scipy");
+        e.set("title", "SciPy");
+        e.set("doi", "10.5281/zenodo.4004276");
+        let merged = enrich(&Canned, &mut e, &EnrichPolicy::default(), &mut MetadataStore::new(), "2026-10-16").unwrap();
+        assert_eq!(merged.added, vec!["author", "publisher", "version", "year", "repository", "keywords"]);
+        assert_eq!(merged.conflicts.iter().map(|c| c.field.as_str()).collect::<Vec<_>>(), vec!["title"]);
+        assert_eq!(e.entry_type().name(), "software");
+        assert_eq!(e.get("title"), Some("SciPy"));
+    }
+}
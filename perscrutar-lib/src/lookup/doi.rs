@@ -0,0 +1,47 @@
+/*!
+
+Any DOI, through the resolver at <https://doi.org>.
+
+Crossref, DataCite and the smaller registration agencies all answer a DOI
+asked for as CSL-JSON (content negotiation), so one request gives an
+entry for a DOI from any of them, read with `csl::from_csl_json`.
+
+*/
+
+use crate::bibtex::data::Entry;
+use crate::csl::{from_csl_json, CslError};
+use crate::lookup::LookupError;
+#[cfg(feature = "net")]
+use crate::net::{self, HttpClient};
+
+pub const RESOLVER: &str = "https://doi.org";
+
+/** The CSL-JSON media type asked for. */
+pub const CSL_JSON: &str = "application/vnd.citationstyles.csl+json";
+
+/**
+The entry in a resolver's CSL-JSON answer for `doi`, with the `doi` field
+set even if the answer leaves it out.
+*/
+pub fn entry_from_csl(body: &str, doi: &str) -> Result<Entry, LookupError> {
+    let mut entries = from_csl_json(body).map_err(|e| match e {
+        CslError::Json(e) => LookupError::Json(e),
+        e => LookupError::Invalid(e.to_string()),
+    })?;
+    if entries.is_empty() {
+        return Err(LookupError::Invalid(String::from("no item in CSL-JSON")));
+    }
+    let mut entry = entries.remove(0);
+    if !entry.has("doi") {
+        entry.set("doi", doi.trim());
+    }
+    Ok(entry)
+}
+
+/** The entry for `doi`, from whichever agency registered it. */
+#[cfg(feature = "net")]
+pub fn fetch_entry<C: HttpClient>(client: &C, doi: &str) -> Result<Entry, LookupError> {
+    let url = format!("{}/{}", RESOLVER, doi.trim());
+    let response = net::expect_success(&url, client.get(&url, &[("Accept", CSL_JSON)])?)?;
+    entry_from_csl(&response.body, doi)
+}
@@ -0,0 +1,208 @@
+/*!
+
+Books by ISBN, from Open Library (<https://openlibrary.org/dev/docs/api/books>)
+or, for books it does not know, Google Books.
+
+Open Library answers a query for `ISBN:...` with an object keyed by it:
+
+```json
+{"ISBN:9780201896831": {
+  "title": "The Art of Computer Programming", "subtitle": "Fundamental Algorithms",
+  "authors": [{"name": "Donald E. Knuth"}], "publishers": [{"name": "Addison-Wesley"}],
+  "publish_places": [{"name": "Reading, Mass"}], "publish_date": "1997", "number_of_pages": 650
+}}
+```
+
+and Google Books with the `volumeInfo` of its matches. Either becomes a
+`@book` entry with the ISBN as given, without hyphens. `normalize` checks
+an ISBN's check digit before anything is fetched, so a mistyped ISBN fails
+instead of finding the wrong book.
+
+*/
+
+use crate::bibtex::data::{BibType, Entry};
+use crate::bibtex::keys::citation_key;
+use crate::bibtex::names::{bibtex_name, parse_names};
+use crate::identifiers::normalize_isbn;
+use crate::json::{self, JsonValue};
+use crate::lookup::LookupError;
+#[cfg(feature = "net")]
+use crate::net::{self, HttpClient};
+
+pub const OPEN_LIBRARY: &str = "https://openlibrary.org/api/books";
+pub const GOOGLE_BOOKS: &str = "https://www.googleapis.com/books/v1/volumes";
+
+/**
+`isbn` as digits (and a final `X`), without hyphens, spaces or an `ISBN`
+prefix, if its check digit is right.
+*/
+pub fn normalize(isbn: &str) -> Option<String> {
+    let isbn = isbn.trim();
+    let isbn = isbn.strip_prefix("ISBN").or_else(|| isbn.strip_prefix("isbn")).unwrap_or(isbn);
+    let isbn = isbn.strip_prefix("-13").or_else(|| isbn.strip_prefix("-10")).unwrap_or(isbn);
+    normalize_isbn(isbn.trim_start_matches(':').trim())
+}
+
+/** The ISBN-13 for a normalized ISBN-10, or the ISBN-13 itself. */
+pub fn to_isbn13(isbn: &str) -> String {
+    if isbn.len() != 10 {
+        return String::from(isbn);
+    }
+    let body = format!("978{}", &isbn[..9]);
+    let sum: u32 = body.chars().filter_map(|c| c.to_digit(10)).enumerate()
+        .map(|(i, d)| if i % 2 == 0 { d } else { 3 * d })
+        .sum();
+    format!("{}{}", body, (10 - sum % 10) % 10)
+}
+
+fn book(isbn: &str, title: Option<&str>, authors: Vec<&str>) -> Result<Entry, LookupError> {
+    let title = title.filter(|t| !t.trim().is_empty())
+        .ok_or_else(|| LookupError::Invalid(format!("no title for ISBN {}", isbn)))?;
+    let mut entry = Entry::new(BibType::Book, "");
+    let authors: Vec<String> = authors.into_iter()
+        .filter_map(|name| parse_names(name).into_iter().next())
+        .map(|n| bibtex_name(&n.first, &n.von, &n.last, &n.jr))
+        .collect();
+    if !authors.is_empty() {
+        entry.set("author", &authors.join(" and "));
+    }
+    entry.set("title", title.trim());
+    Ok(entry)
+}
+
+/** The first four-digit year in a free-form date such as `May 1997`. */
+fn year(date: &str) -> Option<&str> {
+    date.match_indices(|c: char| c.is_ascii_digit())
+        .map(|(i, _)| &date[i..])
+        .find(|rest| rest.len() >= 4 && rest[..4].chars().all(|c| c.is_ascii_digit())
+            && !rest[4..].starts_with(|c: char| c.is_ascii_digit()))
+        .map(|rest| &rest[..4])
+}
+
+fn finish(mut entry: Entry, isbn: &str) -> Entry {
+    entry.set("isbn", isbn);
+    entry.set_key(&citation_key(&entry));
+    entry
+}
+
+/** The `@book` for `isbn` (normalized) in an Open Library `jscmd=data` answer. */
+pub fn entry_from_open_library(body: &str, isbn: &str) -> Result<Entry, LookupError> {
+    let answer = json::parse(body)?;
+    let Some(data) = answer.get(&format!("ISBN:{}", isbn)) else {
+        return Err(LookupError::Invalid(format!("Open Library has no book with ISBN {}", isbn)));
+    };
+    let names = |key: &str| data.get(key).and_then(JsonValue::as_array).unwrap_or_default().iter()
+        .filter_map(|v| v.get("name").and_then(JsonValue::as_str))
+        .collect::<Vec<&str>>();
+    let mut entry = book(isbn, data.get("title").and_then(JsonValue::as_str), names("authors"))?;
+    if let Some(subtitle) = data.get("subtitle").and_then(JsonValue::as_str) {
+        entry.set("subtitle", subtitle);
+    }
+    if let Some(publisher) = names("publishers").first() {
+        entry.set("publisher", publisher);
+    }
+    if let Some(place) = names("publish_places").first() {
+        entry.set("location", place);
+    }
+    if let Some(year) = data.get("publish_date").and_then(JsonValue::as_str).and_then(year) {
+        entry.set("year", year);
+    }
+    if let Some(pages) = data.get("number_of_pages").and_then(JsonValue::as_f64) {
+        entry.set("pagetotal", &pages.to_string());
+    }
+    Ok(finish(entry, isbn))
+}
+
+/** The `@book` for `isbn` (normalized) in a Google Books volume search. */
+pub fn entry_from_google_books(body: &str, isbn: &str) -> Result<Entry, LookupError> {
+    let answer = json::parse(body)?;
+    let Some(info) = answer.get("items").and_then(JsonValue::as_array).and_then(|items| items.first())
+        .and_then(|item| item.get("volumeInfo")) else {
+        return Err(LookupError::Invalid(format!("Google Books has no book with ISBN {}", isbn)));
+    };
+    let authors = info.get("authors").and_then(JsonValue::as_array).unwrap_or_default().iter()
+        .filter_map(JsonValue::as_str)
+        .collect();
+    let mut entry = book(isbn, info.get("title").and_then(JsonValue::as_str), authors)?;
+    if let Some(subtitle) = info.get("subtitle").and_then(JsonValue::as_str) {
+        entry.set("subtitle", subtitle);
+    }
+    if let Some(publisher) = info.get("publisher").and_then(JsonValue::as_str) {
+        entry.set("publisher", publisher);
+    }
+    if let Some(year) = info.get("publishedDate").and_then(JsonValue::as_str).and_then(year) {
+        entry.set("year", year);
+    }
+    if let Some(pages) = info.get("pageCount").and_then(JsonValue::as_f64) {
+        entry.set("pagetotal", &pages.to_string());
+    }
+    Ok(finish(entry, isbn))
+}
+
+/**
+The `@book` for `isbn`, from Open Library or else Google Books. An ISBN
+with a wrong check digit is `Invalid` without a request being made.
+*/
+#[cfg(feature = "net")]
+pub fn fetch_entry<C: HttpClient>(client: &C, isbn: &str) -> Result<Entry, LookupError> {
+    let isbn = normalize(isbn).ok_or_else(|| LookupError::Invalid(format!("`{}` is not a valid ISBN", isbn.trim())))?;
+    let url = format!("{}?bibkeys=ISBN:{}&format=json&jscmd=data", OPEN_LIBRARY, isbn);
+    let body = net::expect_success(&url, client.get(&url, &[("Accept", "application/json")])?)?.body;
+    match entry_from_open_library(&body, &isbn) {
+        Err(LookupError::Invalid(_)) => {
+            let url = format!("{}?q=isbn:{}", GOOGLE_BOOKS, isbn);
+            let body = net::expect_success(&url, client.get(&url, &[("Accept", "application/json")])?)?.body;
+            entry_from_google_books(&body, &isbn)
+        }
+        found => found,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_entry_from_open_library() {
+        assert_eq!(normalize("ISBN-13: 978-0-201-89683-1").as_deref(), Some("9780201896831"));
+        assert_eq!(normalize("0-201-89683-4").as_deref(), Some("0201896834"));
+        assert_eq!(normalize("978-0-201-89683-2"), None);
+        assert_eq!(to_isbn13("0201896834"), "9780201896831");
+
+        let body = r#"{"ISBN:9780201896831": {"title": "The Art of Computer Programming", "subtitle": "Fundamental Algorithms",
+            "authors": [{"name": "Donald E. Knuth"}], "publishers": [{"name": "Addison-Wesley"}],
+            "publish_places": [{"name": "Reading, Mass"}], "publish_date": "July 1997", "number_of_pages": 650}}"#;
+        let e = entry_from_open_library(body, "9780201896831").unwrap();
+        assert_eq!((e.key(), e.entry_type()), ("knuth1997", &BibType::Book));
+        assert_eq!(e.get("author"), Some("Knuth, Donald E."));
+        assert_eq!((e.get("title"), e.get("subtitle")), (Some("The Art of Computer Programming"), Some("Fundamental Algorithms")));
+        assert_eq!((e.get("publisher"), e.get("location")), (Some("Addison-Wesley"), Some("Reading, Mass")));
+        assert_eq!((e.get("pagetotal"), e.get("isbn")), (Some("650"), Some("9780201896831")));
+        assert!(matches!(entry_from_open_library("{}", "9780201896831"), Err(LookupError::Invalid(_))));
+    }
+
+    #[cfg(feature = "net")]
+    #[test]
+    fn test_fetch_entry() {
+        use crate::net::{NetError, Response};
+
+        struct Canned;
+        impl HttpClient for Canned {
+            fn get(&self, url: &str, _: &[(&str, &str)]) -> Result<Response, NetError> {
+                let body = match url {
+                    "https://openlibrary.org/api/books?bibkeys=ISBN:0201896834&format=json&jscmd=data" => "{}",
+                    "https://www.googleapis.com/books/v1/volumes?q=isbn:0201896834" =>
+                        r#"{"items": [{"volumeInfo": {"title": "The Art of Computer Programming", "authors": ["Donald Ervin Knuth"],
+                            "publisher": "Addison-Wesley Professional", "publishedDate": "1997-07-07", "pageCount": 672}}]}"#,
+                    url => panic!("unexpected request for {}", url),
+                };
+                Ok(Response { status: 200, headers: vec![], body: String::from(body) })
+            }
+        }
+
+        let e = fetch_entry(&Canned, "0-201-89683-4").unwrap();
+        assert_eq!((e.key(), e.get("year"), e.get("isbn")), ("knuth1997", Some("1997"), Some("0201896834")));
+        assert!(matches!(fetch_entry(&Canned, "0-201-89683-5"), Err(LookupError::Invalid(_))));
+    }
+}
@@ -0,0 +1,59 @@
+/*!
+
+Metadata lookup in external bibliographic databases.
+
+The conversion from a service's response to perscrutar's data model is
+always available, so responses obtained by other means can be used too;
+fetching them requires the `net` feature. Lookups that fill in fields of
+an existing entry merge them as an `EnrichPolicy` says (see `policy`).
+
+*/
+
+pub mod arxiv;
+pub mod crossref;
+pub mod datacite;
+pub mod doi;
+pub mod isbn;
+pub mod policy;
+pub mod rfc;
+mod xml;
+
+use std::fmt;
+use crate::json::JsonError;
+#[cfg(feature = "net")]
+use crate::net::NetError;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LookupError {
+    #[cfg(feature = "net")]
+    Net(NetError),
+    Json(JsonError),
+    /** The service answered, but without the expected data. */
+    Invalid(String),
+}
+
+impl fmt::Display for LookupError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            #[cfg(feature = "net")]
+            LookupError::Net(e) => write!(f, "{}", e),
+            LookupError::Json(e) => write!(f, "{}", e),
+            LookupError::Invalid(msg) => write!(f, "unexpected response: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for LookupError {}
+
+#[cfg(feature = "net")]
+impl From<NetError> for LookupError {
+    fn from(e: NetError) -> Self {
+        LookupError::Net(e)
+    }
+}
+
+impl From<JsonError> for LookupError {
+    fn from(e: JsonError) -> Self {
+        LookupError::Json(e)
+    }
+}
@@ -0,0 +1,160 @@
+/*!
+
+How looked-up values are merged into an entry.
+
+Every lookup that fills in fields (`crossref::enrich_pages`,
+`crossref::enrich_funding`, `datacite::enrich`) hands what it found to an
+`EnrichPolicy`, which decides field by field, with a `Rule`:
+
+- `Rule::Fill` only sets fields the entry does not have;
+- `Rule::Update` also replaces values, except those edited by hand;
+- `Rule::Refresh` always takes the looked-up value, for data that goes
+  stale, such as citation counts.
+
+Whether a value was edited by hand comes from its provenance in the
+`MetadataStore` (see `provenance`), where the policy also records the
+source of every value it sets. Values a rule keeps although the lookup
+disagrees are reported as `Conflict`s.
+
+By default every field is filled only, except `funding`, whose grants are
+merged with those already listed.
+
+*/
+
+use crate::bibtex::data::Entry;
+use crate::metadata::MetadataStore;
+use crate::provenance::{Conflict, Source};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rule {
+    Fill,
+    Update,
+    Refresh,
+}
+
+impl Rule {
+    pub fn from_name(name: &str) -> Option<Rule> {
+        match name.to_lowercase().as_str() {
+            "fill" => Some(Rule::Fill),
+            "update" => Some(Rule::Update),
+            "refresh" => Some(Rule::Refresh),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnrichPolicy {
+    default: Rule,
+    fields: Vec<(String, Rule)>,
+}
+
+impl Default for EnrichPolicy {
+    fn default() -> Self {
+        EnrichPolicy::new(Rule::Fill).with("funding", Rule::Update)
+    }
+}
+
+/** What `EnrichPolicy::apply` did. */
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Merged {
+    pub added: Vec<String>,
+    pub updated: Vec<String>,
+    /** Looked-up values that were not taken. */
+    pub conflicts: Vec<Conflict>,
+}
+
+impl Merged {
+    pub fn changed(&self) -> bool {
+        !self.added.is_empty() || !self.updated.is_empty()
+    }
+}
+
+impl EnrichPolicy {
+    /** `default` for every field. */
+    pub fn new(default: Rule) -> EnrichPolicy {
+        EnrichPolicy { default, fields: Vec::new() }
+    }
+
+    /** Use `rule` for `field`. */
+    pub fn with(mut self, field: &str, rule: Rule) -> EnrichPolicy {
+        self.fields.retain(|(f, _)| !f.eq_ignore_ascii_case(field));
+        self.fields.push((field.to_lowercase(), rule));
+        self
+    }
+
+    pub fn rule(&self, field: &str) -> Rule {
+        self.fields.iter().find(|(f, _)| f.eq_ignore_ascii_case(field)).map(|(_, r)| *r).unwrap_or(self.default)
+    }
+
+    /**
+    Merge the fields of `found` into `entry` by the rules, recording
+    `source` as the provenance of every value set.
+    */
+    pub fn apply(&self, entry: &mut Entry, found: &Entry, store: &mut MetadataStore, source: &Source) -> Merged {
+        let mut merged = Merged::default();
+        for (field, value) in found.fields() {
+            let current = entry.get(field).map(str::trim).filter(|v| !v.is_empty()).map(String::from);
+            match current {
+                None => merged.added.push(String::from(field)),
+                Some(current) if current == value.trim() => continue,
+                Some(current) => {
+                    let origin = store.source(entry, field);
+                    let replace = match self.rule(field) {
+                        Rule::Fill => false,
+                        Rule::Update => !origin.is_manual(),
+                        Rule::Refresh => true,
+                    };
+                    if !replace {
+                        merged.conflicts.push(Conflict {
+                            field: String::from(field),
+                            current,
+                            proposed: String::from(value),
+                            source: origin,
+                        });
+                        continue;
+                    }
+                    merged.updated.push(String::from(field));
+                }
+            }
+            entry.set(field, value);
+            store.set_source(entry, field, source);
+        }
+        merged
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::bibtex::data::BibType;
+
+    #[test]
+    fn test_apply() {
+        let mut e = Entry::new(BibType::Article, "knuth84");
+        e.set("title", "Literate Programming");
+        e.set("citations", "10");
+        let mut found = Entry::new(BibType::Article, "knuth84");
+        found.set("title", "Literate programming");
+        found.set("pages", "97--111");
+        found.set("citations", "4211");
+        let mut store = MetadataStore::new();
+        let crossref = Source::lookup("crossref", "2026-10-16");
+
+        let policy = EnrichPolicy::default().with("citations", Rule::Refresh);
+        let merged = policy.apply(&mut e, &found, &mut store, &crossref);
+        assert_eq!((merged.added, merged.updated), (vec![String::from("pages")], vec![String::from("citations")]));
+        assert_eq!(merged.conflicts.iter().map(|c| c.field.as_str()).collect::<Vec<_>>(), vec!["title"]);
+        assert_eq!(store.source(&e, "citations"), crossref);
+
+        // a value corrected by hand survives an update, a looked-up one does not
+        e.set("pages", "97--112");
+        found.set("citations", "4300");
+        let merged = EnrichPolicy::new(Rule::Update).apply(&mut e, &found, &mut store, &crossref);
+        assert_eq!(merged.updated, vec!["title", "citations"]);
+        assert_eq!(merged.conflicts[0].source, Source::User);
+        assert_eq!(e.get("pages"), Some("97--112"));
+        assert_eq!(Rule::from_name("Refresh"), Some(Rule::Refresh));
+    }
+}
@@ -0,0 +1,136 @@
+/*!
+
+RFC metadata from the IETF's BibXML service (<https://bib.ietf.org>).
+
+Each RFC has a `reference` element there with its title, authors, date and
+series information:
+
+```text
+<reference anchor="RFC8446" target="https://www.rfc-editor.org/info/rfc8446">
+  <front>
+    <title>The Transport Layer Security (TLS) Protocol Version 1.3</title>
+    <author fullname="E. Rescorla" initials="E." surname="Rescorla"/>
+    <date month="August" year="2018"/>
+  </front>
+  <seriesInfo name="RFC" value="8446"/>
+  <seriesInfo name="DOI" value="10.17487/RFC8446"/>
+</reference>
+```
+
+`entry_from_bibxml` turns it into a `@misc` entry in the RFC series, in the
+form `bibtex::standards` describes.
+
+*/
+
+use crate::bibtex::data::{BibType, Entry};
+use crate::bibtex::months::parse_month;
+use crate::bibtex::standards::{Body, Standard};
+use crate::lookup::LookupError;
+use crate::lookup::xml::{attribute, tags, text, unescape};
+#[cfg(feature = "net")]
+use crate::net::{self, HttpClient};
+
+pub const BIBXML: &str = "https://bib.ietf.org/public/rfc/bibxml";
+
+/**
+Address of the BibXML reference for RFC `number`.
+*/
+pub fn reference_url(number: u32) -> String {
+    format!("{}/reference.RFC.{:04}.xml", BIBXML, number)
+}
+
+/**
+A `@misc` entry, keyed `rfcNNNN`, for a BibXML `reference`.
+*/
+pub fn entry_from_bibxml(xml: &str) -> Result<Entry, LookupError> {
+    let invalid = |what: &str| LookupError::Invalid(format!("no {} in BibXML reference", what));
+    let number = tags(xml, "seriesInfo")
+        .find(|t| attribute(t, "name") == Some("RFC"))
+        .and_then(|t| attribute(t, "value"))
+        .and_then(|n| Standard::parse(&format!("RFC {}", n)))
+        .filter(|s| s.body == Body::Rfc)
+        .ok_or_else(|| invalid("RFC number"))?;
+    let mut entry = Entry::new(BibType::Misc, &format!("rfc{}", number.number));
+    let authors: Vec<String> = tags(xml, "author").filter_map(|t| {
+        let surname = attribute(t, "surname").map(unescape).filter(|s| !s.is_empty());
+        match (surname, attribute(t, "initials").filter(|i| !i.is_empty())) {
+            (Some(surname), Some(initials)) => Some(format!("{}, {}", surname, unescape(initials))),
+            (Some(surname), None) => Some(surname),
+            // organisations as authors are kept whole
+            (None, _) => attribute(t, "fullname").filter(|n| !n.is_empty()).map(|n| format!("{{{}}}", unescape(n))),
+        }
+    }).collect();
+    if !authors.is_empty() {
+        entry.set("author", &authors.join(" and "));
+    }
+    entry.set("title", &text(xml, "title").ok_or_else(|| invalid("title"))?);
+    entry.set("series", "Request for Comments");
+    entry.set("number", &number.number);
+    entry.set("howpublished", &number.canonical_number());
+    entry.set("publisher", "RFC Editor");
+    if let Some(date) = tags(xml, "date").next() {
+        if let Some(month) = attribute(date, "month").and_then(|m| parse_month(m, &[])) {
+            entry.set("month", &month.to_string());
+        }
+        if let Some(year) = attribute(date, "year") {
+            entry.set("year", year);
+        }
+    }
+    let doi = tags(xml, "seriesInfo")
+        .find(|t| attribute(t, "name") == Some("DOI"))
+        .and_then(|t| attribute(t, "value"))
+        .map(String::from)
+        .or_else(|| number.doi());
+    if let Some(doi) = doi {
+        entry.set("doi", &doi);
+    }
+    if let Some(url) = number.url() {
+        entry.set("url", &url);
+    }
+    Ok(entry)
+}
+
+/**
+Fetch RFC `number` from BibXML as an entry.
+*/
+#[cfg(feature = "net")]
+pub fn fetch_rfc<C: HttpClient>(client: &C, number: u32) -> Result<Entry, LookupError> {
+    let url = reference_url(number);
+    let response = net::expect_success(&url, client.get(&url, &[("Accept", "application/xml")])?)?;
+    entry_from_bibxml(&response.body)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    const TLS: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<reference anchor="RFC8446" target="https://www.rfc-editor.org/info/rfc8446">
+  <front>
+    <title>The Transport Layer Security (TLS) Protocol
+      Version 1.3</title>
+    <author fullname="E. Rescorla" initials="E." surname="Rescorla"/>
+    <author fullname="Internet Engineering Task Force"/>
+    <date month="August" year="2018"/>
+    <abstract><t>This document specifies &lt;TLS&gt;.</t></abstract>
+  </front>
+  <seriesInfo name="RFC" value="8446"/>
+  <seriesInfo name="DOI" value="10.17487/RFC8446"/>
+</reference>"#;
+
+    #[test]
+    fn test_entry_from_bibxml() {
+        let e = entry_from_bibxml(TLS).unwrap();
+        assert_eq!(e.key(), "rfc8446");
+        assert_eq!(e.get("author"), Some("Rescorla, E. and {Internet Engineering Task Force}"));
+        assert_eq!(e.get("title"), Some("The Transport Layer Security (TLS) Protocol Version 1.3"));
+        assert_eq!(e.get("howpublished"), Some("RFC 8446"));
+        assert_eq!(e.get("month"), Some("8"));
+        assert_eq!(e.get("doi"), Some("10.17487/RFC8446"));
+        assert_eq!(e.get("url"), Some("https://www.rfc-editor.org/info/rfc8446"));
+        assert_eq!(Standard::from_entry(&e).map(|s| s.canonical_number()).as_deref(), Some("RFC 8446"));
+        assert_eq!(reference_url(791), "https://bib.ietf.org/public/rfc/bibxml/reference.RFC.0791.xml");
+        assert!(matches!(entry_from_bibxml("<reference/>"), Err(LookupError::Invalid(_))));
+    }
+}
@@ -0,0 +1,49 @@
+/*!
+
+Just enough XML for the services that answer in it: opening tags with
+their attributes, and the text of simple elements.
+
+*/
+
+/**
+Opening tags of the elements called `name`, attributes included.
+*/
+pub fn tags<'a>(xml: &'a str, name: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+    xml.match_indices('<')
+        .map(move |(i, _)| &xml[i + 1..])
+        .filter(move |t| t.starts_with(name) && t[name.len()..].starts_with(|c: char| c.is_whitespace() || c == '>' || c == '/'))
+        .filter_map(|t| t.find('>').map(|end| &t[..end]))
+}
+
+pub fn attribute<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    let pattern = format!("{}=\"", name);
+    let start = tag.match_indices(&pattern)
+        .find(|(i, _)| tag[..*i].ends_with(char::is_whitespace))?.0 + pattern.len();
+    let end = tag[start..].find('"')?;
+    Some(&tag[start..start + end])
+}
+
+pub fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<").replace("&gt;", ">").replace("&quot;", "\"").replace("&apos;", "'").replace("&amp;", "&")
+}
+
+/**
+Text of each element called `name`, unescaped and with whitespace
+collapsed. The elements are expected to hold text only.
+*/
+pub fn texts<'a>(xml: &'a str, name: &'a str) -> impl Iterator<Item = String> + 'a {
+    let close = format!("</{}>", name);
+    xml.match_indices('<')
+        .map(move |(i, _)| &xml[i + 1..])
+        .filter(move |t| t.starts_with(name) && t[name.len()..].starts_with(|c: char| c.is_whitespace() || c == '>'))
+        .filter_map(move |t| {
+            let body = &t[t.find('>')? + 1..];
+            let text = &body[..body.find(close.as_str())?];
+            Some(unescape(&text.split_whitespace().collect::<Vec<_>>().join(" ")))
+        })
+}
+
+/** Text of the first element called `name`. */
+pub fn text(xml: &str, name: &str) -> Option<String> {
+    texts(xml, name).next()
+}
@@ -0,0 +1,99 @@
+/*!
+
+How alike two titles are.
+
+Titles of the same work differ in small ways from one source to the
+next: `Proc. Int. Conf. on Software Engineering` and `Proceedings of the
+International Conference on Software Engineering`, `A Survey of Parsing`
+and `Parsing: a survey`, or a typo. Comparing them takes three steps:
+
+- `simplify` drops TeX markup, accents, case and punctuation;
+- `tokens` also expands the abbreviations in `ABBREVIATIONS` and drops
+  the words in `STOPWORDS`;
+- `token_sort_ratio` sorts the tokens of both titles and compares them
+  by edit distance (`ratio`), so word order does not count either.
+
+Scores go from 0 (nothing alike) to 1 (the same). `dedupe` uses these for
+`Strategy::FuzzyTitle`; other tools that pair entries by title should use
+them too, so that the same two titles are equally alike everywhere.
+
+*/
+
+use crate::bibtex::keys::levenshtein;
+use crate::bibtex::names::purify;
+
+/** Words that say little about what a title is about. */
+pub const STOPWORDS: [&str; 18] = [
+    "a", "an", "and", "as", "at", "by", "for", "from", "in", "into", "of", "on", "or", "the", "to", "via",
+    "with", "without",
+];
+
+/** Abbreviations common in titles and venues, each with the word it is short for. */
+pub const ABBREVIATIONS: [(&str, &str); 22] = [
+    ("acad", "academy"), ("annu", "annual"), ("assoc", "association"), ("comput", "computing"),
+    ("conf", "conference"), ("dept", "department"), ("eng", "engineering"), ("int", "international"),
+    ("intl", "international"), ("j", "journal"), ("lett", "letters"), ("natl", "national"),
+    ("proc", "proceedings"), ("rev", "review"), ("sci", "science"), ("soc", "society"),
+    ("symp", "symposium"), ("syst", "systems"), ("tech", "technical"), ("trans", "transactions"),
+    ("univ", "university"), ("vs", "versus"),
+];
+
+/** `text` without markup, accents or punctuation, in lower case, one space between words. */
+pub fn simplify(text: &str) -> String {
+    purify(text).to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+/** The words of `text`, simplified, with abbreviations written out and without stopwords. */
+pub fn tokens(text: &str) -> Vec<String> {
+    simplify(text).split(' ')
+        .filter(|w| !w.is_empty())
+        .map(|w| ABBREVIATIONS.iter().find(|(short, _)| *short == w).map(|(_, long)| *long).unwrap_or(w))
+        .filter(|w| !STOPWORDS.contains(w))
+        .map(String::from)
+        .collect()
+}
+
+/** The tokens of `text` in alphabetical order, as `token_sort_ratio` compares them. */
+pub fn token_sort(text: &str) -> String {
+    let mut tokens = tokens(text);
+    tokens.sort();
+    tokens.join(" ")
+}
+
+/** One less the edit distance between `a` and `b` over the length of the longer. */
+pub fn ratio(a: &str, b: &str) -> f64 {
+    let longest = a.chars().count().max(b.chars().count());
+    if longest == 0 {
+        return 1.0;
+    }
+    1.0 - levenshtein(a, b) as f64 / longest as f64
+}
+
+/** The `ratio` of the `token_sort`s of `a` and `b`. */
+pub fn token_sort_ratio(a: &str, b: &str) -> f64 {
+    ratio(&token_sort(a), &token_sort(b))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_token_sort_ratio() {
+        assert_eq!(simplify("{T}he \\'{E}cole: a {\\TeX} story."), "the ecole a tex story");
+        assert_eq!(tokens("Proc. of the Int. Conf. on Software Eng."),
+            vec!["proceedings", "international", "conference", "software", "engineering"]);
+        assert_eq!(token_sort_ratio("Proc. Int. Conf. on Software Engineering",
+            "Proceedings of the International Conference on Software Engineering"), 1.0);
+        assert_eq!(token_sort_ratio("A Survey of Parsing", "Parsing: a survey"), 1.0);
+        assert_eq!(ratio("kitten", "sitting"), 1.0 - 3.0 / 7.0);
+        assert_eq!(ratio("", ""), 1.0);
+        assert!(token_sort_ratio("Literate Programming", "Literate Programing") > 0.9);
+        assert!(token_sort_ratio("Literate Programming", "Structured Programming") < 0.8);
+    }
+}
@@ -0,0 +1,308 @@
+/*!
+
+Metadata kept beside a bibliography rather than in it.
+
+Citation counts, open-access links, reading status and the like are useful
+to keep per entry but do not belong in the `.bib` file that goes into a
+paper. A `MetadataStore` holds them in a JSON sidecar next to the
+bibliography (`library.bib` has `library.meta.json`):
+
+```json
+{
+  "version": 1,
+  "entries": {
+    "knuth84": {"fingerprint": "doi:10.1093/comjnl/27.2.97", "citations": 4211, "read": true}
+  }
+}
+```
+
+Records are found by citation key. Each also carries the entry's
+`fingerprint`, which does not change when the key does, so `sync` can follow
+entries that were renamed and drop the records of entries that are gone.
+
+The sources of field values are kept here too (see `provenance`), as is
+a log of changes to the bibliography (see `audit`).
+
+*/
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+use crate::audit::Event;
+use crate::bibtex::data::Entry;
+use crate::bibtex::names::{parse_names, purify};
+use crate::bibtex::values::Doi;
+use crate::json::{parse, JsonError, JsonValue};
+
+const VERSION: f64 = 1.0;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MetadataError {
+    Io(String),
+    Json(JsonError),
+    Invalid(String),
+}
+
+impl fmt::Display for MetadataError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MetadataError::Io(msg) => f.write_str(msg),
+            MetadataError::Json(e) => write!(f, "{}", e),
+            MetadataError::Invalid(msg) => write!(f, "invalid metadata: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for MetadataError {}
+
+impl From<JsonError> for MetadataError {
+    fn from(e: JsonError) -> Self {
+        MetadataError::Json(e)
+    }
+}
+
+fn words(s: &str) -> String {
+    purify(s).to_lowercase().split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+/**
+What identifies the work an entry describes, independently of its key:
+its DOI if it has one, otherwise title, year and first author.
+*/
+pub fn fingerprint(entry: &Entry) -> String {
+    if let Ok(Some(Doi(doi))) = entry.get_parsed::<Doi>("doi") {
+        return format!("doi:{}", doi);
+    }
+    let author = entry.get("author").or_else(|| entry.get("editor"))
+        .and_then(|a| parse_names(a).into_iter().next())
+        .map(|n| words(&n.von_last()))
+        .unwrap_or_default();
+    format!("work:{}|{}|{}", words(entry.get("title").unwrap_or("")), words(entry.get("year").unwrap_or("")), author)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Record {
+    pub key: String,
+    pub fingerprint: String,
+    pub data: Vec<(String, JsonValue)>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SyncReport {
+    /** Old and new key of records that followed a renamed entry. */
+    pub renamed: Vec<(String, String)>,
+    /** Keys of records dropped because their entry is gone. */
+    pub dropped: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MetadataStore {
+    records: Vec<Record>,
+    events: Vec<Event>,
+}
+
+impl MetadataStore {
+    pub fn new() -> MetadataStore {
+        MetadataStore::default()
+    }
+
+    /**
+    The sidecar of a bibliography: its path with the extension replaced
+    by `meta.json`.
+    */
+    pub fn sidecar_path<P: AsRef<Path>>(bibliography: P) -> PathBuf {
+        bibliography.as_ref().with_extension("meta.json")
+    }
+
+    pub fn parse(input: &str) -> Result<MetadataStore, MetadataError> {
+        let json = parse(input)?;
+        match json.get("version").and_then(JsonValue::as_f64) {
+            Some(v) if v == VERSION => {}
+            other => return Err(MetadataError::Invalid(format!("unsupported version {:?}", other))),
+        }
+        let Some(JsonValue::Object(entries)) = json.get("entries") else {
+            return Err(MetadataError::Invalid(String::from("no `entries` object")));
+        };
+        let mut store = MetadataStore::new();
+        for (key, value) in entries {
+            let JsonValue::Object(members) = value else {
+                return Err(MetadataError::Invalid(format!("`{}` is not an object", key)));
+            };
+            let fingerprint = value.get("fingerprint").and_then(JsonValue::as_str).unwrap_or("").to_string();
+            let data = members.iter().filter(|(k, _)| k != "fingerprint").cloned().collect();
+            store.records.push(Record { key: key.clone(), fingerprint, data });
+        }
+        if let Some(log) = json.get("log") {
+            let events = log.as_array().ok_or_else(|| MetadataError::Invalid(String::from("`log` is not an array")))?;
+            store.events = events.iter().map(Event::from_json).collect::<Result<_, _>>()?;
+        }
+        Ok(store)
+    }
+
+    /**
+    Read a sidecar; a missing file is an empty store.
+    */
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<MetadataStore, MetadataError> {
+        match std::fs::read_to_string(path.as_ref()) {
+            Ok(s) => MetadataStore::parse(&s),
+            // an age file, see `encryption`
+            Err(e) if e.kind() == std::io::ErrorKind::InvalidData
+                && std::fs::read(path.as_ref()).is_ok_and(|d| d.starts_with(b"age-encryption.org/v1\n")) =>
+                Err(MetadataError::Invalid(format!("{} is encrypted", path.as_ref().display()))),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(MetadataStore::new()),
+            Err(e) => Err(MetadataError::Io(format!("cannot read {}: {}", path.as_ref().display(), e))),
+        }
+    }
+
+    pub fn to_json(&self) -> JsonValue {
+        let mut json = JsonValue::object(vec![
+            ("version", JsonValue::Num(VERSION)),
+            ("entries", JsonValue::Object(self.records.iter().map(|r| {
+                let mut members = vec![(String::from("fingerprint"), JsonValue::str(&r.fingerprint))];
+                members.extend(r.data.iter().cloned());
+                (r.key.clone(), JsonValue::Object(members))
+            }).collect())),
+        ]);
+        if let (JsonValue::Object(members), false) = (&mut json, self.events.is_empty()) {
+            members.push((String::from("log"), JsonValue::Array(self.events.iter().map(Event::to_json).collect())));
+        }
+        json
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), MetadataError> {
+        std::fs::write(path.as_ref(), format!("{}\n", self.to_json().to_pretty_string()))
+            .map_err(|e| MetadataError::Io(format!("cannot write {}: {}", path.as_ref().display(), e)))
+    }
+
+    pub fn record(&self, key: &str) -> Option<&Record> {
+        self.records.iter().find(|r| r.key == key)
+    }
+
+    pub fn records(&self) -> &[Record] {
+        &self.records
+    }
+
+    pub fn get(&self, key: &str, name: &str) -> Option<&JsonValue> {
+        self.record(key)?.data.iter().find(|(k, _)| k == name).map(|(_, v)| v)
+    }
+
+    /**
+    Set `name` for `entry`, creating its record if needed and refreshing
+    its fingerprint.
+    */
+    pub fn set(&mut self, entry: &Entry, name: &str, value: JsonValue) {
+        let fingerprint = fingerprint(entry);
+        let i = match self.records.iter().position(|r| r.key == entry.key()) {
+            Some(i) => i,
+            None => {
+                self.records.push(Record { key: String::from(entry.key()), fingerprint: String::new(), data: vec![] });
+                self.records.len() - 1
+            }
+        };
+        let record = &mut self.records[i];
+        record.fingerprint = fingerprint;
+        match record.data.iter_mut().find(|(k, _)| k == name) {
+            Some((_, v)) => *v = value,
+            None => record.data.push((String::from(name), value)),
+        }
+    }
+
+    /** Append `event` to the log of changes (see `audit`). */
+    pub fn log(&mut self, event: Event) {
+        self.events.push(event);
+    }
+
+    /** The log of changes, oldest first. */
+    pub fn events(&self) -> &[Event] {
+        &self.events
+    }
+
+    pub fn remove(&mut self, key: &str, name: &str) -> Option<JsonValue> {
+        let record = self.records.iter_mut().find(|r| r.key == key)?;
+        let i = record.data.iter().position(|(k, _)| k == name)?;
+        Some(record.data.remove(i).1)
+    }
+
+    /**
+    Bring the records in line with `entries`: records whose key is gone
+    follow the entry with the same fingerprint, if exactly one entry without
+    a record has it, and are dropped otherwise. Fingerprints of the
+    remaining records are refreshed.
+    */
+    pub fn sync(&mut self, entries: &[Entry]) -> SyncReport {
+        let mut report = SyncReport::default();
+        let keys: Vec<String> = self.records.iter().map(|r| r.key.clone()).collect();
+        let unclaimed: Vec<&Entry> = entries.iter().filter(|e| !keys.iter().any(|k| k == e.key())).collect();
+        let mut taken: Vec<String> = Vec::new();
+        for record in self.records.iter_mut() {
+            if let Some(e) = entries.iter().find(|e| e.key() == record.key) {
+                record.fingerprint = fingerprint(e);
+                continue;
+            }
+            let candidates: Vec<&&Entry> = unclaimed.iter()
+                .filter(|e| fingerprint(e) == record.fingerprint && !taken.iter().any(|k| k == e.key()))
+                .collect();
+            match candidates.as_slice() {
+                [e] => {
+                    report.renamed.push((record.key.clone(), String::from(e.key())));
+                    record.key = String::from(e.key());
+                    taken.push(record.key.clone());
+                }
+                _ => report.dropped.push(record.key.clone()),
+            }
+        }
+        self.records.retain(|r| !report.dropped.contains(&r.key));
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::bibtex::data::BibType;
+
+    fn entry(key: &str, title: &str, doi: Option<&str>) -> Entry {
+        let mut e = Entry::new(BibType::Article, key);
+        e.set("author", "Knuth, Donald E.");
+        e.set("title", title);
+        e.set("year", "1984");
+        if let Some(doi) = doi {
+            e.set("doi", doi);
+        }
+        e
+    }
+
+    #[test]
+    fn test_fingerprint() {
+        assert_eq!(fingerprint(&entry("a", "x", Some("https://doi.org/10.1093/COMJNL/27.2.97"))), "doi:10.1093/comjnl/27.2.97");
+        assert_eq!(fingerprint(&entry("a", "{Literate} Programming", None)), "work:literate programming|1984|knuth");
+    }
+
+    #[test]
+    fn test_store() {
+        let lp = entry("knuth", "Literate Programming", Some("10.1093/comjnl/27.2.97"));
+        let tex = entry("texbook", "The TeXbook", None);
+        let mut store = MetadataStore::new();
+        store.set(&lp, "citations", JsonValue::Num(4211.0));
+        store.set(&lp, "read", JsonValue::Boolean(true));
+        store.set(&tex, "read", JsonValue::Boolean(false));
+        assert_eq!(store.remove("texbook", "read"), Some(JsonValue::Boolean(false)));
+
+        let dir = std::env::temp_dir().join(format!("perscrutar-metadata-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = MetadataStore::sidecar_path(dir.join("library.bib"));
+        assert!(path.ends_with("library.meta.json"));
+        store.save(&path).unwrap();
+        let mut store = MetadataStore::load(&path).unwrap();
+        assert_eq!(store.get("knuth", "citations"), Some(&JsonValue::Num(4211.0)));
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let mut renamed = lp.clone();
+        renamed.set_key("knuth84");
+        let report = store.sync(&[renamed, entry("other", "Other", None)]);
+        assert_eq!(report.renamed, vec![(String::from("knuth"), String::from("knuth84"))]);
+        assert_eq!(report.dropped, vec!["texbook"]);
+        assert_eq!(store.get("knuth84", "read"), Some(&JsonValue::Boolean(true)));
+        assert!(MetadataStore::parse(r#"{"version": 2, "entries": {}}"#).is_err());
+    }
+}
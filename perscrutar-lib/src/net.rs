@@ -0,0 +1,415 @@
+/*!
+
+HTTP access for the lookup and enrichment modules (`net` feature).
+
+Everything that talks to the network goes through the `HttpClient` trait so
+that callers can plug in the client their application already uses, and
+tests can substitute canned responses. `CurlClient` is a dependency-free
+default that delegates to the `curl` executable.
+
+`HttpCache` keeps downloaded files on disk and revalidates them with
+conditional requests, so repeated fetches of an unchanged resource cost a
+`304 Not Modified`.
+
+Requests block. For async callers, `Background` runs a lookup on its own
+thread and awaits it as a `Future`, under any executor.
+
+*/
+
+use std::fmt;
+use std::fs;
+use std::future::Future;
+use std::io::Write;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Response {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl Response {
+    /**
+    Value of the first header called `name`, compared case-insensitively.
+    */
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NetError {
+    /** The request could not be performed at all. */
+    Transport(String),
+    /** The server answered with an unexpected status code. */
+    Status { url: String, status: u16 },
+}
+
+impl fmt::Display for NetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NetError::Transport(msg) => write!(f, "request failed: {}", msg),
+            NetError::Status { url, status } => write!(f, "{} returned HTTP {}", url, status),
+        }
+    }
+}
+
+impl std::error::Error for NetError {}
+
+pub trait HttpClient {
+    fn get(&self, url: &str, headers: &[(&str, &str)]) -> Result<Response, NetError>;
+
+    /**
+    Send `body` with a POST request. Clients that only read from services
+    need not implement it.
+    */
+    fn post(&self, url: &str, headers: &[(&str, &str)], body: &str) -> Result<Response, NetError> {
+        let _ = (headers, body);
+        Err(NetError::Transport(format!("POST to {} is not supported by this client", url)))
+    }
+
+    /**
+    Send a HEAD request. Unlike `get`, this must not follow redirects, so
+    that callers can see them. Clients that do not need it may leave it
+    unsupported.
+    */
+    fn head(&self, url: &str, headers: &[(&str, &str)]) -> Result<Response, NetError> {
+        let _ = headers;
+        Err(NetError::Transport(format!("HEAD to {} is not supported by this client", url)))
+    }
+}
+
+/**
+Check the status of a response, turning non-2xx answers into errors.
+*/
+pub fn expect_success(url: &str, response: Response) -> Result<Response, NetError> {
+    if response.is_success() {
+        Ok(response)
+    } else {
+        Err(NetError::Status { url: String::from(url), status: response.status })
+    }
+}
+
+/**
+Percent-encode a string for use as a single URL path segment or query value.
+*/
+pub fn encode_component(s: &str) -> String {
+    let mut out = String::new();
+    for b in s.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => out.push(b as char),
+            _ => out.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    out
+}
+
+#[derive(Debug, Clone)]
+pub struct CurlClient {
+    pub user_agent: String,
+    pub timeout_secs: u32,
+}
+
+impl Default for CurlClient {
+    fn default() -> Self {
+        CurlClient {
+            user_agent: format!("perscrutar/{}", env!("CARGO_PKG_VERSION")),
+            timeout_secs: 30,
+        }
+    }
+}
+
+/**
+Split the output of `curl -D -` into status, headers and body. With
+redirects curl prints one header block per hop; the last one wins.
+*/
+fn parse_curl_output(raw: &str) -> Result<Response, NetError> {
+    let mut rest = raw;
+    loop {
+        let end = rest.find("\r\n\r\n").ok_or_else(|| NetError::Transport(String::from("malformed response")))?;
+        let head = &rest[..end];
+        let body = &rest[end + 4..];
+        let mut lines = head.split("\r\n");
+        let status_line = lines.next().unwrap_or("");
+        let status: u16 = status_line.split_whitespace().nth(1)
+            .and_then(|s| s.parse().ok())
+            .ok_or_else(|| NetError::Transport(format!("bad status line `{}`", status_line)))?;
+        let interim = (100..200).contains(&status) || (300..400).contains(&status);
+        if interim && body.starts_with("HTTP/") {
+            rest = body;
+            continue;
+        }
+        let headers = lines
+            .filter_map(|l| l.split_once(':'))
+            .map(|(k, v)| (k.trim().to_string(), v.trim().to_string()))
+            .collect();
+        return Ok(Response { status, headers, body: String::from(body) });
+    }
+}
+
+impl CurlClient {
+    fn command(&self, url: &str, headers: &[(&str, &str)], body: Option<&str>, head: bool) -> Result<Response, NetError> {
+        let mut cmd = Command::new("curl");
+        cmd.arg("-sS")
+            .arg("--max-time").arg(self.timeout_secs.to_string())
+            .arg("-A").arg(&self.user_agent);
+        if head {
+            // headers only, and no redirects followed
+            cmd.arg("--head");
+        } else {
+            cmd.arg("-L").arg("-D").arg("-");
+        }
+        for (k, v) in headers {
+            cmd.arg("-H").arg(format!("{}: {}", k, v));
+        }
+        if body.is_some() {
+            // the body goes through stdin so that it never shows up in `ps`
+            cmd.arg("--data-binary").arg("@-").stdin(Stdio::piped());
+        }
+        cmd.arg("--").arg(url).stdout(Stdio::piped()).stderr(Stdio::piped());
+        let spawn_error = |e: std::io::Error| NetError::Transport(format!("cannot run curl: {}", e));
+        let mut child = cmd.spawn().map_err(spawn_error)?;
+        if let (Some(body), Some(mut stdin)) = (body, child.stdin.take()) {
+            stdin.write_all(body.as_bytes()).map_err(spawn_error)?;
+        }
+        let output = child.wait_with_output().map_err(spawn_error)?;
+        if !output.status.success() {
+            return Err(NetError::Transport(String::from_utf8_lossy(&output.stderr).trim().to_string()));
+        }
+        parse_curl_output(&String::from_utf8_lossy(&output.stdout))
+    }
+}
+
+impl HttpClient for CurlClient {
+    fn get(&self, url: &str, headers: &[(&str, &str)]) -> Result<Response, NetError> {
+        self.command(url, headers, None, false)
+    }
+
+    fn post(&self, url: &str, headers: &[(&str, &str)], body: &str) -> Result<Response, NetError> {
+        self.command(url, headers, Some(body), false)
+    }
+
+    fn head(&self, url: &str, headers: &[(&str, &str)]) -> Result<Response, NetError> {
+        self.command(url, headers, None, true)
+    }
+}
+
+/**
+The result of a function run on a background thread, as a `Future`. The
+thread wakes the task awaiting it when it is done, so no particular async
+runtime is needed.
+*/
+pub struct Background<T> {
+    shared: Arc<Mutex<(Option<T>, Option<Waker>)>>,
+}
+
+impl<T: Send + 'static> Background<T> {
+    pub fn spawn<F: FnOnce() -> T + Send + 'static>(f: F) -> Background<T> {
+        let shared = Arc::new(Mutex::new((None, None::<Waker>)));
+        let done = Arc::clone(&shared);
+        std::thread::spawn(move || {
+            let value = f();
+            let mut state = done.lock().unwrap_or_else(|e| e.into_inner());
+            state.0 = Some(value);
+            if let Some(waker) = state.1.take() {
+                waker.wake();
+            }
+        });
+        Background { shared }
+    }
+}
+
+impl<T> Future for Background<T> {
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        let mut state = self.shared.lock().unwrap_or_else(|e| e.into_inner());
+        match state.0.take() {
+            Some(value) => Poll::Ready(value),
+            None => {
+                state.1 = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/**
+On-disk cache of GET responses, keyed by URL.
+
+Each URL is stored as two files named after a hash of the URL: the body,
+and a `.meta` file with the validators (`etag`, `last-modified`) used to
+make the next request conditional.
+*/
+#[derive(Debug, Clone)]
+pub struct HttpCache {
+    dir: PathBuf,
+}
+
+/**
+64-bit FNV-1a, stable across platforms and Rust versions unlike `DefaultHasher`.
+*/
+fn fnv1a(s: &str) -> u64 {
+    s.bytes().fold(0xcbf29ce484222325, |h, b| (h ^ b as u64).wrapping_mul(0x100000001b3))
+}
+
+impl HttpCache {
+    pub fn new<P: Into<PathBuf>>(dir: P) -> HttpCache {
+        HttpCache { dir: dir.into() }
+    }
+
+    /**
+    `$XDG_CACHE_HOME/perscrutar/http`, falling back to `~/.cache`.
+    */
+    pub fn default_dir() -> Option<PathBuf> {
+        let base = std::env::var_os("XDG_CACHE_HOME").map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|h| PathBuf::from(h).join(".cache")))?;
+        Some(base.join("perscrutar").join("http"))
+    }
+
+    fn paths(&self, url: &str) -> (PathBuf, PathBuf) {
+        let name = format!("{:016x}", fnv1a(url));
+        (self.dir.join(&name), self.dir.join(format!("{}.meta", name)))
+    }
+
+    /**
+    Cached body of `url`, if any.
+    */
+    pub fn cached(&self, url: &str) -> Option<String> {
+        fs::read_to_string(self.paths(url).0).ok()
+    }
+
+    fn validators(&self, url: &str) -> Vec<(String, String)> {
+        let meta = fs::read_to_string(self.paths(url).1).unwrap_or_default();
+        meta.lines()
+            .filter_map(|l| l.split_once(": "))
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    fn store(&self, url: &str, response: &Response) -> std::io::Result<()> {
+        let (body, meta) = self.paths(url);
+        fs::create_dir_all(&self.dir)?;
+        let mut validators = String::new();
+        for name in ["etag", "last-modified"] {
+            if let Some(v) = response.header(name) {
+                validators.push_str(&format!("{}: {}\n", name, v));
+            }
+        }
+        fs::write(body, &response.body)?;
+        fs::write(meta, validators)
+    }
+
+    /**
+    Fetch `url`, revalidating any cached copy. If the server cannot be
+    reached at all the cached copy is returned as is; a cache that cannot be
+    written is not an error.
+    */
+    pub fn get(&self, client: &dyn HttpClient, url: &str) -> Result<String, NetError> {
+        let cached = self.cached(url);
+        let mut headers = Vec::new();
+        if cached.is_some() {
+            for (name, value) in self.validators(url) {
+                match name.as_str() {
+                    "etag" => headers.push(("If-None-Match", value)),
+                    "last-modified" => headers.push(("If-Modified-Since", value)),
+                    _ => {}
+                }
+            }
+        }
+        let headers: Vec<(&str, &str)> = headers.iter().map(|(k, v)| (*k, v.as_str())).collect();
+        let response = match (client.get(url, &headers), cached) {
+            (Ok(r), Some(body)) if r.status == 304 => return Ok(body),
+            (Err(NetError::Transport(_)), Some(body)) => return Ok(body),
+            (r, _) => expect_success(url, r?)?,
+        };
+        let _ = self.store(url, &response);
+        Ok(response.body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_parse_curl_output() {
+        let raw = "HTTP/1.1 301 Moved\r\nLocation: https://x/\r\n\r\nHTTP/2 200\r\ncontent-type: application/json\r\nETag: \"abc\"\r\n\r\n{\"a\":1}";
+        let r = parse_curl_output(raw).unwrap();
+        assert_eq!(r.status, 200);
+        assert_eq!(r.header("etag"), Some("\"abc\""));
+        assert_eq!(r.body, "{\"a\":1}");
+
+        assert_eq!(parse_curl_output("HTTP/1.1 304 Not Modified\r\nETag: \"abc\"\r\n\r\n").unwrap().status, 304);
+        let r = parse_curl_output("HTTP/1.1 404 Not Found\r\n\r\nnope").unwrap();
+        assert_eq!(r.status, 404);
+        assert!(expect_success("u", r).is_err());
+    }
+
+    #[test]
+    fn test_background() {
+        let mut task = std::pin::pin!(Background::spawn(|| 6 * 7));
+        let mut cx = Context::from_waker(Waker::noop());
+        let value = loop {
+            match task.as_mut().poll(&mut cx) {
+                Poll::Ready(value) => break value,
+                Poll::Pending => std::thread::yield_now(),
+            }
+        };
+        assert_eq!(value, 42);
+    }
+
+    #[test]
+    fn test_encode_component() {
+        assert_eq!(encode_component("10.1002/9781118400722"), "10.1002%2F9781118400722");
+        assert_eq!(encode_component("a b"), "a%20b");
+    }
+
+    struct Server {
+        requests: std::cell::RefCell<Vec<Vec<(String, String)>>>,
+        responses: std::cell::RefCell<Vec<Result<Response, NetError>>>,
+    }
+
+    impl HttpClient for Server {
+        fn get(&self, _url: &str, headers: &[(&str, &str)]) -> Result<Response, NetError> {
+            self.requests.borrow_mut().push(headers.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect());
+            self.responses.borrow_mut().remove(0)
+        }
+    }
+
+    #[test]
+    fn test_http_cache() {
+        let dir = std::env::temp_dir().join(format!("perscrutar-http-cache-{}", std::process::id()));
+        let cache = HttpCache::new(&dir);
+        let ok = Response { status: 200, headers: vec![(String::from("ETag"), String::from("\"v1\""))], body: String::from("first") };
+        let not_modified = Response { status: 304, headers: vec![], body: String::new() };
+        let server = Server {
+            requests: Default::default(),
+            responses: std::cell::RefCell::new(vec![
+                Ok(ok), Ok(not_modified), Err(NetError::Transport(String::from("offline"))),
+                Ok(Response { status: 500, headers: vec![], body: String::new() }),
+            ]),
+        };
+        let url = "https://example.org/lib.bib";
+        assert_eq!(cache.get(&server, url).unwrap(), "first");
+        assert_eq!(cache.get(&server, url).unwrap(), "first");
+        assert_eq!(cache.get(&server, url).unwrap(), "first");
+        assert!(cache.get(&server, url).is_err());
+        let requests = server.requests.borrow();
+        assert!(requests[0].is_empty());
+        assert_eq!(requests[1], vec![(String::from("If-None-Match"), String::from("\"v1\""))]);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,595 @@
+/*!
+
+Citations for pandoc documents, as a JSON filter.
+
+`filter` takes a document in pandoc's JSON representation (`pandoc -t
+json`), fills in the text of every citation (`[@knuth84, p. 3]`,
+`@knuth84`, `[-@knuth84]`) from a bibliography and adds a reference list,
+much like `--citeproc` does for simple styles:
+
+- `CiteStyle::AuthorYear` writes `(Knuth 1984, p. 3)` and `Knuth (1984)`,
+  and sorts the references like `bibtex::sorting`. Different people with
+  the same family name are told apart by their initials (`A. Smith 2001`),
+  different works that would still read the same by a year suffix (`Cox
+  2013a`), and works by the same author cited together are clustered
+  (`Cox 2013a, 2013b`);
+- `CiteStyle::Numeric` writes `[1, p. 3]` and `Knuth [1]`, numbering the
+  references in the order they are first cited; runs of numbers collapse
+  into ranges (`[1, 3–6]`).
+
+References are rendered with a `publist::Template`. They go into the
+document's `Div` with the id `refs` if there is one, otherwise to the end,
+under the heading given by `FilterOptions::title` or the document's
+`reference-section-title` metadata.
+
+Citations of unknown keys are rendered as `key?` and reported.
+
+*/
+
+use std::fmt;
+use crate::bibtex::data::Entry;
+use crate::bibtex::names::purify;
+use crate::bibtex::sorting::{name_source, sort_key, NameSource};
+use crate::json::JsonValue;
+use crate::publist::{PubFormat, Template};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CiteStyle {
+    AuthorYear,
+    Numeric,
+}
+
+impl CiteStyle {
+    pub fn from_name(name: &str) -> Option<CiteStyle> {
+        match name.to_lowercase().as_str() {
+            "author-year" | "authoryear" => Some(CiteStyle::AuthorYear),
+            "numeric" => Some(CiteStyle::Numeric),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FilterOptions {
+    pub style: CiteStyle,
+    /** Template for the references. */
+    pub template: Template,
+    /** Heading above the references. */
+    pub title: Option<String>,
+}
+
+impl Default for FilterOptions {
+    fn default() -> Self {
+        FilterOptions {
+            style: CiteStyle::AuthorYear,
+            template: Template::new("{authors}. {title}.[ {venue}][ {volume}][ ({number})][, {pages}][, {year}].[ https://doi.org/{doi}]"),
+            title: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FilterReport {
+    /** Number of citations filled in. */
+    pub citations: usize,
+    /** Cited keys not in the bibliography, in order of first use. */
+    pub missing: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FilterError(pub String);
+
+impl fmt::Display for FilterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "not a pandoc document: {}", self.0)
+    }
+}
+
+impl std::error::Error for FilterError {}
+
+fn node(t: &str, c: Option<JsonValue>) -> JsonValue {
+    let mut members = vec![("t", JsonValue::str(t))];
+    if let Some(c) = c {
+        members.push(("c", c));
+    }
+    JsonValue::object(members)
+}
+
+/**
+Inlines for plain text: words become `Str`, single spaces `Space`.
+*/
+fn text(s: &str) -> Vec<JsonValue> {
+    let mut out = Vec::new();
+    for (i, word) in s.split(' ').enumerate() {
+        if i > 0 {
+            out.push(node("Space", None));
+        }
+        if !word.is_empty() {
+            out.push(node("Str", Some(JsonValue::str(word))));
+        }
+    }
+    out
+}
+
+fn attr(id: &str, classes: &[&str]) -> JsonValue {
+    JsonValue::Array(vec![
+        JsonValue::str(id),
+        JsonValue::Array(classes.iter().map(|c| JsonValue::str(c)).collect()),
+        JsonValue::Array(vec![]),
+    ])
+}
+
+/**
+Plain text of a metadata value (`MetaString` or `MetaInlines`).
+*/
+fn meta_text(value: &JsonValue) -> Option<String> {
+    let content = value.get("c")?;
+    match value.get("t")?.as_str()? {
+        "MetaString" => content.as_str().map(String::from),
+        "MetaInlines" => Some(content.as_array()?.iter().map(|i| match i.get("t").and_then(JsonValue::as_str) {
+            Some("Str") => i.get("c").and_then(JsonValue::as_str).unwrap_or(""),
+            Some("Space") | Some("SoftBreak") => " ",
+            _ => "",
+        }).collect()),
+        _ => None,
+    }
+}
+
+/**
+A metadata field of the document as plain text, e.g. `bibliography`.
+*/
+pub fn meta_string(doc: &JsonValue, name: &str) -> Option<String> {
+    doc.path(&["meta", name]).and_then(meta_text)
+}
+
+fn visit<F: FnMut(&JsonValue)>(value: &JsonValue, f: &mut F) {
+    f(value);
+    match value {
+        JsonValue::Array(items) => items.iter().for_each(|v| visit(v, f)),
+        JsonValue::Object(members) => members.iter().for_each(|(_, v)| visit(v, f)),
+        _ => {}
+    }
+}
+
+fn visit_mut<F: FnMut(&mut JsonValue)>(value: &mut JsonValue, f: &mut F) {
+    f(value);
+    match value {
+        JsonValue::Array(items) => items.iter_mut().for_each(|v| visit_mut(v, f)),
+        JsonValue::Object(members) => members.iter_mut().for_each(|(_, v)| visit_mut(v, f)),
+        _ => {}
+    }
+}
+
+fn is_node(value: &JsonValue, t: &str) -> bool {
+    value.get("t").and_then(JsonValue::as_str) == Some(t)
+}
+
+/** The citations of a `Cite` node. */
+fn citations(cite: &JsonValue) -> &[JsonValue] {
+    cite.get("c").and_then(JsonValue::as_array).and_then(|c| c.first()).and_then(JsonValue::as_array).unwrap_or(&[])
+}
+
+fn citation_key(citation: &JsonValue) -> &str {
+    citation.get("citationId").and_then(JsonValue::as_str).unwrap_or("")
+}
+
+fn inlines(citation: &JsonValue, field: &str) -> Vec<JsonValue> {
+    citation.get(field).and_then(JsonValue::as_array).map(<[JsonValue]>::to_vec).unwrap_or_default()
+}
+
+fn clean(s: &str) -> String {
+    purify(s).split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+/**
+Initials of given names: `Donald Ervin` gives `D. E.`, `Jean-Paul` `J.-P.`.
+*/
+fn initials(first: &str) -> String {
+    first.split_whitespace().map(|word| word.split('-')
+        .filter_map(|part| clean(part).chars().next().map(|c| format!("{}.", c)))
+        .collect::<Vec<String>>().join("-"))
+        .filter(|w| !w.is_empty())
+        .collect::<Vec<String>>().join(" ")
+}
+
+/**
+Author part of a citation: one family name, two joined by `and`, or the
+first followed by `et al.`. With `given`, names are preceded by the
+initials of the given names.
+*/
+fn author_label(entry: &Entry, given: bool) -> String {
+    match name_source(entry) {
+        Some(NameSource::Names(names)) => {
+            let last: Vec<String> = names.iter().filter(|n| !n.is_others()).map(|n| {
+                let name = clean(&n.von_last());
+                match initials(&n.first) {
+                    i if given && !i.is_empty() => format!("{} {}", i, name),
+                    _ => name,
+                }
+            }).collect();
+            match last.as_slice() {
+                [one] if names.len() == 1 => one.clone(),
+                [a, b] => format!("{} and {}", a, b),
+                [first, ..] => format!("{} et al.", first),
+                [] => clean(entry.key()),
+            }
+        }
+        Some(NameSource::Organization(org)) => clean(org),
+        Some(NameSource::Key(key)) => clean(key),
+        None => String::from(entry.key()),
+    }
+}
+
+/**
+The full names behind an author label, to tell apart different people who
+would be cited the same way.
+*/
+fn author_identity(entry: &Entry) -> String {
+    match name_source(entry) {
+        Some(NameSource::Names(names)) => names.iter().map(|n| format!("{}|{}", clean(&n.first), clean(&n.von_last())))
+            .collect::<Vec<String>>().join(";"),
+        _ => author_label(entry, false),
+    }
+}
+
+fn year(entry: &Entry) -> String {
+    entry.get("year").map(purify).map(|y| y.trim().to_string()).filter(|y| !y.is_empty())
+        .unwrap_or_else(|| String::from("n.d."))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Label {
+    author: String,
+    /** Year, with a suffix if needed, for author-year; the number for numeric. */
+    text: String,
+}
+
+/**
+How each cited entry is referred to, in reference list order.
+
+Authors who would be cited the same way but are different people get the
+initials of their given names (`J. Smith 2001`, `A. Smith 2003`); works
+that still read the same get a year suffix (`Cox 2013a`, `Cox 2013b`).
+*/
+struct Labels<'a> {
+    references: Vec<&'a Entry>,
+    labels: Vec<Label>,
+}
+
+impl<'a> Labels<'a> {
+    fn new(mut cited: Vec<&'a Entry>, style: CiteStyle) -> Labels<'a> {
+        if style == CiteStyle::AuthorYear {
+            cited.sort_by_cached_key(|e| sort_key(e));
+        }
+        let plain: Vec<(String, String)> = cited.iter().map(|e| (author_label(e, false), author_identity(e))).collect();
+        let authors: Vec<String> = cited.iter().zip(plain.iter()).map(|(e, (label, identity))| {
+            match plain.iter().any(|(l, id)| l == label && id != identity) {
+                true => author_label(e, true),
+                false => label.clone(),
+            }
+        }).collect();
+        let texts: Vec<String> = match style {
+            CiteStyle::Numeric => (1..=cited.len()).map(|n| n.to_string()).collect(),
+            CiteStyle::AuthorYear => {
+                let read: Vec<(&String, String)> = authors.iter().zip(cited.iter().map(|e| year(e))).collect();
+                read.iter().enumerate().map(|(i, label)| {
+                    let same: Vec<usize> = (0..read.len()).filter(|j| read[*j] == *label).collect();
+                    match same.iter().position(|j| *j == i) {
+                        Some(n) if same.len() > 1 => format!("{}{}", label.1, (b'a' + (n % 26) as u8) as char),
+                        _ => label.1.clone(),
+                    }
+                }).collect()
+            }
+        };
+        let labels = authors.into_iter().zip(texts).map(|(author, text)| Label { author, text }).collect();
+        Labels { references: cited, labels }
+    }
+
+    fn find(&self, key: &str) -> Option<&Label> {
+        self.references.iter().position(|e| e.is_known_as(key)).map(|i| &self.labels[i])
+    }
+}
+
+fn mode(citation: &JsonValue) -> &str {
+    citation.path(&["citationMode", "t"]).and_then(JsonValue::as_str).unwrap_or("NormalCitation")
+}
+
+/**
+Numbers with runs of three or more collapsed into ranges: `1, 3–6`.
+*/
+fn collapse(mut numbers: Vec<usize>) -> Vec<String> {
+    numbers.sort_unstable();
+    numbers.dedup();
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < numbers.len() {
+        let mut j = i;
+        while j + 1 < numbers.len() && numbers[j + 1] == numbers[j] + 1 {
+            j += 1;
+        }
+        match j - i {
+            0 => out.push(numbers[i].to_string()),
+            1 => out.extend([numbers[i].to_string(), numbers[j].to_string()]),
+            _ => out.push(format!("{}–{}", numbers[i], numbers[j])),
+        }
+        i = j + 1;
+    }
+    out
+}
+
+/**
+The text of one `Cite` node. A first citation in author-in-text form puts
+the author in front of the brackets; the rest are listed inside them.
+
+Consecutive author-year citations of the same author are clustered,
+`(Cox 2013a, 2013b; Lamport 1994)`. Numeric citations without prefixes or
+suffixes are sorted and collapsed, `[1, 3–6]`.
+*/
+fn render_cite(citations: &[JsonValue], labels: &Labels, style: CiteStyle) -> Vec<JsonValue> {
+    let (open, close, separator) = match style {
+        CiteStyle::AuthorYear => ("(", ")", ";"),
+        CiteStyle::Numeric => ("[", "]", ","),
+    };
+    let found: Vec<Option<&Label>> = citations.iter().map(|c| labels.find(citation_key(c))).collect();
+    let mut out = Vec::new();
+    let in_text = citations.first().map(|c| mode(c) == "AuthorInText").unwrap_or(false);
+    if in_text {
+        let author = match found[0] {
+            Some(label) => label.author.clone(),
+            None => format!("{}?", citation_key(&citations[0])),
+        };
+        out.extend(text(&author));
+        out.extend(text(" "));
+    }
+    let mut inside = vec![node("Str", Some(JsonValue::str(open)))];
+    let plain = citations.iter().all(|c| inlines(c, "citationPrefix").is_empty() && inlines(c, "citationSuffix").is_empty());
+    if style == CiteStyle::Numeric && plain && found.iter().all(Option::is_some) {
+        let numbers = found.iter().flatten().filter_map(|l| l.text.parse().ok()).collect();
+        inside.extend(text(&collapse(numbers).join(", ")));
+        inside.push(node("Str", Some(JsonValue::str(close))));
+        out.extend(inside);
+        return out;
+    }
+    for (i, citation) in citations.iter().enumerate() {
+        let prefix = inlines(citation, "citationPrefix");
+        // same author as the citation before, which has nothing after it
+        let clustered = i > 0 && style == CiteStyle::AuthorYear && prefix.is_empty()
+            && inlines(&citations[i - 1], "citationSuffix").is_empty()
+            && matches!((found[i - 1], found[i]), (Some(a), Some(b)) if a.author == b.author);
+        if i > 0 {
+            inside.push(node("Str", Some(JsonValue::str(if clustered { "," } else { separator }))));
+            inside.push(node("Space", None));
+        }
+        if !prefix.is_empty() {
+            inside.extend(prefix);
+            inside.push(node("Space", None));
+        }
+        let core = match (found[i], style) {
+            (None, _) if i == 0 && in_text => None,
+            (None, _) => Some(format!("{}?", citation_key(citation))),
+            (Some(label), CiteStyle::Numeric) => Some(label.text.clone()),
+            (Some(label), _) if clustered || mode(citation) == "SuppressAuthor" || (i == 0 && in_text) => Some(label.text.clone()),
+            (Some(label), _) => Some(format!("{} {}", label.author, label.text)),
+        };
+        if let Some(core) = core {
+            inside.extend(text(&core));
+        }
+        let suffix = inlines(citation, "citationSuffix");
+        let punctuated = suffix.first()
+            .and_then(|s| s.get("c")).and_then(JsonValue::as_str)
+            .map(|s| s.starts_with(|c: char| c.is_ascii_punctuation()))
+            .unwrap_or(false);
+        if !suffix.is_empty() && !punctuated {
+            inside.push(node("Str", Some(JsonValue::str(","))));
+            if !is_node(&suffix[0], "Space") {
+                inside.push(node("Space", None));
+            }
+        }
+        inside.extend(suffix);
+    }
+    inside.push(node("Str", Some(JsonValue::str(close))));
+    if in_text && inside.len() == 2 {
+        // nothing to put in the brackets
+        out.pop();
+        return out;
+    }
+    out.extend(inside);
+    out
+}
+
+/**
+Drop the period a template adds after a value that already ends a sentence,
+as in `Knuth, Donald E..` or `Why?.`; ellipses are kept.
+*/
+fn tidy(s: &str) -> String {
+    let chars: Vec<char> = s.chars().collect();
+    let mut out = String::new();
+    for (i, &c) in chars.iter().enumerate() {
+        let before = |n: usize| i.checked_sub(n).map(|j| chars[j]);
+        let doubled = before(1) == Some('.') && before(2) != Some('.') && chars.get(i + 1) != Some(&'.');
+        if c == '.' && (doubled || matches!(before(1), Some('?') | Some('!'))) {
+            continue;
+        }
+        out.push(c);
+    }
+    out
+}
+
+fn reference_list(labels: &Labels, options: &FilterOptions) -> Vec<JsonValue> {
+    labels.references.iter().zip(labels.labels.iter()).map(|(entry, label)| {
+        let mut rendered = tidy(&options.template.render(entry, PubFormat::Markdown).replace("--", "–"));
+        if options.style == CiteStyle::Numeric {
+            rendered = format!("[{}] {}", label.text, rendered);
+        }
+        let para = node("Para", Some(JsonValue::Array(text(&rendered))));
+        node("Div", Some(JsonValue::Array(vec![
+            attr(&format!("ref-{}", entry.key()), &["csl-entry"]),
+            JsonValue::Array(vec![para]),
+        ])))
+    }).collect()
+}
+
+/**
+Fill in the citations of `doc` from `entries` and add the references.
+*/
+pub fn filter(doc: &mut JsonValue, entries: &[Entry], options: &FilterOptions) -> Result<FilterReport, FilterError> {
+    if doc.get("blocks").and_then(JsonValue::as_array).is_none() {
+        return Err(FilterError(String::from("no `blocks`")));
+    }
+    let mut keys: Vec<String> = Vec::new();
+    visit(doc, &mut |v| if is_node(v, "Cite") {
+        for c in citations(v) {
+            let key = citation_key(c);
+            if !keys.iter().any(|k| k == key) {
+                keys.push(String::from(key));
+            }
+        }
+    });
+    let mut report = FilterReport::default();
+    let mut cited: Vec<&Entry> = Vec::new();
+    for key in keys.iter() {
+        match entries.iter().find(|e| e.is_known_as(key)) {
+            Some(e) if !cited.iter().any(|c| std::ptr::eq(*c, e)) => cited.push(e),
+            Some(_) => {}
+            None => report.missing.push(key.clone()),
+        }
+    }
+    let labels = Labels::new(cited, options.style);
+
+    let blocks = doc.get_mut("blocks").expect("checked above");
+    visit_mut(blocks, &mut |v| if is_node(v, "Cite") {
+        let rendered = render_cite(citations(v), &labels, options.style);
+        if let Some(JsonValue::Array(c)) = v.get_mut("c") {
+            if c.len() == 2 {
+                c[1] = JsonValue::Array(rendered);
+                report.citations += 1;
+            }
+        }
+    });
+
+    let references = reference_list(&labels, options);
+    let mut placed = false;
+    visit_mut(blocks, &mut |v| if !placed && is_node(v, "Div") {
+        let Some(JsonValue::Array(c)) = v.get_mut("c") else { return };
+        let is_refs = c.first().and_then(JsonValue::as_array).and_then(|a| a.first()).and_then(JsonValue::as_str) == Some("refs");
+        if is_refs && c.len() == 2 {
+            c[1] = JsonValue::Array(references.clone());
+            placed = true;
+        }
+    });
+    if !placed && !references.is_empty() {
+        let title = options.title.clone().or_else(|| meta_string(doc, "reference-section-title"));
+        let Some(JsonValue::Array(blocks)) = doc.get_mut("blocks") else { unreachable!() };
+        if let Some(title) = title {
+            blocks.push(node("Header", Some(JsonValue::Array(vec![
+                JsonValue::Num(1.0),
+                attr("bibliography", &["unnumbered"]),
+                JsonValue::Array(text(&title)),
+            ]))));
+        }
+        blocks.push(node("Div", Some(JsonValue::Array(vec![attr("refs", &["references"]), JsonValue::Array(references)]))));
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::bibtex::data::BibType;
+    use crate::json::parse;
+
+    fn cite(citations: &[(&str, &str, &str)]) -> String {
+        let items: Vec<String> = citations.iter().map(|(key, mode, suffix)| format!(
+            r#"{{"citationId":"{}","citationPrefix":[],"citationSuffix":[{}],"citationMode":{{"t":"{}"}},"citationNoteNum":1,"citationHash":0}}"#,
+            key, suffix, mode)).collect();
+        format!(r#"{{"t":"Cite","c":[[{}],[{{"t":"Str","c":"[@x]"}}]]}}"#, items.join(","))
+    }
+
+    fn document(inlines: &[String]) -> JsonValue {
+        parse(&format!(r#"{{"pandoc-api-version":[1,23,1],"meta":{{"reference-section-title":{{"t":"MetaInlines","c":[{{"t":"Str","c":"References"}}]}}}},"blocks":[{{"t":"Para","c":[{}]}}]}}"#,
+            inlines.join(r#",{"t":"Space"},"#))).unwrap()
+    }
+
+    fn plain(inlines: &JsonValue) -> String {
+        inlines.as_array().unwrap().iter().map(|i| match i.get("t").and_then(JsonValue::as_str) {
+            Some("Space") => " ",
+            _ => i.get("c").and_then(JsonValue::as_str).unwrap_or(""),
+        }).collect()
+    }
+
+    fn library() -> Vec<Entry> {
+        let entry = |key: &str, author: &str, year: &str| {
+            let mut e = Entry::new(BibType::Article, key);
+            e.set("author", author);
+            e.set("title", "A title");
+            e.set("journal", "J");
+            e.set("year", year);
+            e
+        };
+        vec![
+            entry("knuth84", "Knuth, Donald E.", "1984"),
+            entry("knuth84b", "Knuth, Donald E.", "1984"),
+            entry("lamport", "Lamport, Leslie and Knuth, Donald", "1994"),
+            entry("many", "A, B and C, D and E, F", "2000"),
+            entry("smith-a", "Smith, Alan", "2001"),
+            entry("smith-j", "Smith, John Paul", "2003"),
+        ]
+    }
+
+    fn rendered(doc: &JsonValue) -> Vec<String> {
+        let para = &doc.get("blocks").unwrap().as_array().unwrap()[0];
+        para.get("c").unwrap().as_array().unwrap().iter()
+            .filter(|i| is_node(i, "Cite"))
+            .map(|i| plain(&i.get("c").unwrap().as_array().unwrap()[1]))
+            .collect()
+    }
+
+    #[test]
+    fn test_author_year() {
+        let page = r#"{"t":"Str","c":","},{"t":"Space"},{"t":"Str","c":"p."},{"t":"Space"},{"t":"Str","c":"3"}"#;
+        let mut doc = document(&[
+            cite(&[("knuth84", "NormalCitation", page), ("lamport", "NormalCitation", "")]),
+            cite(&[("knuth84b", "AuthorInText", "")]),
+            cite(&[("many", "SuppressAuthor", ""), ("nobody", "NormalCitation", "")]),
+            cite(&[("knuth84", "NormalCitation", ""), ("knuth84b", "NormalCitation", ""), ("smith-a", "NormalCitation", ""), ("smith-j", "NormalCitation", "")]),
+        ]);
+        let report = filter(&mut doc, &library(), &FilterOptions::default()).unwrap();
+        assert_eq!(report, FilterReport { citations: 4, missing: vec![String::from("nobody")] });
+        assert_eq!(rendered(&doc), vec![
+            "(Knuth 1984a, p. 3; Lamport and Knuth 1994)",
+            "Knuth (1984b)",
+            "(2000; nobody?)",
+            "(Knuth 1984a, 1984b; A. Smith 2001; J. P. Smith 2003)",
+        ]);
+        let blocks = doc.get("blocks").unwrap().as_array().unwrap();
+        assert_eq!(plain(&blocks[1].get("c").unwrap().as_array().unwrap()[2]), "References");
+        let refs = blocks[2].get("c").unwrap().as_array().unwrap()[1].as_array().unwrap();
+        let ids: Vec<&str> = refs.iter().map(|r| r.path(&["c"]).unwrap().as_array().unwrap()[0].as_array().unwrap()[0].as_str().unwrap()).collect();
+        assert_eq!(ids, vec!["ref-many", "ref-knuth84", "ref-knuth84b", "ref-lamport", "ref-smith-a", "ref-smith-j"]);
+    }
+
+    #[test]
+    fn test_numeric() {
+        let mut doc = document(&[
+            cite(&[("lamport", "AuthorInText", r#"{"t":"Str","c":"p."},{"t":"Space"},{"t":"Str","c":"7"}"#)]),
+            cite(&[("knuth84", "NormalCitation", ""), ("lamport", "NormalCitation", "")]),
+            cite(&[("many", "NormalCitation", ""), ("knuth84b", "NormalCitation", ""), ("smith-j", "NormalCitation", ""), ("knuth84", "NormalCitation", "")]),
+        ]);
+        let refs = parse(r#"{"t":"Div","c":[["refs",[],[]],[]]}"#).unwrap();
+        let Some(JsonValue::Array(blocks)) = doc.get_mut("blocks") else { unreachable!() };
+        blocks.push(refs);
+        let options = FilterOptions { style: CiteStyle::Numeric, ..FilterOptions::default() };
+        filter(&mut doc, &library(), &options).unwrap();
+        assert_eq!(rendered(&doc), vec!["Lamport and Knuth [1, p. 7]", "[1, 2]", "[2–5]"]);
+        let blocks = doc.get("blocks").unwrap().as_array().unwrap();
+        assert_eq!(blocks.len(), 2);
+        let first = &blocks[1].get("c").unwrap().as_array().unwrap()[1].as_array().unwrap()[0];
+        let para = &first.get("c").unwrap().as_array().unwrap()[1].as_array().unwrap()[0];
+        assert!(plain(para.get("c").unwrap()).starts_with("[1] Leslie Lamport, Donald Knuth. A title. J, 1994."));
+        assert!(filter(&mut JsonValue::Null, &[], &options).is_err());
+        assert_eq!(collapse(vec![7, 1, 3, 4, 5, 6, 9, 10]), vec!["1", "3–7", "9", "10"]);
+        assert_eq!(initials("Jean-Paul {\\'E}mile"), "J.-P. E.");
+        assert_eq!(tidy("Knuth, D.. Why?. Wait... done."), "Knuth, D. Why? Wait... done.");
+    }
+}
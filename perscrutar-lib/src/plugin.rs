@@ -0,0 +1,337 @@
+/*!
+
+Lint rules and transforms from WebAssembly plugins (`plugin` feature).
+
+A plugin lets an institution ship its own bibliography policies, such as
+required fields or house rules for titles, without recompiling perscrutar.
+It is a core WebAssembly module rather than a component, so any toolchain
+that targets `wasm32-unknown-unknown` can build one. It exports
+
+- `memory`, and `alloc(len: i32) -> i32`, which returns where the host
+  may write `len` bytes;
+- `transform(ptr: i32, len: i32) -> i64`, `lint(ptr: i32, len: i32) -> i64`
+  or both.
+
+Both are called once per entry with the entry as JSON (see
+`bibtex::serialize`) at `ptr`, and return where their answer is as
+`ptr << 32 | len`. `transform` answers with the entry as it should be, or
+nothing (`len` 0) to leave it alone, and `lint` with its findings:
+
+```json
+[{"severity": "warning", "message": "no DOI"}]
+```
+
+All the entries of a bibliography go through one instance, so `alloc`
+should reuse memory rather than only ever grow.
+
+Plugins are sandboxed by `wasmtime`: they cannot import anything, and so
+cannot read files, the network or the clock; their memory is limited to
+`MAX_MEMORY` bytes, and a call that runs out of `MAX_FUEL`, such as an
+endless loop, fails. `PluginTransform` makes a plugin usable as a
+`Transform`.
+
+*/
+
+use std::fmt;
+use std::path::Path;
+use wasmtime::{Config, Engine, Instance, Memory, Module, Store, StoreLimits, StoreLimitsBuilder, TypedFunc};
+use crate::bibtex::bibliography::Bibliography;
+use crate::bibtex::data::Entry;
+use crate::json::{parse, JsonValue};
+use crate::lint::{Diagnostic, Severity};
+use crate::transform::{Report, Transform};
+
+/** How much memory a plugin may have, in bytes. */
+pub const MAX_MEMORY: usize = 64 << 20;
+
+/** How much fuel one call may use, roughly one unit per instruction. */
+pub const MAX_FUEL: u64 = 100_000_000;
+
+/** The rule of the diagnostics plugins report; the message names the plugin. */
+pub const RULE: &str = "plugin";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PluginError {
+    Io(String),
+    /** Not a module, or one that does not follow the ABI. */
+    Invalid(String),
+    /** The plugin trapped, ran out of fuel or answered nonsense. */
+    Run(String),
+}
+
+impl fmt::Display for PluginError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PluginError::Io(msg) => f.write_str(msg),
+            PluginError::Invalid(msg) => write!(f, "invalid plugin: {}", msg),
+            PluginError::Run(msg) => write!(f, "plugin failed: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for PluginError {}
+
+fn run_error(e: wasmtime::Error) -> PluginError {
+    PluginError::Run(format!("{:#}", e))
+}
+
+pub struct Plugin {
+    name: String,
+    engine: Engine,
+    module: Module,
+}
+
+impl fmt::Debug for Plugin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Plugin").field("name", &self.name).finish()
+    }
+}
+
+/** An instance of a plugin, for one bibliography. */
+struct Session {
+    store: Store<StoreLimits>,
+    instance: Instance,
+    memory: Memory,
+    alloc: TypedFunc<i32, i32>,
+}
+
+impl Session {
+    /** Call `export` with `input` and return its answer. */
+    fn call(&mut self, export: &str, input: &str) -> Result<String, PluginError> {
+        self.store.set_fuel(MAX_FUEL).map_err(run_error)?;
+        let func = self.instance.get_typed_func::<(i32, i32), i64>(&mut self.store, export)
+            .map_err(|e| PluginError::Invalid(format!("`{}`: {:#}", export, e)))?;
+        let len = i32::try_from(input.len()).map_err(|_| PluginError::Run(String::from("the entry is too large")))?;
+        let ptr = self.alloc.call(&mut self.store, len).map_err(run_error)?;
+        self.memory.write(&mut self.store, ptr as u32 as usize, input.as_bytes())
+            .map_err(|_| PluginError::Run(String::from("`alloc` returned memory the entry does not fit in")))?;
+        let answer = func.call(&mut self.store, (ptr, len)).map_err(run_error)? as u64;
+        let (start, len) = ((answer >> 32) as usize, (answer & 0xffff_ffff) as usize);
+        let bytes = self.memory.data(&self.store).get(start..start + len)
+            .ok_or_else(|| PluginError::Run(format!("`{}` answered outside its memory", export)))?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| PluginError::Run(format!("`{}` answered with invalid UTF-8", export)))
+    }
+}
+
+impl Plugin {
+    /**
+    Compile a plugin from WebAssembly, in its binary or its text format.
+    */
+    pub fn new(name: &str, wasm: &[u8]) -> Result<Plugin, PluginError> {
+        let mut config = Config::new();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config).map_err(|e| PluginError::Invalid(format!("{:#}", e)))?;
+        let module = Module::new(&engine, wasm).map_err(|e| PluginError::Invalid(format!("{:#}", e)))?;
+        if let Some(import) = module.imports().next() {
+            return Err(PluginError::Invalid(format!("imports `{}.{}`, but plugins cannot import anything",
+                import.module(), import.name())));
+        }
+        for export in ["memory", "alloc"] {
+            if module.get_export(export).is_none() {
+                return Err(PluginError::Invalid(format!("no `{}` export", export)));
+            }
+        }
+        let plugin = Plugin { name: String::from(name), engine, module };
+        if !plugin.has_transform() && !plugin.has_lint() {
+            return Err(PluginError::Invalid(String::from("exports neither `transform` nor `lint`")));
+        }
+        Ok(plugin)
+    }
+
+    /**
+    Load a plugin from a `.wasm` (or `.wat`) file, named after the file.
+    */
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Plugin, PluginError> {
+        let path = path.as_ref();
+        let wasm = std::fs::read(path).map_err(|e| PluginError::Io(format!("cannot read {}: {}", path.display(), e)))?;
+        let name = path.file_stem().and_then(|s| s.to_str()).unwrap_or("plugin");
+        Plugin::new(name, &wasm).map_err(|e| match e {
+            PluginError::Invalid(msg) => PluginError::Invalid(format!("{}: {}", path.display(), msg)),
+            e => e,
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn has_transform(&self) -> bool {
+        self.module.get_export("transform").is_some()
+    }
+
+    pub fn has_lint(&self) -> bool {
+        self.module.get_export("lint").is_some()
+    }
+
+    fn session(&self) -> Result<Session, PluginError> {
+        let limits = StoreLimitsBuilder::new().memory_size(MAX_MEMORY).instances(1).build();
+        let mut store = Store::new(&self.engine, limits);
+        store.limiter(|limits| limits);
+        store.set_fuel(MAX_FUEL).map_err(run_error)?;
+        let instance = Instance::new(&mut store, &self.module, &[]).map_err(run_error)?;
+        let memory = instance.get_memory(&mut store, "memory")
+            .ok_or_else(|| PluginError::Invalid(String::from("`memory` is not a memory")))?;
+        let alloc = instance.get_typed_func::<i32, i32>(&mut store, "alloc")
+            .map_err(|e| PluginError::Invalid(format!("`alloc`: {:#}", e)))?;
+        Ok(Session { store, instance, memory, alloc })
+    }
+
+    /**
+    Run the plugin's `lint` over `entries`. An entry the plugin fails on
+    gets an error diagnostic saying so.
+    */
+    pub fn check(&self, entries: &[Entry]) -> Result<Vec<Diagnostic>, PluginError> {
+        if !self.has_lint() {
+            return Ok(vec![]);
+        }
+        let mut session = self.session()?;
+        let mut diagnostics = Vec::new();
+        for entry in entries {
+            match session.call("lint", &entry.to_json().to_string()).and_then(|answer| self.findings(&answer)) {
+                Ok(findings) => diagnostics.extend(findings.into_iter().map(|(severity, message)|
+                    Diagnostic::new(entry.key(), RULE, severity, &format!("{}: {}", self.name, message)))),
+                Err(e) => diagnostics.push(Diagnostic::new(entry.key(), RULE, Severity::Error, &format!("{}: {}", self.name, e))),
+            }
+        }
+        Ok(diagnostics)
+    }
+
+    fn findings(&self, answer: &str) -> Result<Vec<(Severity, String)>, PluginError> {
+        let invalid = || PluginError::Run(format!("`lint` answered `{}`, not a list of findings", answer));
+        let json = parse(answer).map_err(|_| invalid())?;
+        json.as_array().ok_or_else(invalid)?.iter().map(|finding| {
+            let severity = finding.get("severity").and_then(JsonValue::as_str).and_then(Severity::parse);
+            let message = finding.get("message").and_then(JsonValue::as_str);
+            severity.zip(message).map(|(s, m)| (s, String::from(m))).ok_or_else(invalid)
+        }).collect()
+    }
+}
+
+/**
+A plugin's `transform` run on every entry. Entries the plugin fails on are
+left unchanged and reported in the `Report` messages.
+*/
+pub struct PluginTransform {
+    pub plugin: Plugin,
+}
+
+impl Transform for PluginTransform {
+    fn name(&self) -> &str {
+        self.plugin.name()
+    }
+
+    fn description(&self) -> &str {
+        "WebAssembly plugin"
+    }
+
+    fn apply(&self, bibliography: &mut Bibliography) -> Report {
+        if !self.plugin.has_transform() {
+            return Report::default();
+        }
+        let mut session = match self.plugin.session() {
+            Ok(session) => session,
+            Err(e) => return Report { changed: vec![], messages: vec![e.to_string()] },
+        };
+        let mut messages = Vec::new();
+        let changed = bibliography.visit_mut(|entry| {
+            let answer = session.call("transform", &entry.to_json().to_string()).and_then(|answer| {
+                if answer.is_empty() {
+                    return Ok(None);
+                }
+                let json = parse(&answer).map_err(|e| PluginError::Run(format!("`transform` answered invalid JSON: {}", e)))?;
+                Entry::from_json(&json).map(Some).map_err(|e| PluginError::Run(format!("`transform` answered {}", e)))
+            });
+            match answer {
+                Ok(Some(after)) => *entry = after,
+                Ok(None) => {}
+                Err(e) => messages.push(format!("{}: {}", entry.key(), e)),
+            }
+        });
+        Report { changed, messages }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::bibtex::data::BibType;
+
+    /** A plugin whose answer is always `answer`, with the functions in `exports`. */
+    fn plugin(answer: &str, exports: &str) -> Result<Plugin, PluginError> {
+        let wat = format!(r#"(module
+            (memory (export "memory") 1)
+            (global $next (mut i32) (i32.const 1024))
+            (data (i32.const 0) "{}")
+            (func (export "alloc") (param i32) (result i32)
+                (global.get $next)
+                (global.set $next (i32.add (global.get $next) (local.get 0))))
+            {})"#, answer.replace('"', "\\\""), exports.replace("LEN", &answer.len().to_string()));
+        Plugin::new("policy", wat.as_bytes())
+    }
+
+    const LINT: &str = r#"(func (export "lint") (param i32 i32) (result i64) (i64.const LEN))"#;
+    const TRANSFORM: &str = r#"(func (export "transform") (param i32 i32) (result i64) (i64.const LEN))"#;
+    const ECHO: &str = r#"(func (export "transform") (param i32 i32) (result i64)
+        (i64.or (i64.shl (i64.extend_i32_u (local.get 0)) (i64.const 32)) (i64.extend_i32_u (local.get 1))))"#;
+
+    fn entries() -> Vec<Entry> {
+        let mut a = Entry::new(BibType::Article, "a");
+        a.set("title", "Árvores");
+        vec![a, Entry::new(BibType::Book, "b")]
+    }
+
+    #[test]
+    fn test_lint() {
+        let p = plugin(r#"[{"severity": "warning", "message": "no DOI"}]"#, LINT).unwrap();
+        assert!(p.has_lint() && !p.has_transform());
+        let diagnostics = p.check(&entries()).unwrap();
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[1].to_string(), "b: warning [plugin] policy: no DOI");
+
+        let nonsense = plugin(r#"{"message": "no DOI"}"#, LINT).unwrap().check(&entries()).unwrap();
+        assert_eq!(nonsense[0].severity, Severity::Error);
+        assert!(nonsense[0].message.starts_with("policy: plugin failed: `lint` answered"), "{}", nonsense[0].message);
+    }
+
+    #[test]
+    fn test_transform() {
+        let p = plugin(r#"{"type": "misc", "key": "a", "fields": {"note": "checked"}}"#, TRANSFORM).unwrap();
+        let mut bib = Bibliography::from_entries(entries());
+        let report = PluginTransform { plugin: p }.apply(&mut bib);
+        assert_eq!(report.messages, Vec::<String>::new());
+        assert_eq!(bib.entries()[0].get("note"), Some("checked"));
+        assert_eq!(*bib.entries()[0].entry_type(), BibType::Misc);
+
+        let mut bib = Bibliography::from_entries(entries());
+        let report = PluginTransform { plugin: plugin("", ECHO).unwrap() }.apply(&mut bib);
+        assert!(report.is_empty(), "{:?}", report);
+        assert_eq!(bib.entries(), &entries()[..]);
+
+        let mut bib = Bibliography::from_entries(entries());
+        let report = PluginTransform { plugin: plugin("{", TRANSFORM).unwrap() }.apply(&mut bib);
+        assert!(report.changed.is_empty());
+        assert!(report.messages[0].starts_with("a: plugin failed: `transform` answered invalid JSON"), "{:?}", report);
+    }
+
+    #[test]
+    fn test_sandbox() {
+        let invalid = |r: Result<Plugin, PluginError>| matches!(r, Err(PluginError::Invalid(_)));
+        assert!(invalid(Plugin::new("x", b"not wasm")));
+        assert!(invalid(plugin("", "")));
+        assert!(invalid(Plugin::new("x", br#"(module (import "wasi" "fd_write" (func)) (memory (export "memory") 1)
+            (func (export "alloc") (param i32) (result i32) (i32.const 0)))"#)));
+
+        let endless = plugin("[]", r#"(func (export "lint") (param i32 i32) (result i64) (loop (br 0)) (i64.const 0))"#).unwrap();
+        let diagnostics = endless.check(&entries()).unwrap();
+        assert_eq!(diagnostics.len(), 2);
+        assert!(diagnostics[0].message.contains("fuel"), "{}", diagnostics[0].message);
+
+        let outside = plugin("[]", r#"(func (export "lint") (param i32 i32) (result i64) (i64.const 0xffffff))"#).unwrap();
+        assert!(outside.check(&entries()).unwrap()[0].message.contains("outside its memory"));
+
+        let greedy = plugin("", r#"(memory (export "big") 2000)
+            (func (export "lint") (param i32 i32) (result i64) (i64.const 0))"#).unwrap();
+        assert!(matches!(greedy.check(&entries()), Err(PluginError::Run(_))));
+    }
+}
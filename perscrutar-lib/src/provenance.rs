@@ -0,0 +1,167 @@
+/*!
+
+Where the value of a field came from.
+
+Values are read from the `.bib` file, typed in by the user, or filled in by
+a lookup such as Crossref. Telling these apart keeps enrichment from
+overwriting a title the user has corrected by hand. The source of each
+field set by a tool is kept in the `MetadataStore` sidecar, under
+`provenance`, together with the value it set:
+
+```json
+"knuth84": {
+  "fingerprint": "doi:10.1093/comjnl/27.2.97",
+  "provenance": {
+    "pages": {"source": "crossref@2026-10-16", "value": "97--111"},
+    "title": {"source": "user", "value": "Literate Programming"}
+  }
+}
+```
+
+A field whose value no longer matches the recorded one has been edited
+since, so its source is `Source::User`; a field with no record comes from
+the file (`Source::Parsed`). `conflicts` lists the fields where a lookup
+disagrees with the entry, with the source of the value it would replace.
+
+*/
+
+use std::fmt;
+use crate::bibtex::data::Entry;
+use crate::json::JsonValue;
+use crate::metadata::MetadataStore;
+
+const MEMBER: &str = "provenance";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    /** Read from the bibliography file, with nothing known about its origin. */
+    Parsed,
+    /** Set or corrected by hand. */
+    User,
+    /** Filled in by a lookup service on a date (`YYYY-MM-DD`). */
+    Lookup { service: String, date: String },
+}
+
+impl Source {
+    pub fn lookup(service: &str, date: &str) -> Source {
+        Source::Lookup { service: String::from(service), date: String::from(date) }
+    }
+
+    /** `parsed`, `user` or `service@date`. */
+    pub fn parse(s: &str) -> Option<Source> {
+        match s.trim() {
+            "parsed" => Some(Source::Parsed),
+            "user" => Some(Source::User),
+            s => s.split_once('@')
+                .filter(|(service, date)| !service.is_empty() && !date.is_empty())
+                .map(|(service, date)| Source::lookup(service, date)),
+        }
+    }
+
+    pub fn is_manual(&self) -> bool {
+        *self == Source::User
+    }
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Source::Parsed => f.write_str("parsed"),
+            Source::User => f.write_str("user"),
+            Source::Lookup { service, date } => write!(f, "{}@{}", service, date),
+        }
+    }
+}
+
+/** A field a lookup would change, and where its current value came from. */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conflict {
+    pub field: String,
+    pub current: String,
+    pub proposed: String,
+    pub source: Source,
+}
+
+impl MetadataStore {
+    /**
+    Record that `source` set `field` of `entry` to its current value. A
+    field the entry does not have is forgotten instead.
+    */
+    pub fn set_source(&mut self, entry: &Entry, field: &str, source: &Source) {
+        let mut fields = match self.get(entry.key(), MEMBER) {
+            Some(JsonValue::Object(members)) => members.clone(),
+            _ => Vec::new(),
+        };
+        fields.retain(|(name, _)| name != field);
+        if let Some(value) = entry.get(field) {
+            fields.push((String::from(field), JsonValue::object(vec![
+                ("source", JsonValue::str(&source.to_string())),
+                ("value", JsonValue::str(value)),
+            ])));
+        }
+        self.set(entry, MEMBER, JsonValue::Object(fields));
+    }
+
+    /**
+    Where the current value of `field` came from: the recorded source if
+    the value is still the one recorded, `User` if it has been edited
+    since, and `Parsed` if nothing is recorded.
+    */
+    pub fn source(&self, entry: &Entry, field: &str) -> Source {
+        let Some(record) = self.get(entry.key(), MEMBER).and_then(|p| p.get(field)) else { return Source::Parsed };
+        let recorded = record.get("value").and_then(JsonValue::as_str);
+        match (recorded, entry.get(field)) {
+            (Some(recorded), Some(current)) if recorded == current => record.get("source")
+                .and_then(JsonValue::as_str)
+                .and_then(Source::parse)
+                .unwrap_or(Source::Parsed),
+            _ => Source::User,
+        }
+    }
+}
+
+/**
+The fields `found` (the result of a lookup) gives a different value for
+than `entry`, with the source of the entry's value. Fields the entry does
+not have are not conflicts.
+*/
+pub fn conflicts(store: &MetadataStore, entry: &Entry, found: &Entry) -> Vec<Conflict> {
+    found.fields().filter_map(|(field, proposed)| {
+        let current = entry.get(field)?;
+        (current.trim() != proposed.trim()).then(|| Conflict {
+            field: String::from(field),
+            current: String::from(current),
+            proposed: String::from(proposed),
+            source: store.source(entry, field),
+        })
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::bibtex::data::BibType;
+
+    #[test]
+    fn test_source() {
+        let mut e = Entry::new(BibType::Article, "knuth84");
+        e.set("title", "Literate Programming");
+        e.set("pages", "97--111");
+        let mut store = MetadataStore::new();
+        assert_eq!(store.source(&e, "pages"), Source::Parsed);
+        store.set_source(&e, "pages", &Source::lookup("crossref", "2026-10-16"));
+        assert_eq!(store.source(&e, "pages"), Source::lookup("crossref", "2026-10-16"));
+        e.set("pages", "97--112");
+        assert!(store.source(&e, "pages").is_manual());
+        assert_eq!(Source::parse("crossref@2026-10-16"), Some(Source::lookup("crossref", "2026-10-16")));
+        assert_eq!(Source::parse("@"), None);
+
+        let mut found = Entry::new(BibType::Article, "knuth84");
+        found.set("title", "Literate programming");
+        found.set("pages", "97--111");
+        found.set("volume", "27");
+        let fields: Vec<(String, Source)> = conflicts(&store, &e, &found).into_iter().map(|c| (c.field, c.source)).collect();
+        assert_eq!(fields, vec![(String::from("title"), Source::Parsed), (String::from("pages"), Source::User)]);
+    }
+}
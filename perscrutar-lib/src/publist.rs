@@ -0,0 +1,600 @@
+/*!
+
+Publication lists for personal and group websites.
+
+`render` selects the entries of one author, groups them by year (newest
+first, undated entries last) and renders them as HTML or Markdown. Each
+entry is rendered with a `Template`, a line of text with `{field}`
+placeholders:
+
+- `{authors}` lists the authors as `Donald E. Knuth, Leslie Lamport`, with
+  `et al.` for `others` (see `bibtex::names`),
+  `{venue}` is the journal, booktitle, school, institution or publisher,
+  `{title}` includes the subtitle and title addition, `{shorttitle}` falls
+  back to the title (see `bibtex::titles`),
+  `{series}` places books in their series or multi-volume work (see
+  `bibtex::volumes`),
+  `{holder}` is the list of patent holders like `{authors}`,
+  and any other name is looked up as a field (`{title}`, `{doi}`, `{key}`);
+- text in square brackets is only kept if every placeholder in it has a
+  value, so `[, doi:{doi}]` disappears for entries without a DOI. Brackets
+  nest, so Markdown links can be used inside such a segment.
+
+For `@patent` entries the inventors are the authors, `{venue}` is the
+patent number with its office (`Patent US 7,654,321 B2`, see
+`bibtex::patents`) and `{number}` is empty so as not to repeat it. For `@jurisdiction` entries
+`{venue}` is the reporter citation or the court and docket (see
+`bibtex::legal`).
+
+Values are stripped of TeX grouping braces and, for HTML, escaped.
+Punctuation is not doubled: a value that ends with a period, such as `D. E.`
+or `et al.`, is not followed by the period of the template, and separators
+left at the start of an item by empty segments are dropped, so the default
+templates need neither authors nor a title.
+
+Years are ordered as numbers, so that `999` comes after `2011`.
+
+With the `templates` feature, a `PageTemplate` lays out the whole page
+with [Handlebars](https://handlebarsjs.com) instead, for sites whose
+markup the fixed layout of `render` does not fit. The template sees
+
+- `title`, the heading, if any;
+- `years`, newest first, each with `year` (missing for undated entries)
+  and `entries`;
+- for each entry `key`, `type`, `item` (the entry rendered with the item
+  `Template`), `authors` (a list of names, without `others`), `et_al`
+  (whether the list ends with `others`) and `fields`, every field by name
+  with its value cleaned as for the item template.
+
+Values are already escaped for the format, so `{{item}}` is used rather
+than `{{{item}}}`:
+
+```text
+{{#each years}}
+<h2>{{#if year}}{{year}}{{else}}Undated{{/if}}</h2>
+<ul>
+{{#each entries}}  <li id="{{key}}">{{item}}</li>
+{{/each}}</ul>
+{{/each}}
+```
+
+*/
+
+use crate::bibtex::data::Entry;
+use crate::bibtex::legal;
+use crate::bibtex::names::Name;
+use crate::bibtex::patents::PatentNumber;
+use crate::bibtex::policy::FieldPolicy;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PubFormat {
+    Html,
+    Markdown,
+}
+
+impl PubFormat {
+    pub fn from_name(name: &str) -> Option<PubFormat> {
+        match name.to_lowercase().as_str() {
+            "html" => Some(PubFormat::Html),
+            "markdown" | "md" => Some(PubFormat::Markdown),
+            _ => None,
+        }
+    }
+}
+
+/**
+Selects the entries of one person, by name or by ORCID iD.
+
+Names match an author if the family name is equal and the given names, if
+provided, are compatible (`D. E. Knuth` matches `Knuth, Donald Ervin`).
+ORCID iDs are looked for in the `orcid` and `orcid-numbers` fields.
+*/
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AuthorFilter {
+    pub name: Option<String>,
+    pub orcid: Option<String>,
+}
+
+/** Given names, lower-cased and split at spaces, periods and hyphens. */
+fn given_names(name: &Name) -> Vec<String> {
+    strip_braces(&name.first).split(|c: char| c.is_whitespace() || c == '.' || c == '-')
+        .filter(|w| !w.is_empty())
+        .map(|w| w.to_lowercase())
+        .collect()
+}
+
+fn family_name(name: &Name) -> String {
+    strip_braces(&name.last).split_whitespace().collect::<Vec<&str>>().join(" ").to_lowercase()
+}
+
+fn names_match(wanted: &Name, author: &Name) -> bool {
+    let (wg, ag) = (given_names(wanted), given_names(author));
+    // every given name asked for must match the author's, in order;
+    // an initial matches any name starting with it
+    family_name(wanted) == family_name(author) && wg.iter().zip(ag.iter()).all(|(w, a)| {
+        if w.chars().count() == 1 || a.chars().count() == 1 {
+            w.chars().next() == a.chars().next()
+        } else {
+            w == a
+        }
+    })
+}
+
+impl AuthorFilter {
+    pub fn matches(&self, entry: &Entry) -> bool {
+        let by_name = self.name.as_deref().map(|wanted| {
+            let wanted = Name::parse(wanted);
+            entry.authors().iter().any(|author| !author.is_others() && names_match(&wanted, author))
+        });
+        let by_orcid = self.orcid.as_deref().map(|id| {
+            ["orcid", "orcid-numbers"].iter().any(|f| entry.get(f).map(|v| v.contains(id)).unwrap_or(false))
+        });
+        match (by_name, by_orcid) {
+            (None, None) => true,
+            (Some(a), Some(b)) => a || b,
+            (Some(a), None) | (None, Some(a)) => a,
+        }
+    }
+}
+
+fn strip_braces(s: &str) -> String {
+    s.chars().filter(|c| *c != '{' && *c != '}').collect()
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+const VENUE_FIELDS: [&str; 5] = ["journal", "booktitle", "school", "institution", "publisher"];
+
+/** A name as printed: `Donald E. Knuth`, `Ludwig van Beethoven`, `Henry Ford, Jr.`. */
+fn display_name(name: &Name) -> String {
+    let mut out = [&name.first, &name.von, &name.last].into_iter()
+        .filter(|part| !part.trim().is_empty())
+        .map(|part| part.trim())
+        .collect::<Vec<&str>>()
+        .join(" ");
+    if !name.jr.trim().is_empty() {
+        out.push_str(", ");
+        out.push_str(name.jr.trim());
+    }
+    out
+}
+
+/** The names, as printed, with `others` as `et al.`; `None` if there are none. */
+fn name_list(names: &[Name]) -> Option<String> {
+    let list: Vec<String> = names.iter()
+        .map(|n| if n.is_others() { String::from("et al.") } else { display_name(n) })
+        .filter(|n| !n.is_empty())
+        .collect();
+    if list.is_empty() { None } else { Some(list.join(", ")) }
+}
+
+fn value(entry: &Entry, name: &str) -> Option<String> {
+    let patent = entry.entry_type().name() == "patent";
+    let raw = match name {
+        "key" => Some(entry.key().to_string()),
+        "type" => Some(entry.entry_type().name().to_string()),
+        "authors" => name_list(&entry.authors()),
+        "holder" => name_list(&entry.holders()),
+        "venue" if patent => PatentNumber::from_entry(entry).map(|p| p.describe())
+            .or_else(|| entry.get("number").map(|n| format!("Patent {}", n))),
+        "number" if patent => None,
+        "venue" if entry.entry_type().name() == "jurisdiction" => legal::venue(entry),
+        "title" => entry.title_parts().map(|t| t.full()),
+        "shorttitle" => entry.title_parts().map(|t| String::from(t.short())),
+        "series" => match entry.volume_info() {
+            Some(info) => info.describe(),
+            None => entry.get("series").map(String::from),
+        },
+        "venue" => VENUE_FIELDS.iter().find_map(|f| entry.get(f)).map(String::from),
+        _ => entry.get(name).map(String::from),
+    }?;
+    let clean = strip_braces(&raw).split_whitespace().collect::<Vec<&str>>().join(" ");
+    if clean.is_empty() { None } else { Some(clean) }
+}
+
+fn matching_bracket(s: &str, open: usize) -> Option<usize> {
+    let mut depth = 0;
+    for (i, c) in s[open..].char_indices() {
+        match c {
+            '[' => depth += 1,
+            ']' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(open + i);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/**
+An item as it is rendered: text from the template is added without a
+period that would follow one ending a value, and without separators at
+the start of the item.
+*/
+#[derive(Debug, Clone, Default)]
+struct Output {
+    text: String,
+    after_period: bool,
+}
+
+impl Output {
+    fn push_text(&mut self, text: &str) {
+        let mut text = if self.after_period { text.strip_prefix('.').unwrap_or(text) } else { text };
+        if self.text.is_empty() {
+            text = text.trim_start_matches(|c: char| c.is_whitespace() || ".,;:".contains(c));
+        }
+        if !text.is_empty() {
+            self.after_period = false;
+            self.text.push_str(text);
+        }
+    }
+
+    fn push_value(&mut self, value: &str) {
+        self.text.push_str(value);
+        self.after_period = value.ends_with('.');
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template(String);
+
+impl Template {
+    pub fn new(template: &str) -> Template {
+        Template(String::from(template))
+    }
+
+    /**
+    Default item template for `format`.
+    */
+    pub fn default_for(format: PubFormat) -> Template {
+        match format {
+            PubFormat::Html => Template::new("{authors}[. <em>{title}</em>][. {series}][. {venue}][, {year}].[ <a href=\"https://doi.org/{doi}\">doi:{doi}</a>]"),
+            PubFormat::Markdown => Template::new("{authors}[. *{title}*][. {series}][. {venue}][, {year}].[ [doi:{doi}](https://doi.org/{doi})]"),
+        }
+    }
+
+    /**
+    Add `segment` with its placeholders filled in to `out`. If `optional`,
+    a placeholder without a value drops the whole segment, leaving `out` as
+    it was, otherwise it renders as nothing.
+    */
+    fn fill(segment: &str, entry: &Entry, escape: fn(&str) -> String, optional: bool, out: &mut Output) {
+        let mut filled = out.clone();
+        let mut rest = segment;
+        while let Some(open) = rest.find('{') {
+            filled.push_text(&rest[..open]);
+            let Some(close) = rest[open..].find('}').map(|c| open + c) else {
+                rest = &rest[open..];
+                break;
+            };
+            match value(entry, &rest[open + 1..close]) {
+                Some(v) => filled.push_value(&escape(&v)),
+                None if optional => return,
+                None => {}
+            }
+            rest = &rest[close + 1..];
+        }
+        filled.push_text(rest);
+        *out = filled;
+    }
+
+    pub fn render(&self, entry: &Entry, format: PubFormat) -> String {
+        let escape: fn(&str) -> String = match format {
+            PubFormat::Html => escape_html,
+            PubFormat::Markdown => |s: &str| String::from(s),
+        };
+        let mut out = Output::default();
+        let mut rest = self.0.as_str();
+        while let Some(open) = rest.find('[') {
+            let Some(close) = matching_bracket(rest, open) else { break };
+            Template::fill(&rest[..open], entry, escape, false, &mut out);
+            Template::fill(&rest[open + 1..close], entry, escape, true, &mut out);
+            rest = &rest[close + 1..];
+        }
+        Template::fill(rest, entry, escape, false, &mut out);
+        out.text
+    }
+}
+
+/**
+Entries grouped by `year`, newest first by number; entries without a year
+come last under `None`. Within a year the input order is kept.
+*/
+pub fn group_by_year<'a>(entries: &[&'a Entry]) -> Vec<(Option<String>, Vec<&'a Entry>)> {
+    let mut groups: Vec<(Option<String>, Vec<&Entry>)> = Vec::new();
+    for &e in entries {
+        let year = value(e, "year");
+        match groups.iter_mut().find(|(y, _)| *y == year) {
+            Some((_, list)) => list.push(e),
+            None => groups.push((year, vec![e])),
+        }
+    }
+    // years that are not numbers, such as `in press`, come after the others
+    let number = |y: &str| y.parse::<i64>().ok();
+    groups.sort_by(|(a, _), (b, _)| match (a, b) {
+        (None, None) => std::cmp::Ordering::Equal,
+        (None, Some(_)) => std::cmp::Ordering::Greater,
+        (Some(_), None) => std::cmp::Ordering::Less,
+        (Some(a), Some(b)) => match (number(a), number(b)) {
+            (Some(x), Some(y)) => y.cmp(&x),
+            (Some(_), None) => std::cmp::Ordering::Less,
+            (None, Some(_)) => std::cmp::Ordering::Greater,
+            (None, None) => b.cmp(a),
+        },
+    });
+    groups
+}
+
+#[derive(Debug, Clone)]
+pub struct PublistOptions {
+    pub format: PubFormat,
+    pub filter: AuthorFilter,
+    /** Item template; `Template::default_for(format)` if unset. */
+    pub template: Option<Template>,
+    /** Heading for the whole list. */
+    pub title: Option<String>,
+    /** Fields templates cannot show, as private. */
+    pub policy: FieldPolicy,
+}
+
+pub fn render(entries: &[Entry], options: &PublistOptions) -> String {
+    let public = options.policy.apply_all(entries);
+    let selected: Vec<&Entry> = public.iter().filter(|e| options.filter.matches(e)).collect();
+    let template = options.template.clone().unwrap_or_else(|| Template::default_for(options.format));
+    let mut out = String::new();
+    match options.format {
+        PubFormat::Html => {
+            if let Some(t) = &options.title {
+                out.push_str(&format!("<h1>{}</h1>\n", escape_html(t)));
+            }
+            for (year, list) in group_by_year(&selected) {
+                out.push_str(&format!("<h2>{}</h2>\n<ul>\n", escape_html(year.as_deref().unwrap_or("Undated"))));
+                for e in list {
+                    out.push_str(&format!("  <li id=\"{}\">{}</li>\n", escape_html(e.key()), template.render(e, options.format)));
+                }
+                out.push_str("</ul>\n");
+            }
+        }
+        PubFormat::Markdown => {
+            if let Some(t) = &options.title {
+                out.push_str(&format!("# {}\n\n", t));
+            }
+            for (year, list) in group_by_year(&selected) {
+                out.push_str(&format!("## {}\n\n", year.as_deref().unwrap_or("Undated")));
+                for e in list {
+                    out.push_str(&format!("- {}\n", template.render(e, options.format)));
+                }
+                out.push('\n');
+            }
+        }
+    }
+    out
+}
+
+/**
+A whole page laid out by a Handlebars template; see the module
+documentation for what the template sees.
+*/
+#[cfg(feature = "templates")]
+#[derive(Debug)]
+pub struct PageTemplate(handlebars::Handlebars<'static>);
+
+#[cfg(feature = "templates")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TemplateError {
+    Syntax(String),
+    Render(String),
+}
+
+#[cfg(feature = "templates")]
+impl std::fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TemplateError::Syntax(msg) => write!(f, "invalid page template: {}", msg),
+            TemplateError::Render(msg) => write!(f, "cannot render the page template: {}", msg),
+        }
+    }
+}
+
+#[cfg(feature = "templates")]
+impl std::error::Error for TemplateError {}
+
+#[cfg(feature = "templates")]
+impl PageTemplate {
+    pub fn new(source: &str) -> Result<PageTemplate, TemplateError> {
+        let mut registry = handlebars::Handlebars::new();
+        // values are escaped for the format before the template sees them
+        registry.register_escape_fn(handlebars::no_escape);
+        registry.register_template_string("page", source).map_err(|e| TemplateError::Syntax(e.to_string()))?;
+        Ok(PageTemplate(registry))
+    }
+
+    /**
+    The page for the entries `options` selects, with each item rendered by
+    its item template.
+    */
+    pub fn render(&self, entries: &[Entry], options: &PublistOptions) -> Result<String, TemplateError> {
+        use serde_json::{json, Map, Value};
+
+        let escape = |s: &str| match options.format {
+            PubFormat::Html => escape_html(s),
+            PubFormat::Markdown => String::from(s),
+        };
+        let public = options.policy.apply_all(entries);
+        let selected: Vec<&Entry> = public.iter().filter(|e| options.filter.matches(e)).collect();
+        let template = options.template.clone().unwrap_or_else(|| Template::default_for(options.format));
+        let years: Vec<Value> = group_by_year(&selected).into_iter().map(|(year, list)| {
+            let entries: Vec<Value> = list.into_iter().map(|e| {
+                let names = e.authors();
+                let authors: Vec<String> = names.iter().filter(|n| !n.is_others())
+                    .map(|n| escape(&strip_braces(&display_name(n))))
+                    .collect();
+                let fields: Map<String, Value> = e.field_names().into_iter()
+                    .filter_map(|f| value(e, f).map(|v| (String::from(f), Value::String(escape(&v)))))
+                    .collect();
+                json!({
+                    "key": escape(e.key()),
+                    "type": e.entry_type().name(),
+                    "item": template.render(e, options.format),
+                    "authors": authors,
+                    "et_al": names.last().is_some_and(Name::is_others),
+                    "fields": fields,
+                })
+            }).collect();
+            json!({"year": year.map(|y| escape(&y)), "entries": entries})
+        }).collect();
+        let context = json!({"title": options.title.as_deref().map(escape), "years": years});
+        self.0.render("page", &context).map_err(|e| TemplateError::Render(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::bibtex::data::BibType;
+
+    fn entry(key: &str, author: &str, year: Option<&str>) -> Entry {
+        let mut e = Entry::new(BibType::Article, key);
+        e.set("author", author);
+        e.set("title", "The {TeX}book & more");
+        e.set("journal", "TUGboat");
+        if let Some(y) = year {
+            e.set("year", y);
+        }
+        e
+    }
+
+    #[test]
+    fn test_filter() {
+        let e = entry("a", "Knuth, Donald Ervin and Lamport, Leslie", None);
+        let by = |name: &str| AuthorFilter { name: Some(String::from(name)), orcid: None }.matches(&e);
+        assert!(by("D. E. Knuth"));
+        assert!(by("Knuth"));
+        assert!(by("Leslie Lamport"));
+        assert!(!by("Dorothy Knuth"));
+        assert!(!by("others"));
+        let wrapped = entry("b", "Lamport, Leslie and\nKnuth, D. E. and others", None);
+        assert!(AuthorFilter { name: Some(String::from("Donald Knuth")), orcid: None }.matches(&wrapped));
+        let mut o = e.clone();
+        o.set("orcid-numbers", "Knuth, Donald/0000-0002-1825-0097");
+        assert!(AuthorFilter { name: None, orcid: Some(String::from("0000-0002-1825-0097")) }.matches(&o));
+    }
+
+    #[test]
+    fn test_template() {
+        let mut e = entry("a", "Knuth, Donald and Lamport, Leslie", Some("1984"));
+        assert_eq!(Template::default_for(PubFormat::Markdown).render(&e, PubFormat::Markdown),
+            "Donald Knuth, Leslie Lamport. *The TeXbook & more*. TUGboat, 1984.");
+        e.set("doi", "10.1/x");
+        assert!(Template::default_for(PubFormat::Markdown).render(&e, PubFormat::Markdown)
+            .ends_with("TUGboat, 1984. [doi:10.1/x](https://doi.org/10.1/x)"));
+        assert!(Template::default_for(PubFormat::Html).render(&e, PubFormat::Html)
+            .ends_with("<em>The TeXbook &amp; more</em>. TUGboat, 1984. <a href=\"https://doi.org/10.1/x\">doi:10.1/x</a>"));
+        assert_eq!(Template::new("{key}: {missing}[ ({missing})]").render(&e, PubFormat::Markdown), "a: ");
+        e.set("subtitle", "Volume B");
+        assert!(Template::new("{title} / {shorttitle}").render(&e, PubFormat::Markdown)
+            .starts_with("The TeXbook & more: Volume B / The TeXbook"));
+        e.set_entry_type(BibType::Book);
+        e.set("series", "LNCS");
+        e.set("number", "42");
+        assert!(Template::default_for(PubFormat::Markdown).render(&e, PubFormat::Markdown).contains("*. LNCS 42. TUGboat"));
+
+        let markdown = |e: &Entry| Template::default_for(PubFormat::Markdown).render(e, PubFormat::Markdown);
+        let initials = entry("i", "Knuth, D. E. and van Beethoven, Jr., Ludwig and others", Some("1984"));
+        assert_eq!(markdown(&initials), "D. E. Knuth, Ludwig van Beethoven, Jr., et al. *The TeXbook & more*. TUGboat, 1984.");
+        let mut bare = Entry::new(BibType::Misc, "bare");
+        assert_eq!(markdown(&bare), "");
+        bare.set("author", " and ");
+        bare.set("title", "Untitled");
+        assert_eq!(markdown(&bare), "*Untitled*.");
+        bare.set("author", "Knuth, D. E.");
+        bare.remove("title");
+        assert_eq!(Template::default_for(PubFormat::Html).render(&bare, PubFormat::Html), "D. E. Knuth.");
+
+        let mut p = entry("p", "Doe, Jane", Some("2010"));
+        p.set_entry_type(BibType::parse("patent"));
+        p.remove("journal");
+        p.set("number", "US7654321B2");
+        p.set("holder", "{Acme Corporation}");
+        assert_eq!(Template::new("{authors}. {title}. {venue}[ ({number})][, {holder}], {year}.").render(&p, PubFormat::Markdown),
+            "Jane Doe. The TeXbook & more. Patent US 7,654,321 B2, Acme Corporation, 2010.");
+    }
+
+    #[test]
+    fn test_render() {
+        let mut entries = vec![
+            entry("old", "Knuth, D.", Some("1984")),
+            entry("undated", "Knuth, D.", None),
+            entry("new", "Knuth, D.", Some("2011")),
+            entry("other", "Lamport, L.", Some("2020")),
+        ];
+        let options = PublistOptions {
+            format: PubFormat::Markdown,
+            filter: AuthorFilter { name: Some(String::from("Knuth")), orcid: None },
+            template: Some(Template::new("{key}[ ({note})]")),
+            title: None,
+            policy: FieldPolicy::new(),
+        };
+        assert_eq!(render(&entries, &options), "## 2011\n\n- new\n\n## 1984\n\n- old\n\n## Undated\n\n- undated\n\n");
+        let years = [entry("a", "Knuth, D.", Some("999")), entry("b", "Knuth, D.", Some("in press")), entry("c", "Knuth, D.", Some("2011"))];
+        let refs: Vec<&Entry> = years.iter().collect();
+        let order: Vec<Option<String>> = group_by_year(&refs).into_iter().map(|(y, _)| y).collect();
+        assert_eq!(order, [Some("2011"), Some("999"), Some("in press")].map(|y| y.map(String::from)));
+        entries[0].set("note", "to reread");
+        assert!(render(&entries, &options).contains("- old (to reread)\n"));
+        let private = PublistOptions { policy: FieldPolicy::new().private("note"), ..options.clone() };
+        assert!(render(&entries, &private).contains("- old\n"));
+        let html = render(&entries, &PublistOptions { format: PubFormat::Html, title: Some(String::from("Papers")), ..options });
+        assert!(html.starts_with("<h1>Papers</h1>\n<h2>2011</h2>\n<ul>\n  <li id=\"new\">new</li>\n</ul>\n"));
+    }
+
+    #[test]
+    fn test_snapshots() {
+        let mut entries = vec![
+            entry("old", "Knuth, Donald", Some("1984")),
+            entry("undated", "Knuth, Donald and Lamport, Leslie", None),
+            entry("new", "Knuth, Donald", Some("2011")),
+        ];
+        entries[2].set("doi", "10.1/x");
+        for (name, format) in [("html", PubFormat::Html), ("markdown", PubFormat::Markdown)] {
+            let options = PublistOptions {
+                format,
+                filter: AuthorFilter::default(),
+                template: None,
+                title: Some(String::from("Publications & talks")),
+                policy: FieldPolicy::new(),
+            };
+            insta::assert_snapshot!(name, render(&entries, &options));
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "templates")]
+    fn test_page_template() {
+        let entries = vec![
+            entry("old", "Knuth, Donald and others", Some("1984")),
+            entry("new", "Knuth, Donald", Some("2011")),
+            entry("undated", "Knuth, Donald", None),
+        ];
+        let options = PublistOptions {
+            format: PubFormat::Html,
+            filter: AuthorFilter::default(),
+            template: Some(Template::new("{title}")),
+            title: Some(String::from("Papers & talks")),
+            policy: FieldPolicy::new(),
+        };
+        let page = PageTemplate::new("<h1>{{title}}</h1>\n{{#each years}}<h2>{{#if year}}{{year}}{{else}}Undated{{/if}}</h2>\n\
+            {{#each entries}}<p id=\"{{key}}\">{{item}} ({{fields.journal}}; {{#each authors}}{{this}}{{/each}}{{#if et_al}} et al.{{/if}})</p>\n{{/each}}{{/each}}").unwrap();
+        assert_eq!(page.render(&entries, &options).unwrap(), "<h1>Papers &amp; talks</h1>\n\
+            <h2>2011</h2>\n<p id=\"new\">The TeXbook &amp; more (TUGboat; Donald Knuth)</p>\n\
+            <h2>1984</h2>\n<p id=\"old\">The TeXbook &amp; more (TUGboat; Donald Knuth et al.)</p>\n\
+            <h2>Undated</h2>\n<p id=\"undated\">The TeXbook &amp; more (TUGboat; Donald Knuth)</p>\n");
+        assert!(matches!(PageTemplate::new("{{#each years}}"), Err(TemplateError::Syntax(_))));
+    }
+}
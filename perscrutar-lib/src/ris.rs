@@ -0,0 +1,358 @@
+/*!
+
+Reading and writing RIS.
+
+RIS is the tagged format of EndNote and Reference Manager, and what most
+publisher sites offer under "export citation". Each line is a two-letter
+tag and a value; `TY` starts a record and `ER` ends it:
+
+```text
+TY  - JOUR
+AU  - Knuth, Donald E.
+TI  - Literate programming
+JO  - The Computer Journal
+VL  - 27
+IS  - 2
+SP  - 97
+EP  - 111
+PY  - 1984/05//
+DO  - 10.1093/comjnl/27.2.97
+ER  -
+```
+
+`from_ris` turns each record into an entry. The `TY` picks the entry type
+(the audiovisual types through `media::from_ris_type`); `AU`, `A1` and
+`ED`, `A2` give authors and editors, already in BibTeX's `Last, First`
+form; `PY`, `Y1` or `DA` give `date` and `year`. The secondary title (`T2`,
+`JO`, ...) is the journal of an article and the book title of a chapter or
+paper. Lines without a tag continue the value before them, as long
+abstracts are sometimes wrapped. The `ID` is the citation key if it is
+one, otherwise the key comes from the first author and the year.
+
+`to_ris` writes entries as records again, with the citation key as `ID`
+and values decoded from LaTeX to Unicode (see `latex::decode`). Fields RIS
+has no tag for are left out.
+
+*/
+
+use std::fmt;
+use crate::bibtex::data::{BibType, Entry};
+use crate::bibtex::keys::{citation_key, is_key, unique_key};
+use crate::bibtex::media::{from_ris_type, ris_type};
+use crate::bibtex::months::parse_month;
+use crate::bibtex::names::{bibtex_name, parse_names};
+use crate::bibtex::policy::FieldPolicy;
+use crate::latex::decode::decode;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RisError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for RisError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for RisError {}
+
+/** The entry type for a RIS type. Types without a counterpart become `@misc`. */
+pub fn entry_type(ty: &str) -> BibType {
+    if let Some(media) = from_ris_type(ty) {
+        return media;
+    }
+    let name = match ty.trim() {
+        "JOUR" | "JFULL" | "MGZN" | "NEWS" | "EJOUR" => "article",
+        "BOOK" | "EBOOK" | "EDBOOK" => "book",
+        "CHAP" | "ECHAP" => "incollection",
+        "CONF" | "CPAPER" => "inproceedings",
+        "ENCYC" | "DICT" => "inreference",
+        "RPRT" => "report",
+        "THES" => "thesis",
+        "UNPB" | "MANSCPT" => "unpublished",
+        "ELEC" | "WEB" | "BLOG" => "online",
+        "COMP" => "software",
+        "DATA" => "dataset",
+        "PAT" => "patent",
+        "STAND" => "standard",
+        "CASE" => "jurisdiction",
+        "STAT" | "BILL" => "legislation",
+        _ => "misc",
+    };
+    BibType::parse(name)
+}
+
+/** The tag and value of a tagged line: two capitals or digits, then `  -`. */
+fn tagged(line: &str) -> Option<(&str, &str)> {
+    let tag = line.get(..2)?;
+    let rest = line[2..].trim_start_matches(' ').strip_prefix('-')?;
+    tag.chars().all(|c| c.is_ascii_uppercase() || c.is_ascii_digit()).then(|| (tag, rest.trim()))
+}
+
+/** `2001/05/12/`, `2001/05//` or `2001` as `2001-05-12`, `2001-05`, `2001`. */
+fn date(value: &str) -> Option<String> {
+    let parts: Vec<&str> = value.split(['/', '-']).take(3).take_while(|p| !p.is_empty()).collect();
+    let valid = parts.first().map(|y| y.len() == 4).unwrap_or(false)
+        && parts.iter().all(|p| p.len() <= 4 && p.chars().all(|c| c.is_ascii_digit()));
+    valid.then(|| parts.iter().enumerate()
+        .map(|(i, p)| if i == 0 { String::from(*p) } else { format!("{:0>2}", p) })
+        .collect::<Vec<_>>()
+        .join("-"))
+}
+
+/** One record as tags and values, in order. */
+type Record = Vec<(String, String)>;
+
+fn values<'a>(record: &'a Record, tags: &[&str]) -> Vec<&'a str> {
+    record.iter().filter(|(t, v)| tags.contains(&t.as_str()) && !v.is_empty()).map(|(_, v)| v.as_str()).collect()
+}
+
+fn first<'a>(record: &'a Record, tags: &[&str]) -> Option<&'a str> {
+    values(record, tags).first().copied()
+}
+
+fn entry(record: &Record) -> Entry {
+    let ty = entry_type(first(record, &["TY"]).unwrap_or(""));
+    let mut entry = Entry::new(ty.clone(), "");
+    for (field, tags) in [("author", ["AU", "A1"]), ("editor", ["ED", "A2"])] {
+        let names = values(record, &tags);
+        if !names.is_empty() {
+            entry.set(field, &names.join(" and "));
+        }
+    }
+    let container = match ty.name() {
+        "article" => "journal",
+        "incollection" | "inproceedings" | "inreference" => "booktitle",
+        _ => "series",
+    };
+    let publisher = match ty.name() {
+        "report" => "institution",
+        "thesis" => "school",
+        _ => "publisher",
+    };
+    let fields: [(&str, &[&str]); 15] = [
+        ("title", &["TI", "T1", "CT"]),
+        (container, &["T2", "JO", "JF", "JA", "J2", "BT"]),
+        ("series", &["T3"]),
+        ("volume", &["VL"]),
+        ("number", &["IS", "M1"]),
+        ("edition", &["ET"]),
+        (publisher, &["PB"]),
+        ("location", &["CY", "PP"]),
+        ("doi", &["DO"]),
+        ("url", &["UR", "L2"]),
+        (if matches!(ty.name(), "book" | "incollection") { "isbn" } else { "issn" }, &["SN"]),
+        ("abstract", &["AB", "N2"]),
+        ("note", &["N1"]),
+        ("language", &["LA"]),
+        ("urldate", &["Y2"]),
+    ];
+    for (field, tags) in fields {
+        if entry.has(field) {
+            continue;
+        }
+        if let Some(value) = first(record, tags) {
+            entry.set(field, value);
+        }
+    }
+    match (first(record, &["SP"]), first(record, &["EP"])) {
+        (Some(start), Some(end)) if start != end => { entry.set("pages", &format!("{}--{}", start, end)); }
+        (Some(start), _) => { entry.set("pages", start); }
+        _ => {}
+    }
+    if let Some(date) = first(record, &["PY", "Y1", "DA"]).and_then(date) {
+        entry.set("date", &date);
+        entry.set("year", &date[..4]);
+    }
+    if let Some(doi) = entry.get("doi").map(|d| d.trim_start_matches("https://doi.org/").to_string()) {
+        entry.set("doi", &doi);
+    }
+    let keywords = values(record, &["KW"]);
+    if !keywords.is_empty() {
+        entry.set("keywords", &keywords.join(", "));
+    }
+    let key = first(record, &["ID"]).filter(|id| is_key(id)).map(String::from).unwrap_or_else(|| citation_key(&entry));
+    entry.set_key(&key);
+    entry
+}
+
+/**
+The entries for the records of a RIS file. Generated keys that come out
+the same for two records get a letter appended, `knuth1984a`.
+*/
+pub fn from_ris(input: &str) -> Result<Vec<Entry>, RisError> {
+    let mut entries: Vec<Entry> = Vec::new();
+    let mut record: Option<Record> = None;
+    let mut finish = |record: Record| {
+        let mut entry = entry(&record);
+        let key = unique_key(entry.key(), |k| entries.iter().any(|e| e.key() == k));
+        entry.set_key(&key);
+        entries.push(entry);
+    };
+    for (i, line) in input.trim_start_matches('\u{feff}').lines().enumerate() {
+        let error = |message: &str| RisError { line: i + 1, message: String::from(message) };
+        match (tagged(line), record.as_mut()) {
+            (Some(("TY", ty)), None) => record = Some(vec![(String::from("TY"), String::from(ty))]),
+            (Some(("TY", _)), Some(_)) => return Err(error("`TY` before the `ER` of the previous record")),
+            (Some(("ER", _)), Some(_)) => finish(record.take().unwrap_or_default()),
+            (Some((tag, value)), Some(fields)) => fields.push((String::from(tag), String::from(value))),
+            (None, Some(fields)) if !line.trim().is_empty() => {
+                if let Some((_, value)) = fields.last_mut() {
+                    value.push(' ');
+                    value.push_str(line.trim());
+                }
+            }
+            (Some(_), None) => return Err(error("expected `TY` to start a record")),
+            (None, _) => {}
+        }
+    }
+    if let Some(record) = record {
+        finish(record);
+    }
+    Ok(entries)
+}
+
+/** The RIS type for an entry type, the reverse of `entry_type`. */
+pub fn record_type(ty: &BibType) -> &'static str {
+    if let Some(media) = ris_type(ty.name()) {
+        return media;
+    }
+    match ty.name() {
+        "article" => "JOUR",
+        "book" | "mvbook" => "BOOK",
+        "collection" | "mvcollection" | "proceedings" | "mvproceedings" => "EDBOOK",
+        "incollection" | "inbook" | "bookinbook" => "CHAP",
+        "inproceedings" => "CPAPER",
+        "inreference" => "ENCYC",
+        "techreport" => "RPRT",
+        "thesis" | "phdthesis" | "mastersthesis" => "THES",
+        "unpublished" => "UNPB",
+        "online" => "ELEC",
+        "software" => "COMP",
+        "dataset" => "DATA",
+        "patent" => "PAT",
+        "standard" => "STAND",
+        "jurisdiction" => "CASE",
+        "legislation" => "STAT",
+        _ => "GEN",
+    }
+}
+
+/** The tags and values of the record for `entry`, in order. */
+fn record(entry: &Entry) -> Vec<(&'static str, String)> {
+    let mut out = vec![("TY", String::from(record_type(entry.entry_type()))), ("ID", String::from(entry.key()))];
+    for (tag, field) in [("AU", "author"), ("ED", "editor")] {
+        for name in parse_names(entry.get(field).unwrap_or_default()).iter().filter(|n| !n.is_others()) {
+            out.push((tag, decode(&bibtex_name(&name.first, &name.von, &name.last, &name.jr))));
+        }
+    }
+    let number = if matches!(entry.entry_type().name(), "techreport" | "standard") { "M1" } else { "IS" };
+    let fields: [(&str, &[&str]); 17] = [
+        ("TI", &["title"]),
+        ("T2", &["journaltitle", "journal", "booktitle"]),
+        ("T3", &["series"]),
+        ("VL", &["volume"]),
+        (number, &["number"]),
+        ("ET", &["edition"]),
+        ("PB", &["publisher", "institution", "school"]),
+        ("CY", &["location", "address"]),
+        ("SN", &["isbn", "issn"]),
+        ("DO", &["doi"]),
+        ("UR", &["url"]),
+        ("Y2", &["urldate"]),
+        ("AB", &["abstract"]),
+        ("N1", &["note"]),
+        ("LA", &["language"]),
+        ("M3", &["type"]),
+        ("ST", &["shorttitle"]),
+    ];
+    for (tag, names) in fields {
+        if let Some(value) = names.iter().find_map(|f| entry.field_unicode(f)) {
+            out.push((tag, value));
+        }
+    }
+    if let Some(pages) = entry.get("pages") {
+        match pages.split_once('-') {
+            Some((start, end)) => {
+                out.push(("SP", String::from(start.trim())));
+                out.push(("EP", String::from(end.trim_start_matches('-').trim())));
+            }
+            None => out.push(("SP", String::from(pages.trim()))),
+        }
+    }
+    if let Some(date) = entry.get("date") {
+        out.push(("PY", date.split('/').next().unwrap_or_default().replace('-', "/")));
+    } else if let Some(year) = entry.get("year") {
+        match entry.get("month").and_then(|m| parse_month(m, &[])) {
+            Some(month) => out.push(("PY", format!("{}/{:02}", year, month))),
+            None => out.push(("PY", String::from(year))),
+        }
+    }
+    for keyword in entry.field_unicode("keywords").unwrap_or_default().split([',', ';']).map(str::trim).filter(|k| !k.is_empty()) {
+        out.push(("KW", String::from(keyword)));
+    }
+    out
+}
+
+/** A RIS file with a record for each entry, in order. */
+pub fn to_ris(entries: &[Entry]) -> String {
+    let mut out = String::new();
+    for entry in entries {
+        for (tag, value) in record(entry) {
+            out.push_str(&format!("{}  - {}\n", tag, value.replace('\n', " ")));
+        }
+        out.push_str("ER  - \n\n");
+    }
+    out
+}
+
+/** `to_ris` without the fields `policy` makes private. */
+pub fn to_ris_with(entries: &[Entry], policy: &FieldPolicy) -> String {
+    to_ris(&policy.apply_all(entries))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_from_ris() {
+        let entries = from_ris("\u{feff}TY  - JOUR\r\nAU  - Knuth, Donald E.\r\nTI  - Literate programming\r\n\
+            JO  - The Computer Journal\r\nVL  - 27\r\nIS  - 2\r\nSP  - 97\r\nEP  - 111\r\nPY  - 1984/05//\r\n\
+            DO  - 10.1093/comjnl/27.2.97\r\nAB  - A long\r\n  abstract.\r\nKW  - literate\r\nKW  - WEB\r\nER  - \r\n\r\n\
+            TY  - CHAP\nID  - fb-ch2\nA1  - Doe, Jane\nED  - Roe, Richard\nT1  - A chapter\nBT  - A book\nSN  - 978-3-16-148410-0\nER  -\n\
+            TY  - MPCT\nTI  - Vertigo\nPY  - 1958\nER  -\n").unwrap();
+        let keys: Vec<&str> = entries.iter().map(|e| e.key()).collect();
+        assert_eq!(keys, vec!["knuth1984", "fb-ch2", "vertigo1958"]);
+
+        let article = &entries[0];
+        assert_eq!(article.entry_type(), &BibType::Article);
+        assert_eq!((article.get("journal"), article.get("pages")), (Some("The Computer Journal"), Some("97--111")));
+        assert_eq!((article.get("date"), article.get("year")), (Some("1984-05"), Some("1984")));
+        assert_eq!(article.get("abstract"), Some("A long abstract."));
+        assert_eq!(article.get("keywords"), Some("literate, WEB"));
+        assert_eq!(entries[1].get("booktitle"), Some("A book"));
+        assert_eq!((entries[1].get("editor"), entries[1].get("isbn")), (Some("Roe, Richard"), Some("978-3-16-148410-0")));
+        assert_eq!(entries[2].entry_type().name(), "movie");
+
+        assert_eq!(from_ris("AU  - Knuth\n").unwrap_err().line, 1);
+        assert_eq!(from_ris("TY  - JOUR\nTY  - BOOK\n").unwrap_err().line, 2);
+    }
+
+    #[test]
+    fn test_to_ris() {
+        let text = "TY  - JOUR\nID  - knuth84\nAU  - Knuth, Donald E.\nED  - van Leunen, Mary-Claire\nTI  - Literate programming\n\
+            T2  - The Computer Journal\nVL  - 27\nIS  - 2\nDO  - 10.1093/comjnl/27.2.97\nSP  - 97\nEP  - 111\nPY  - 1984/05\n\
+            KW  - literate\nKW  - WEB\nER  - \n\nTY  - MPCT\nID  - vertigo1958\nTI  - Vertigo\nPY  - 1958\nER  - \n\n";
+        let entries = from_ris(text).unwrap();
+        assert_eq!(to_ris(&entries), text);
+
+        let mut e = Entry::new(BibType::Report, "r");
+        e.set("author", "G{\\\"o}del, Kurt and others");
+        e.set("number", "TR-7");
+        assert_eq!(to_ris(&[e]), "TY  - RPRT\nID  - r\nAU  - Gödel, Kurt\nM1  - TR-7\nER  - \n\n");
+    }
+}
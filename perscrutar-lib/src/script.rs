@@ -0,0 +1,253 @@
+/*!
+
+Field-manipulation scripts in Rhai (`script` feature).
+
+A script is run once per entry and changes it through the `entry` variable:
+
+```text
+// arXiv preprints that were published
+if entry.type == "misc" && entry.has("eprint") && entry.has("journal") {
+    entry.set_type("article");
+}
+if entry.get("publisher") == "Springer-Verlag" {
+    entry.set("publisher", "Springer");
+} else if !entry.has("publisher") && entry.type == "book" {
+    entry.set("note", "publisher unknown");
+}
+```
+
+Scripts are [Rhai](https://rhai.rs), with its language and standard
+library of string functions (`to_lower`, `contains`, `replace`, `split`
+and so on). `entry` provides:
+
+- `type` and `key`, `get(field)`, which is `()` for a missing field, and
+  `has(field)`;
+- `set(field, value)`, `remove(field)`, `set_type(type)`, `set_key(key)`.
+
+`set_key` and `set_type` fail on keys and types that a .bib file could
+not hold, such as `a b` or `@@`. Scripts cannot read files or the
+network, expressions nest at most `MAX_DEPTH` deep, and a run that takes
+more than `MAX_OPERATIONS` steps, such as an endless loop, fails.
+
+`ScriptTransform` makes a script usable as a `Transform`.
+
+*/
+
+use std::fmt;
+use rhai::{Dynamic, Engine, EvalAltResult, Position, Scope, AST};
+use crate::bibtex::bibliography::Bibliography;
+use crate::bibtex::data::{BibType, Entry};
+use crate::transform::{Report, Transform};
+
+/** How deep expressions may nest. */
+pub const MAX_DEPTH: usize = 64;
+
+/** How many steps a script may take on one entry. */
+pub const MAX_OPERATIONS: u64 = 1_000_000;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScriptError {
+    /** 1-based line of the error, if it has one. */
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl ScriptError {
+    fn new(position: Position, message: String) -> ScriptError {
+        ScriptError { line: position.line(), message }
+    }
+}
+
+impl From<Box<EvalAltResult>> for ScriptError {
+    fn from(mut e: Box<EvalAltResult>) -> ScriptError {
+        let position = e.take_position();
+        match *e {
+            // from `set_key` and `set_type`, and `throw`
+            EvalAltResult::ErrorRuntime(value, _) => ScriptError::new(position, value.to_string()),
+            e => ScriptError::new(position, e.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "line {}: {}", line, self.message),
+            None => f.write_str(&self.message),
+        }
+    }
+}
+
+impl std::error::Error for ScriptError {}
+
+/** `entry` as scripts see it. */
+#[derive(Debug, Clone)]
+struct ScriptEntry(Entry);
+
+fn invalid(what: &str, value: &str) -> Box<EvalAltResult> {
+    format!("invalid {} `{}`", what, value).into()
+}
+
+fn engine() -> Engine {
+    let mut engine = Engine::new();
+    engine.set_module_resolver(rhai::module_resolvers::DummyModuleResolver::new());
+    engine.set_max_expr_depths(MAX_DEPTH, MAX_DEPTH);
+    engine.set_max_operations(MAX_OPERATIONS);
+    engine.set_max_call_levels(MAX_DEPTH);
+    engine.set_max_string_size(1 << 20);
+    engine.set_max_array_size(1 << 16);
+    engine.set_max_map_size(1 << 16);
+    engine.register_type_with_name::<ScriptEntry>("Entry")
+        .register_get("type", |e: &mut ScriptEntry| String::from(e.0.entry_type().name()))
+        .register_get("key", |e: &mut ScriptEntry| String::from(e.0.key()))
+        .register_fn("get", |e: &mut ScriptEntry, field: &str| e.0.get(field).map_or(Dynamic::UNIT, |v| Dynamic::from(String::from(v))))
+        .register_fn("has", |e: &mut ScriptEntry, field: &str| e.0.has(field))
+        .register_fn("set", |e: &mut ScriptEntry, field: &str, value: &str| {
+            e.0.set(field, value);
+        })
+        .register_fn("remove", |e: &mut ScriptEntry, field: &str| {
+            e.0.remove(field);
+        })
+        .register_fn("set_type", |e: &mut ScriptEntry, itemtype: &str| {
+            if itemtype.is_empty() || !itemtype.chars().all(|c| c.is_ascii_alphabetic() || c == '-' || c == '_') {
+                return Err(invalid("entry type", itemtype));
+            }
+            e.0.set_entry_type(BibType::parse(itemtype));
+            Ok(())
+        })
+        .register_fn("set_key", |e: &mut ScriptEntry, key: &str| {
+            if key.is_empty() || !key.chars().all(|c| c.is_alphanumeric() || "-_:./+'".contains(c)) {
+                return Err(invalid("citation key", key));
+            }
+            e.0.set_key(key);
+            Ok(())
+        });
+    engine
+}
+
+pub struct Script {
+    engine: Engine,
+    ast: AST,
+}
+
+impl fmt::Debug for Script {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Script").field("source", &self.ast.source()).finish()
+    }
+}
+
+impl Script {
+    pub fn parse(source: &str) -> Result<Script, ScriptError> {
+        let engine = engine();
+        let ast = engine.compile(source).map_err(|e| ScriptError::new(e.1, e.0.to_string()))?;
+        Ok(Script { engine, ast })
+    }
+
+    /**
+    Run the script on `entry`. Returns whether the entry changed; on an
+    error the entry is left as it was.
+    */
+    pub fn run(&self, entry: &mut Entry) -> Result<bool, ScriptError> {
+        let mut scope = Scope::new();
+        scope.push("entry", ScriptEntry(entry.clone()));
+        self.engine.run_ast_with_scope(&mut scope, &self.ast)?;
+        let after = scope.get_value::<ScriptEntry>("entry")
+            .ok_or_else(|| ScriptError { line: None, message: String::from("`entry` was replaced") })?.0;
+        let changed = !after.is_identical(entry);
+        *entry = after;
+        Ok(changed)
+    }
+}
+
+/**
+A script run on every entry as a `Transform`. Entries the script fails on
+are left unchanged and reported in the `Report` messages.
+*/
+pub struct ScriptTransform {
+    pub name: String,
+    pub script: Script,
+}
+
+impl Transform for ScriptTransform {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> &str {
+        "User script"
+    }
+
+    fn apply(&self, bibliography: &mut Bibliography) -> Report {
+        let mut messages = Vec::new();
+        let changed = bibliography.visit_mut(|entry| {
+            if let Err(e) = self.script.run(entry) {
+                messages.push(format!("{}: {}", entry.key(), e));
+            }
+        });
+        Report { changed, messages }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    const SCRIPT: &str = r#"
+        // published preprints
+        if entry.type == "misc" && entry.has("eprint") {
+            entry.set_type("article");
+            entry.set("note", "arXiv:" + entry.get("eprint"));
+        } else if entry.has("publisher") && entry.get("publisher").to_lower() == "springer-verlag" {
+            entry.set("publisher", "Springer")
+        }
+        if !entry.has("year") { entry.remove("month") }
+    "#;
+
+    #[test]
+    fn test_run() {
+        let script = Script::parse(SCRIPT).unwrap();
+        let mut e = Entry::new(BibType::Misc, "a");
+        e.set("eprint", "2101.00001");
+        e.set("month", "3");
+        assert!(script.run(&mut e).unwrap());
+        assert_eq!(*e.entry_type(), BibType::Article);
+        assert_eq!(e.get("note"), Some("arXiv:2101.00001"));
+        assert!(!e.has("month"));
+
+        let mut b = Entry::new(BibType::Book, "b");
+        b.set("publisher", "Springer-Verlag");
+        b.set("year", "1990");
+        assert!(script.run(&mut b).unwrap());
+        assert_eq!(b.get("publisher"), Some("Springer"));
+        assert!(!script.run(&mut b).unwrap());
+
+        let missing = Script::parse("if entry.get(\"doi\") == () { entry.set(\"note\", \"no DOI\") }").unwrap();
+        assert!(missing.run(&mut b).unwrap());
+        assert_eq!(b.get("note"), Some("no DOI"));
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(Script::parse("if entry.has(\"x\") {\n entry.set(\"a\", \"b\")\n").unwrap_err().line, Some(3));
+        let script = Script::parse("entry.get(\"title\").frobnicate()").unwrap();
+        let transform = ScriptTransform { name: String::from("t"), script };
+        let mut e = Entry::new(BibType::Misc, "a");
+        e.set("title", "x");
+        let mut bib = Bibliography::from_entries(vec![e]);
+        let messages = transform.apply(&mut bib).messages;
+        assert!(messages[0].starts_with("a: line 1: Function not found: frobnicate"), "{:?}", messages);
+
+        assert!(Script::parse(&format!("{}true{}", "(".repeat(10_000), ")".repeat(10_000))).is_err());
+        let endless = Script::parse("loop { entry.set(\"note\", \"x\") }").unwrap();
+        let mut e = Entry::new(BibType::Misc, "a");
+        assert!(endless.run(&mut e).is_err());
+        assert!(!e.has("note"));
+        assert!(Script::parse("import \"other\" as o;").unwrap().run(&mut e).is_err());
+        for call in ["entry.set_key(\"\")", "entry.set_key(\"a b\")", "entry.set_type(\"@@\")"] {
+            let mut e = Entry::new(BibType::Misc, "a");
+            assert!(Script::parse(call).unwrap().run(&mut e).unwrap_err().message.starts_with("invalid"), "{}", call);
+            assert_eq!((e.key(), e.entry_type()), ("a", &BibType::Misc));
+        }
+    }
+}
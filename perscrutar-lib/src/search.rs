@@ -0,0 +1,242 @@
+/*!
+
+Full-text search over a bibliography.
+
+A `SearchIndex` maps the words of every entry's key, names, titles, venue,
+year, keywords, abstract and identifiers to the keys of the entries using
+them. A query matches the entries containing all of its words, the last
+one as a prefix so that results can be shown while typing:
+
+```text
+knuth literate prog   ->   knuth84
+```
+
+Building the index for a large library is split over several threads.
+Afterwards it is kept up to date entry by entry: `insert` and `remove` for
+single entries, or `update` with the `LibraryEvent`s of a change (from
+`events::diff` or a `Bibliography` journal), which only reindexes the
+entries the events name.
+
+Entries sharing a citation key are indexed as one.
+
+*/
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::num::NonZeroUsize;
+use std::thread;
+use crate::bibtex::data::Entry;
+use crate::bibtex::names::purify;
+use crate::events::LibraryEvent;
+
+/** Fields whose words are indexed, besides the citation key. */
+const SEARCHED: [&str; 16] = [
+    "author", "editor", "title", "subtitle", "booktitle", "journal", "journaltitle", "series",
+    "publisher", "institution", "school", "year", "date", "keywords", "abstract", "doi",
+];
+
+/** Below this many entries per thread, building in parallel is not worth it. */
+const CHUNK: usize = 256;
+
+/**
+The lower-case words of `text`, with TeX markup removed.
+*/
+pub fn words(text: &str) -> Vec<String> {
+    purify(text).to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+/**
+The distinct words `entry` is found by.
+*/
+fn terms(entry: &Entry) -> BTreeSet<String> {
+    let mut out: BTreeSet<String> = words(entry.key()).into_iter().collect();
+    out.insert(entry.key().to_lowercase());
+    for field in SEARCHED {
+        if let Some(value) = entry.get(field) {
+            out.extend(words(value));
+        }
+    }
+    out
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SearchIndex {
+    /** Terms of every indexed key, to unindex it again. */
+    documents: HashMap<String, BTreeSet<String>>,
+    /** Keys using every term. */
+    postings: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl SearchIndex {
+    pub fn new() -> SearchIndex {
+        SearchIndex::default()
+    }
+
+    /**
+    Index `entries` using as many threads as the machine has cores.
+    */
+    pub fn build(entries: &[Entry]) -> SearchIndex {
+        let threads = thread::available_parallelism().map(NonZeroUsize::get).unwrap_or(1);
+        SearchIndex::build_with(entries, threads)
+    }
+
+    /**
+    Index `entries` using at most `threads` threads.
+    */
+    pub fn build_with(entries: &[Entry], threads: usize) -> SearchIndex {
+        let threads = threads.clamp(1, entries.len().div_ceil(CHUNK).max(1));
+        if threads == 1 {
+            let mut index = SearchIndex::new();
+            index.add_all(entries);
+            return index;
+        }
+        let parts: Vec<SearchIndex> = thread::scope(|scope| {
+            let handles: Vec<_> = entries.chunks(entries.len().div_ceil(threads))
+                .map(|chunk| scope.spawn(move || {
+                    let mut part = SearchIndex::new();
+                    part.add_all(chunk);
+                    part
+                }))
+                .collect();
+            handles.into_iter().map(|h| h.join().expect("indexing thread panicked")).collect()
+        });
+        let mut index = SearchIndex::new();
+        for part in parts {
+            index.merge(part);
+        }
+        index
+    }
+
+    fn add_all(&mut self, entries: &[Entry]) {
+        for entry in entries {
+            self.add(entry.key(), terms(entry));
+        }
+    }
+
+    fn add(&mut self, key: &str, terms: BTreeSet<String>) {
+        for term in terms.iter() {
+            self.postings.entry(term.clone()).or_default().insert(String::from(key));
+        }
+        self.documents.entry(String::from(key)).or_default().extend(terms);
+    }
+
+    fn merge(&mut self, other: SearchIndex) {
+        for (key, terms) in other.documents {
+            self.add(&key, terms);
+        }
+    }
+
+    /**
+    Index `entry`, replacing what was indexed under its key.
+    */
+    pub fn insert(&mut self, entry: &Entry) {
+        self.remove(entry.key());
+        self.add(entry.key(), terms(entry));
+    }
+
+    /**
+    Forget the entry with citation key `key`; returns whether it was indexed.
+    */
+    pub fn remove(&mut self, key: &str) -> bool {
+        let Some(terms) = self.documents.remove(key) else { return false };
+        for term in terms.iter() {
+            if let Some(keys) = self.postings.get_mut(term) {
+                keys.remove(key);
+                if keys.is_empty() {
+                    self.postings.remove(term);
+                }
+            }
+        }
+        true
+    }
+
+    /**
+    Bring the index up to date with `events`, reading the current version of
+    added and changed entries from `entries`.
+    */
+    pub fn update(&mut self, events: &[LibraryEvent], entries: &[Entry]) {
+        for event in events {
+            self.remove(&event.key);
+            // a removed entry may have shared its key with one still there
+            for entry in entries.iter().filter(|e| e.key() == event.key) {
+                self.add(entry.key(), terms(entry));
+            }
+        }
+    }
+
+    /**
+    Keys of the entries matching every word of `query`, the last one as a
+    prefix, in key order. An empty query matches nothing.
+    */
+    pub fn search(&self, query: &str) -> Vec<&str> {
+        let words = words(query);
+        let Some((last, rest)) = words.split_last() else { return vec![] };
+        let mut found: BTreeSet<&str> = self.postings.range(last.clone()..)
+            .take_while(|(term, _)| term.starts_with(last.as_str()))
+            .flat_map(|(_, keys)| keys.iter().map(String::as_str))
+            .collect();
+        for word in rest {
+            let keys = self.postings.get(word);
+            found.retain(|k| keys.map(|keys| keys.contains(*k)).unwrap_or(false));
+        }
+        found.into_iter().collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.documents.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.documents.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::bibtex::data::BibType;
+    use crate::events::diff;
+
+    fn entry(key: &str, author: &str, title: &str) -> Entry {
+        let mut e = Entry::new(BibType::Article, key);
+        e.set("author", author);
+        e.set("title", title);
+        e
+    }
+
+    #[test]
+    fn test_search() {
+        let entries: Vec<Entry> = (0..1000).map(|i| entry(&format!("k{}", i), "Someone, A.", &format!("Paper {}", i)))
+            .chain([entry("knuth", "Knuth, Donald E.", "{Literate} Programming"), entry("texbook", "Knuth, Donald", "The {\\TeX}book")])
+            .collect();
+        let index = SearchIndex::build_with(&entries, 4);
+        assert_eq!(index, SearchIndex::build_with(&entries, 1));
+        assert_eq!(index.len(), 1002);
+        assert_eq!(index.search("knuth literate prog"), vec!["knuth"]);
+        assert_eq!(index.search("Knuth"), vec!["knuth", "texbook"]);
+        assert_eq!(index.search("paper 999"), vec!["k999"]);
+        assert_eq!(index.search("TeXb"), vec!["texbook"]);
+        assert!(index.search("literate cobol").is_empty());
+        assert!(index.search("  ").is_empty());
+    }
+
+    #[test]
+    fn test_update() {
+        let old = vec![entry("a", "Knuth, Donald", "Sorting"), entry("b", "Cox, David", "Primes")];
+        let mut index = SearchIndex::build(&old);
+        let mut new = vec![entry("a", "Knuth, Donald", "Searching"), entry("c", "Tate, John", "Primes")];
+        index.update(&diff(&old, &new), &new);
+        assert_eq!(index, SearchIndex::build(&new));
+        assert_eq!(index.search("primes"), vec!["c"]);
+        assert!(index.search("sorting").is_empty());
+
+        new.remove(0);
+        assert!(index.remove("a"));
+        assert!(!index.remove("a"));
+        assert_eq!(index, SearchIndex::build(&new));
+    }
+}
@@ -0,0 +1,292 @@
+/*!
+
+Signed entries, for libraries that many people read but few curate.
+
+A curator signs an entry with their `SigningKey` once they have checked
+it; the Ed25519 signature, with the public key and when it was made, goes
+in the `MetadataStore` sidecar under `signature`:
+
+```json
+"knuth84": {
+  "fingerprint": "doi:10.1093/comjnl/27.2.97",
+  "signature": {"key": "d75a9801...", "signature": "e5564300...", "signer": "ana", "time": "2026-10-16T09:12:44Z"}
+}
+```
+
+Readers check entries against the public keys they trust with
+`MetadataStore::attestation`. Any change to the entry's type, key or field
+values after it was signed makes the signature fail, and so does any
+change to the signer or time recorded with it. What is signed is the
+entry's `canonical` form, so reordering fields, changing the case of
+their names or rewrapping values does not count as a change; the values
+of `VERBATIM_FIELDS` are compared exactly.
+
+Keys are 32 random bytes, written as 64 hexadecimal digits; keep the
+secret key out of the repository. This module is only built with the
+`sign` feature; signatures are made and checked by `ed25519-dalek`, which
+rejects non-canonical encodings and keys of small order.
+
+*/
+
+use std::fmt;
+use std::time::SystemTime;
+use crate::audit::{timestamp, user};
+use crate::bibtex::data::{is_verbatim, Entry};
+use crate::json::JsonValue;
+use crate::metadata::MetadataStore;
+
+const MEMBER: &str = "signature";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SigningError {
+    Io(String),
+    Invalid(String),
+}
+
+impl fmt::Display for SigningError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SigningError::Io(msg) => f.write_str(msg),
+            SigningError::Invalid(msg) => write!(f, "invalid key: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for SigningError {}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn unhex<const N: usize>(text: &str) -> Option<[u8; N]> {
+    let text = text.trim();
+    if text.len() != 2 * N || !text.is_ascii() {
+        return None;
+    }
+    let mut out = [0u8; N];
+    for (i, byte) in out.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&text[2 * i..2 * i + 2], 16).ok()?;
+    }
+    Some(out)
+}
+
+/** `text` preceded by its length in bytes, so that it cannot run into what follows. */
+fn prefixed(text: &str) -> String {
+    format!("{}:{}", text.len(), text)
+}
+
+/**
+What is signed: the type and key, then each field's name and value on a
+line of its own, sorted by name. Every part is preceded by its length in
+bytes, as values of `VERBATIM_FIELDS` may hold line breaks and text that
+looks like another field. Other values have their runs of whitespace made
+single spaces.
+*/
+pub fn canonical(entry: &Entry) -> String {
+    let mut fields: Vec<(&str, String)> = entry.fields().map(|(name, value)| match is_verbatim(name) {
+        true => (name, String::from(value)),
+        false => (name, value.split_whitespace().collect::<Vec<&str>>().join(" ")),
+    }).collect();
+    fields.sort();
+    let mut out = format!("{} {}\n", prefixed(&entry.entry_type().name().to_lowercase()), prefixed(entry.key()));
+    for (name, value) in fields {
+        out.push_str(&format!("{} {}\n", prefixed(name), prefixed(&value)));
+    }
+    out
+}
+
+/**
+What a signature covers: who signed and when, then the entry's `canonical`
+form, all length-prefixed like it.
+*/
+fn message(entry: &Entry, signer: &str, time: &str) -> String {
+    format!("{} {}\n{}", prefixed(signer), prefixed(time), canonical(entry))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PublicKey([u8; 32]);
+
+impl PublicKey {
+    pub fn parse(text: &str) -> Result<PublicKey, SigningError> {
+        unhex(text).map(PublicKey)
+            .ok_or_else(|| SigningError::Invalid(String::from("a public key is 64 hexadecimal digits")))
+    }
+
+    /** Whether `signature` is this key's over `entry` as it is now, signed by `signer` at `time`. */
+    pub fn verify(&self, entry: &Entry, signer: &str, time: &str, signature: &Signature) -> bool {
+        let Ok(key) = ed25519_dalek::VerifyingKey::from_bytes(&self.0) else { return false };
+        let signature = ed25519_dalek::Signature::from_bytes(&signature.0);
+        key.verify_strict(message(entry, signer, time).as_bytes(), &signature).is_ok()
+    }
+}
+
+impl fmt::Display for PublicKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&hex(&self.0))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Signature([u8; 64]);
+
+impl Signature {
+    pub fn parse(text: &str) -> Option<Signature> {
+        unhex(text).map(Signature)
+    }
+}
+
+impl fmt::Display for Signature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&hex(&self.0))
+    }
+}
+
+/** A secret key. It has no `Display`, so that it is not printed by accident. */
+#[derive(Clone, PartialEq, Eq)]
+pub struct SigningKey {
+    seed: [u8; 32],
+}
+
+impl fmt::Debug for SigningKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SigningKey({})", self.public_key())
+    }
+}
+
+impl SigningKey {
+    pub fn from_seed(seed: [u8; 32]) -> SigningKey {
+        SigningKey { seed }
+    }
+
+    /** A new key from the operating system's random number generator. */
+    pub fn generate() -> Result<SigningKey, SigningError> {
+        use rand_core::RngCore;
+
+        let mut seed = [0u8; 32];
+        rand_core::OsRng.try_fill_bytes(&mut seed)
+            .map_err(|e| SigningError::Io(format!("cannot get random numbers: {}", e)))?;
+        Ok(SigningKey { seed })
+    }
+
+    /** A key as `export` writes it; lines starting with `#` are comments. */
+    pub fn parse(text: &str) -> Result<SigningKey, SigningError> {
+        let digits: String = text.lines().filter(|l| !l.trim_start().starts_with('#')).collect();
+        unhex(&digits).map(SigningKey::from_seed)
+            .ok_or_else(|| SigningError::Invalid(String::from("a secret key is 64 hexadecimal digits")))
+    }
+
+    /** The secret key, to be saved somewhere private. */
+    pub fn export(&self) -> String {
+        format!("# perscrutar secret key for {}\n{}\n", self.public_key(), hex(&self.seed))
+    }
+
+    pub fn public_key(&self) -> PublicKey {
+        PublicKey(ed25519_dalek::SigningKey::from_bytes(&self.seed).verifying_key().to_bytes())
+    }
+
+    /** The signature over `entry`, signed by `signer` at `time`. */
+    pub fn sign(&self, entry: &Entry, signer: &str, time: &str) -> Signature {
+        use ed25519_dalek::Signer;
+
+        let key = ed25519_dalek::SigningKey::from_bytes(&self.seed);
+        Signature(key.sign(message(entry, signer, time).as_bytes()).to_bytes())
+    }
+}
+
+/** What the sidecar says about an entry. */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Attestation {
+    /** Signed with a trusted key, and unchanged since. */
+    Valid(PublicKey),
+    Unsigned,
+    /** Signed, but with a key that is not trusted. */
+    Untrusted(PublicKey),
+    /** Changed since it was signed, or with an unreadable signature. */
+    Tampered,
+}
+
+impl MetadataStore {
+    /** Sign `entry` with `key` as the current user, replacing any earlier signature. */
+    pub fn attest(&mut self, entry: &Entry, key: &SigningKey) {
+        let (signer, time) = (user(), timestamp(SystemTime::now()));
+        let signature = JsonValue::object(vec![
+            ("key", JsonValue::str(&key.public_key().to_string())),
+            ("signature", JsonValue::str(&key.sign(entry, &signer, &time).to_string())),
+            ("signer", JsonValue::str(&signer)),
+            ("time", JsonValue::str(&time)),
+        ]);
+        self.set(entry, MEMBER, signature);
+    }
+
+    /** Whether `entry` is signed by one of `trusted` and unchanged since. */
+    pub fn attestation(&self, entry: &Entry, trusted: &[PublicKey]) -> Attestation {
+        let Some(record) = self.get(entry.key(), MEMBER) else { return Attestation::Unsigned };
+        let field = |name: &str| record.get(name).and_then(JsonValue::as_str);
+        let (Some(key), Some(signature)) = (field("key").and_then(|k| PublicKey::parse(k).ok()), field("signature").and_then(Signature::parse)) else {
+            return Attestation::Tampered;
+        };
+        if !trusted.contains(&key) {
+            return Attestation::Untrusted(key);
+        }
+        let (Some(signer), Some(time)) = (field("signer"), field("time")) else { return Attestation::Tampered };
+        match key.verify(entry, signer, time, &signature) {
+            true => Attestation::Valid(key),
+            false => Attestation::Tampered,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::bibtex::parser::parse_entries;
+
+    #[test]
+    fn test_attestation() {
+        let entries = parse_entries("@article{knuth84,\n  title = {Literate\n    Programming},\n  URL = {https://x.org/a  b},\n  year = 1984\n}").unwrap();
+        let mut entry = entries[0].clone();
+        assert_eq!(canonical(&entry), "7:article 7:knuth84\n5:title 20:Literate Programming\n3:url 18:https://x.org/a  b\n4:year 4:1984\n");
+
+        let key = SigningKey::from_seed([7; 32]);
+        assert_eq!(SigningKey::parse(&key.export()), Ok(key.clone()));
+        assert_eq!(PublicKey::parse(&key.public_key().to_string()), Ok(key.public_key()));
+        let other = SigningKey::from_seed([8; 32]).public_key();
+
+        let mut store = MetadataStore::new();
+        assert_eq!(store.attestation(&entry, &[key.public_key()]), Attestation::Unsigned);
+        store.attest(&entry, &key);
+        let store = MetadataStore::parse(&store.to_json().to_string()).unwrap();
+        assert_eq!(store.attestation(&entry, &[other, key.public_key()]), Attestation::Valid(key.public_key()));
+        assert_eq!(store.attestation(&entry, &[other]), Attestation::Untrusted(key.public_key()));
+        entry.reorder(&["year"]);
+        entry.set("title", "Literate Programming");
+        assert_eq!(store.attestation(&entry, &[key.public_key()]), Attestation::Valid(key.public_key()));
+        entry.set("year", "1985");
+        assert_eq!(store.attestation(&entry, &[key.public_key()]), Attestation::Tampered);
+        entry.set("year", "1984");
+
+        // the signer and time are signed too
+        let record = store.get("knuth84", MEMBER).unwrap().clone();
+        let field = |name: &str| JsonValue::str(record.get(name).and_then(JsonValue::as_str).unwrap());
+        let forged = JsonValue::object(vec![("key", field("key")), ("signature", field("signature")),
+                                            ("signer", JsonValue::str("mallory")), ("time", field("time"))]);
+        let mut store = store.clone();
+        store.set(&entry, MEMBER, forged);
+        assert_eq!(store.attestation(&entry, &[key.public_key()]), Attestation::Tampered);
+
+        // RFC 8032, test 1
+        let rfc = SigningKey::parse("9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60").unwrap();
+        assert_eq!(rfc.public_key().to_string(), "d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a");
+        let generated = SigningKey::generate().unwrap();
+        assert_ne!(generated, SigningKey::generate().unwrap());
+        assert_eq!(SigningKey::parse(&generated.export()).unwrap(), generated);
+
+        // a line break in a verbatim value must not pass for another field
+        let split = parse_entries("@misc{a, url = {https://x.org}, year = 1984}\n@misc{a, url = {https://x.org\nyear = 1984}}").unwrap();
+        assert_ne!(canonical(&split[0]), canonical(&split[1]));
+        let mut store = MetadataStore::new();
+        store.attest(&split[0], &key);
+        assert_eq!(store.attestation(&split[1], &[key.public_key()]), Attestation::Tampered);
+    }
+}
@@ -0,0 +1,627 @@
+/*!
+
+Software and dataset citations: CITATION.cff and codemeta.json.
+
+Research software describes how it wants to be cited in a `CITATION.cff`
+file (<https://citation-file-format.github.io>) or in CodeMeta's
+`codemeta.json` (<https://codemeta.github.io>). `from_cff` and
+`from_codemeta` read either into a biblatex `@software` or `@dataset`
+entry, with the fields of the biblatex-software package:
+
+```text
+@software{virtanen2020,
+  author = {Virtanen, Pauli and {SciPy Contributors}},
+  title = {SciPy},
+  version = {1.5.2},
+  date = {2020-08-21},
+  year = {2020},
+  doi = {10.5281/zenodo.4004276},
+  swhid = {swh:1:rel:...},
+  repository = {https://github.com/scipy/scipy},
+  license = {BSD-3-Clause}
+}
+```
+
+Organisations are kept whole in braces. A CFF `preferred-citation`, which
+asks to cite a paper instead of the software, is not followed; the entry
+always describes the software itself.
+
+`to_cff` goes the other way and writes a `CITATION.cff` for an entry.
+
+CFF is YAML. Only the part of YAML such files use is read: block mappings
+and sequences, plain, quoted and block (`|`, `>`) scalars, flow sequences
+of scalars and comments.
+
+*/
+
+use std::fmt;
+use crate::bibtex::data::{BibType, Entry};
+use crate::bibtex::keys::citation_key;
+use crate::bibtex::names::bibtex_name;
+use crate::bibtex::policy::FieldPolicy;
+use crate::json::{self, JsonError, JsonValue};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SoftwareError {
+    /** The CFF file is not YAML as far as it is understood here. */
+    Yaml { line: usize, message: String },
+    Json(JsonError),
+    /** A field the format requires is missing. */
+    Missing(&'static str),
+}
+
+impl fmt::Display for SoftwareError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SoftwareError::Yaml { line, message } => write!(f, "line {}: {}", line, message),
+            SoftwareError::Json(e) => write!(f, "{}", e),
+            SoftwareError::Missing(field) => write!(f, "no {}", field),
+        }
+    }
+}
+
+impl std::error::Error for SoftwareError {}
+
+impl From<JsonError> for SoftwareError {
+    fn from(e: JsonError) -> Self {
+        SoftwareError::Json(e)
+    }
+}
+
+/** A line of YAML without its comment: number, indentation and text. */
+#[derive(Debug, Clone)]
+struct Line {
+    number: usize,
+    indent: usize,
+    text: String,
+}
+
+fn yaml_error(line: usize, message: &str) -> SoftwareError {
+    SoftwareError::Yaml { line, message: String::from(message) }
+}
+
+/**
+`text` up to a comment: a `#` at the start or after a space, outside quotes.
+*/
+fn strip_comment(text: &str) -> &str {
+    let mut quote = None;
+    let mut previous = ' ';
+    let mut chars = text.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match (quote, c) {
+            (None, '#') if previous == ' ' => return text[..i].trim_end(),
+            (None, '"' | '\'') if previous == ' ' || previous == '[' || previous == '-' => quote = Some(c),
+            (Some('"'), '\\') => {
+                chars.next();
+            }
+            (Some(q), c) if c == q => quote = None,
+            _ => {}
+        }
+        previous = c;
+    }
+    text.trim_end()
+}
+
+/**
+Position of the `:` separating a mapping key from its value, if `text`
+is a `key: value` pair.
+*/
+fn key_end(text: &str) -> Option<usize> {
+    let body = match text.chars().next() {
+        Some(q @ ('"' | '\'')) => text[1..].find(q)? + 2,
+        _ => 0,
+    };
+    text[body..].match_indices(':')
+        .map(|(i, _)| body + i)
+        .find(|i| text[i + 1..].is_empty() || text[i + 1..].starts_with(' '))
+}
+
+fn unquote(text: &str, line: usize) -> Result<String, SoftwareError> {
+    let text = text.trim();
+    if text.starts_with('"') {
+        // a double-quoted YAML scalar uses JSON's escapes
+        return match json::parse(text) {
+            Ok(JsonValue::Str(s)) => Ok(s),
+            _ => Err(yaml_error(line, "invalid double-quoted string")),
+        };
+    }
+    if let Some(inner) = text.strip_prefix('\'') {
+        let inner = inner.strip_suffix('\'').ok_or_else(|| yaml_error(line, "unterminated single-quoted string"))?;
+        return Ok(inner.replace("''", "'"));
+    }
+    Ok(String::from(text))
+}
+
+fn scalar(text: &str, line: usize) -> Result<JsonValue, SoftwareError> {
+    let text = text.trim();
+    if let Some(inner) = text.strip_prefix('[') {
+        let inner = inner.strip_suffix(']').ok_or_else(|| yaml_error(line, "unterminated flow sequence"))?;
+        return inner.split(',')
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(|s| unquote(s, line).map(JsonValue::Str))
+            .collect::<Result<Vec<_>, _>>()
+            .map(JsonValue::Array);
+    }
+    match text {
+        "" | "~" | "null" => Ok(JsonValue::Null),
+        _ => unquote(text, line).map(JsonValue::Str),
+    }
+}
+
+struct Yaml {
+    lines: Vec<Line>,
+    /** The lines as written, for block scalars. */
+    raw: Vec<String>,
+    pos: usize,
+}
+
+impl Yaml {
+    fn new(text: &str) -> Result<Yaml, SoftwareError> {
+        let raw: Vec<String> = text.lines().map(String::from).collect();
+        let mut lines = Vec::new();
+        for (i, l) in raw.iter().enumerate() {
+            let content = strip_comment(l);
+            let text = content.trim_start();
+            if text.is_empty() || text == "---" || text == "..." {
+                continue;
+            }
+            let indent = &content[..content.len() - text.len()];
+            if indent.contains('\t') {
+                return Err(yaml_error(i + 1, "tabs cannot indent YAML"));
+            }
+            lines.push(Line { number: i + 1, indent: indent.len(), text: String::from(text) });
+        }
+        Ok(Yaml { lines, raw, pos: 0 })
+    }
+
+    fn peek(&self) -> Option<&Line> {
+        self.lines.get(self.pos)
+    }
+
+    fn is_item(text: &str) -> bool {
+        text == "-" || text.starts_with("- ")
+    }
+
+    fn node(&mut self, indent: usize) -> Result<JsonValue, SoftwareError> {
+        match self.peek() {
+            Some(l) if Yaml::is_item(&l.text) => self.sequence(indent),
+            Some(_) => self.mapping(indent),
+            None => Ok(JsonValue::Null),
+        }
+    }
+
+    fn sequence(&mut self, indent: usize) -> Result<JsonValue, SoftwareError> {
+        let mut items = Vec::new();
+        while let Some(l) = self.peek().filter(|l| l.indent == indent && Yaml::is_item(&l.text)).cloned() {
+            let rest = l.text[1..].trim_start();
+            if rest.is_empty() {
+                self.pos += 1;
+                items.push(match self.peek() {
+                    Some(next) if next.indent > indent => {
+                        let child = next.indent;
+                        self.node(child)?
+                    }
+                    _ => JsonValue::Null,
+                });
+            } else {
+                // `- key: value` starts a mapping indented to `key`
+                let child = indent + (l.text.len() - rest.len());
+                self.lines[self.pos] = Line { number: l.number, indent: child, text: String::from(rest) };
+                items.push(if key_end(rest).is_some() || Yaml::is_item(rest) { self.node(child)? } else { self.value(child, rest, l.number)? });
+            }
+        }
+        Ok(JsonValue::Array(items))
+    }
+
+    fn mapping(&mut self, indent: usize) -> Result<JsonValue, SoftwareError> {
+        let mut members = Vec::new();
+        while let Some(l) = self.peek().filter(|l| l.indent == indent).cloned() {
+            if Yaml::is_item(&l.text) {
+                break;
+            }
+            let end = key_end(&l.text).ok_or_else(|| yaml_error(l.number, "expected `key: value`"))?;
+            let key = unquote(&l.text[..end], l.number)?;
+            let rest = l.text[end + 1..].trim();
+            let value = if rest.is_empty() {
+                self.pos += 1;
+                match self.peek() {
+                    Some(next) if next.indent > indent || (next.indent == indent && Yaml::is_item(&next.text)) => {
+                        let child = next.indent;
+                        self.node(child)?
+                    }
+                    _ => JsonValue::Null,
+                }
+            } else {
+                self.value(indent, rest, l.number)?
+            };
+            members.push((key, value));
+        }
+        match self.peek() {
+            Some(l) if l.indent > indent => Err(yaml_error(l.number, "unexpected indentation")),
+            _ => Ok(JsonValue::Object(members)),
+        }
+    }
+
+    /**
+    The scalar `text` on the current line, continued by any lines indented
+    deeper than `indent`, or the block scalar it introduces.
+    */
+    fn value(&mut self, indent: usize, text: &str, number: usize) -> Result<JsonValue, SoftwareError> {
+        self.pos += 1;
+        let mut continued = Vec::new();
+        while let Some(l) = self.peek().filter(|l| l.indent > indent) {
+            continued.push(l.number);
+            self.pos += 1;
+        }
+        if let Some(style) = text.chars().next().filter(|c| *c == '|' || *c == '>') {
+            let (first, last) = match (continued.first(), continued.last()) {
+                (Some(f), Some(l)) => (*f, *l),
+                _ => return Ok(JsonValue::Str(String::new())),
+            };
+            // the first line sets the indentation of the block; literal blocks
+            // keep what their lines have beyond it, and blank lines inside a
+            // folded block are kept as paragraph breaks
+            let raw = &self.raw[first - 1..last];
+            let margin = raw[0].len() - raw[0].trim_start().len();
+            let text = if style == '|' {
+                raw.iter().map(|l| l.get(margin..).unwrap_or("").trim_end()).collect::<Vec<_>>().join("\n")
+            } else {
+                let block: Vec<&str> = raw.iter().map(|l| l.trim()).collect();
+                block.split(|l| l.is_empty()).map(|p| p.join(" ")).collect::<Vec<_>>().join("\n")
+            };
+            return Ok(JsonValue::Str(text));
+        }
+        let mut text = String::from(text);
+        for n in continued {
+            text.push(' ');
+            text.push_str(self.raw[n - 1].trim());
+        }
+        scalar(&text, number)
+    }
+}
+
+/**
+Read YAML as far as CFF files need it, into the JSON data model; every
+scalar is a string.
+*/
+pub fn parse_yaml(text: &str) -> Result<JsonValue, SoftwareError> {
+    let mut yaml = Yaml::new(text)?;
+    let indent = yaml.peek().map(|l| l.indent).unwrap_or(0);
+    let value = yaml.node(indent)?;
+    match yaml.peek() {
+        Some(l) => Err(yaml_error(l.number, "unexpected indentation")),
+        None => Ok(value),
+    }
+}
+
+/** A string or number member, trimmed, if not empty. */
+fn text(value: &JsonValue, name: &str) -> Option<String> {
+    let v = value.get(name)?;
+    let s = match v {
+        JsonValue::Num(_) => v.to_string(),
+        _ => String::from(v.as_str()?),
+    };
+    Some(String::from(s.trim())).filter(|s| !s.is_empty())
+}
+
+/** `text` in lower case, for comparing names of types. */
+fn text_of(value: &JsonValue, name: &str) -> Option<String> {
+    text(value, name).map(|t| t.to_lowercase())
+}
+
+/** A member that may be one value or a list of them, as a list. */
+fn list<'a>(value: &'a JsonValue, name: &str) -> &'a [JsonValue] {
+    match value.get(name) {
+        Some(JsonValue::Array(items)) => items,
+        Some(v) => std::slice::from_ref(v),
+        None => &[],
+    }
+}
+
+fn set_date(entry: &mut Entry, date: &str) {
+    entry.set("date", date);
+    if let Some(year) = date.get(..4).filter(|y| y.chars().all(|c| c.is_ascii_digit())) {
+        entry.set("year", year);
+    }
+}
+
+fn finish(mut entry: Entry) -> Entry {
+    let key = citation_key(&entry);
+    entry.set_key(&key);
+    entry
+}
+
+/**
+An entry for the software or dataset a CITATION.cff file describes.
+*/
+pub fn from_cff(input: &str) -> Result<Entry, SoftwareError> {
+    let cff = parse_yaml(input)?;
+    let itemtype = match text_of(&cff, "type").as_deref() {
+        Some("dataset") => "dataset",
+        _ => "software",
+    };
+    let mut entry = Entry::new(BibType::parse(itemtype), "");
+    let authors: Vec<String> = list(&cff, "authors").iter().filter_map(|a| {
+        let part = |name: &str| text(a, name).unwrap_or_default();
+        match (text(a, "family-names"), text(a, "name")) {
+            (Some(last), _) => Some(bibtex_name(&part("given-names"), &part("name-particle"), &last, &part("name-suffix"))),
+            (None, Some(name)) => Some(format!("{{{}}}", name)),
+            (None, None) => text(a, "given-names"),
+        }
+    }).collect();
+    if !authors.is_empty() {
+        entry.set("author", &authors.join(" and "));
+    }
+    entry.set("title", &text(&cff, "title").ok_or(SoftwareError::Missing("title"))?);
+    if let Some(version) = text(&cff, "version") {
+        entry.set("version", &version);
+    }
+    if let Some(date) = text(&cff, "date-released") {
+        set_date(&mut entry, &date);
+    }
+    if let Some(doi) = text(&cff, "doi") {
+        entry.set("doi", &doi);
+    }
+    for identifier in list(&cff, "identifiers") {
+        match (text(identifier, "type").as_deref(), text(identifier, "value")) {
+            (Some("doi"), Some(v)) if !entry.has("doi") => { entry.set("doi", &v); }
+            (Some("swh"), Some(v)) if !entry.has("swhid") => { entry.set("swhid", &v); }
+            _ => {}
+        }
+    }
+    for (from, to) in [("repository-code", "repository"), ("url", "url"), ("license", "license"), ("abstract", "abstract")] {
+        if let Some(v) = text(&cff, from) {
+            entry.set(to, &v);
+        }
+    }
+    let keywords: Vec<&str> = list(&cff, "keywords").iter().filter_map(|k| k.as_str()).collect();
+    if !keywords.is_empty() {
+        entry.set("keywords", &keywords.join(", "));
+    }
+    Ok(finish(entry))
+}
+
+/**
+An entry for the software or dataset a codemeta.json file describes.
+*/
+pub fn from_codemeta(input: &str) -> Result<Entry, SoftwareError> {
+    let meta = json::parse(input)?;
+    let itemtype = match text_of(&meta, "@type").as_deref() {
+        Some("dataset" | "schema:dataset") => "dataset",
+        _ => "software",
+    };
+    let mut entry = Entry::new(BibType::parse(itemtype), "");
+    let authors: Vec<String> = list(&meta, "author").iter().filter_map(|a| {
+        let organisation = text_of(a, "@type").map(|t| t.ends_with("organization")).unwrap_or(false);
+        match (text(a, "familyName"), text(a, "name")) {
+            (Some(last), _) if !organisation => Some(bibtex_name(&text(a, "givenName").unwrap_or_default(), "", &last, "")),
+            (_, Some(name)) if organisation => Some(format!("{{{}}}", name)),
+            (_, name) => name,
+        }
+    }).collect();
+    if !authors.is_empty() {
+        entry.set("author", &authors.join(" and "));
+    }
+    entry.set("title", &text(&meta, "name").ok_or(SoftwareError::Missing("name"))?);
+    if let Some(version) = text(&meta, "version").or_else(|| text(&meta, "softwareVersion")) {
+        entry.set("version", &version);
+    }
+    if let Some(date) = text(&meta, "datePublished").or_else(|| text(&meta, "dateModified")) {
+        set_date(&mut entry, &date);
+    }
+    for identifier in list(&meta, "identifier").iter().filter_map(|i| i.as_str().or_else(|| i.get("value")?.as_str())) {
+        let identifier = identifier.trim();
+        let doi = identifier.strip_prefix("https://doi.org/").or_else(|| identifier.strip_prefix("doi:"))
+            .or_else(|| identifier.starts_with("10.").then_some(identifier));
+        match doi {
+            Some(doi) if !entry.has("doi") => { entry.set("doi", doi); }
+            _ if identifier.starts_with("swh:") && !entry.has("swhid") => { entry.set("swhid", identifier); }
+            _ => {}
+        }
+    }
+    if let Some(repository) = text(&meta, "codeRepository") {
+        entry.set("repository", &repository);
+    }
+    if let Some(url) = text(&meta, "url") {
+        entry.set("url", &url);
+    }
+    let license = list(&meta, "license").first()
+        .and_then(|l| l.as_str().map(String::from).or_else(|| text(l, "identifier").or_else(|| text(l, "name"))));
+    if let Some(license) = license {
+        // SPDX licenses are often given as their URL
+        entry.set("license", license.trim_start_matches("https://spdx.org/licenses/").trim_start_matches("http://spdx.org/licenses/"));
+    }
+    if let Some(description) = text(&meta, "description") {
+        entry.set("abstract", &description);
+    }
+    let keywords: Vec<String> = match meta.get("keywords") {
+        Some(JsonValue::Str(k)) => k.split(',').map(|k| String::from(k.trim())).filter(|k| !k.is_empty()).collect(),
+        _ => list(&meta, "keywords").iter().filter_map(|k| k.as_str()).map(String::from).collect(),
+    };
+    if !keywords.is_empty() {
+        entry.set("keywords", &keywords.join(", "));
+    }
+    Ok(finish(entry))
+}
+
+/** Field text without TeX's grouping braces and ties. */
+fn plain(value: &str) -> String {
+    value.replace(['{', '}'], "").replace('~', " ")
+}
+
+/** A YAML double-quoted scalar. */
+fn quoted(value: &str) -> String {
+    JsonValue::Str(plain(value)).to_string()
+}
+
+/**
+A CITATION.cff file for `entry`, which needs a title and authors, as CFF
+requires them.
+*/
+pub fn to_cff(entry: &Entry) -> Result<String, SoftwareError> {
+    let title = entry.get("title").ok_or(SoftwareError::Missing("title"))?;
+    let names = entry.authors();
+    if names.is_empty() {
+        return Err(SoftwareError::Missing("author"));
+    }
+    let dataset = entry.entry_type().name() == "dataset";
+    let mut out = String::from("cff-version: 1.2.0\n");
+    out.push_str(&format!("message: \"If you use this {}, please cite it as below.\"\n", if dataset { "dataset" } else { "software" }));
+    if dataset {
+        out.push_str("type: dataset\n");
+    }
+    out.push_str(&format!("title: {}\n", quoted(title)));
+    out.push_str("authors:\n");
+    for name in names.iter().filter(|n| !n.is_others()) {
+        if name.first.is_empty() && name.von.is_empty() && name.last.starts_with('{') {
+            out.push_str(&format!("  - name: {}\n", quoted(&name.last)));
+            continue;
+        }
+        out.push_str(&format!("  - family-names: {}\n", quoted(&name.last)));
+        for (key, part) in [("given-names", &name.first), ("name-particle", &name.von), ("name-suffix", &name.jr)] {
+            if !part.is_empty() {
+                out.push_str(&format!("    {}: {}\n", key, quoted(part)));
+            }
+        }
+    }
+    let field = |out: &mut String, key: &str, name: &str| {
+        if let Some(v) = entry.get(name).filter(|v| !v.trim().is_empty()) {
+            out.push_str(&format!("{}: {}\n", key, quoted(v.trim())));
+        }
+    };
+    field(&mut out, "version", "version");
+    field(&mut out, "doi", "doi");
+    // CFF dates are full dates
+    if let Some(date) = entry.get("date").filter(|d| d.len() == 10 && d.as_bytes()[4] == b'-' && d.as_bytes()[7] == b'-') {
+        out.push_str(&format!("date-released: {}\n", quoted(date)));
+    }
+    field(&mut out, "repository-code", "repository");
+    field(&mut out, "url", "url");
+    field(&mut out, "license", "license");
+    field(&mut out, "abstract", "abstract");
+    let keywords: Vec<&str> = entry.get("keywords").unwrap_or("").split([',', ';']).map(str::trim).filter(|k| !k.is_empty()).collect();
+    if !keywords.is_empty() {
+        out.push_str("keywords:\n");
+        for keyword in keywords {
+            out.push_str(&format!("  - {}\n", quoted(keyword)));
+        }
+    }
+    if let Some(swhid) = entry.get("swhid") {
+        out.push_str(&format!("identifiers:\n  - type: swh\n    value: {}\n", quoted(swhid.trim())));
+    }
+    Ok(out)
+}
+
+/** `to_cff` without the fields `policy` makes private. */
+pub fn to_cff_with(entry: &Entry, policy: &FieldPolicy) -> Result<String, SoftwareError> {
+    to_cff(&policy.apply(entry))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    const CFF: &str = r#"# This CITATION.cff file was generated with cffinit.
+cff-version: 1.2.0
+message: If you use this software, please cite it as below.
+type: software
+title: "SciPy: Open Source Scientific Tools"
+authors:
+- family-names: Virtanen
+  given-names: Pauli
+- given-names: Ralf
+  name-particle: van der
+  family-names: Walt
+  name-suffix: Jr.
+  affiliation: 'Berkeley''s Institute'
+- name: "SciPy Contributors"
+version: 1.5.2
+date-released: 2020-08-21
+identifiers:
+  - type: doi
+    value: 10.5281/zenodo.4004276
+  - type: swh
+    value: "swh:1:rel:cf2c2d7ba6a1b5e4a2b0bcf4b2e6d3e4a9c1b2d3"
+repository-code: "https://github.com/scipy/scipy"
+license: BSD-3-Clause
+keywords: [python, "scientific computing"]
+abstract: >
+  Fundamental algorithms
+  for scientific computing.
+
+  In Python.
+preferred-citation:
+  type: article
+  title: "SciPy 1.0"
+"#;
+
+    #[test]
+    fn test_parse_yaml() {
+        let v = parse_yaml(CFF).unwrap();
+        assert_eq!(v.get("cff-version").and_then(|v| v.as_str()), Some("1.2.0"));
+        assert_eq!(v.path(&["preferred-citation", "title"]).and_then(|v| v.as_str()), Some("SciPy 1.0"));
+        let authors = v.get("authors").and_then(|a| a.as_array()).unwrap();
+        assert_eq!(authors.len(), 3);
+        assert_eq!(authors[1].get("affiliation").and_then(|v| v.as_str()), Some("Berkeley's Institute"));
+        assert_eq!(v.get("abstract").and_then(|v| v.as_str()), Some("Fundamental algorithms for scientific computing.\nIn Python."));
+        assert_eq!(parse_yaml("a:\n  b: 1\n c: 2\n").unwrap_err(), yaml_error(3, "unexpected indentation"));
+        assert!(parse_yaml("title: \"open\n").is_err());
+        let literal = parse_yaml("abstract: |\n  Usage:\n\n    perscrutar lint\n  Done.\nname: x\n").unwrap();
+        assert_eq!(literal.get("abstract").and_then(|v| v.as_str()), Some("Usage:\n\n  perscrutar lint\nDone."));
+    }
+
+    #[test]
+    fn test_cff() {
+        let e = from_cff(CFF).unwrap();
+        assert_eq!(e.key(), "virtanen2020");
+        assert_eq!(e.entry_type().name(), "software");
+        assert_eq!(e.get("author"), Some("Virtanen, Pauli and van der Walt, Jr., Ralf and {SciPy Contributors}"));
+        assert_eq!(e.get("title"), Some("SciPy: Open Source Scientific Tools"));
+        assert_eq!(e.get("date"), Some("2020-08-21"));
+        assert_eq!(e.get("doi"), Some("10.5281/zenodo.4004276"));
+        assert!(e.get("swhid").unwrap().starts_with("swh:1:rel:"));
+        assert_eq!(e.get("keywords"), Some("python, scientific computing"));
+
+        let written = to_cff(&e).unwrap();
+        assert!(written.contains("  - family-names: \"Walt\"\n    given-names: \"Ralf\"\n    name-particle: \"van der\"\n"));
+        assert!(written.contains("  - name: \"SciPy Contributors\"\n"));
+        let again = from_cff(&written).unwrap();
+        assert_eq!(again, e);
+        assert_eq!(to_cff(&Entry::new(BibType::parse("software"), "x")), Err(SoftwareError::Missing("title")));
+        let e = from_cff("title: x\ndate-released: \"1éé\"\n").unwrap();
+        assert_eq!((e.get("date"), e.get("year")), (Some("1éé"), None));
+        assert_eq!(from_cff("title: x\ndate-released: 2024-01-02\n").unwrap().get("year"), Some("2024"));
+    }
+
+    #[test]
+    fn test_codemeta() {
+        let meta = r#"{
+  "@context": "https://doi.org/10.5063/schema/codemeta-2.0",
+  "@type": "SoftwareSourceCode",
+  "name": "perscrutar",
+  "version": 0.1,
+  "author": [
+    {"@type": "Person", "givenName": "Ada", "familyName": "Lovelace"},
+    {"@type": "Organization", "name": "Analytical Engines Ltd"}
+  ],
+  "identifier": ["https://doi.org/10.1234/abc", "swh:1:dir:0123"],
+  "codeRepository": "https://github.com/diagprov/perscrutar",
+  "license": "https://spdx.org/licenses/MIT",
+  "datePublished": "2023-05-01",
+  "keywords": "bibtex, citations"
+}"#;
+        let e = from_codemeta(meta).unwrap();
+        assert_eq!(e.key(), "lovelace2023");
+        assert_eq!(e.get("author"), Some("Lovelace, Ada and {Analytical Engines Ltd}"));
+        assert_eq!(e.get("version"), Some("0.1"));
+        assert_eq!(e.get("doi"), Some("10.1234/abc"));
+        assert_eq!(e.get("swhid"), Some("swh:1:dir:0123"));
+        assert_eq!(e.get("license"), Some("MIT"));
+        assert_eq!(e.get("keywords"), Some("bibtex, citations"));
+        assert!(matches!(from_codemeta("{\"@type\": \"Dataset\"}"), Err(SoftwareError::Missing("name"))));
+        let e = from_codemeta("{\"name\": \"x\", \"datePublished\": \"1éé\"}").unwrap();
+        assert_eq!((e.get("date"), e.get("year")), (Some("1éé"), None));
+    }
+}
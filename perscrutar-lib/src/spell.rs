@@ -0,0 +1,550 @@
+/*!
+
+Spell checking of free-text fields.
+
+Dictionaries are read in Hunspell format: a `.dic` word list whose words
+may carry affix flags (`walk/DSG`), and optionally the matching `.aff` file
+whose `PFX`/`SFX` rules are expanded when the dictionary is loaded. Flags
+are single characters, pairs (`FLAG long`) or numbers (`FLAG num`), or
+numbered sets given by `AF`. Affixes combine as their cross-product
+settings allow, and an affix's continuation class (`SFX A 0 able/S .`)
+lets the affixed word take further affixes. Words and affixes flagged
+`NEEDAFFIX`, `FORBIDDENWORD` or `ONLYINCOMPOUND` are not accepted on their
+own. Other `.aff` directives, such as those for compounds or suggestions,
+are ignored. A personal word list (one word per line) can be added on top.
+
+Only prose is checked. TeX commands, inline and display math, URLs and
+words that look like acronyms or identifiers (`GPU`, `LaTeX`, `x86`) are
+skipped, and capitalised words are accepted if their lower-case form is
+known.
+
+*/
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use crate::bibtex::data::Entry;
+use crate::lint::{Diagnostic, Severity};
+
+/** Fields whose values are prose worth checking. */
+pub const SPELL_FIELDS: [&str; 5] = ["title", "booktitle", "abstract", "note", "annote"];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum CondPart {
+    Any,
+    Char(char),
+    Class(Vec<char>, bool),
+}
+
+impl CondPart {
+    fn matches(&self, c: char) -> bool {
+        match self {
+            CondPart::Any => true,
+            CondPart::Char(x) => *x == c,
+            CondPart::Class(set, negated) => set.contains(&c) != *negated,
+        }
+    }
+}
+
+fn parse_condition(cond: &str) -> Vec<CondPart> {
+    if cond == "." {
+        return vec![];
+    }
+    let mut parts = Vec::new();
+    let mut chars = cond.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '.' => parts.push(CondPart::Any),
+            '[' => {
+                let mut set = Vec::new();
+                let mut negated = false;
+                for d in chars.by_ref() {
+                    match d {
+                        ']' => break,
+                        '^' if set.is_empty() && !negated => negated = true,
+                        _ => set.push(d),
+                    }
+                }
+                parts.push(CondPart::Class(set, negated));
+            }
+            _ => parts.push(CondPart::Char(c)),
+        }
+    }
+    parts
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AffixError {
+    /** 1-based line of the `.aff` file. */
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AffixError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for AffixError {}
+
+/** How flags are written, from the `FLAG` directive. */
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum FlagType {
+    /** One character per flag, the default and `FLAG UTF-8`. */
+    #[default]
+    Char,
+    /** Two characters per flag, `FLAG long`. */
+    Long,
+    /** Decimal numbers separated by commas, `FLAG num`. */
+    Num,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct AffixRule {
+    strip: String,
+    add: String,
+    condition: Vec<CondPart>,
+    /** Whether the rule combines with affixes on the other side (`Y` in its header). */
+    cross: bool,
+    /** The continuation class: flags of further affixes the affixed word takes. */
+    continuation: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct Affixes {
+    flag_type: FlagType,
+    /** Flag sets of `AF`, which words and rules may give by number instead. */
+    aliases: Vec<Vec<String>>,
+    /** Flags of words and affixes that are not words on their own. */
+    not_alone: HashSet<String>,
+    prefixes: HashMap<String, Vec<AffixRule>>,
+    suffixes: HashMap<String, Vec<AffixRule>>,
+}
+
+impl Affixes {
+    fn split(&self, s: &str) -> Vec<String> {
+        match self.flag_type {
+            FlagType::Char => s.chars().map(String::from).collect(),
+            FlagType::Long => s.chars().collect::<Vec<char>>().chunks(2).map(|c| c.iter().collect()).collect(),
+            FlagType::Num => s.split(',').map(str::trim).filter(|f| !f.is_empty()).map(String::from).collect(),
+        }
+    }
+
+    /** The flags `s` stands for: an `AF` number if there are aliases, else flags as `FLAG` says. */
+    fn flags(&self, s: &str) -> Vec<String> {
+        match s.parse::<usize>() {
+            Ok(n) if !self.aliases.is_empty() => self.aliases.get(n.wrapping_sub(1)).cloned().unwrap_or_default(),
+            _ => self.split(s),
+        }
+    }
+
+    fn is_alone(&self, flags: &[String]) -> bool {
+        !flags.iter().any(|f| self.not_alone.contains(f))
+    }
+}
+
+/**
+Read the `FLAG`, `AF` and `PFX`/`SFX` directives, and those marking words
+that are not words on their own (`NEEDAFFIX`, `FORBIDDENWORD`,
+`ONLYINCOMPOUND`). The first line of a `PFX` or `SFX` flag is its header,
+`SFX D Y 4`, whose `Y` lets its rules combine with affixes on the other
+side; the rules follow, `SFX D y ied/XY [^aeiou]y`, with an optional
+continuation class after the `/`.
+*/
+fn parse_affixes(aff: &str) -> Result<Affixes, AffixError> {
+    let mut affixes = Affixes::default();
+    let error = |line: usize, message: String| AffixError { line: line + 1, message };
+    // the flag type and aliases first, as they say how flags elsewhere are read;
+    // the first `AF` line is the number of aliases
+    let mut af_count = false;
+    for (i, line) in aff.lines().enumerate() {
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        match parts.as_slice() {
+            ["FLAG", kind, ..] => affixes.flag_type = match *kind {
+                "UTF-8" => FlagType::Char,
+                "long" => FlagType::Long,
+                "num" => FlagType::Num,
+                _ => return Err(error(i, format!("unknown flag type `{}`", kind))),
+            },
+            ["AF", _, ..] if !af_count => af_count = true,
+            ["AF", flags, ..] => affixes.aliases.push(affixes.split(flags)),
+            _ => {}
+        }
+    }
+    let mut headers: HashMap<(&str, &str), bool> = HashMap::new();
+    for (i, line) in aff.lines().enumerate() {
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        match parts.as_slice() {
+            [kind @ ("NEEDAFFIX" | "PSEUDOROOT" | "FORBIDDENWORD" | "ONLYINCOMPOUND"), flag, ..] => {
+                let flags = affixes.split(flag);
+                if flags.len() != 1 {
+                    return Err(error(i, format!("`{}` takes a single flag", kind)));
+                }
+                affixes.not_alone.extend(flags);
+            }
+            [kind @ ("PFX" | "SFX"), flag, rest @ ..] => {
+                let Some(&cross) = headers.get(&(kind, flag)) else {
+                    match rest {
+                        [cross @ ("Y" | "N"), count, ..] if count.parse::<usize>().is_ok() => {
+                            headers.insert((kind, flag), *cross == "Y");
+                        }
+                        _ => return Err(error(i, format!("expected a header `{} {} Y|N count`", kind, flag))),
+                    }
+                    continue;
+                };
+                let (strip, add) = match rest {
+                    [strip, add, ..] => (*strip, *add),
+                    _ => return Err(error(i, format!("expected `{} {} strip add condition`", kind, flag))),
+                };
+                let (add, continuation) = match add.split_once('/') {
+                    Some((add, flags)) => (add, affixes.flags(flags)),
+                    None => (add, vec![]),
+                };
+                let rule = AffixRule {
+                    strip: String::from(if strip == "0" { "" } else { strip }),
+                    add: String::from(if add == "0" { "" } else { add }),
+                    condition: parse_condition(rest.get(2).copied().unwrap_or(".")),
+                    cross,
+                    continuation,
+                };
+                let table = if *kind == "PFX" { &mut affixes.prefixes } else { &mut affixes.suffixes };
+                table.entry(String::from(*flag)).or_default().push(rule);
+            }
+            _ => {}
+        }
+    }
+    Ok(affixes)
+}
+
+fn apply_suffix(word: &str, rule: &AffixRule) -> Option<String> {
+    let chars: Vec<char> = word.chars().collect();
+    if rule.condition.len() > chars.len() {
+        return None;
+    }
+    let tail = &chars[chars.len() - rule.condition.len()..];
+    if !rule.condition.iter().zip(tail).all(|(p, c)| p.matches(*c)) {
+        return None;
+    }
+    let stem = word.strip_suffix(rule.strip.as_str())?;
+    Some(format!("{}{}", stem, rule.add))
+}
+
+fn apply_prefix(word: &str, rule: &AffixRule) -> Option<String> {
+    let chars: Vec<char> = word.chars().collect();
+    if rule.condition.len() > chars.len() {
+        return None;
+    }
+    if !rule.condition.iter().zip(chars.iter()).all(|(p, c)| p.matches(*c)) {
+        return None;
+    }
+    let stem = word.strip_prefix(rule.strip.as_str())?;
+    Some(format!("{}{}", rule.add, stem))
+}
+
+/** A word formed from a stem, with what it may still take. */
+struct Form {
+    word: String,
+    /** Flags of the suffixes it may take next: the stem's, then a continuation class. */
+    suffixes: Vec<String>,
+    /** Flags of the prefixes it may take. */
+    prefixes: Vec<String>,
+    suffixed: bool,
+    /** Whether it may still take a prefix: not if a suffix said `N`. */
+    cross: bool,
+    /** Whether it is a word on its own. */
+    alone: bool,
+}
+
+impl Affixes {
+    /**
+    The words `stem` with `flags` stands for: the stem, its suffixed forms
+    and those of their continuation classes (two suffixes at most, as in
+    Hunspell), and all of these prefixed where the cross products allow,
+    with the suffixes of the prefixes' continuation classes.
+    */
+    fn expand(&self, stem: &str, flags: &[String]) -> Vec<String> {
+        let mut forms = vec![Form {
+            word: String::from(stem),
+            suffixes: flags.to_vec(),
+            prefixes: flags.to_vec(),
+            suffixed: false,
+            cross: true,
+            alone: self.is_alone(flags),
+        }];
+        let mut from = 0;
+        for _ in 0..2 {
+            let mut next = vec![];
+            for form in &forms[from..] {
+                for rule in form.suffixes.iter().flat_map(|f| self.suffixes.get(f)).flatten() {
+                    let Some(word) = apply_suffix(&form.word, rule) else { continue };
+                    let mut prefixes = form.prefixes.clone();
+                    prefixes.extend(rule.continuation.iter().cloned());
+                    next.push(Form {
+                        word,
+                        suffixes: rule.continuation.clone(),
+                        prefixes,
+                        suffixed: true,
+                        cross: form.cross && rule.cross,
+                        alone: self.is_alone(&rule.continuation),
+                    });
+                }
+            }
+            from = forms.len();
+            forms.extend(next);
+        }
+        let mut prefixed = vec![];
+        for form in forms.iter().filter(|f| f.cross) {
+            for rule in form.prefixes.iter().flat_map(|f| self.prefixes.get(f)).flatten() {
+                if form.suffixed && !rule.cross {
+                    continue;
+                }
+                let Some(word) = apply_prefix(&form.word, rule) else { continue };
+                // suffixes the prefix brings only go on the stem, not on more suffixes
+                let suffixes = rule.continuation.iter().filter(|_| !form.suffixed).flat_map(|f| self.suffixes.get(f)).flatten();
+                for suffix in suffixes.filter(|s| s.cross) {
+                    prefixed.extend(apply_suffix(&word, suffix));
+                }
+                if self.is_alone(&rule.continuation) {
+                    prefixed.push(word);
+                }
+            }
+        }
+        forms.into_iter().filter(|f| f.alone).map(|f| f.word).chain(prefixed).collect()
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Dictionary {
+    words: HashSet<String>,
+}
+
+impl Dictionary {
+    pub fn new() -> Dictionary {
+        Dictionary::default()
+    }
+
+    /**
+    Load a Hunspell dictionary. The first line of a `.dic` file is the word
+    count and is skipped if numeric. Fails on an `.aff` file whose `FLAG`
+    type or affix lines cannot be read.
+    */
+    pub fn from_hunspell(dic: &str, aff: Option<&str>) -> Result<Dictionary, AffixError> {
+        let affixes = aff.map(parse_affixes).transpose()?.unwrap_or_default();
+        let mut dict = Dictionary::new();
+        for (idx, line) in dic.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || (idx == 0 && line.chars().all(|c| c.is_ascii_digit())) {
+                continue;
+            }
+            // morphological fields may follow after whitespace
+            let entry = line.split_whitespace().next().unwrap_or("");
+            let (word, word_flags) = match entry.split_once('/') {
+                Some((w, f)) => (w, affixes.flags(f)),
+                None => (entry, vec![]),
+            };
+            dict.words.extend(affixes.expand(word, &word_flags));
+        }
+        Ok(dict)
+    }
+
+    /**
+    Add the words of a personal word list, one per line; `#` starts a comment.
+    */
+    pub fn add_word_list(&mut self, list: &str) {
+        for line in list.lines() {
+            let word = line.split('#').next().unwrap_or("").trim();
+            if !word.is_empty() {
+                self.words.insert(String::from(word));
+            }
+        }
+    }
+
+    pub fn add_word(&mut self, word: &str) {
+        self.words.insert(String::from(word));
+    }
+
+    pub fn len(&self) -> usize {
+        self.words.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+
+    pub fn contains(&self, word: &str) -> bool {
+        self.words.contains(word) || self.words.contains(&word.to_lowercase())
+    }
+}
+
+/**
+Looks like an acronym, identifier or number rather than a word: more than
+one capital letter, or any digit.
+*/
+fn is_exempt(word: &str) -> bool {
+    word.chars().filter(|c| c.is_uppercase()).count() > 1 || word.chars().any(|c| c.is_numeric())
+}
+
+/**
+The prose words of a TeX field value, with their byte offsets.
+*/
+pub fn words(text: &str) -> Vec<(usize, &str)> {
+    let mut out = Vec::new();
+    let chars: Vec<(usize, char)> = text.char_indices().collect();
+    let at = |i: usize| chars.get(i).map(|(_, c)| *c);
+    let offset = |i: usize| chars.get(i).map(|(o, _)| *o).unwrap_or(text.len());
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i].1;
+        if c == '\\' {
+            match at(i + 1) {
+                // `\(...\)` and `\[...\]` math
+                Some(open @ ('(' | '[')) => {
+                    let close = if open == '(' { "\\)" } else { "\\]" };
+                    i = text[offset(i + 2)..].find(close)
+                        .map(|p| chars.partition_point(|(o, _)| *o < offset(i + 2) + p + 2))
+                        .unwrap_or(chars.len());
+                }
+                Some(d) if d.is_alphabetic() => {
+                    i += 1;
+                    while at(i).map(|c| c.is_alphabetic()).unwrap_or(false) {
+                        i += 1;
+                    }
+                }
+                _ => i += 2,
+            }
+            continue;
+        }
+        if c == '$' {
+            let display = at(i + 1) == Some('$');
+            let start = i + if display { 2 } else { 1 };
+            let close = if display { "$$" } else { "$" };
+            i = text[offset(start)..].find(close)
+                .map(|p| chars.partition_point(|(o, _)| *o < offset(start) + p + close.len()))
+                .unwrap_or(chars.len());
+            continue;
+        }
+        if c.is_alphanumeric() {
+            let start = i;
+            while at(i).map(|c| c.is_alphanumeric() || c == ':' || c == '/' || c == '.' || c == '\'').unwrap_or(false) {
+                i += 1;
+            }
+            let token = &text[offset(start)..offset(i)];
+            // URLs, DOIs and similar are not prose
+            if token.contains("://") || token.contains('/') {
+                continue;
+            }
+            let mut pos = offset(start);
+            for piece in token.split([':', '.']) {
+                let word = piece.trim_matches('\'');
+                let lead = piece.len() - piece.trim_start_matches('\'').len();
+                if word.chars().count() > 1 && !is_exempt(word) {
+                    out.push((pos + lead, word));
+                }
+                pos += piece.len() + 1;
+            }
+            continue;
+        }
+        i += 1;
+    }
+    out
+}
+
+/**
+Report the words of the prose fields of `entry` missing from `dict`, once
+per word and field.
+*/
+pub fn check_entry(entry: &Entry, dict: &Dictionary) -> Vec<Diagnostic> {
+    let mut out = Vec::new();
+    for field in SPELL_FIELDS {
+        let Some(value) = entry.get(field) else { continue };
+        let mut seen = HashSet::new();
+        for (_, word) in words(value) {
+            if !dict.contains(word) && seen.insert(word) {
+                out.push(Diagnostic::new(entry.key(), "spelling", Severity::Info,
+                    &format!("possible misspelling `{}` in {}", word, field)));
+            }
+        }
+    }
+    out
+}
+
+pub fn check(entries: &[Entry], dict: &Dictionary) -> Vec<Diagnostic> {
+    entries.iter().flat_map(|e| check_entry(e, dict)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::bibtex::data::BibType;
+
+    const AFF: &str = "SET UTF-8\nSFX S Y 2\nSFX S y ies [^aeiou]y\nSFX S 0 s [^y]\nPFX U Y 1\nPFX U 0 un .\n";
+    const DIC: &str = "6\nprogram/S\ntheory/S\nliterate\nthe\nand\nknown/U\n";
+
+    #[test]
+    fn test_dictionary() {
+        let dict = Dictionary::from_hunspell(DIC, Some(AFF)).unwrap();
+        for w in ["programs", "theories", "Theory", "unknown", "literate"] {
+            assert!(dict.contains(w), "{}", w);
+        }
+        assert!(!dict.contains("theorys"));
+        assert!(!dict.contains("unprogram"));
+        let mut dict = Dictionary::from_hunspell(DIC, None).unwrap();
+        assert!(!dict.contains("programs"));
+        dict.add_word_list("# lab jargon\nperscrutar\n");
+        assert!(dict.contains("perscrutar"));
+    }
+
+    #[test]
+    fn test_affixes() {
+        let accepts = |dic: &str, aff: &str| {
+            let dict = Dictionary::from_hunspell(dic, Some(aff)).unwrap();
+            let mut words: Vec<String> = dict.words.into_iter().collect();
+            words.sort();
+            words
+        };
+        // cross products: `re` combines with `able` but not with `ment`
+        let cross = "SFX A Y 1\nSFX A 0 able .\nSFX M N 1\nSFX M 0 ment .\nPFX R Y 1\nPFX R 0 re .\n";
+        assert_eq!(accepts("1\nread/AMR\n", cross), ["read", "readable", "readment", "reread", "rereadable"]);
+        // continuation classes, on suffixes and prefixes, and words that need an affix
+        let classes = "NEEDAFFIX X\nSFX A Y 1\nSFX A 0 able/S .\nSFX S Y 1\nSFX S 0 s .\nPFX U Y 1\nPFX U 0 un/S .\n";
+        assert_eq!(accepts("2\nread/AUX\nwalk/S\n", classes),
+            ["readable", "readables", "unread", "unreadable", "unreadables", "unreads", "walk", "walks"]);
+        let forbidden = "FORBIDDENWORD F\nSFX S Y 1\nSFX S 0 s .\n";
+        assert_eq!(accepts("teh/F\nthe\n", forbidden), ["the"]);
+
+        // the same rules with long, numeric, UTF-8 and aliased flags
+        let long = "FLAG long\nSFX Aa Y 1\nSFX Aa 0 able/Ss .\nSFX Ss Y 1\nSFX Ss 0 s .\n";
+        assert_eq!(accepts("read/AaSs\n", long), ["read", "readable", "readables", "reads"]);
+        let num = "FLAG num\nSFX 101 Y 1\nSFX 101 0 able/7 .\nSFX 7 Y 1\nSFX 7 0 s .\n";
+        assert_eq!(accepts("read/101,7\n", num), ["read", "readable", "readables", "reads"]);
+        let utf8 = "SET UTF-8\nFLAG UTF-8\nSFX é Y 1\nSFX é 0 able/ß .\nSFX ß Y 1\nSFX ß 0 s .\n";
+        assert_eq!(accepts("read/éß\n", utf8), ["read", "readable", "readables", "reads"]);
+        let aliases = "AF 2\nAF AS # 1\nAF S\nSFX A Y 1\nSFX A 0 able/2 .\nSFX S Y 1\nSFX S 0 s .\n";
+        assert_eq!(accepts("read/1\n", aliases), ["read", "readable", "readables", "reads"]);
+
+        let error = Dictionary::from_hunspell("read\n", Some("SET UTF-8\nFLAG wide\n")).unwrap_err();
+        assert_eq!(error.to_string(), "line 2: unknown flag type `wide`");
+        assert_eq!(Dictionary::from_hunspell("read\n", Some("SFX A 0 able .\n")).unwrap_err().line, 1);
+    }
+
+    #[test]
+    fn test_words() {
+        let found: Vec<&str> = words("The \\emph{Teh} $O(n^2)$ GPU x86 \\(a+b\\) LaTeX see https://x.org/a, doi:10.1/x don't").into_iter()
+            .map(|(_, w)| w).collect();
+        assert_eq!(found, vec!["The", "Teh", "see", "don't"]);
+        assert_eq!(words("a Progam")[0], (2, "Progam"));
+    }
+
+    #[test]
+    fn test_check() {
+        let dict = Dictionary::from_hunspell(DIC, Some(AFF)).unwrap();
+        let mut e = Entry::new(BibType::Article, "Knuth-LP");
+        e.set("title", "Literate progams and the progams");
+        e.set("journal", "Compter Journal");
+        let diags = check(&[e], &dict);
+        assert_eq!(diags.len(), 1);
+        assert_eq!(diags[0].rule, "spelling");
+        assert!(diags[0].message.contains("`progams` in title"));
+    }
+}
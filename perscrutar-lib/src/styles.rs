@@ -0,0 +1,306 @@
+/*!
+
+CSL styles by name.
+
+`StyleStore` keeps Citation Style Language styles as `<name>.csl` files in a
+cache directory, so a style can be asked for as `apa` instead of by path.
+With the `net` feature, missing styles are downloaded from the official
+repository (<https://github.com/citation-style-language/styles>).
+
+Many styles in the repository are *dependent*: they only name an
+independent parent style whose formatting they share (`nature-physics`
+uses `nature`). `load` follows that link, so it always returns a style that
+can be rendered with; fetching a dependent style also fetches its parent.
+
+*/
+
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+#[cfg(feature = "net")]
+use crate::net::{HttpClient, NetError};
+
+/** Raw files of the official style repository. */
+pub const STYLES_URL: &str = "https://raw.githubusercontent.com/citation-style-language/styles/master";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StyleError {
+    /** Style names are lowercase letters, digits and hyphens. */
+    InvalidName(String),
+    NotFound(String),
+    /** The file is not a CSL style. */
+    Invalid { name: String, message: String },
+    Io(String),
+    #[cfg(feature = "net")]
+    Net(NetError),
+}
+
+impl fmt::Display for StyleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StyleError::InvalidName(name) => write!(f, "`{}` is not a valid style name", name),
+            StyleError::NotFound(name) => write!(f, "style `{}` not found", name),
+            StyleError::Invalid { name, message } => write!(f, "style `{}`: {}", name, message),
+            StyleError::Io(msg) => f.write_str(msg),
+            #[cfg(feature = "net")]
+            StyleError::Net(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for StyleError {}
+
+#[cfg(feature = "net")]
+impl From<NetError> for StyleError {
+    fn from(e: NetError) -> Self {
+        StyleError::Net(e)
+    }
+}
+
+pub fn is_valid_name(name: &str) -> bool {
+    !name.is_empty() && !name.starts_with('-')
+        && name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+}
+
+/**
+Text of the first `<tag>` element in `xml`, without unescaping.
+*/
+fn element_text<'a>(xml: &'a str, tag: &str) -> Option<&'a str> {
+    let start = xml.find(&format!("<{}>", tag))? + tag.len() + 2;
+    let end = xml[start..].find(&format!("</{}>", tag))?;
+    Some(xml[start..start + end].trim())
+}
+
+/**
+Value of `attribute` in an element's opening tag.
+*/
+fn attribute<'a>(tag: &'a str, attribute: &str) -> Option<&'a str> {
+    let pattern = format!("{}=\"", attribute);
+    let start = tag.match_indices(&pattern)
+        .find(|(i, _)| tag[..*i].ends_with(char::is_whitespace))?.0 + pattern.len();
+    let end = tag[start..].find('"')?;
+    Some(&tag[start..start + end])
+}
+
+/**
+Human-readable title from the style's `<info>`.
+*/
+pub fn style_title(xml: &str) -> Option<&str> {
+    element_text(xml, "title")
+}
+
+/**
+Name of the independent parent of a dependent style, taken from its
+`<link rel="independent-parent" href="http://www.zotero.org/styles/NAME"/>`.
+*/
+pub fn parent_name(xml: &str) -> Option<&str> {
+    xml.match_indices("<link ")
+        .filter_map(|(i, _)| xml[i..].find('>').map(|end| &xml[i..i + end]))
+        .find(|tag| attribute(tag, "rel") == Some("independent-parent"))
+        .and_then(|tag| attribute(tag, "href"))
+        .and_then(|href| href.trim_end_matches('/').rsplit('/').next())
+}
+
+fn check_style(name: &str, xml: &str) -> Result<(), StyleError> {
+    let invalid = |message: &str| StyleError::Invalid { name: String::from(name), message: String::from(message) };
+    if !xml.contains("<style") || !xml.contains("http://purl.org/net/xbiblio/csl") {
+        return Err(invalid("not a CSL style"));
+    }
+    if xml.contains("<bibliography") || xml.contains("<citation") {
+        return Ok(());
+    }
+    match parent_name(xml) {
+        Some(_) => Ok(()),
+        None => Err(invalid("neither formatting nor an independent parent")),
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct StyleStore {
+    dir: PathBuf,
+}
+
+impl StyleStore {
+    pub fn new<P: Into<PathBuf>>(dir: P) -> StyleStore {
+        StyleStore { dir: dir.into() }
+    }
+
+    /**
+    `$XDG_CACHE_HOME/perscrutar/styles`, falling back to `~/.cache`.
+    */
+    pub fn default_dir() -> Option<PathBuf> {
+        let base = std::env::var_os("XDG_CACHE_HOME").map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|h| PathBuf::from(h).join(".cache")))?;
+        Some(base.join("perscrutar").join("styles"))
+    }
+
+    pub fn path(&self, name: &str) -> Result<PathBuf, StyleError> {
+        if !is_valid_name(name) {
+            return Err(StyleError::InvalidName(String::from(name)));
+        }
+        Ok(self.dir.join(format!("{}.csl", name)))
+    }
+
+    /**
+    The stored style called `name`, as is.
+    */
+    pub fn cached(&self, name: &str) -> Result<Option<String>, StyleError> {
+        match fs::read_to_string(self.path(name)?) {
+            Ok(xml) => Ok(Some(xml)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(StyleError::Io(format!("cannot read style `{}`: {}", name, e))),
+        }
+    }
+
+    /**
+    The style called `name`, or its independent parent if it is a
+    dependent style. Nothing is downloaded.
+    */
+    pub fn load(&self, name: &str) -> Result<String, StyleError> {
+        let xml = self.cached(name)?.ok_or_else(|| StyleError::NotFound(String::from(name)))?;
+        match parent_name(&xml) {
+            Some(parent) if parent != name => {
+                let parent = parent.to_string();
+                self.cached(&parent)?.ok_or(StyleError::NotFound(parent))
+            }
+            _ => Ok(xml),
+        }
+    }
+
+    /**
+    Names of the stored styles, sorted.
+    */
+    pub fn installed(&self) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(&self.dir).into_iter().flatten().flatten()
+            .filter_map(|e| e.file_name().to_str().and_then(|n| n.strip_suffix(".csl")).map(String::from))
+            .filter(|n| is_valid_name(n))
+            .collect();
+        names.sort();
+        names
+    }
+
+    /**
+    Store `xml` as the style called `name`, e.g. a style that is not in the
+    repository. It must be a CSL style.
+    */
+    pub fn install(&self, name: &str, xml: &str) -> Result<(), StyleError> {
+        check_style(name, xml)?;
+        let io = |e: std::io::Error| StyleError::Io(format!("cannot store style `{}`: {}", name, e));
+        fs::create_dir_all(&self.dir).map_err(io)?;
+        fs::write(self.path(name)?, xml).map_err(io)
+    }
+
+    /**
+    Download `name` (and, for a dependent style, its parent) from the
+    repository, replacing any stored copy, and return it like `load`.
+    */
+    #[cfg(feature = "net")]
+    pub fn fetch(&self, client: &dyn HttpClient, name: &str) -> Result<String, StyleError> {
+        let xml = self.download(client, name)?;
+        if let Some(parent) = parent_name(&xml).filter(|p| *p != name) {
+            self.download(client, parent)?;
+        }
+        self.load(name)
+    }
+
+    /**
+    The stored style if there is one, downloading it otherwise.
+    */
+    #[cfg(feature = "net")]
+    pub fn get(&self, client: &dyn HttpClient, name: &str) -> Result<String, StyleError> {
+        match self.load(name) {
+            Err(StyleError::NotFound(_)) => self.fetch(client, name),
+            other => other,
+        }
+    }
+
+    #[cfg(feature = "net")]
+    fn download(&self, client: &dyn HttpClient, name: &str) -> Result<String, StyleError> {
+        self.path(name)?;
+        // independent styles live at the top level, dependent ones below
+        for url in [format!("{}/{}.csl", STYLES_URL, name), format!("{}/dependent/{}.csl", STYLES_URL, name)] {
+            let response = client.get(&url, &[])?;
+            if response.status == 404 {
+                continue;
+            }
+            let xml = crate::net::expect_success(&url, response)?.body;
+            self.install(name, &xml)?;
+            return Ok(xml);
+        }
+        Err(StyleError::NotFound(String::from(name)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    const NATURE: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<style xmlns="http://purl.org/net/xbiblio/csl" class="in-text" version="1.0">
+  <info><title>Nature</title><id>http://www.zotero.org/styles/nature</id></info>
+  <citation><layout/></citation>
+</style>"#;
+
+    const NATURE_PHYSICS: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<style xmlns="http://purl.org/net/xbiblio/csl" version="1.0" default-locale="en-GB">
+  <info>
+    <title>Nature Physics</title>
+    <link href="http://www.zotero.org/styles/nature-physics" rel="self"/>
+    <link href="http://www.zotero.org/styles/nature" rel="independent-parent"/>
+  </info>
+</style>"#;
+
+    fn store(name: &str) -> StyleStore {
+        StyleStore::new(std::env::temp_dir().join(format!("perscrutar-styles-{}-{}", name, std::process::id())))
+    }
+
+    #[test]
+    fn test_parse_style() {
+        assert_eq!(style_title(NATURE_PHYSICS), Some("Nature Physics"));
+        assert_eq!(parent_name(NATURE_PHYSICS), Some("nature"));
+        assert_eq!(parent_name(NATURE), None);
+        assert!(check_style("nature", NATURE).is_ok());
+        assert!(check_style("x", "<html></html>").is_err());
+        assert!(is_valid_name("apa-6th-edition"));
+        assert!(!is_valid_name("../apa") && !is_valid_name("APA") && !is_valid_name(""));
+    }
+
+    #[test]
+    fn test_load() {
+        let styles = store("load");
+        assert_eq!(styles.load("nature"), Err(StyleError::NotFound(String::from("nature"))));
+        styles.install("nature-physics", NATURE_PHYSICS).unwrap();
+        assert_eq!(styles.load("nature-physics"), Err(StyleError::NotFound(String::from("nature"))));
+        styles.install("nature", NATURE).unwrap();
+        assert_eq!(styles.load("nature-physics").unwrap(), NATURE);
+        assert_eq!(styles.installed(), vec!["nature", "nature-physics"]);
+        assert!(matches!(styles.path("a/b"), Err(StyleError::InvalidName(_))));
+        let _ = fs::remove_dir_all(&styles.dir);
+    }
+
+    #[cfg(feature = "net")]
+    #[test]
+    fn test_fetch() {
+        use crate::net::Response;
+
+        struct Repository;
+
+        impl HttpClient for Repository {
+            fn get(&self, url: &str, _headers: &[(&str, &str)]) -> Result<Response, NetError> {
+                let body = match url.strip_prefix(STYLES_URL) {
+                    Some("/nature.csl") => NATURE,
+                    Some("/dependent/nature-physics.csl") => NATURE_PHYSICS,
+                    _ => return Ok(Response { status: 404, headers: vec![], body: String::new() }),
+                };
+                Ok(Response { status: 200, headers: vec![], body: String::from(body) })
+            }
+        }
+
+        let styles = store("fetch");
+        assert_eq!(styles.get(&Repository, "nature-physics").unwrap(), NATURE);
+        assert_eq!(styles.installed(), vec!["nature", "nature-physics"]);
+        assert_eq!(styles.get(&Repository, "apa"), Err(StyleError::NotFound(String::from("apa"))));
+        let _ = fs::remove_dir_all(&styles.dir);
+    }
+}
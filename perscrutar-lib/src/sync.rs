@@ -0,0 +1,323 @@
+/*!
+
+Keeping a library in sync with a git remote, such as an Overleaf project.
+
+`Git` drives the `git` executable, so any remote and authentication method
+the user's git is configured for works. `sync` commits the local changes to
+every .bib file with a message listing the entries that were added, changed
+or removed, then rebases onto the remote and pushes.
+
+Files can be passed through a formatter before they are committed, so that
+the history only ever contains canonically formatted bibliographies.
+
+This module is only built with the `sync` feature.
+
+*/
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use crate::bibtex::data::Entry;
+use crate::bibtex::error::ParseError;
+use crate::bibtex::parser::{parse_with, Macros, ParseOptions};
+use crate::events::{diff, EventKind};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SyncError {
+    /** A git command failed. */
+    Git { command: String, message: String },
+    /** A .bib file in the repository could not be parsed. */
+    Parse { file: String, error: ParseError },
+    /** The formatter rejected a file. */
+    Format { file: String, message: String },
+}
+
+impl fmt::Display for SyncError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SyncError::Git { command, message } => write!(f, "`git {}` failed: {}", command, message),
+            SyncError::Parse { file, error } => write!(f, "{}: {}", file, error),
+            SyncError::Format { file, message } => write!(f, "{}: {}", file, message),
+        }
+    }
+}
+
+impl std::error::Error for SyncError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EntryChange {
+    Added(String),
+    Removed(String),
+    /** The entry's key and the fields whose values changed. */
+    Changed(String, Vec<String>),
+}
+
+impl fmt::Display for EntryChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EntryChange::Added(key) => write!(f, "Add {}", key),
+            EntryChange::Removed(key) => write!(f, "Remove {}", key),
+            EntryChange::Changed(key, fields) => write!(f, "Update {} ({})", key, fields.join(", ")),
+        }
+    }
+}
+
+/**
+Entry-level differences between two versions of a bibliography, in the
+order of `events::diff`.
+*/
+pub fn changes(old: &[Entry], new: &[Entry]) -> Vec<EntryChange> {
+    diff(old, new).into_iter().map(|e| match e.kind {
+        EventKind::Added => EntryChange::Added(e.key),
+        EventKind::Removed => EntryChange::Removed(e.key),
+        EventKind::Changed => EntryChange::Changed(e.key, e.fields.into_iter().map(|r| r.name).collect()),
+    }).collect()
+}
+
+/**
+Commit message for the changes to `file`: a single change becomes the
+subject, several are summarised in the subject and listed in the body.
+*/
+pub fn commit_message(file: &str, changes: &[EntryChange]) -> String {
+    if let [single] = changes {
+        return format!("{}\n", single);
+    }
+    let count = |f: fn(&EntryChange) -> bool| changes.iter().filter(|c| f(c)).count();
+    let mut parts = Vec::new();
+    for (n, what) in [
+        (count(|c| matches!(c, EntryChange::Added(_))), "added"),
+        (count(|c| matches!(c, EntryChange::Changed(..))), "changed"),
+        (count(|c| matches!(c, EntryChange::Removed(_))), "removed"),
+    ] {
+        if n > 0 {
+            parts.push(format!("{} {}", n, what));
+        }
+    }
+    let subject = if parts.is_empty() {
+        format!("Reformat {}", file)
+    } else {
+        format!("Update {}: {}", file, parts.join(", "))
+    };
+    let body: String = changes.iter().map(|c| format!("- {}\n", c)).collect();
+    if body.is_empty() { format!("{}\n", subject) } else { format!("{}\n\n{}", subject, body) }
+}
+
+/**
+A git working copy, operated through the `git` executable.
+*/
+#[derive(Debug, Clone)]
+pub struct Git {
+    dir: PathBuf,
+}
+
+impl Git {
+    pub fn open<P: Into<PathBuf>>(dir: P) -> Git {
+        Git { dir: dir.into() }
+    }
+
+    /**
+    Clone `remote` into `dir`.
+    */
+    pub fn clone_from(remote: &str, dir: &Path) -> Result<Git, SyncError> {
+        let git = Git::open(dir);
+        git.run_in(None, &["clone", "--quiet", "--", remote, &dir.to_string_lossy()])?;
+        Ok(git)
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn run_in(&self, dir: Option<&Path>, args: &[&str]) -> Result<String, SyncError> {
+        let mut cmd = Command::new("git");
+        if let Some(dir) = dir {
+            cmd.arg("-C").arg(dir);
+        }
+        let output = cmd.args(args).output().map_err(|e| SyncError::Git {
+            command: args.join(" "),
+            message: format!("cannot run git: {}", e),
+        })?;
+        if !output.status.success() {
+            return Err(SyncError::Git {
+                command: args.join(" "),
+                message: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            });
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    }
+
+    pub fn run(&self, args: &[&str]) -> Result<String, SyncError> {
+        self.run_in(Some(&self.dir), args)
+    }
+
+    /**
+    Paths, relative to the working copy, of .bib files with uncommitted
+    changes (including new files). A renamed file is listed under its new
+    name and then its old one.
+    */
+    pub fn changed_bib_files(&self) -> Result<Vec<String>, SyncError> {
+        let status = self.run(&["status", "--porcelain", "-z", "--untracked-files=all"])?;
+        let mut fields = status.split('\0').filter(|f| !f.is_empty());
+        let mut paths = Vec::new();
+        // `XY path`, followed by the old path as a field of its own for renames and copies
+        while let Some(field) = fields.next() {
+            let Some((code, path)) = field.split_at_checked(3) else { continue };
+            paths.push(path);
+            if code.contains(['R', 'C']) {
+                paths.extend(fields.next());
+            }
+        }
+        Ok(paths.into_iter()
+            .filter(|p| p.to_lowercase().ends_with(".bib"))
+            .map(String::from)
+            .collect())
+    }
+
+    /**
+    Content of `path` in the last commit; `None` if it is not tracked yet.
+    */
+    pub fn committed(&self, path: &str) -> Option<String> {
+        self.run(&["show", &format!("HEAD:{}", path)]).ok()
+    }
+
+    /**
+    Name of the first configured remote, usually `origin`.
+    */
+    pub fn remote(&self) -> Option<String> {
+        self.run(&["remote"]).ok()?.lines().next().map(String::from)
+    }
+
+    pub fn has_upstream(&self) -> bool {
+        self.run(&["rev-parse", "--abbrev-ref", "@{u}"]).is_ok()
+    }
+}
+
+pub type Formatter = dyn Fn(&str) -> Result<String, String>;
+
+#[derive(Default)]
+pub struct SyncOptions<'a> {
+    /** Applied to every changed file before it is committed. */
+    pub formatter: Option<&'a Formatter>,
+    /** Push after committing; otherwise only pull. */
+    pub push: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SyncReport {
+    /** Committed files and the entry changes recorded for each. */
+    pub commits: Vec<(String, Vec<EntryChange>)>,
+    pub pushed: bool,
+}
+
+fn parse(file: &str, content: &str) -> Result<Vec<Entry>, SyncError> {
+    parse_with(content, &mut Macros::new(), ParseOptions::standard()).map_err(|error| SyncError::Parse { file: String::from(file), error })
+}
+
+/**
+Commit local changes file by file, then rebase onto the remote branch (if
+there is one) and optionally push.
+*/
+pub fn sync(git: &Git, options: &SyncOptions) -> Result<SyncReport, SyncError> {
+    let mut report = SyncReport::default();
+    for file in git.changed_bib_files()? {
+        let path = git.dir().join(&file);
+        let Ok(mut content) = std::fs::read_to_string(&path) else {
+            // deleted files are committed as a whole
+            let old = git.committed(&file).map(|c| parse(&file, &c)).transpose()?.unwrap_or_default();
+            let changes = changes(&old, &[]);
+            git.run(&["rm", "--quiet", "--cached", "--ignore-unmatch", "--", &file])?;
+            git.run(&["commit", "--quiet", "-m", &commit_message(&file, &changes), "--", &file])?;
+            report.commits.push((file, changes));
+            continue;
+        };
+        if let Some(format) = options.formatter {
+            content = format(&content).map_err(|message| SyncError::Format { file: file.clone(), message })?;
+            std::fs::write(&path, &content).map_err(|e| SyncError::Format { file: file.clone(), message: e.to_string() })?;
+        }
+        let old = git.committed(&file).map(|c| parse(&file, &c)).transpose()?.unwrap_or_default();
+        let changes = changes(&old, &parse(&file, &content)?);
+        git.run(&["add", "--", &file])?;
+        if git.run(&["diff", "--cached", "--quiet", "--", &file]).is_ok() {
+            // the formatter undid the only changes
+            continue;
+        }
+        git.run(&["commit", "--quiet", "-m", &commit_message(&file, &changes), "--", &file])?;
+        report.commits.push((file, changes));
+    }
+    if let Some(remote) = git.remote() {
+        let upstream = git.has_upstream();
+        if upstream {
+            git.run(&["pull", "--quiet", "--rebase"])?;
+        }
+        if options.push {
+            if upstream {
+                git.run(&["push", "--quiet"])?;
+            } else {
+                // a fresh remote: publish the current branch
+                git.run(&["push", "--quiet", "--set-upstream", &remote, "HEAD"])?;
+            }
+            report.pushed = true;
+        }
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::bibtex::data::BibType;
+
+    fn entry(key: &str, title: &str) -> Entry {
+        let mut e = Entry::new(BibType::Misc, key);
+        e.set("title", title);
+        e
+    }
+
+    #[test]
+    fn test_changes_and_messages() {
+        let old = vec![entry("a", "A"), entry("b", "B"), entry("c", "C")];
+        let new = vec![entry("a", "A"), entry("b", "Bee"), entry("d", "D")];
+        let c = changes(&old, &new);
+        assert_eq!(c, vec![
+            EntryChange::Changed(String::from("b"), vec![String::from("title")]),
+            EntryChange::Added(String::from("d")),
+            EntryChange::Removed(String::from("c")),
+        ]);
+        assert_eq!(commit_message("lib.bib", &c),
+            "Update lib.bib: 1 added, 1 changed, 1 removed\n\n- Update b (title)\n- Add d\n- Remove c\n");
+        assert_eq!(commit_message("lib.bib", &c[1..2]), "Add d\n");
+        assert_eq!(commit_message("lib.bib", &[]), "Reformat lib.bib\n");
+    }
+
+    #[test]
+    fn test_sync() {
+        let root = std::env::temp_dir().join(format!("perscrutar-sync-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        let remote = root.join("remote.git");
+        std::fs::create_dir_all(&remote).unwrap();
+        if Git::open(&remote).run(&["init", "--quiet", "--bare"]).is_err() {
+            return; // no git available
+        }
+        let git = Git::clone_from(&remote.to_string_lossy(), &root.join("work")).unwrap();
+        git.run(&["config", "user.name", "Test"]).unwrap();
+        git.run(&["config", "user.email", "test@example.org"]).unwrap();
+
+        std::fs::write(git.dir().join("lib.bib"), "@misc{a,\n  title = {A}\n}\n").unwrap();
+        let upper: &Formatter = &|s: &str| Ok(s.replace("title", "TITLE"));
+        let report = sync(&git, &SyncOptions { formatter: Some(upper), push: true }).unwrap();
+        assert_eq!(report.commits, vec![(String::from("lib.bib"), vec![EntryChange::Added(String::from("a"))])]);
+        assert!(report.pushed);
+        assert!(git.committed("lib.bib").unwrap().contains("TITLE"));
+        assert_eq!(git.run(&["log", "-1", "--format=%s"]).unwrap().trim(), "Add a");
+        assert!(sync(&git, &SyncOptions::default()).unwrap().commits.is_empty());
+
+        git.run(&["mv", "lib.bib", "my \"lib\" ü.bib"]).unwrap();
+        assert_eq!(git.changed_bib_files().unwrap(), vec!["my \"lib\" ü.bib", "lib.bib"]);
+        let report = sync(&git, &SyncOptions::default()).unwrap();
+        assert_eq!(report.commits.len(), 2);
+        assert!(git.changed_bib_files().unwrap().is_empty());
+        assert!(git.committed("my \"lib\" ü.bib").is_some() && git.committed("lib.bib").is_none());
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,340 @@
+/*!
+
+Named transforms over a whole bibliography.
+
+A `Transform` rewrites a `Bibliography` in place and says what it did in a
+`Report`. Transforms are looked up by name in a `TransformRegistry`, which
+is how the `transform` command runs them; crates using perscrutar as a
+library can implement the trait and `register` their own next to the
+built-in ones:
+
+- `split-title` moves a subtitle out of `title` (`bibtex::titles`);
+- `normalize-month` turns month names into numbers (`bibtex::months`);
+- `normalize-booktitle` rewrites conference names canonically
+  (`bibtex::conference`);
+- `minimize-minimal`, `minimize-standard`, `minimize-ieee` and
+  `minimize-acm` strip entries down to what a publisher expects
+  (`bibtex::minimize`);
+- `key-case-lower`, `key-case-upper` and `key-case-first` rewrite citation
+  keys, and the cross-references to them, in one case convention
+  (`bibtex::keys`);
+- `thesis-biblatex` and `thesis-bibtex` convert theses between the BibTeX
+  and biblatex conventions (`bibtex::theses`);
+- `normalize-standards` writes RFC and standard numbers canonically and
+  adds their URLs and DOIs (`bibtex::standards`);
+- `retype-misc` gives `@misc` entries the type their fields point to,
+  where that is clear enough (`bibtex::inference`);
+- `encode-ascii` writes non-ASCII characters as LaTeX commands
+  (`latex::decode`).
+
+`archive::ArchiveTransform` (`archive-urls`) talks to the network and is
+not registered by default; the `transform` command adds it in builds with
+the `net` feature.
+
+No transform can rename, retype or remove a pinned entry (see
+`Bibliography::pin`); `key-case-*` leaves pinned keys and the references
+to them alone from the start.
+
+Most transforms work entry by entry; `EntryTransform` turns a function on
+one `Entry` into a `Transform`. `batch::BatchEdit`, setting fields from
+templates over other fields, is one too; the `transform` command builds it
+from `--set`.
+
+*/
+
+use crate::bibtex::bibliography::{Bibliography, KEY_REFERENCES};
+use crate::bibtex::conference::{normalize_booktitle, CANONICAL};
+use crate::bibtex::data::Entry;
+use crate::bibtex::keys::KeyCase;
+use crate::bibtex::minimize::Profile;
+use crate::bibtex::months::{normalize_month, Language};
+use crate::bibtex::{inference, standards, theses};
+use crate::bibtex::titles::split_title;
+use crate::bibtex::types::TypeRegistry;
+use crate::json::JsonValue;
+use crate::latex::decode::AsciiTransform;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Report {
+    /** Keys of the entries that changed, in bibliography order. */
+    pub changed: Vec<String>,
+    /** Anything else worth telling the user. */
+    pub messages: Vec<String>,
+}
+
+impl Report {
+    pub fn is_empty(&self) -> bool {
+        self.changed.is_empty() && self.messages.is_empty()
+    }
+
+    pub fn to_json(&self) -> JsonValue {
+        JsonValue::object(vec![
+            ("changed", JsonValue::Array(self.changed.iter().map(|k| JsonValue::str(k)).collect())),
+            ("messages", JsonValue::Array(self.messages.iter().map(|m| JsonValue::str(m)).collect())),
+        ])
+    }
+}
+
+pub trait Transform {
+    /** Name used to select the transform, e.g. `split-title`. */
+    fn name(&self) -> &str;
+
+    /** One line for `transform --list`. */
+    fn description(&self) -> &str;
+
+    fn apply(&self, bibliography: &mut Bibliography) -> Report;
+}
+
+/**
+A transform applying `f` to every entry; `f` returns whether it changed the
+entry.
+*/
+pub struct EntryTransform<F: Fn(&mut Entry) -> bool> {
+    pub name: String,
+    pub description: String,
+    pub f: F,
+}
+
+impl<F: Fn(&mut Entry) -> bool> EntryTransform<F> {
+    pub fn new(name: &str, description: &str, f: F) -> EntryTransform<F> {
+        EntryTransform { name: String::from(name), description: String::from(description), f }
+    }
+}
+
+impl<F: Fn(&mut Entry) -> bool> Transform for EntryTransform<F> {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn apply(&self, bibliography: &mut Bibliography) -> Report {
+        Report { changed: bibliography.visit_mut(|e| { (self.f)(e); }), messages: vec![] }
+    }
+}
+
+/**
+`normalize-month` accepting month names in `languages` besides English.
+*/
+pub fn month_transform(languages: Vec<Language>) -> Box<dyn Transform> {
+    Box::new(EntryTransform::new("normalize-month", "Replace month names and abbreviations by month numbers",
+        move |e: &mut Entry| normalize_month(e, &languages)))
+}
+
+/**
+`minimize-NAME`, reducing entries to the fields `profile` keeps.
+*/
+pub fn minimize_transform(profile: Profile) -> Box<dyn Transform> {
+    let registry = TypeRegistry::default();
+    Box::new(EntryTransform::new(&format!("minimize-{}", profile.name),
+        &format!("Strip entries to the fields of the `{}` submission profile", profile.name),
+        move |e: &mut Entry| profile.minimize(e, &registry)))
+}
+
+/**
+`key-case-NAME`, rewriting keys in the `case` convention. A key whose new
+spelling would be another entry's is left alone and reported, since the two
+entries need merging or renaming by hand. References to keys follow them.
+*/
+pub struct KeyCaseTransform {
+    case: KeyCase,
+    name: String,
+}
+
+impl KeyCaseTransform {
+    pub fn new(case: KeyCase) -> KeyCaseTransform {
+        KeyCaseTransform { case, name: format!("key-case-{}", case.name()) }
+    }
+}
+
+impl Transform for KeyCaseTransform {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> &str {
+        match self.case {
+            KeyCase::Lower => "Put citation keys in lower case",
+            KeyCase::Upper => "Put citation keys in upper case",
+            KeyCase::First => "Spell keys differing only in case like the first of them",
+        }
+    }
+
+    fn apply(&self, bibliography: &mut Bibliography) -> Report {
+        let keys: Vec<String> = bibliography.entries().iter().map(|e| String::from(e.key())).collect();
+        let refs: Vec<&str> = keys.iter().map(String::as_str).collect();
+        let canonical = self.case.canonical(&refs);
+        let mut messages = Vec::new();
+        let mut renamed = keys.clone();
+        for i in 0..keys.len() {
+            if canonical[i] == keys[i] {
+                continue;
+            }
+            if bibliography.is_pinned(&keys[i]) {
+                messages.push(format!("{}: pinned, so not renamed to `{}`", keys[i], canonical[i]));
+                continue;
+            }
+            match (0..keys.len()).find(|j| *j != i && canonical[*j] == canonical[i]) {
+                Some(j) => messages.push(format!("{}: not renamed to `{}`, which `{}` becomes too", keys[i], canonical[i], keys[j])),
+                None => renamed[i] = canonical[i].clone(),
+            }
+        }
+        // a reference follows the key it names, or the one it names up to case
+        let follow = |target: &str| {
+            let i = keys.iter().position(|k| k == target)
+                .or_else(|| keys.iter().position(|k| k.to_lowercase() == target.to_lowercase()));
+            match (i, self.case) {
+                (Some(i), _) => renamed[i].clone(),
+                (None, KeyCase::First) => String::from(target),
+                (None, _) => self.case.canonical(&[target]).remove(0),
+            }
+        };
+        let mut i = 0;
+        let changed = bibliography.visit_mut(|e| {
+            e.set_key(&renamed[i]);
+            i += 1;
+            for field in KEY_REFERENCES {
+                let Some(value) = e.get(field) else { continue };
+                let targets: Vec<&str> = value.split(',').map(str::trim).collect();
+                let followed: Vec<String> = targets.iter().map(|t| follow(t)).collect();
+                if followed != targets {
+                    e.set(field, &followed.join(", "));
+                }
+            }
+        });
+        Report { changed, messages }
+    }
+}
+
+pub struct TransformRegistry {
+    transforms: Vec<Box<dyn Transform>>,
+}
+
+impl TransformRegistry {
+    pub fn empty() -> TransformRegistry {
+        TransformRegistry { transforms: Vec::new() }
+    }
+
+    /**
+    Add a transform, replacing any registered under the same name.
+    */
+    pub fn register(&mut self, transform: Box<dyn Transform>) {
+        self.transforms.retain(|t| t.name() != transform.name());
+        self.transforms.push(transform);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&dyn Transform> {
+        self.transforms.iter().find(|t| t.name() == name).map(|t| t.as_ref())
+    }
+
+    /**
+    All transforms in registration order.
+    */
+    pub fn iter(&self) -> impl Iterator<Item = &dyn Transform> {
+        self.transforms.iter().map(|t| t.as_ref())
+    }
+}
+
+impl Default for TransformRegistry {
+    fn default() -> Self {
+        let mut r = TransformRegistry::empty();
+        r.register(Box::new(EntryTransform::new("split-title", "Move a subtitle after `: ` in the title into subtitle",
+            split_title)));
+        r.register(month_transform(vec![]));
+        r.register(Box::new(EntryTransform::new("normalize-booktitle", "Rewrite conference booktitles in a canonical form",
+            |e: &mut Entry| normalize_booktitle(e, CANONICAL))));
+        for name in Profile::PRESETS {
+            r.register(minimize_transform(Profile::preset(name).expect("presets exist")));
+        }
+        for case in KeyCase::ALL {
+            r.register(Box::new(KeyCaseTransform::new(case)));
+        }
+        r.register(Box::new(EntryTransform::new("thesis-biblatex", "Turn @phdthesis and @mastersthesis into biblatex @thesis with a type",
+            theses::to_biblatex)));
+        r.register(Box::new(EntryTransform::new("thesis-bibtex", "Turn biblatex @thesis into @phdthesis or @mastersthesis by its type",
+            theses::to_bibtex)));
+        r.register(Box::new(EntryTransform::new("normalize-standards", "Write RFC and standard numbers canonically and add their URL and DOI",
+            standards::normalize_standard)));
+        r.register(Box::new(EntryTransform::new("retype-misc", "Give @misc entries the type their fields clearly point to",
+            inference::retype)));
+        r.register(Box::new(AsciiTransform));
+        r
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::bibtex::data::BibType;
+
+    struct DropMisc;
+
+    impl Transform for DropMisc {
+        fn name(&self) -> &str {
+            "drop-misc"
+        }
+
+        fn description(&self) -> &str {
+            "Remove @misc entries"
+        }
+
+        fn apply(&self, bibliography: &mut Bibliography) -> Report {
+            let before = bibliography.len();
+            bibliography.retain(|e| *e.entry_type() != BibType::Misc);
+            Report { changed: vec![], messages: vec![format!("removed {} entries", before - bibliography.len())] }
+        }
+    }
+
+    #[test]
+    fn test_registry() {
+        let mut registry = TransformRegistry::default();
+        registry.register(Box::new(DropMisc));
+        registry.register(month_transform(vec![Language::French]));
+        let names: Vec<&str> = registry.iter().map(|t| t.name()).collect();
+        assert_eq!(names, vec!["split-title", "normalize-booktitle", "minimize-minimal", "minimize-standard",
+            "minimize-ieee", "minimize-acm", "key-case-lower", "key-case-upper", "key-case-first", "thesis-biblatex",
+            "thesis-bibtex", "normalize-standards", "retype-misc", "encode-ascii", "drop-misc", "normalize-month"]);
+
+        let mut a = Entry::new(BibType::Article, "a");
+        a.set("month", "avril");
+        a.set("title", "Plain");
+        let mut bib = Bibliography::from_entries(vec![a, Entry::new(BibType::Misc, "m")]);
+        assert_eq!(registry.get("normalize-month").unwrap().apply(&mut bib).changed, vec!["a"]);
+        assert_eq!(bib.get("a").and_then(|e| e.get("month")), Some("4"));
+        assert!(registry.get("split-title").unwrap().apply(&mut bib).is_empty());
+        let report = registry.get("drop-misc").unwrap().apply(&mut bib);
+        assert_eq!(report.to_json().to_string(), r#"{"changed":[],"messages":["removed 1 entries"]}"#);
+        assert_eq!(bib.len(), 1);
+    }
+
+    #[test]
+    fn test_key_case() {
+        let mut proc = Entry::new(BibType::parse("proceedings"), "POPL84");
+        proc.set("title", "POPL");
+        let mut paper = Entry::new(BibType::InProceedings, "Knuth84");
+        paper.set("crossref", "popl84");
+        let entries = vec![proc, paper, Entry::new(BibType::Misc, "Cox2013"), Entry::new(BibType::Misc, "cox2013")];
+
+        let mut bib = Bibliography::from_entries(entries.clone());
+        let report = KeyCaseTransform::new(KeyCase::Lower).apply(&mut bib);
+        assert_eq!(report.changed, vec!["popl84", "knuth84"]);
+        assert_eq!(report.messages, vec!["Cox2013: not renamed to `cox2013`, which `cox2013` becomes too"]);
+        assert_eq!(bib.get("knuth84").and_then(|e| e.get("crossref")), Some("popl84"));
+
+        let mut bib = Bibliography::from_entries(entries.clone());
+        bib.pin("POPL84");
+        let report = KeyCaseTransform::new(KeyCase::Lower).apply(&mut bib);
+        assert_eq!(report.changed, vec!["knuth84"]);
+        assert_eq!(report.messages[0], "POPL84: pinned, so not renamed to `popl84`");
+        assert_eq!(bib.get("knuth84").and_then(|e| e.get("crossref")), Some("POPL84"));
+
+        let mut bib = Bibliography::from_entries(entries);
+        let report = KeyCaseTransform::new(KeyCase::First).apply(&mut bib);
+        assert_eq!(report.changed, vec!["Knuth84"]);
+        assert_eq!(bib.get("Knuth84").and_then(|e| e.get("crossref")), Some("POPL84"));
+        assert_eq!(report.messages.len(), 1);
+    }
+}
@@ -0,0 +1,326 @@
+/*!
+
+Checking entries' fields against the rules for their types.
+
+A `RuleSet` says which fields each entry type requires and which it may
+have, through a `TypeRegistry`, and which fields any entry may have
+(`GENERAL_FIELDS` to start with). Validating an entry gives a
+`ValidationIssue` for:
+
+- an entry type the rules do not know;
+- a required field that is missing or empty, e.g. the `journal` of an
+  `@article`;
+- a field the rules do not mention for the type, often a typo such as
+  `adress`;
+- a field with an empty value.
+
+`RuleSet::biber` is stricter, checking entries against biblatex's default
+data model as biber does (see `datamodel`): it also reports values of the
+wrong datatype, such as a `date` that is not ISO 8601, and `date` and
+`year` given together.
+
+`RuleSet::bibtex` has the classic BibTeX requirements, those of
+`TypeRegistry::default()`; `RuleSet::biblatex` those of the biblatex
+manual, which accept `date` for `year`, `journaltitle` for `journal` and
+`institution` for `school`. Projects with their own conventions start
+from either, or from `RuleSet::new` with their own registry, and `allow`
+more fields.
+
+`RuleSet::validate_bibliography` counts the fields an entry inherits
+(see `bibtex::crossrefs`) as present, so a paper does not need the year of
+the proceedings it cross-references.
+
+*/
+
+use std::fmt;
+use crate::bibtex::bibliography::Bibliography;
+use crate::bibtex::crossrefs::Inheritance;
+use crate::bibtex::data::Entry;
+use crate::bibtex::types::{TypeRegistry, TypeSchema};
+use crate::datamodel;
+
+/** Fields any entry may have, whatever its type. */
+pub const GENERAL_FIELDS: [&str; 34] = [
+    "abstract", "addendum", "annotation", "annote", "archiveprefix", "crossref", "date", "doi", "eprint",
+    "eprintclass", "eprinttype", "extra", "file", "ids", "isbn", "issn", "key", "keywords", "langid",
+    "language", "month", "note", "options", "pdf", "primaryclass", "pubstate", "related", "shorttitle",
+    "sortkey", "subtitle", "titleaddon", "url", "urldate", "xdata",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IssueKind {
+    UnknownType,
+    MissingField,
+    UnknownField,
+    EmptyValue,
+    /** A value biber cannot use for its field's datatype. */
+    InvalidValue,
+    /** Fields only one of which may be given, such as `date` and `year`. */
+    ExclusiveFields,
+}
+
+impl IssueKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            IssueKind::UnknownType => "unknown-type",
+            IssueKind::MissingField => "missing-field",
+            IssueKind::UnknownField => "unknown-field",
+            IssueKind::EmptyValue => "empty-value",
+            IssueKind::InvalidValue => "invalid-value",
+            IssueKind::ExclusiveFields => "exclusive-fields",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationIssue {
+    /** Citation key of the entry. */
+    pub key: String,
+    pub kind: IssueKind,
+    /** The field, or for a missing one the fields any of which would do. */
+    pub fields: Vec<String>,
+    /** For an invalid value, what is wrong with it, e.g. "is not a valid ISBN". */
+    pub reason: Option<String>,
+}
+
+impl ValidationIssue {
+    fn new(entry: &Entry, kind: IssueKind, fields: Vec<String>) -> ValidationIssue {
+        ValidationIssue { key: String::from(entry.key()), kind, fields, reason: None }
+    }
+
+    /** The issue without the citation key. */
+    pub fn message(&self) -> String {
+        let fields = self.fields.join(" or ");
+        match self.kind {
+            IssueKind::UnknownType => String::from("unknown entry type"),
+            IssueKind::MissingField => format!("missing required field {}", fields),
+            IssueKind::UnknownField => format!("unknown field {}", fields),
+            IssueKind::EmptyValue => format!("field {} is empty", fields),
+            IssueKind::InvalidValue => format!("field {} {}", fields, self.reason.as_deref().unwrap_or("is invalid")),
+            IssueKind::ExclusiveFields => format!("only one of {} may be given", fields),
+        }
+    }
+}
+
+impl fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.key, self.message())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RuleSet {
+    pub types: TypeRegistry,
+    /** Fields allowed on every type, besides those its schema mentions. */
+    pub general: Vec<String>,
+    /** How `validate_bibliography` finds inherited fields. */
+    pub inheritance: Inheritance,
+    /** Map and check entries as biber does with biblatex's data model. */
+    pub datamodel: bool,
+}
+
+impl RuleSet {
+    pub fn new(types: TypeRegistry) -> RuleSet {
+        RuleSet {
+            types,
+            general: GENERAL_FIELDS.iter().map(|f| String::from(*f)).collect(),
+            inheritance: Inheritance::BibTeX,
+            datamodel: false,
+        }
+    }
+
+    pub fn bibtex() -> RuleSet {
+        RuleSet::new(TypeRegistry::default())
+    }
+
+    /** The required fields of the biblatex manual, on top of the BibTeX types. */
+    pub fn biblatex() -> RuleSet {
+        let mut r = TypeRegistry::default();
+        let date = ["date", "year"];
+        let person = ["author", "editor"];
+        let contribution = |container: &[&str]| TypeSchema::new()
+            .require("author").require("title").require_any(container).require_any(&date)
+            .optional("bookauthor").optional("editor").optional("maintitle").optional("booksubtitle")
+            .optional("volume").optional("series").optional("number").optional("pages")
+            .optional("publisher").optional("location").optional("address").optional("organization")
+            .optional("eventtitle").optional("eventdate").optional("venue");
+        r.register("article", TypeSchema::new()
+            .require("author").require("title").require_any(&["journaltitle", "journal"]).require_any(&date)
+            .optional("journalsubtitle").optional("volume").optional("number").optional("issue")
+            .optional("pages").optional("eid").optional("series").optional("editor"));
+        for name in ["book", "mvbook"] {
+            r.register(name, TypeSchema::new()
+                .require("author").require("title").require_any(&date)
+                .optional("editor").optional("maintitle").optional("volume").optional("volumes")
+                .optional("series").optional("number").optional("edition").optional("publisher")
+                .optional("location").optional("address").optional("pagetotal"));
+        }
+        for name in ["collection", "mvcollection", "periodical"] {
+            r.register(name, TypeSchema::new()
+                .require("editor").require("title").require_any(&date)
+                .optional("maintitle").optional("volume").optional("volumes").optional("series")
+                .optional("number").optional("issue").optional("edition").optional("publisher")
+                .optional("location").optional("address"));
+        }
+        for name in ["proceedings", "mvproceedings"] {
+            r.register(name, TypeSchema::new()
+                .require("title").require_any(&date)
+                .optional("editor").optional("maintitle").optional("eventtitle").optional("eventdate")
+                .optional("venue").optional("volume").optional("volumes").optional("series")
+                .optional("number").optional("organization").optional("publisher").optional("location")
+                .optional("address"));
+        }
+        r.register("inbook", contribution(&["booktitle"]));
+        r.register("incollection", contribution(&["booktitle"]));
+        r.register("inproceedings", contribution(&["booktitle"]));
+        for name in ["manual", "misc", "unpublished"] {
+            r.register(name, TypeSchema::new()
+                .require_any(&person).require("title").require_any(&date)
+                .optional("howpublished").optional("type").optional("version").optional("organization")
+                .optional("publisher").optional("location").optional("address"));
+        }
+        r.register("online", TypeSchema::new()
+            .require_any(&person).require("title").require_any(&date).require_any(&["doi", "eprint", "url"])
+            .optional("version").optional("organization"));
+        for name in ["techreport", "phdthesis", "mastersthesis"] {
+            r.register(name, TypeSchema::new()
+                .require("author").require("title").require_any(&["institution", "school"]).require_any(&date)
+                .optional("type").optional("number").optional("version").optional("location")
+                .optional("address"));
+        }
+        r.register("thesis", TypeSchema::new()
+            .require("author").require("title").require("type").require_any(&["institution", "school"])
+            .require_any(&date).optional("location").optional("address"));
+        RuleSet { inheritance: Inheritance::Biblatex, ..RuleSet::new(r) }
+    }
+
+    /**
+    biblatex's default data model, checked as `biber --validate-datamodel`
+    does, after renaming BibTeX types and fields as biber's source map does.
+    */
+    pub fn biber() -> RuleSet {
+        RuleSet {
+            types: datamodel::registry(),
+            general: datamodel::GLOBAL_FIELDS.iter().map(|f| String::from(*f)).collect(),
+            inheritance: Inheritance::Biblatex,
+            datamodel: true,
+        }
+    }
+
+    /** Allow `field` on every type. */
+    pub fn allow(mut self, field: &str) -> RuleSet {
+        self.general.push(field.to_lowercase());
+        self
+    }
+
+    /** The issues with `entry` on its own. */
+    pub fn validate(&self, entry: &Entry) -> Vec<ValidationIssue> {
+        self.check(entry, |field| has_value(entry.get(field)))
+    }
+
+    /** The issues with every entry of `bib`, in order, counting inherited fields as present. */
+    pub fn validate_bibliography(&self, bib: &Bibliography) -> Vec<ValidationIssue> {
+        bib.entries().iter()
+            .flat_map(|entry| self.check(entry, |field| has_value(bib.inherited(entry, field, self.inheritance))))
+            .collect()
+    }
+
+    fn check<F: Fn(&str) -> bool>(&self, entry: &Entry, present: F) -> Vec<ValidationIssue> {
+        let mut out = Vec::new();
+        let mapped = self.datamodel.then(|| datamodel::biber_entry(entry));
+        let entry = mapped.as_ref().unwrap_or(entry);
+        // a field can be inherited under its BibTeX name, e.g. `address` for `location`
+        let present = |field: &str| has_value(entry.get(field)) || present(field)
+            || (self.datamodel && datamodel::bibtex_alias(field).is_some_and(&present));
+        match self.types.schema(entry.entry_type()) {
+            None => out.push(ValidationIssue::new(entry, IssueKind::UnknownType, Vec::new())),
+            Some(schema) => {
+                for req in schema.required() {
+                    if !req.is_satisfied_by(present) {
+                        out.push(ValidationIssue::new(entry, IssueKind::MissingField, req.fields().to_vec()));
+                    }
+                }
+                for name in entry.field_names() {
+                    if !schema.mentions(name) && !self.general.iter().any(|f| f == name) {
+                        out.push(ValidationIssue::new(entry, IssueKind::UnknownField, vec![String::from(name)]));
+                    }
+                }
+            }
+        }
+        if self.datamodel && datamodel::DATED_TYPES.contains(&entry.entry_type().name())
+            && has_value(entry.get("date")) && has_value(entry.get("year")) {
+            out.push(ValidationIssue::new(entry, IssueKind::ExclusiveFields, vec![String::from("date"), String::from("year")]));
+        }
+        for (name, value) in entry.fields() {
+            if value.trim().is_empty() {
+                out.push(ValidationIssue::new(entry, IssueKind::EmptyValue, vec![String::from(name)]));
+            } else if let Some(reason) = self.datamodel.then(|| datamodel::check_value(name, value)).flatten() {
+                out.push(ValidationIssue { reason: Some(reason), ..ValidationIssue::new(entry, IssueKind::InvalidValue, vec![String::from(name)]) });
+            }
+        }
+        out
+    }
+}
+
+fn has_value(value: Option<&str>) -> bool {
+    value.map(|v| !v.trim().is_empty()).unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::bibtex::data::BibType;
+    use crate::bibtex::parser::parse_bibliography;
+
+    #[test]
+    fn test_validate() {
+        let bib = parse_bibliography("@article{knuth84, author = {Knuth}, title = {Literate Programming},\n\
+            journaltitle = {The Computer Journal}, date = {1984-05}, adress = {Oxford}, note = {}}\n\
+            @inproceedings{paper, author = {Cox}, title = {Primes}, crossref = {procs}}\n\
+            @proceedings{procs, title = {Proceedings of X}, year = 2001}\n@talk{t, title = {T}}").unwrap();
+        let issues = |rules: &RuleSet| rules.validate_bibliography(&bib).iter().map(|i| i.to_string()).collect::<Vec<String>>();
+        assert_eq!(issues(&RuleSet::bibtex()), vec![
+            "knuth84: missing required field journal",
+            "knuth84: missing required field year",
+            "knuth84: unknown field journaltitle",
+            "knuth84: unknown field adress",
+            "knuth84: field note is empty",
+            "paper: missing required field booktitle",
+            "t: unknown entry type",
+        ]);
+        assert_eq!(issues(&RuleSet::biblatex()), vec![
+            "knuth84: unknown field adress",
+            "knuth84: field note is empty",
+            "t: unknown entry type",
+        ]);
+        let rules = RuleSet::biblatex().allow("Adress");
+        let knuth = rules.validate(bib.get("knuth84").unwrap());
+        assert_eq!(knuth.iter().map(|i| i.kind).collect::<Vec<IssueKind>>(), vec![IssueKind::EmptyValue]);
+        assert_eq!(rules.validate(bib.get("paper").unwrap())[0].fields, vec!["booktitle"]);
+    }
+
+    #[test]
+    fn test_biber() {
+        let bib = parse_bibliography("@article{knuth84, author = {Knuth}, title = {Literate Programming},\n\
+            journal = {The Computer Journal}, year = 1984, month = may, publisher = {OUP}, issn = {0010-4620}}\n\
+            @phdthesis{cox, author = {Cox, David, Jr., III}, title = {Primes}, school = {MIT}, date = {May 1993}}\n\
+            @inproceedings{paper, author = {Cox}, title = {Primes}, crossref = {procs}}\n\
+            @proceedings{procs, title = {Proceedings of X}, address = {Oxford}, date = {2001-05}, year = 2001}").unwrap();
+        let issues: Vec<String> = RuleSet::biber().validate_bibliography(&bib).iter().map(|i| i.to_string()).collect();
+        assert_eq!(issues, vec![
+            "knuth84: unknown field publisher",
+            "cox: field author has a name with too many commas: `Cox, David, Jr., III`",
+            "cox: field date is not an ISO 8601 date such as 2001-05-17, 2001-05 or 2001/2003",
+            "procs: only one of date or year may be given",
+        ]);
+        let thesis = RuleSet::biber().validate(&Entry::new(BibType::PhdThesis, "t"));
+        assert_eq!(thesis.iter().map(|i| i.message()).collect::<Vec<String>>(), vec![
+            "missing required field author",
+            "missing required field title",
+            "missing required field institution",
+            "missing required field date or year",
+        ]);
+        assert_eq!(thesis[0].kind, IssueKind::MissingField);
+    }
+}